@@ -12,7 +12,7 @@ async fn main() -> Result<(), anyhow::Error> {
 	let gen_cfg = GenerationConfig {
 		thinking_config: Some(ThinkingConfig {
 			include_thoughts: Some(true),
-			thinking_budget: 2048,
+			thinking_budget: Some(2048),
 		}),
 		..Default::default()
 	};
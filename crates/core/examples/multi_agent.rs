@@ -89,7 +89,6 @@ async fn main() -> Result<(), anyhow::Error> {
 	// Spin up a CLI chatbot using the multi-agent system
 	let chatbot = ChatBotBuilder::new()
 		.agent(multi_agent_system)
-		.max_turns(1)
 		.build();
 
 	chatbot.run().await?;
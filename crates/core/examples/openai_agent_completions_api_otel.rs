@@ -45,6 +45,9 @@ async fn main() -> Result<(), anyhow::Error> {
 		.with(otel_layer)
 		.init();
 
+	// Message bodies are only recorded on spans when opted in
+	clankers::telemetry::set_record_messages(true);
+
 	// Create OpenAI client
 	let agent = providers::openai::Client::from_env()
 		.completion_model(openai::completion::types::GPT_4O)
@@ -131,6 +131,9 @@ async fn main() -> Result<(), anyhow::Error> {
 		.with(otel_layer)
 		.init();
 
+	// Message bodies are only recorded on spans when opted in
+	clankers::telemetry::set_record_messages(true);
+
 	// Create agent with a single context prompt and two tools
 	let calculator_agent = providers::openai::Client::from_env()
 		.agent(providers::openai::completion::types::GPT_4O)
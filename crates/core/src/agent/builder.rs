@@ -3,8 +3,11 @@ use std::sync::Arc;
 
 use tokio::sync::RwLock;
 
-use super::Agent;
-use crate::completion::{CompletionModel, Document};
+use super::context::{ContextFailurePolicy, ContextProvider, ContextProviderDyn};
+use super::moderation::{ModerationPolicy, Moderator, ModeratorDyn};
+use super::tool_selection::{ToolSelector, ToolSelectorDyn};
+use super::{Agent, UnknownToolPolicy};
+use crate::completion::{CompletionModel, Document, PromptTemplate};
 use crate::message::ToolChoice;
 use crate::tool::server::{ToolServer, ToolServerHandle};
 use crate::tool::{Tool, ToolDyn, ToolSet};
@@ -51,6 +54,20 @@ where
 	max_tokens: Option<u64>,
 	/// List of vector store, with the sample number
 	dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn + Send + Sync>)>,
+	/// Providers of context documents, called concurrently on each request
+	context_providers: Vec<Arc<dyn ContextProviderDyn>>,
+	/// What to do when a context provider fails
+	context_failure_policy: ContextFailurePolicy,
+	/// Moderator checking the prompt before it is sent
+	moderator: Option<Arc<dyn ModeratorDyn>>,
+	/// What to do when the prompt is flagged by the moderator
+	moderation_policy: ModerationPolicy,
+	/// Selector of the tools advertised for each prompt
+	tool_selector: Option<Arc<dyn ToolSelectorDyn>>,
+	/// Whether calls to tools which weren't advertised fail
+	strict_tool_selection: bool,
+	/// What to do when the model calls a tool the agent doesn't have
+	unknown_tool_policy: UnknownToolPolicy,
 	/// Temperature of the model
 	temperature: Option<f64>,
 	/// Tool server handle
@@ -59,6 +76,14 @@ where
 	tool_choice: Option<ToolChoice>,
 	/// Default maximum depth for multi-turn agent calls
 	default_max_turns: Option<usize>,
+	/// Default number of tool calls executed concurrently within a turn
+	tool_concurrency: Option<usize>,
+	/// System prompt template, rendered at request-build time
+	preamble_template: Option<PromptTemplate>,
+	/// Context document templates, rendered at request-build time
+	context_templates: Vec<PromptTemplate>,
+	/// Initial values for the template variables
+	template_vars: HashMap<String, String>,
 }
 
 impl<M> AgentBuilder<M>
@@ -76,9 +101,20 @@ where
 			max_tokens: None,
 			additional_params: None,
 			dynamic_context: vec![],
+			context_providers: vec![],
+			context_failure_policy: ContextFailurePolicy::default(),
+			moderator: None,
+			moderation_policy: ModerationPolicy::default(),
+			tool_selector: None,
+			strict_tool_selection: false,
+			unknown_tool_policy: UnknownToolPolicy::default(),
 			tool_server_handle: None,
 			tool_choice: None,
 			default_max_turns: None,
+			tool_concurrency: None,
+			preamble_template: None,
+			context_templates: vec![],
+			template_vars: HashMap::new(),
 		}
 	}
 
@@ -126,6 +162,27 @@ where
 		self
 	}
 
+	/// Set a system prompt template. It is rendered with the agent's template variables
+	/// every time a request is built and takes precedence over [Self::preamble].
+	pub fn preamble_template(mut self, template: impl Into<PromptTemplate>) -> Self {
+		self.preamble_template = Some(template.into());
+		self
+	}
+
+	/// Add a context document template, rendered with the agent's template variables
+	/// every time a request is built
+	pub fn context_template(mut self, template: impl Into<PromptTemplate>) -> Self {
+		self.context_templates.push(template.into());
+		self
+	}
+
+	/// Set the initial value of a template variable.
+	/// Use [Agent::set_template_var] to change it after the agent is built.
+	pub fn template_var(mut self, name: &str, value: impl Into<String>) -> Self {
+		self.template_vars.insert(name.to_string(), value.into());
+		self
+	}
+
 	/// Add a static tool to the agent
 	pub fn tool(self, tool: impl Tool + 'static) -> AgentBuilderSimple<M> {
 		let toolname = tool.name();
@@ -142,11 +199,22 @@ where
 			additional_params: self.additional_params,
 			max_tokens: self.max_tokens,
 			dynamic_context: vec![],
+			context_providers: self.context_providers,
+			context_failure_policy: self.context_failure_policy,
+			moderator: self.moderator,
+			moderation_policy: self.moderation_policy,
+			tool_selector: self.tool_selector,
+			strict_tool_selection: self.strict_tool_selection,
+			unknown_tool_policy: self.unknown_tool_policy,
 			dynamic_tools: vec![],
 			temperature: self.temperature,
 			tools,
 			tool_choice: self.tool_choice,
 			default_max_turns: self.default_max_turns,
+			tool_concurrency: self.tool_concurrency,
+			preamble_template: self.preamble_template,
+			context_templates: self.context_templates,
+			template_vars: self.template_vars,
 		}
 	}
 
@@ -166,11 +234,22 @@ where
 			additional_params: self.additional_params,
 			max_tokens: self.max_tokens,
 			dynamic_context: vec![],
+			context_providers: self.context_providers,
+			context_failure_policy: self.context_failure_policy,
+			moderator: self.moderator,
+			moderation_policy: self.moderation_policy,
+			tool_selector: self.tool_selector,
+			strict_tool_selection: self.strict_tool_selection,
+			unknown_tool_policy: self.unknown_tool_policy,
 			dynamic_tools: vec![],
 			temperature: self.temperature,
 			tools,
 			tool_choice: self.tool_choice,
 			default_max_turns: self.default_max_turns,
+			tool_concurrency: self.tool_concurrency,
+			preamble_template: self.preamble_template,
+			context_templates: self.context_templates,
+			template_vars: self.template_vars,
 		}
 	}
 
@@ -191,17 +270,76 @@ where
 		self
 	}
 
+	/// Add a context provider to the agent. On each prompt, the documents it retrieves are
+	/// inserted in the request, and returned in [PromptResponse::context_documents].
+	///
+	/// [PromptResponse::context_documents]: crate::agent::PromptResponse::context_documents
+	pub fn context_provider(mut self, provider: impl ContextProvider + 'static) -> Self {
+		self.context_providers.push(Arc::new(provider));
+		self
+	}
+
+	/// Set what to do when a context provider fails (skipping its documents by default)
+	pub fn context_failure_policy(mut self, policy: ContextFailurePolicy) -> Self {
+		self.context_failure_policy = policy;
+		self
+	}
+
+	/// Check every prompt with `moderator` before it is sent to the model, blocking or
+	/// annotating flagged prompts depending on `policy`. Tool results aren't moderated.
+	pub fn with_input_moderation(
+		mut self,
+		moderator: impl Moderator + 'static,
+		policy: ModerationPolicy,
+	) -> Self {
+		self.moderator = Some(Arc::new(moderator));
+		self.moderation_policy = policy;
+		self
+	}
+
+	/// Advertise the tools picked by `selector` for each prompt rather than all of them, e.g.
+	/// with an [EmbeddingToolSelector](super::EmbeddingToolSelector).
+	/// The model can still call the other tools, unless [Self::strict_tool_selection] is set.
+	pub fn tool_selector(mut self, selector: impl ToolSelector + 'static) -> Self {
+		self.tool_selector = Some(Arc::new(selector));
+		self
+	}
+
+	/// Set whether calls to tools which weren't advertised in the request fail, as if the tool
+	/// didn't exist (false by default)
+	pub fn strict_tool_selection(mut self, strict: bool) -> Self {
+		self.strict_tool_selection = strict;
+		self
+	}
+
+	/// Set what to do when the model calls a tool the agent doesn't have
+	/// ([UnknownToolPolicy::ReturnErrorResult] by default)
+	pub fn unknown_tool_policy(mut self, policy: UnknownToolPolicy) -> Self {
+		self.unknown_tool_policy = policy;
+		self
+	}
+
 	pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
 		self.tool_choice = Some(tool_choice);
 		self
 	}
 
-	/// Set the default maximum depth that an agent will use for multi-turn.
+	/// Set the default maximum depth that an agent will use for multi-turn, [DEFAULT_MAX_TURNS]
+	/// otherwise.
+	///
+	/// [DEFAULT_MAX_TURNS]: crate::agent::DEFAULT_MAX_TURNS
 	pub fn default_max_turns(mut self, default_max_turns: usize) -> Self {
 		self.default_max_turns = Some(default_max_turns);
 		self
 	}
 
+	/// Set how many tool calls from a single model turn are executed concurrently (1 by default).
+	/// Tool results are always returned to the model in the order the calls were made.
+	pub fn tool_concurrency(mut self, concurrency: usize) -> Self {
+		self.tool_concurrency = Some(concurrency);
+		self
+	}
+
 	/// Add some dynamic tools to the agent. On each prompt, `sample` tools from the
 	/// dynamic toolset will be inserted in the request.
 	pub fn dynamic_tools(
@@ -223,11 +361,22 @@ where
 			additional_params: self.additional_params,
 			max_tokens: self.max_tokens,
 			dynamic_context: vec![],
+			context_providers: self.context_providers,
+			context_failure_policy: self.context_failure_policy,
+			moderator: self.moderator,
+			moderation_policy: self.moderation_policy,
+			tool_selector: self.tool_selector,
+			strict_tool_selection: self.strict_tool_selection,
+			unknown_tool_policy: self.unknown_tool_policy,
 			dynamic_tools,
 			temperature: self.temperature,
 			tools: toolset,
 			tool_choice: self.tool_choice,
 			default_max_turns: self.default_max_turns,
+			tool_concurrency: self.tool_concurrency,
+			preamble_template: self.preamble_template,
+			context_templates: self.context_templates,
+			template_vars: self.template_vars,
 		}
 	}
 
@@ -268,8 +417,19 @@ where
 			additional_params: self.additional_params,
 			tool_choice: self.tool_choice,
 			dynamic_context: Arc::new(RwLock::new(self.dynamic_context)),
+			context_providers: self.context_providers,
+			context_failure_policy: self.context_failure_policy,
+			moderator: self.moderator,
+			moderation_policy: self.moderation_policy,
+			tool_selector: self.tool_selector,
+			strict_tool_selection: self.strict_tool_selection,
+			unknown_tool_policy: self.unknown_tool_policy,
 			tool_server_handle,
 			default_max_turns: self.default_max_turns,
+			tool_concurrency: self.tool_concurrency,
+			preamble_template: self.preamble_template,
+			context_templates: self.context_templates,
+			template_vars: Arc::new(RwLock::new(self.template_vars)),
 		}
 	}
 }
@@ -317,6 +477,20 @@ where
 	max_tokens: Option<u64>,
 	/// List of vector store, with the sample number
 	dynamic_context: Vec<(usize, Box<dyn VectorStoreIndexDyn + Send + Sync>)>,
+	/// Providers of context documents, called concurrently on each request
+	context_providers: Vec<Arc<dyn ContextProviderDyn>>,
+	/// What to do when a context provider fails
+	context_failure_policy: ContextFailurePolicy,
+	/// Moderator checking the prompt before it is sent
+	moderator: Option<Arc<dyn ModeratorDyn>>,
+	/// What to do when the prompt is flagged by the moderator
+	moderation_policy: ModerationPolicy,
+	/// Selector of the tools advertised for each prompt
+	tool_selector: Option<Arc<dyn ToolSelectorDyn>>,
+	/// Whether calls to tools which weren't advertised fail
+	strict_tool_selection: bool,
+	/// What to do when the model calls a tool the agent doesn't have
+	unknown_tool_policy: UnknownToolPolicy,
 	/// Dynamic tools
 	dynamic_tools: Vec<(usize, Box<dyn VectorStoreIndexDyn + Send + Sync>)>,
 	/// Temperature of the model
@@ -327,6 +501,14 @@ where
 	tool_choice: Option<ToolChoice>,
 	/// Default maximum depth for multi-turn agent calls
 	default_max_turns: Option<usize>,
+	/// Default number of tool calls executed concurrently within a turn
+	tool_concurrency: Option<usize>,
+	/// System prompt template, rendered at request-build time
+	preamble_template: Option<PromptTemplate>,
+	/// Context document templates, rendered at request-build time
+	context_templates: Vec<PromptTemplate>,
+	/// Initial values for the template variables
+	template_vars: HashMap<String, String>,
 }
 
 impl<M> AgentBuilderSimple<M>
@@ -345,10 +527,21 @@ where
 			max_tokens: None,
 			additional_params: None,
 			dynamic_context: vec![],
+			context_providers: vec![],
+			context_failure_policy: ContextFailurePolicy::default(),
+			moderator: None,
+			moderation_policy: ModerationPolicy::default(),
+			tool_selector: None,
+			strict_tool_selection: false,
+			unknown_tool_policy: UnknownToolPolicy::default(),
 			dynamic_tools: vec![],
 			tools: ToolSet::default(),
 			tool_choice: None,
 			default_max_turns: None,
+			tool_concurrency: None,
+			preamble_template: None,
+			context_templates: vec![],
+			template_vars: HashMap::new(),
 		}
 	}
 
@@ -396,6 +589,27 @@ where
 		self
 	}
 
+	/// Set a system prompt template. It is rendered with the agent's template variables
+	/// every time a request is built and takes precedence over [Self::preamble].
+	pub fn preamble_template(mut self, template: impl Into<PromptTemplate>) -> Self {
+		self.preamble_template = Some(template.into());
+		self
+	}
+
+	/// Add a context document template, rendered with the agent's template variables
+	/// every time a request is built
+	pub fn context_template(mut self, template: impl Into<PromptTemplate>) -> Self {
+		self.context_templates.push(template.into());
+		self
+	}
+
+	/// Set the initial value of a template variable.
+	/// Use [Agent::set_template_var] to change it after the agent is built.
+	pub fn template_var(mut self, name: &str, value: impl Into<String>) -> Self {
+		self.template_vars.insert(name.to_string(), value.into());
+		self
+	}
+
 	/// Add a static tool to the agent
 	pub fn tool(mut self, tool: impl Tool + 'static) -> Self {
 		let toolname = tool.name();
@@ -424,17 +638,76 @@ where
 		self
 	}
 
+	/// Add a context provider to the agent. On each prompt, the documents it retrieves are
+	/// inserted in the request, and returned in [PromptResponse::context_documents].
+	///
+	/// [PromptResponse::context_documents]: crate::agent::PromptResponse::context_documents
+	pub fn context_provider(mut self, provider: impl ContextProvider + 'static) -> Self {
+		self.context_providers.push(Arc::new(provider));
+		self
+	}
+
+	/// Set what to do when a context provider fails (skipping its documents by default)
+	pub fn context_failure_policy(mut self, policy: ContextFailurePolicy) -> Self {
+		self.context_failure_policy = policy;
+		self
+	}
+
+	/// Check every prompt with `moderator` before it is sent to the model, blocking or
+	/// annotating flagged prompts depending on `policy`. Tool results aren't moderated.
+	pub fn with_input_moderation(
+		mut self,
+		moderator: impl Moderator + 'static,
+		policy: ModerationPolicy,
+	) -> Self {
+		self.moderator = Some(Arc::new(moderator));
+		self.moderation_policy = policy;
+		self
+	}
+
+	/// Advertise the tools picked by `selector` for each prompt rather than all of them, e.g.
+	/// with an [EmbeddingToolSelector](super::EmbeddingToolSelector).
+	/// The model can still call the other tools, unless [Self::strict_tool_selection] is set.
+	pub fn tool_selector(mut self, selector: impl ToolSelector + 'static) -> Self {
+		self.tool_selector = Some(Arc::new(selector));
+		self
+	}
+
+	/// Set whether calls to tools which weren't advertised in the request fail, as if the tool
+	/// didn't exist (false by default)
+	pub fn strict_tool_selection(mut self, strict: bool) -> Self {
+		self.strict_tool_selection = strict;
+		self
+	}
+
+	/// Set what to do when the model calls a tool the agent doesn't have
+	/// ([UnknownToolPolicy::ReturnErrorResult] by default)
+	pub fn unknown_tool_policy(mut self, policy: UnknownToolPolicy) -> Self {
+		self.unknown_tool_policy = policy;
+		self
+	}
+
 	pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
 		self.tool_choice = Some(tool_choice);
 		self
 	}
 
-	/// Set the default maximum depth that an agent will use for multi-turn.
+	/// Set the default maximum depth that an agent will use for multi-turn, [DEFAULT_MAX_TURNS]
+	/// otherwise.
+	///
+	/// [DEFAULT_MAX_TURNS]: crate::agent::DEFAULT_MAX_TURNS
 	pub fn default_max_turns(mut self, default_max_turns: usize) -> Self {
 		self.default_max_turns = Some(default_max_turns);
 		self
 	}
 
+	/// Set how many tool calls from a single model turn are executed concurrently (1 by default).
+	/// Tool results are always returned to the model in the order the calls were made.
+	pub fn tool_concurrency(mut self, concurrency: usize) -> Self {
+		self.tool_concurrency = Some(concurrency);
+		self
+	}
+
 	/// Add some dynamic tools to the agent. On each prompt, `sample` tools from the
 	/// dynamic toolset will be inserted in the request.
 	pub fn dynamic_tools(
@@ -485,8 +758,19 @@ where
 			additional_params: self.additional_params,
 			tool_choice: self.tool_choice,
 			dynamic_context: Arc::new(RwLock::new(self.dynamic_context)),
+			context_providers: self.context_providers,
+			context_failure_policy: self.context_failure_policy,
+			moderator: self.moderator,
+			moderation_policy: self.moderation_policy,
+			tool_selector: self.tool_selector,
+			strict_tool_selection: self.strict_tool_selection,
+			unknown_tool_policy: self.unknown_tool_policy,
 			tool_server_handle,
 			default_max_turns: self.default_max_turns,
+			tool_concurrency: self.tool_concurrency,
+			preamble_template: self.preamble_template,
+			context_templates: self.context_templates,
+			template_vars: Arc::new(RwLock::new(self.template_vars)),
 		}
 	}
 }
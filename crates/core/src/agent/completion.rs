@@ -1,24 +1,45 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use futures::{StreamExt, TryStreamExt, stream};
 use tokio::sync::RwLock;
 
-use super::prompt_request::{self, PromptRequest};
+use super::context::{self, ContextFailurePolicy, ContextProviderDyn};
+use super::moderation::{ModerationPolicy, ModeratorDyn};
+use super::prompt_request::{self, PromptOptions, PromptRequest};
+use super::tool_selection::{ToolSelectorDyn, select_tools};
+use crate::OneOrMany;
 use crate::agent::prompt_request::streaming::StreamingPromptRequest;
 use crate::completion::{
-	Chat, Completion, CompletionError, CompletionModel, CompletionRequestBuilder, Document,
-	GetTokenUsage, Message, Prompt, PromptError,
+	CachedModel, Chat, Completion, CompletionCache, CompletionError, CompletionModel,
+	CompletionRequestBuilder, Document, GetTokenUsage, Message, MissingVar, Prompt, PromptError,
+	PromptTemplate, TokenCounter,
 };
-use crate::message::ToolChoice;
+use crate::message::{ToolChoice, ToolResultContent};
 use crate::streaming::{StreamingChat, StreamingCompletion, StreamingPrompt};
-use crate::tool::server::ToolServerHandle;
+use crate::tool::ToolSetError;
+use crate::tool::server::{ToolServerError, ToolServerHandle};
 use crate::vector_store::VectorStoreError;
 use crate::vector_store::request::VectorSearchRequest;
 use crate::wasm_compat::WasmCompatSend;
 
 const UNKNOWN_AGENT_NAME: &str = "Unnamed Agent";
 
+/// What to do when the model calls a tool the agent doesn't have, e.g. a hallucinated tool
+/// name, or a tool which wasn't advertised with [strict tool
+/// selection](super::AgentBuilder::strict_tool_selection).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownToolPolicy {
+	/// Fail the prompt with a [ToolSetError::ToolNotFoundError]
+	Error,
+	/// Send back a tool result naming the tools advertised in the request, so the model can
+	/// retry with one of them
+	#[default]
+	ReturnErrorResult,
+	/// Send back an empty tool result and carry on
+	Ignore,
+}
+
 pub type DynamicContextStore = Arc<
 	RwLock<
 		Vec<(
@@ -28,6 +49,8 @@ pub type DynamicContextStore = Arc<
 	>,
 >;
 
+pub type TemplateVars = Arc<RwLock<HashMap<String, String>>>;
+
 /// Struct representing an LLM agent. An agent is an LLM model combined with a preamble
 /// (i.e.: system prompt) and a static set of context documents and tools.
 /// All context documents and tools are always provided to the agent when prompted.
@@ -73,10 +96,33 @@ where
 	pub tool_server_handle: ToolServerHandle,
 	/// List of vector store, with the sample number
 	pub dynamic_context: DynamicContextStore,
+	/// Providers of context documents, called concurrently on each request
+	pub context_providers: Vec<Arc<dyn ContextProviderDyn>>,
+	/// What to do when a context provider fails
+	pub context_failure_policy: ContextFailurePolicy,
+	/// Moderator checking the prompt before it is sent to the model
+	pub moderator: Option<Arc<dyn ModeratorDyn>>,
+	/// What to do when the prompt is flagged by the moderator
+	pub moderation_policy: ModerationPolicy,
+	/// Selector of the tools advertised for each prompt, all tools are advertised if not set
+	pub tool_selector: Option<Arc<dyn ToolSelectorDyn>>,
+	/// Whether calls to tools which weren't advertised in the request fail
+	pub strict_tool_selection: bool,
+	/// What to do when the model calls a tool the agent doesn't have
+	pub unknown_tool_policy: UnknownToolPolicy,
 	/// Whether or not the underlying LLM should be forced to use a tool before providing a response.
 	pub tool_choice: Option<ToolChoice>,
 	/// Default maximum depth for recursive agent calls
 	pub default_max_turns: Option<usize>,
+	/// Default number of tool calls executed concurrently within a turn
+	pub tool_concurrency: Option<usize>,
+	/// System prompt template, rendered with `template_vars` every time a request is built.
+	/// Takes precedence over `preamble` when set.
+	pub preamble_template: Option<PromptTemplate>,
+	/// Context document templates, rendered with `template_vars` every time a request is built
+	pub context_templates: Vec<PromptTemplate>,
+	/// Variables used to render the preamble and context templates
+	pub template_vars: TemplateVars,
 }
 
 impl<M> Agent<M>
@@ -87,46 +133,175 @@ where
 	pub(crate) fn name(&self) -> &str {
 		self.name.as_deref().unwrap_or(UNKNOWN_AGENT_NAME)
 	}
-}
 
-impl<M> Completion<M> for Agent<M>
-where
-	M: CompletionModel,
-{
-	async fn completion(
+	/// Returns the token counter of the agent's model, for estimating prompt sizes.
+	pub fn token_counter(&self) -> Arc<dyn TokenCounter> {
+		self.model.token_counter()
+	}
+
+	/// Prompts the agent with `history` as the previous messages. The prompt and every message
+	/// of the conversation, including tool calls and results, are appended to `history`.
+	///
+	/// Shorthand for `agent.prompt(prompt).with_history(history)`.
+	pub fn prompt_with_history<'a>(
+		&'a self,
+		prompt: impl Into<Message>,
+		history: &'a mut Vec<Message>,
+	) -> PromptRequest<'a, prompt_request::Standard, M, ()> {
+		PromptRequest::new(self, prompt).with_history(history)
+	}
+
+	/// Prompts the agent with `options` overriding its model parameters for this prompt only,
+	/// e.g. to lower the temperature of one request without building another agent.
+	///
+	/// Shorthand for `agent.prompt(prompt).with_options(options)`.
+	pub fn prompt_with_options(
 		&self,
-		prompt: impl Into<Message> + WasmCompatSend,
+		prompt: impl Into<Message>,
+		options: PromptOptions,
+	) -> PromptRequest<'_, prompt_request::Standard, M, ()> {
+		PromptRequest::new(self, prompt).with_options(options)
+	}
+
+	/// Streaming version of [Agent::prompt_with_options].
+	pub fn stream_prompt_with_options(
+		&self,
+		prompt: impl Into<Message>,
+		options: PromptOptions,
+	) -> StreamingPromptRequest<M, ()>
+	where
+		M: 'static,
+		M::StreamingResponse: GetTokenUsage,
+	{
+		StreamingPromptRequest::new(Arc::new(self.clone()), prompt).with_options(options)
+	}
+
+	/// Serves the agent's repeated completion requests from `cache`, see
+	/// [CompletionModel::with_cache]. Streamed prompts bypass the cache.
+	pub fn with_cache(
+		self,
+		cache: impl CompletionCache + 'static,
+		namespace: impl Into<String>,
+	) -> Agent<CachedModel<M>> {
+		Agent {
+			name: self.name,
+			description: self.description,
+			model: Arc::new(Arc::unwrap_or_clone(self.model).with_cache(cache, namespace)),
+			preamble: self.preamble,
+			static_context: self.static_context,
+			temperature: self.temperature,
+			max_tokens: self.max_tokens,
+			additional_params: self.additional_params,
+			tool_server_handle: self.tool_server_handle,
+			dynamic_context: self.dynamic_context,
+			context_providers: self.context_providers,
+			context_failure_policy: self.context_failure_policy,
+			moderator: self.moderator,
+			moderation_policy: self.moderation_policy,
+			tool_selector: self.tool_selector,
+			strict_tool_selection: self.strict_tool_selection,
+			unknown_tool_policy: self.unknown_tool_policy,
+			tool_choice: self.tool_choice,
+			default_max_turns: self.default_max_turns,
+			tool_concurrency: self.tool_concurrency,
+			preamble_template: self.preamble_template,
+			context_templates: self.context_templates,
+			template_vars: self.template_vars,
+		}
+	}
+
+	/// Set (or overwrite) a template variable. The new value is used by every
+	/// subsequent prompt, including prompts on clones of this agent.
+	pub async fn set_template_var(&self, name: &str, value: impl Into<String>) {
+		self.template_vars
+			.write()
+			.await
+			.insert(name.to_string(), value.into());
+	}
+
+	/// Renders the preamble and context templates with the current template variables.
+	async fn render_templates(&self) -> Result<(Option<String>, Vec<Document>), MissingVar> {
+		if self.preamble_template.is_none() && self.context_templates.is_empty() {
+			return Ok((self.preamble.clone(), vec![]));
+		}
+
+		let vars = self.template_vars.read().await;
+		let preamble = match &self.preamble_template {
+			Some(template) => Some(template.render(&vars)?),
+			None => self.preamble.clone(),
+		};
+		let documents = self
+			.context_templates
+			.iter()
+			.enumerate()
+			.map(|(i, template)| {
+				Ok(Document {
+					id: format!("template_doc_{i}"),
+					text: template.render(&vars)?,
+					additional_props: HashMap::new(),
+				})
+			})
+			.collect::<Result<Vec<_>, MissingVar>>()?;
+
+		Ok((preamble, documents))
+	}
+
+	/// Builds the completion request like [Completion::completion], with `options` overriding
+	/// the agent's model parameters, also returning the documents retrieved from the context
+	/// providers.
+	pub(crate) async fn completion_with_context(
+		&self,
+		prompt: impl Into<Message>,
 		chat_history: Vec<Message>,
-	) -> Result<CompletionRequestBuilder<M>, CompletionError> {
+		options: &PromptOptions,
+	) -> Result<(CompletionRequestBuilder<M>, Vec<Document>), CompletionError> {
 		let prompt = prompt.into();
 
 		// Find the latest message in the chat history that contains RAG text
-		let rag_text = prompt.rag_text();
-		let rag_text = rag_text.or_else(|| {
-			chat_history
-				.iter()
-				.rev()
-				.find_map(|message| message.rag_text())
-		});
+		let rag_message = Some(&prompt)
+			.filter(|message| message.rag_text().is_some())
+			.or_else(|| {
+				chat_history
+					.iter()
+					.rev()
+					.find(|message| message.rag_text().is_some())
+			});
+		let rag_text = rag_message.and_then(Message::rag_text);
+
+		let (preamble, templated_context) = self
+			.render_templates()
+			.await
+			.map_err(|e| CompletionError::RequestError(Box::new(e)))?;
+
+		let provided_context = context::retrieve_context(
+			&self.context_providers,
+			self.context_failure_policy,
+			rag_message.unwrap_or(&prompt),
+		)
+		.await
+		.map_err(|e| CompletionError::RequestError(Box::new(e)))?;
 
 		let completion_request = self
 			.model
 			.completion_request(prompt)
 			.messages(chat_history)
-			.temperature_opt(self.temperature)
-			.max_tokens_opt(self.max_tokens)
-			.additional_params_opt(self.additional_params.clone())
-			.documents(self.static_context.clone());
-		let completion_request = if let Some(preamble) = &self.preamble {
-			completion_request.preamble(preamble.to_owned())
-		} else {
-			completion_request
-		};
-		let completion_request = if let Some(tool_choice) = &self.tool_choice {
-			completion_request.tool_choice(tool_choice.clone())
+			.temperature_opt(options.temperature.or(self.temperature))
+			.max_tokens_opt(options.max_tokens.or(self.max_tokens))
+			.additional_params_opt(options.merge_params(self.additional_params.as_ref()))
+			.documents(self.static_context.clone())
+			.documents(templated_context)
+			.documents(provided_context.clone());
+		let completion_request = if let Some(preamble) = preamble {
+			completion_request.preamble(preamble)
 		} else {
 			completion_request
 		};
+		let completion_request =
+			if let Some(tool_choice) = options.tool_choice.as_ref().or(self.tool_choice.as_ref()) {
+				completion_request.tool_choice(tool_choice.clone())
+			} else {
+				completion_request
+			};
 
 		// If the agent has RAG text, we need to fetch the dynamic context and tools
 		let agent = match &rag_text {
@@ -167,6 +342,9 @@ where
 					.map_err(|_| {
 						CompletionError::RequestError("Failed to get tool definitions".into())
 					})?;
+				let tooldefs = select_tools(self.tool_selector.as_deref(), text, tooldefs)
+					.await
+					.map_err(|e| CompletionError::RequestError(Box::new(e)))?;
 
 				completion_request
 					.documents(dynamic_context)
@@ -185,7 +363,63 @@ where
 			}
 		};
 
-		Ok(agent)
+		Ok((agent, provided_context))
+	}
+
+	/// Calls a tool requested by the model. With strict tool selection, the tools which weren't
+	/// `advertised` in the request fail as if they didn't exist.
+	///
+	/// Calls to unknown tools are handled with the [UnknownToolPolicy]. The inner error is sent
+	/// back to the model as the tool result, while the outer one fails the prompt.
+	pub(crate) async fn call_tool(
+		&self,
+		advertised: &HashSet<String>,
+		tool_name: &str,
+		args: &str,
+	) -> Result<Result<OneOrMany<ToolResultContent>, String>, PromptError> {
+		let result = if self.strict_tool_selection && !advertised.contains(tool_name) {
+			Err(ToolSetError::ToolNotFoundError(tool_name.to_string()).into())
+		} else {
+			self.tool_server_handle
+				.call_tool_content(tool_name, args)
+				.await
+		};
+
+		match result {
+			Ok(content) => Ok(Ok(content)),
+			Err(ToolServerError::ToolsetError(ToolSetError::ToolNotFoundError(_))) => {
+				match self.unknown_tool_policy {
+					UnknownToolPolicy::Error => {
+						Err(ToolSetError::ToolNotFoundError(tool_name.to_string()).into())
+					}
+					UnknownToolPolicy::ReturnErrorResult => {
+						let mut available: Vec<_> = advertised.iter().map(String::as_str).collect();
+						available.sort_unstable();
+						Ok(Err(format!(
+							"Unknown tool `{tool_name}`, available tools: [{}]",
+							available.join(", ")
+						)))
+					}
+					UnknownToolPolicy::Ignore => Ok(Ok(OneOrMany::one(String::new().into()))),
+				}
+			}
+			Err(e) => Ok(Err(e.to_string())),
+		}
+	}
+}
+
+impl<M> Completion<M> for Agent<M>
+where
+	M: CompletionModel,
+{
+	async fn completion(
+		&self,
+		prompt: impl Into<Message> + WasmCompatSend,
+		chat_history: Vec<Message>,
+	) -> Result<CompletionRequestBuilder<M>, CompletionError> {
+		self.completion_with_context(prompt, chat_history, &PromptOptions::default())
+			.await
+			.map(|(request, _)| request)
 	}
 }
 
@@ -283,3 +517,127 @@ where
 		StreamingPromptRequest::new(arc, prompt).with_history(chat_history)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agent::AgentBuilder;
+	use crate::test_utils::MockCompletionModel;
+
+	#[tokio::test]
+	async fn test_templates_render_at_request_time() {
+		let model = MockCompletionModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.preamble("ignored")
+			.preamble_template("You are {persona}.")
+			.context_template("The user's name is {user}.")
+			.template_var("persona", "a pirate")
+			.template_var("user", "Ada")
+			.build();
+
+		agent.prompt("Hello").await.unwrap();
+		agent.set_template_var("persona", "a poet").await;
+		agent.prompt("Hello again").await.unwrap();
+
+		let requests = model.requests();
+		assert_eq!(requests[0].preamble.as_deref(), Some("You are a pirate."));
+		assert_eq!(requests[1].preamble.as_deref(), Some("You are a poet."));
+		assert_eq!(requests[0].documents.len(), 1);
+		assert_eq!(requests[0].documents[0].text, "The user's name is Ada.");
+	}
+
+	#[tokio::test]
+	async fn test_missing_template_var_fails_request() {
+		let agent = AgentBuilder::new(MockCompletionModel::default())
+			.preamble_template("You are {persona}.")
+			.build();
+
+		let err = agent.prompt("Hello").await.unwrap_err();
+		assert!(err.to_string().contains("persona"), "{err}");
+	}
+
+	#[tokio::test]
+	async fn test_request_overrides_agent_settings() {
+		use crate::completion::CompletionRequest;
+
+		let agent = AgentBuilder::new(MockCompletionModel::default())
+			.preamble("You are a pirate.")
+			.temperature(0.5)
+			.max_tokens(100)
+			.additional_params(serde_json::json!({ "a": 1, "b": 1 }))
+			.build();
+
+		let request = CompletionRequest::from(agent.completion("Hello", vec![]).await.unwrap());
+		assert_eq!(request.preamble.as_deref(), Some("You are a pirate."));
+		assert_eq!(request.temperature, Some(0.5));
+		assert_eq!(request.max_tokens, Some(100));
+
+		let request = agent
+			.completion("Hello", vec![])
+			.await
+			.unwrap()
+			.preamble("You are a poet.".to_string())
+			.temperature(0.9)
+			.additional_params(serde_json::json!({ "b": 2 }))
+			.build();
+		assert_eq!(request.preamble.as_deref(), Some("You are a poet."));
+		assert_eq!(request.temperature, Some(0.9));
+		assert_eq!(request.max_tokens, Some(100));
+		assert_eq!(
+			request.additional_params,
+			Some(serde_json::json!({ "a": 1, "b": 2 }))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_prompt_options_override_agent_settings_once() {
+		use futures::StreamExt;
+		use serde_json::json;
+
+		let model = MockCompletionModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.temperature(0.8)
+			.max_tokens(100)
+			.additional_params(
+				json!({ "reasoning": { "effort": "high", "summary": "auto" }, "seed": 1 }),
+			)
+			.build();
+		let options = PromptOptions {
+			temperature: Some(0.1),
+			tool_choice: Some(ToolChoice::None),
+			additional_params: Some(json!({ "reasoning": { "effort": "low" } })),
+			..Default::default()
+		};
+
+		agent
+			.prompt_with_options("Hello", options.clone())
+			.await
+			.unwrap();
+		agent.prompt("Hello again").await.unwrap();
+		let mut stream = agent.stream_prompt_with_options("Hello", options).await;
+		while stream.next().await.is_some() {}
+		let mut stream = agent.stream_prompt("Hello again").await;
+		while stream.next().await.is_some() {}
+
+		let requests = model.requests();
+		assert_eq!(requests.len(), 4);
+		for overridden in [&requests[0], &requests[2]] {
+			assert_eq!(overridden.temperature, Some(0.1));
+			assert_eq!(overridden.max_tokens, Some(100));
+			assert_eq!(overridden.tool_choice, Some(ToolChoice::None));
+			assert_eq!(
+				overridden.additional_params,
+				Some(json!({ "reasoning": { "effort": "low", "summary": "auto" }, "seed": 1 }))
+			);
+		}
+		for default in [&requests[1], &requests[3]] {
+			assert_eq!(default.temperature, Some(0.8));
+			assert_eq!(default.max_tokens, Some(100));
+			assert_eq!(default.tool_choice, None);
+			assert_eq!(
+				default.additional_params,
+				Some(json!({ "reasoning": { "effort": "high", "summary": "auto" }, "seed": 1 }))
+			);
+		}
+	}
+}
@@ -0,0 +1,240 @@
+//! Context providers, which retrieve documents to add to the agent's requests at prompt time.
+//!
+//! Unlike [`AgentBuilder::dynamic_context`](crate::agent::AgentBuilder::dynamic_context), which
+//! samples documents from a vector store index, a [ContextProvider] can retrieve documents from
+//! anywhere (a search API, a database, the user's session, ...).
+//!
+//! # Example
+//! ```no_run
+//! use clankers::{
+//!     agent::ContextProvider,
+//!     client::{CompletionClient, ProviderClient},
+//!     completion::{Document, Message, Prompt},
+//!     providers::openai,
+//! };
+//!
+//! struct Weather;
+//!
+//! impl ContextProvider for Weather {
+//!     type Error = std::io::Error;
+//!
+//!     async fn context(&self, _prompt: &Message) -> Result<Vec<Document>, Self::Error> {
+//!         Ok(vec![Document {
+//!             id: "weather".to_string(),
+//!             text: "It is sunny today.".to_string(),
+//!             additional_props: Default::default(),
+//!         }])
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let agent = openai::Client::from_env()
+//!         .agent("gpt-4o")
+//!         .context_provider(Weather)
+//!         .build();
+//!
+//!     let response = agent
+//!         .prompt("Should I take an umbrella?")
+//!         .extended_details()
+//!         .await
+//!         .expect("Failed to prompt the agent");
+//!
+//!     for document in &response.context_documents {
+//!         println!("Used document {}", document.id);
+//!     }
+//! }
+//! ```
+use std::sync::Arc;
+
+use futures::future;
+
+use crate::completion::{Document, Message};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
+
+/// Trait for retrieving context documents for a prompt, e.g. to implement RAG.
+///
+/// The context providers of an agent are called concurrently every time it builds a completion
+/// request, and the documents they return are added to the request's documents.
+pub trait ContextProvider: WasmCompatSend + WasmCompatSync {
+	/// The error type of the context provider.
+	type Error: std::error::Error + WasmCompatSend + WasmCompatSync + 'static;
+
+	/// Retrieves the documents relevant to the prompt.
+	fn context(
+		&self,
+		prompt: &Message,
+	) -> impl Future<Output = Result<Vec<Document>, Self::Error>> + WasmCompatSend;
+}
+
+/// Wrapper trait to allow for dynamic dispatch of context providers
+pub trait ContextProviderDyn: WasmCompatSend + WasmCompatSync {
+	fn context<'a>(
+		&'a self,
+		prompt: &'a Message,
+	) -> WasmBoxedFuture<'a, Result<Vec<Document>, ContextError>>;
+}
+
+impl<T: ContextProvider> ContextProviderDyn for T {
+	fn context<'a>(
+		&'a self,
+		prompt: &'a Message,
+	) -> WasmBoxedFuture<'a, Result<Vec<Document>, ContextError>> {
+		Box::pin(async move {
+			<Self as ContextProvider>::context(self, prompt)
+				.await
+				.map_err(|e| ContextError(Box::new(e)))
+		})
+	}
+}
+
+/// Error returned by a [ContextProvider]
+#[derive(Debug, thiserror::Error)]
+#[error("ContextProviderError: {0}")]
+pub struct ContextError(
+	#[cfg(not(target_family = "wasm"))]
+	#[source]
+	Box<dyn std::error::Error + Send + Sync>,
+	#[cfg(target_family = "wasm")]
+	#[source]
+	Box<dyn std::error::Error>,
+);
+
+/// What to do when a context provider fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextFailurePolicy {
+	/// Log a warning and send the request without the documents of the failing provider
+	#[default]
+	Skip,
+	/// Fail the request with the provider's error
+	Fail,
+}
+
+/// Calls all the context providers concurrently and collects their documents, in the order the
+/// providers were added.
+pub(crate) async fn retrieve_context(
+	providers: &[Arc<dyn ContextProviderDyn>],
+	policy: ContextFailurePolicy,
+	prompt: &Message,
+) -> Result<Vec<Document>, ContextError> {
+	let results = future::join_all(providers.iter().map(|provider| provider.context(prompt))).await;
+
+	let mut documents = vec![];
+	for result in results {
+		match result {
+			Ok(docs) => documents.extend(docs),
+			Err(e) if policy == ContextFailurePolicy::Skip => {
+				tracing::warn!("Skipping the documents of a failed context provider: {e}");
+			}
+			Err(e) => return Err(e),
+		}
+	}
+
+	Ok(documents)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::*;
+	use crate::agent::AgentBuilder;
+	use crate::completion::{Prompt, PromptError};
+	use crate::test_utils::MockCompletionModel;
+
+	#[derive(Debug, thiserror::Error)]
+	#[error("index unavailable")]
+	struct IndexUnavailable;
+
+	/// Returns a document echoing the prompt, or fails if `fail` is set.
+	struct MockProvider {
+		id: &'static str,
+		fail: bool,
+	}
+
+	impl ContextProvider for MockProvider {
+		type Error = IndexUnavailable;
+
+		async fn context(&self, prompt: &Message) -> Result<Vec<Document>, Self::Error> {
+			if self.fail {
+				return Err(IndexUnavailable);
+			}
+
+			Ok(vec![Document {
+				id: self.id.to_string(),
+				text: format!("About: {}", prompt.rag_text().unwrap_or_default()),
+				additional_props: HashMap::new(),
+			}])
+		}
+	}
+
+	fn agent_builder(model: MockCompletionModel) -> AgentBuilder<MockCompletionModel> {
+		AgentBuilder::new(model)
+			.context("Static document")
+			.context_provider(MockProvider {
+				id: "search",
+				fail: false,
+			})
+			.context_provider(MockProvider {
+				id: "broken",
+				fail: true,
+			})
+	}
+
+	#[tokio::test]
+	async fn test_failing_provider_is_skipped() {
+		let model = MockCompletionModel::default();
+		let agent = agent_builder(model.clone()).build();
+
+		let response = agent
+			.prompt("Rust editions")
+			.extended_details()
+			.await
+			.unwrap();
+
+		let documents = &model.requests()[0].documents;
+		assert_eq!(documents.len(), 2);
+		assert_eq!(documents[0].text, "Static document");
+		assert_eq!(documents[1].id, "search");
+		assert_eq!(documents[1].text, "About: Rust editions");
+
+		assert_eq!(response.context_documents.len(), 1);
+		assert_eq!(response.context_documents[0].id, "search");
+	}
+
+	#[tokio::test]
+	async fn test_failing_provider_fails_prompt() {
+		let model = MockCompletionModel::default();
+		let agent = agent_builder(model.clone())
+			.context_failure_policy(ContextFailurePolicy::Fail)
+			.build();
+
+		let err = agent.prompt("Rust editions").await.unwrap_err();
+
+		assert!(matches!(err, PromptError::CompletionError(_)), "{err:?}");
+		assert!(err.to_string().contains("index unavailable"), "{err}");
+		assert!(model.requests().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_streaming_final_response_has_context_documents() {
+		use futures::StreamExt;
+
+		use crate::agent::MultiTurnStreamItem;
+		use crate::streaming::StreamingPrompt;
+
+		let agent = agent_builder(MockCompletionModel::default()).build();
+
+		let mut stream = agent.stream_prompt("Rust editions").await;
+		let mut final_response = None;
+		while let Some(item) = stream.next().await {
+			if let MultiTurnStreamItem::FinalResponse(response) = item.unwrap() {
+				final_response = Some(response);
+			}
+		}
+
+		let documents = final_response.unwrap().context_documents().to_vec();
+		assert_eq!(documents.len(), 1);
+		assert_eq!(documents[0].text, "About: Rust editions");
+	}
+}
@@ -108,16 +108,24 @@
 //! ```
 mod builder;
 mod completion;
+mod context;
+mod moderation;
 pub(crate) mod prompt_request;
 mod tool;
+mod tool_selection;
 
 pub use builder::{AgentBuilder, AgentBuilderSimple};
-pub use completion::Agent;
+pub use completion::{Agent, UnknownToolPolicy};
+pub use context::{ContextError, ContextFailurePolicy, ContextProvider, ContextProviderDyn};
+pub use moderation::{ModerationError, ModerationPolicy, Moderator, ModeratorDyn};
 pub use prompt_request::hooks::{HookAction, PromptHook, ToolCallHookAction};
 pub use prompt_request::streaming::{
-	FinalResponse, MultiTurnStreamItem, StreamingError, StreamingPromptRequest, StreamingResult,
-	stream_to_stdout,
+	AgentEventStream, AgentStreamEvent, FinalResponse, MultiTurnStreamItem, StreamingError,
+	StreamingPromptRequest, StreamingResult, stream_to_stdout,
+};
+pub use prompt_request::{DEFAULT_MAX_TURNS, PromptOptions, PromptRequest, PromptResponse};
+pub use tool_selection::{
+	EmbeddingToolSelector, ToolSelectionError, ToolSelector, ToolSelectorDyn,
 };
-pub use prompt_request::{PromptRequest, PromptResponse};
 
 pub use crate::message::Text;
@@ -0,0 +1,197 @@
+//! Input moderation, which checks the user's prompt with a moderation model before it is sent to
+//! the agent's completion model.
+//!
+//! # Example
+//! ```
+//! use clankers::{
+//!     agent::ModerationPolicy,
+//!     completion::{Prompt, PromptError},
+//!     providers::openai,
+//! };
+//!
+//! let client = openai::Client::from_env();
+//!
+//! let agent = client
+//!     .agent("gpt-4o")
+//!     .with_input_moderation(
+//!         client.moderation_model(openai::moderation::OMNI_MODERATION_LATEST),
+//!         ModerationPolicy::Block,
+//!     )
+//!     .build();
+//!
+//! match agent.prompt("Hello!").await {
+//!     Err(PromptError::Moderated { categories }) => println!("Flagged for {categories:?}"),
+//!     response => println!("{response:?}"),
+//! }
+//! ```
+use crate::completion::{Message, PromptError};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
+
+/// Trait for moderation models which can check a prompt for harmful content, e.g.
+/// [openai::moderation::ModerationModel](crate::providers::openai::moderation::ModerationModel).
+pub trait Moderator: WasmCompatSend + WasmCompatSync {
+	/// The error type of the moderator.
+	type Error: std::error::Error + WasmCompatSend + WasmCompatSync + 'static;
+
+	/// Returns the categories the prompt was flagged for, empty if it wasn't flagged.
+	fn moderate(
+		&self,
+		prompt: &Message,
+	) -> impl Future<Output = Result<Vec<String>, Self::Error>> + WasmCompatSend;
+}
+
+/// Wrapper trait to allow for dynamic dispatch of moderators
+pub trait ModeratorDyn: WasmCompatSend + WasmCompatSync {
+	fn moderate<'a>(
+		&'a self,
+		prompt: &'a Message,
+	) -> WasmBoxedFuture<'a, Result<Vec<String>, ModerationError>>;
+}
+
+impl<T: Moderator> ModeratorDyn for T {
+	fn moderate<'a>(
+		&'a self,
+		prompt: &'a Message,
+	) -> WasmBoxedFuture<'a, Result<Vec<String>, ModerationError>> {
+		Box::pin(async move {
+			<Self as Moderator>::moderate(self, prompt)
+				.await
+				.map_err(|e| ModerationError(Box::new(e)))
+		})
+	}
+}
+
+/// Error returned by a [Moderator]
+#[derive(Debug, thiserror::Error)]
+#[error("ModerationError: {0}")]
+pub struct ModerationError(
+	#[cfg(not(target_family = "wasm"))]
+	#[source]
+	Box<dyn std::error::Error + Send + Sync>,
+	#[cfg(target_family = "wasm")]
+	#[source]
+	Box<dyn std::error::Error>,
+);
+
+/// What to do when the prompt is flagged by the moderator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModerationPolicy {
+	/// Fail with [PromptError::Moderated] without sending the prompt to the completion model
+	#[default]
+	Block,
+	/// Send the prompt anyway, logging a warning and returning the flagged categories with the
+	/// response
+	Annotate,
+}
+
+/// Moderates the prompt if the agent has a moderator, returning the categories it was flagged
+/// for when the policy allows it to be sent anyway.
+pub(crate) async fn moderate_prompt(
+	moderator: Option<&dyn ModeratorDyn>,
+	policy: ModerationPolicy,
+	prompt: &Message,
+) -> Result<Vec<String>, PromptError> {
+	let Some(moderator) = moderator else {
+		return Ok(vec![]);
+	};
+
+	let categories = moderator
+		.moderate(prompt)
+		.await
+		.map_err(|e| crate::completion::CompletionError::RequestError(Box::new(e)))?;
+
+	if categories.is_empty() {
+		return Ok(categories);
+	}
+
+	match policy {
+		ModerationPolicy::Block => Err(PromptError::Moderated { categories }),
+		ModerationPolicy::Annotate => {
+			tracing::warn!(
+				"The prompt was flagged by moderation for {}",
+				categories.join(", ")
+			);
+			Ok(categories)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agent::AgentBuilder;
+	use crate::completion::Prompt;
+	use crate::test_utils::MockCompletionModel;
+
+	/// Flags prompts containing "attack" as `violence`.
+	struct MockModerator;
+
+	impl Moderator for MockModerator {
+		type Error = std::io::Error;
+
+		async fn moderate(&self, prompt: &Message) -> Result<Vec<String>, Self::Error> {
+			let text = prompt.rag_text().unwrap_or_default();
+			Ok(if text.contains("attack") {
+				vec!["violence".to_string()]
+			} else {
+				vec![]
+			})
+		}
+	}
+
+	#[tokio::test]
+	async fn test_flagged_prompt_is_blocked() {
+		let model = MockCompletionModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.with_input_moderation(MockModerator, ModerationPolicy::Block)
+			.build();
+
+		let err = agent.prompt("How do I attack a castle?").await.unwrap_err();
+		match err {
+			PromptError::Moderated { categories } => assert_eq!(categories, ["violence"]),
+			other => panic!("Expected a moderation error, got {other:?}"),
+		}
+		assert!(model.requests().is_empty());
+
+		agent.prompt("How do I build a castle?").await.unwrap();
+		assert_eq!(model.requests().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_flagged_prompt_is_annotated() {
+		let model = MockCompletionModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.with_input_moderation(MockModerator, ModerationPolicy::Annotate)
+			.build();
+
+		let response = agent
+			.prompt("How do I attack a castle?")
+			.extended_details()
+			.await
+			.unwrap();
+		assert_eq!(response.flagged_categories, ["violence"]);
+		assert_eq!(model.requests().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_flagged_streaming_prompt_is_blocked() {
+		use futures::StreamExt;
+
+		use crate::agent::StreamingError;
+		use crate::streaming::StreamingPrompt;
+
+		let model = MockCompletionModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.with_input_moderation(MockModerator, ModerationPolicy::Block)
+			.build();
+
+		let mut stream = agent.stream_prompt("How do I attack a castle?").await;
+		let err = stream.next().await.unwrap().unwrap_err();
+		assert!(
+			matches!(err, StreamingError::Prompt(ref e) if matches!(**e, PromptError::Moderated { .. })),
+			"{err:?}"
+		);
+		assert!(stream.next().await.is_none());
+		assert!(model.requests().is_empty());
+	}
+}
@@ -8,13 +8,22 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use futures::{StreamExt, stream};
 use hooks::{HookAction, PromptHook, ToolCallHookAction};
 use tracing::span::Id;
-use tracing::{Instrument, info_span};
+use tracing::{Instrument, Span, info_span};
 
 use super::Agent;
-use crate::completion::{Completion, CompletionModel, Message, PromptError, Usage};
-use crate::message::{AssistantContent, ToolResultContent, UserContent};
+use super::moderation::moderate_prompt;
+use crate::completion::metadata::Stopwatch;
+use crate::completion::{CompletionModel, Document, Message, PromptError, Usage};
+use crate::json_utils::ArrayMergeStrategy;
+use crate::message::{
+	AssistantContent, MimeType, ToolCall, ToolChoice, ToolResultContent, UserContent,
+};
 use crate::wasm_compat::WasmBoxedFuture;
-use crate::{OneOrMany, json_utils};
+use crate::{OneOrMany, json_utils, telemetry};
+
+/// The maximum number of tool round-trips of a single prompt after the first one, when neither
+/// the agent nor the request sets one, see [AgentBuilder::default_max_turns](crate::agent::AgentBuilder::default_max_turns).
+pub const DEFAULT_MAX_TURNS: usize = 10;
 
 pub trait PromptType {}
 pub struct Standard;
@@ -23,14 +32,41 @@ pub struct Extended;
 impl PromptType for Standard {}
 impl PromptType for Extended {}
 
+/// Model parameters overriding the agent's for a single prompt, see
+/// [Agent::prompt_with_options]. The parameters which aren't set keep the agent's values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PromptOptions {
+	pub temperature: Option<f64>,
+	pub max_tokens: Option<u64>,
+	pub tool_choice: Option<ToolChoice>,
+	/// Deep merged over the agent's additional parameters
+	pub additional_params: Option<serde_json::Value>,
+}
+
+impl PromptOptions {
+	/// The agent's additional parameters with the ones of these options merged over them
+	pub(crate) fn merge_params(
+		&self,
+		params: Option<&serde_json::Value>,
+	) -> Option<serde_json::Value> {
+		match (params, self.additional_params.as_ref()) {
+			(Some(params), Some(overrides)) => Some(json_utils::deep_merge(
+				params.clone(),
+				overrides.clone(),
+				ArrayMergeStrategy::Replace,
+			)),
+			(params, overrides) => overrides.or(params).cloned(),
+		}
+	}
+}
+
 /// A builder for creating prompt requests with customizable options.
 /// Uses generics to track which options have been set during the build process.
 ///
-/// If you expect to continuously call tools, you will want to ensure you use the `.multi_turn()`
-/// argument to add more turns as by default, it is 0 (meaning only 1 tool round-trip). Otherwise,
-/// attempting to await (which will send the prompt request) can potentially return
-/// [`crate::completion::request::PromptError::MaxTurnsError`] if the agent decides to call tools
-/// back to back.
+/// Each tool round-trip after the first one counts as a turn, so `.max_turns(0)` still lets the
+/// agent call tools once before answering ([DEFAULT_MAX_TURNS] turns unless the agent sets its own
+/// default). If the agent is still calling tools when the limit is reached, awaiting the request
+/// returns [`crate::completion::request::PromptError::MaxTurnsError`] with the history so far.
 pub struct PromptRequest<'a, S, M, P>
 where
 	S: PromptType,
@@ -52,6 +88,8 @@ where
 	hook: Option<P>,
 	/// How many tools should be executed at the same time (1 by default).
 	concurrency: usize,
+	/// Model parameters overriding the agent's
+	options: PromptOptions,
 }
 
 impl<'a, M> PromptRequest<'a, Standard, M, ()>
@@ -63,11 +101,12 @@ where
 		Self {
 			prompt: prompt.into(),
 			chat_history: None,
-			max_turns: agent.default_max_turns.unwrap_or_default(),
+			max_turns: agent.default_max_turns.unwrap_or(DEFAULT_MAX_TURNS),
 			agent,
 			state: PhantomData,
 			hook: None,
-			concurrency: 1,
+			concurrency: agent.tool_concurrency.unwrap_or(1),
+			options: PromptOptions::default(),
 		}
 	}
 }
@@ -92,6 +131,7 @@ where
 			state: PhantomData,
 			hook: self.hook,
 			concurrency: self.concurrency,
+			options: self.options,
 		}
 	}
 	/// Set the maximum number of turns for multi-turn conversations. A given agent may require multiple turns for tool-calling before giving an answer.
@@ -105,16 +145,25 @@ where
 			state: PhantomData,
 			hook: self.hook,
 			concurrency: self.concurrency,
+			options: self.options,
 		}
 	}
 
 	/// Add concurrency to the prompt request.
-	/// This will cause the agent to execute tools concurrently.
+	/// This will cause the agent to execute tools concurrently, overriding
+	/// [`AgentBuilder::tool_concurrency`](crate::agent::AgentBuilder::tool_concurrency).
+	/// Tool results keep the order of the tool calls regardless of which finishes first.
 	pub fn with_tool_concurrency(mut self, concurrency: usize) -> Self {
 		self.concurrency = concurrency;
 		self
 	}
 
+	/// Override the agent's model parameters for this prompt only
+	pub fn with_options(mut self, options: PromptOptions) -> Self {
+		self.options = options;
+		self
+	}
+
 	/// Add chat history to the prompt request
 	pub fn with_history(self, history: &'a mut Vec<Message>) -> PromptRequest<'a, S, M, P> {
 		PromptRequest {
@@ -125,6 +174,7 @@ where
 			state: PhantomData,
 			hook: self.hook,
 			concurrency: self.concurrency,
+			options: self.options,
 		}
 	}
 
@@ -141,6 +191,7 @@ where
 			state: PhantomData,
 			hook: Some(hook),
 			concurrency: self.concurrency,
+			options: self.options,
 		}
 	}
 }
@@ -188,6 +239,14 @@ where
 pub struct PromptResponse {
 	pub output: String,
 	pub total_usage: Usage,
+	/// Documents retrieved from the agent's context providers, deduplicated by id
+	pub context_documents: Vec<Document>,
+	/// Categories the prompt was flagged for by the agent's moderator, when flagged prompts are
+	/// only annotated
+	pub flagged_categories: Vec<String>,
+	/// The last assistant message, with all of its content (e.g. reasoning), to append to a
+	/// history held by the caller
+	pub message: Option<Message>,
 }
 
 impl PromptResponse {
@@ -195,6 +254,33 @@ impl PromptResponse {
 		Self {
 			output: output.into(),
 			total_usage,
+			context_documents: vec![],
+			flagged_categories: vec![],
+			message: None,
+		}
+	}
+
+	pub fn with_context_documents(mut self, documents: Vec<Document>) -> Self {
+		self.context_documents = documents;
+		self
+	}
+
+	pub fn with_flagged_categories(mut self, categories: Vec<String>) -> Self {
+		self.flagged_categories = categories;
+		self
+	}
+
+	pub fn with_message(mut self, message: Message) -> Self {
+		self.message = Some(message);
+		self
+	}
+}
+
+/// Adds the documents whose id is not already in `acc`.
+pub(crate) fn merge_context_documents(acc: &mut Vec<Document>, documents: Vec<Document>) {
+	for document in documents {
+		if !acc.iter().any(|doc| doc.id == document.id) {
+			acc.push(document);
 		}
 	}
 }
@@ -221,6 +307,13 @@ where
 		};
 
 		let agent = self.agent;
+		let flagged_categories = moderate_prompt(
+			agent.moderator.as_deref(),
+			agent.moderation_policy,
+			&self.prompt,
+		)
+		.await?;
+
 		let chat_history = if let Some(history) = self.chat_history {
 			history.push(self.prompt.to_owned());
 			history
@@ -234,6 +327,7 @@ where
 
 		let mut current_max_turns = 0;
 		let mut usage = Usage::new();
+		let mut context_documents = vec![];
 		let current_span_id: AtomicU64 = AtomicU64::new(0);
 
 		// We need to do at least 2 loops for 1 roundtrip (user expects normal message)
@@ -279,6 +373,8 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
 				gen_ai.input.messages = tracing::field::Empty,
 				gen_ai.output.messages = tracing::field::Empty,
 			);
@@ -294,15 +390,18 @@ where
 				current_span_id.store(id.into_u64(), Ordering::SeqCst);
 			};
 
-			let resp = agent
-				.completion(
+			let (request, documents) = agent
+				.completion_with_context(
 					prompt.clone(),
 					chat_history[..chat_history.len() - 1].to_vec(),
+					&self.options,
 				)
-				.await?
-				.send()
-				.instrument(chat_span.clone())
 				.await?;
+			merge_context_documents(&mut context_documents, documents);
+			let advertised_tools = request.tool_names();
+			let advertised_tools = &advertised_tools;
+
+			let resp = request.send().instrument(chat_span.clone()).await?;
 
 			usage += resp.usage;
 
@@ -343,9 +442,16 @@ where
 				agent_span.record("gen_ai.completion", &merged_texts);
 				agent_span.record("gen_ai.usage.input_tokens", usage.input_tokens);
 				agent_span.record("gen_ai.usage.output_tokens", usage.output_tokens);
+				tracing::debug!(target: "clankers", "Agent token usage: {usage}");
 
 				// If there are no tool calls, depth is not relevant, we can just return the merged text response.
-				return Ok(PromptResponse::new(merged_texts, usage));
+				return Ok(PromptResponse::new(merged_texts, usage)
+					.with_context_documents(context_documents)
+					.with_flagged_categories(flagged_categories)
+					.with_message(Message::Assistant {
+						id: None,
+						content: resp.choice,
+					}));
 			}
 
 			let hook = self.hook.clone();
@@ -356,15 +462,7 @@ where
 					let hook1 = hook.clone();
 					let hook2 = hook.clone();
 
-					let tool_span = info_span!(
-						"execute_tool",
-						gen_ai.operation.name = "execute_tool",
-						gen_ai.tool.type = "function",
-						gen_ai.tool.name = tracing::field::Empty,
-						gen_ai.tool.call.id = tracing::field::Empty,
-						gen_ai.tool.call.arguments = tracing::field::Empty,
-						gen_ai.tool.call.result = tracing::field::Empty
-					);
+					let tool_span = execute_tool_span();
 
 					let tool_span = if current_span_id.load(Ordering::SeqCst) != 0 {
 						let id = Id::from_u64(current_span_id.load(Ordering::SeqCst));
@@ -386,9 +484,7 @@ where
 								json_utils::value_to_json_string(&tool_call.function.arguments);
 							let internal_call_id = nanoid::nanoid!();
 							let tool_span = tracing::Span::current();
-							tool_span.record("gen_ai.tool.name", tool_name);
-							tool_span.record("gen_ai.tool.call.id", &tool_call.id);
-							tool_span.record("gen_ai.tool.call.arguments", &args);
+							record_tool_call(&tool_span, &tool_call, &args);
 							if let Some(hook) = hook1 {
 								let action = hook
 									.on_tool_call(
@@ -427,14 +523,11 @@ where
 									}
 								}
 							}
-							let output =
-								match agent.tool_server_handle.call_tool(tool_name, &args).await {
-									Ok(res) => res,
-									Err(e) => {
-										tracing::warn!("Error while executing tool: {e}");
-										e.to_string()
-									}
-								};
+							let stopwatch = Stopwatch::start();
+							let result =
+								agent.call_tool(advertised_tools, tool_name, &args).await?;
+							let (content, output) =
+								tool_call_content(&tool_span, stopwatch, result);
 							if let Some(hook) = hook2
 								&& let HookAction::Terminate { reason } = hook
 									.on_tool_result(
@@ -442,7 +535,7 @@ where
 										tool_call.call_id.clone(),
 										&internal_call_id,
 										&args,
-										&output.to_string(),
+										&output,
 									)
 									.await
 							{
@@ -452,7 +545,6 @@ where
 								));
 							}
 
-							tool_span.record("gen_ai.tool.call.result", &output);
 							tracing::info!(
 								"executed tool {tool_name} with args {args}. result: {output}"
 							);
@@ -460,13 +552,10 @@ where
 								Ok(UserContent::tool_result_with_call_id(
 									tool_call.id.clone(),
 									call_id,
-									ToolResultContent::from_tool_output(output),
+									content,
 								))
 							} else {
-								Ok(UserContent::tool_result(
-									tool_call.id.clone(),
-									ToolResultContent::from_tool_output(output),
-								))
+								Ok(UserContent::tool_result(tool_call.id.clone(), content))
 							}
 						} else {
 							unreachable!(
@@ -476,7 +565,7 @@ where
 					}
 					.instrument(tool_span)
 				})
-				.buffer_unordered(self.concurrency)
+				.buffered(self.concurrency.max(1))
 				.collect::<Vec<Result<UserContent, PromptError>>>()
 				.await
 				.into_iter()
@@ -495,3 +584,570 @@ where
 		})
 	}
 }
+
+/// Renders the content of a tool result as text, for hooks and tracing.
+/// Images are replaced by a placeholder naming their media type.
+pub(crate) fn tool_result_to_string(content: &OneOrMany<ToolResultContent>) -> String {
+	content
+		.iter()
+		.map(|content| match content {
+			ToolResultContent::Text(text) => text.text.clone(),
+			ToolResultContent::Image(image) => match &image.media_type {
+				Some(media_type) => format!("[image: {}]", media_type.to_mime_type()),
+				None => "[image]".to_string(),
+			},
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Creates the span of a tool call made by the agent, filled in by [record_tool_call] and
+/// [tool_call_content].
+pub(crate) fn execute_tool_span() -> Span {
+	info_span!(
+		"execute_tool",
+		gen_ai.operation.name = "execute_tool",
+		gen_ai.tool.type = "function",
+		gen_ai.tool.name = tracing::field::Empty,
+		gen_ai.tool.call.id = tracing::field::Empty,
+		gen_ai.tool.call.arguments = tracing::field::Empty,
+		gen_ai.tool.call.result = tracing::field::Empty,
+		gen_ai.tool.call.result.length = tracing::field::Empty,
+		duration_ms = tracing::field::Empty,
+		error = tracing::field::Empty,
+		error.message = tracing::field::Empty,
+	)
+}
+
+/// Records a tool call on its span, the arguments go through the telemetry redactor.
+pub(crate) fn record_tool_call(span: &Span, tool_call: &ToolCall, args: &str) {
+	span.record("gen_ai.tool.name", &tool_call.function.name);
+	span.record("gen_ai.tool.call.id", &tool_call.id);
+	span.record(
+		"gen_ai.tool.call.arguments",
+		telemetry::redact_tool_payload(args),
+	);
+}
+
+/// Turns the result of a tool call into the content sent back to the model and its text,
+/// recording the outcome on the span of the call. Errors are sent to the model as text.
+pub(crate) fn tool_call_content(
+	span: &Span,
+	stopwatch: Stopwatch,
+	result: Result<OneOrMany<ToolResultContent>, String>,
+) -> (OneOrMany<ToolResultContent>, String) {
+	if let Some(elapsed) = stopwatch.elapsed() {
+		span.record("duration_ms", elapsed.as_millis() as u64);
+	}
+
+	let content = match result {
+		Ok(content) => content,
+		Err(e) => {
+			tracing::warn!("Error while executing tool: {e}");
+			span.record("error", true);
+			span.record("error.message", e.as_str());
+			OneOrMany::one(e.into())
+		}
+	};
+
+	let output = tool_result_to_string(&content);
+	span.record(
+		"gen_ai.tool.call.result",
+		telemetry::redact_tool_payload(&output),
+	);
+	span.record("gen_ai.tool.call.result.length", output.len());
+	(content, output)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use serde::Deserialize;
+	use serde_json::json;
+	use tracing_subscriber::layer::SubscriberExt;
+
+	use super::*;
+	use crate::agent::AgentBuilder;
+	use crate::completion::{Prompt, ToolDefinition};
+	use crate::message::ImageMediaType;
+	use crate::test_utils::{MockCompletionModel, RecordedFields};
+	use crate::tool::Tool;
+
+	#[derive(Debug, thiserror::Error)]
+	#[error("Sleep error")]
+	struct SleepError;
+
+	#[derive(Deserialize)]
+	struct SleepArgs {
+		millis: u64,
+	}
+
+	/// Sleeps for the given duration, or panics if asked for 0 milliseconds.
+	struct Sleep;
+
+	impl Tool for Sleep {
+		const NAME: &'static str = "sleep";
+		type Error = SleepError;
+		type Args = SleepArgs;
+		type Output = String;
+
+		async fn definition(&self, _prompt: String) -> ToolDefinition {
+			ToolDefinition {
+				name: Self::NAME.to_string(),
+				description: "Sleep for a while".to_string(),
+				parameters: json!({
+					"type": "object",
+					"properties": { "millis": { "type": "number" } },
+				}),
+			}
+		}
+
+		async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+			if args.millis == 0 {
+				panic!("cannot sleep for 0ms");
+			}
+			tokio::time::sleep(Duration::from_millis(args.millis)).await;
+			Ok(format!("slept {}ms", args.millis))
+		}
+	}
+
+	fn sleep_calls(millis: &[u64]) -> OneOrMany<AssistantContent> {
+		OneOrMany::many(millis.iter().enumerate().map(|(i, millis)| {
+			AssistantContent::tool_call(format!("call-{i}"), "sleep", json!({ "millis": millis }))
+		}))
+		.unwrap()
+	}
+
+	/// Returns the `(id, text)` of every tool result sent back in the last request.
+	fn tool_results(model: &MockCompletionModel) -> Vec<(String, String)> {
+		let request = model.requests().pop().unwrap();
+		let Message::User { content } = request.chat_history.last() else {
+			panic!("expected the tool results as the last message");
+		};
+
+		content
+			.iter()
+			.map(|content| {
+				let UserContent::ToolResult(result) = content else {
+					panic!("expected a tool result");
+				};
+				let ToolResultContent::Text(text) = result.content.first() else {
+					panic!("expected a text tool result");
+				};
+				(result.id.clone(), text.text)
+			})
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn test_concurrent_tool_results_keep_call_order() {
+		let model = MockCompletionModel::with_responses([sleep_calls(&[60, 30, 1])]);
+		let agent = AgentBuilder::new(model.clone())
+			.tool(Sleep)
+			.tool_concurrency(3)
+			.build();
+
+		let response = agent.prompt("Sleep").await.unwrap();
+		assert_eq!(response, "done");
+
+		assert_eq!(
+			tool_results(&model),
+			[
+				("call-0".to_string(), "\"slept 60ms\"".to_string()),
+				("call-1".to_string(), "\"slept 30ms\"".to_string()),
+				("call-2".to_string(), "\"slept 1ms\"".to_string()),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_tool_execution_span() {
+		let fields = RecordedFields::default();
+		let _guard =
+			tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+		let model = MockCompletionModel::with_responses([sleep_calls(&[1])]);
+		let agent = AgentBuilder::new(model).tool(Sleep).build();
+		agent.prompt("Sleep").await.unwrap();
+
+		let fields = fields.get();
+		assert_eq!(fields["gen_ai.tool.name"], "sleep");
+		assert_eq!(fields["gen_ai.tool.call.id"], "call-0");
+		assert_eq!(fields["gen_ai.tool.call.arguments"], r#"{"millis":1}"#);
+		assert_eq!(fields["gen_ai.tool.call.result"], "\"slept 1ms\"");
+		assert_eq!(fields["gen_ai.tool.call.result.length"], "11");
+		assert!(fields.contains_key("duration_ms"));
+		assert!(!fields.contains_key("error"));
+	}
+
+	#[tokio::test]
+	async fn test_failing_tool_execution_span() {
+		use crate::streaming::StreamingPrompt;
+
+		let fields = RecordedFields::default();
+		let _guard =
+			tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+		let blob = "QUJD".repeat(100);
+		let model = MockCompletionModel::with_responses([OneOrMany::one(
+			AssistantContent::tool_call("call-0", "missing", json!({ "image": blob })),
+		)]);
+		let agent = AgentBuilder::new(model).tool(Sleep).build();
+		let mut stream = agent.stream_prompt("Look").await;
+		while stream.next().await.is_some() {}
+
+		let fields = fields.get();
+		assert_eq!(fields["gen_ai.tool.name"], "missing");
+		assert_eq!(fields["gen_ai.tool.call.id"], "call-0");
+		assert_eq!(
+			fields["gen_ai.tool.call.arguments"],
+			r#"{"image":"[300 bytes]"}"#
+		);
+		assert_eq!(fields["error"], "true");
+		assert!(
+			fields["error.message"].contains("missing"),
+			"{}",
+			fields["error.message"]
+		);
+		assert_eq!(
+			fields["gen_ai.tool.call.result.length"],
+			fields["error.message"].len().to_string()
+		);
+		assert!(fields.contains_key("duration_ms"));
+	}
+
+	#[tokio::test]
+	async fn test_panicking_tool_does_not_affect_other_calls() {
+		let model = MockCompletionModel::with_responses([sleep_calls(&[20, 0, 10])]);
+		let agent = AgentBuilder::new(model.clone()).tool(Sleep).build();
+
+		let response = agent
+			.prompt("Sleep")
+			.with_tool_concurrency(3)
+			.await
+			.unwrap();
+		assert_eq!(response, "done");
+
+		let results = tool_results(&model);
+		assert_eq!(results.len(), 3);
+		assert_eq!(results[0].1, "\"slept 20ms\"");
+		assert!(
+			results[1].1.contains("panicked: cannot sleep for 0ms"),
+			"{}",
+			results[1].1
+		);
+		assert_eq!(results[2].1, "\"slept 10ms\"");
+	}
+
+	#[tokio::test]
+	async fn test_streaming_tool_concurrency() {
+		use crate::streaming::{StreamedUserContent, StreamingPrompt};
+
+		let model = MockCompletionModel::with_responses([sleep_calls(&[150, 0, 100, 150])]);
+		let agent = AgentBuilder::new(model).tool(Sleep).build();
+
+		let started = std::time::Instant::now();
+		let mut stream = agent
+			.stream_prompt("Sleep")
+			.with_tool_concurrency(4)
+			.await;
+		let mut results = vec![];
+		while let Some(item) = stream.next().await {
+			if let Ok(streaming::MultiTurnStreamItem::StreamUserItem(
+				StreamedUserContent::ToolResult { tool_result, .. },
+			)) = item
+			{
+				let ToolResultContent::Text(text) = tool_result.content.first() else {
+					panic!("expected a text tool result");
+				};
+				results.push((tool_result.id, text.text));
+			}
+		}
+
+		// Run one after another, the calls would take 400ms
+		assert!(started.elapsed() < Duration::from_millis(350));
+		let ids: Vec<_> = results.iter().map(|(id, _)| id.as_str()).collect();
+		assert_eq!(ids, ["call-0", "call-1", "call-2", "call-3"]);
+		assert_eq!(results[0].1, "\"slept 150ms\"");
+		assert!(
+			results[1].1.contains("panicked: cannot sleep for 0ms"),
+			"{}",
+			results[1].1
+		);
+		assert_eq!(results[2].1, "\"slept 100ms\"");
+	}
+
+	#[tokio::test]
+	async fn test_unknown_tool_policies() {
+		use crate::agent::UnknownToolPolicy;
+		use crate::streaming::StreamingPrompt;
+		use crate::tool::ToolSetError;
+
+		let agent = |policy| {
+			let model = MockCompletionModel::with_responses([OneOrMany::one(
+				AssistantContent::tool_call("call-0", "missing", json!({})),
+			)]);
+			let agent = AgentBuilder::new(model.clone())
+				.tool(Sleep)
+				.unknown_tool_policy(policy)
+				.build();
+			(model, agent)
+		};
+
+		let (model, default_agent) = agent(UnknownToolPolicy::default());
+		assert_eq!(default_agent.prompt("Sleep").await.unwrap(), "done");
+		assert_eq!(
+			tool_results(&model),
+			[(
+				"call-0".to_string(),
+				"Unknown tool `missing`, available tools: [sleep]".to_string()
+			)]
+		);
+
+		let (model, ignoring_agent) = agent(UnknownToolPolicy::Ignore);
+		assert_eq!(ignoring_agent.prompt("Sleep").await.unwrap(), "done");
+		assert_eq!(
+			tool_results(&model),
+			[("call-0".to_string(), String::new())]
+		);
+
+		let (model, failing_agent) = agent(UnknownToolPolicy::Error);
+		let err = failing_agent.prompt("Sleep").await.unwrap_err();
+		assert!(
+			matches!(&err, PromptError::ToolError(ToolSetError::ToolNotFoundError(name)) if name == "missing"),
+			"{err}"
+		);
+		assert_eq!(model.requests().len(), 1);
+
+		let (_, failing_agent) = agent(UnknownToolPolicy::Error);
+		let mut stream = failing_agent.stream_prompt("Sleep").await;
+		let mut errors = vec![];
+		while let Some(item) = stream.next().await {
+			if let Err(e) = item {
+				errors.push(e.to_string());
+			}
+		}
+		assert!(
+			errors
+				.iter()
+				.any(|e| e.contains("ToolNotFoundError: missing")),
+			"{errors:?}"
+		);
+	}
+
+	/// Asserts that `err` is a max turns error whose history holds the original prompt followed
+	/// by `round_trips` tool calls and their results.
+	fn assert_max_turns_history(err: &PromptError, round_trips: usize) {
+		let PromptError::MaxTurnsError {
+			max_turns,
+			chat_history,
+			prompt,
+		} = err
+		else {
+			panic!("expected a max turns error, got {err:?}");
+		};
+
+		assert_eq!(*max_turns, 1);
+		assert_eq!(chat_history.len(), 1 + 2 * round_trips);
+		assert_eq!(chat_history.first(), Some(&Message::user("Loop")));
+		assert_eq!(chat_history.last(), Some(prompt.as_ref()));
+		for pair in chat_history[1..].chunks(2) {
+			assert!(matches!(pair[0], Message::Assistant { .. }));
+			assert!(matches!(pair[1], Message::User { .. }));
+		}
+	}
+
+	#[tokio::test]
+	async fn test_max_turns_stops_tool_loop() {
+		let model = MockCompletionModel::with_responses((0..10).map(|_| sleep_calls(&[1])));
+		let agent = AgentBuilder::new(model.clone()).tool(Sleep).build();
+
+		let err = agent.prompt("Loop").max_turns(1).await.unwrap_err();
+
+		assert_eq!(model.requests().len(), 3);
+		assert_max_turns_history(&err, 3);
+	}
+
+	#[tokio::test]
+	async fn test_streaming_max_turns_stops_tool_loop() {
+		use crate::agent::prompt_request::streaming::StreamingError;
+		use crate::streaming::StreamingPrompt;
+
+		let model = MockCompletionModel::with_responses((0..10).map(|_| sleep_calls(&[1])));
+		let agent = AgentBuilder::new(model.clone())
+			.tool(Sleep)
+			.default_max_turns(1)
+			.build();
+
+		let mut stream = agent.stream_prompt("Loop").await;
+		let mut last = None;
+		while let Some(item) = stream.next().await {
+			last = Some(item);
+		}
+
+		let Some(Err(StreamingError::Prompt(err))) = last else {
+			panic!("expected the stream to end with a prompt error");
+		};
+		assert_eq!(model.requests().len(), 3);
+		assert_max_turns_history(&err, 3);
+	}
+
+	#[tokio::test]
+	async fn test_stream_events_across_tool_turn() {
+		use crate::agent::AgentStreamEvent;
+		use crate::message::Reasoning;
+		use crate::streaming::StreamingPrompt;
+
+		let tool_turn = OneOrMany::many([
+			AssistantContent::Reasoning(Reasoning::new("I should sleep first")),
+			AssistantContent::tool_call("call-0", "sleep", json!({ "millis": 1 })),
+		])
+		.unwrap();
+		let answer = OneOrMany::many([
+			AssistantContent::text("Slept "),
+			AssistantContent::text("well"),
+		])
+		.unwrap();
+
+		let model = MockCompletionModel::with_responses([tool_turn, answer]);
+		let agent = AgentBuilder::new(model.clone())
+			.tool(Sleep)
+			.default_max_turns(1)
+			.build();
+
+		let events = agent
+			.stream_prompt("Sleep")
+			.events()
+			.collect::<Vec<_>>()
+			.await
+			.into_iter()
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+
+		let [
+			AgentStreamEvent::Reasoning { reasoning },
+			AgentStreamEvent::ToolCallStarted {
+				tool_call,
+				internal_call_id: started_id,
+			},
+			AgentStreamEvent::ToolCallCompleted {
+				tool_result,
+				internal_call_id: completed_id,
+			},
+			AgentStreamEvent::TextDelta { text: first },
+			AgentStreamEvent::TextDelta { text: second },
+			AgentStreamEvent::Final(response),
+		] = events.as_slice()
+		else {
+			panic!("unexpected events: {events:#?}");
+		};
+
+		assert_eq!(reasoning, "I should sleep first");
+		assert_eq!(tool_call.function.name, "sleep");
+		assert_eq!(started_id, completed_id);
+		assert_eq!(tool_result.id, "call-0");
+		assert_eq!(
+			tool_result.content.first(),
+			ToolResultContent::text("\"slept 1ms\"")
+		);
+		assert_eq!(format!("{first}{second}"), "Slept well");
+		assert_eq!(response.response(), "Slept well");
+		assert_eq!(model.requests().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_stream_events_hold_back_text_of_tool_turns() {
+		use crate::agent::AgentStreamEvent;
+		use crate::streaming::StreamingPrompt;
+
+		let tool_turn = OneOrMany::many([
+			AssistantContent::text("Let me sleep on it"),
+			AssistantContent::tool_call("call-0", "sleep", json!({ "millis": 1 })),
+		])
+		.unwrap();
+		let model = MockCompletionModel::with_responses([
+			tool_turn,
+			OneOrMany::one(AssistantContent::text("Rested")),
+		]);
+		let agent = AgentBuilder::new(model).tool(Sleep).build();
+
+		let events = agent
+			.stream_prompt("Sleep")
+			.events()
+			.collect::<Vec<_>>()
+			.await
+			.into_iter()
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+
+		let texts: Vec<_> = events
+			.iter()
+			.filter_map(|event| match event {
+				AgentStreamEvent::TextDelta { text } => Some(text.as_str()),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(texts, ["Rested"]);
+		assert!(matches!(
+			events.as_slice(),
+			[
+				AgentStreamEvent::ToolCallStarted { .. },
+				AgentStreamEvent::ToolCallCompleted { .. },
+				AgentStreamEvent::TextDelta { .. },
+				AgentStreamEvent::Final(_),
+			]
+		));
+	}
+
+	#[tokio::test]
+	async fn test_multi_part_prompt_with_history() {
+		let answer = OneOrMany::many([
+			AssistantContent::reasoning("Whiskers, pointy ears"),
+			AssistantContent::text("A cat."),
+		])
+		.unwrap();
+		let model = MockCompletionModel::with_responses([answer.clone()]);
+		let agent = AgentBuilder::new(model.clone()).build();
+
+		let prompt = Message::User {
+			content: OneOrMany::many([
+				UserContent::image_url(
+					"https://example.com/cat.png",
+					Some(ImageMediaType::PNG),
+					None,
+				),
+				UserContent::text("What is this?"),
+			])
+			.unwrap(),
+		};
+		let mut history = vec![Message::user("Hi"), Message::assistant("Hello!")];
+
+		let response = agent
+			.prompt_with_history(prompt.clone(), &mut history)
+			.extended_details()
+			.await
+			.unwrap();
+
+		assert_eq!(response.output, "A cat.");
+		let request = model.requests().pop().unwrap();
+		assert_eq!(request.chat_history.len(), 3);
+		assert_eq!(request.chat_history.last(), prompt);
+
+		// The assistant message is returned whole, reasoning included
+		let message = Message::Assistant {
+			id: None,
+			content: answer,
+		};
+		assert_eq!(response.message, Some(message.clone()));
+		assert_eq!(
+			history,
+			[
+				Message::user("Hi"),
+				Message::assistant("Hello!"),
+				prompt,
+				message
+			]
+		);
+	}
+}
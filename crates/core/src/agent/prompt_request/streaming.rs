@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::pin::Pin;
 use std::sync::Arc;
 
@@ -7,15 +8,21 @@ use tokio::sync::RwLock;
 use tracing::info_span;
 use tracing_futures::Instrument;
 
-use super::ToolCallHookAction;
+use super::{
+	DEFAULT_MAX_TURNS, PromptOptions, ToolCallHookAction, execute_tool_span, merge_context_documents,
+	record_tool_call, tool_call_content,
+};
 use crate::agent::Agent;
+use crate::agent::moderation::moderate_prompt;
 use crate::agent::prompt_request::HookAction;
 use crate::agent::prompt_request::hooks::PromptHook;
-use crate::completion::{CompletionError, CompletionModel, GetTokenUsage, PromptError};
+use crate::completion::metadata::Stopwatch;
+use crate::completion::{CompletionError, CompletionModel, Document, GetTokenUsage, PromptError};
 use crate::message::{
-	AssistantContent, Message, Reasoning, Text, ToolResult, ToolResultContent, UserContent,
+	AssistantContent, Message, Reasoning, Text, ToolCall, ToolResult, ToolResultContent,
+	UserContent,
 };
-use crate::streaming::{StreamedAssistantContent, StreamedUserContent, StreamingCompletion};
+use crate::streaming::{StreamedAssistantContent, StreamedUserContent};
 use crate::tool::ToolSetError;
 use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend};
 use crate::{OneOrMany, json_utils};
@@ -28,6 +35,13 @@ pub type StreamingResult<R> =
 pub type StreamingResult<R> =
 	Pin<Box<dyn Stream<Item = Result<MultiTurnStreamItem<R>, StreamingError>>>>;
 
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub type AgentEventStream =
+	Pin<Box<dyn Stream<Item = Result<AgentStreamEvent, StreamingError>> + Send>>;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub type AgentEventStream = Pin<Box<dyn Stream<Item = Result<AgentStreamEvent, StreamingError>>>>;
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "type", rename_all = "camelCase")]
 #[non_exhaustive]
@@ -45,6 +59,10 @@ pub enum MultiTurnStreamItem<R> {
 pub struct FinalResponse {
 	response: String,
 	aggregated_usage: crate::completion::Usage,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	context_documents: Vec<Document>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	flagged_categories: Vec<String>,
 }
 
 impl FinalResponse {
@@ -52,6 +70,8 @@ impl FinalResponse {
 		Self {
 			response: String::new(),
 			aggregated_usage: crate::completion::Usage::new(),
+			context_documents: vec![],
+			flagged_categories: vec![],
 		}
 	}
 
@@ -62,6 +82,17 @@ impl FinalResponse {
 	pub fn usage(&self) -> crate::completion::Usage {
 		self.aggregated_usage
 	}
+
+	/// Documents retrieved from the agent's context providers, deduplicated by id
+	pub fn context_documents(&self) -> &[Document] {
+		&self.context_documents
+	}
+
+	/// Categories the prompt was flagged for by the agent's moderator, when flagged prompts are
+	/// only annotated
+	pub fn flagged_categories(&self) -> &[String] {
+		&self.flagged_categories
+	}
 }
 
 impl<R> MultiTurnStreamItem<R> {
@@ -73,10 +104,35 @@ impl<R> MultiTurnStreamItem<R> {
 		Self::FinalResponse(FinalResponse {
 			response: response.to_string(),
 			aggregated_usage,
+			context_documents: vec![],
+			flagged_categories: vec![],
 		})
 	}
 }
 
+/// A progress event of a streamed agent run, see [StreamingPromptRequest::events].
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum AgentStreamEvent {
+	/// A chunk of the text written by the model.
+	TextDelta { text: String },
+	/// A chunk of the reasoning of the model.
+	Reasoning { reasoning: String },
+	/// The model called a tool, which is being executed.
+	ToolCallStarted {
+		tool_call: ToolCall,
+		internal_call_id: String,
+	},
+	/// A tool finished executing, its result being sent back to the model.
+	ToolCallCompleted {
+		tool_result: ToolResult,
+		internal_call_id: String,
+	},
+	/// The run finished with the final answer.
+	Final(FinalResponse),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum StreamingError {
 	#[error("CompletionError: {0}")]
@@ -90,11 +146,10 @@ pub enum StreamingError {
 /// A builder for creating prompt requests with customizable options.
 /// Uses generics to track which options have been set during the build process.
 ///
-/// If you expect to continuously call tools, you will want to ensure you use the `.multi_turn()`
-/// argument to add more turns as by default, it is 0 (meaning only 1 tool round-trip). Otherwise,
-/// attempting to await (which will send the prompt request) can potentially return
-/// [`crate::completion::request::PromptError::MaxTurnsError`] if the agent decides to call tools
-/// back to back.
+/// Each tool round-trip after the first one counts as a turn, so `.multi_turn(0)` still lets the
+/// agent call tools once before answering ([DEFAULT_MAX_TURNS] turns unless the agent sets its own
+/// default). If the agent is still calling tools when the limit is reached, the stream ends with a
+/// [`crate::completion::request::PromptError::MaxTurnsError`] holding the history so far.
 pub struct StreamingPromptRequest<M, P>
 where
 	M: CompletionModel,
@@ -111,6 +166,10 @@ where
 	agent: Arc<Agent<M>>,
 	/// Optional per-request hook for events
 	hook: Option<P>,
+	/// How many tools should be executed at the same time (1 by default).
+	concurrency: usize,
+	/// Model parameters overriding the agent's
+	options: PromptOptions,
 }
 
 impl<M, P> StreamingPromptRequest<M, P>
@@ -124,9 +183,11 @@ where
 		Self {
 			prompt: prompt.into(),
 			chat_history: None,
-			max_turns: agent.default_max_turns.unwrap_or_default(),
-			agent,
+			max_turns: agent.default_max_turns.unwrap_or(DEFAULT_MAX_TURNS),
 			hook: None,
+			concurrency: agent.tool_concurrency.unwrap_or(1),
+			agent,
+			options: PromptOptions::default(),
 		}
 	}
 
@@ -137,6 +198,21 @@ where
 		self
 	}
 
+	/// Add concurrency to the prompt request.
+	/// This will cause the agent to execute tools concurrently, overriding
+	/// [`AgentBuilder::tool_concurrency`](crate::agent::AgentBuilder::tool_concurrency).
+	/// Tool results keep the order of the tool calls regardless of which finishes first.
+	pub fn with_tool_concurrency(mut self, concurrency: usize) -> Self {
+		self.concurrency = concurrency;
+		self
+	}
+
+	/// Override the agent's model parameters for this prompt only
+	pub fn with_options(mut self, options: PromptOptions) -> Self {
+		self.options = options;
+		self
+	}
+
 	/// Add chat history to the prompt request
 	pub fn with_history(mut self, history: Vec<Message>) -> Self {
 		self.chat_history = Some(history);
@@ -154,9 +230,94 @@ where
 			max_turns: self.max_turns,
 			agent: self.agent,
 			hook: Some(hook),
+			concurrency: self.concurrency,
+			options: self.options,
 		}
 	}
 
+	/// Streams the run as [AgentStreamEvent]s: the text and reasoning of the model, and the tool
+	/// calls as they are executed, across every turn until the final answer.
+	///
+	/// Unlike the items of the [StreamingResult], the raw tool calls and the provider's response
+	/// of each turn aren't surfaced. Only the text of the final turn is streamed: text a model
+	/// writes before calling tools in the same turn (e.g. "Let me look that up") can't be told
+	/// apart from an answer until the tool calls arrive, so the text of each turn is held back
+	/// until the turn ends and dropped if it called tools.
+	pub fn events(self) -> AgentEventStream {
+		let mut items = futures::stream::once(self.into_future()).flatten();
+
+		Box::pin(async_stream::stream! {
+			// Providers streaming reasoning deltas also send the whole reasoning at the end
+			let mut streamed_reasoning = false;
+			// The text deltas of the current turn, until it's known not to call tools
+			let mut turn_text: Vec<String> = Vec::new();
+
+			while let Some(item) = items.next().await {
+				let item = match item {
+					Ok(item) => item,
+					Err(e) => {
+						yield Err(e);
+						continue;
+					}
+				};
+
+				let event = match item {
+					MultiTurnStreamItem::StreamAssistantItem(content) => match content {
+						StreamedAssistantContent::Text(Text { text }) => {
+							turn_text.push(text);
+							continue;
+						}
+						StreamedAssistantContent::ReasoningDelta { reasoning, .. } => {
+							streamed_reasoning = true;
+							AgentStreamEvent::Reasoning { reasoning }
+						}
+						StreamedAssistantContent::Reasoning(Reasoning { reasoning, .. }) => {
+							if std::mem::take(&mut streamed_reasoning) {
+								continue;
+							}
+							AgentStreamEvent::Reasoning {
+								reasoning: reasoning.join("\n"),
+							}
+						}
+						StreamedAssistantContent::ToolCall {
+							tool_call,
+							internal_call_id,
+						} => {
+							turn_text.clear();
+							AgentStreamEvent::ToolCallStarted {
+								tool_call,
+								internal_call_id,
+							}
+						}
+						StreamedAssistantContent::ToolCallDelta { .. } => continue,
+						// The turn ended, and the text left is the answer if it called no tools
+						StreamedAssistantContent::Final(_) => {
+							for text in turn_text.drain(..) {
+								yield Ok(AgentStreamEvent::TextDelta { text });
+							}
+							continue;
+						}
+					},
+					MultiTurnStreamItem::StreamUserItem(StreamedUserContent::ToolResult {
+						tool_result,
+						internal_call_id,
+					}) => AgentStreamEvent::ToolCallCompleted {
+						tool_result,
+						internal_call_id,
+					},
+					MultiTurnStreamItem::FinalResponse(response) => {
+						for text in turn_text.drain(..) {
+							yield Ok(AgentStreamEvent::TextDelta { text });
+						}
+						AgentStreamEvent::Final(response)
+					}
+				};
+
+				yield Ok(event);
+			}
+		})
+	}
+
 	async fn send(self) -> StreamingResult<M::StreamingResponse> {
 		let agent_span = if tracing::Span::current().is_disabled() {
 			info_span!(
@@ -187,13 +348,13 @@ where
 		};
 
 		let mut current_max_turns = 0;
-		let mut last_prompt_error = String::new();
 
 		let mut last_text_response = String::new();
 		let mut is_text_response = false;
 		let mut max_turns_reached = false;
 
 		let mut aggregated_usage = crate::completion::Usage::new();
+		let mut context_documents = vec![];
 
 		// NOTE: We use .instrument(agent_span) instead of span.enter() to avoid
 		// span context leaking to other concurrent tasks. Using span.enter() inside
@@ -203,11 +364,23 @@ where
 		// See also: https://github.com/rust-lang/rust-clippy/issues/8722
 		let stream = async_stream::stream! {
 			let mut current_prompt = prompt.clone();
-			let mut did_call_tool = false;
+
+			let flagged_categories = match moderate_prompt(
+				agent.moderator.as_deref(),
+				agent.moderation_policy,
+				&prompt,
+			).await {
+				Ok(categories) => categories,
+				Err(e) => {
+					yield Err(StreamingError::Prompt(Box::new(e)));
+					return;
+				}
+			};
 
 			'outer: loop {
 				if current_max_turns > self.max_turns + 1 {
-					last_prompt_error = current_prompt.rag_text().unwrap_or_default();
+					// Keep the unanswered prompt in the history so the caller can resume from it
+					chat_history.write().await.push(current_prompt.clone());
 					max_turns_reached = true;
 					break;
 				}
@@ -246,21 +419,27 @@ where
 					gen_ai.response.model = tracing::field::Empty,
 					gen_ai.usage.output_tokens = tracing::field::Empty,
 					gen_ai.usage.input_tokens = tracing::field::Empty,
+					gen_ai.usage.cost = tracing::field::Empty,
+					gen_ai.usage.cached_cost = tracing::field::Empty,
 					gen_ai.input.messages = tracing::field::Empty,
 					gen_ai.output.messages = tracing::field::Empty,
 				);
 
+				let (request, documents) = agent
+					.completion_with_context(current_prompt.clone(), (*chat_history.read().await).clone(), &self.options)
+					.await?;
+				merge_context_documents(&mut context_documents, documents);
+				let advertised_tools = request.tool_names();
+
 				let mut stream = tracing::Instrument::instrument(
-					agent
-					.stream_completion(current_prompt.clone(), (*chat_history.read().await).clone())
-					.await?
-					.stream(), chat_stream_span
+					request.stream(), chat_stream_span
 				)
 
 				.await?;
 
 				chat_history.write().await.push(current_prompt.clone());
 
+				let mut pending_tool_calls = vec![];
 				let mut tool_calls = vec![];
 				let mut tool_results = vec![];
 				let mut accumulated_reasoning: Option<clankers::message::Reasoning> = None;
@@ -281,98 +460,10 @@ where
 								}
 
 							yield Ok(MultiTurnStreamItem::stream_item(StreamedAssistantContent::Text(text)));
-							did_call_tool = false;
 						},
 						Ok(StreamedAssistantContent::ToolCall { tool_call, internal_call_id }) => {
-							let tool_span = info_span!(
-								parent: tracing::Span::current(),
-								"execute_tool",
-								gen_ai.operation.name = "execute_tool",
-								gen_ai.tool.type = "function",
-								gen_ai.tool.name = tracing::field::Empty,
-								gen_ai.tool.call.id = tracing::field::Empty,
-								gen_ai.tool.call.arguments = tracing::field::Empty,
-								gen_ai.tool.call.result = tracing::field::Empty
-							);
-
 							yield Ok(MultiTurnStreamItem::stream_item(StreamedAssistantContent::ToolCall { tool_call: tool_call.clone(), internal_call_id: internal_call_id.clone() }));
-
-							let tc_result = async {
-								let tool_span = tracing::Span::current();
-								let tool_args = json_utils::value_to_json_string(&tool_call.function.arguments);
-								if let Some(ref hook) = self.hook {
-									let action = hook
-										.on_tool_call(&tool_call.function.name, tool_call.call_id.clone(), &internal_call_id, &tool_args)
-										.await;
-
-									if let ToolCallHookAction::Terminate { reason } = action {
-										return Err(StreamingError::Prompt(PromptError::prompt_cancelled(chat_history.read().await.to_vec(),
-											reason
-										).into()));
-									}
-
-									if let ToolCallHookAction::Skip { reason } = action {
-										// Tool execution rejected, return rejection message as tool result
-										tracing::info!(
-											tool_name = tool_call.function.name.as_str(),
-											reason = reason,
-											"Tool call rejected"
-										);
-										let tool_call_msg = AssistantContent::ToolCall(tool_call.clone());
-										tool_calls.push(tool_call_msg);
-										tool_results.push((tool_call.id.clone(), tool_call.call_id.clone(), reason.clone()));
-										did_call_tool = true;
-										return Ok(reason);
-									}
-								}
-
-								tool_span.record("gen_ai.tool.name", &tool_call.function.name);
-								tool_span.record("gen_ai.tool.call.arguments", &tool_args);
-
-								let tool_result = match
-								agent.tool_server_handle.call_tool(&tool_call.function.name, &tool_args).await {
-									Ok(thing) => thing,
-									Err(e) => {
-										tracing::warn!("Error while calling tool: {e}");
-										e.to_string()
-									}
-								};
-
-								tool_span.record("gen_ai.tool.call.result", &tool_result);
-
-								if let Some(ref hook) = self.hook &&
-									let HookAction::Terminate { reason } =
-									hook.on_tool_result(
-										&tool_call.function.name,
-										tool_call.call_id.clone(),
-										&internal_call_id,
-										&tool_args,
-										&tool_result.to_string()
-									)
-									.await {
-										return Err(StreamingError::Prompt(PromptError::prompt_cancelled(chat_history.read().await.to_vec(),
-											reason
-										).into()));
-									}
-
-								let tool_call_msg = AssistantContent::ToolCall(tool_call.clone());
-
-								tool_calls.push(tool_call_msg);
-								tool_results.push((tool_call.id.clone(), tool_call.call_id.clone(), tool_result.clone()));
-
-								did_call_tool = true;
-								Ok(tool_result)
-							}.instrument(tool_span).await;
-
-							match tc_result {
-								Ok(text) => {
-									let tr = ToolResult { id: tool_call.id, call_id: tool_call.call_id, content: ToolResultContent::from_tool_output(text) };
-									yield Ok(MultiTurnStreamItem::StreamUserItem(StreamedUserContent::ToolResult{ tool_result: tr, internal_call_id }));
-								}
-								Err(e) => {
-									yield Err(e);
-								}
-							}
+							pending_tool_calls.push((tool_call, internal_call_id));
 						},
 						Ok(StreamedAssistantContent::ToolCallDelta { id, internal_call_id, content }) => {
 							if let Some(ref hook) = self.hook {
@@ -403,11 +494,9 @@ where
 								});
 							}
 							yield Ok(MultiTurnStreamItem::stream_item(StreamedAssistantContent::Reasoning(clankers::message::Reasoning { reasoning, id, signature })));
-							did_call_tool = false;
 						},
 						Ok(StreamedAssistantContent::ReasoningDelta { reasoning, id }) => {
 							yield Ok(MultiTurnStreamItem::stream_item(StreamedAssistantContent::ReasoningDelta { reasoning, id }));
-							did_call_tool = false;
 						},
 						Ok(StreamedAssistantContent::Final(final_resp)) => {
 							if let Some(usage) = final_resp.token_usage() { aggregated_usage += usage; };
@@ -431,6 +520,45 @@ where
 					}
 				}
 
+				// Execute the tool calls of the turn, `concurrency` at a time. Their results are
+				// streamed in the order of the calls, whichever finishes first.
+				let hook = &self.hook;
+				let chat_history_ref = &chat_history;
+				let advertised_tools = &advertised_tools;
+				let agent_ref = agent.as_ref();
+				let mut executions = futures::stream::iter(pending_tool_calls)
+					.map(|(tool_call, internal_call_id)| {
+						let tool_span = execute_tool_span();
+						async move {
+							let content = execute_tool_call(
+								agent_ref,
+								hook.as_ref(),
+								advertised_tools,
+								chat_history_ref,
+								&tool_call,
+								&internal_call_id,
+							).await;
+							(tool_call, internal_call_id, content)
+						}
+						.instrument(tool_span)
+					})
+					.buffered(self.concurrency.max(1));
+
+				while let Some((tool_call, internal_call_id, content)) = executions.next().await {
+					match content {
+						Ok(content) => {
+							tool_calls.push(AssistantContent::ToolCall(tool_call.clone()));
+							tool_results.push((tool_call.id.clone(), tool_call.call_id.clone(), content.clone()));
+							let tr = ToolResult { id: tool_call.id, call_id: tool_call.call_id, content };
+							yield Ok(MultiTurnStreamItem::StreamUserItem(StreamedUserContent::ToolResult{ tool_result: tr, internal_call_id }));
+						}
+						Err(e) => {
+							yield Err(e);
+						}
+					}
+				}
+				let did_call_tool = !tool_calls.is_empty();
+
 				// Add reasoning and tool calls to chat history.
 				// OpenAI Responses API requires reasoning items to precede function_call items.
 				if !tool_calls.is_empty() || accumulated_reasoning.is_some() {
@@ -452,20 +580,20 @@ where
 				}
 
 				// Add tool results to chat history
-				for (id, call_id, tool_result) in tool_results {
+				for (id, call_id, content) in tool_results {
 					if let Some(call_id) = call_id {
 						chat_history.write().await.push(Message::User {
 							content: OneOrMany::one(UserContent::tool_result_with_call_id(
 								&id,
 								call_id.clone(),
-								OneOrMany::one(ToolResultContent::text(&tool_result)),
+								content,
 							)),
 						});
 					} else {
 						chat_history.write().await.push(Message::User {
 							content: OneOrMany::one(UserContent::tool_result(
 								&id,
-								OneOrMany::one(ToolResultContent::text(&tool_result)),
+								content,
 							)),
 						});
 					}
@@ -481,8 +609,14 @@ where
 					let current_span = tracing::Span::current();
 					current_span.record("gen_ai.usage.input_tokens", aggregated_usage.input_tokens);
 					current_span.record("gen_ai.usage.output_tokens", aggregated_usage.output_tokens);
-					tracing::info!("Agent multi-turn stream finished");
-					yield Ok(MultiTurnStreamItem::final_response(&last_text_response, aggregated_usage));
+					tracing::info!("Agent multi-turn stream finished, tokens: {aggregated_usage}");
+					let final_response = FinalResponse {
+						response: last_text_response.clone(),
+						aggregated_usage,
+						context_documents: context_documents.clone(),
+						flagged_categories: flagged_categories.clone(),
+					};
+					yield Ok(MultiTurnStreamItem::FinalResponse(final_response));
 					break;
 				}
 			}
@@ -491,7 +625,7 @@ where
 				yield Err(Box::new(PromptError::MaxTurnsError {
 					max_turns: self.max_turns,
 					chat_history: Box::new((*chat_history.read().await).clone()),
-					prompt: Box::new(last_prompt_error.clone().into()),
+					prompt: Box::new(current_prompt.clone()),
 				}).into());
 			}
 		};
@@ -500,6 +634,76 @@ where
 	}
 }
 
+/// Runs a tool call of a streamed turn with the hooks around it, returning the content sent back
+/// to the model. A tool call skipped by the hook returns the reason as its content.
+async fn execute_tool_call<M, P>(
+	agent: &Agent<M>,
+	hook: Option<&P>,
+	advertised_tools: &HashSet<String>,
+	chat_history: &RwLock<Vec<Message>>,
+	tool_call: &ToolCall,
+	internal_call_id: &str,
+) -> Result<OneOrMany<ToolResultContent>, StreamingError>
+where
+	M: CompletionModel,
+	P: PromptHook<M>,
+{
+	let tool_span = tracing::Span::current();
+	let tool_args = json_utils::value_to_json_string(&tool_call.function.arguments);
+	record_tool_call(&tool_span, tool_call, &tool_args);
+	if let Some(hook) = hook {
+		let action = hook
+			.on_tool_call(
+				&tool_call.function.name,
+				tool_call.call_id.clone(),
+				internal_call_id,
+				&tool_args,
+			)
+			.await;
+
+		if let ToolCallHookAction::Terminate { reason } = action {
+			return Err(StreamingError::Prompt(
+				PromptError::prompt_cancelled(chat_history.read().await.to_vec(), reason).into(),
+			));
+		}
+
+		if let ToolCallHookAction::Skip { reason } = action {
+			// Tool execution rejected, return rejection message as tool result
+			tracing::info!(
+				tool_name = tool_call.function.name.as_str(),
+				reason = reason,
+				"Tool call rejected"
+			);
+			return Ok(OneOrMany::one(ToolResultContent::text(&reason)));
+		}
+	}
+
+	let stopwatch = Stopwatch::start();
+	let result = agent
+		.call_tool(advertised_tools, &tool_call.function.name, &tool_args)
+		.await
+		.map_err(|e| StreamingError::Prompt(e.into()))?;
+	let (content, tool_result) = tool_call_content(&tool_span, stopwatch, result);
+
+	if let Some(hook) = hook
+		&& let HookAction::Terminate { reason } = hook
+			.on_tool_result(
+				&tool_call.function.name,
+				tool_call.call_id.clone(),
+				internal_call_id,
+				&tool_args,
+				&tool_result,
+			)
+			.await
+	{
+		return Err(StreamingError::Prompt(
+			PromptError::prompt_cancelled(chat_history.read().await.to_vec(), reason).into(),
+		));
+	}
+
+	Ok(content)
+}
+
 impl<M, P> IntoFuture for StreamingPromptRequest<M, P>
 where
 	M: CompletionModel + 'static,
@@ -0,0 +1,379 @@
+//! Tool selection, which narrows the tools advertised to the model down to the ones relevant to
+//! the prompt. Agents advertise all their tools unless a [ToolSelector] is set.
+//!
+//! # Example
+//! ```
+//! use clankers::{
+//!     agent::EmbeddingToolSelector,
+//!     completion::Prompt,
+//!     providers::openai,
+//! };
+//!
+//! let client = openai::Client::from_env();
+//! let embedding_model = client.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+//!
+//! let agent = client
+//!     .agent("gpt-4o-mini")
+//!     .tool(Add)
+//!     .tool(Subtract)
+//!     .tool(Multiply)
+//!     .tool_selector(EmbeddingToolSelector::new(embedding_model, 2))
+//!     .build();
+//!
+//! let response = agent.prompt("What is 2 + 2?").await?;
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::completion::ToolDefinition;
+use crate::embeddings::distance::VectorDistance;
+use crate::embeddings::{Embedding, EmbeddingError, EmbeddingModel};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
+
+/// Trait for selecting the tools advertised to the model for a prompt.
+pub trait ToolSelector: WasmCompatSend + WasmCompatSync {
+	/// The error type of the selector.
+	type Error: std::error::Error + WasmCompatSend + WasmCompatSync + 'static;
+
+	/// Returns the tools to advertise for `prompt`, picked from `tools`.
+	fn select(
+		&self,
+		prompt: &str,
+		tools: Vec<ToolDefinition>,
+	) -> impl Future<Output = Result<Vec<ToolDefinition>, Self::Error>> + WasmCompatSend;
+}
+
+/// Wrapper trait to allow for dynamic dispatch of tool selectors
+pub trait ToolSelectorDyn: WasmCompatSend + WasmCompatSync {
+	fn select<'a>(
+		&'a self,
+		prompt: &'a str,
+		tools: Vec<ToolDefinition>,
+	) -> WasmBoxedFuture<'a, Result<Vec<ToolDefinition>, ToolSelectionError>>;
+}
+
+impl<T: ToolSelector> ToolSelectorDyn for T {
+	fn select<'a>(
+		&'a self,
+		prompt: &'a str,
+		tools: Vec<ToolDefinition>,
+	) -> WasmBoxedFuture<'a, Result<Vec<ToolDefinition>, ToolSelectionError>> {
+		Box::pin(async move {
+			<Self as ToolSelector>::select(self, prompt, tools)
+				.await
+				.map_err(|e| ToolSelectionError(Box::new(e)))
+		})
+	}
+}
+
+/// Error returned by a [ToolSelector]
+#[derive(Debug, thiserror::Error)]
+#[error("ToolSelectionError: {0}")]
+pub struct ToolSelectionError(
+	#[cfg(not(target_family = "wasm"))]
+	#[source]
+	Box<dyn std::error::Error + Send + Sync>,
+	#[cfg(target_family = "wasm")]
+	#[source]
+	Box<dyn std::error::Error>,
+);
+
+/// Selects the `top_k` tools whose name and description are the most similar to the prompt, by
+/// cosine similarity of their embeddings.
+///
+/// Tool embeddings are computed once and kept for later prompts, keyed by name and description.
+pub struct EmbeddingToolSelector<M> {
+	model: M,
+	top_k: usize,
+	embeddings: Mutex<HashMap<String, Embedding>>,
+}
+
+impl<M> EmbeddingToolSelector<M>
+where
+	M: EmbeddingModel,
+{
+	pub fn new(model: M, top_k: usize) -> Self {
+		Self {
+			model,
+			top_k,
+			embeddings: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Embeds the tools which haven't been embedded yet.
+	async fn embed_tools(&self, tools: &[ToolDefinition]) -> Result<(), EmbeddingError> {
+		let missing = {
+			let embeddings = self
+				.embeddings
+				.lock()
+				.unwrap_or_else(|err| err.into_inner());
+			tools
+				.iter()
+				.map(tool_text)
+				.filter(|text| !embeddings.contains_key(text))
+				.collect::<Vec<_>>()
+		};
+
+		for texts in missing.chunks(M::MAX_DOCUMENTS.max(1)) {
+			let embeddings = self.model.embed_texts(texts.to_vec()).await?;
+			self.embeddings
+				.lock()
+				.unwrap_or_else(|err| err.into_inner())
+				.extend(texts.iter().cloned().zip(embeddings));
+		}
+
+		Ok(())
+	}
+}
+
+/// The text embedded for a tool.
+fn tool_text(tool: &ToolDefinition) -> String {
+	format!("{}: {}", tool.name, tool.description)
+}
+
+impl<M> ToolSelector for EmbeddingToolSelector<M>
+where
+	M: EmbeddingModel,
+{
+	type Error = EmbeddingError;
+
+	async fn select(
+		&self,
+		prompt: &str,
+		tools: Vec<ToolDefinition>,
+	) -> Result<Vec<ToolDefinition>, Self::Error> {
+		if tools.len() <= self.top_k {
+			return Ok(tools);
+		}
+
+		self.embed_tools(&tools).await?;
+		let prompt = self.model.embed_text(prompt).await?;
+
+		let mut scores = {
+			let embeddings = self
+				.embeddings
+				.lock()
+				.unwrap_or_else(|err| err.into_inner());
+			tools
+				.iter()
+				.enumerate()
+				.map(|(i, tool)| {
+					let score = embeddings
+						.get(&tool_text(tool))
+						.map(|embedding| embedding.cosine_similarity(&prompt, false))
+						// Zero vectors have no similarity
+						.filter(|score| !score.is_nan())
+						.unwrap_or(f64::NEG_INFINITY);
+					(i, score)
+				})
+				.collect::<Vec<_>>()
+		};
+		scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+		let selected = scores
+			.into_iter()
+			.take(self.top_k)
+			.map(|(i, _)| i)
+			.collect::<HashSet<_>>();
+
+		// Keep the registration order of the selected tools
+		Ok(tools
+			.into_iter()
+			.enumerate()
+			.filter(|(i, _)| selected.contains(i))
+			.map(|(_, tool)| tool)
+			.collect())
+	}
+}
+
+/// Selects the tools to advertise for the prompt if the agent has a tool selector, all of them
+/// otherwise.
+pub(crate) async fn select_tools(
+	selector: Option<&dyn ToolSelectorDyn>,
+	prompt: &str,
+	tools: Vec<ToolDefinition>,
+) -> Result<Vec<ToolDefinition>, ToolSelectionError> {
+	let Some(selector) = selector else {
+		return Ok(tools);
+	};
+
+	let available = tools.len();
+	let tools = selector.select(prompt, tools).await?;
+	tracing::debug!(
+		target: "clankers",
+		"Selected {} of {available} tools: {}",
+		tools.len(),
+		tools
+			.iter()
+			.map(|tool| tool.name.as_str())
+			.collect::<Vec<_>>()
+			.join(", ")
+	);
+
+	Ok(tools)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::OneOrMany;
+	use crate::agent::AgentBuilder;
+	use crate::completion::{AssistantContent, Message, Prompt};
+	use crate::message::{ToolResultContent, UserContent};
+	use crate::test_utils::MockCompletionModel;
+	use crate::tool::Tool;
+
+	const TOPICS: [&str; 3] = ["weather", "time", "math"];
+
+	/// Embeds a text as the topics it mentions.
+	#[derive(Clone, Default)]
+	struct TopicEmbeddingModel {
+		calls: std::sync::Arc<Mutex<Vec<Vec<String>>>>,
+	}
+
+	impl EmbeddingModel for TopicEmbeddingModel {
+		const MAX_DOCUMENTS: usize = 16;
+
+		type Client = ();
+
+		fn make(_: &Self::Client, _: impl Into<String>, _: Option<usize>) -> Self {
+			Self::default()
+		}
+
+		fn ndims(&self) -> usize {
+			TOPICS.len()
+		}
+
+		async fn embed_texts(
+			&self,
+			texts: impl IntoIterator<Item = String> + WasmCompatSend,
+		) -> Result<Vec<Embedding>, EmbeddingError> {
+			let texts = texts.into_iter().collect::<Vec<_>>();
+			self.calls.lock().unwrap().push(texts.clone());
+
+			Ok(texts
+				.into_iter()
+				.map(|document| Embedding {
+					vec: TOPICS
+						.iter()
+						.map(|topic| document.contains(topic) as u8 as f64)
+						.collect(),
+					document,
+				})
+				.collect())
+		}
+	}
+
+	/// Answers with its own name.
+	struct Named(&'static str);
+
+	impl Tool for Named {
+		const NAME: &'static str = "named";
+		type Error = std::io::Error;
+		type Args = serde_json::Value;
+		type Output = String;
+
+		fn name(&self) -> String {
+			self.0.to_string()
+		}
+
+		async fn definition(&self, _prompt: String) -> ToolDefinition {
+			ToolDefinition {
+				name: self.0.to_string(),
+				description: format!("Answers questions about the {}", self.0),
+				parameters: json!({ "type": "object", "properties": {} }),
+			}
+		}
+
+		async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+			Ok(self.0.to_string())
+		}
+	}
+
+	fn advertised_tools(model: &MockCompletionModel) -> Vec<Vec<String>> {
+		model
+			.requests()
+			.iter()
+			.map(|request| request.tools.iter().map(|tool| tool.name.clone()).collect())
+			.collect()
+	}
+
+	/// Returns the text of the tool result sent back in the last request.
+	fn tool_result(model: &MockCompletionModel) -> String {
+		let request = model.requests().pop().unwrap();
+		let Message::User { content } = request.chat_history.last() else {
+			panic!("expected the tool result as the last message");
+		};
+		let UserContent::ToolResult(result) = content.first() else {
+			panic!("expected a tool result");
+		};
+		let ToolResultContent::Text(text) = result.content.first() else {
+			panic!("expected a text tool result");
+		};
+		text.text
+	}
+
+	#[tokio::test]
+	async fn test_embedding_tool_selector_advertises_top_k() {
+		let model = MockCompletionModel::default();
+		let embedding_model = TopicEmbeddingModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.tool(Named("weather"))
+			.tool(Named("time"))
+			.tool(Named("math"))
+			.tool_selector(EmbeddingToolSelector::new(embedding_model.clone(), 1))
+			.build();
+
+		agent.prompt("How is the weather today?").await.unwrap();
+		agent.prompt("What time is it?").await.unwrap();
+
+		assert_eq!(advertised_tools(&model), [["weather"], ["time"]]);
+		// The tools are only embedded once
+		let calls = embedding_model.calls.lock().unwrap().clone();
+		assert_eq!(calls.len(), 3);
+		assert_eq!(calls[0].len(), 3);
+	}
+
+	#[tokio::test]
+	async fn test_all_tools_are_advertised_by_default() {
+		let model = MockCompletionModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.tool(Named("weather"))
+			.tool(Named("time"))
+			.build();
+
+		agent.prompt("How is the weather today?").await.unwrap();
+
+		assert_eq!(advertised_tools(&model), [["weather", "time"]]);
+	}
+
+	#[tokio::test]
+	async fn test_unadvertised_tool_calls() {
+		let call_time = || OneOrMany::one(AssistantContent::tool_call("call-0", "time", json!({})));
+
+		for strict in [false, true] {
+			let model = MockCompletionModel::with_responses([call_time()]);
+			let agent = AgentBuilder::new(model.clone())
+				.tool(Named("weather"))
+				.tool(Named("time"))
+				.tool_selector(EmbeddingToolSelector::new(
+					TopicEmbeddingModel::default(),
+					1,
+				))
+				.strict_tool_selection(strict)
+				.build();
+
+			agent.prompt("How is the weather today?").await.unwrap();
+
+			assert_eq!(advertised_tools(&model)[0], ["weather"]);
+			let result = tool_result(&model);
+			if strict {
+				assert_eq!(result, "Unknown tool `time`, available tools: [weather]");
+			} else {
+				assert_eq!(result, "\"time\"");
+			}
+		}
+	}
+}
@@ -1,5 +1,7 @@
 //! Everything related to audio generation (ie, Text To Speech).
 //! Clankers abstracts over a number of different providers using the [AudioGenerationModel] trait.
+use bytes::Bytes;
+use futures::Stream;
 use serde_json::Value;
 use thiserror::Error;
 
@@ -27,6 +29,10 @@ pub enum AudioGenerationError {
 	/// Error returned by the transcription model provider
 	#[error("ProviderError: {0}")]
 	ProviderError(String),
+
+	/// The model doesn't support the requested operation
+	#[error("Unsupported: {0}")]
+	Unsupported(String),
 }
 pub trait AudioGeneration<M>
 where
@@ -68,6 +74,21 @@ pub trait AudioGenerationModel: Sized + Clone + WasmCompatSend + WasmCompatSync
 		Output = Result<AudioGenerationResponse<Self::Response>, AudioGenerationError>,
 	> + Send;
 
+	/// Generates audio, streaming chunks of the encoded audio as soon as they are synthesized
+	/// so playback can start before the generation finishes.
+	/// Models that can't stream yield a single [AudioGenerationError::Unsupported] error.
+	fn stream_audio_generation(
+		&self,
+		request: AudioGenerationRequest,
+	) -> impl Stream<Item = Result<Bytes, AudioGenerationError>> + Send {
+		let _ = request;
+		futures::stream::once(async {
+			Err(AudioGenerationError::Unsupported(
+				"streaming audio generation is not supported by this model".to_string(),
+			))
+		})
+	}
+
 	fn audio_generation_request(&self) -> AudioGenerationRequestBuilder<Self> {
 		AudioGenerationRequestBuilder::new(self.clone())
 	}
@@ -145,4 +166,18 @@ where
 
 		model.audio_generation(self.build()).await
 	}
+
+	/// Sends the request, streaming chunks of the encoded audio as they are generated.
+	pub fn stream(self) -> impl Stream<Item = Result<Bytes, AudioGenerationError>> + Send {
+		let model = self.model.clone();
+		let request = self.build();
+
+		async_stream::stream! {
+			let stream = model.stream_audio_generation(request);
+			futures::pin_mut!(stream);
+			while let Some(item) = futures::StreamExt::next(&mut stream).await {
+				yield item;
+			}
+		}
+	}
 }
@@ -0,0 +1,273 @@
+//! Credentials that are refreshed at runtime, for short-lived tokens such as Azure AD or GCP OAuth
+//! access tokens.
+//!
+//! # Example
+//! ```
+//! use std::time::Duration;
+//!
+//! use clankers::client::DynamicAuth;
+//! use clankers::providers::openai;
+//!
+//! async fn fetch_token() -> Result<String, std::io::Error> {
+//!     // Exchange credentials with your identity provider...
+//!     Ok("short-lived-token".to_string())
+//! }
+//!
+//! let auth = DynamicAuth::new(fetch_token).ttl(Duration::from_secs(3600));
+//!
+//! let client = openai::Client::builder()
+//!     .api_key(auth)
+//!     .build()
+//!     .expect("Failed to build the client");
+//! ```
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+use tokio::sync::Mutex;
+
+use crate::http_client::{self, make_auth_header};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync, system_time_now};
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+type RefreshFn = dyn Fn() -> WasmBoxedFuture<'static, http_client::Result<String>> + Send + Sync;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+type RefreshFn = dyn Fn() -> WasmBoxedFuture<'static, http_client::Result<String>>;
+
+/// A credential produced by an async callback and cached until it is about to expire, replacing
+/// the static API key of a client.
+///
+/// The callback is called before the first request, and again before any request made within
+/// [DynamicAuth::refresh_margin] of the token's expiry. Concurrent requests wait for a single
+/// refresh instead of each calling the callback.
+#[derive(Clone)]
+pub struct DynamicAuth {
+	refresh: Arc<RefreshFn>,
+	header: Option<HeaderName>,
+	ttl: Duration,
+	refresh_margin: Duration,
+	cache: Arc<Mutex<Option<CachedToken>>>,
+}
+
+struct CachedToken {
+	header: (HeaderName, HeaderValue),
+	expires_at: SystemTime,
+}
+
+impl DynamicAuth {
+	/// Tokens are assumed to be valid for an hour unless set otherwise with [DynamicAuth::ttl]
+	pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+	pub const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+	/// Create a credential sent as a bearer token, fetched by calling `refresh`.
+	pub fn new<F, Fut, E>(refresh: F) -> Self
+	where
+		F: Fn() -> Fut + WasmCompatSend + WasmCompatSync + 'static,
+		Fut: Future<Output = Result<String, E>> + WasmCompatSend + 'static,
+		E: std::error::Error + WasmCompatSend + WasmCompatSync + 'static,
+	{
+		Self {
+			refresh: Arc::new(move || {
+				let token = refresh();
+				Box::pin(async move {
+					token
+						.await
+						.map_err(|e| http_client::Error::Instance(Box::new(e)))
+				})
+			}),
+			header: None,
+			ttl: Self::DEFAULT_TTL,
+			refresh_margin: Self::DEFAULT_REFRESH_MARGIN,
+			cache: Arc::new(Mutex::new(None)),
+		}
+	}
+
+	/// Send the token as is in the given header (e.g. Azure's `api-key`), instead of as a bearer
+	/// token in the `Authorization` header.
+	pub fn header(mut self, header: HeaderName) -> Self {
+		self.header = Some(header);
+		self
+	}
+
+	/// How long a token stays valid after being fetched.
+	pub fn ttl(mut self, ttl: Duration) -> Self {
+		self.ttl = ttl;
+		self
+	}
+
+	/// How long before its expiry a token is refreshed.
+	pub fn refresh_margin(mut self, refresh_margin: Duration) -> Self {
+		self.refresh_margin = refresh_margin;
+		self
+	}
+
+	/// Returns the auth header, refreshing the token first if it is missing or about to expire.
+	pub async fn auth_header(&self) -> http_client::Result<(HeaderName, HeaderValue)> {
+		// Held during the refresh so concurrent requests don't refresh the token again
+		let mut cache = self.cache.lock().await;
+
+		if let Some(token) = cache.as_ref().filter(|token| self.is_fresh(token)) {
+			return Ok(token.header.clone());
+		}
+
+		let token = (self.refresh)().await?;
+		let header = match &self.header {
+			Some(name) => (name.clone(), HeaderValue::from_str(&token)?),
+			None => make_auth_header(token)?,
+		};
+
+		*cache = Some(CachedToken {
+			header: header.clone(),
+			expires_at: system_time_now() + self.ttl,
+		});
+
+		Ok(header)
+	}
+
+	/// Inserts the auth header into `headers`, replacing any existing one.
+	pub(crate) async fn apply(&self, headers: &mut HeaderMap) -> http_client::Result<()> {
+		let (name, mut value) = self.auth_header().await?;
+		value.set_sensitive(true);
+		headers.insert(name, value);
+
+		Ok(())
+	}
+
+	fn is_fresh(&self, token: &CachedToken) -> bool {
+		system_time_now() + self.refresh_margin < token.expires_at
+	}
+}
+
+impl std::fmt::Debug for DynamicAuth {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DynamicAuth")
+			.field("header", &self.header)
+			.field("ttl", &self.ttl)
+			.field("refresh_margin", &self.refresh_margin)
+			.finish_non_exhaustive()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use super::*;
+
+	/// Returns `token-1`, `token-2`, ... and the counter of tokens issued.
+	fn token_source(delay: Duration) -> (DynamicAuth, Arc<AtomicUsize>) {
+		let issued = Arc::new(AtomicUsize::new(0));
+		let counter = issued.clone();
+
+		let auth = DynamicAuth::new(move || {
+			let issued = issued.clone();
+			async move {
+				tokio::time::sleep(delay).await;
+				let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+				Ok::<_, std::io::Error>(format!("token-{n}"))
+			}
+		});
+
+		(auth, counter)
+	}
+
+	async fn bearer(auth: &DynamicAuth) -> String {
+		let (name, value) = auth.auth_header().await.unwrap();
+		assert_eq!(name, http::header::AUTHORIZATION);
+		value.to_str().unwrap().to_string()
+	}
+
+	#[tokio::test]
+	async fn test_token_is_cached_until_expiry() {
+		let (auth, issued) = token_source(Duration::ZERO);
+		let auth = auth
+			.ttl(Duration::from_millis(200))
+			.refresh_margin(Duration::ZERO);
+
+		assert_eq!(bearer(&auth).await, "Bearer token-1");
+		assert_eq!(bearer(&auth).await, "Bearer token-1");
+		assert_eq!(issued.load(Ordering::SeqCst), 1);
+
+		tokio::time::sleep(Duration::from_millis(250)).await;
+		assert_eq!(bearer(&auth).await, "Bearer token-2");
+		assert_eq!(issued.load(Ordering::SeqCst), 2);
+	}
+
+	#[tokio::test]
+	async fn test_token_is_refreshed_within_margin() {
+		let (auth, issued) = token_source(Duration::ZERO);
+		let auth = auth
+			.ttl(Duration::from_secs(60))
+			.refresh_margin(Duration::from_secs(60));
+
+		assert_eq!(bearer(&auth).await, "Bearer token-1");
+		assert_eq!(bearer(&auth).await, "Bearer token-2");
+		assert_eq!(issued.load(Ordering::SeqCst), 2);
+	}
+
+	#[tokio::test]
+	async fn test_concurrent_requests_share_one_refresh() {
+		let (auth, issued) = token_source(Duration::from_millis(50));
+
+		let tokens = futures::future::join_all((0..10).map(|_| bearer(&auth))).await;
+
+		assert!(tokens.iter().all(|token| token == "Bearer token-1"));
+		assert_eq!(issued.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn test_custom_header() {
+		let (auth, _) = token_source(Duration::ZERO);
+		let auth = auth.header(HeaderName::from_static("api-key"));
+
+		let (name, value) = auth.auth_header().await.unwrap();
+		assert_eq!(name, "api-key");
+		assert_eq!(value, "token-1");
+	}
+
+	#[tokio::test]
+	async fn test_client_sends_refreshed_token() {
+		use bytes::Bytes;
+
+		use crate::http_client::{HttpClientExt, NoBody};
+		use crate::providers::{azure, openai};
+		use crate::test_utils::MockSseClient;
+
+		let (auth, _) = token_source(Duration::ZERO);
+		let http_client = MockSseClient::default().with_json_response("{}");
+		let client = openai::Client::<MockSseClient>::builder()
+			.api_key(auth.ttl(Duration::ZERO))
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+
+		for _ in 0..2 {
+			let req = client
+				.post("/chat/completions")
+				.unwrap()
+				.body(NoBody)
+				.unwrap();
+			client.send::<_, Bytes>(req).await.unwrap();
+		}
+
+		let headers = http_client.request_headers();
+		assert_eq!(headers[0][http::header::AUTHORIZATION], "Bearer token-1");
+		assert_eq!(headers[1][http::header::AUTHORIZATION], "Bearer token-2");
+
+		let (auth, _) = token_source(Duration::ZERO);
+		let http_client = MockSseClient::default().with_json_response("{}");
+		let client = azure::Client::<MockSseClient>::builder()
+			.api_key(auth.header(HeaderName::from_static("api-key")))
+			.azure_endpoint("https://example.openai.azure.com".into())
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+
+		let req = client.post("/embeddings").unwrap().body(NoBody).unwrap();
+		client.send::<_, Bytes>(req).await.unwrap();
+
+		let headers = &http_client.request_headers()[0];
+		assert_eq!(headers["api-key"], "token-1");
+		assert!(!headers.contains_key(http::header::AUTHORIZATION));
+	}
+}
@@ -1,4 +1,19 @@
-use crate::completion::CompletionError;
+//! A registry of the built-in providers, to pick a provider at runtime, e.g. from a
+//! configuration file, see [ProviderKind].
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Capabilities, Capable, Client, ClientError, Nothing, ProviderClient};
+use crate::completion::dynamic::CompletionModelDyn;
+use crate::completion::{CompletionError, CompletionModel};
+use crate::embeddings::dynamic::EmbeddingModelDyn;
+use crate::embeddings::{EmbeddingError, EmbeddingModel};
+use crate::providers::{
+	anthropic, cohere, deepseek, gemini, groq, mistral, moonshot, openai, openrouter, perplexity,
+	together, voyageai, xai,
+};
+use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -6,6 +21,210 @@ pub enum Error {
 	NotFound(String),
 	#[error("Provider '{provider}' cannot be coerced to a '{role}'")]
 	NotCapable { provider: String, role: String },
+	#[error("Error building client\n{0}")]
+	Client(#[from] ClientError),
 	#[error("Error generating response\n{0}")]
 	Completion(#[from] CompletionError),
+	#[error("Error generating embeddings\n{0}")]
+	Embedding(#[from] EmbeddingError),
+}
+
+/// The built-in providers whose clients can be created from the environment alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProviderKind {
+	Anthropic,
+	Cohere,
+	DeepSeek,
+	Gemini,
+	Groq,
+	Mistral,
+	Moonshot,
+	OpenAI,
+	OpenRouter,
+	Perplexity,
+	Together,
+	VoyageAI,
+	XAI,
+}
+
+impl ProviderKind {
+	pub const ALL: [ProviderKind; 13] = [
+		Self::Anthropic,
+		Self::Cohere,
+		Self::DeepSeek,
+		Self::Gemini,
+		Self::Groq,
+		Self::Mistral,
+		Self::Moonshot,
+		Self::OpenAI,
+		Self::OpenRouter,
+		Self::Perplexity,
+		Self::Together,
+		Self::VoyageAI,
+		Self::XAI,
+	];
+
+	/// The lowercase name of the provider, as parsed by [FromStr]
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Anthropic => "anthropic",
+			Self::Cohere => "cohere",
+			Self::DeepSeek => "deepseek",
+			Self::Gemini => "gemini",
+			Self::Groq => "groq",
+			Self::Mistral => "mistral",
+			Self::Moonshot => "moonshot",
+			Self::OpenAI => "openai",
+			Self::OpenRouter => "openrouter",
+			Self::Perplexity => "perplexity",
+			Self::Together => "together",
+			Self::VoyageAI => "voyageai",
+			Self::XAI => "xai",
+		}
+	}
+
+	/// Creates a client of the provider from the environment with its
+	/// [ProviderClient::try_from_env], so the variables it reads besides the API key, e.g.
+	/// `OPENAI_BASE_URL`, are honored. Unlike [ProviderClient::from_env], an improperly
+	/// configured environment is an [Error::Client] instead of a panic.
+	pub fn client_from_env(self) -> Result<Box<dyn DynProviderClient>, Error> {
+		Ok(match self {
+			Self::Anthropic => Box::new(anthropic::Client::try_from_env()?),
+			Self::Cohere => Box::new(cohere::Client::try_from_env()?),
+			Self::DeepSeek => Box::new(deepseek::Client::try_from_env()?),
+			Self::Gemini => Box::new(gemini::Client::try_from_env()?),
+			Self::Groq => Box::new(groq::Client::try_from_env()?),
+			Self::Mistral => Box::new(mistral::Client::try_from_env()?),
+			Self::Moonshot => Box::new(moonshot::Client::try_from_env()?),
+			Self::OpenAI => Box::new(openai::Client::try_from_env()?),
+			Self::OpenRouter => Box::new(openrouter::Client::try_from_env()?),
+			Self::Perplexity => Box::new(perplexity::Client::try_from_env()?),
+			Self::Together => Box::new(together::Client::try_from_env()?),
+			Self::VoyageAI => Box::new(voyageai::Client::try_from_env()?),
+			Self::XAI => Box::new(xai::Client::try_from_env()?),
+		})
+	}
+
+	/// Creates the completion model `model` with a client from the environment.
+	pub fn completion_model(self, model: &str) -> Result<Box<dyn CompletionModelDyn>, Error> {
+		let client = self.client_from_env()?;
+		self.require_completion(client.as_ref(), model)
+	}
+
+	/// Creates the embedding model `model` with a client from the environment.
+	pub fn embedding_model(self, model: &str) -> Result<Box<dyn EmbeddingModelDyn>, Error> {
+		let client = self.client_from_env()?;
+		self.require_embeddings(client.as_ref(), model)
+	}
+
+	pub(crate) fn require_completion(
+		self,
+		client: &dyn DynProviderClient,
+		model: &str,
+	) -> Result<Box<dyn CompletionModelDyn>, Error> {
+		client
+			.completion_model(model)
+			.ok_or_else(|| Error::NotCapable {
+				provider: self.to_string(),
+				role: "completion model".to_string(),
+			})
+	}
+
+	pub(crate) fn require_embeddings(
+		self,
+		client: &dyn DynProviderClient,
+		model: &str,
+	) -> Result<Box<dyn EmbeddingModelDyn>, Error> {
+		client
+			.embedding_model(model)
+			.ok_or_else(|| Error::NotCapable {
+				provider: self.to_string(),
+				role: "embedding model".to_string(),
+			})
+	}
+}
+
+impl fmt::Display for ProviderKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+impl FromStr for ProviderKind {
+	type Err = Error;
+
+	/// Parses the [name](Self::name) of a provider, ignoring case
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.into_iter()
+			.find(|kind| kind.name().eq_ignore_ascii_case(s))
+			.ok_or_else(|| Error::NotFound(s.to_string()))
+	}
+}
+
+/// Type-erased provider client, creating boxed models by name. Implemented by every [Client]
+/// whose capabilities are either [Capable] or [Nothing].
+pub trait DynProviderClient: WasmCompatSend + WasmCompatSync {
+	/// The completion model `model`, `None` if the provider has no completion models
+	fn completion_model(&self, model: &str) -> Option<Box<dyn CompletionModelDyn>>;
+
+	/// The embedding model `model`, `None` if the provider has no embedding models
+	fn embedding_model(&self, model: &str) -> Option<Box<dyn EmbeddingModelDyn>>;
+}
+
+impl<Ext, H> DynProviderClient for Client<Ext, H>
+where
+	Ext: Capabilities<H> + WasmCompatSend + WasmCompatSync,
+	Ext::Completion: DynCompletionCapability<Self>,
+	Ext::Embeddings: DynEmbeddingsCapability<Self>,
+	H: WasmCompatSend + WasmCompatSync,
+{
+	fn completion_model(&self, model: &str) -> Option<Box<dyn CompletionModelDyn>> {
+		Ext::Completion::completion_model(self, model)
+	}
+
+	fn embedding_model(&self, model: &str) -> Option<Box<dyn EmbeddingModelDyn>> {
+		Ext::Embeddings::embedding_model(self, model)
+	}
+}
+
+/// A completion [capability](super::Capability) creating type-erased models for the client `C`
+pub trait DynCompletionCapability<C> {
+	fn completion_model(client: &C, model: &str) -> Option<Box<dyn CompletionModelDyn>>;
+}
+
+impl<C, M> DynCompletionCapability<C> for Capable<M>
+where
+	M: CompletionModel<Client = C> + 'static,
+	M::StreamingResponse: 'static,
+{
+	fn completion_model(client: &C, model: &str) -> Option<Box<dyn CompletionModelDyn>> {
+		Some(Box::new(M::make(client, model)))
+	}
+}
+
+impl<C> DynCompletionCapability<C> for Nothing {
+	fn completion_model(_: &C, _: &str) -> Option<Box<dyn CompletionModelDyn>> {
+		None
+	}
+}
+
+/// An embeddings [capability](super::Capability) creating type-erased models for the client `C`
+pub trait DynEmbeddingsCapability<C> {
+	fn embedding_model(client: &C, model: &str) -> Option<Box<dyn EmbeddingModelDyn>>;
+}
+
+impl<C, M> DynEmbeddingsCapability<C> for Capable<M>
+where
+	M: EmbeddingModel<Client = C> + 'static,
+{
+	fn embedding_model(client: &C, model: &str) -> Option<Box<dyn EmbeddingModelDyn>> {
+		Some(Box::new(M::make(client, model, None)))
+	}
+}
+
+impl<C> DynEmbeddingsCapability<C> for Nothing {
+	fn embedding_model(_: &C, _: &str) -> Option<Box<dyn EmbeddingModelDyn>> {
+		None
+	}
 }
@@ -0,0 +1,233 @@
+//! Capture of the requests sent by the models of a client, see
+//! [ClientBuilder::capture_last_request].
+//!
+//! [ClientBuilder::capture_last_request]: super::ClientBuilder::capture_last_request
+
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderValue, Method};
+
+/// A request as sent by a client, with the exact bytes of its serialized body. Useful to
+/// reproduce the requests rejected by a provider.
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+	pub method: Method,
+	pub uri: String,
+	/// The headers of the request, with the values of the authentication headers redacted.
+	/// Headers added by a [DynamicAuth](super::DynamicAuth) aren't included.
+	pub headers: HeaderMap,
+	/// The serialized body as sent, multipart forms included
+	pub body: Bytes,
+}
+
+impl CapturedRequest {
+	fn new<T>(request: &http::Request<T>, body: Bytes) -> Self {
+		let mut headers = request.headers().clone();
+		for (name, value) in headers.iter_mut() {
+			if value.is_sensitive()
+				|| name == http::header::AUTHORIZATION
+				|| name.as_str().contains("api-key")
+			{
+				*value = HeaderValue::from_static("[redacted]");
+			}
+		}
+
+		Self {
+			method: request.method().clone(),
+			uri: request.uri().to_string(),
+			headers,
+			body,
+		}
+	}
+
+	/// The body as text, e.g. the JSON sent to the provider
+	pub fn body_text(&self) -> std::borrow::Cow<'_, str> {
+		String::from_utf8_lossy(&self.body)
+	}
+}
+
+/// Slot holding the last request sent by a client, shared with its clones. Each model created
+/// from the client gets its own slot.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LastRequest(Arc<Mutex<Option<CapturedRequest>>>);
+
+impl LastRequest {
+	pub(crate) fn store<T>(&self, request: &http::Request<T>, body: Bytes) {
+		let captured = CapturedRequest::new(request, body);
+		*self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(captured);
+	}
+
+	pub(crate) fn get(&self) -> Option<CapturedRequest> {
+		self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::StreamExt;
+
+	use crate::client::{CompletionClient, EmbeddingsClient, TranscriptionClient};
+	use crate::completion::CompletionModel as _;
+	use crate::embeddings::EmbeddingModel as _;
+	use crate::providers::openai;
+	use crate::test_utils::MockSseClient;
+	use crate::transcription::TranscriptionModel as _;
+
+	const SSE: &str = concat!(
+		"data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1,\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\n",
+		"data: [DONE]\n\n",
+	);
+
+	#[tokio::test]
+	async fn test_last_request_not_captured_by_default() {
+		let http_client = MockSseClient::default().with_status_response(400, "{}");
+		let client = openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap()
+			.completions_api();
+		let model = client.completion_model("gpt-4o");
+
+		let _ = model
+			.completion(model.completion_request("Hello").build())
+			.await;
+
+		assert!(model.last_request().is_none());
+	}
+
+	#[tokio::test]
+	async fn test_captures_rejected_completion_request() {
+		let http_client = MockSseClient::default().with_status_response(400, "{}");
+		let client = openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.capture_last_request(true)
+			.build()
+			.unwrap()
+			.completions_api();
+		let model = client.completion_model("gpt-4o");
+
+		let result = model
+			.completion(model.completion_request("Hello").build())
+			.await;
+		assert!(result.is_err());
+
+		let request = model.last_request().unwrap();
+		assert_eq!(request.method, http::Method::POST);
+		assert_eq!(request.uri, http_client.request_uris()[0]);
+		assert!(
+			request.uri.ends_with("/chat/completions"),
+			"{}",
+			request.uri
+		);
+		assert_eq!(request.headers["content-type"], "application/json");
+		assert_eq!(request.headers["authorization"], "[redacted]");
+		assert_eq!(
+			serde_json::from_slice::<serde_json::Value>(&request.body).unwrap(),
+			http_client.request_bodies()[0]
+		);
+		assert!(request.body_text().contains("\"Hello\""));
+	}
+
+	#[tokio::test]
+	async fn test_captures_last_request_of_each_path() {
+		let http_client = MockSseClient::new(SSE).with_json_responses([
+			r#"{ "object": "list", "model": "text-embedding-3-small", "data": [{ "object": "embedding", "embedding": [0.1], "index": 0 }], "usage": { "prompt_tokens": 1, "total_tokens": 1 } }"#,
+			r#"{ "text": "Hello" }"#,
+		]);
+		let client = openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.capture_last_request(true)
+			.build()
+			.unwrap();
+
+		let model = client.completion_model("gpt-4o").completions_api();
+		let mut stream = model
+			.stream(model.completion_request("Hello").build())
+			.await
+			.unwrap();
+		while stream.next().await.is_some() {}
+		let request = model.last_request().unwrap();
+		assert!(
+			request.uri.ends_with("/chat/completions"),
+			"{}",
+			request.uri
+		);
+		assert!(request.body_text().contains("\"stream\":true"));
+
+		let embedding_model = client.embedding_model(openai::TEXT_EMBEDDING_3_SMALL);
+		embedding_model.embed_text("Hello").await.unwrap();
+		let request = embedding_model.last_request().unwrap();
+		assert!(request.uri.ends_with("/embeddings"), "{}", request.uri);
+		assert!(request.body_text().contains("\"Hello\""));
+
+		let model = client.transcription_model(openai::WHISPER_1);
+		model
+			.transcription(
+				model
+					.transcription_request()
+					.data(b"audio bytes".to_vec())
+					.filename(Some("audio.mp3".to_string()))
+					.build(),
+			)
+			.await
+			.unwrap();
+		let request = model.last_request().unwrap();
+		assert!(
+			request.uri.ends_with("/audio/transcriptions"),
+			"{}",
+			request.uri
+		);
+		assert!(request.body_text().contains("whisper-1"));
+		assert!(request.body_text().contains("audio bytes"));
+		// The multipart body is captured as sent, with the boundary of its content type
+		assert_eq!(http_client.raw_request_bodies().last(), Some(&request.body));
+		let content_type = request.headers["content-type"].to_str().unwrap();
+		let boundary = content_type
+			.strip_prefix("multipart/form-data; boundary=")
+			.unwrap();
+		assert!(request.body_text().ends_with(&format!("--{boundary}--\r\n")));
+	}
+
+	#[tokio::test]
+	async fn test_models_capture_their_own_requests() {
+		let http_client = MockSseClient::default().with_status_response(400, "{}");
+		let client = openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.capture_last_request(true)
+			.build()
+			.unwrap()
+			.completions_api();
+		let first = client.completion_model("gpt-4o");
+		let second = client.completion_model("gpt-4o-mini");
+
+		let _ = first
+			.completion(first.completion_request("First").build())
+			.await;
+		let _ = second
+			.completion(second.completion_request("Second").build())
+			.await;
+
+		assert!(first.last_request().unwrap().body_text().contains("First"));
+		assert!(
+			second
+				.last_request()
+				.unwrap()
+				.body_text()
+				.contains("Second")
+		);
+		// Clones of a model share its slot
+		assert!(
+			first
+				.clone()
+				.last_request()
+				.unwrap()
+				.body_text()
+				.contains("First")
+		);
+	}
+}
@@ -3,7 +3,9 @@
 //! Dyn-compatible traits have been provided to allow for more provider-agnostic code.
 
 pub mod audio_generation;
+pub mod auth;
 pub mod builder;
+pub mod capture;
 pub mod completion;
 pub mod embeddings;
 pub mod image_generation;
@@ -13,12 +15,17 @@ pub mod verify;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[cfg(feature = "audio")]
 use audio_generation::*;
+pub use auth::DynamicAuth;
 use bytes::Bytes;
+pub use capture::CapturedRequest;
+use capture::LastRequest;
 pub use completion::CompletionClient;
 pub use embeddings::EmbeddingsClient;
+use futures::future::Either;
 use http::{HeaderMap, HeaderName, HeaderValue};
 #[cfg(feature = "image")]
 use image_generation::ImageGenerationClient;
@@ -28,14 +35,18 @@ pub use verify::{VerifyClient, VerifyError};
 
 #[cfg(feature = "audio")]
 use crate::audio_generation::*;
-use crate::completion::CompletionModel;
+use crate::completion::metadata::Stopwatch;
+use crate::completion::{CompletionModel, CompletionRequest, RequestDefaults, ResponseMetadata};
 use crate::embeddings::EmbeddingModel;
+use crate::http_client::stall::{self, StallTimeout};
 use crate::http_client::{
 	self, Builder, HttpClientExt, LazyBody, MultipartForm, Request, Response, make_auth_header,
 };
 #[cfg(feature = "image")]
 use crate::image_generation::ImageGenerationModel;
 use crate::prelude::TranscriptionClient;
+use crate::telemetry::PricingTable;
+use crate::telemetry::pricing::CostRecorder;
 use crate::transcription::TranscriptionModel;
 use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
 
@@ -52,15 +63,53 @@ pub enum ClientBuilderError {
 	InvalidProperty(&'static str),
 }
 
+/// An error creating a client from the process's environment, see
+/// [ProviderClient::try_from_env]
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ClientError {
+	/// The environment variables checked and not set. Alternatives, e.g. `AZURE_API_KEY` and
+	/// `AZURE_TOKEN`, are listed together when neither is set.
+	#[error("Environment variables not set: {}", display_env_vars(.0))]
+	MissingEnvVar(Vec<&'static str>),
+	#[error("Invalid environment variable `{name}`: {reason}")]
+	InvalidEnvVar { name: &'static str, reason: String },
+	#[error("HttpError: {0}")]
+	HttpError(#[from] http_client::Error),
+}
+
+fn display_env_vars(names: &[&str]) -> String {
+	names
+		.iter()
+		.map(|name| format!("`{name}`"))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Reads the environment variable `name`, a [ClientError::MissingEnvVar] if it isn't set
+pub(crate) fn env_var(name: &'static str) -> Result<String, ClientError> {
+	std::env::var(name).map_err(|_| ClientError::MissingEnvVar(vec![name]))
+}
+
 /// Abstracts over the ability to instantiate a client, either via environment variables or some
 /// `Self::Input`
 pub trait ProviderClient {
 	type Input;
 
 	/// Create a client from the process's environment.
-	/// Panics if an environment is improperly configured.
+	/// Panics if an environment is improperly configured, see [Self::try_from_env].
 	fn from_env() -> Self;
 
+	/// Create a client from the process's environment, with an error naming the environment
+	/// variables checked if it is improperly configured. Defaults to calling [Self::from_env],
+	/// which panics instead.
+	fn try_from_env() -> Result<Self, ClientError>
+	where
+		Self: Sized,
+	{
+		Ok(Self::from_env())
+	}
+
 	fn from_val(input: Self::Input) -> Self;
 }
 
@@ -84,14 +133,37 @@ pub trait ApiKey: Sized {
 	fn into_header(self) -> Option<http_client::Result<(HeaderName, HeaderValue)>> {
 		None
 	}
+
+	/// A credential refreshed before each request instead of being set in the default headers
+	fn dynamic_auth(&self) -> Option<DynamicAuth> {
+		None
+	}
 }
 
-/// An API key which will be inserted into a `Client`'s default headers as a bearer auth token
-pub struct BearerAuth(String);
+/// An API key which will be inserted into a `Client`'s default headers as a bearer auth token,
+/// or a [DynamicAuth] refreshed before each request.
+#[derive(Clone)]
+pub struct BearerAuth(BearerToken);
+
+#[derive(Clone)]
+enum BearerToken {
+	Static(String),
+	Dynamic(DynamicAuth),
+}
 
 impl ApiKey for BearerAuth {
 	fn into_header(self) -> Option<http_client::Result<(HeaderName, HeaderValue)>> {
-		Some(make_auth_header(self.0))
+		match self.0 {
+			BearerToken::Static(key) => Some(make_auth_header(key)),
+			BearerToken::Dynamic(_) => None,
+		}
+	}
+
+	fn dynamic_auth(&self) -> Option<DynamicAuth> {
+		match &self.0 {
+			BearerToken::Static(_) => None,
+			BearerToken::Dynamic(auth) => Some(auth.clone()),
+		}
 	}
 }
 
@@ -100,7 +172,13 @@ where
 	S: Into<String>,
 {
 	fn from(value: S) -> Self {
-		Self(value.into())
+		Self(BearerToken::Static(value.into()))
+	}
+}
+
+impl From<DynamicAuth> for BearerAuth {
+	fn from(auth: DynamicAuth) -> Self {
+		Self(BearerToken::Dynamic(auth))
 	}
 }
 
@@ -127,6 +205,12 @@ pub struct Client<Ext = Nothing, H = reqwest::Client> {
 	headers: Arc<HeaderMap>,
 	http_client: H,
 	ext: Ext,
+	pricing: Option<Arc<PricingTable>>,
+	capture_response_headers: bool,
+	stream_stall_timeout: Option<Duration>,
+	default_params: Option<Arc<RequestDefaults>>,
+	auth: Option<DynamicAuth>,
+	last_request: Option<LastRequest>,
 }
 
 pub trait DebugExt: Debug {
@@ -187,14 +271,7 @@ pub trait Provider: Sized {
 	) -> http_client::Result<Self>;
 
 	fn build_uri(&self, base_url: &str, path: &str, _transport: Transport) -> String {
-		// Some providers (like Azure) have a blank base URL to allow users to input their own endpoints.
-		let base_url = if base_url.is_empty() {
-			base_url.to_string()
-		} else {
-			base_url.to_string() + "/"
-		};
-
-		base_url.to_string() + path.trim_start_matches('/')
+		join_url(base_url, path)
 	}
 
 	fn with_custom(&self, req: http_client::Builder) -> http_client::Result<http_client::Builder> {
@@ -202,6 +279,52 @@ pub trait Provider: Sized {
 	}
 }
 
+/// Joins a base URL and a request path, keeping the path segments of the base URL (e.g. the `/v1`
+/// of `https://host/llm/v1`) and collapsing duplicate slashes between path segments, so a trailing
+/// slash on the base URL or a leading slash on the path makes no difference. Queries are left as is,
+/// and a query on the base URL (e.g. `?api-version=...`) is kept after the path.
+pub(crate) fn join_url(base_url: &str, path: &str) -> String {
+	// Some providers (like Azure) have a blank base URL to allow users to input their own endpoints.
+	if base_url.is_empty() {
+		return path.trim_start_matches('/').to_string();
+	}
+
+	let (path, path_query) = split_query(path);
+	let (base_url, base_query) = split_query(base_url);
+	let (scheme, rest) = base_url
+		.split_once("://")
+		.map_or(("", base_url), |(scheme, rest)| (scheme, rest));
+
+	let joined = path_segments(rest)
+		.chain(path_segments(path))
+		.collect::<Vec<_>>()
+		.join("/");
+
+	let mut url = if scheme.is_empty() {
+		joined
+	} else {
+		format!("{scheme}://{joined}")
+	};
+
+	for (i, query) in path_query.into_iter().chain(base_query).enumerate() {
+		url.push(if i == 0 { '?' } else { '&' });
+		url.push_str(query);
+	}
+
+	url
+}
+
+fn split_query(url: &str) -> (&str, Option<&str>) {
+	match url.split_once('?') {
+		Some((url, query)) => (url, Some(query)),
+		None => (url, None),
+	}
+}
+
+fn path_segments(path: &str) -> impl Iterator<Item = &str> {
+	path.split('/').filter(|segment| !segment.is_empty())
+}
+
 /// A wrapper type providing runtime checks on a provider's capabilities via the [Capability] trait
 pub struct Capable<M>(PhantomData<M>);
 
@@ -252,7 +375,7 @@ impl<Ext, ExtBuilder, Key, H> Client<Ext, H>
 where
 	ExtBuilder: Clone + Default + ProviderBuilder<Output = Ext, ApiKey = Key>,
 	Ext: Provider<Builder = ExtBuilder>,
-	H: Default + HttpClientExt,
+	H: Default + HttpClientExt + 'static,
 	Key: ApiKey,
 {
 	pub fn new(api_key: impl Into<Key>) -> http_client::Result<Self> {
@@ -273,36 +396,105 @@ impl<Ext, H> Client<Ext, H> {
 		&self.ext
 	}
 
+	/// The pricing table used to estimate the cost of requests, see [ClientBuilder::pricing]
+	pub fn pricing(&self) -> Option<&PricingTable> {
+		self.pricing.as_deref()
+	}
+
+	/// The parameters used when a request doesn't set them, see [ClientBuilder::default_params]
+	pub fn default_params(&self) -> Option<&RequestDefaults> {
+		self.default_params.as_deref()
+	}
+
+	/// The last request sent by this client or its clones. Only kept when enabled with
+	/// [ClientBuilder::capture_last_request]. Read by the models with their `last_request`.
+	pub(crate) fn last_request(&self) -> Option<CapturedRequest> {
+		self.last_request.as_ref().and_then(LastRequest::get)
+	}
+
+	/// A clone of the client for a new model, capturing its requests apart from the other
+	/// models of the client.
+	fn for_model(&self) -> Self
+	where
+		Ext: Clone,
+		H: Clone,
+	{
+		Self {
+			last_request: self.last_request.as_ref().map(|_| LastRequest::default()),
+			..self.clone()
+		}
+	}
+
+	/// Fills in the parameters `request` doesn't set with the client defaults. Called by the
+	/// completion models before converting the request to the provider format.
+	pub(crate) fn apply_default_params(&self, request: CompletionRequest) -> CompletionRequest {
+		match &self.default_params {
+			Some(defaults) => defaults.apply(request),
+			None => request,
+		}
+	}
+
+	pub(crate) fn cost_recorder(
+		&self,
+		provider: &'static str,
+		model: impl Into<String>,
+	) -> CostRecorder {
+		CostRecorder::new(self.pricing.clone(), provider, model)
+	}
+
 	pub fn with_ext<NewExt>(self, new_ext: NewExt) -> Client<NewExt, H> {
 		Client {
 			base_url: self.base_url,
 			headers: self.headers,
 			http_client: self.http_client,
 			ext: new_ext,
+			pricing: self.pricing,
+			capture_response_headers: self.capture_response_headers,
+			stream_stall_timeout: self.stream_stall_timeout,
+			default_params: self.default_params,
+			auth: self.auth,
+			last_request: self.last_request,
 		}
 	}
 }
 
 impl<Ext, H> HttpClientExt for Client<Ext, H>
 where
-	H: HttpClientExt + 'static,
+	H: HttpClientExt + Clone + 'static,
 	Ext: WasmCompatSend + WasmCompatSync + 'static,
 {
 	fn send<T, U>(
 		&self,
-		mut req: Request<T>,
+		req: Request<T>,
 	) -> impl Future<Output = http_client::Result<Response<LazyBody<U>>>> + WasmCompatSend + 'static
 	where
 		T: Into<Bytes> + WasmCompatSend,
 		U: From<Bytes>,
 		U: WasmCompatSend + 'static,
 	{
+		// Converted eagerly since the body type may not outlive the request
+		let mut req = req.map(Into::<Bytes>::into);
 		req.headers_mut().insert(
 			http::header::CONTENT_TYPE,
 			http::HeaderValue::from_static("application/json"),
 		);
+		if let Some(last_request) = &self.last_request {
+			last_request.store(&req, req.body().clone());
+		}
+		let capture_headers = self.capture_response_headers;
+		let auth = self.auth.clone();
+		let http_client = self.http_client.clone();
 
-		self.http_client.send(req)
+		async move {
+			if let Some(auth) = auth {
+				auth.apply(req.headers_mut()).await?;
+			}
+
+			let stopwatch = Stopwatch::start();
+			let mut response = http_client.send(req).await?;
+			attach_response_metadata(&mut response, capture_headers, stopwatch);
+			Ok(response)
+		}
 	}
 
 	fn send_multipart<U>(
@@ -313,25 +505,92 @@ where
 		U: From<Bytes>,
 		U: WasmCompatSend + 'static,
 	{
-		self.http_client.send_multipart(req)
+		// When capturing, the form is encoded here rather than by the HTTP client, so that the
+		// captured body and boundary are the ones sent
+		let req = match &self.last_request {
+			Some(last_request) => {
+				let (mut parts, form) = req.into_parts();
+				let (boundary, body) = form.encode();
+				parts.headers.insert(
+					http::header::CONTENT_TYPE,
+					HeaderValue::from_str(&format!("multipart/form-data; boundary={boundary}"))
+						.expect("the boundary is a valid header value"),
+				);
+				let req = Request::from_parts(parts, body);
+				last_request.store(&req, req.body().clone());
+				Either::Left(req)
+			}
+			None => Either::Right(req),
+		};
+		let auth = self.auth.clone();
+		let http_client = self.http_client.clone();
+
+		async move {
+			match req {
+				Either::Left(mut req) => {
+					if let Some(auth) = auth {
+						auth.apply(req.headers_mut()).await?;
+					}
+					http_client.send(req).await
+				}
+				Either::Right(mut req) => {
+					if let Some(auth) = auth {
+						auth.apply(req.headers_mut()).await?;
+					}
+					http_client.send_multipart(req).await
+				}
+			}
+		}
 	}
 
 	fn send_streaming<T>(
 		&self,
-		mut req: Request<T>,
+		req: Request<T>,
 	) -> impl Future<Output = http_client::Result<http_client::StreamingResponse>> + WasmCompatSend
 	where
 		T: Into<Bytes>,
 	{
+		// Converted eagerly since the body type may not outlive the request
+		let mut req = req.map(Into::<Bytes>::into);
 		req.headers_mut().insert(
 			http::header::CONTENT_TYPE,
 			http::HeaderValue::from_static("application/json"),
 		);
+		if let Some(last_request) = &self.last_request {
+			last_request.store(&req, req.body().clone());
+		}
+		let capture_headers = self.capture_response_headers;
+		let stall_timeout = req
+			.extensions()
+			.get::<StallTimeout>()
+			.map_or(self.stream_stall_timeout, |timeout| timeout.0);
+		let auth = self.auth.clone();
+		let http_client = self.http_client.clone();
+
+		async move {
+			if let Some(auth) = auth {
+				auth.apply(req.headers_mut()).await?;
+			}
 
-		self.http_client.send_streaming(req)
+			let stopwatch = Stopwatch::start();
+			let mut response = http_client.send_streaming(req).await?;
+			attach_response_metadata(&mut response, capture_headers, stopwatch);
+			Ok(response.map(|body| stall::guard(body, stall_timeout)))
+		}
 	}
 }
 
+/// Adds the [ResponseMetadata] of a response to its extensions, where providers read it from.
+fn attach_response_metadata<B>(
+	response: &mut Response<B>,
+	capture_headers: bool,
+	stopwatch: Stopwatch,
+) {
+	let metadata = ResponseMetadata::from_headers(response.headers(), capture_headers)
+		.with_latency(stopwatch.elapsed());
+	response.extensions_mut().insert(metadata);
+}
+
 impl<Ext, Builder, H> Client<Ext, H>
 where
 	H: Default + HttpClientExt,
@@ -397,6 +656,23 @@ where
 
 		self.ext.with_custom(req)
 	}
+
+	pub fn delete<S>(&self, path: S) -> http_client::Result<Builder>
+	where
+		S: AsRef<str>,
+	{
+		let uri = self
+			.ext
+			.build_uri(&self.base_url, path.as_ref(), Transport::Http);
+
+		let mut req = Request::delete(uri);
+
+		if let Some(hs) = req.headers_mut() {
+			hs.extend(self.headers.iter().map(|(k, v)| (k.clone(), v.clone())));
+		}
+
+		self.ext.with_custom(req)
+	}
 }
 
 impl<Ext, H> VerifyClient for Client<Ext, H>
@@ -407,10 +683,13 @@ where
 	async fn verify(&self) -> Result<(), VerifyError> {
 		use http::StatusCode;
 
-		let req = self
+		let mut req = self
 			.get(Ext::VERIFY_PATH)?
 			.body(http_client::NoBody)
 			.map_err(http_client::Error::from)?;
+		if let Some(auth) = &self.auth {
+			auth.apply(req.headers_mut()).await?;
+		}
 
 		let response = self.http_client.send(req).await?;
 
@@ -454,6 +733,11 @@ pub struct ClientBuilder<Ext, ApiKey = NeedsApiKey, H = reqwest::Client> {
 	headers: HeaderMap,
 	http_client: Option<H>,
 	ext: Ext,
+	pricing: Option<PricingTable>,
+	capture_response_headers: bool,
+	stream_stall_timeout: Option<Duration>,
+	default_params: Option<RequestDefaults>,
+	capture_last_request: bool,
 }
 
 impl<ExtBuilder, H> Default for ClientBuilder<ExtBuilder, NeedsApiKey, H>
@@ -468,6 +752,11 @@ where
 			base_url: ExtBuilder::BASE_URL.into(),
 			http_client: None,
 			ext: Default::default(),
+			pricing: None,
+			capture_response_headers: false,
+			stream_stall_timeout: None,
+			default_params: None,
+			capture_last_request: false,
 		}
 	}
 }
@@ -482,6 +771,11 @@ impl<Ext, H> ClientBuilder<Ext, NeedsApiKey, H> {
 			headers: self.headers,
 			http_client: self.http_client,
 			ext: self.ext,
+			pricing: self.pricing,
+			capture_response_headers: self.capture_response_headers,
+			stream_stall_timeout: self.stream_stall_timeout,
+			default_params: self.default_params,
+			capture_last_request: self.capture_last_request,
 		}
 	}
 }
@@ -501,6 +795,11 @@ where
 			headers,
 			http_client,
 			ext,
+			pricing,
+			capture_response_headers,
+			stream_stall_timeout,
+			default_params,
+			capture_last_request,
 		} = self;
 
 		let new_ext = f(ext.clone());
@@ -511,6 +810,11 @@ where
 			headers,
 			http_client,
 			ext: new_ext,
+			pricing,
+			capture_response_headers,
+			stream_stall_timeout,
+			default_params,
+			capture_last_request,
 		}
 	}
 
@@ -525,7 +829,12 @@ where
 		}
 	}
 
-	/// Set the HTTP backend used in this client
+	/// Set the HTTP backend used in this client, e.g. a [reqwest::Client] with a proxy or custom TLS
+	/// configuration. It is used for every request made by the client and its models.
+	///
+	/// Without one, [ClientBuilder::build] falls back to the default reqwest client set with
+	/// [http_client::set_default_reqwest_client], or to `U::default()`. This is why `build`
+	/// requires the backend to implement [Default], even when one is given here.
 	pub fn http_client<U>(self, http_client: U) -> ClientBuilder<Ext, ApiKey, U> {
 		ClientBuilder {
 			http_client: Some(http_client),
@@ -533,14 +842,93 @@ where
 			api_key: self.api_key,
 			headers: self.headers,
 			ext: self.ext,
+			pricing: self.pricing,
+			capture_response_headers: self.capture_response_headers,
+			stream_stall_timeout: self.stream_stall_timeout,
+			default_params: self.default_params,
+			capture_last_request: self.capture_last_request,
 		}
 	}
 
+	/// Use a [reqwest::Client] built from `configure`, e.g. to set a proxy or TLS roots:
+	///
+	/// ```ignore
+	/// let client = openai::Client::builder()
+	///     .api_key("sk-...")
+	///     .reqwest_client(|builder| builder.proxy(reqwest::Proxy::all("http://proxy:8080")?))?
+	///     .build()?;
+	/// ```
+	pub fn reqwest_client<F>(
+		self,
+		configure: F,
+	) -> http_client::Result<ClientBuilder<Ext, ApiKey, reqwest::Client>>
+	where
+		F: FnOnce(reqwest::ClientBuilder) -> reqwest::ClientBuilder,
+	{
+		let http_client = configure(reqwest::Client::builder())
+			.build()
+			.map_err(|e| http_client::Error::Instance(Box::new(e)))?;
+
+		Ok(self.http_client(http_client))
+	}
+
 	/// Set the HTTP headers used in this client
 	pub fn http_headers(self, headers: HeaderMap) -> Self {
 		Self { headers, ..self }
 	}
 
+	/// Set the pricing table used to estimate the cost of requests made with this client.
+	/// Takes precedence over the global pricing table, see [crate::telemetry::pricing].
+	pub fn pricing(self, pricing: PricingTable) -> Self {
+		Self {
+			pricing: Some(pricing),
+			..self
+		}
+	}
+
+	/// Copy every response header into the [ResponseMetadata] of completion responses. Only the
+	/// request ID and latency are recorded by default.
+	pub fn capture_response_headers(self, capture_response_headers: bool) -> Self {
+		Self {
+			capture_response_headers,
+			..self
+		}
+	}
+
+	/// Keep the last request sent by each model of the client, with its URL, headers and
+	/// serialized body, read with the `last_request` method of the model (e.g.
+	/// [CompletionModel::last_request]). Meant to debug the requests rejected by a provider, it
+	/// is disabled by default so that no request is retained.
+	pub fn capture_last_request(self, capture_last_request: bool) -> Self {
+		Self {
+			capture_last_request,
+			..self
+		}
+	}
+
+	/// Fail streaming responses with [http_client::Error::StreamStalled] once no bytes arrived
+	/// for `timeout`, e.g. when a proxy keeps the connection open after the provider stopped
+	/// responding. Disabled by default, it can be overridden per request with
+	/// [StallTimeout](http_client::stall::StallTimeout).
+	pub fn stream_stall_timeout(self, timeout: Duration) -> Self {
+		Self {
+			stream_stall_timeout: Some(timeout),
+			..self
+		}
+	}
+
+	/// Set parameters used by every completion request of this client that doesn't set them,
+	/// e.g. `json!({ "temperature": 0.2, "top_p": 0.9 })`. `temperature` and `max_tokens` map to
+	/// the fields of the request, the other keys are deep merged under its additional parameters.
+	/// Model defaults, see [CompletionModel::with_default_params], take precedence.
+	pub fn default_params(self, params: serde_json::Value) -> Self {
+		let defaults = self.default_params.unwrap_or_default().params(params);
+		Self {
+			default_params: Some(defaults),
+			..self
+		}
+	}
+
 	pub(crate) fn headers_mut(&mut self) -> &mut HeaderMap {
 		&mut self.headers
 	}
@@ -567,8 +955,10 @@ where
 	ExtBuilder: Clone + ProviderBuilder<Output = Ext, ApiKey = Key> + Default,
 	Ext: Provider<Builder = ExtBuilder>,
 	Key: ApiKey,
-	H: Default,
+	H: Default + 'static,
 {
+	/// Build the client. Without an HTTP backend set with [ClientBuilder::http_client], the
+	/// default reqwest client is used, see [http_client::set_default_reqwest_client].
 	pub fn build(mut self) -> http_client::Result<Client<ExtBuilder::Output, H>> {
 		let ext = self.ext.clone();
 
@@ -580,20 +970,32 @@ where
 			base_url,
 			mut headers,
 			api_key,
+			pricing,
+			capture_response_headers,
+			stream_stall_timeout,
+			default_params,
+			capture_last_request,
 			..
 		} = self;
 
+		let auth = api_key.dynamic_auth();
 		if let Some((k, v)) = api_key.into_header().transpose()? {
 			headers.insert(k, v);
 		}
 
-		let http_client = http_client.unwrap_or_default();
+		let http_client = http_client.unwrap_or_else(http_client::default_http_client);
 
 		Ok(Client {
 			http_client,
 			base_url: Arc::from(base_url.as_str()),
 			headers: Arc::new(headers),
 			ext,
+			pricing: pricing.map(Arc::new),
+			capture_response_headers,
+			stream_stall_timeout,
+			default_params: default_params.map(Arc::new),
+			auth,
+			last_request: capture_last_request.then(LastRequest::default),
 		})
 	}
 }
@@ -602,11 +1004,13 @@ impl<M, Ext, H> CompletionClient for Client<Ext, H>
 where
 	Ext: Capabilities<H, Completion = Capable<M>>,
 	M: CompletionModel<Client = Self>,
+	Ext: Clone,
+	H: Clone,
 {
 	type CompletionModel = M;
 
 	fn completion_model(&self, model: impl Into<String>) -> Self::CompletionModel {
-		M::make(self, model)
+		M::make(&self.for_model(), model)
 	}
 }
 
@@ -614,11 +1018,13 @@ impl<M, Ext, H> EmbeddingsClient for Client<Ext, H>
 where
 	Ext: Capabilities<H, Embeddings = Capable<M>>,
 	M: EmbeddingModel<Client = Self>,
+	Ext: Clone,
+	H: Clone,
 {
 	type EmbeddingModel = M;
 
 	fn embedding_model(&self, model: impl Into<String>) -> Self::EmbeddingModel {
-		M::make(self, model, None)
+		M::make(&self.for_model(), model, None)
 	}
 
 	fn embedding_model_with_ndims(
@@ -626,7 +1032,7 @@ where
 		model: impl Into<String>,
 		ndims: usize,
 	) -> Self::EmbeddingModel {
-		M::make(self, model, Some(ndims))
+		M::make(&self.for_model(), model, Some(ndims))
 	}
 }
 
@@ -634,11 +1040,13 @@ impl<M, Ext, H> TranscriptionClient for Client<Ext, H>
 where
 	Ext: Capabilities<H, Transcription = Capable<M>>,
 	M: TranscriptionModel<Client = Self> + WasmCompatSend,
+	Ext: Clone,
+	H: Clone,
 {
 	type TranscriptionModel = M;
 
 	fn transcription_model(&self, model: impl Into<String>) -> Self::TranscriptionModel {
-		M::make(self, model)
+		M::make(&self.for_model(), model)
 	}
 }
 
@@ -0,0 +1,493 @@
+//! Caching of completions, to serve repeated requests without calling the provider again.
+//!
+//! Useful for deterministic workloads (e.g. extraction with a temperature of 0) sending the same
+//! requests over and over. Streamed requests always go to the provider.
+//!
+//! # Example
+//! ```rust
+//! use clankers::client::CompletionClient;
+//! use clankers::completion::{CompletionModel, InMemoryCompletionCache};
+//! use clankers::providers::openai;
+//!
+//! let openai = openai::Client::from_env();
+//!
+//! let model = openai
+//!     .completion_model(openai::GPT_4O)
+//!     .with_cache(InMemoryCompletionCache::new(1000), openai::GPT_4O);
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::{
+	AssistantContent, CompletionError, CompletionModel, CompletionRequest, CompletionResponse,
+	TokenCounter, Usage, ValidationReport,
+};
+use crate::OneOrMany;
+use crate::streaming::StreamingCompletionResponse;
+use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
+
+/// Stable hash of a completion request, identical for requests with the same content regardless
+/// of the order of the fields of their JSON values (e.g. `additional_params`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheKey(pub u64);
+
+impl CacheKey {
+	/// Hashes the canonical JSON of `request`, along with the `namespace` of the model.
+	pub fn new(namespace: &str, request: &CompletionRequest) -> Self {
+		let value = json!({
+			"namespace": namespace,
+			"preamble": request.preamble,
+			"chat_history": request.chat_history,
+			"documents": request.documents,
+			"tools": request.tools,
+			"temperature": request.temperature,
+			"max_tokens": request.max_tokens,
+			"stop_sequences": request.stop_sequences,
+			"tool_choice": request.tool_choice,
+			"additional_params": request.additional_params,
+		});
+
+		let mut canonical = String::new();
+		write_canonical_json(&value, &mut canonical);
+
+		Self(fnv1a(canonical.as_bytes()))
+	}
+}
+
+impl std::fmt::Display for CacheKey {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{:016x}", self.0)
+	}
+}
+
+/// Writes `value` as JSON with the keys of every object sorted.
+fn write_canonical_json(value: &Value, out: &mut String) {
+	match value {
+		Value::Object(map) => {
+			let mut entries = map.iter().collect::<Vec<_>>();
+			entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+			out.push('{');
+			for (i, (key, value)) in entries.into_iter().enumerate() {
+				if i > 0 {
+					out.push(',');
+				}
+				out.push_str(&Value::String(key.clone()).to_string());
+				out.push(':');
+				write_canonical_json(value, out);
+			}
+			out.push('}');
+		}
+		Value::Array(values) => {
+			out.push('[');
+			for (i, value) in values.iter().enumerate() {
+				if i > 0 {
+					out.push(',');
+				}
+				write_canonical_json(value, out);
+			}
+			out.push(']');
+		}
+		value => out.push_str(&value.to_string()),
+	}
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hashers doesn't change between releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+	bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+		(hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+	})
+}
+
+/// A completion stored in a [CompletionCache], with the raw response of the provider as JSON.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CachedCompletion {
+	pub choice: OneOrMany<AssistantContent>,
+	pub usage: Usage,
+	pub raw_response: Value,
+}
+
+/// Storage of the completions served by a [CachedModel].
+pub trait CompletionCache: WasmCompatSend + WasmCompatSync {
+	fn get(&self, key: &CacheKey) -> Option<CachedCompletion>;
+
+	fn put(&self, key: CacheKey, completion: CachedCompletion);
+}
+
+impl<C> CompletionCache for Arc<C>
+where
+	C: CompletionCache + ?Sized,
+{
+	fn get(&self, key: &CacheKey) -> Option<CachedCompletion> {
+		(**self).get(key)
+	}
+
+	fn put(&self, key: CacheKey, completion: CachedCompletion) {
+		(**self).put(key, completion)
+	}
+}
+
+/// An in-memory cache evicting the least recently used completion once full.
+pub struct InMemoryCompletionCache {
+	capacity: usize,
+	state: Mutex<LruState>,
+}
+
+#[derive(Default)]
+struct LruState {
+	/// Completions by key, with the tick they were last used at
+	entries: HashMap<CacheKey, (u64, CachedCompletion)>,
+	/// Keys by the tick they were last used at, the least recently used first
+	order: BTreeMap<u64, CacheKey>,
+	tick: u64,
+}
+
+impl LruState {
+	/// Marks `key` as the most recently used, returning its completion.
+	fn touch(&mut self, key: &CacheKey) -> Option<&CachedCompletion> {
+		self.tick += 1;
+		let (tick, completion) = self.entries.get_mut(key)?;
+		self.order.remove(tick);
+		self.order.insert(self.tick, *key);
+		*tick = self.tick;
+		Some(completion)
+	}
+}
+
+impl InMemoryCompletionCache {
+	/// A cache holding at most `capacity` completions.
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			capacity,
+			state: Mutex::default(),
+		}
+	}
+
+	/// The number of cached completions.
+	pub fn len(&self) -> usize {
+		self.state
+			.lock()
+			.expect("cache lock poisoned")
+			.entries
+			.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn clear(&self) {
+		*self.state.lock().expect("cache lock poisoned") = LruState::default();
+	}
+}
+
+impl Default for InMemoryCompletionCache {
+	/// A cache holding at most 1000 completions.
+	fn default() -> Self {
+		Self::new(1000)
+	}
+}
+
+impl CompletionCache for InMemoryCompletionCache {
+	fn get(&self, key: &CacheKey) -> Option<CachedCompletion> {
+		let mut state = self.state.lock().expect("cache lock poisoned");
+		state.touch(key).cloned()
+	}
+
+	fn put(&self, key: CacheKey, completion: CachedCompletion) {
+		if self.capacity == 0 {
+			return;
+		}
+
+		let mut state = self.state.lock().expect("cache lock poisoned");
+		if state.touch(&key).is_none() {
+			while state.entries.len() >= self.capacity {
+				let Some((_, evicted)) = state.order.pop_first() else {
+					break;
+				};
+				state.entries.remove(&evicted);
+			}
+			let tick = state.tick;
+			state.order.insert(tick, key);
+		}
+
+		let tick = state.tick;
+		state.entries.insert(key, (tick, completion));
+	}
+}
+
+/// A completion model serving repeated requests from a [CompletionCache], see
+/// [CompletionModel::with_cache].
+///
+/// Requests are looked up by their [CacheKey], which includes the namespace of the model, e.g.
+/// its name. Models sharing a cache must be given different namespaces, otherwise they are served
+/// each other's completions.
+#[derive(Clone)]
+pub struct CachedModel<M> {
+	model: M,
+	cache: Arc<dyn CompletionCache>,
+	namespace: String,
+}
+
+impl<M> CachedModel<M> {
+	/// Caches the completions of `model` in `cache` under `namespace`, which distinguishes the
+	/// requests of this model from the requests of other models in the cache.
+	pub fn new(
+		model: M,
+		cache: impl CompletionCache + 'static,
+		namespace: impl Into<String>,
+	) -> Self {
+		Self {
+			model,
+			cache: Arc::new(cache),
+			namespace: namespace.into(),
+		}
+	}
+
+	/// The wrapped model
+	pub fn inner(&self) -> &M {
+		&self.model
+	}
+}
+
+impl<M> CompletionModel for CachedModel<M>
+where
+	M: CompletionModel,
+{
+	type Response = M::Response;
+	type StreamingResponse = M::StreamingResponse;
+	type Client = M::Client;
+
+	/// Caches the completions of the model in a new [InMemoryCompletionCache], with the model
+	/// name as namespace.
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		let model = model.into();
+		Self::new(
+			M::make(client, model.clone()),
+			InMemoryCompletionCache::default(),
+			model,
+		)
+	}
+
+	async fn completion(
+		&self,
+		request: CompletionRequest,
+	) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+		let key = CacheKey::new(&self.namespace, &request);
+
+		if let Some(cached) = self.cache.get(&key) {
+			match serde_json::from_value(cached.raw_response) {
+				Ok(raw_response) => {
+					tracing::debug!(target: "clankers::completions", %key, "Completion served from cache");
+					return Ok(CompletionResponse {
+						choice: cached.choice,
+						usage: cached.usage,
+						raw_response,
+						response_metadata: None,
+					});
+				}
+				Err(error) => {
+					tracing::warn!(target: "clankers::completions", %key, %error, "Ignoring invalid cached completion");
+				}
+			}
+		}
+
+		let response = self.model.completion(request).await?;
+
+		match serde_json::to_value(&response.raw_response) {
+			Ok(raw_response) => self.cache.put(
+				key,
+				CachedCompletion {
+					choice: response.choice.clone(),
+					usage: response.usage,
+					raw_response,
+				},
+			),
+			Err(error) => {
+				tracing::warn!(target: "clankers::completions", %key, %error, "Completion not cached");
+			}
+		}
+
+		Ok(response)
+	}
+
+	/// Streamed requests bypass the cache.
+	async fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		self.model.stream(request).await
+	}
+
+	fn token_counter(&self) -> Arc<dyn TokenCounter> {
+		self.model.token_counter()
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.model.last_request()
+	}
+
+	fn validate_request(&self, request: &CompletionRequest, report: &mut ValidationReport) {
+		self.model.validate_request(request, report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agent::AgentBuilder;
+	use crate::completion::Prompt;
+	use crate::test_utils::MockCompletionModel;
+
+	fn cached(model: &MockCompletionModel) -> CachedModel<MockCompletionModel> {
+		model
+			.clone()
+			.with_cache(InMemoryCompletionCache::default(), "mock")
+	}
+
+	#[tokio::test]
+	async fn test_cache_hit_skips_the_model() {
+		let model = MockCompletionModel::with_responses([
+			OneOrMany::one(AssistantContent::text("Paris")),
+			OneOrMany::one(AssistantContent::text("Lyon")),
+		]);
+		let cached = cached(&model);
+
+		for _ in 0..2 {
+			let response = cached
+				.completion_request("Capital of France?")
+				.temperature(0.0)
+				.send()
+				.await
+				.unwrap();
+			assert_eq!(
+				response.choice,
+				OneOrMany::one(AssistantContent::text("Paris"))
+			);
+		}
+
+		assert_eq!(model.requests().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_different_temperature_misses() {
+		let model = MockCompletionModel::default();
+		let cached = cached(&model);
+
+		for temperature in [0.0, 0.5] {
+			cached
+				.completion_request("Capital of France?")
+				.temperature(temperature)
+				.send()
+				.await
+				.unwrap();
+		}
+
+		assert_eq!(model.requests().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_agent_with_cache() {
+		let model = MockCompletionModel::default();
+		let agent = AgentBuilder::new(model.clone())
+			.preamble("Answer briefly.")
+			.build()
+			.with_cache(InMemoryCompletionCache::default(), "mock");
+
+		agent.prompt("Capital of France?").await.unwrap();
+		agent.prompt("Capital of France?").await.unwrap();
+		agent.prompt("Capital of Italy?").await.unwrap();
+
+		assert_eq!(model.requests().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_models_sharing_a_cache_miss() {
+		let cache = Arc::new(InMemoryCompletionCache::default());
+		let gpt_4o = MockCompletionModel::default();
+		let gpt_4o_mini = MockCompletionModel::default();
+
+		for (model, name) in [(&gpt_4o, "gpt-4o"), (&gpt_4o_mini, "gpt-4o-mini")] {
+			model
+				.clone()
+				.with_cache(cache.clone(), name)
+				.completion_request("Capital of France?")
+				.temperature(0.0)
+				.send()
+				.await
+				.unwrap();
+		}
+
+		assert_eq!(gpt_4o.requests().len(), 1);
+		assert_eq!(gpt_4o_mini.requests().len(), 1);
+		assert_eq!(cache.len(), 2);
+	}
+
+	#[test]
+	fn test_cache_key_is_canonical() {
+		let request = |params: &str| {
+			CompletionRequest::builder("Hello")
+				.temperature(0.0)
+				.additional_params(serde_json::from_str(params).unwrap())
+				.build()
+		};
+
+		let key = CacheKey::new(
+			"model",
+			&request(r#"{"top_p": 1, "response_format": {"type": "json", "strict": true}}"#),
+		);
+
+		assert_eq!(
+			key,
+			CacheKey::new(
+				"model",
+				&request(r#"{"response_format": {"strict": true, "type": "json"}, "top_p": 1}"#),
+			)
+		);
+		assert_ne!(
+			key,
+			CacheKey::new(
+				"model",
+				&request(r#"{"top_p": 0.9, "response_format": {"type": "json", "strict": true}}"#),
+			)
+		);
+		assert_ne!(
+			key,
+			CacheKey::new(
+				"other-model",
+				&request(r#"{"top_p": 1, "response_format": {"type": "json", "strict": true}}"#),
+			)
+		);
+
+		let mut canonical = String::new();
+		write_canonical_json(
+			&serde_json::from_str(r#"{"b": [{"d": 1, "c": "x"}], "a": null}"#).unwrap(),
+			&mut canonical,
+		);
+		assert_eq!(canonical, r#"{"a":null,"b":[{"c":"x","d":1}]}"#);
+	}
+
+	#[test]
+	fn test_lru_eviction() {
+		let cache = InMemoryCompletionCache::new(2);
+		let completion = |text: &str| CachedCompletion {
+			choice: OneOrMany::one(AssistantContent::text(text)),
+			usage: Usage::new(),
+			raw_response: Value::Null,
+		};
+
+		cache.put(CacheKey(1), completion("one"));
+		cache.put(CacheKey(2), completion("two"));
+		// Using the first completion makes the second one the least recently used
+		assert!(cache.get(&CacheKey(1)).is_some());
+		cache.put(CacheKey(3), completion("three"));
+
+		assert_eq!(cache.len(), 2);
+		assert!(cache.get(&CacheKey(2)).is_none());
+		assert!(cache.get(&CacheKey(1)).is_some());
+		assert!(cache.get(&CacheKey(3)).is_some());
+	}
+}
@@ -9,6 +9,16 @@ use super::message::{
 use crate::OneOrMany;
 
 impl Message {
+	/// Fills in missing media types on the raw binary content of a user message.
+	/// See [`UserContent::sniff_media_type`].
+	pub(crate) fn sniff_media_types(&mut self) {
+		if let Message::User { content } = self {
+			for content in content.iter_mut() {
+				content.sniff_media_type();
+			}
+		}
+	}
+
 	/// This helper method is primarily used to extract the first string prompt from a `Message`.
 	/// Since `Message` might have more than just text content, we need to find the first text.
 	pub(crate) fn rag_text(&self) -> Option<String> {
@@ -25,6 +35,18 @@ impl Message {
 		}
 	}
 
+	/// Whether the message only contains blank text, e.g. an empty prompt.
+	pub(crate) fn is_empty(&self) -> bool {
+		match self {
+			Message::User { content } => content.iter().all(
+				|item| matches!(item, UserContent::Text(Text { text }) if text.trim().is_empty()),
+			),
+			Message::Assistant { content, .. } => content.iter().all(
+				|item| matches!(item, AssistantContent::Text(Text { text }) if text.trim().is_empty()),
+			),
+		}
+	}
+
 	/// Helper constructor to make creating user messages easier.
 	pub fn user(text: impl Into<String>) -> Self {
 		Message::User {
@@ -74,7 +96,67 @@ impl Message {
 	}
 }
 
+impl Image {
+	/// Fills in a missing media type on raw image data by sniffing its magic bytes.
+	pub fn sniff_media_type(&mut self) {
+		if let Image {
+			data: DocumentSourceKind::Raw(data),
+			media_type: media_type @ None,
+			..
+		} = self && let Some(MediaType::Image(sniffed)) = MediaType::sniff(data)
+		{
+			*media_type = Some(sniffed);
+		}
+	}
+}
+
+impl Audio {
+	/// Fills in a missing media type on raw audio data by sniffing its magic bytes.
+	pub fn sniff_media_type(&mut self) {
+		if let Audio {
+			data: DocumentSourceKind::Raw(data),
+			media_type: media_type @ None,
+			..
+		} = self && let Some(MediaType::Audio(sniffed)) = MediaType::sniff(data)
+		{
+			*media_type = Some(sniffed);
+		}
+	}
+}
+
+impl Document {
+	/// Fills in a missing media type on raw document data by sniffing its magic bytes.
+	pub fn sniff_media_type(&mut self) {
+		if let Document {
+			data: DocumentSourceKind::Raw(data),
+			media_type: media_type @ None,
+			..
+		} = self && let Some(MediaType::Document(sniffed)) = MediaType::sniff(data)
+		{
+			*media_type = Some(sniffed);
+		}
+	}
+}
+
 impl UserContent {
+	/// Fills in a missing media type on raw image, audio or document data (including images
+	/// inside tool results) by sniffing its magic bytes. See [`MediaType::sniff`].
+	pub fn sniff_media_type(&mut self) {
+		match self {
+			UserContent::Image(image) => image.sniff_media_type(),
+			UserContent::Audio(audio) => audio.sniff_media_type(),
+			UserContent::Document(document) => document.sniff_media_type(),
+			UserContent::ToolResult(ToolResult { content, .. }) => {
+				for content in content.iter_mut() {
+					if let ToolResultContent::Image(image) = content {
+						image.sniff_media_type();
+					}
+				}
+			}
+			_ => {}
+		}
+	}
+
 	/// Helper constructor to make creating user text content easier.
 	pub fn text(text: impl Into<String>) -> Self {
 		UserContent::Text(text.into().into())
@@ -100,12 +182,14 @@ impl UserContent {
 		media_type: Option<ImageMediaType>,
 		detail: Option<ImageDetail>,
 	) -> Self {
-		UserContent::Image(Image {
+		let mut content = UserContent::Image(Image {
 			data: DocumentSourceKind::Raw(data.into()),
 			media_type,
 			detail,
 			..Default::default()
-		})
+		});
+		content.sniff_media_type();
+		content
 	}
 
 	/// Helper constructor to make creating user image content easier.
@@ -133,11 +217,13 @@ impl UserContent {
 
 	/// Helper constructor to make creating user audio content from raw unencoded bytes easier.
 	pub fn audio_raw(data: impl Into<Vec<u8>>, media_type: Option<AudioMediaType>) -> Self {
-		UserContent::Audio(Audio {
+		let mut content = UserContent::Audio(Audio {
 			data: DocumentSourceKind::Raw(data.into()),
 			media_type,
 			..Default::default()
-		})
+		});
+		content.sniff_media_type();
+		content
 	}
 
 	/// Helper to create an audio resource from a URL
@@ -162,11 +248,13 @@ impl UserContent {
 
 	/// Helper to create a document from raw unencoded bytes
 	pub fn document_raw(data: impl Into<Vec<u8>>, media_type: Option<DocumentMediaType>) -> Self {
-		UserContent::Document(Document {
+		let mut content = UserContent::Document(Document {
 			data: DocumentSourceKind::Raw(data.into()),
 			media_type,
 			..Default::default()
-		})
+		});
+		content.sniff_media_type();
+		content
 	}
 
 	/// Helper to create a document from a URL
@@ -285,12 +373,14 @@ impl ToolResultContent {
 		media_type: Option<ImageMediaType>,
 		detail: Option<ImageDetail>,
 	) -> Self {
-		ToolResultContent::Image(Image {
+		let mut image = Image {
 			data: DocumentSourceKind::Raw(data.into()),
 			media_type,
 			detail,
 			..Default::default()
-		})
+		};
+		image.sniff_media_type();
+		ToolResultContent::Image(image)
 	}
 
 	/// Helper constructor to make tool result images from a URL.
@@ -315,6 +405,9 @@ impl ToolResultContent {
 	/// 3. Hybrid JSON: `{"response": {...}, "parts": [...]}` → `OneOrMany::many([Text, Image, ...])`
 	///
 	/// If JSON parsing fails, treats the entire string as text.
+	///
+	/// This is how the default [Tool::into_tool_result](crate::tool::Tool::into_tool_result)
+	/// converts tool outputs. New tools should return images by overriding it instead.
 	pub fn from_tool_output(output: impl Into<String>) -> OneOrMany<ToolResultContent> {
 		let output_str = output.into();
 
@@ -423,6 +516,79 @@ impl MimeType for MediaType {
 	}
 }
 
+impl MediaType {
+	/// Detects the media type of raw data from its leading magic bytes.
+	///
+	/// Recognizes PNG, JPEG, GIF and WEBP images, PDF documents, and WAV, MP3, AIFF, OGG and
+	/// FLAC audio. Returns `None` for anything else.
+	pub fn sniff(data: &[u8]) -> Option<Self> {
+		Some(match data {
+			[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n', ..] => {
+				MediaType::Image(ImageMediaType::PNG)
+			}
+			[0xFF, 0xD8, 0xFF, ..] => MediaType::Image(ImageMediaType::JPEG),
+			[b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => {
+				MediaType::Image(ImageMediaType::GIF)
+			}
+			[
+				b'R',
+				b'I',
+				b'F',
+				b'F',
+				_,
+				_,
+				_,
+				_,
+				b'W',
+				b'E',
+				b'B',
+				b'P',
+				..,
+			] => MediaType::Image(ImageMediaType::WEBP),
+			[b'%', b'P', b'D', b'F', b'-', ..] => MediaType::Document(DocumentMediaType::PDF),
+			[
+				b'R',
+				b'I',
+				b'F',
+				b'F',
+				_,
+				_,
+				_,
+				_,
+				b'W',
+				b'A',
+				b'V',
+				b'E',
+				..,
+			] => MediaType::Audio(AudioMediaType::WAV),
+			[
+				b'F',
+				b'O',
+				b'R',
+				b'M',
+				_,
+				_,
+				_,
+				_,
+				b'A',
+				b'I',
+				b'F',
+				b'F' | b'C',
+				..,
+			] => MediaType::Audio(AudioMediaType::AIFF),
+			[b'O', b'g', b'g', b'S', ..] => MediaType::Audio(AudioMediaType::OGG),
+			[b'f', b'L', b'a', b'C', ..] => MediaType::Audio(AudioMediaType::FLAC),
+			// MP3 either starts with an ID3 tag or directly with an MPEG audio frame sync
+			// (11 set bits followed by a non-reserved layer).
+			[b'I', b'D', b'3', ..] => MediaType::Audio(AudioMediaType::MP3),
+			[0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 != 0 => {
+				MediaType::Audio(AudioMediaType::MP3)
+			}
+			_ => return None,
+		})
+	}
+}
+
 impl MimeType for ImageMediaType {
 	fn from_mime_type(mime_type: &str) -> Option<Self> {
 		match mime_type {
@@ -700,3 +866,92 @@ impl From<ToolResultContent> for Message {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rag_text_of_multi_part_message() {
+		let image = UserContent::image_url("https://example.com/cat.png", None, None);
+		let message = Message::User {
+			content: OneOrMany::many([image.clone(), UserContent::text("What is this?")]).unwrap(),
+		};
+
+		assert_eq!(message.rag_text().as_deref(), Some("What is this?"));
+		assert_eq!(Message::from(image).rag_text(), None);
+	}
+
+	#[test]
+	fn test_sniff_media_types() {
+		let cases: &[(&[u8], MediaType)] = &[
+			(
+				b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR",
+				MediaType::Image(ImageMediaType::PNG),
+			),
+			(
+				b"\xff\xd8\xff\xe0\0\x10JFIF",
+				MediaType::Image(ImageMediaType::JPEG),
+			),
+			(b"GIF87a\x01\0", MediaType::Image(ImageMediaType::GIF)),
+			(b"GIF89a\x01\0", MediaType::Image(ImageMediaType::GIF)),
+			(
+				b"RIFF\x24\0\0\0WEBPVP8 ",
+				MediaType::Image(ImageMediaType::WEBP),
+			),
+			(
+				b"%PDF-1.7\n%\xe2\xe3",
+				MediaType::Document(DocumentMediaType::PDF),
+			),
+			(
+				b"RIFF\x24\0\0\0WAVEfmt ",
+				MediaType::Audio(AudioMediaType::WAV),
+			),
+			(b"ID3\x04\0\0", MediaType::Audio(AudioMediaType::MP3)),
+			(b"\xff\xfb\x90\x64", MediaType::Audio(AudioMediaType::MP3)),
+			(
+				b"FORM\0\0\0\x24AIFFCOMM",
+				MediaType::Audio(AudioMediaType::AIFF),
+			),
+			(b"OggS\0\x02", MediaType::Audio(AudioMediaType::OGG)),
+			(b"fLaC\0\0\0\x22", MediaType::Audio(AudioMediaType::FLAC)),
+		];
+
+		for (data, expected) in cases {
+			assert_eq!(MediaType::sniff(data).as_ref(), Some(expected), "{data:?}");
+		}
+	}
+
+	#[test]
+	fn test_sniff_rejects_unknown_data() {
+		assert_eq!(MediaType::sniff(b""), None);
+		assert_eq!(MediaType::sniff(b"hello world"), None);
+		assert_eq!(MediaType::sniff(b"RIFF\x24\0\0\0AVI LIST"), None);
+		// An AAC ADTS header shares the frame sync but has a reserved layer
+		assert_eq!(MediaType::sniff(b"\xff\xf1\x50\x80"), None);
+	}
+
+	#[test]
+	fn test_raw_constructors_sniff_missing_media_type() {
+		let UserContent::Document(document) = UserContent::document_raw(b"%PDF-1.4".to_vec(), None)
+		else {
+			panic!("expected a document");
+		};
+		assert_eq!(document.media_type, Some(DocumentMediaType::PDF));
+
+		// An explicit media type is never overridden
+		let UserContent::Audio(audio) =
+			UserContent::audio_raw(b"ID3\x04".to_vec(), Some(AudioMediaType::WAV))
+		else {
+			panic!("expected audio");
+		};
+		assert_eq!(audio.media_type, Some(AudioMediaType::WAV));
+
+		// Data of another kind is not forced into the wrong media type
+		let UserContent::Image(image) = UserContent::image_raw(b"%PDF-1.4".to_vec(), None, None)
+		else {
+			panic!("expected an image");
+		};
+		assert_eq!(image.media_type, None);
+	}
+}
@@ -0,0 +1,346 @@
+//! Default parameters for completion requests, set once instead of at every call site.
+//!
+//! Defaults can be set on a client with [ClientBuilder::default_params](crate::client::ClientBuilder::default_params)
+//! and on a model with [CompletionModel::with_default_temperature] and friends. They are merged
+//! under the parameters of each request before it is converted to the provider request, so they
+//! show up in the request logged at `TRACE` level. The request wins over the model defaults,
+//! which win over the client defaults.
+//!
+//! # Example
+//! ```rust
+//! use clankers::client::CompletionClient;
+//! use clankers::completion::CompletionModel;
+//! use clankers::providers::openai;
+//! use serde_json::json;
+//!
+//! let openai = openai::Client::builder()
+//!     .api_key("sk-...")
+//!     .default_params(json!({ "temperature": 0.2, "top_p": 0.9 }))
+//!     .build()
+//!     .unwrap();
+//!
+//! let model = openai
+//!     .completion_model(openai::GPT_4O)
+//!     .with_default_temperature(0.0);
+//! ```
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use super::{
+	CompletionError, CompletionModel, CompletionRequest, CompletionResponse, TokenCounter,
+	ValidationReport,
+};
+use crate::json_utils::{self, ArrayMergeStrategy};
+use crate::streaming::StreamingCompletionResponse;
+
+/// Parameters used when a completion request doesn't set them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestDefaults {
+	/// Used when the request has no temperature
+	pub temperature: Option<f64>,
+	/// Used when the request has no max tokens
+	pub max_tokens: Option<u64>,
+	/// Deep merged under the additional parameters of the request
+	pub additional_params: Option<Value>,
+}
+
+impl RequestDefaults {
+	/// Splits `params` into defaults, `temperature` and `max_tokens` set the matching fields of
+	/// the request while every other key becomes an additional parameter.
+	pub fn from_params(params: Value) -> Self {
+		let Value::Object(mut params) = params else {
+			return Self {
+				additional_params: Some(params),
+				..Self::default()
+			};
+		};
+
+		let temperature = params.get("temperature").and_then(Value::as_f64);
+		if temperature.is_some() {
+			params.remove("temperature");
+		}
+		let max_tokens = params.get("max_tokens").and_then(Value::as_u64);
+		if max_tokens.is_some() {
+			params.remove("max_tokens");
+		}
+
+		Self {
+			temperature,
+			max_tokens,
+			additional_params: (!params.is_empty()).then_some(Value::Object(params)),
+		}
+	}
+
+	pub fn temperature(mut self, temperature: f64) -> Self {
+		self.temperature = Some(temperature);
+		self
+	}
+
+	pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+		self.max_tokens = Some(max_tokens);
+		self
+	}
+
+	/// Merges `params` over the current defaults, see [RequestDefaults::from_params].
+	pub fn params(self, params: Value) -> Self {
+		self.merge(Self::from_params(params))
+	}
+
+	/// Combines two sets of defaults, `other` taking precedence.
+	pub fn merge(self, other: Self) -> Self {
+		Self {
+			temperature: other.temperature.or(self.temperature),
+			max_tokens: other.max_tokens.or(self.max_tokens),
+			additional_params: merge_params(self.additional_params, other.additional_params),
+		}
+	}
+
+	/// Fills in the parameters `request` doesn't set.
+	pub fn apply(&self, mut request: CompletionRequest) -> CompletionRequest {
+		request.temperature = request.temperature.or(self.temperature);
+		request.max_tokens = request.max_tokens.or(self.max_tokens);
+		request.additional_params =
+			merge_params(self.additional_params.clone(), request.additional_params);
+		request
+	}
+}
+
+fn merge_params(defaults: Option<Value>, params: Option<Value>) -> Option<Value> {
+	match (defaults, params) {
+		(Some(defaults), Some(params)) => Some(json_utils::deep_merge(
+			defaults,
+			params,
+			ArrayMergeStrategy::Replace,
+		)),
+		(defaults, params) => params.or(defaults),
+	}
+}
+
+/// A completion model applying [RequestDefaults] to every request, see
+/// [CompletionModel::with_default_params].
+#[derive(Clone, Debug)]
+pub struct DefaultParamsModel<M> {
+	model: M,
+	defaults: Arc<RequestDefaults>,
+}
+
+impl<M> DefaultParamsModel<M> {
+	pub fn new(model: M, defaults: RequestDefaults) -> Self {
+		Self {
+			model,
+			defaults: Arc::new(defaults),
+		}
+	}
+
+	/// Sets the default temperature, replacing the previous one.
+	pub fn with_default_temperature(self, temperature: f64) -> Self {
+		self.map_defaults(|defaults| defaults.temperature(temperature))
+	}
+
+	/// Sets the default max tokens, replacing the previous one.
+	pub fn with_default_max_tokens(self, max_tokens: u64) -> Self {
+		self.map_defaults(|defaults| defaults.max_tokens(max_tokens))
+	}
+
+	/// Merges `params` over the current defaults, see [RequestDefaults::from_params].
+	pub fn with_default_params(self, params: Value) -> Self {
+		self.map_defaults(|defaults| defaults.params(params))
+	}
+
+	/// The defaults applied to requests
+	pub fn defaults(&self) -> &RequestDefaults {
+		&self.defaults
+	}
+
+	/// The wrapped model
+	pub fn inner(&self) -> &M {
+		&self.model
+	}
+
+	fn map_defaults(self, f: impl FnOnce(RequestDefaults) -> RequestDefaults) -> Self {
+		Self {
+			defaults: Arc::new(f(Arc::unwrap_or_clone(self.defaults))),
+			model: self.model,
+		}
+	}
+}
+
+impl<M> CompletionModel for DefaultParamsModel<M>
+where
+	M: CompletionModel,
+{
+	type Response = M::Response;
+	type StreamingResponse = M::StreamingResponse;
+	type Client = M::Client;
+
+	/// Wraps the model without any default.
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		Self::new(M::make(client, model), RequestDefaults::default())
+	}
+
+	async fn completion(
+		&self,
+		request: CompletionRequest,
+	) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+		self.model.completion(self.defaults.apply(request)).await
+	}
+
+	async fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		self.model.stream(self.defaults.apply(request)).await
+	}
+
+	fn token_counter(&self) -> Arc<dyn TokenCounter> {
+		self.model.token_counter()
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.model.last_request()
+	}
+
+	fn validate_request(&self, request: &CompletionRequest, report: &mut ValidationReport) {
+		self.model
+			.validate_request(&self.defaults.apply(request.clone()), report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::StreamExt;
+	use serde_json::json;
+
+	use super::*;
+	use crate::client::CompletionClient;
+	use crate::providers::openai;
+	use crate::test_utils::{MockCompletionModel, MockSseClient};
+
+	const RESPONSE: &str = r#"{
+		"id": "chatcmpl-1",
+		"object": "chat.completion",
+		"created": 1741290958,
+		"model": "gpt-4o",
+		"choices": [{
+			"index": 0,
+			"message": { "role": "assistant", "content": "Hello!" },
+			"finish_reason": "stop"
+		}],
+		"usage": { "prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7 }
+	}"#;
+
+	fn client(http_client: MockSseClient, params: Value) -> openai::Client<MockSseClient> {
+		openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.default_params(params)
+			.build()
+			.unwrap()
+	}
+
+	#[test]
+	fn test_from_params_splits_request_fields() {
+		let defaults = RequestDefaults::from_params(json!({
+			"temperature": 0.2,
+			"max_tokens": 512,
+			"top_p": 0.9,
+		}));
+
+		assert_eq!(defaults.temperature, Some(0.2));
+		assert_eq!(defaults.max_tokens, Some(512));
+		assert_eq!(defaults.additional_params, Some(json!({ "top_p": 0.9 })));
+	}
+
+	#[tokio::test]
+	async fn test_request_wins_over_model_defaults() {
+		let mock = MockCompletionModel::default();
+		let model = mock
+			.clone()
+			.with_default_temperature(0.2)
+			.with_default_params(json!({ "reasoning": { "effort": "low", "summary": "auto" } }));
+
+		model.completion_request("Hello").send().await.unwrap();
+		model
+			.completion_request("Hello")
+			.temperature(0.7)
+			.additional_params(json!({ "reasoning": { "effort": "high" } }))
+			.send()
+			.await
+			.unwrap();
+
+		let requests = mock.requests();
+		assert_eq!(requests[0].temperature, Some(0.2));
+		assert_eq!(
+			requests[0].additional_params,
+			Some(json!({ "reasoning": { "effort": "low", "summary": "auto" } }))
+		);
+		assert_eq!(requests[1].temperature, Some(0.7));
+		assert_eq!(
+			requests[1].additional_params,
+			Some(json!({ "reasoning": { "effort": "high", "summary": "auto" } }))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_client_defaults_precedence() {
+		let http_client =
+			MockSseClient::default().with_json_responses([RESPONSE, RESPONSE, RESPONSE, RESPONSE]);
+		let client = client(
+			http_client.clone(),
+			json!({
+				"temperature": 0.1,
+				"top_p": 0.5,
+				"metadata": { "team": "search", "env": "dev" },
+			}),
+		);
+		let model = client.completion_model("gpt-4o").completions_api();
+
+		// Client defaults only
+		model.completion_request("Hello").send().await.unwrap();
+
+		// Model defaults override the client defaults
+		let model = model
+			.with_default_temperature(0.2)
+			.with_default_params(json!({ "metadata": { "env": "prod" } }));
+		model.completion_request("Hello").send().await.unwrap();
+
+		// Request values override both
+		model
+			.completion_request("Hello")
+			.temperature(0.3)
+			.additional_params(json!({ "top_p": 0.8, "metadata": { "team": "ads" } }))
+			.send()
+			.await
+			.unwrap();
+
+		// Streamed requests get the defaults too
+		let mut stream = model.completion_request("Hello").stream().await.unwrap();
+		while stream.next().await.is_some() {}
+
+		let bodies = http_client.request_bodies();
+		assert_eq!(bodies[0]["temperature"], 0.1);
+		assert_eq!(bodies[0]["top_p"], 0.5);
+		assert_eq!(
+			bodies[0]["metadata"],
+			json!({ "team": "search", "env": "dev" })
+		);
+
+		assert_eq!(bodies[1]["temperature"], 0.2);
+		assert_eq!(bodies[1]["top_p"], 0.5);
+		assert_eq!(
+			bodies[1]["metadata"],
+			json!({ "team": "search", "env": "prod" })
+		);
+
+		assert_eq!(bodies[2]["temperature"], 0.3);
+		assert_eq!(bodies[2]["top_p"], 0.8);
+		assert_eq!(
+			bodies[2]["metadata"],
+			json!({ "team": "ads", "env": "prod" })
+		);
+
+		assert_eq!(bodies[3]["temperature"], 0.2);
+		assert_eq!(bodies[3]["top_p"], 0.5);
+	}
+}
@@ -0,0 +1,92 @@
+//! Type-erased completion models, for storing models of different providers together.
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use super::{CompletionError, CompletionModel, CompletionRequest, CompletionResponse};
+use super::{GetTokenUsage, Usage};
+use crate::streaming::{StreamingCompletionResponse, StreamingResult};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
+
+/// The final response of a streamed completion, with the provider's response serialized to JSON.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErasedStreamingResponse {
+	/// The token usage reported by the provider's response
+	pub usage: Option<Usage>,
+	/// The provider's response
+	pub raw: serde_json::Value,
+}
+
+impl ErasedStreamingResponse {
+	pub fn new<R>(response: R) -> Result<Self, CompletionError>
+	where
+		R: Serialize + GetTokenUsage,
+	{
+		Ok(Self {
+			usage: response.token_usage(),
+			raw: serde_json::to_value(&response)?,
+		})
+	}
+}
+
+impl GetTokenUsage for ErasedStreamingResponse {
+	fn token_usage(&self) -> Option<Usage> {
+		self.usage
+	}
+}
+
+/// Type-erased [`CompletionModel`] for dynamic dispatch, with the raw responses serialized to
+/// JSON.
+pub trait CompletionModelDyn: WasmCompatSend + WasmCompatSync {
+	fn completion(
+		&self,
+		request: CompletionRequest,
+	) -> WasmBoxedFuture<'_, Result<CompletionResponse<serde_json::Value>, CompletionError>>;
+
+	fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> WasmBoxedFuture<
+		'_,
+		Result<StreamingCompletionResponse<ErasedStreamingResponse>, CompletionError>,
+	>;
+}
+
+impl<M> CompletionModelDyn for M
+where
+	M: CompletionModel + 'static,
+	M::StreamingResponse: 'static,
+{
+	fn completion(
+		&self,
+		request: CompletionRequest,
+	) -> WasmBoxedFuture<'_, Result<CompletionResponse<serde_json::Value>, CompletionError>> {
+		Box::pin(async move {
+			let response = CompletionModel::completion(self, request).await?;
+
+			Ok(CompletionResponse {
+				choice: response.choice,
+				usage: response.usage,
+				raw_response: serde_json::to_value(&response.raw_response)?,
+				response_metadata: response.response_metadata,
+			})
+		})
+	}
+
+	fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> WasmBoxedFuture<
+		'_,
+		Result<StreamingCompletionResponse<ErasedStreamingResponse>, CompletionError>,
+	> {
+		Box::pin(async move {
+			let stream = CompletionModel::stream(self, request)
+				.await?
+				.into_raw_stream()
+				.map(|choice| choice?.map_final_response(ErasedStreamingResponse::new));
+			let stream: StreamingResult<ErasedStreamingResponse> = Box::pin(stream);
+
+			Ok(StreamingCompletionResponse::stream(stream))
+		})
+	}
+}
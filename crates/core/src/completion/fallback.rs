@@ -0,0 +1,457 @@
+//! A completion model that falls back to other providers when one is unavailable.
+//!
+//! # Example
+//! ```rust
+//! use clankers::agent::AgentBuilder;
+//! use clankers::client::CompletionClient;
+//! use clankers::completion::FallbackModel;
+//! use clankers::providers::{anthropic, openai};
+//!
+//! let openai = openai::Client::from_env();
+//! let anthropic = anthropic::Client::from_env();
+//!
+//! // Requests go to OpenAI, and to Anthropic while OpenAI is overloaded or rate limited
+//! let model = FallbackModel::new()
+//!     .model("openai", openai.completion_model(openai::GPT_4O))
+//!     .model("anthropic", anthropic.completion_model(anthropic::CLAUDE_4_SONNET));
+//!
+//! let agent = AgentBuilder::new(model).build();
+//! ```
+use std::sync::Arc;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use super::dynamic::{self, ErasedStreamingResponse};
+use super::{
+	CompletionError, CompletionModel, CompletionRequest, CompletionResponse, GetTokenUsage, Usage,
+};
+use crate::http_client;
+use crate::streaming::{RawStreamingChoice, StreamingCompletionResponse, StreamingResult};
+
+/// A model that didn't serve a request, and why.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FallbackAttempt {
+	pub provider: String,
+	/// The error the model failed with, or why it was skipped
+	pub reason: String,
+}
+
+/// The raw response of a [FallbackModel], recording which provider served the request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FallbackResponse<R> {
+	/// The provider that served the request
+	pub provider: String,
+	/// The models tried before, in order
+	pub attempts: Vec<FallbackAttempt>,
+	/// The raw response of the provider
+	pub response: R,
+}
+
+impl<R> GetTokenUsage for FallbackResponse<R>
+where
+	R: GetTokenUsage,
+{
+	fn token_usage(&self) -> Option<Usage> {
+		self.response.token_usage()
+	}
+}
+
+#[derive(Clone)]
+struct FallbackEntry {
+	provider: String,
+	model: Arc<dyn dynamic::CompletionModelDyn>,
+	supports_tools: bool,
+}
+
+impl FallbackEntry {
+	/// Why the model can't serve `request`, if it can't.
+	fn skip_reason(&self, request: &CompletionRequest) -> Option<String> {
+		if !self.supports_tools && (!request.tools.is_empty() || request.tool_choice.is_some()) {
+			return Some("tools are not supported".to_string());
+		}
+		None
+	}
+
+	fn attempt(&self, reason: impl ToString) -> FallbackAttempt {
+		FallbackAttempt {
+			provider: self.provider.clone(),
+			reason: reason.to_string(),
+		}
+	}
+}
+
+/// A completion model sending each request to a list of models in order, until one serves it.
+///
+/// The next model is only tried when the previous one failed with an error
+/// [FallbackModel::can_fall_back] accepts, other errors are returned right away. Streamed
+/// requests only fall back when the error happens before the first chunk is received. The
+/// request is passed to every model unchanged.
+#[derive(Clone, Default)]
+pub struct FallbackModel {
+	models: Vec<FallbackEntry>,
+}
+
+impl FallbackModel {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a model to try after the previous ones, `provider` names it in the
+	/// [FallbackResponse].
+	pub fn model<M>(self, provider: impl Into<String>, model: M) -> Self
+	where
+		M: CompletionModel + 'static,
+		M::StreamingResponse: 'static,
+	{
+		self.push(provider.into(), Arc::new(model), true)
+	}
+
+	/// Adds a model that doesn't support tool calling, skipped for requests with tools.
+	pub fn model_without_tools<M>(self, provider: impl Into<String>, model: M) -> Self
+	where
+		M: CompletionModel + 'static,
+		M::StreamingResponse: 'static,
+	{
+		self.push(provider.into(), Arc::new(model), false)
+	}
+
+	fn push(
+		mut self,
+		provider: String,
+		model: Arc<dyn dynamic::CompletionModelDyn>,
+		supports_tools: bool,
+	) -> Self {
+		self.models.push(FallbackEntry {
+			provider,
+			model,
+			supports_tools,
+		});
+		self
+	}
+
+	/// Whether the next model is tried after `error`: when the provider is rate limited or
+	/// overloaded, or couldn't be reached at all.
+	pub fn can_fall_back(error: &CompletionError) -> bool {
+		match error {
+			CompletionError::ApiError(error) => error.kind.is_retryable(),
+			CompletionError::HttpError(http_client::Error::Instance(_)) => true,
+			_ => false,
+		}
+	}
+
+	fn fall_back(entry: &FallbackEntry, error: &CompletionError) {
+		tracing::warn!(
+			target: "clankers::completions",
+			"Falling back from {}: {error}",
+			entry.provider
+		);
+	}
+}
+
+/// The error returned when every model was skipped.
+fn no_model_error(attempts: &[FallbackAttempt]) -> CompletionError {
+	let reasons = attempts
+		.iter()
+		.map(|attempt| format!("{}: {}", attempt.provider, attempt.reason))
+		.collect::<Vec<_>>();
+
+	CompletionError::RequestError(
+		format!("No model can serve the request ({})", reasons.join(", ")).into(),
+	)
+}
+
+impl CompletionModel for FallbackModel {
+	type Response = FallbackResponse<serde_json::Value>;
+	type StreamingResponse = FallbackResponse<ErasedStreamingResponse>;
+	/// Fallback models are built from models of other clients, see [FallbackModel::model]
+	type Client = ();
+
+	fn make(_: &Self::Client, _: impl Into<String>) -> Self {
+		Self::new()
+	}
+
+	async fn completion(
+		&self,
+		request: CompletionRequest,
+	) -> Result<CompletionResponse<Self::Response>, CompletionError> {
+		let mut attempts = Vec::new();
+		let mut last_error = None;
+
+		for entry in &self.models {
+			if let Some(reason) = entry.skip_reason(&request) {
+				attempts.push(entry.attempt(reason));
+				continue;
+			}
+
+			match entry.model.completion(request.clone()).await {
+				Ok(response) => {
+					return Ok(CompletionResponse {
+						choice: response.choice,
+						usage: response.usage,
+						raw_response: FallbackResponse {
+							provider: entry.provider.clone(),
+							attempts,
+							response: response.raw_response,
+						},
+						response_metadata: response.response_metadata,
+					});
+				}
+				Err(error) if Self::can_fall_back(&error) => {
+					Self::fall_back(entry, &error);
+					attempts.push(entry.attempt(&error));
+					last_error = Some(error);
+				}
+				Err(error) => return Err(error),
+			}
+		}
+
+		Err(last_error.unwrap_or_else(|| no_model_error(&attempts)))
+	}
+
+	async fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let mut attempts = Vec::new();
+		let mut last_error = None;
+
+		for entry in &self.models {
+			if let Some(reason) = entry.skip_reason(&request) {
+				attempts.push(entry.attempt(reason));
+				continue;
+			}
+
+			let mut stream = match entry.model.stream(request.clone()).await {
+				Ok(response) => response.into_raw_stream(),
+				Err(error) if Self::can_fall_back(&error) => {
+					Self::fall_back(entry, &error);
+					attempts.push(entry.attempt(&error));
+					last_error = Some(error);
+					continue;
+				}
+				Err(error) => return Err(error),
+			};
+
+			// Wait for the first chunk, the response metadata yielded when the stream opens
+			// and the usage reported before any content don't count
+			let mut metadata = Vec::new();
+			let first = loop {
+				match stream.next().await {
+					Some(Ok(
+						choice @ (RawStreamingChoice::ResponseMetadata(_)
+						| RawStreamingChoice::Usage(_)),
+					)) => metadata.push(Ok(choice)),
+					first => break first,
+				}
+			};
+
+			match first {
+				Some(Err(error)) if Self::can_fall_back(&error) => {
+					Self::fall_back(entry, &error);
+					attempts.push(entry.attempt(&error));
+					last_error = Some(error);
+				}
+				first => {
+					let provider = entry.provider.clone();
+					let stream = futures::stream::iter(metadata.into_iter().chain(first))
+						.chain(stream)
+						.map(move |choice| {
+							choice?.map_final_response(|response| {
+								Ok(FallbackResponse {
+									provider: provider.clone(),
+									attempts: attempts.clone(),
+									response,
+								})
+							})
+						});
+					let stream: StreamingResult<Self::StreamingResponse> = Box::pin(stream);
+
+					return Ok(StreamingCompletionResponse::stream(stream));
+				}
+			}
+		}
+
+		Err(last_error.unwrap_or_else(|| no_model_error(&attempts)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use http::StatusCode;
+
+	use super::*;
+	use crate::OneOrMany;
+	use crate::completion::{ApiError, AssistantContent, ToolDefinition};
+	use crate::test_utils::MockCompletionModel;
+
+	/// A model whose API is always unavailable. When streaming, the error is raised after
+	/// `chunks` text chunks.
+	#[derive(Clone, Default)]
+	struct UnavailableModel {
+		chunks: usize,
+	}
+
+	fn unavailable() -> CompletionError {
+		CompletionError::ApiError(ApiError::new(
+			StatusCode::SERVICE_UNAVAILABLE,
+			"upstream connect error",
+		))
+	}
+
+	impl CompletionModel for UnavailableModel {
+		type Response = ();
+		type StreamingResponse = ();
+		type Client = ();
+
+		fn make(_: &Self::Client, _: impl Into<String>) -> Self {
+			Self::default()
+		}
+
+		async fn completion(
+			&self,
+			_: CompletionRequest,
+		) -> Result<CompletionResponse<()>, CompletionError> {
+			Err(unavailable())
+		}
+
+		async fn stream(
+			&self,
+			_: CompletionRequest,
+		) -> Result<StreamingCompletionResponse<()>, CompletionError> {
+			let chunks = (0..self.chunks)
+				.map(|_| Ok(RawStreamingChoice::Message("partial".to_string())))
+				.chain([Err(unavailable())]);
+			let stream: StreamingResult<()> = Box::pin(futures::stream::iter(chunks));
+
+			Ok(StreamingCompletionResponse::stream(stream))
+		}
+	}
+
+	fn backup() -> MockCompletionModel {
+		MockCompletionModel::with_responses([
+			OneOrMany::one(AssistantContent::text("from backup")),
+			OneOrMany::one(AssistantContent::text("from backup")),
+		])
+	}
+
+	#[tokio::test]
+	async fn test_completion_falls_back_on_unavailable_provider() {
+		let backup = backup();
+		let model = FallbackModel::new()
+			.model("openai", UnavailableModel::default())
+			.model("anthropic", backup.clone());
+
+		let request = model.completion_request("Hello").temperature(0.3).build();
+		let response = model.completion(request).await.unwrap();
+
+		assert_eq!(
+			response.choice,
+			OneOrMany::one(AssistantContent::text("from backup"))
+		);
+		assert_eq!(response.raw_response.provider, "anthropic");
+		assert_eq!(response.raw_response.attempts.len(), 1);
+		assert_eq!(response.raw_response.attempts[0].provider, "openai");
+		assert!(
+			response.raw_response.attempts[0]
+				.reason
+				.contains("upstream connect error")
+		);
+		// The request is passed through unchanged
+		assert_eq!(backup.requests()[0].temperature, Some(0.3));
+	}
+
+	#[tokio::test]
+	async fn test_completion_returns_last_error_when_every_model_fails() {
+		let model = FallbackModel::new()
+			.model("openai", UnavailableModel::default())
+			.model("azure", UnavailableModel::default());
+
+		let error = model
+			.completion(model.completion_request("Hello").build())
+			.await
+			.unwrap_err();
+
+		assert!(FallbackModel::can_fall_back(&error));
+	}
+
+	#[tokio::test]
+	async fn test_models_without_tools_are_skipped() {
+		let tool = ToolDefinition {
+			name: "add".to_string(),
+			description: "Add two numbers".to_string(),
+			parameters: serde_json::json!({"type": "object"}),
+		};
+		let local = backup();
+		let model = FallbackModel::new()
+			.model_without_tools("ollama", local.clone())
+			.model("anthropic", backup());
+
+		let request = model.completion_request("Hello").tool(tool.clone()).build();
+		let response = model.completion(request).await.unwrap();
+
+		assert_eq!(response.raw_response.provider, "anthropic");
+		assert_eq!(
+			response.raw_response.attempts,
+			vec![FallbackAttempt {
+				provider: "ollama".to_string(),
+				reason: "tools are not supported".to_string(),
+			}]
+		);
+		assert!(local.requests().is_empty());
+
+		// Requests without tools are still served by the model
+		let response = model
+			.completion(model.completion_request("Hello").build())
+			.await
+			.unwrap();
+		assert_eq!(response.raw_response.provider, "ollama");
+
+		// Requests no model can serve are rejected
+		let model = FallbackModel::new().model_without_tools("ollama", local);
+		let error = model
+			.completion(model.completion_request("Hello").tool(tool).build())
+			.await
+			.unwrap_err();
+		assert!(
+			error
+				.to_string()
+				.contains("No model can serve the request (ollama: tools are not supported)")
+		);
+	}
+
+	#[tokio::test]
+	async fn test_stream_falls_back_before_first_chunk() {
+		let model = FallbackModel::new()
+			.model("openai", UnavailableModel::default())
+			.model("anthropic", backup());
+
+		let mut stream = model
+			.stream(model.completion_request("Hello").build())
+			.await
+			.unwrap();
+		let response = stream.final_response().await.unwrap();
+
+		assert_eq!(response.text, "from backup");
+		let response = response.response.unwrap();
+		assert_eq!(response.provider, "anthropic");
+		assert_eq!(response.attempts[0].provider, "openai");
+	}
+
+	#[tokio::test]
+	async fn test_stream_does_not_fall_back_after_first_chunk() {
+		let backup = backup();
+		let model = FallbackModel::new()
+			.model("openai", UnavailableModel { chunks: 1 })
+			.model("anthropic", backup.clone());
+
+		let mut stream = model
+			.stream(model.completion_request("Hello").build())
+			.await
+			.unwrap();
+
+		let error = stream.final_response().await.unwrap_err();
+		assert!(matches!(error, CompletionError::ApiError(_)));
+		assert!(backup.requests().is_empty());
+	}
+}
@@ -0,0 +1,824 @@
+//! Conversion of conversations to and from fine-tuning dataset formats: the chat format of
+//! OpenAI's fine-tuning JSONL files, and ShareGPT.
+//!
+//! Content without an equivalent in these text-only formats (images, audio, reasoning...) is
+//! left out of the exported examples and reported as [DroppedContent]. [write_jsonl] and
+//! [write_json_array] write the examples one at a time, without building the whole dataset in
+//! memory.
+//!
+//! ```
+//! use clankers::message::Message;
+//! use clankers::message::export::{Conversation, OpenAIExample, read_jsonl, write_jsonl};
+//!
+//! let conversation = Conversation::new(vec![Message::user("Hi"), Message::assistant("Hello!")])
+//!     .preamble("You are a helpful assistant.");
+//! let exported = conversation.to_openai();
+//! assert!(exported.dropped.is_empty());
+//!
+//! let mut file = Vec::new();
+//! write_jsonl(&mut file, [exported.example]).unwrap();
+//!
+//! let examples = read_jsonl::<OpenAIExample, _>(file.as_slice())
+//!     .collect::<Result<Vec<_>, _>>()
+//!     .unwrap();
+//! assert_eq!(
+//!     Conversation::from_openai(examples[0].clone()).unwrap(),
+//!     conversation
+//! );
+//! ```
+
+use std::io::{BufRead, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::{AssistantContent, Message, ToolCall, ToolFunction, ToolResultContent, UserContent};
+use crate::OneOrMany;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+	#[error("IoError: {0}")]
+	Io(#[from] std::io::Error),
+
+	#[error("JsonError: {0}")]
+	Json(#[from] serde_json::Error),
+
+	/// The example can't be converted to messages, e.g. a tool result without a tool call
+	#[error("InvalidExample: {0}")]
+	InvalidExample(String),
+}
+
+/// A conversation to export, e.g. the chat history of an agent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Conversation {
+	pub preamble: Option<String>,
+	pub messages: Vec<Message>,
+}
+
+/// An exported example, with the content that couldn't be represented in its format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exported<T> {
+	pub example: T,
+	pub dropped: Vec<DroppedContent>,
+}
+
+/// Content left out of an exported example.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DroppedContent {
+	/// The index of the message holding the content in [Conversation::messages]
+	pub message: usize,
+	pub kind: ContentKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+	Image,
+	Audio,
+	Video,
+	Document,
+	Reasoning,
+}
+
+/// A training example in the chat format of OpenAI's fine-tuning files, one per JSONL line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIExample {
+	pub messages: Vec<OpenAIMessage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum OpenAIMessage {
+	System {
+		content: String,
+	},
+	User {
+		content: String,
+	},
+	Assistant {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		content: Option<String>,
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		tool_calls: Vec<OpenAIToolCall>,
+	},
+	Tool {
+		tool_call_id: String,
+		content: String,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIToolCall {
+	pub id: String,
+	/// Always `function`
+	pub r#type: String,
+	pub function: OpenAIFunction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenAIFunction {
+	pub name: String,
+	/// The arguments serialized to a JSON string
+	pub arguments: String,
+}
+
+impl From<&ToolCall> for OpenAIToolCall {
+	fn from(call: &ToolCall) -> Self {
+		Self {
+			id: call.id.clone(),
+			r#type: "function".to_string(),
+			function: OpenAIFunction {
+				name: call.function.name.clone(),
+				arguments: call.function.arguments.to_string(),
+			},
+		}
+	}
+}
+
+impl From<OpenAIToolCall> for ToolCall {
+	fn from(call: OpenAIToolCall) -> Self {
+		ToolCall::new(
+			call.id,
+			ToolFunction::new(call.function.name, parse_arguments(call.function.arguments)),
+		)
+	}
+}
+
+/// A conversation in the ShareGPT format. Tool calls are `function_call` turns holding the
+/// call as `{"name": ..., "arguments": ...}`, or an array of calls for parallel calls, and
+/// their results are `observation` turns, holding an array of results for parallel calls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShareGptConversation {
+	pub conversations: Vec<ShareGptTurn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShareGptTurn {
+	pub from: ShareGptRole,
+	pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareGptRole {
+	System,
+	Human,
+	Gpt,
+	FunctionCall,
+	Observation,
+}
+
+impl ShareGptTurn {
+	fn new(from: ShareGptRole, value: impl Into<String>) -> Self {
+		Self {
+			from,
+			value: value.into(),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShareGptFunctionCall {
+	name: String,
+	arguments: serde_json::Value,
+}
+
+/// The parts of a message representable in the export formats
+struct Parts<'a> {
+	texts: Vec<&'a str>,
+	tool_calls: Vec<&'a ToolCall>,
+	tool_results: Vec<(&'a str, String)>,
+}
+
+impl<'a> Parts<'a> {
+	fn of(message: &'a Message, index: usize, dropped: &mut Vec<DroppedContent>) -> Self {
+		let mut parts = Parts {
+			texts: vec![],
+			tool_calls: vec![],
+			tool_results: vec![],
+		};
+		let mut flag = |kind| {
+			dropped.push(DroppedContent {
+				message: index,
+				kind,
+			})
+		};
+
+		match message {
+			Message::User { content } => {
+				for content in content.iter() {
+					match content {
+						UserContent::Text(text) => parts.texts.push(&text.text),
+						UserContent::ToolResult(result) => {
+							let mut texts = vec![];
+							for content in result.content.iter() {
+								match content {
+									ToolResultContent::Text(text) => texts.push(text.text.as_str()),
+									ToolResultContent::Image(_) => flag(ContentKind::Image),
+								}
+							}
+							parts.tool_results.push((&result.id, texts.join("\n")));
+						}
+						UserContent::Image(_) => flag(ContentKind::Image),
+						UserContent::Audio(_) => flag(ContentKind::Audio),
+						UserContent::Video(_) => flag(ContentKind::Video),
+						UserContent::Document(_) => flag(ContentKind::Document),
+					}
+				}
+			}
+			Message::Assistant { content, .. } => {
+				for content in content.iter() {
+					match content {
+						AssistantContent::Text(text) => parts.texts.push(&text.text),
+						AssistantContent::ToolCall(call) => parts.tool_calls.push(call),
+						AssistantContent::Reasoning(_) => flag(ContentKind::Reasoning),
+						AssistantContent::Image(_) => flag(ContentKind::Image),
+						AssistantContent::Document(_) => flag(ContentKind::Document),
+					}
+				}
+			}
+		}
+
+		parts
+	}
+
+	fn text(&self) -> Option<String> {
+		(!self.texts.is_empty()).then(|| self.texts.join("\n"))
+	}
+}
+
+impl Conversation {
+	pub fn new(messages: Vec<Message>) -> Self {
+		Self {
+			preamble: None,
+			messages,
+		}
+	}
+
+	pub fn preamble(mut self, preamble: impl Into<String>) -> Self {
+		self.preamble = Some(preamble.into());
+		self
+	}
+
+	/// Exports the conversation as an OpenAI fine-tuning example. Tool results become `tool`
+	/// messages, sent before the text of their user message.
+	pub fn to_openai(&self) -> Exported<OpenAIExample> {
+		let mut dropped = vec![];
+		let mut messages = vec![];
+		if let Some(preamble) = &self.preamble {
+			messages.push(OpenAIMessage::System {
+				content: preamble.clone(),
+			});
+		}
+
+		for (index, message) in self.messages.iter().enumerate() {
+			let parts = Parts::of(message, index, &mut dropped);
+			let content = parts.text();
+
+			match message {
+				Message::User { .. } => {
+					messages.extend(parts.tool_results.into_iter().map(|(id, content)| {
+						OpenAIMessage::Tool {
+							tool_call_id: id.to_string(),
+							content,
+						}
+					}));
+					if let Some(content) = content {
+						messages.push(OpenAIMessage::User { content });
+					}
+				}
+				Message::Assistant { .. } => {
+					let tool_calls = parts
+						.tool_calls
+						.into_iter()
+						.map(OpenAIToolCall::from)
+						.collect::<Vec<_>>();
+					if content.is_some() || !tool_calls.is_empty() {
+						messages.push(OpenAIMessage::Assistant {
+							content,
+							tool_calls,
+						});
+					}
+				}
+			}
+		}
+
+		Exported {
+			example: OpenAIExample { messages },
+			dropped,
+		}
+	}
+
+	/// Imports an OpenAI fine-tuning example. Consecutive `tool` messages become a single user
+	/// message, the results of parallel tool calls being sent together.
+	pub fn from_openai(example: OpenAIExample) -> Result<Self, ExportError> {
+		let mut conversation = Conversation::default();
+
+		for message in example.messages {
+			match message {
+				OpenAIMessage::System { content } => conversation.push_preamble(content),
+				OpenAIMessage::User { content } => {
+					conversation.messages.push(Message::user(content));
+				}
+				OpenAIMessage::Assistant {
+					content,
+					tool_calls,
+				} => {
+					let content = content.into_iter().map(AssistantContent::text).chain(
+						tool_calls
+							.into_iter()
+							.map(|call| AssistantContent::ToolCall(call.into())),
+					);
+					let content = OneOrMany::many(content).map_err(|_| {
+						ExportError::InvalidExample(
+							"Assistant message without content or tool calls".to_string(),
+						)
+					})?;
+					conversation
+						.messages
+						.push(Message::Assistant { id: None, content });
+				}
+				OpenAIMessage::Tool {
+					tool_call_id,
+					content,
+				} => conversation.push_tool_result(tool_call_id, content, true),
+			}
+		}
+
+		Ok(conversation)
+	}
+
+	/// Exports the conversation as a ShareGPT conversation. Tool call IDs aren't part of the
+	/// format, the results being matched to the calls by their order.
+	pub fn to_sharegpt(&self) -> Exported<ShareGptConversation> {
+		let mut dropped = vec![];
+		let mut turns = vec![];
+		if let Some(preamble) = &self.preamble {
+			turns.push(ShareGptTurn::new(ShareGptRole::System, preamble));
+		}
+
+		for (index, message) in self.messages.iter().enumerate() {
+			let parts = Parts::of(message, index, &mut dropped);
+			let content = parts.text();
+
+			match message {
+				Message::User { .. } => {
+					let results = parts
+						.tool_results
+						.into_iter()
+						.map(|(_, content)| content)
+						.collect::<Vec<_>>();
+					match results.as_slice() {
+						[] => {}
+						[result] => {
+							turns.push(ShareGptTurn::new(ShareGptRole::Observation, result))
+						}
+						results => turns.push(ShareGptTurn::new(
+							ShareGptRole::Observation,
+							serde_json::to_string(results).expect("strings serialize"),
+						)),
+					}
+					if let Some(content) = content {
+						turns.push(ShareGptTurn::new(ShareGptRole::Human, content));
+					}
+				}
+				Message::Assistant { .. } => {
+					if let Some(content) = content {
+						turns.push(ShareGptTurn::new(ShareGptRole::Gpt, content));
+					}
+					let calls = parts
+						.tool_calls
+						.into_iter()
+						.map(|call| ShareGptFunctionCall {
+							name: call.function.name.clone(),
+							arguments: call.function.arguments.clone(),
+						})
+						.collect::<Vec<_>>();
+					let value = match calls.as_slice() {
+						[] => continue,
+						[call] => serde_json::to_string(call),
+						calls => serde_json::to_string(calls),
+					};
+					turns.push(ShareGptTurn::new(
+						ShareGptRole::FunctionCall,
+						value.expect("function calls serialize"),
+					));
+				}
+			}
+		}
+
+		Exported {
+			example: ShareGptConversation {
+				conversations: turns,
+			},
+			dropped,
+		}
+	}
+
+	/// Imports a ShareGPT conversation. The tool calls are given the IDs `call_0`, `call_1`...
+	/// in order, and a `function_call` turn following a `gpt` turn is part of the same message.
+	pub fn from_sharegpt(conversation: ShareGptConversation) -> Result<Self, ExportError> {
+		let mut imported = Conversation::default();
+		let mut call_count = 0;
+		let mut pending_calls = vec![];
+
+		for turn in conversation.conversations {
+			match turn.from {
+				ShareGptRole::System => imported.push_preamble(turn.value),
+				ShareGptRole::Human => imported.messages.push(Message::user(turn.value)),
+				ShareGptRole::Gpt => imported.messages.push(Message::assistant(turn.value)),
+				ShareGptRole::FunctionCall => {
+					let calls = match serde_json::from_str(&turn.value)? {
+						serde_json::Value::Array(calls) => calls,
+						call => vec![call],
+					};
+					pending_calls.clear();
+					let mut tool_calls = vec![];
+					for call in calls {
+						let ShareGptFunctionCall { name, arguments } =
+							serde_json::from_value(call)?;
+						let id = format!("call_{call_count}");
+						call_count += 1;
+						pending_calls.push(id.clone());
+						tool_calls.push(AssistantContent::ToolCall(ToolCall::new(
+							id,
+							ToolFunction::new(name, arguments),
+						)));
+					}
+
+					match imported.messages.last_mut() {
+						Some(Message::Assistant { content, .. })
+							if !content.iter().any(|content| {
+								matches!(content, AssistantContent::ToolCall(_))
+							}) =>
+						{
+							tool_calls.into_iter().for_each(|call| content.push(call));
+						}
+						_ => {
+							let content = OneOrMany::many(tool_calls).map_err(|_| {
+								ExportError::InvalidExample(
+									"Function call turn without calls".to_string(),
+								)
+							})?;
+							imported
+								.messages
+								.push(Message::Assistant { id: None, content });
+						}
+					}
+				}
+				ShareGptRole::Observation => {
+					let results = match pending_calls.len() {
+						0 => {
+							return Err(ExportError::InvalidExample(
+								"Observation without a function call".to_string(),
+							));
+						}
+						1 => vec![turn.value],
+						count => {
+							let results: Vec<String> = serde_json::from_str(&turn.value)?;
+							if results.len() != count {
+								return Err(ExportError::InvalidExample(format!(
+									"Observation with {} results for {count} function calls",
+									results.len()
+								)));
+							}
+							results
+						}
+					};
+					for (index, (id, result)) in pending_calls.drain(..).zip(results).enumerate() {
+						imported.push_tool_result(id, result, index > 0);
+					}
+				}
+			}
+		}
+
+		Ok(imported)
+	}
+
+	fn push_preamble(&mut self, preamble: String) {
+		match &mut self.preamble {
+			Some(existing) => {
+				existing.push('\n');
+				existing.push_str(&preamble);
+			}
+			None => self.preamble = Some(preamble),
+		}
+	}
+
+	/// Appends a tool result, to the last message when `merge` is set and it only holds tool
+	/// results.
+	fn push_tool_result(&mut self, id: String, result: String, merge: bool) {
+		let result = UserContent::tool_result(id, OneOrMany::one(ToolResultContent::text(result)));
+
+		if merge
+			&& let Some(Message::User { content }) = self.messages.last_mut()
+			&& content
+				.iter()
+				.all(|content| matches!(content, UserContent::ToolResult(_)))
+		{
+			content.push(result);
+		} else {
+			self.messages.push(Message::User {
+				content: OneOrMany::one(result),
+			});
+		}
+	}
+}
+
+fn parse_arguments(arguments: String) -> serde_json::Value {
+	serde_json::from_str(&arguments).unwrap_or(serde_json::Value::String(arguments))
+}
+
+/// Writes `examples` as JSON lines, e.g. the [OpenAIExample]s of a fine-tuning file.
+pub fn write_jsonl<W, T>(
+	mut writer: W,
+	examples: impl IntoIterator<Item = T>,
+) -> Result<(), ExportError>
+where
+	W: Write,
+	T: Serialize,
+{
+	for example in examples {
+		serde_json::to_writer(&mut writer, &example)?;
+		writer.write_all(b"\n")?;
+	}
+	Ok(writer.flush()?)
+}
+
+/// Reads examples from JSON lines, one at a time, skipping blank lines.
+pub fn read_jsonl<T, R>(reader: R) -> impl Iterator<Item = Result<T, ExportError>>
+where
+	T: DeserializeOwned,
+	R: BufRead,
+{
+	reader
+		.lines()
+		.filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+		.map(|line| Ok(serde_json::from_str(&line?)?))
+}
+
+/// Writes `examples` as a JSON array, one at a time, e.g. the [ShareGptConversation]s of a
+/// dataset. The array can be read back with [serde_json::from_reader].
+pub fn write_json_array<W, T>(
+	mut writer: W,
+	examples: impl IntoIterator<Item = T>,
+) -> Result<(), ExportError>
+where
+	W: Write,
+	T: Serialize,
+{
+	writer.write_all(b"[")?;
+	for (index, example) in examples.into_iter().enumerate() {
+		if index > 0 {
+			writer.write_all(b",\n")?;
+		}
+		serde_json::to_writer(&mut writer, &example)?;
+	}
+	writer.write_all(b"]\n")?;
+	Ok(writer.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::message::{Image, Reasoning};
+
+	fn tool_call(id: &str, name: &str, arguments: serde_json::Value) -> AssistantContent {
+		AssistantContent::ToolCall(ToolCall::new(
+			id.to_string(),
+			ToolFunction::new(name.to_string(), arguments),
+		))
+	}
+
+	fn tool_result(id: &str, result: &str) -> UserContent {
+		UserContent::tool_result(id, OneOrMany::one(ToolResultContent::text(result)))
+	}
+
+	/// A weather lookup with parallel tool calls, then a single one
+	fn conversation() -> Conversation {
+		Conversation::new(vec![
+			Message::user("What's the weather in Paris and Rome?"),
+			Message::Assistant {
+				id: None,
+				content: OneOrMany::many([
+					AssistantContent::text("Let me check."),
+					tool_call("call_0", "get_weather", json!({ "city": "Paris" })),
+					tool_call("call_1", "get_weather", json!({ "city": "Rome" })),
+				])
+				.unwrap(),
+			},
+			Message::User {
+				content: OneOrMany::many([
+					tool_result("call_0", "Sunny, 24°C"),
+					tool_result("call_1", "Rainy, 18°C"),
+				])
+				.unwrap(),
+			},
+			Message::assistant("Paris is sunny, Rome is rainy."),
+			Message::user("And tomorrow in Paris?"),
+			Message::Assistant {
+				id: None,
+				content: OneOrMany::one(tool_call(
+					"call_2",
+					"get_forecast",
+					json!({ "city": "Paris", "days": 1 }),
+				)),
+			},
+			Message::User {
+				content: OneOrMany::one(tool_result("call_2", "Cloudy")),
+			},
+			Message::assistant("Cloudy tomorrow."),
+		])
+		.preamble("You are a weather bot.")
+	}
+
+	#[test]
+	fn test_openai_round_trip() {
+		let conversation = conversation();
+		let exported = conversation.to_openai();
+		assert!(exported.dropped.is_empty());
+
+		let example = serde_json::to_value(&exported.example).unwrap();
+		assert_eq!(
+			example["messages"][0],
+			json!({ "role": "system", "content": "You are a weather bot." })
+		);
+		assert_eq!(
+			example["messages"][2],
+			json!({
+				"role": "assistant",
+				"content": "Let me check.",
+				"tool_calls": [
+					{
+						"id": "call_0",
+						"type": "function",
+						"function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" }
+					},
+					{
+						"id": "call_1",
+						"type": "function",
+						"function": { "name": "get_weather", "arguments": "{\"city\":\"Rome\"}" }
+					}
+				]
+			})
+		);
+		assert_eq!(
+			example["messages"][4],
+			json!({ "role": "tool", "tool_call_id": "call_1", "content": "Rainy, 18°C" })
+		);
+		assert_eq!(
+			example["messages"][7],
+			json!({
+				"role": "assistant",
+				"tool_calls": [{
+					"id": "call_2",
+					"type": "function",
+					"function": {
+						"name": "get_forecast",
+						"arguments": "{\"city\":\"Paris\",\"days\":1}"
+					}
+				}]
+			})
+		);
+
+		let mut file = vec![];
+		write_jsonl(&mut file, [&exported.example, &exported.example]).unwrap();
+		let text = String::from_utf8(file.clone()).unwrap();
+		assert_eq!(text.lines().count(), 2);
+
+		let examples = read_jsonl::<OpenAIExample, _>(file.as_slice())
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+		assert_eq!(examples.len(), 2);
+		for example in examples {
+			assert_eq!(Conversation::from_openai(example).unwrap(), conversation);
+		}
+	}
+
+	#[test]
+	fn test_sharegpt_round_trip() {
+		let conversation = conversation();
+		let exported = conversation.to_sharegpt();
+		assert!(exported.dropped.is_empty());
+
+		let turns = &exported.example.conversations;
+		let roles = turns.iter().map(|turn| turn.from).collect::<Vec<_>>();
+		use ShareGptRole::*;
+		assert_eq!(
+			roles,
+			[
+				System,
+				Human,
+				Gpt,
+				FunctionCall,
+				Observation,
+				Gpt,
+				Human,
+				FunctionCall,
+				Observation,
+				Gpt
+			]
+		);
+		assert_eq!(
+			serde_json::from_str::<serde_json::Value>(&turns[3].value).unwrap(),
+			json!([
+				{ "name": "get_weather", "arguments": { "city": "Paris" } },
+				{ "name": "get_weather", "arguments": { "city": "Rome" } }
+			])
+		);
+		assert_eq!(turns[4].value, r#"["Sunny, 24°C","Rainy, 18°C"]"#);
+		assert_eq!(
+			serde_json::from_str::<serde_json::Value>(&turns[7].value).unwrap(),
+			json!({ "name": "get_forecast", "arguments": { "city": "Paris", "days": 1 } })
+		);
+		assert_eq!(turns[8].value, "Cloudy");
+
+		let mut file = vec![];
+		write_json_array(&mut file, [&exported.example, &exported.example]).unwrap();
+		let dataset: Vec<ShareGptConversation> = serde_json::from_slice(&file).unwrap();
+		assert_eq!(
+			serde_json::to_value(&dataset[0]).unwrap()["conversations"][1],
+			json!({ "from": "human", "value": "What's the weather in Paris and Rome?" })
+		);
+
+		for example in dataset {
+			assert_eq!(Conversation::from_sharegpt(example).unwrap(), conversation);
+		}
+	}
+
+	#[test]
+	fn test_unrepresentable_content_is_dropped() {
+		let conversation = Conversation::new(vec![
+			Message::User {
+				content: OneOrMany::many([
+					UserContent::text("What's in this image?"),
+					UserContent::Image(Image {
+						data: crate::message::DocumentSourceKind::url(
+							"https://example.com/cat.png",
+						),
+						media_type: None,
+						detail: None,
+						additional_params: None,
+					}),
+				])
+				.unwrap(),
+			},
+			Message::Assistant {
+				id: None,
+				content: OneOrMany::many([
+					AssistantContent::Reasoning(Reasoning::new("It looks like a cat")),
+					AssistantContent::text("A cat."),
+				])
+				.unwrap(),
+			},
+		]);
+		let dropped = vec![
+			DroppedContent {
+				message: 0,
+				kind: ContentKind::Image,
+			},
+			DroppedContent {
+				message: 1,
+				kind: ContentKind::Reasoning,
+			},
+		];
+
+		let exported = conversation.to_openai();
+		assert_eq!(exported.dropped, dropped);
+		assert_eq!(
+			serde_json::to_value(&exported.example).unwrap(),
+			json!({
+				"messages": [
+					{ "role": "user", "content": "What's in this image?" },
+					{ "role": "assistant", "content": "A cat." }
+				]
+			})
+		);
+
+		let exported = conversation.to_sharegpt();
+		assert_eq!(exported.dropped, dropped);
+		assert_eq!(exported.example.conversations.len(), 2);
+	}
+
+	#[test]
+	fn test_invalid_examples() {
+		let observation = ShareGptConversation {
+			conversations: vec![
+				ShareGptTurn::new(ShareGptRole::Human, "Hi"),
+				ShareGptTurn::new(ShareGptRole::Observation, "Sunny"),
+			],
+		};
+		assert!(matches!(
+			Conversation::from_sharegpt(observation),
+			Err(ExportError::InvalidExample(_))
+		));
+
+		let example: OpenAIExample =
+			serde_json::from_value(json!({ "messages": [{ "role": "assistant" }] })).unwrap();
+		assert!(matches!(
+			Conversation::from_openai(example),
+			Err(ExportError::InvalidExample(_))
+		));
+	}
+}
@@ -1,3 +1,7 @@
+pub mod export;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -60,6 +64,8 @@ pub enum AssistantContent {
 	ToolCall(ToolCall),
 	Reasoning(Reasoning),
 	Image(Image),
+	/// A file generated by the model, e.g. the output of a code interpreter
+	Document(Document),
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -222,8 +228,11 @@ pub struct Image {
 }
 
 impl Image {
-	pub fn try_into_url(self) -> Result<String, MessageError> {
-		match self.data {
+	/// Returns the image URL, or a base64 data URL for inline image data.
+	/// The media type of raw image data is sniffed if missing.
+	pub fn try_into_url(mut self) -> Result<String, MessageError> {
+		self.sniff_media_type();
+		match self.data.into_base64() {
 			DocumentSourceKind::Url(url) => Ok(url),
 			DocumentSourceKind::Base64(data) => {
 				let Some(media_type) = self.media_type else {
@@ -234,7 +243,7 @@ impl Image {
 				};
 
 				Ok(format!(
-					"data:image/{ty};base64,{data}",
+					"data:{ty};base64,{data}",
 					ty = media_type.to_mime_type()
 				))
 			}
@@ -290,6 +299,14 @@ impl DocumentSourceKind {
 			_ => None,
 		}
 	}
+
+	/// Encodes raw bytes as base64, leaving other sources untouched.
+	pub fn into_base64(self) -> Self {
+		match self {
+			Self::Raw(bytes) => Self::Base64(BASE64_STANDARD.encode(bytes)),
+			source => source,
+		}
+	}
 }
 
 impl std::fmt::Display for DocumentSourceKind {
@@ -389,6 +406,11 @@ impl DocumentMediaType {
 	pub fn is_code(&self) -> bool {
 		matches!(self, Self::Javascript | Self::Python)
 	}
+
+	/// Whether documents of this type are plain text, i.e. anything but a PDF.
+	pub fn is_text(&self) -> bool {
+		!matches!(self, Self::PDF)
+	}
 }
 
 /// Describes the audio media type of the content. Not every provider supports every media type.
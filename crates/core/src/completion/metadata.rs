@@ -0,0 +1,151 @@
+//! Metadata of the HTTP response a completion was read from.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use http::{HeaderMap, Response};
+use serde::{Deserialize, Serialize};
+
+/// Headers providers send the ID of a request in, in order of preference
+const REQUEST_ID_HEADERS: [&str; 4] = [
+	"x-request-id",
+	// Anthropic
+	"request-id",
+	// Amazon Bedrock
+	"x-amzn-requestid",
+	"x-goog-request-id",
+];
+
+/// Metadata of the HTTP response a completion was read from, e.g. the request ID to give in
+/// support tickets.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResponseMetadata {
+	/// The ID the provider assigned to the request, if it sent one
+	pub request_id: Option<String>,
+	/// Every response header, with lowercase names. Only captured when enabled with
+	/// [crate::client::ClientBuilder::capture_response_headers].
+	pub headers: HashMap<String, String>,
+	/// The time from sending the request to receiving the response headers
+	pub latency: Option<Duration>,
+}
+
+impl ResponseMetadata {
+	/// Reads the metadata from the headers of a response, copying every header if
+	/// `capture_headers` is set.
+	pub fn from_headers(headers: &HeaderMap, capture_headers: bool) -> Self {
+		let request_id = REQUEST_ID_HEADERS
+			.iter()
+			.find_map(|name| headers.get(*name)?.to_str().ok())
+			.map(str::to_string);
+
+		let headers = if capture_headers {
+			headers
+				.iter()
+				.filter_map(|(name, value)| {
+					Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+				})
+				.collect()
+		} else {
+			HashMap::new()
+		};
+
+		Self {
+			request_id,
+			headers,
+			latency: None,
+		}
+	}
+
+	/// The metadata the [crate::client::Client] attached to a response, `None` if it was sent
+	/// with another HTTP client.
+	pub fn from_response<B>(response: &Response<B>) -> Option<Self> {
+		response.extensions().get::<Self>().cloned()
+	}
+
+	pub fn with_latency(mut self, latency: Option<Duration>) -> Self {
+		self.latency = latency;
+		self
+	}
+
+	/// The value of a captured header, `name` is case-insensitive.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers
+			.get(&name.to_ascii_lowercase())
+			.map(String::as_str)
+	}
+}
+
+/// Measures the latency of a request. Clocks aren't available on `wasm32`, where no latency is
+/// measured.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Stopwatch {
+	#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+	start: std::time::Instant,
+}
+
+impl Stopwatch {
+	pub(crate) fn start() -> Self {
+		Self {
+			#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+			start: std::time::Instant::now(),
+		}
+	}
+
+	#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+	pub(crate) fn elapsed(&self) -> Option<Duration> {
+		Some(self.start.elapsed())
+	}
+
+	#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+	pub(crate) fn elapsed(&self) -> Option<Duration> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn headers() -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			"request-id",
+			"req_011CSHoEeqs5C35K2UUqR7Fy".parse().unwrap(),
+		);
+		headers.insert(
+			"anthropic-ratelimit-requests-remaining",
+			"49".parse().unwrap(),
+		);
+		headers
+	}
+
+	#[test]
+	fn test_request_id_only_by_default() {
+		let metadata = ResponseMetadata::from_headers(&headers(), false);
+
+		assert_eq!(
+			metadata.request_id.as_deref(),
+			Some("req_011CSHoEeqs5C35K2UUqR7Fy")
+		);
+		assert!(metadata.headers.is_empty());
+	}
+
+	#[test]
+	fn test_capture_headers() {
+		let metadata = ResponseMetadata::from_headers(&headers(), true);
+
+		assert_eq!(metadata.headers.len(), 2);
+		assert_eq!(
+			metadata.header("Anthropic-Ratelimit-Requests-Remaining"),
+			Some("49")
+		);
+	}
+
+	#[test]
+	fn test_request_id_preference() {
+		let mut headers = headers();
+		headers.insert("x-request-id", "req_openai".parse().unwrap());
+
+		let metadata = ResponseMetadata::from_headers(&headers, false);
+		assert_eq!(metadata.request_id.as_deref(), Some("req_openai"));
+	}
+}
@@ -1,6 +1,25 @@
+pub mod cache;
 pub mod conversions;
+pub mod defaults;
+pub mod dynamic;
+pub mod fallback;
 pub mod message;
+pub mod metadata;
+pub mod provider_error;
+pub mod redact;
 pub mod request;
+pub mod template;
+pub mod tokens;
 
+pub use cache::{
+	CacheKey, CachedCompletion, CachedModel, CompletionCache, InMemoryCompletionCache,
+};
+pub use defaults::{DefaultParamsModel, RequestDefaults};
+pub use fallback::{FallbackAttempt, FallbackModel, FallbackResponse};
 pub use message::{AssistantContent, Message, MessageError};
+pub use metadata::ResponseMetadata;
+pub use provider_error::{ApiError, ProviderErrorKind};
+pub use redact::Redactor;
 pub use request::*;
+pub use template::{MissingVar, PromptTemplate};
+pub use tokens::{HeuristicTokenCounter, TokenCounter};
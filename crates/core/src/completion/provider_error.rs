@@ -0,0 +1,229 @@
+//! Classification of the error responses returned by provider APIs.
+//!
+//! Non-success responses from the providers that parse their error bodies are surfaced as
+//! [CompletionError::ApiError], carrying a [ProviderErrorKind] so that callers can tell an
+//! authentication failure from a rate limit without matching on error strings.
+use std::fmt;
+use std::time::Duration;
+
+use http::{HeaderMap, StatusCode};
+
+use super::CompletionError;
+use crate::http_client;
+
+/// The category of an error returned by a provider's API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProviderErrorKind {
+	/// The API key is missing or invalid, or lacks the required permissions
+	AuthenticationFailed,
+	/// Too many requests were sent; `retry_after` is the delay suggested by the provider, if any
+	RateLimited { retry_after: Option<Duration> },
+	/// The prompt and requested output don't fit in the model's context window
+	ContextLengthExceeded,
+	/// The request or the generated content was blocked by the provider's content filters
+	ContentFiltered,
+	/// The provider is temporarily overloaded or unavailable
+	Overloaded,
+	/// The model or endpoint doesn't exist
+	NotFound,
+	/// Any other error
+	Other,
+}
+
+impl ProviderErrorKind {
+	/// Classifies an error from its HTTP status alone.
+	pub fn from_status(status: StatusCode) -> Self {
+		match status.as_u16() {
+			401 | 403 => Self::AuthenticationFailed,
+			404 => Self::NotFound,
+			429 => Self::RateLimited { retry_after: None },
+			502..=504 | 529 => Self::Overloaded,
+			_ => Self::Other,
+		}
+	}
+
+	/// Whether sending the same request again later may succeed.
+	pub fn is_retryable(&self) -> bool {
+		matches!(self, Self::RateLimited { .. } | Self::Overloaded)
+	}
+}
+
+/// A non-success response from a provider's API.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+	pub status: StatusCode,
+	pub kind: ProviderErrorKind,
+	/// The error message from the body, or the whole body if it couldn't be parsed
+	pub message: String,
+	/// The raw response body
+	pub body: String,
+}
+
+impl fmt::Display for ApiError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.body.is_empty() {
+			write!(f, "{}", self.status)
+		} else {
+			write!(f, "{} ({})", self.body, self.status)
+		}
+	}
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+	/// Creates an error classified by its HTTP status only.
+	/// Provider modules refine the classification from the body.
+	pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+		let body = body.into();
+		Self {
+			status,
+			kind: ProviderErrorKind::from_status(status),
+			message: body.clone(),
+			body,
+		}
+	}
+
+	/// The delay before retrying suggested by the provider, if rate limited.
+	pub fn retry_after(&self) -> Option<Duration> {
+		match self.kind {
+			ProviderErrorKind::RateLimited { retry_after } => retry_after,
+			_ => None,
+		}
+	}
+
+	/// Fills in the retry delay of a rate limit from the `Retry-After` header, unless the
+	/// body already provided one.
+	pub fn with_retry_after(mut self, headers: &HeaderMap) -> Self {
+		if let ProviderErrorKind::RateLimited { retry_after } = &mut self.kind
+			&& retry_after.is_none()
+		{
+			*retry_after = headers
+				.get(http::header::RETRY_AFTER)
+				.and_then(|value| value.to_str().ok())
+				.and_then(|value| value.trim().parse::<f64>().ok())
+				.and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+		}
+		self
+	}
+}
+
+/// Parses a provider's error body into a classified [ApiError].
+pub(crate) type ErrorParser = fn(StatusCode, String) -> ApiError;
+
+impl From<http_client::Error> for CompletionError {
+	fn from(error: http_client::Error) -> Self {
+		match error {
+			http_client::Error::StreamStalled(timeout) => Self::StreamStalled(timeout),
+			error => Self::HttpError(error),
+		}
+	}
+}
+
+impl CompletionError {
+	/// Classifies non-success HTTP responses with the provider's `parse`, other errors are
+	/// kept as [CompletionError::HttpError].
+	pub(crate) fn from_http_error(error: http_client::Error, parse: ErrorParser) -> Self {
+		match error {
+			http_client::Error::InvalidStatusCodeWithMessage(status, body) => {
+				Self::ApiError(parse(status, body))
+			}
+			http_client::Error::InvalidStatusCode(status) => {
+				Self::ApiError(parse(status, String::new()))
+			}
+			error => error.into(),
+		}
+	}
+
+	/// Like [CompletionError::from_http_error] for errors raised while streaming, where other
+	/// errors are reported as [CompletionError::ProviderError].
+	pub(crate) fn from_stream_error(error: http_client::Error, parse: ErrorParser) -> Self {
+		match Self::from_http_error(error, parse) {
+			Self::HttpError(error) => Self::ProviderError(error.to_string()),
+			error => error,
+		}
+	}
+
+	/// The classification of the error if the provider's API rejected the request.
+	pub fn provider_error_kind(&self) -> Option<&ProviderErrorKind> {
+		match self {
+			Self::ApiError(error) => Some(&error.kind),
+			_ => None,
+		}
+	}
+}
+
+/// Parses a delay such as `"23s"`, `"1.5s"` or `"120ms"`.
+pub(crate) fn parse_delay(delay: &str) -> Option<Duration> {
+	let delay = delay.trim();
+	let (value, scale) = if let Some(millis) = delay.strip_suffix("ms") {
+		(millis, 0.001)
+	} else {
+		(delay.strip_suffix('s')?, 1.0)
+	};
+
+	Duration::try_from_secs_f64(value.trim().parse::<f64>().ok()? * scale).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_classifies_status_codes() {
+		assert_eq!(
+			ProviderErrorKind::from_status(StatusCode::UNAUTHORIZED),
+			ProviderErrorKind::AuthenticationFailed
+		);
+		assert_eq!(
+			ProviderErrorKind::from_status(StatusCode::TOO_MANY_REQUESTS),
+			ProviderErrorKind::RateLimited { retry_after: None }
+		);
+		assert_eq!(
+			ProviderErrorKind::from_status(StatusCode::from_u16(529).unwrap()),
+			ProviderErrorKind::Overloaded
+		);
+		assert_eq!(
+			ProviderErrorKind::from_status(StatusCode::BAD_REQUEST),
+			ProviderErrorKind::Other
+		);
+	}
+
+	#[test]
+	fn test_retry_after_header() {
+		let mut headers = HeaderMap::new();
+		headers.insert(http::header::RETRY_AFTER, "7".parse().unwrap());
+
+		let error = ApiError::new(StatusCode::TOO_MANY_REQUESTS, "").with_retry_after(&headers);
+		assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+
+		let error = ApiError::new(StatusCode::BAD_REQUEST, "").with_retry_after(&headers);
+		assert_eq!(error.kind, ProviderErrorKind::Other);
+	}
+
+	#[test]
+	fn test_display_keeps_body() {
+		let error = CompletionError::from_http_error(
+			http_client::Error::InvalidStatusCodeWithMessage(
+				StatusCode::TOO_MANY_REQUESTS,
+				"slow down".to_string(),
+			),
+			ApiError::new,
+		);
+
+		assert_eq!(
+			error.to_string(),
+			"ProviderError: slow down (429 Too Many Requests)"
+		);
+		assert!(error.provider_error_kind().unwrap().is_retryable());
+	}
+
+	#[test]
+	fn test_parse_delay() {
+		assert_eq!(parse_delay("23s"), Some(Duration::from_secs(23)));
+		assert_eq!(parse_delay("1.5s"), Some(Duration::from_millis(1500)));
+		assert_eq!(parse_delay("120ms"), Some(Duration::from_millis(120)));
+		assert_eq!(parse_delay("soon"), None);
+	}
+}
@@ -0,0 +1,608 @@
+//! Redaction of messages before they are logged or recorded in telemetry.
+//!
+//! Chat histories can't be logged as is: they contain large base64 payloads and personal data.
+//! A [Redactor] replaces binary data with a `[{n} bytes {mime}]` placeholder, truncates long
+//! texts and, with the `regex` feature, masks the strings matching user supplied patterns.
+//!
+//! # Example
+//! Masking patterns require the `regex` feature.
+//! ```
+//! use clankers::completion::{Message, Redactor};
+//!
+//! let redactor = Redactor::new()
+//!     .max_text_len(Some(200))
+//!     .mask_pattern(r"[\w.+-]+@[\w-]+\.[\w.]+")
+//!     .expect("The pattern should be valid");
+//!
+//! let message = Message::user("Contact me at jane.doe@example.com");
+//! tracing::info!("{:?}", message.redact(&redactor));
+//! ```
+#[cfg(feature = "regex")]
+use regex::Regex;
+use serde_json::Value;
+
+use crate::OneOrMany;
+use crate::completion::message::{
+	AssistantContent, Audio, Document, DocumentSourceKind, Image, Message, MimeType, Reasoning,
+	Text, ToolCall, ToolFunction, ToolResult, ToolResultContent, UserContent, Video,
+};
+
+/// Texts longer than this many characters are truncated by default.
+pub const DEFAULT_MAX_TEXT_LEN: usize = 2000;
+
+/// Replaces the strings matching a masking pattern.
+#[cfg(feature = "regex")]
+const MASK: &str = "[REDACTED]";
+
+/// Strings at least this long that look like base64 are replaced in JSON values.
+const MAX_BLOB_LEN: usize = 256;
+
+/// Redacts messages for logging and telemetry, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Redactor {
+	max_text_len: Option<usize>,
+	#[cfg(feature = "regex")]
+	patterns: Vec<Regex>,
+}
+
+impl Default for Redactor {
+	fn default() -> Self {
+		Self {
+			max_text_len: Some(DEFAULT_MAX_TEXT_LEN),
+			#[cfg(feature = "regex")]
+			patterns: vec![],
+		}
+	}
+}
+
+impl Redactor {
+	/// Creates a redactor truncating texts to [DEFAULT_MAX_TEXT_LEN] characters, masking nothing.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Texts longer than `max_text_len` characters are truncated, `None` keeps them whole.
+	pub fn max_text_len(mut self, max_text_len: Option<usize>) -> Self {
+		self.max_text_len = max_text_len;
+		self
+	}
+
+	/// Masks the strings matching `pattern`, e.g. email addresses or API keys.
+	#[cfg(feature = "regex")]
+	pub fn mask(mut self, pattern: Regex) -> Self {
+		self.patterns.push(pattern);
+		self
+	}
+
+	/// Same as [Redactor::mask], compiling the pattern.
+	#[cfg(feature = "regex")]
+	pub fn mask_pattern(self, pattern: &str) -> Result<Self, regex::Error> {
+		Ok(self.mask(Regex::new(pattern)?))
+	}
+
+	/// Masks then truncates `text`. Truncated texts end with an ellipsis and their original
+	/// length.
+	pub fn redact_text(&self, text: &str) -> String {
+		let text = self.mask_text(text);
+
+		match self.max_text_len {
+			Some(max_text_len) if text.chars().count() > max_text_len => {
+				let len = text.chars().count();
+				let truncated = text.chars().take(max_text_len).collect::<String>();
+				format!("{truncated}… [{len} chars]")
+			}
+			_ => text,
+		}
+	}
+
+	#[cfg(feature = "regex")]
+	fn mask_text(&self, text: &str) -> String {
+		let mut text = text.to_string();
+		for pattern in &self.patterns {
+			if let std::borrow::Cow::Owned(masked) = pattern.replace_all(&text, MASK) {
+				text = masked;
+			}
+		}
+		text
+	}
+
+	#[cfg(not(feature = "regex"))]
+	fn mask_text(&self, text: &str) -> String {
+		text.to_string()
+	}
+
+	/// Redacts a message, see [Message::redact].
+	pub fn redact_message(&self, message: &Message) -> Message {
+		match message {
+			Message::User { content } => Message::User {
+				content: self.redact_all(content, |content| self.redact_user_content(content)),
+			},
+			Message::Assistant { id, content } => Message::Assistant {
+				id: id.clone(),
+				content: self.redact_all(content, |content| self.redact_assistant_content(content)),
+			},
+		}
+	}
+
+	/// Redacts a JSON value in place, e.g. a provider message: base64 payloads are replaced
+	/// with a placeholder, other strings are masked and truncated.
+	pub fn redact_json(&self, value: &mut Value) {
+		match value {
+			Value::String(text) => {
+				if let Some((prefix, data)) = text.split_once(";base64,")
+					&& let Some(media_type) = prefix.strip_prefix("data:")
+				{
+					*text = format!("[{} bytes {media_type}]", base64_decoded_len(data));
+				} else if text.len() >= MAX_BLOB_LEN
+					&& text.bytes().all(|b| {
+						b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'-' | b'_')
+					}) {
+					*text = format!("[{} bytes]", base64_decoded_len(text));
+				} else {
+					*text = self.redact_text(text);
+				}
+			}
+			Value::Array(values) => values.iter_mut().for_each(|value| self.redact_json(value)),
+			Value::Object(map) => map.values_mut().for_each(|value| self.redact_json(value)),
+			_ => {}
+		}
+	}
+
+	fn redact_all<T>(&self, items: &OneOrMany<T>, redact: impl Fn(&T) -> T) -> OneOrMany<T>
+	where
+		T: Clone,
+	{
+		let mut redacted = OneOrMany::one(redact(items.first_ref()));
+		for item in items.iter().skip(1) {
+			redacted.push(redact(item));
+		}
+		redacted
+	}
+
+	fn redact_user_content(&self, content: &UserContent) -> UserContent {
+		match content {
+			UserContent::Text(text) => UserContent::Text(self.redact_text_content(text)),
+			UserContent::ToolResult(result) => UserContent::ToolResult(ToolResult {
+				id: result.id.clone(),
+				call_id: result.call_id.clone(),
+				content: self.redact_all(&result.content, |content| match content {
+					ToolResultContent::Text(text) => {
+						ToolResultContent::Text(self.redact_text_content(text))
+					}
+					ToolResultContent::Image(image) => {
+						ToolResultContent::Image(self.redact_image(image))
+					}
+				}),
+			}),
+			UserContent::Image(image) => UserContent::Image(self.redact_image(image)),
+			UserContent::Audio(audio) => UserContent::Audio(Audio {
+				data: self.redact_data(&audio.data, audio.media_type.as_ref()),
+				media_type: audio.media_type.clone(),
+				additional_params: self.redact_params(&audio.additional_params),
+			}),
+			UserContent::Video(video) => UserContent::Video(Video {
+				data: self.redact_data(&video.data, video.media_type.as_ref()),
+				media_type: video.media_type.clone(),
+				additional_params: self.redact_params(&video.additional_params),
+			}),
+			UserContent::Document(document) => {
+				UserContent::Document(self.redact_document(document))
+			}
+		}
+	}
+
+	fn redact_assistant_content(&self, content: &AssistantContent) -> AssistantContent {
+		match content {
+			AssistantContent::Text(text) => AssistantContent::Text(self.redact_text_content(text)),
+			AssistantContent::ToolCall(tool_call) => AssistantContent::ToolCall(ToolCall {
+				function: ToolFunction {
+					name: tool_call.function.name.clone(),
+					arguments: self.redacted_json(&tool_call.function.arguments),
+				},
+				additional_params: self.redact_params(&tool_call.additional_params),
+				..tool_call.clone()
+			}),
+			AssistantContent::Reasoning(reasoning) => AssistantContent::Reasoning(Reasoning {
+				reasoning: reasoning
+					.reasoning
+					.iter()
+					.map(|text| self.redact_text(text))
+					.collect(),
+				..reasoning.clone()
+			}),
+			AssistantContent::Image(image) => AssistantContent::Image(self.redact_image(image)),
+			AssistantContent::Document(document) => {
+				AssistantContent::Document(self.redact_document(document))
+			}
+		}
+	}
+
+	fn redact_text_content(&self, text: &Text) -> Text {
+		Text {
+			text: self.redact_text(&text.text),
+		}
+	}
+
+	fn redact_image(&self, image: &Image) -> Image {
+		Image {
+			data: self.redact_data(&image.data, image.media_type.as_ref()),
+			media_type: image.media_type.clone(),
+			detail: image.detail.clone(),
+			additional_params: self.redact_params(&image.additional_params),
+		}
+	}
+
+	fn redact_document(&self, document: &Document) -> Document {
+		Document {
+			data: self.redact_data(&document.data, document.media_type.as_ref()),
+			media_type: document.media_type.clone(),
+			additional_params: self.redact_params(&document.additional_params),
+		}
+	}
+
+	/// Replaces binary data with a placeholder, texts and URLs are masked and truncated.
+	fn redact_data<M>(
+		&self,
+		data: &DocumentSourceKind,
+		media_type: Option<&M>,
+	) -> DocumentSourceKind
+	where
+		M: MimeType,
+	{
+		let placeholder = |bytes: usize| {
+			DocumentSourceKind::String(match media_type {
+				Some(media_type) => format!("[{bytes} bytes {}]", media_type.to_mime_type()),
+				None => format!("[{bytes} bytes]"),
+			})
+		};
+
+		match data {
+			DocumentSourceKind::Base64(data) => placeholder(base64_decoded_len(data)),
+			DocumentSourceKind::Raw(data) => placeholder(data.len()),
+			DocumentSourceKind::Url(url) => match url.split_once(";base64,") {
+				Some((prefix, data)) if prefix.starts_with("data:") => {
+					placeholder(base64_decoded_len(data))
+				}
+				_ => DocumentSourceKind::Url(self.redact_text(url)),
+			},
+			DocumentSourceKind::String(text) => DocumentSourceKind::String(self.redact_text(text)),
+			DocumentSourceKind::Unknown => DocumentSourceKind::Unknown,
+		}
+	}
+
+	fn redact_params(&self, params: &Option<Value>) -> Option<Value> {
+		params.as_ref().map(|params| self.redacted_json(params))
+	}
+
+	fn redacted_json(&self, value: &Value) -> Value {
+		let mut value = value.clone();
+		self.redact_json(&mut value);
+		value
+	}
+}
+
+/// Number of bytes encoded by a base64 string.
+fn base64_decoded_len(data: &str) -> usize {
+	let data = data.trim_end_matches('=');
+	data.len() * 3 / 4
+}
+
+impl Message {
+	/// Returns a copy of the message safe to log: binary data is replaced with a
+	/// `[{n} bytes {mime}]` placeholder, and texts are masked and truncated by `redactor`.
+	pub fn redact(&self, redactor: &Redactor) -> Message {
+		redactor.redact_message(self)
+	}
+}
+
+impl OneOrMany<Message> {
+	/// Redacts every message, see [Message::redact].
+	pub fn redact(&self, redactor: &Redactor) -> OneOrMany<Message> {
+		redactor.redact_all(self, |message| redactor.redact_message(message))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use base64::Engine;
+	use base64::prelude::BASE64_STANDARD;
+	use serde_json::json;
+
+	use super::*;
+	use crate::completion::message::{
+		AudioMediaType, DocumentMediaType, ImageMediaType, VideoMediaType,
+	};
+
+	fn user(content: UserContent) -> Message {
+		Message::User {
+			content: OneOrMany::one(content),
+		}
+	}
+
+	fn user_content(message: Message) -> UserContent {
+		match message {
+			Message::User { content } => content.first(),
+			message => panic!("expected a user message, got {message:?}"),
+		}
+	}
+
+	fn assistant_content(message: Message) -> AssistantContent {
+		match message {
+			Message::Assistant { content, .. } => content.first(),
+			message => panic!("expected an assistant message, got {message:?}"),
+		}
+	}
+
+	fn placeholder(text: &str) -> DocumentSourceKind {
+		DocumentSourceKind::String(text.to_string())
+	}
+
+	#[test]
+	fn test_redact_text() {
+		let redactor = Redactor::new().max_text_len(Some(5));
+
+		assert_eq!(redactor.redact_text("short"), "short");
+		assert_eq!(redactor.redact_text("héllo world"), "héllo… [11 chars]");
+		assert_eq!(
+			Redactor::new()
+				.max_text_len(None)
+				.redact_text(&"a".repeat(5000)),
+			"a".repeat(5000)
+		);
+	}
+
+	#[cfg(feature = "regex")]
+	#[test]
+	fn test_mask_patterns() {
+		let redactor = Redactor::new()
+			.mask_pattern(r"[\w.+-]+@[\w-]+\.[\w.]+")
+			.unwrap()
+			.mask_pattern(r"sk-[A-Za-z0-9]+")
+			.unwrap();
+
+		let message = Message::user("Mail jane.doe@example.com with the key sk-abc123");
+
+		assert_eq!(
+			message.redact(&redactor),
+			Message::user("Mail [REDACTED] with the key [REDACTED]")
+		);
+		assert!(Redactor::new().mask_pattern("(").is_err());
+	}
+
+	#[cfg(feature = "regex")]
+	#[test]
+	fn test_mask_tool_call_arguments() {
+		let redactor = Redactor::new()
+			.max_text_len(Some(8))
+			.mask_pattern(r"\d{4}-\d{4}")
+			.unwrap();
+
+		let tool_call = assistant_content(
+			Message::Assistant {
+				id: None,
+				content: OneOrMany::one(AssistantContent::tool_call(
+					"call_1",
+					"lookup",
+					json!({ "card": "1234-5678", "limit": 10 }),
+				)),
+			}
+			.redact(&redactor),
+		);
+		let AssistantContent::ToolCall(tool_call) = tool_call else {
+			panic!("expected a tool call");
+		};
+		assert_eq!(
+			tool_call.function.arguments,
+			json!({ "card": "[REDACTE… [10 chars]", "limit": 10 })
+		);
+	}
+
+	#[test]
+	fn test_redact_user_media() {
+		let redactor = Redactor::new();
+		let data = BASE64_STANDARD.encode([0u8; 30]);
+
+		let image = user_content(
+			user(UserContent::image_base64(
+				&data,
+				Some(ImageMediaType::PNG),
+				None,
+			))
+			.redact(&redactor),
+		);
+		assert!(
+			matches!(image, UserContent::Image(Image { data, .. }) if data == placeholder("[30 bytes image/png]"))
+		);
+
+		let audio = user_content(
+			user(UserContent::Audio(Audio {
+				data: DocumentSourceKind::Raw(vec![0; 1024]),
+				media_type: Some(AudioMediaType::WAV),
+				additional_params: None,
+			}))
+			.redact(&redactor),
+		);
+		assert!(
+			matches!(audio, UserContent::Audio(Audio { data, .. }) if data == placeholder("[1024 bytes audio/wav]"))
+		);
+
+		let video = user_content(
+			user(UserContent::Video(Video {
+				data: DocumentSourceKind::Url(format!("data:video/mp4;base64,{data}")),
+				media_type: Some(VideoMediaType::MP4),
+				additional_params: None,
+			}))
+			.redact(&redactor),
+		);
+		assert!(
+			matches!(video, UserContent::Video(Video { data, .. }) if data == placeholder("[30 bytes video/mp4]"))
+		);
+
+		let document = user_content(
+			user(UserContent::Document(Document {
+				data: DocumentSourceKind::Base64(data.clone()),
+				media_type: None,
+				additional_params: None,
+			}))
+			.redact(&redactor),
+		);
+		assert!(
+			matches!(document, UserContent::Document(Document { data, .. }) if data == placeholder("[30 bytes]"))
+		);
+
+		// URLs and text documents are kept, only truncated
+		let url = "https://example.com/cat.png";
+		let image = user_content(user(UserContent::image_url(url, None, None)).redact(&redactor));
+		assert!(
+			matches!(image, UserContent::Image(Image { data: DocumentSourceKind::Url(kept), .. }) if kept == url)
+		);
+
+		let document = user_content(
+			user(UserContent::Document(Document {
+				data: DocumentSourceKind::String("a".repeat(3000)),
+				media_type: Some(DocumentMediaType::TXT),
+				additional_params: None,
+			}))
+			.redact(&redactor),
+		);
+		assert!(
+			matches!(document, UserContent::Document(Document { data, .. })
+			if data == placeholder(&format!("{}… [3000 chars]", "a".repeat(2000))))
+		);
+	}
+
+	#[test]
+	fn test_redact_tool_result() {
+		let redactor = Redactor::new().max_text_len(Some(4));
+		let message = user(UserContent::ToolResult(ToolResult {
+			id: "call_1".to_string(),
+			call_id: None,
+			content: OneOrMany::many([
+				ToolResultContent::text("sunny and warm"),
+				ToolResultContent::image_base64(
+					BASE64_STANDARD.encode([0u8; 6]),
+					Some(ImageMediaType::JPEG),
+					None,
+				),
+			])
+			.unwrap(),
+		}));
+
+		let UserContent::ToolResult(result) = user_content(message.redact(&redactor)) else {
+			panic!("expected a tool result");
+		};
+		assert_eq!(result.id, "call_1");
+		assert_eq!(
+			result.content.first(),
+			ToolResultContent::text("sunn… [14 chars]")
+		);
+		assert!(
+			matches!(result.content.last(), ToolResultContent::Image(Image { data, .. })
+			if data == placeholder("[6 bytes image/jpeg]"))
+		);
+	}
+
+	#[test]
+	fn test_redact_assistant_content() {
+		let redactor = Redactor::new().max_text_len(Some(8));
+
+		let text = assistant_content(
+			Message::assistant("The answer is a very long text").redact(&redactor),
+		);
+		assert_eq!(text, AssistantContent::text("The answ… [30 chars]"));
+
+		let tool_call = assistant_content(
+			Message::Assistant {
+				id: Some("msg_1".to_string()),
+				content: OneOrMany::one(AssistantContent::tool_call(
+					"call_1",
+					"lookup",
+					json!({ "card": "1234-5678-9012", "limit": 10 }),
+				)),
+			}
+			.redact(&redactor),
+		);
+		let AssistantContent::ToolCall(tool_call) = tool_call else {
+			panic!("expected a tool call");
+		};
+		assert_eq!(tool_call.id, "call_1");
+		assert_eq!(tool_call.function.name, "lookup");
+		assert_eq!(
+			tool_call.function.arguments,
+			json!({ "card": "1234-567… [14 chars]", "limit": 10 })
+		);
+
+		let reasoning = assistant_content(
+			Message::Assistant {
+				id: None,
+				content: OneOrMany::one(AssistantContent::reasoning("Let me think about it")),
+			}
+			.redact(&redactor),
+		);
+		assert_eq!(
+			reasoning,
+			AssistantContent::reasoning("Let me t… [21 chars]")
+		);
+	}
+
+	#[test]
+	fn test_redact_json_uses_placeholders() {
+		let data = BASE64_STANDARD.encode([0u8; 300]);
+		let mut value = json!({
+			"image": data,
+			"url": format!("data:image/png;base64,{data}"),
+			"text": "short",
+		});
+
+		Redactor::new().redact_json(&mut value);
+
+		assert_eq!(
+			value,
+			json!({
+				"image": "[300 bytes]",
+				"url": "[300 bytes image/png]",
+				"text": "short",
+			})
+		);
+	}
+
+	#[test]
+	fn test_redact_history() {
+		let history = OneOrMany::many([
+			Message::user("a".repeat(10)),
+			Message::assistant("b".repeat(10)),
+		])
+		.unwrap();
+
+		let redacted = history.redact(&Redactor::new().max_text_len(Some(1)));
+
+		assert_eq!(
+			redacted,
+			OneOrMany::many([
+				Message::user("a… [10 chars]"),
+				Message::assistant("b… [10 chars]"),
+			])
+			.unwrap()
+		);
+	}
+
+	#[test]
+	fn test_huge_image_is_bounded() {
+		let data = BASE64_STANDARD.encode(vec![0u8; 10 * 1024 * 1024]);
+		let message = Message::User {
+			content: OneOrMany::many([
+				UserContent::text("What is in this image?"),
+				UserContent::image_base64(data, Some(ImageMediaType::PNG), None),
+			])
+			.unwrap(),
+		};
+
+		let redacted = serde_json::to_string(&message.redact(&Redactor::new())).unwrap();
+
+		assert!(redacted.len() < 512, "{redacted}");
+		assert!(
+			redacted.contains("[10485760 bytes image/png]"),
+			"{redacted}"
+		);
+	}
+}
@@ -63,16 +63,24 @@
 //! For more information on how to use the completion functionality, refer to the documentation of
 //! the individual traits, structs, and enums defined in this module.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Add, AddAssign};
+use std::sync::Arc;
 
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use super::cache::{CachedModel, CompletionCache};
+use super::defaults::{DefaultParamsModel, RequestDefaults};
 use super::message::{AssistantContent, DocumentMediaType};
+use super::metadata::ResponseMetadata;
+use super::provider_error::ApiError;
+use super::tokens::{HeuristicTokenCounter, TokenCounter};
+use crate::client::CapturedRequest;
 use crate::message::{Message, ToolChoice, UserContent};
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::{Cost, Pricing};
 use crate::tool::ToolSetError;
 use crate::tool::server::ToolServerError;
 use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
@@ -83,7 +91,7 @@ use crate::{OneOrMany, http_client, json_utils};
 pub enum CompletionError {
 	/// Http error (e.g.: connection error, timeout, etc.)
 	#[error("HttpError: {0}")]
-	HttpError(#[from] http_client::Error),
+	HttpError(#[source] http_client::Error),
 
 	/// Json error (e.g.: serialization, deserialization)
 	#[error("JsonError: {0}")]
@@ -110,6 +118,15 @@ pub enum CompletionError {
 	/// Error returned by the completion model provider
 	#[error("ProviderError: {0}")]
 	ProviderError(String),
+
+	/// The provider's API rejected the request, see [ApiError::kind] for the classification
+	#[error("ProviderError: {0}")]
+	ApiError(ApiError),
+
+	/// No data was received from the response stream for the stall timeout, see
+	/// [ClientBuilder::stream_stall_timeout](crate::client::ClientBuilder::stream_stall_timeout)
+	#[error("StreamStalled: no data received for {0:?}")]
+	StreamStalled(std::time::Duration),
 }
 
 /// Prompt errors
@@ -129,7 +146,10 @@ pub enum PromptError {
 
 	/// The LLM tried to call too many tools during a multi-turn conversation.
 	/// To fix this, you may either need to lower the amount of tools your model has access to (and then create other agents to share the tool load)
-	/// or increase the amount of turns given in `.multi_turn()`.
+	/// or increase the amount of turns given in `.max_turns()` (or `.multi_turn()` when streaming).
+	///
+	/// `chat_history` holds the conversation up to the limit, ending with `prompt`, the tool
+	/// results the model never got to answer. It can be used to inspect or resume the run.
 	#[error("MaxTurnError: (reached max turn limit: {max_turns})")]
 	MaxTurnsError {
 		max_turns: usize,
@@ -143,6 +163,11 @@ pub enum PromptError {
 		chat_history: Box<Vec<Message>>,
 		reason: String,
 	},
+
+	/// The prompt was flagged by the agent's moderator, see
+	/// [AgentBuilder::with_input_moderation](crate::agent::AgentBuilder::with_input_moderation).
+	#[error("Moderated: the prompt was flagged for {}", categories.join(", "))]
+	Moderated { categories: Vec<String> },
 }
 
 impl PromptError {
@@ -162,10 +187,11 @@ pub struct Document {
 	pub additional_props: HashMap<String, String>,
 }
 
-impl std::fmt::Display for Document {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(
-			f,
+impl Document {
+	/// Renders the document as it is shown to the model, in a `<file>` block naming its id and
+	/// starting with its metadata if any. Every provider presents documents this way.
+	pub fn render_for_prompt(&self) -> String {
+		format!(
 			concat!("<file id: {}>\n", "{}\n", "</file>\n"),
 			self.id,
 			if self.additional_props.is_empty() {
@@ -184,6 +210,12 @@ impl std::fmt::Display for Document {
 	}
 }
 
+impl std::fmt::Display for Document {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.render_for_prompt())
+	}
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct ToolDefinition {
 	pub name: String,
@@ -256,6 +288,16 @@ pub struct CompletionResponse<T> {
 	pub usage: Usage,
 	/// The raw response returned by the completion model provider
 	pub raw_response: T,
+	/// Metadata of the HTTP response, e.g. the request ID, `None` if the provider doesn't
+	/// report it
+	pub response_metadata: Option<ResponseMetadata>,
+}
+
+impl<T> CompletionResponse<T> {
+	pub fn with_response_metadata(mut self, response_metadata: Option<ResponseMetadata>) -> Self {
+		self.response_metadata = response_metadata;
+		self
+	}
 }
 
 /// A trait for grabbing the token usage of a completion response.
@@ -271,6 +313,12 @@ impl GetTokenUsage for () {
 	}
 }
 
+impl GetTokenUsage for Usage {
+	fn token_usage(&self) -> Option<crate::completion::Usage> {
+		Some(*self)
+	}
+}
+
 impl<T> GetTokenUsage for Option<T>
 where
 	T: GetTokenUsage,
@@ -286,16 +334,27 @@ where
 
 /// Struct representing the token usage for a completion request.
 /// If tokens used are `0`, then the provider failed to supply token usage metrics.
+///
+/// The serialized field names are part of the public API and don't change between releases,
+/// regardless of the names of the Rust fields.
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct Usage {
 	/// The number of input ("prompt") tokens used in a given request.
+	#[serde(rename = "input_tokens")]
 	pub input_tokens: u64,
 	/// The number of output ("completion") tokens used in a given request.
+	#[serde(rename = "output_tokens")]
 	pub output_tokens: u64,
 	/// We store this separately as some providers may only report one number
+	#[serde(rename = "total_tokens")]
 	pub total_tokens: u64,
 	/// The number of cached input tokens (from prompt caching). 0 if not reported by provider.
+	#[serde(rename = "cached_input_tokens", default)]
 	pub cached_input_tokens: u64,
+	/// The number of input tokens written to the prompt cache, billed at a higher rate by some
+	/// providers. 0 if not reported by provider.
+	#[serde(rename = "cache_creation_input_tokens", default)]
+	pub cache_creation_input_tokens: u64,
 }
 
 impl Usage {
@@ -306,6 +365,37 @@ impl Usage {
 			output_tokens: 0,
 			total_tokens: 0,
 			cached_input_tokens: 0,
+			cache_creation_input_tokens: 0,
+		}
+	}
+
+	/// Whether no tokens were reported at all.
+	pub fn is_empty(&self) -> bool {
+		*self == Self::new()
+	}
+
+	/// Estimates the cost of this usage with the given rates.
+	/// `cached_input_tokens` and `cache_creation_input_tokens` are assumed to be part of
+	/// `input_tokens` and are billed at the cached input and cache creation rates.
+	pub fn estimate_cost(&self, pricing: &Pricing) -> Cost {
+		const PER_TOKENS: f64 = 1_000_000.0;
+
+		let cached_input_tokens = self.cached_input_tokens.min(self.input_tokens);
+		let cache_creation_input_tokens = self
+			.cache_creation_input_tokens
+			.min(self.input_tokens - cached_input_tokens);
+		let uncached_input_tokens =
+			self.input_tokens - cached_input_tokens - cache_creation_input_tokens;
+
+		Cost {
+			input: uncached_input_tokens as f64 * pricing.input / PER_TOKENS,
+			cached_input: cached_input_tokens as f64
+				* pricing.cached_input.unwrap_or(pricing.input)
+				/ PER_TOKENS,
+			cache_creation_input: cache_creation_input_tokens as f64
+				* pricing.cache_creation_input.unwrap_or(pricing.input)
+				/ PER_TOKENS,
+			output: self.output_tokens as f64 * pricing.output / PER_TOKENS,
 		}
 	}
 }
@@ -316,25 +406,76 @@ impl Default for Usage {
 	}
 }
 
+/// Token counts saturate at [u64::MAX] instead of overflowing.
 impl Add for Usage {
 	type Output = Self;
 
-	fn add(self, other: Self) -> Self::Output {
-		Self {
-			input_tokens: self.input_tokens + other.input_tokens,
-			output_tokens: self.output_tokens + other.output_tokens,
-			total_tokens: self.total_tokens + other.total_tokens,
-			cached_input_tokens: self.cached_input_tokens + other.cached_input_tokens,
-		}
+	fn add(mut self, other: Self) -> Self::Output {
+		self += other;
+		self
 	}
 }
 
 impl AddAssign for Usage {
 	fn add_assign(&mut self, other: Self) {
-		self.input_tokens += other.input_tokens;
-		self.output_tokens += other.output_tokens;
-		self.total_tokens += other.total_tokens;
-		self.cached_input_tokens += other.cached_input_tokens;
+		self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
+		self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
+		self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+		self.cached_input_tokens = self
+			.cached_input_tokens
+			.saturating_add(other.cached_input_tokens);
+		self.cache_creation_input_tokens = self
+			.cache_creation_input_tokens
+			.saturating_add(other.cache_creation_input_tokens);
+	}
+}
+
+impl std::iter::Sum for Usage {
+	fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+		iter.fold(Self::new(), Add::add)
+	}
+}
+
+/// Formats the usage for logs, e.g. `in: 1.2k, out: 540, cached: 800, total: 1.7k`. Cache counts
+/// are only shown when reported.
+impl std::fmt::Display for Usage {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"in: {}, out: {}",
+			TokenCount(self.input_tokens),
+			TokenCount(self.output_tokens)
+		)?;
+
+		if self.cached_input_tokens > 0 {
+			write!(f, ", cached: {}", TokenCount(self.cached_input_tokens))?;
+		}
+		if self.cache_creation_input_tokens > 0 {
+			write!(
+				f,
+				", cache write: {}",
+				TokenCount(self.cache_creation_input_tokens)
+			)?;
+		}
+
+		write!(f, ", total: {}", TokenCount(self.total_tokens))
+	}
+}
+
+/// A token count shortened to thousands or millions with one decimal, e.g. `1.2k` or `3M`.
+struct TokenCount(u64);
+
+impl std::fmt::Display for TokenCount {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let (value, suffix) = match self.0 {
+			n if n < 1_000 => return write!(f, "{n}"),
+			// Counts that would round up to `1000.0k` are shown in millions
+			n if n < 999_950 => (n as f64 / 1e3, "k"),
+			n => (n as f64 / 1e6, "M"),
+		};
+
+		let value = format!("{value:.1}");
+		write!(f, "{}{suffix}", value.trim_end_matches(".0"))
 	}
 }
 
@@ -375,10 +516,70 @@ pub trait CompletionModel: Clone + WasmCompatSend + WasmCompatSync {
 	fn completion_request(&self, prompt: impl Into<Message>) -> CompletionRequestBuilder<Self> {
 		CompletionRequestBuilder::new(self.clone(), prompt)
 	}
+
+	/// Returns a counter for estimating the prompt size of requests to this model.
+	/// Defaults to a [`HeuristicTokenCounter`].
+	fn token_counter(&self) -> Arc<dyn TokenCounter> {
+		Arc::new(HeuristicTokenCounter::default())
+	}
+
+	/// The last request sent by this model or its clones, streaming ones included. Only kept
+	/// when the client was built with
+	/// [capture_last_request](crate::client::ClientBuilder::capture_last_request).
+	fn last_request(&self) -> Option<CapturedRequest> {
+		None
+	}
+
+	/// Checks a request against the limits of this model, on top of the checks of
+	/// [CompletionRequest::validate]. Called by [CompletionRequestBuilder::validate].
+	fn validate_request(&self, _request: &CompletionRequest, _report: &mut ValidationReport) {}
+
+	/// Uses `temperature` for the requests that don't set one. See [crate::completion::defaults].
+	fn with_default_temperature(self, temperature: f64) -> DefaultParamsModel<Self>
+	where
+		Self: Sized,
+	{
+		DefaultParamsModel::new(self, RequestDefaults::default().temperature(temperature))
+	}
+
+	/// Uses `max_tokens` for the requests that don't set them.
+	fn with_default_max_tokens(self, max_tokens: u64) -> DefaultParamsModel<Self>
+	where
+		Self: Sized,
+	{
+		DefaultParamsModel::new(self, RequestDefaults::default().max_tokens(max_tokens))
+	}
+
+	/// Merges `params` under the parameters of every request, see
+	/// [RequestDefaults::from_params]. Takes precedence over the defaults of the client.
+	fn with_default_params(self, params: serde_json::Value) -> DefaultParamsModel<Self>
+	where
+		Self: Sized,
+	{
+		DefaultParamsModel::new(self, RequestDefaults::from_params(params))
+	}
+
+	/// Serves repeated requests from `cache` instead of sending them to the provider again.
+	/// `namespace` (e.g. the model name) keeps the completions of models sharing a cache apart,
+	/// see [CachedModel].
+	fn with_cache(
+		self,
+		cache: impl CompletionCache + 'static,
+		namespace: impl Into<String>,
+	) -> CachedModel<Self>
+	where
+		Self: Sized,
+	{
+		CachedModel::new(self, cache, namespace)
+	}
 }
 
 /// Struct representing a general completion request that can be sent to a completion model provider.
+///
+/// Use [CompletionRequest::builder] (or [CompletionModel::completion_request]) to create one,
+/// new fields are added in minor releases.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct CompletionRequest {
 	/// The preamble to be sent to the completion model provider
 	pub preamble: Option<String>,
@@ -393,6 +594,8 @@ pub struct CompletionRequest {
 	pub temperature: Option<f64>,
 	/// The max tokens to be sent to the completion model provider
 	pub max_tokens: Option<u64>,
+	/// Sequences that stop generation when produced by the model
+	pub stop_sequences: Vec<String>,
 	/// Whether tools are required to be used by the model provider or not before providing a response.
 	pub tool_choice: Option<ToolChoice>,
 	/// Additional provider-specific parameters to be sent to the completion model provider
@@ -400,6 +603,20 @@ pub struct CompletionRequest {
 }
 
 impl CompletionRequest {
+	/// Returns a builder for a request ending with `prompt`, not bound to a model.
+	///
+	/// ```rust
+	/// use clankers::completion::CompletionRequest;
+	///
+	/// let request = CompletionRequest::builder("Who are you?")
+	///     .preamble("You are Marvin.".to_string())
+	///     .temperature(0.5)
+	///     .build();
+	/// ```
+	pub fn builder(prompt: impl Into<Message>) -> CompletionRequestBuilder {
+		CompletionRequestBuilder::with_model((), prompt)
+	}
+
 	/// Returns documents normalized into a message (if any).
 	/// Most providers do not accept documents directly as input, so it needs to convert into a
 	///  `Message` so that it can be incorporated into `chat_history` as a
@@ -427,6 +644,132 @@ impl CompletionRequest {
 			content: OneOrMany::many(messages).expect("There will be atleast one document"),
 		})
 	}
+
+	/// Returns the documents rendered into a single text message (if any), for the providers
+	/// which only accept text messages. The documents then form one block of context rather than
+	/// messages looking like user prompts.
+	pub fn rendered_documents(&self) -> Option<Message> {
+		if self.documents.is_empty() {
+			return None;
+		}
+
+		Some(Message::user(
+			self.documents
+				.iter()
+				.map(Document::render_for_prompt)
+				.collect::<String>(),
+		))
+	}
+
+	/// Fails if the request has more stop sequences than `provider` accepts.
+	pub(crate) fn check_stop_sequences(
+		&self,
+		provider: &str,
+		max: usize,
+	) -> Result<(), CompletionError> {
+		if self.stop_sequences.len() > max {
+			return Err(CompletionError::RequestError(
+				format!(
+					"{provider} supports at most {max} stop sequences, got {}",
+					self.stop_sequences.len()
+				)
+				.into(),
+			));
+		}
+
+		Ok(())
+	}
+
+	/// Warns that the stop sequences of the request are ignored, for providers without them.
+	pub(crate) fn warn_unsupported_stop_sequences(&self, provider: &str) {
+		if !self.stop_sequences.is_empty() {
+			tracing::warn!("{provider} doesn't support stop sequences, ignoring them");
+		}
+	}
+
+	/// Checks the request for invalid combinations of settings that don't depend on the model.
+	/// See [CompletionModel::validate_request] for the model-specific checks.
+	pub fn validate(&self) -> ValidationReport {
+		let mut report = ValidationReport::default();
+
+		if self.max_tokens == Some(0) {
+			report.error("`max_tokens` must be greater than 0");
+		}
+
+		if let Some(temperature) = self.temperature
+			&& !(temperature.is_finite() && temperature >= 0.0)
+		{
+			report.error(format!(
+				"`temperature` must be a non-negative number, got {temperature}"
+			));
+		}
+
+		if self.chat_history.iter().all(Message::is_empty) {
+			report.error("The chat history only contains empty messages");
+		}
+
+		match &self.tool_choice {
+			Some(ToolChoice::Auto | ToolChoice::None) | None => {}
+			Some(_) if self.tools.is_empty() => {
+				report.warn("`tool_choice` is set but there are no tools, it will be ignored");
+			}
+			Some(ToolChoice::Specific { function_names }) => {
+				for name in function_names {
+					if !self.tools.iter().any(|tool| &tool.name == name) {
+						report.error(format!("`tool_choice` requires the unknown tool `{name}`"));
+					}
+				}
+			}
+			Some(ToolChoice::Required) => {}
+		}
+
+		report
+	}
+
+	/// The non-fatal problems found by [CompletionRequest::validate], e.g. settings that will be
+	/// ignored.
+	pub fn warnings(&self) -> Vec<String> {
+		self.validate().warnings
+	}
+}
+
+/// Problems found when validating a [CompletionRequest].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+	errors: Vec<String>,
+	warnings: Vec<String>,
+}
+
+impl ValidationReport {
+	/// Reports a problem that would make the request fail or misbehave.
+	pub fn error(&mut self, error: impl Into<String>) {
+		self.errors.push(error.into());
+	}
+
+	/// Reports a problem that doesn't prevent the request from being sent.
+	pub fn warn(&mut self, warning: impl Into<String>) {
+		self.warnings.push(warning.into());
+	}
+
+	pub fn errors(&self) -> &[String] {
+		&self.errors
+	}
+
+	pub fn warnings(&self) -> &[String] {
+		&self.warnings
+	}
+
+	/// Returns the warnings, or a [CompletionError::RequestError] listing the errors if there are
+	/// any.
+	pub fn into_result(self) -> Result<Vec<String>, CompletionError> {
+		if self.errors.is_empty() {
+			Ok(self.warnings)
+		} else {
+			Err(CompletionError::RequestError(
+				format!("Invalid completion request: {}", self.errors.join("; ")).into(),
+			))
+		}
+	}
 }
 
 /// Builder struct for constructing a completion request.
@@ -472,8 +815,9 @@ impl CompletionRequest {
 /// ```
 ///
 /// Note: It is usually unnecessary to create a completion request builder directly.
-/// Instead, use the [CompletionModel::completion_request] method.
-pub struct CompletionRequestBuilder<M: CompletionModel> {
+/// Instead, use the [CompletionModel::completion_request] method, or [CompletionRequest::builder]
+/// for a request that isn't sent right away.
+pub struct CompletionRequestBuilder<M = ()> {
 	model: M,
 	prompt: Message,
 	preamble: Option<String>,
@@ -482,12 +826,19 @@ pub struct CompletionRequestBuilder<M: CompletionModel> {
 	tools: Vec<ToolDefinition>,
 	temperature: Option<f64>,
 	max_tokens: Option<u64>,
+	stop_sequences: Vec<String>,
 	tool_choice: Option<ToolChoice>,
 	additional_params: Option<serde_json::Value>,
 }
 
 impl<M: CompletionModel> CompletionRequestBuilder<M> {
 	pub fn new(model: M, prompt: impl Into<Message>) -> Self {
+		Self::with_model(model, prompt)
+	}
+}
+
+impl<M> CompletionRequestBuilder<M> {
+	fn with_model(model: M, prompt: impl Into<Message>) -> Self {
 		Self {
 			model,
 			prompt: prompt.into(),
@@ -497,6 +848,7 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
 			tools: Vec::new(),
 			temperature: None,
 			max_tokens: None,
+			stop_sequences: Vec::new(),
 			tool_choice: None,
 			additional_params: None,
 		}
@@ -605,16 +957,39 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
 		self
 	}
 
-	/// Sets the thing.
+	/// Adds a stop sequence to the completion request.
+	/// Note: Providers limit the number of stop sequences (e.g. 4 for OpenAI)
+	pub fn stop_sequence(mut self, stop_sequence: impl Into<String>) -> Self {
+		self.stop_sequences.push(stop_sequence.into());
+		self
+	}
+
+	/// Adds a list of stop sequences to the completion request.
+	pub fn stop_sequences(self, stop_sequences: Vec<String>) -> Self {
+		stop_sequences
+			.into_iter()
+			.fold(self, |builder, stop| builder.stop_sequence(stop))
+	}
+
+	/// Sets whether (and which) tools the model must use before providing a response.
 	pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
 		self.tool_choice = Some(tool_choice);
 		self
 	}
 
+	/// Sets whether (and which) tools the model must use before providing a response.
+	pub fn tool_choice_opt(mut self, tool_choice: Option<ToolChoice>) -> Self {
+		self.tool_choice = tool_choice;
+		self
+	}
+
 	/// Builds the completion request.
 	pub fn build(self) -> CompletionRequest {
-		let chat_history = OneOrMany::many([self.chat_history, vec![self.prompt]].concat())
+		let mut chat_history = OneOrMany::many([self.chat_history, vec![self.prompt]].concat())
 			.expect("There will always be atleast the prompt");
+		for message in chat_history.iter_mut() {
+			message.sniff_media_types();
+		}
 
 		CompletionRequest {
 			preamble: self.preamble,
@@ -623,15 +998,50 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
 			tools: self.tools,
 			temperature: self.temperature,
 			max_tokens: self.max_tokens,
+			stop_sequences: self.stop_sequences,
 			tool_choice: self.tool_choice,
 			additional_params: self.additional_params,
 		}
 	}
 
+	/// The names of the tools advertised in the request.
+	pub(crate) fn tool_names(&self) -> HashSet<String> {
+		self.tools.iter().map(|tool| tool.name.clone()).collect()
+	}
+}
+
+impl<M: CompletionModel> CompletionRequestBuilder<M> {
+	/// Checks the request for invalid combinations of settings, including the model-specific
+	/// ones, returning the non-fatal warnings. Called automatically when sending the request.
+	pub fn validate(&self) -> Result<Vec<String>, CompletionError> {
+		let request = CompletionRequest {
+			preamble: self.preamble.clone(),
+			chat_history: OneOrMany::many(
+				self.chat_history
+					.iter()
+					.chain([&self.prompt])
+					.cloned()
+					.collect::<Vec<_>>(),
+			)
+			.expect("There will always be atleast the prompt"),
+			documents: self.documents.clone(),
+			tools: self.tools.clone(),
+			temperature: self.temperature,
+			max_tokens: self.max_tokens,
+			stop_sequences: self.stop_sequences.clone(),
+			tool_choice: self.tool_choice.clone(),
+			additional_params: self.additional_params.clone(),
+		};
+
+		validate_with(&self.model, &request)
+	}
+
 	/// Sends the completion request to the completion model provider and returns the completion response.
 	pub async fn send(self) -> Result<CompletionResponse<M::Response>, CompletionError> {
 		let model = self.model.clone();
-		model.completion(self.build()).await
+		let request = self.build();
+		validate_with(&model, &request)?;
+		model.completion(request).await
 	}
 
 	/// Stream the completion request
@@ -643,14 +1053,63 @@ impl<M: CompletionModel> CompletionRequestBuilder<M> {
 		Self: 'a,
 	{
 		let model = self.model.clone();
-		model.stream(self.build()).await
+		let request = self.build();
+		validate_with(&model, &request)?;
+		model.stream(request).await
+	}
+}
+
+impl<M> From<CompletionRequestBuilder<M>> for CompletionRequest {
+	fn from(builder: CompletionRequestBuilder<M>) -> Self {
+		builder.build()
+	}
+}
+
+impl From<CompletionRequest> for CompletionRequestBuilder {
+	/// Reopens a request, e.g. one built by an agent, with its last message as the prompt.
+	fn from(request: CompletionRequest) -> Self {
+		let mut chat_history = request.chat_history.into_iter().collect::<Vec<_>>();
+		let prompt = chat_history
+			.pop()
+			.expect("There will always be atleast the prompt");
+
+		Self {
+			model: (),
+			prompt,
+			preamble: request.preamble,
+			chat_history,
+			documents: request.documents,
+			tools: request.tools,
+			temperature: request.temperature,
+			max_tokens: request.max_tokens,
+			stop_sequences: request.stop_sequences,
+			tool_choice: request.tool_choice,
+			additional_params: request.additional_params,
+		}
 	}
 }
 
+/// Runs the generic and model-specific checks of a request, logging the warnings.
+fn validate_with<M: CompletionModel>(
+	model: &M,
+	request: &CompletionRequest,
+) -> Result<Vec<String>, CompletionError> {
+	let mut report = request.validate();
+	model.validate_request(request, &mut report);
+
+	let warnings = report.into_result()?;
+	for warning in &warnings {
+		tracing::warn!(target: "clankers::completions", "{warning}");
+	}
+
+	Ok(warnings)
+}
+
 #[cfg(test)]
 mod tests {
 
 	use super::*;
+	use crate::test_utils::MockCompletionModel;
 
 	#[test]
 	fn test_document_display_without_metadata() {
@@ -699,16 +1158,9 @@ mod tests {
 			additional_props: HashMap::new(),
 		};
 
-		let request = CompletionRequest {
-			preamble: None,
-			chat_history: OneOrMany::one("What is the capital of France?".into()),
-			documents: vec![doc1, doc2],
-			tools: Vec::new(),
-			temperature: None,
-			max_tokens: None,
-			tool_choice: None,
-			additional_params: None,
-		};
+		let request = CompletionRequest::builder("What is the capital of France?")
+			.documents(vec![doc1, doc2])
+			.build();
 
 		let expected = Message::User {
 			content: OneOrMany::many(vec![
@@ -727,19 +1179,372 @@ mod tests {
 		assert_eq!(request.normalized_documents(), Some(expected));
 	}
 
+	#[test]
+	fn test_text_only_providers_render_documents_alike() {
+		use crate::providers::{deepseek, galadriel, ollama};
+
+		let documents = ["doc1", "doc2"].map(|id| Document {
+			id: id.to_string(),
+			text: format!("Text of {id}."),
+			additional_props: HashMap::new(),
+		});
+		let request = CompletionRequest::builder("What is the capital of France?")
+			.preamble("Answer briefly.".to_string())
+			.documents(documents.to_vec())
+			.build();
+
+		let messages = [
+			serde_json::to_value(
+				deepseek::completion::DeepseekCompletionRequest::try_from((
+					"deepseek-chat",
+					request.clone(),
+				))
+				.unwrap(),
+			),
+			serde_json::to_value(
+				galadriel::completion::GaladrielCompletionRequest::try_from((
+					"llama3.1:70b",
+					request.clone(),
+				))
+				.unwrap(),
+			),
+			serde_json::to_value(
+				ollama::completion::OllamaCompletionRequest::try_from(("llama3.2", request))
+					.unwrap(),
+			),
+		]
+		.map(|request| {
+			request.unwrap()["messages"]
+				.as_array()
+				.unwrap()
+				.iter()
+				.map(|message| (message["role"].clone(), message["content"].clone()))
+				.collect::<Vec<_>>()
+		});
+
+		let expected = [
+			("system", "Answer briefly."),
+			(
+				"user",
+				concat!(
+					"<file id: doc1>\nText of doc1.\n</file>\n",
+					"<file id: doc2>\nText of doc2.\n</file>\n",
+				),
+			),
+			("user", "What is the capital of France?"),
+		]
+		.map(|(role, content)| (role.into(), content.into()));
+		for messages in messages {
+			assert_eq!(messages, expected);
+		}
+	}
+
 	#[test]
 	fn test_normalize_documents_without_documents() {
-		let request = CompletionRequest {
-			preamble: None,
-			chat_history: OneOrMany::one("What is the capital of France?".into()),
-			documents: Vec::new(),
-			tools: Vec::new(),
-			temperature: None,
-			max_tokens: None,
-			tool_choice: None,
-			additional_params: None,
-		};
+		let request = CompletionRequest::builder("What is the capital of France?").build();
 
 		assert_eq!(request.normalized_documents(), None);
 	}
+
+	#[test]
+	fn test_builder_defaults() {
+		let request = CompletionRequest::builder("Hello").build();
+
+		assert_eq!(request.preamble, None);
+		assert_eq!(request.chat_history, OneOrMany::one("Hello".into()));
+		assert!(request.documents.is_empty());
+		assert!(request.tools.is_empty());
+		assert_eq!(request.temperature, None);
+		assert_eq!(request.max_tokens, None);
+		assert!(request.stop_sequences.is_empty());
+		assert_eq!(request.tool_choice, None);
+		assert_eq!(request.additional_params, None);
+	}
+
+	#[test]
+	fn test_builder_overrides() {
+		let request = CompletionRequest::builder("Hello")
+			.temperature(0.5)
+			.temperature(0.9)
+			.max_tokens_opt(Some(100))
+			.max_tokens_opt(None)
+			.additional_params(serde_json::json!({ "a": 1, "b": 1 }))
+			.additional_params(serde_json::json!({ "b": 2 }))
+			.stop_sequence("END")
+			.stop_sequences(vec!["STOP".to_string()])
+			.build();
+
+		assert_eq!(request.temperature, Some(0.9));
+		assert_eq!(request.max_tokens, None);
+		// Additional parameters are merged, the last value wins
+		assert_eq!(
+			request.additional_params,
+			Some(serde_json::json!({ "a": 1, "b": 2 }))
+		);
+		assert_eq!(request.stop_sequences, ["END", "STOP"]);
+	}
+
+	#[test]
+	fn test_builder_from_request() {
+		let request = CompletionRequest::builder("How are you?")
+			.messages(vec![Message::user("Hello"), Message::assistant("Hi!")])
+			.preamble("Be nice.".to_string())
+			.tool(weather_tool())
+			.tool_choice(ToolChoice::Required)
+			.build();
+
+		let reopened = CompletionRequestBuilder::from(request.clone())
+			.tool_choice_opt(None)
+			.build();
+
+		assert_eq!(reopened.chat_history, request.chat_history);
+		assert_eq!(reopened.chat_history.last(), Message::user("How are you?"));
+		assert_eq!(reopened.preamble, request.preamble);
+		assert_eq!(reopened.tools, request.tools);
+		assert_eq!(reopened.tool_choice, None);
+	}
+
+	fn weather_tool() -> ToolDefinition {
+		ToolDefinition {
+			name: "get_weather".to_string(),
+			description: "Get the weather".to_string(),
+			parameters: serde_json::json!({"type": "object"}),
+		}
+	}
+
+	fn validate<M: CompletionModel>(
+		builder: CompletionRequestBuilder<M>,
+	) -> Result<Vec<String>, String> {
+		builder.validate().map_err(|e| e.to_string())
+	}
+
+	#[test]
+	fn test_validate_errors() {
+		let model = MockCompletionModel::default();
+		let request = || model.completion_request("Hello");
+
+		assert_eq!(validate(request()), Ok(vec![]));
+
+		let err = validate(request().max_tokens(0)).unwrap_err();
+		assert!(err.contains("`max_tokens` must be greater than 0"), "{err}");
+
+		let err = validate(request().temperature(-0.5)).unwrap_err();
+		assert!(err.contains("non-negative"), "{err}");
+		let err = validate(request().temperature(f64::NAN)).unwrap_err();
+		assert!(err.contains("non-negative"), "{err}");
+
+		let err = validate(model.completion_request(" ")).unwrap_err();
+		assert!(err.contains("empty messages"), "{err}");
+		// A tool result is not empty, even without text
+		assert!(validate(model.completion_request(Message::tool_result("call_1", ""))).is_ok());
+
+		let err = validate(
+			request()
+				.tool(weather_tool())
+				.tool_choice(ToolChoice::Specific {
+					function_names: vec!["get_weather".into(), "get_time".into()],
+				}),
+		)
+		.unwrap_err();
+		assert!(err.contains("unknown tool `get_time`"), "{err}");
+		assert!(!err.contains("get_weather"), "{err}");
+
+		// Every error is reported at once
+		let err = validate(request().max_tokens(0).temperature(-1.0)).unwrap_err();
+		assert!(
+			err.contains("max_tokens") && err.contains("temperature"),
+			"{err}"
+		);
+	}
+
+	#[test]
+	fn test_validate_warnings() {
+		let model = MockCompletionModel::default();
+
+		let warnings = validate(
+			model
+				.completion_request("Hello")
+				.tool_choice(ToolChoice::Required),
+		)
+		.unwrap();
+		assert_eq!(
+			warnings,
+			["`tool_choice` is set but there are no tools, it will be ignored"]
+		);
+
+		let request = model
+			.completion_request("Hello")
+			.tool_choice(ToolChoice::Auto)
+			.build();
+		assert!(request.warnings().is_empty());
+
+		let request = model
+			.completion_request("Hello")
+			.tool(weather_tool())
+			.tool_choice(ToolChoice::Required)
+			.build();
+		assert!(request.warnings().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_invalid_request_is_not_sent() {
+		let model = MockCompletionModel::default();
+
+		let err = model
+			.completion_request("Hello")
+			.max_tokens(0)
+			.send()
+			.await
+			.unwrap_err();
+		assert!(matches!(err, CompletionError::RequestError(_)), "{err:?}");
+
+		let err = model
+			.completion_request("Hello")
+			.temperature(-1.0)
+			.stream()
+			.await
+			.err()
+			.unwrap();
+		assert!(matches!(err, CompletionError::RequestError(_)), "{err:?}");
+		assert!(model.requests().is_empty());
+
+		// Warnings don't prevent the request from being sent
+		model
+			.completion_request("Hello")
+			.tool_choice(ToolChoice::Required)
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(model.requests().len(), 1);
+	}
+
+	#[test]
+	fn test_provider_validation() {
+		use crate::client::CompletionClient;
+		use crate::providers::{anthropic, gemini, openai};
+		use crate::test_utils::MockSseClient;
+
+		let openai = openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(MockSseClient::default())
+			.build()
+			.unwrap();
+		let chat = openai.completion_model("gpt-4o").completions_api();
+		let responses = openai.completion_model("gpt-4o");
+
+		assert!(validate(chat.completion_request("Hello").temperature(2.0)).is_ok());
+		let err = validate(chat.completion_request("Hello").temperature(2.5)).unwrap_err();
+		assert!(err.contains("between 0 and 2"), "{err}");
+		let err = validate(responses.completion_request("Hello").temperature(2.5)).unwrap_err();
+		assert!(err.contains("between 0 and 2"), "{err}");
+
+		let anthropic = anthropic::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(MockSseClient::default())
+			.build()
+			.unwrap()
+			.completion_model("claude-sonnet-4-5");
+		let err = validate(anthropic.completion_request("Hello").temperature(1.5)).unwrap_err();
+		assert!(err.contains("between 0 and 1"), "{err}");
+
+		let gemini = gemini::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(MockSseClient::default())
+			.build()
+			.unwrap();
+		let gemini = gemini::completion::CompletionModel::new(gemini, "gemini-2.5-flash");
+		let warnings = validate(gemini.completion_request("Hello").document(Document {
+			id: "doc1".to_string(),
+			text: "Document 1 text.".to_string(),
+			additional_props: HashMap::new(),
+		}))
+		.unwrap();
+		assert_eq!(
+			warnings,
+			["Gemini doesn't support `documents`, they will be ignored"]
+		);
+	}
+
+	fn usage(input_tokens: u64, output_tokens: u64, cached_input_tokens: u64) -> Usage {
+		Usage {
+			input_tokens,
+			output_tokens,
+			total_tokens: input_tokens + output_tokens,
+			cached_input_tokens,
+			cache_creation_input_tokens: 0,
+		}
+	}
+
+	#[test]
+	fn test_usage_display() {
+		assert_eq!(
+			usage(1_234, 540, 800).to_string(),
+			"in: 1.2k, out: 540, cached: 800, total: 1.8k"
+		);
+		assert_eq!(
+			usage(999, 1_000, 0).to_string(),
+			"in: 999, out: 1k, total: 2k"
+		);
+		assert_eq!(
+			usage(999_949, 999_950, 0).to_string(),
+			"in: 999.9k, out: 1M, total: 2M"
+		);
+		assert_eq!(
+			usage(12_345_678, 0, 0).to_string(),
+			"in: 12.3M, out: 0, total: 12.3M"
+		);
+
+		let written = Usage {
+			cache_creation_input_tokens: 1_500,
+			..usage(2_000, 10, 0)
+		};
+		assert_eq!(
+			written.to_string(),
+			"in: 2k, out: 10, cache write: 1.5k, total: 2k"
+		);
+	}
+
+	#[test]
+	fn test_usage_addition_saturates() {
+		let mut total = usage(10, 20, 5);
+		total += usage(1, 2, 3);
+		assert_eq!(total, usage(11, 22, 8));
+		assert_eq!(
+			[usage(1, 1, 0), usage(2, 2, 1)].into_iter().sum::<Usage>(),
+			usage(3, 3, 1)
+		);
+
+		let max = Usage {
+			total_tokens: u64::MAX,
+			..usage(u64::MAX - 1, 1, 0)
+		};
+		let sum = max + usage(5, 5, 5);
+		assert_eq!(sum.input_tokens, u64::MAX);
+		assert_eq!(sum.output_tokens, 6);
+		assert_eq!(sum.total_tokens, u64::MAX);
+		assert_eq!(sum.cached_input_tokens, 5);
+
+		assert!(Usage::new().is_empty());
+		assert!(!sum.is_empty());
+	}
+
+	#[test]
+	fn test_usage_serialization_is_stable() {
+		let value = serde_json::to_value(usage(1, 2, 3)).unwrap();
+		assert_eq!(
+			value,
+			serde_json::json!({
+				"input_tokens": 1,
+				"output_tokens": 2,
+				"total_tokens": 3,
+				"cached_input_tokens": 3,
+				"cache_creation_input_tokens": 0
+			})
+		);
+
+		let usage: Usage =
+			serde_json::from_str(r#"{"input_tokens": 1, "output_tokens": 2, "total_tokens": 3}"#)
+				.unwrap();
+		assert_eq!(usage.cached_input_tokens, 0);
+	}
 }
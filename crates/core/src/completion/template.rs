@@ -0,0 +1,223 @@
+//! Minimal prompt templating with `{variable}` placeholders.
+//!
+//! Placeholders are variable names made of ASCII alphanumerics and underscores wrapped in
+//! braces. Literal braces can be written as `{{` and `}}`. Any other brace (e.g. inside a
+//! JSON snippet) is left untouched, so existing prompts render unchanged.
+//!
+//! # Example
+//! ```rust
+//! use std::collections::HashMap;
+//! use clankers::completion::PromptTemplate;
+//!
+//! let template = PromptTemplate::new("You are {name}, answer in {{JSON}}.");
+//! let vars = HashMap::from([("name", "a helpful assistant".to_string())]);
+//!
+//! assert_eq!(
+//!     template.render(&vars).unwrap(),
+//!     "You are a helpful assistant, answer in {JSON}."
+//! );
+//! ```
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use thiserror::Error;
+
+/// Error returned when rendering a [PromptTemplate] without all of its variables.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Missing template variable(s): {}", names.join(", "))]
+pub struct MissingVar {
+	/// Names of the variables that were not provided, in order of first appearance
+	pub names: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+	Literal(String),
+	Variable(String),
+}
+
+/// A prompt template with `{variable}` placeholders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+	source: String,
+	segments: Vec<Segment>,
+}
+
+impl PromptTemplate {
+	/// Parses a new template from its source text.
+	pub fn new(source: impl Into<String>) -> Self {
+		let source = source.into();
+		let segments = parse(&source);
+		Self { source, segments }
+	}
+
+	/// Returns the raw template source.
+	pub fn source(&self) -> &str {
+		&self.source
+	}
+
+	/// Returns the names of the variables referenced by the template, in order of appearance.
+	pub fn variables(&self) -> impl Iterator<Item = &str> {
+		self.segments.iter().filter_map(|segment| match segment {
+			Segment::Variable(name) => Some(name.as_str()),
+			Segment::Literal(_) => None,
+		})
+	}
+
+	/// Renders the template, substituting every placeholder with its value in `vars`.
+	/// Fails with [MissingVar] listing every variable that has no value.
+	pub fn render<K>(&self, vars: &HashMap<K, String>) -> Result<String, MissingVar>
+	where
+		K: Borrow<str> + Hash + Eq,
+	{
+		let mut rendered = String::with_capacity(self.source.len());
+		let mut missing: Vec<String> = Vec::new();
+
+		for segment in &self.segments {
+			match segment {
+				Segment::Literal(text) => rendered.push_str(text),
+				Segment::Variable(name) => match vars.get(name.as_str()) {
+					Some(value) => rendered.push_str(value),
+					None if !missing.contains(name) => missing.push(name.clone()),
+					None => {}
+				},
+			}
+		}
+
+		if missing.is_empty() {
+			Ok(rendered)
+		} else {
+			Err(MissingVar { names: missing })
+		}
+	}
+}
+
+impl From<&str> for PromptTemplate {
+	fn from(source: &str) -> Self {
+		Self::new(source)
+	}
+}
+
+impl From<String> for PromptTemplate {
+	fn from(source: String) -> Self {
+		Self::new(source)
+	}
+}
+
+impl std::fmt::Display for PromptTemplate {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.source)
+	}
+}
+
+fn is_variable_name(name: &str) -> bool {
+	!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse(source: &str) -> Vec<Segment> {
+	let mut segments = Vec::new();
+	let mut literal = String::new();
+	let mut rest = source;
+
+	while let Some(idx) = rest.find(['{', '}']) {
+		literal.push_str(&rest[..idx]);
+		let tail = &rest[idx..];
+
+		if let Some(after) = tail.strip_prefix("{{") {
+			literal.push('{');
+			rest = after;
+		} else if let Some(after) = tail.strip_prefix("}}") {
+			literal.push('}');
+			rest = after;
+		} else if let Some(end) = tail.strip_prefix('{').and_then(|t| t.find('}'))
+			&& is_variable_name(&tail[1..=end])
+		{
+			if !literal.is_empty() {
+				segments.push(Segment::Literal(std::mem::take(&mut literal)));
+			}
+			segments.push(Segment::Variable(tail[1..=end].to_string()));
+			rest = &tail[end + 2..];
+		} else {
+			literal.push_str(&tail[..1]);
+			rest = &tail[1..];
+		}
+	}
+
+	literal.push_str(rest);
+	if !literal.is_empty() {
+		segments.push(Segment::Literal(literal));
+	}
+
+	segments
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_render_variables() {
+		let template = PromptTemplate::new("Hello {name}, welcome to {place}!");
+		let vars = HashMap::from([
+			("name", "Ada".to_string()),
+			("place", "the lab".to_string()),
+		]);
+
+		assert_eq!(
+			template.variables().collect::<Vec<_>>(),
+			vec!["name", "place"]
+		);
+		assert_eq!(
+			template.render(&vars).unwrap(),
+			"Hello Ada, welcome to the lab!"
+		);
+	}
+
+	#[test]
+	fn test_escaped_braces() {
+		let template = PromptTemplate::new("{{name}} is literal, {name} is not");
+		let vars = HashMap::from([("name", "Ada".to_string())]);
+
+		assert_eq!(
+			template.render(&vars).unwrap(),
+			"{name} is literal, Ada is not"
+		);
+	}
+
+	#[test]
+	fn test_non_variable_braces_are_kept() {
+		let template = PromptTemplate::new(r#"Respond with {"answer": {answer}} or { }"#);
+		let vars = HashMap::from([("answer", "42".to_string())]);
+
+		assert_eq!(
+			template.render(&vars).unwrap(),
+			r#"Respond with {"answer": 42} or { }"#
+		);
+	}
+
+	#[test]
+	fn test_missing_variables_are_listed() {
+		let template = PromptTemplate::new("{greeting} {name}, {greeting} again");
+		let vars: HashMap<&str, String> = HashMap::new();
+
+		let err = template.render(&vars).unwrap_err();
+		assert_eq!(err.names, vec!["greeting", "name"]);
+		assert_eq!(
+			err.to_string(),
+			"Missing template variable(s): greeting, name"
+		);
+	}
+
+	#[test]
+	fn test_render_with_owned_keys() {
+		let template = PromptTemplate::from("{a}{b}");
+		let vars = HashMap::from([
+			("a".to_string(), "1".to_string()),
+			("b".to_string(), "2".to_string()),
+		]);
+
+		assert_eq!(template.render(&vars).unwrap(), "12");
+	}
+}
@@ -0,0 +1,209 @@
+//! Client-side token counting, used to estimate the size of a prompt before it is sent.
+//!
+//! Every [`CompletionModel`](super::CompletionModel) exposes a counter through
+//! [`CompletionModel::token_counter`](super::CompletionModel::token_counter). Unless a provider
+//! knows better, it is a [`HeuristicTokenCounter`]. With the `tiktoken` feature enabled,
+//! OpenAI models count with their exact BPE encoding instead.
+
+use std::sync::Arc;
+
+use super::message::{
+	AssistantContent, Document, DocumentSourceKind, Message, ToolResultContent, UserContent,
+};
+use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
+
+/// Tokens added to every message for its role and formatting.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Counts tokens the way a model's tokenizer would, or an estimate thereof.
+pub trait TokenCounter: WasmCompatSend + WasmCompatSync {
+	/// Counts the tokens in `text`.
+	fn count_text(&self, text: &str) -> usize;
+
+	/// Counts the tokens in a conversation, including a small overhead per message.
+	/// Only text is counted: images, audio, video and non-text documents are ignored.
+	fn count_messages(&self, messages: &[Message]) -> usize {
+		messages
+			.iter()
+			.map(|message| {
+				MESSAGE_OVERHEAD
+					+ message_texts(message)
+						.iter()
+						.map(|text| self.count_text(text))
+						.sum::<usize>()
+			})
+			.sum()
+	}
+}
+
+impl<T> TokenCounter for Arc<T>
+where
+	T: TokenCounter + ?Sized,
+{
+	fn count_text(&self, text: &str) -> usize {
+		(**self).count_text(text)
+	}
+
+	fn count_messages(&self, messages: &[Message]) -> usize {
+		(**self).count_messages(messages)
+	}
+}
+
+/// Estimates tokens from the number of characters in the text.
+///
+/// The default of four characters per token is a good approximation for English text
+/// with most modern tokenizers; code and other languages usually need more tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeuristicTokenCounter {
+	pub chars_per_token: f64,
+}
+
+impl Default for HeuristicTokenCounter {
+	fn default() -> Self {
+		Self {
+			chars_per_token: 4.0,
+		}
+	}
+}
+
+impl TokenCounter for HeuristicTokenCounter {
+	fn count_text(&self, text: &str) -> usize {
+		(text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+	}
+}
+
+/// Counts tokens exactly with the BPE encoding used by an OpenAI model.
+#[cfg(feature = "tiktoken")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tiktoken")))]
+#[derive(Clone, Copy)]
+pub struct TiktokenCounter {
+	bpe: &'static tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenCounter {
+	/// Returns a counter for the encoding of `model`, or `None` if the model is not known.
+	pub fn for_model(model: &str) -> Option<Self> {
+		use tiktoken_rs::tokenizer::{Tokenizer, get_tokenizer};
+
+		let bpe = match get_tokenizer(model)? {
+			Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+			Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+			Tokenizer::P50kBase => tiktoken_rs::p50k_base_singleton(),
+			Tokenizer::P50kEdit => tiktoken_rs::p50k_edit_singleton(),
+			Tokenizer::R50kBase | Tokenizer::Gpt2 => tiktoken_rs::r50k_base_singleton(),
+		};
+
+		Some(Self { bpe })
+	}
+}
+
+#[cfg(feature = "tiktoken")]
+impl TokenCounter for TiktokenCounter {
+	fn count_text(&self, text: &str) -> usize {
+		self.bpe.encode_with_special_tokens(text).len()
+	}
+}
+
+/// Collects the text that a message contributes to the prompt.
+fn message_texts(message: &Message) -> Vec<String> {
+	match message {
+		Message::User { content } => content
+			.iter()
+			.flat_map(|content| match content {
+				UserContent::Text(text) => vec![text.text.clone()],
+				UserContent::ToolResult(result) => result
+					.content
+					.iter()
+					.filter_map(|content| match content {
+						ToolResultContent::Text(text) => Some(text.text.clone()),
+						ToolResultContent::Image(_) => None,
+					})
+					.collect(),
+				UserContent::Document(Document {
+					data: DocumentSourceKind::String(text),
+					..
+				}) => vec![text.clone()],
+				_ => vec![],
+			})
+			.collect(),
+		Message::Assistant { content, .. } => content
+			.iter()
+			.flat_map(|content| match content {
+				AssistantContent::Text(text) => vec![text.text.clone()],
+				AssistantContent::ToolCall(tool_call) => vec![
+					tool_call.function.name.clone(),
+					tool_call.function.arguments.to_string(),
+				],
+				AssistantContent::Reasoning(reasoning) => reasoning.reasoning.clone(),
+				AssistantContent::Image(_) | AssistantContent::Document(_) => vec![],
+			})
+			.collect(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::OneOrMany;
+
+	#[test]
+	fn test_heuristic_count_text() {
+		let counter = HeuristicTokenCounter::default();
+
+		assert_eq!(counter.count_text(""), 0);
+		assert_eq!(counter.count_text("abc"), 1);
+		assert_eq!(counter.count_text("abcd"), 1);
+		assert_eq!(counter.count_text("abcde"), 2);
+		// Characters, not bytes
+		assert_eq!(counter.count_text("ééééé"), 2);
+
+		let counter = HeuristicTokenCounter {
+			chars_per_token: 2.0,
+		};
+		assert_eq!(counter.count_text("abcde"), 3);
+	}
+
+	#[test]
+	fn test_heuristic_count_messages() {
+		let counter = HeuristicTokenCounter::default();
+		let messages = vec![
+			Message::user("What's the weather in Paris?"),
+			Message::Assistant {
+				id: None,
+				content: OneOrMany::one(AssistantContent::tool_call(
+					"call-1",
+					"weather",
+					json!({ "city": "Paris" }),
+				)),
+			},
+			Message::tool_result("call-1", "Sunny, 24°C"),
+			Message::User {
+				content: OneOrMany::one(UserContent::image_base64("aGVsbG8=", None, None)),
+			},
+		];
+
+		let expected = MESSAGE_OVERHEAD
+			+ counter.count_text("What's the weather in Paris?")
+			+ MESSAGE_OVERHEAD
+			+ counter.count_text("weather")
+			+ counter.count_text(r#"{"city":"Paris"}"#)
+			+ MESSAGE_OVERHEAD
+			+ counter.count_text("Sunny, 24°C")
+			+ MESSAGE_OVERHEAD;
+		assert_eq!(counter.count_messages(&messages), expected);
+
+		let shared: Arc<dyn TokenCounter> = Arc::new(counter);
+		assert_eq!(shared.count_messages(&messages), expected);
+	}
+
+	#[cfg(feature = "tiktoken")]
+	#[test]
+	fn test_tiktoken_count_text() {
+		let counter = TiktokenCounter::for_model("gpt-4o").unwrap();
+		assert_eq!(counter.count_text("hello world"), 2);
+		assert!(TiktokenCounter::for_model("not-a-model").is_none());
+	}
+}
@@ -1,11 +1,15 @@
 //! The module defines the [EmbeddingsBuilder] struct which accumulates objects to be embedded
 //! and batch generates the embeddings for each object when built.
 //! Only types that implement the [Embed] trait can be added to the [EmbeddingsBuilder].
+//!
+//! Each document can carry an id and metadata, returned with its embeddings by
+//! [EmbeddingsBuilder::build_documents] as an [EmbeddedDocument].
 
 use std::cmp::max;
 use std::collections::HashMap;
 
 use futures::{StreamExt, stream};
+use serde::Serialize;
 
 use crate::OneOrMany;
 use crate::embeddings::embed::TextEmbedder;
@@ -52,7 +56,49 @@ where
 	T: Embed,
 {
 	model: M,
-	documents: Vec<(T, Vec<String>)>,
+	documents: Vec<PendingDocument<T>>,
+}
+
+/// A document waiting to be embedded, along with its texts.
+struct PendingDocument<T> {
+	id: String,
+	metadata: serde_json::Value,
+	document: T,
+	texts: Vec<String>,
+}
+
+/// A document along with its id, metadata and embeddings.
+/// Each embedding carries the text it was generated from in [Embedding::document].
+#[derive(Clone, Debug)]
+pub struct EmbeddedDocument<T> {
+	/// The id given when adding the document, or its [content_id] otherwise
+	pub id: String,
+	/// The metadata given when adding the document, `null` if none was given
+	pub metadata: serde_json::Value,
+	pub document: T,
+	pub embeddings: OneOrMany<Embedding>,
+}
+
+/// Generates a deterministic id from the texts of a document: the hex encoded 128-bit FNV-1a
+/// hash of the texts, each prefixed with its length.
+/// The id is stable across runs and platforms, so re-embedding the same content yields the same id.
+pub fn content_id(texts: &[String]) -> String {
+	const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+	const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+	let hash = texts
+		.iter()
+		.flat_map(|text| {
+			(text.len() as u64)
+				.to_le_bytes()
+				.into_iter()
+				.chain(text.bytes())
+		})
+		.fold(OFFSET_BASIS, |hash, byte| {
+			(hash ^ u128::from(byte)).wrapping_mul(PRIME)
+		});
+
+	format!("{hash:032x}")
 }
 
 impl<M, T> EmbeddingsBuilder<M, T>
@@ -69,11 +115,39 @@ where
 	}
 
 	/// Add a document to be embedded to the builder. `document` must implement the [Embed] trait.
-	pub fn document(mut self, document: T) -> Result<Self, EmbedError> {
+	/// Its id is the [content_id] of its texts.
+	pub fn document(self, document: T) -> Result<Self, EmbedError> {
+		self.push(None, serde_json::Value::Null, document)
+	}
+
+	/// Add a document along with its id and metadata, both kept in the output of
+	/// [EmbeddingsBuilder::build_documents]. Without an id, the [content_id] of the document's
+	/// texts is used.
+	pub fn document_with_metadata(
+		self,
+		id: Option<String>,
+		metadata: impl Serialize,
+		document: T,
+	) -> Result<Self, EmbedError> {
+		let metadata = serde_json::to_value(metadata).map_err(EmbedError::new)?;
+		self.push(id, metadata, document)
+	}
+
+	fn push(
+		mut self,
+		id: Option<String>,
+		metadata: serde_json::Value,
+		document: T,
+	) -> Result<Self, EmbedError> {
 		let mut embedder = TextEmbedder::default();
 		document.embed(&mut embedder)?;
 
-		self.documents.push((document, embedder.texts));
+		self.documents.push(PendingDocument {
+			id: id.unwrap_or_else(|| content_id(&embedder.texts)),
+			metadata,
+			document,
+			texts: embedder.texts,
+		});
 
 		Ok(self)
 	}
@@ -97,21 +171,28 @@ where
 	/// Generate embeddings for all documents in the builder.
 	/// Returns a vector of tuples, where the first element is the document and the second element is the embeddings (either one embedding or many).
 	pub async fn build(self) -> Result<Vec<(T, OneOrMany<Embedding>)>, EmbeddingError> {
-		use stream::TryStreamExt;
+		Ok(self
+			.build_documents()
+			.await?
+			.into_iter()
+			.map(|embedded| (embedded.document, embedded.embeddings))
+			.collect())
+	}
 
-		// Store the documents and their texts in a HashMap for easy access.
-		let mut docs = HashMap::new();
-		let mut texts = Vec::new();
+	/// Generate embeddings for all documents in the builder, keeping their ids and metadata.
+	/// The documents are returned in the order they were added.
+	pub async fn build_documents(self) -> Result<Vec<EmbeddedDocument<T>>, EmbeddingError> {
+		use stream::TryStreamExt;
 
-		// Iterate over all documents in the builder and insert their docs and texts into the lookup stores.
-		for (i, (doc, doc_texts)) in self.documents.into_iter().enumerate() {
-			docs.insert(i, doc);
-			texts.push((i, doc_texts));
-		}
+		let texts = self
+			.documents
+			.iter()
+			.enumerate()
+			.flat_map(|(i, doc)| doc.texts.iter().cloned().map(move |text| (i, text)))
+			.collect::<Vec<_>>();
 
 		// Compute the embeddings.
-		let mut embeddings = stream::iter(texts.into_iter())
-			.flat_map(|(i, texts)| stream::iter(texts.into_iter().map(move |text| (i, text))))
+		let mut embeddings = stream::iter(texts)
 			.chunks(M::MAX_DOCUMENTS)
 			.map(|text| async {
 				let (ids, docs): (Vec<_>, Vec<_>) = text.into_iter().unzip();
@@ -137,13 +218,15 @@ where
 			.await?;
 
 		// Merge the embeddings with their respective documents
-		Ok(docs
+		Ok(self
+			.documents
 			.into_iter()
-			.map(|(i, doc)| {
-				(
-					doc,
-					embeddings.remove(&i).expect("Document should be present"),
-				)
+			.enumerate()
+			.map(|(i, doc)| EmbeddedDocument {
+				id: doc.id,
+				metadata: doc.metadata,
+				document: doc.document,
+				embeddings: embeddings.remove(&i).expect("Document should be present"),
 			})
 			.collect())
 	}
@@ -151,7 +234,9 @@ where
 
 #[cfg(test)]
 mod tests {
-	use super::EmbeddingsBuilder;
+	use serde_json::json;
+
+	use super::{EmbeddingsBuilder, content_id};
 	use crate::Embed;
 	use crate::client::Nothing;
 	use crate::embeddings::embed::{EmbedError, TextEmbedder};
@@ -397,4 +482,60 @@ mod tests {
             second_definition.1.rest()[0].document, "A fictional creature found in the distant, swampy marshlands of the planet Glibbo in the Andromeda galaxy.".to_string()
         )
 	}
+
+	#[test]
+	fn test_content_id_is_deterministic() {
+		let texts = vec![
+			"A green alien".to_string(),
+			"that lives on cold planets.".to_string(),
+		];
+
+		assert_eq!(content_id(&texts), content_id(&texts.clone()));
+		assert_eq!(content_id(&texts).len(), 32);
+		assert_eq!(content_id(&[]), "6c62272e07bb014262b821756295c58d");
+
+		// Moving text between texts changes the id
+		assert_ne!(
+			content_id(&["ab".to_string(), "c".to_string()]),
+			content_id(&["a".to_string(), "bc".to_string()])
+		);
+	}
+
+	#[tokio::test]
+	async fn test_build_documents_keeps_ids_and_metadata() {
+		let definitions = definitions_single_text();
+
+		let result = EmbeddingsBuilder::new(Model)
+			.document_with_metadata(
+				Some("flurbo".to_string()),
+				json!({ "source": "dictionary", "page": 12 }),
+				definitions[0].clone(),
+			)
+			.unwrap()
+			.document(definitions[1].clone())
+			.unwrap()
+			.build_documents()
+			.await
+			.unwrap();
+
+		assert_eq!(result.len(), 2);
+
+		assert_eq!(result[0].id, "flurbo");
+		assert_eq!(
+			result[0].metadata,
+			json!({ "source": "dictionary", "page": 12 })
+		);
+		assert_eq!(result[0].document.id, "doc0");
+		assert_eq!(
+			result[0].embeddings.first().document,
+			definitions[0].definition
+		);
+
+		assert_eq!(
+			result[1].id,
+			content_id(&[definitions[1].definition.clone()])
+		);
+		assert!(result[1].metadata.is_null());
+		assert_eq!(result[1].embeddings.first().vec.len(), 10);
+	}
 }
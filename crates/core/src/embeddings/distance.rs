@@ -1,3 +1,10 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ordered_float::OrderedFloat;
+
+use crate::embeddings::Embedding;
+
 pub trait VectorDistance {
 	/// Get dot product of two embedding vectors
 	fn dot_product(&self, other: &Self) -> f64;
@@ -19,8 +26,107 @@ pub trait VectorDistance {
 	fn chebyshev_distance(&self, other: &Self) -> f64;
 }
 
+/// The embeddings compared don't have the same number of dimensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Embedding dimension mismatch: expected {expected}, got {actual}")]
+pub struct DimensionMismatch {
+	pub expected: usize,
+	pub actual: usize,
+}
+
+impl Embedding {
+	fn check_dimensions(&self, other: &Self) -> Result<(), DimensionMismatch> {
+		if self.vec.len() == other.vec.len() {
+			Ok(())
+		} else {
+			Err(DimensionMismatch {
+				expected: self.vec.len(),
+				actual: other.vec.len(),
+			})
+		}
+	}
+
+	/// Dot product of two embedding vectors.
+	pub fn try_dot(&self, other: &Self) -> Result<f64, DimensionMismatch> {
+		self.check_dimensions(other)?;
+
+		Ok(self.vec.iter().zip(&other.vec).map(|(x, y)| x * y).sum())
+	}
+
+	/// Euclidean norm of the embedding vector.
+	pub fn magnitude(&self) -> f64 {
+		self.vec.iter().map(|x| x * x).sum::<f64>().sqrt()
+	}
+
+	/// Cosine similarity of two embedding vectors, between -1 and 1.
+	/// The vectors don't need to be normalized. A zero vector has no similarity to any vector,
+	/// so `0.0` is returned.
+	pub fn try_cosine_similarity(&self, other: &Self) -> Result<f64, DimensionMismatch> {
+		let dot = self.try_dot(other)?;
+		let magnitudes = self.magnitude() * other.magnitude();
+
+		if magnitudes == 0.0 {
+			Ok(0.0)
+		} else {
+			Ok((dot / magnitudes).clamp(-1.0, 1.0))
+		}
+	}
+
+	/// Euclidean distance of two embedding vectors.
+	pub fn try_euclidean_distance(&self, other: &Self) -> Result<f64, DimensionMismatch> {
+		self.check_dimensions(other)?;
+
+		Ok(self
+			.vec
+			.iter()
+			.zip(&other.vec)
+			.map(|(x, y)| (x - y) * (x - y))
+			.sum::<f64>()
+			.sqrt())
+	}
+
+	/// Scales the embedding vector to a magnitude of 1, after which [Embedding::try_dot] gives
+	/// the cosine similarity. A zero vector is left as is.
+	pub fn normalize(&mut self) {
+		let magnitude = self.magnitude();
+
+		if magnitude > 0.0 {
+			self.vec.iter_mut().for_each(|x| *x /= magnitude);
+		}
+	}
+}
+
+/// Returns the indices of the `k` candidates most similar to `query` by cosine similarity, along
+/// with their similarity, most similar first.
+pub fn top_k(
+	query: &Embedding,
+	candidates: &[Embedding],
+	k: usize,
+) -> Result<Vec<(usize, f64)>, DimensionMismatch> {
+	// Min-heap of the best candidates so far, the least similar on top
+	let mut best = BinaryHeap::with_capacity(k.min(candidates.len()) + 1);
+
+	for (index, candidate) in candidates.iter().enumerate() {
+		let score = OrderedFloat(query.try_cosine_similarity(candidate)?);
+		if k == 0 {
+			continue;
+		}
+
+		best.push(Reverse((score, Reverse(index))));
+		if best.len() > k {
+			best.pop();
+		}
+	}
+
+	Ok(best
+		.into_sorted_vec()
+		.into_iter()
+		.map(|Reverse((score, Reverse(index)))| (index, score.0))
+		.collect())
+}
+
 #[cfg(not(feature = "rayon"))]
-impl VectorDistance for crate::embeddings::Embedding {
+impl VectorDistance for Embedding {
 	fn dot_product(&self, other: &Self) -> f64 {
 		self.vec
 			.iter()
@@ -136,7 +242,7 @@ mod rayon {
 
 #[cfg(test)]
 mod tests {
-	use super::VectorDistance;
+	use super::{DimensionMismatch, VectorDistance, top_k};
 	use crate::embeddings::Embedding;
 
 	fn embeddings() -> (Embedding, Embedding) {
@@ -200,4 +306,135 @@ mod tests {
 
 		assert_eq!(embedding_1.chebyshev_distance(&embedding_2), 4.0)
 	}
+
+	/// Deterministic pseudo-random vectors, so the properties below are checked over many
+	/// inputs without extra dependencies
+	fn random_embeddings(count: usize, dimensions: usize) -> Vec<Embedding> {
+		let mut state = 0x2545_f491_4f6c_dd1d_u64;
+		let mut next = move || {
+			state ^= state << 13;
+			state ^= state >> 7;
+			state ^= state << 17;
+			(state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+		};
+
+		(0..count)
+			.map(|i| Embedding {
+				document: format!("doc-{i}"),
+				vec: (0..dimensions).map(|_| next()).collect(),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_similarity_is_symmetric() {
+		let embeddings = random_embeddings(50, 16);
+
+		for pair in embeddings.windows(2) {
+			let (a, b) = (&pair[0], &pair[1]);
+
+			assert_eq!(a.try_dot(b).unwrap(), b.try_dot(a).unwrap());
+			assert_eq!(
+				a.try_cosine_similarity(b).unwrap(),
+				b.try_cosine_similarity(a).unwrap()
+			);
+			assert_eq!(
+				a.try_euclidean_distance(b).unwrap(),
+				b.try_euclidean_distance(a).unwrap()
+			);
+
+			let similarity = a.try_cosine_similarity(b).unwrap();
+			assert!((-1.0..=1.0).contains(&similarity), "{similarity}");
+		}
+	}
+
+	#[test]
+	fn test_self_similarity() {
+		for mut embedding in random_embeddings(50, 16) {
+			assert!((embedding.try_cosine_similarity(&embedding).unwrap() - 1.0).abs() < 1e-12);
+			assert_eq!(embedding.try_euclidean_distance(&embedding).unwrap(), 0.0);
+
+			embedding.normalize();
+			assert!((embedding.magnitude() - 1.0).abs() < 1e-12);
+			assert!((embedding.try_dot(&embedding).unwrap() - 1.0).abs() < 1e-12);
+		}
+	}
+
+	#[test]
+	fn test_normalized_dot_is_cosine_similarity() {
+		let embeddings = random_embeddings(20, 8);
+
+		for pair in embeddings.windows(2) {
+			let similarity = pair[0].try_cosine_similarity(&pair[1]).unwrap();
+			let (mut a, mut b) = (pair[0].clone(), pair[1].clone());
+			a.normalize();
+			b.normalize();
+
+			assert!((a.try_dot(&b).unwrap() - similarity).abs() < 1e-12);
+		}
+	}
+
+	#[test]
+	fn test_zero_vector() {
+		let (embedding, _) = embeddings();
+		let mut zero = Embedding {
+			document: "zero".to_string(),
+			vec: vec![0.0; 3],
+		};
+
+		assert_eq!(zero.try_cosine_similarity(&embedding).unwrap(), 0.0);
+		zero.normalize();
+		assert_eq!(zero.vec, vec![0.0; 3]);
+	}
+
+	#[test]
+	fn test_dimension_mismatch() {
+		let (embedding, _) = embeddings();
+		let other = Embedding {
+			document: "other".to_string(),
+			vec: vec![1.0, 2.0],
+		};
+		let mismatch = DimensionMismatch {
+			expected: 3,
+			actual: 2,
+		};
+
+		assert_eq!(embedding.try_dot(&other), Err(mismatch));
+		assert_eq!(embedding.try_cosine_similarity(&other), Err(mismatch));
+		assert_eq!(embedding.try_euclidean_distance(&other), Err(mismatch));
+		assert_eq!(
+			top_k(&embedding, &[embedding.clone(), other], 1),
+			Err(mismatch)
+		);
+	}
+
+	#[test]
+	fn test_top_k() {
+		let query = Embedding {
+			document: "query".to_string(),
+			vec: vec![1.0, 0.0],
+		};
+		let candidates = [[0.0, 1.0], [1.0, 0.1], [-1.0, 0.0], [2.0, 0.0], [1.0, 1.0]]
+			.into_iter()
+			.map(|vec| Embedding {
+				document: String::new(),
+				vec: vec.to_vec(),
+			})
+			.collect::<Vec<_>>();
+
+		let indices = |k| {
+			top_k(&query, &candidates, k)
+				.unwrap()
+				.into_iter()
+				.map(|(index, _)| index)
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(indices(3), [3, 1, 4]);
+		assert_eq!(indices(10), [3, 1, 4, 0, 2]);
+		assert!(indices(0).is_empty());
+
+		let best = top_k(&query, &candidates, 1).unwrap();
+		assert_eq!(best, [(3, 1.0)]);
+	}
 }
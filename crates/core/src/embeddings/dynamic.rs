@@ -0,0 +1,31 @@
+//! Type-erased embedding models, for storing models of different providers together.
+use super::{Embedding, EmbeddingError, EmbeddingModel};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
+
+/// Type-erased [`EmbeddingModel`] for dynamic dispatch.
+pub trait EmbeddingModelDyn: WasmCompatSend + WasmCompatSync {
+	/// The number of dimensions in the embedding vector.
+	fn ndims(&self) -> usize;
+
+	/// Embed multiple text documents in a single request
+	fn embed_texts(
+		&self,
+		texts: Vec<String>,
+	) -> WasmBoxedFuture<'_, Result<Vec<Embedding>, EmbeddingError>>;
+}
+
+impl<M> EmbeddingModelDyn for M
+where
+	M: EmbeddingModel + 'static,
+{
+	fn ndims(&self) -> usize {
+		EmbeddingModel::ndims(self)
+	}
+
+	fn embed_texts(
+		&self,
+		texts: Vec<String>,
+	) -> WasmBoxedFuture<'_, Result<Vec<Embedding>, EmbeddingError>> {
+		Box::pin(EmbeddingModel::embed_texts(self, texts))
+	}
+}
@@ -8,6 +8,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::client::CapturedRequest;
+use crate::completion::message::{ImageMediaType, MimeType};
 use crate::http_client;
 use crate::wasm_compat::*;
 
@@ -43,6 +45,52 @@ pub enum EmbeddingError {
 	ProviderError(String),
 }
 
+/// An image to embed with [ImageEmbeddingModel::embed_images], from its bytes or a URL.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageInput {
+	Bytes {
+		data: Vec<u8>,
+		media_type: ImageMediaType,
+	},
+	Url {
+		url: String,
+		media_type: ImageMediaType,
+	},
+}
+
+impl ImageInput {
+	pub fn bytes(data: impl Into<Vec<u8>>, media_type: ImageMediaType) -> Self {
+		Self::Bytes {
+			data: data.into(),
+			media_type,
+		}
+	}
+
+	pub fn url(url: impl Into<String>, media_type: ImageMediaType) -> Self {
+		Self::Url {
+			url: url.into(),
+			media_type,
+		}
+	}
+
+	pub fn media_type(&self) -> &ImageMediaType {
+		match self {
+			Self::Bytes { media_type, .. } | Self::Url { media_type, .. } => media_type,
+		}
+	}
+}
+
+/// Used as the [Embedding::document] of the image, e.g. `image/png (1024 bytes)`.
+impl std::fmt::Display for ImageInput {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let mime_type = self.media_type().to_mime_type();
+		match self {
+			Self::Bytes { data, .. } => write!(f, "{mime_type} ({} bytes)", data.len()),
+			Self::Url { url, .. } => write!(f, "{mime_type} ({url})"),
+		}
+	}
+}
+
 /// Trait for embedding models that can generate embeddings for documents.
 pub trait EmbeddingModel: WasmCompatSend + WasmCompatSync {
 	/// The maximum number of documents that can be embedded in a single request.
@@ -55,6 +103,12 @@ pub trait EmbeddingModel: WasmCompatSend + WasmCompatSync {
 	/// The number of dimensions in the embedding vector.
 	fn ndims(&self) -> usize;
 
+	/// The last request sent by this model or its clones. Only kept when the client was built
+	/// with [capture_last_request](crate::client::ClientBuilder::capture_last_request).
+	fn last_request(&self) -> Option<CapturedRequest> {
+		None
+	}
+
 	/// Embed multiple text documents in a single request
 	fn embed_texts(
 		&self,
@@ -76,28 +130,24 @@ pub trait EmbeddingModel: WasmCompatSend + WasmCompatSync {
 	}
 }
 
-/// Trait for embedding models that can generate embeddings for images.
-pub trait ImageEmbeddingModel: Clone + WasmCompatSend + WasmCompatSync {
-	/// The maximum number of images that can be embedded in a single request.
-	const MAX_DOCUMENTS: usize;
-
-	/// The number of dimensions in the embedding vector.
-	fn ndims(&self) -> usize;
-
-	/// Embed multiple images in a single request from bytes.
+/// Trait for embedding models that can also generate embeddings for images, in the same vector
+/// space as their texts for multimodal models. Texts and images are embedded with separate
+/// calls, batches never mix both.
+pub trait ImageEmbeddingModel: EmbeddingModel + Clone {
+	/// Embed multiple images, in batches of at most [EmbeddingModel::MAX_DOCUMENTS].
 	fn embed_images(
 		&self,
-		images: impl IntoIterator<Item = Vec<u8>> + WasmCompatSend,
-	) -> impl std::future::Future<Output = Result<Vec<Embedding>, EmbeddingError>> + Send;
+		images: Vec<ImageInput>,
+	) -> impl std::future::Future<Output = Result<Vec<Embedding>, EmbeddingError>> + WasmCompatSend;
 
-	/// Embed a single image from bytes.
-	fn embed_image<'a>(
-		&'a self,
-		bytes: &'a [u8],
+	/// Embed a single image.
+	fn embed_image(
+		&self,
+		image: ImageInput,
 	) -> impl std::future::Future<Output = Result<Embedding, EmbeddingError>> + WasmCompatSend {
 		async move {
 			Ok(self
-				.embed_images(vec![bytes.to_owned()])
+				.embed_images(vec![image])
 				.await?
 				.pop()
 				.expect("There should be at least one embedding"))
@@ -4,12 +4,13 @@
 //! and document similarity.
 
 pub mod builder;
+pub mod dynamic;
 pub mod embed;
 pub mod embedding;
 pub mod tool;
 
 pub mod distance;
-pub use builder::EmbeddingsBuilder;
+pub use builder::{EmbeddedDocument, EmbeddingsBuilder, content_id};
 pub use embed::{Embed, EmbedError, TextEmbedder, to_texts};
 pub use embedding::*;
 pub use tool::ToolSchema;
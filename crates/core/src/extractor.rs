@@ -27,6 +27,10 @@
 //!     .await
 //!     .expect("Failed to extract data from text");
 //! ```
+//!
+//! Since tool parameters must be objects, targets that are not (e.g. `Vec<Person>` or an enum)
+//! are transparently wrapped in an object with a single `items` (for arrays) or `value` (for
+//! anything else) property, which is unwrapped again before deserialization.
 
 use std::marker::PhantomData;
 
@@ -35,12 +39,16 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::agent::{Agent, AgentBuilder, AgentBuilderSimple};
-use crate::completion::{Completion, CompletionError, CompletionModel, ToolDefinition};
-use crate::message::{AssistantContent, Message, ToolCall, ToolChoice, ToolFunction};
+use crate::completion::{Completion, CompletionError, CompletionModel, ToolDefinition, Usage};
+use crate::message::{AssistantContent, Message, ToolChoice};
 use crate::tool::Tool;
 use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
 
 const SUBMIT_TOOL_NAME: &str = "submit";
+/// Property top-level arrays are wrapped in.
+const ITEMS_KEY: &str = "items";
+/// Property other top-level non-object targets (enums, primitives, ...) are wrapped in.
+const VALUE_KEY: &str = "value";
 
 #[derive(Debug, thiserror::Error)]
 pub enum ExtractionError {
@@ -52,6 +60,32 @@ pub enum ExtractionError {
 
 	#[error("CompletionError: {0}")]
 	CompletionError(#[from] CompletionError),
+
+	/// The model kept submitting data that could not be deserialized, even after being
+	/// asked to correct it.
+	#[error("Failed to deserialize the extracted data after {} attempts", .attempts.len())]
+	InvalidOutput {
+		/// Every rejected submission, in order.
+		attempts: Vec<InvalidOutput>,
+		/// Token usage accumulated over all attempts.
+		usage: Usage,
+	},
+}
+
+/// A submission from the model that failed to deserialize into the target type.
+#[derive(Debug)]
+pub struct InvalidOutput {
+	/// The raw data passed to the `submit` tool.
+	pub output: serde_json::Value,
+	/// The deserialization error.
+	pub error: serde_json::Error,
+}
+
+/// Extracted data along with the token usage of every request made to obtain it.
+#[derive(Debug)]
+pub struct ExtractionResponse<T> {
+	pub data: T,
+	pub usage: Usage,
 }
 
 /// Extractor for structured data from text
@@ -63,6 +97,8 @@ where
 	agent: Agent<M>,
 	_t: PhantomData<T>,
 	retries: u64,
+	max_retries: u64,
+	wrapper: Option<&'static str>,
 }
 
 impl<M, T> Extractor<M, T>
@@ -80,26 +116,9 @@ where
 		&self,
 		text: impl Into<Message> + WasmCompatSend,
 	) -> Result<T, ExtractionError> {
-		let mut last_error = None;
-		let text_message = text.into();
-
-		for i in 0..=self.retries {
-			tracing::debug!(
-				"Attempting to extract JSON. Retries left: {retries}",
-				retries = self.retries - i
-			);
-			let attempt_text = text_message.clone();
-			match self.extract_json(attempt_text, vec![]).await {
-				Ok(data) => return Ok(data),
-				Err(e) => {
-					tracing::warn!("Attempt {i} to extract JSON failed: {e:?}. Retrying...");
-					last_error = Some(e);
-				}
-			}
-		}
-
-		// If the loop finishes without a successful extraction, return the last error encountered.
-		Err(last_error.unwrap_or(ExtractionError::NoData))
+		self.extract_with_usage(text, vec![])
+			.await
+			.map(|response| response.data)
 	}
 
 	/// Attempts to extract data from the given text with a number of retries.
@@ -113,7 +132,20 @@ where
 		text: impl Into<Message> + WasmCompatSend,
 		chat_history: Vec<Message>,
 	) -> Result<T, ExtractionError> {
+		self.extract_with_usage(text, chat_history)
+			.await
+			.map(|response| response.data)
+	}
+
+	/// Same as [`Extractor::extract_with_chat_history`], but also returns the token usage
+	/// accumulated over every request made during the extraction, including retries.
+	pub async fn extract_with_usage(
+		&self,
+		text: impl Into<Message> + WasmCompatSend,
+		chat_history: Vec<Message>,
+	) -> Result<ExtractionResponse<T>, ExtractionError> {
 		let mut last_error = None;
+		let mut usage = Usage::new();
 		let text_message = text.into();
 
 		for i in 0..=self.retries {
@@ -122,8 +154,11 @@ where
 				retries = self.retries - i
 			);
 			let attempt_text = text_message.clone();
-			match self.extract_json(attempt_text, chat_history.clone()).await {
-				Ok(data) => return Ok(data),
+			match self
+				.extract_json(attempt_text, chat_history.clone(), &mut usage)
+				.await
+			{
+				Ok(data) => return Ok(ExtractionResponse { data, usage }),
 				Err(e) => {
 					tracing::warn!("Attempt {i} to extract JSON failed: {e:?}. Retrying...");
 					last_error = Some(e);
@@ -135,63 +170,91 @@ where
 		Err(last_error.unwrap_or(ExtractionError::NoData))
 	}
 
+	/// Runs a single extraction. If the submitted data does not deserialize into `T`, the
+	/// error is sent back to the model as the `submit` tool result so it can correct itself,
+	/// up to `max_retries` times.
 	async fn extract_json(
 		&self,
-		text: impl Into<Message> + WasmCompatSend,
-		messages: Vec<Message>,
+		text: Message,
+		mut history: Vec<Message>,
+		usage: &mut Usage,
 	) -> Result<T, ExtractionError> {
-		let response = self.agent.completion(text, messages).await?.send().await?;
-
-		if !response.choice.iter().any(|x| {
-			let AssistantContent::ToolCall(ToolCall {
-				function: ToolFunction { name, .. },
-				..
-			}) = x
-			else {
-				return false;
+		let mut prompt = text;
+		let mut attempts = Vec::new();
+
+		loop {
+			let response = self
+				.agent
+				.completion(prompt.clone(), history.clone())
+				.await?
+				.send()
+				.await?;
+			*usage += response.usage;
+
+			// We filter tool calls to look for submit tool calls
+			let submit_calls = response
+				.choice
+				.iter()
+				.filter_map(|content| match content {
+					AssistantContent::ToolCall(tool_call)
+						if tool_call.function.name == SUBMIT_TOOL_NAME =>
+					{
+						Some(tool_call.clone())
+					}
+					_ => None,
+				})
+				.collect::<Vec<_>>();
+
+			if submit_calls.is_empty() {
+				tracing::warn!(
+					"The submit tool was not called. If this happens more than once, please ensure the model you are using is powerful enough to reliably call tools."
+				);
+			}
+
+			if submit_calls.len() > 1 {
+				tracing::warn!(
+					"Multiple submit calls detected, using the first one. Providers / agents should only ensure one submit call."
+				);
+			}
+
+			let Some(submit_call) = submit_calls.into_iter().next() else {
+				return Err(ExtractionError::NoData);
 			};
 
-			name == SUBMIT_TOOL_NAME
-		}) {
-			tracing::warn!(
-				"The submit tool was not called. If this happens more than once, please ensure the model you are using is powerful enough to reliably call tools."
+			let output = submit_call.function.arguments;
+			let error = match serde_json::from_value(unwrap_submission(&output, self.wrapper)) {
+				Ok(data) => return Ok(data),
+				Err(error) => error,
+			};
+
+			let feedback = format!(
+				"The submitted data could not be deserialized: {error}\n\
+				Submitted data: {output}\n\
+				Call the `submit` function again with corrected data."
 			);
-		}
+			attempts.push(InvalidOutput { output, error });
 
-		let arguments = response
-			.choice
-			.into_iter()
-			// We filter tool calls to look for submit tool calls
-			.filter_map(|content| {
-				if let AssistantContent::ToolCall(ToolCall {
-					function: ToolFunction { arguments, name },
-					..
-				}) = content
-				{
-					if name == SUBMIT_TOOL_NAME {
-						Some(arguments)
-					} else {
-						None
-					}
-				} else {
-					None
-				}
-			})
-			.collect::<Vec<_>>();
+			if attempts.len() as u64 > self.max_retries {
+				return Err(ExtractionError::InvalidOutput {
+					attempts,
+					usage: *usage,
+				});
+			}
 
-		if arguments.len() > 1 {
 			tracing::warn!(
-				"Multiple submit calls detected, using the last one. Providers / agents should only ensure one submit call."
+				"Submitted data failed to deserialize ({} of {} corrections used). Asking the model to correct it...",
+				attempts.len(),
+				self.max_retries
 			);
-		}
-
-		let raw_data = if let Some(arg) = arguments.into_iter().next() {
-			arg
-		} else {
-			return Err(ExtractionError::NoData);
-		};
 
-		Ok(serde_json::from_value(raw_data)?)
+			history.push(prompt);
+			history.push(Message::Assistant {
+				id: None,
+				content: response.choice,
+			});
+			prompt =
+				Message::tool_result_with_call_id(submit_call.id, submit_call.call_id, feedback);
+		}
 	}
 
 	pub async fn get_inner(&self) -> &Agent<M> {
@@ -212,6 +275,8 @@ where
 	agent_builder: AgentBuilderSimple<M>,
 	_t: PhantomData<T>,
 	retries: Option<u64>,
+	max_retries: Option<u64>,
+	wrapper: Option<&'static str>,
 }
 
 impl<M, T> ExtractorBuilder<M, T>
@@ -220,7 +285,8 @@ where
 	T: JsonSchema + for<'a> Deserialize<'a> + Serialize + WasmCompatSend + WasmCompatSync + 'static,
 {
 	pub fn new(model: M) -> Self {
-		Self {
+		let (_, wrapper) = submit_schema::<T>();
+		let builder = Self {
             agent_builder: AgentBuilder::new(model)
                 .preamble("\
                     You are an AI assistant whose purpose is to extract structured data from the provided text.\n\
@@ -231,8 +297,20 @@ where
                 .tool(SubmitTool::<T> {_t: PhantomData})
                 .tool_choice(ToolChoice::Required),
             retries: None,
+            max_retries: None,
+            wrapper,
             _t: PhantomData,
-        }
+        };
+
+		match wrapper {
+			Some(key) => Self {
+				agent_builder: builder.agent_builder.append_preamble(&format!(
+					"Submit the extracted data as the `{key}` property of the `submit` function's arguments."
+				)),
+				..builder
+			},
+			None => builder,
+		}
 	}
 
 	/// Add additional preamble to the extractor
@@ -266,6 +344,13 @@ where
 		self
 	}
 
+	/// Set how many times the model is asked to correct data that fails to deserialize
+	/// into the target type before the extraction attempt fails. Defaults to 1.
+	pub fn max_retries(mut self, max_retries: u64) -> Self {
+		self.max_retries = Some(max_retries);
+		self
+	}
+
 	/// Set the `tool_choice` option for the inner Agent.
 	pub fn tool_choice(mut self, choice: ToolChoice) -> Self {
 		self.agent_builder = self.agent_builder.tool_choice(choice);
@@ -278,10 +363,60 @@ where
 			agent: self.agent_builder.build(),
 			_t: PhantomData,
 			retries: self.retries.unwrap_or(0),
+			max_retries: self.max_retries.unwrap_or(1),
+			wrapper: self.wrapper,
 		}
 	}
 }
 
+/// Returns the schema of the `submit` tool's parameters for `T`, along with the property the
+/// data is wrapped in if `T` is not a plain object.
+fn submit_schema<T: JsonSchema>() -> (serde_json::Value, Option<&'static str>) {
+	let mut schema = json!(schema_for!(T));
+	let Some(inner) = schema.as_object_mut() else {
+		return (schema, None);
+	};
+
+	let is_object = inner.get("type").and_then(|t| t.as_str()) == Some("object")
+		&& !["oneOf", "anyOf", "allOf"]
+			.iter()
+			.any(|key| inner.contains_key(*key));
+	if is_object {
+		return (schema, None);
+	}
+
+	let key = if inner.get("type").and_then(|t| t.as_str()) == Some("array") {
+		ITEMS_KEY
+	} else {
+		VALUE_KEY
+	};
+
+	// Keep the metadata and definitions at the root so `$ref`s still resolve
+	let mut wrapper = serde_json::Map::new();
+	for property in ["$schema", "title", "$defs"] {
+		if let Some(value) = inner.remove(property) {
+			wrapper.insert(property.to_string(), value);
+		}
+	}
+	wrapper.insert("type".to_string(), json!("object"));
+	wrapper.insert(
+		"properties".to_string(),
+		json!({ key: std::mem::take(inner) }),
+	);
+	wrapper.insert("required".to_string(), json!([key]));
+	wrapper.insert("additionalProperties".to_string(), json!(false));
+
+	(wrapper.into(), Some(key))
+}
+
+/// Unwraps the data submitted by the model, leaving it as is if the model omitted the wrapper.
+fn unwrap_submission(output: &serde_json::Value, wrapper: Option<&str>) -> serde_json::Value {
+	match wrapper.and_then(|key| output.get(key)) {
+		Some(data) => data.clone(),
+		None => output.clone(),
+	}
+}
+
 #[derive(Deserialize, Serialize)]
 struct SubmitTool<T>
 where
@@ -300,15 +435,16 @@ where
 {
 	const NAME: &'static str = SUBMIT_TOOL_NAME;
 	type Error = SubmitError;
-	type Args = T;
-	type Output = T;
+	// Not `T`, since the data may be wrapped (see [submit_schema])
+	type Args = serde_json::Value;
+	type Output = serde_json::Value;
 
 	async fn definition(&self, _prompt: String) -> ToolDefinition {
 		ToolDefinition {
 			name: Self::NAME.to_string(),
 			description: "Submit the structured data you extracted from the provided text."
 				.to_string(),
-			parameters: json!(schema_for!(T)),
+			parameters: submit_schema::<T>().0,
 		}
 	}
 
@@ -316,3 +452,182 @@ where
 		Ok(data)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::OneOrMany;
+	use crate::message::UserContent;
+	use crate::test_utils::MockCompletionModel;
+
+	#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+	struct Person {
+		name: String,
+		age: u8,
+	}
+
+	fn submit(arguments: serde_json::Value) -> OneOrMany<AssistantContent> {
+		OneOrMany::one(AssistantContent::tool_call(
+			"call-1",
+			SUBMIT_TOOL_NAME,
+			arguments,
+		))
+	}
+
+	fn usage(input_tokens: u64, output_tokens: u64) -> Usage {
+		Usage {
+			input_tokens,
+			output_tokens,
+			total_tokens: input_tokens + output_tokens,
+			cached_input_tokens: 0,
+			cache_creation_input_tokens: 0,
+		}
+	}
+
+	#[tokio::test]
+	async fn test_invalid_json_is_sent_back_for_correction() {
+		let model = MockCompletionModel::with_responses([
+			submit(json!({ "name": "John", "age": "thirty" })),
+			submit(json!({ "name": "John", "age": 30 })),
+		])
+		.usage(usage(10, 5));
+		let extractor = ExtractorBuilder::<_, Person>::new(model.clone()).build();
+
+		let response = extractor
+			.extract_with_usage("John is 30.", vec![])
+			.await
+			.unwrap();
+		assert_eq!(response.data.name, "John");
+		assert_eq!(response.data.age, 30);
+		assert_eq!(response.usage, usage(20, 10));
+
+		let requests = model.requests();
+		assert_eq!(requests.len(), 2);
+		let Message::User { content } = requests[1].chat_history.last() else {
+			panic!("expected the correction to be a user message");
+		};
+		let UserContent::ToolResult(result) = content.first() else {
+			panic!("expected the correction to be a tool result");
+		};
+		assert_eq!(result.id, "call-1");
+		let feedback = serde_json::to_string(&result.content).unwrap();
+		assert!(feedback.contains("thirty"), "{feedback}");
+		assert!(feedback.contains("invalid type"), "{feedback}");
+	}
+
+	#[tokio::test]
+	async fn test_exhausted_retries_report_every_attempt() {
+		let model = MockCompletionModel::with_responses([
+			submit(json!({ "name": 1, "age": 30 })),
+			submit(json!({ "name": "John" })),
+			submit(json!({ "name": "John", "age": 30 })),
+		])
+		.usage(usage(10, 5));
+		let extractor = ExtractorBuilder::<_, Person>::new(model.clone())
+			.max_retries(1)
+			.build();
+
+		let err = extractor.extract("John is 30.").await.unwrap_err();
+		let ExtractionError::InvalidOutput {
+			attempts,
+			usage: total,
+		} = err
+		else {
+			panic!("unexpected error: {err}");
+		};
+		assert_eq!(attempts.len(), 2);
+		assert_eq!(attempts[0].output, json!({ "name": 1, "age": 30 }));
+		assert!(
+			attempts[1]
+				.error
+				.to_string()
+				.contains("missing field `age`")
+		);
+		assert_eq!(total, usage(20, 10));
+		assert_eq!(model.requests().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_struct_is_not_wrapped() {
+		let model =
+			MockCompletionModel::with_responses([submit(json!({ "name": "John", "age": 30 }))]);
+		let extractor = ExtractorBuilder::<_, Person>::new(model.clone()).build();
+
+		let person = extractor.extract("John is 30.").await.unwrap();
+		assert_eq!(person.name, "John");
+
+		let parameters = &model.requests()[0].tools[0].parameters;
+		assert_eq!(parameters["properties"]["age"]["type"], "integer");
+		assert!(parameters["properties"].get(ITEMS_KEY).is_none());
+	}
+
+	#[tokio::test]
+	async fn test_vec_is_wrapped_in_items() {
+		let model = MockCompletionModel::with_responses([submit(json!({
+			"items": [{ "name": "John", "age": 30 }, { "name": "Jane", "age": 28 }]
+		}))]);
+		let extractor = ExtractorBuilder::<_, Vec<Person>>::new(model.clone()).build();
+
+		let people = extractor
+			.extract("John is 30 and Jane is 28.")
+			.await
+			.unwrap();
+		assert_eq!(people.len(), 2);
+		assert_eq!(people[1].name, "Jane");
+
+		let request = &model.requests()[0];
+		let parameters = &request.tools[0].parameters;
+		assert_eq!(parameters["type"], "object");
+		assert_eq!(parameters["required"], json!(["items"]));
+		assert_eq!(parameters["properties"]["items"]["type"], "array");
+		// The item definitions stay at the root so the `$ref`s resolve
+		let item_ref = parameters["properties"]["items"]["items"]["$ref"]
+			.as_str()
+			.unwrap();
+		assert_eq!(item_ref, "#/$defs/Person");
+		assert!(parameters["$defs"].get("Person").is_some());
+		assert!(
+			request
+				.preamble
+				.as_ref()
+				.unwrap()
+				.contains("`items` property")
+		);
+	}
+
+	#[derive(Debug, PartialEq, Deserialize, Serialize, JsonSchema)]
+	#[serde(tag = "sentiment", rename_all = "snake_case")]
+	enum Sentiment {
+		Positive { confidence: f64 },
+		Negative { confidence: f64 },
+		Neutral,
+	}
+
+	#[tokio::test]
+	async fn test_enum_is_wrapped_in_value() {
+		let model = MockCompletionModel::with_responses([
+			submit(json!({ "value": { "sentiment": "positive", "confidence": 0.9 } })),
+			// Submissions missing the wrapper are accepted as well
+			submit(json!({ "sentiment": "neutral" })),
+		]);
+		let extractor = ExtractorBuilder::<_, Sentiment>::new(model.clone()).build();
+
+		let sentiment = extractor.extract("I love it!").await.unwrap();
+		assert_eq!(sentiment, Sentiment::Positive { confidence: 0.9 });
+		let sentiment = extractor.extract("It's fine.").await.unwrap();
+		assert_eq!(sentiment, Sentiment::Neutral);
+
+		let requests = model.requests();
+		assert_eq!(requests.len(), 2);
+		let parameters = &requests[0].tools[0].parameters;
+		assert_eq!(parameters["type"], "object");
+		assert_eq!(parameters["required"], json!(["value"]));
+		assert_eq!(
+			parameters["properties"]["value"]["oneOf"]
+				.as_array()
+				.unwrap()
+				.len(),
+			3
+		);
+	}
+}
@@ -9,8 +9,11 @@ use crate::http_client::sse::BoxedStream;
 pub mod multipart;
 pub mod retry;
 pub mod sse;
+pub mod stall;
 
+use std::any::Any;
 use std::pin::Pin;
+use std::sync::RwLock;
 
 pub use multipart::MultipartForm;
 pub use reqwest::Client as ReqwestClient;
@@ -31,6 +34,9 @@ pub enum Error {
 	NoHeaders,
 	#[error("Stream ended")]
 	StreamEnded,
+	/// No bytes were received for the stall timeout, see [stall::StallTimeout]
+	#[error("Stream stalled: no data received for {0:?}")]
+	StreamStalled(std::time::Duration),
 	#[error("Invalid content type was returned: {0:?}")]
 	InvalidContentType(HeaderValue),
 	#[cfg(not(target_family = "wasm"))]
@@ -100,6 +106,54 @@ pub fn with_bearer_auth(mut req: Builder, auth: &str) -> Result<Builder> {
 	Ok(req)
 }
 
+static DEFAULT_REQWEST_CLIENT: RwLock<Option<reqwest::Client>> = RwLock::new(None);
+
+/// Sets the [reqwest::Client] used by clients built without an HTTP backend, e.g. with
+/// `from_env`, to configure a proxy, TLS roots or timeouts once for every provider.
+///
+/// Only clients built afterwards use it. Clients using `reqwest_middleware` get it wrapped
+/// without any middleware.
+pub fn set_default_reqwest_client(client: reqwest::Client) {
+	*DEFAULT_REQWEST_CLIENT
+		.write()
+		.unwrap_or_else(|err| err.into_inner()) = Some(client);
+}
+
+/// Removes the default [reqwest::Client], going back to [reqwest::Client::default].
+pub fn clear_default_reqwest_client() {
+	*DEFAULT_REQWEST_CLIENT
+		.write()
+		.unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// The HTTP backend of a client built without one: a clone of the default reqwest client if one
+/// was set and `H` is backed by reqwest, otherwise `H::default()`.
+pub(crate) fn default_http_client<H: Default + 'static>() -> H {
+	let client = DEFAULT_REQWEST_CLIENT
+		.read()
+		.unwrap_or_else(|err| err.into_inner())
+		.clone();
+
+	let Some(client) = client else {
+		return H::default();
+	};
+
+	let mut slot = Some(client.clone());
+	if let Some(http_client) = (&mut slot as &mut dyn Any).downcast_mut::<Option<H>>() {
+		return http_client.take().unwrap_or_default();
+	}
+
+	#[cfg(feature = "reqwest-middleware")]
+	{
+		let mut slot = Some(reqwest_middleware::ClientWithMiddleware::from(client));
+		if let Some(http_client) = (&mut slot as &mut dyn Any).downcast_mut::<Option<H>>() {
+			return http_client.take().unwrap_or_default();
+		}
+	}
+
+	H::default()
+}
+
 /// A helper trait to make generic requests (both regular and SSE) possible.
 pub trait HttpClientExt: WasmCompatSend + WasmCompatSync {
 	/// Send a HTTP request, get a response back (as bytes). Response must be able to be turned back into Bytes.
@@ -418,3 +472,94 @@ impl HttpClientExt for reqwest_middleware::ClientWithMiddleware {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+	use tokio::net::TcpListener;
+
+	use super::*;
+	use crate::completion::Prompt;
+	use crate::prelude::CompletionClient;
+	use crate::providers::openai;
+
+	const RESPONSE: &str = r#"{
+		"id": "chatcmpl-1",
+		"object": "chat.completion",
+		"created": 1700000000,
+		"model": "gpt-4o",
+		"choices": [{
+			"index": 0,
+			"message": {"role": "assistant", "content": "Hello!"},
+			"finish_reason": "stop"
+		}]
+	}"#;
+
+	/// Answers a single request with a chat completion, returning the request's head.
+	async fn serve_once(listener: TcpListener) -> String {
+		let (mut socket, _) = listener.accept().await.unwrap();
+
+		let mut request = Vec::new();
+		let mut buf = [0; 4096];
+		let head_end = loop {
+			let n = socket.read(&mut buf).await.unwrap();
+			request.extend_from_slice(&buf[..n]);
+			if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+				break pos + 4;
+			}
+		};
+
+		let head = String::from_utf8_lossy(&request[..head_end]).to_lowercase();
+		let content_length = head
+			.lines()
+			.find_map(|line| line.strip_prefix("content-length:"))
+			.map_or(0, |len| len.trim().parse::<usize>().unwrap());
+		while request.len() < head_end + content_length {
+			let n = socket.read(&mut buf).await.unwrap();
+			request.extend_from_slice(&buf[..n]);
+		}
+
+		let response = format!(
+			"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{RESPONSE}",
+			RESPONSE.len()
+		);
+		socket.write_all(response.as_bytes()).await.unwrap();
+
+		head
+	}
+
+	#[tokio::test]
+	async fn test_default_reqwest_client_is_used() {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let base_url = format!("http://{}", listener.local_addr().unwrap());
+		let server = tokio::spawn(serve_once(listener));
+
+		let mut headers = HeaderMap::new();
+		headers.insert("x-default-client", HeaderValue::from_static("marker"));
+		set_default_reqwest_client(
+			reqwest::Client::builder()
+				.default_headers(headers)
+				.build()
+				.unwrap(),
+		);
+
+		let client = openai::CompletionsClient::<reqwest::Client>::builder()
+			.api_key("test")
+			.base_url(&base_url)
+			.build();
+		clear_default_reqwest_client();
+
+		let response = client
+			.unwrap()
+			.agent("gpt-4o")
+			.build()
+			.prompt("Hi")
+			.await
+			.unwrap();
+		assert_eq!(response, "Hello!");
+
+		let head = server.await.unwrap();
+		assert!(head.starts_with("post /chat/completions"), "{head}");
+		assert!(head.contains("x-default-client: marker"), "{head}");
+	}
+}
@@ -19,22 +19,26 @@ use http::{HeaderName, HeaderValue, Request, Response, StatusCode};
 use mime_guess::mime;
 use pin_project_lite::pin_project;
 
+use crate::completion::ResponseMetadata;
 use crate::http_client::retry::{DEFAULT_RETRY, RetryPolicy};
 use crate::http_client::{HttpClientExt, Result as StreamResult, instance_error};
 use crate::wasm_compat::{WasmCompatSend, WasmCompatSendStream};
 
 pub type BoxedStream = Pin<Box<dyn WasmCompatSendStream<InnerItem = StreamResult<Bytes>>>>;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
 type ResponseFuture<T> = BoxFuture<'static, Result<Response<T>, super::Error>>;
 #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 type ResponseFuture<T> = LocalBoxFuture<'static, Result<Response<T>, super::Error>>;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
 type EventStream = BoxStream<'static, Result<MessageEvent, EventStreamError<super::Error>>>;
 #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 type EventStream = LocalBoxStream<'static, Result<MessageEvent, EventStreamError<super::Error>>>;
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
 type BoxedRetry = Box<dyn RetryPolicy + Send + Unpin + 'static>;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+type BoxedRetry = Box<dyn RetryPolicy + Unpin + 'static>;
 
 /// The ready state of a [`GenericEventSource`]
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
@@ -68,6 +72,7 @@ pin_project! {
 		retry_policy: BoxedRetry,
 		last_event_id: String,
 		last_retry: Option<(usize, Duration)>,
+		response_metadata: Option<ResponseMetadata>,
 	}
 }
 
@@ -79,7 +84,7 @@ impl<HttpClient, RequestBody>
 	>
 where
 	HttpClient: HttpClientExt + Clone + 'static,
-	RequestBody: Into<Bytes> + Clone + Send + 'static,
+	RequestBody: Into<Bytes> + Clone + WasmCompatSend + 'static,
 {
 	pub fn new(client: HttpClient, req: Request<RequestBody>) -> Self {
 		let client_clone = client.clone();
@@ -99,6 +104,7 @@ where
 			retry_policy: Box::new(DEFAULT_RETRY),
 			last_event_id: String::new(),
 			last_retry: None,
+			response_metadata: None,
 		}
 	}
 
@@ -112,6 +118,12 @@ where
 		&self.last_event_id
 	}
 
+	/// Get the metadata of the last successful response, `None` until the stream is opened or if
+	/// the request wasn't sent by a [crate::client::Client]
+	pub fn response_metadata(&self) -> Option<&ResponseMetadata> {
+		self.response_metadata.as_ref()
+	}
+
 	/// Get the current ready state
 	pub fn ready_state(&self) -> ReadyState {
 		if self.is_closed {
@@ -153,6 +165,7 @@ where
 		T: Stream<Item = StreamResult<Bytes>> + WasmCompatSend + 'static,
 	{
 		self.last_retry.take();
+		*self.response_metadata = ResponseMetadata::from_response(&res);
 		let mut stream = res.into_body().eventsource();
 		stream.set_last_event_id(self.last_event_id.clone());
 		self.cur_stream.replace(Box::pin(stream));
@@ -167,7 +180,10 @@ where
 
 	fn handle_error(&mut self, error: &super::Error) {
 		self.clear_fetch();
-		if let Some(retry_delay) = self.retry_policy.retry(error, *self.last_retry) {
+		// Reconnecting wouldn't resume the response, a stalled request is aborted
+		if matches!(error, super::Error::StreamStalled(_)) {
+			*self.is_closed = true;
+		} else if let Some(retry_delay) = self.retry_policy.retry(error, *self.last_retry) {
 			let retry_num = self
 				.last_retry
 				.map(|retry| retry.0.saturating_add(1))
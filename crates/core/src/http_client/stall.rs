@@ -0,0 +1,134 @@
+//! Stall detection for streaming responses, for providers (or proxies) that stop sending data
+//! without closing the connection.
+//!
+//! Any bytes reset the timer, including SSE comments and ping events which are never surfaced
+//! as stream items, e.g. OpenAI's `: keep-alive` comments or Anthropic's `ping` events.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::Stream;
+use futures_timer::Delay;
+use pin_project_lite::pin_project;
+
+use super::Error;
+use super::sse::BoxedStream;
+
+/// Overrides the stall timeout of the client for a single streaming request, when added to its
+/// extensions. `StallTimeout(None)` disables stall detection for the request.
+///
+/// ```ignore
+/// let mut req = client.post_sse("/v1/chat/completions")?.body(body)?;
+/// req.extensions_mut().insert(StallTimeout(Some(Duration::from_secs(30))));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StallTimeout(pub Option<Duration>);
+
+pin_project! {
+	/// Byte stream failing with [Error::StreamStalled] once no bytes arrived for `timeout`, then
+	/// ending. Dropping the inner stream aborts the request.
+	pub struct StallGuard<S> {
+		#[pin]
+		inner: Option<S>,
+		timeout: Duration,
+		#[pin]
+		delay: Delay,
+	}
+}
+
+impl<S> StallGuard<S> {
+	pub fn new(inner: S, timeout: Duration) -> Self {
+		Self {
+			inner: Some(inner),
+			timeout,
+			delay: Delay::new(timeout),
+		}
+	}
+}
+
+impl<S> Stream for StallGuard<S>
+where
+	S: Stream<Item = Result<Bytes, Error>>,
+{
+	type Item = Result<Bytes, Error>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let mut this = self.project();
+
+		let Some(inner) = this.inner.as_mut().as_pin_mut() else {
+			return Poll::Ready(None);
+		};
+
+		match inner.poll_next(cx) {
+			Poll::Ready(item) => {
+				this.delay.set(Delay::new(*this.timeout));
+				Poll::Ready(item)
+			}
+			Poll::Pending => match this.delay.poll(cx) {
+				Poll::Ready(()) => {
+					this.inner.set(None);
+					Poll::Ready(Some(Err(Error::StreamStalled(*this.timeout))))
+				}
+				Poll::Pending => Poll::Pending,
+			},
+		}
+	}
+}
+
+/// Wraps `stream` in a [StallGuard] if a timeout is set.
+pub(crate) fn guard(stream: BoxedStream, timeout: Option<Duration>) -> BoxedStream {
+	match timeout {
+		Some(timeout) => Box::pin(StallGuard::new(stream, timeout)),
+		None => stream,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::StreamExt;
+
+	use super::*;
+
+	/// Sends each chunk after its delay.
+	fn paused_stream(chunks: Vec<(u64, &'static str)>) -> impl Stream<Item = Result<Bytes, Error>> {
+		futures::stream::iter(chunks).then(|(delay, chunk)| async move {
+			Delay::new(Duration::from_millis(delay)).await;
+			Ok(Bytes::from_static(chunk.as_bytes()))
+		})
+	}
+
+	#[tokio::test]
+	async fn test_stall_guard_fails_after_timeout() {
+		let stream = StallGuard::new(
+			paused_stream(vec![(0, "data: 1\n\n"), (500, "data: 2\n\n")]),
+			Duration::from_millis(50),
+		);
+
+		let items = stream.collect::<Vec<_>>().await;
+
+		assert_eq!(items.len(), 2);
+		assert_eq!(items[0].as_ref().unwrap(), "data: 1\n\n");
+		assert!(
+			matches!(items[1], Err(Error::StreamStalled(timeout)) if timeout == Duration::from_millis(50))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_stall_guard_is_reset_by_any_bytes() {
+		let stream = StallGuard::new(
+			paused_stream(vec![
+				(30, ": keep-alive\n\n"),
+				(30, ": keep-alive\n\n"),
+				(30, "data: 1\n\n"),
+			]),
+			Duration::from_millis(50),
+		);
+
+		let items = stream.collect::<Vec<_>>().await;
+
+		assert_eq!(items.len(), 3);
+		assert!(items.iter().all(Result::is_ok));
+	}
+}
@@ -1,5 +1,6 @@
 //! Everything related to core image generation abstractions in Clankers.
 //! Clankers allows calling a number of different providers (that support image generation) using the [ImageGenerationModel] trait.
+use futures::Stream;
 use serde_json::Value;
 use thiserror::Error;
 
@@ -26,6 +27,10 @@ pub enum ImageGenerationError {
 	/// Error returned by the transcription model provider
 	#[error("ProviderError: {0}")]
 	ProviderError(String),
+
+	/// The model doesn't support the requested operation
+	#[error("Unsupported: {0}")]
+	Unsupported(String),
 }
 pub trait ImageGeneration<M>
 where
@@ -48,13 +53,45 @@ where
 	> + Send;
 }
 
-/// A unified response for a model image generation, returning both the image and the raw response.
+/// A unified response for a model image generation, returning both the images and the raw response.
 #[derive(Debug)]
 pub struct ImageGenerationResponse<T> {
+	/// The first generated image
 	pub image: Vec<u8>,
+	/// Every generated image, starting with [Self::image]
+	pub images: Vec<Vec<u8>>,
 	pub response: T,
 }
 
+impl<T> ImageGenerationResponse<T> {
+	/// A response with the decoded `images`, failing if the provider returned none.
+	pub fn new(images: Vec<Vec<u8>>, response: T) -> Result<Self, ImageGenerationError> {
+		let image = images.first().cloned().ok_or_else(|| {
+			ImageGenerationError::ResponseError("Response contained no image".to_string())
+		})?;
+
+		Ok(Self {
+			image,
+			images,
+			response,
+		})
+	}
+}
+
+/// An item of a streamed image generation, see [ImageGenerationModel::stream_image_generation].
+#[derive(Debug)]
+pub enum PartialImage<T> {
+	/// A progressively refined preview of the image
+	Partial {
+		/// Index of the preview, starting at 0
+		index: usize,
+		/// The decoded image
+		image: Vec<u8>,
+	},
+	/// The finished image along with the raw response, always the last item of the stream
+	Final(ImageGenerationResponse<T>),
+}
+
 pub trait ImageGenerationModel: Clone + Send + Sync {
 	type Response: Send + Sync;
 
@@ -69,17 +106,43 @@ pub trait ImageGenerationModel: Clone + Send + Sync {
 		Output = Result<ImageGenerationResponse<Self::Response>, ImageGenerationError>,
 	> + Send;
 
+	/// Generates an image, streaming partial previews before the final image.
+	/// Models that can't stream yield a single [ImageGenerationError::Unsupported] error.
+	fn stream_image_generation(
+		&self,
+		request: ImageGenerationRequest,
+	) -> impl Stream<Item = Result<PartialImage<Self::Response>, ImageGenerationError>> + Send {
+		let _ = request;
+		futures::stream::once(async {
+			Err(ImageGenerationError::Unsupported(
+				"streaming image generation is not supported by this model".to_string(),
+			))
+		})
+	}
+
 	fn image_generation_request(&self) -> ImageGenerationRequestBuilder<Self> {
 		ImageGenerationRequestBuilder::new(self.clone())
 	}
 }
 
 /// An image generation request.
+///
+/// The optional fields are ignored by providers that don't support them.
 #[non_exhaustive]
 pub struct ImageGenerationRequest {
 	pub prompt: String,
 	pub width: u32,
 	pub height: u32,
+	/// What the images shouldn't contain
+	pub negative_prompt: Option<String>,
+	/// Seed of the generation, for reproducible images
+	pub seed: Option<u64>,
+	/// Number of inference (denoising) steps
+	pub steps: Option<u32>,
+	/// How closely the images follow the prompt (classifier-free guidance scale)
+	pub cfg_scale: Option<f64>,
+	/// Number of images to generate
+	pub n: Option<u32>,
 	pub additional_params: Option<Value>,
 }
 
@@ -94,6 +157,11 @@ where
 	prompt: String,
 	width: u32,
 	height: u32,
+	negative_prompt: Option<String>,
+	seed: Option<u64>,
+	steps: Option<u32>,
+	cfg_scale: Option<f64>,
+	n: Option<u32>,
 	additional_params: Option<Value>,
 }
 
@@ -107,6 +175,11 @@ where
 			prompt: "".to_string(),
 			height: 256,
 			width: 256,
+			negative_prompt: None,
+			seed: None,
+			steps: None,
+			cfg_scale: None,
+			n: None,
 			additional_params: None,
 		}
 	}
@@ -129,6 +202,36 @@ where
 		self
 	}
 
+	/// Describes what the generated images shouldn't contain
+	pub fn negative_prompt(mut self, negative_prompt: &str) -> Self {
+		self.negative_prompt = Some(negative_prompt.to_string());
+		self
+	}
+
+	/// Sets the seed of the generation, for reproducible images
+	pub fn seed(mut self, seed: u64) -> Self {
+		self.seed = Some(seed);
+		self
+	}
+
+	/// Sets the number of inference steps
+	pub fn steps(mut self, steps: u32) -> Self {
+		self.steps = Some(steps);
+		self
+	}
+
+	/// Sets how closely the images follow the prompt
+	pub fn cfg_scale(mut self, cfg_scale: f64) -> Self {
+		self.cfg_scale = Some(cfg_scale);
+		self
+	}
+
+	/// Sets the number of images to generate
+	pub fn n(mut self, n: u32) -> Self {
+		self.n = Some(n);
+		self
+	}
+
 	/// Adds additional parameters to the image generation request.
 	pub fn additional_params(mut self, params: Value) -> Self {
 		self.additional_params = Some(params);
@@ -140,6 +243,11 @@ where
 			prompt: self.prompt,
 			width: self.width,
 			height: self.height,
+			negative_prompt: self.negative_prompt,
+			seed: self.seed,
+			steps: self.steps,
+			cfg_scale: self.cfg_scale,
+			n: self.n,
 			additional_params: self.additional_params,
 		}
 	}
@@ -149,4 +257,20 @@ where
 
 		model.image_generation(self.build()).await
 	}
+
+	/// Sends the request, streaming partial previews before the final image.
+	pub fn stream(
+		self,
+	) -> impl Stream<Item = Result<PartialImage<M::Response>, ImageGenerationError>> + Send {
+		let model = self.model.clone();
+		let request = self.build();
+
+		async_stream::stream! {
+			let stream = model.stream_image_generation(request);
+			futures::pin_mut!(stream);
+			while let Some(item) = futures::StreamExt::next(&mut stream).await {
+				yield item;
+			}
+		}
+	}
 }
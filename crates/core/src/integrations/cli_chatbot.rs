@@ -2,7 +2,7 @@ use std::io::{self, Write};
 
 use futures::StreamExt;
 
-use crate::agent::{Agent, MultiTurnStreamItem, Text};
+use crate::agent::{Agent, DEFAULT_MAX_TURNS, MultiTurnStreamItem, Text};
 use crate::completion::{Chat, CompletionError, CompletionModel, PromptError, Usage};
 use crate::message::Message;
 use crate::streaming::{StreamedAssistantContent, StreamingPrompt};
@@ -128,7 +128,7 @@ impl ChatBotBuilder<NoImplProvided> {
 	) -> ChatBotBuilder<AgentImpl<M>> {
 		ChatBotBuilder(AgentImpl {
 			agent,
-			max_turns: 1,
+			max_turns: DEFAULT_MAX_TURNS,
 			show_usage: false,
 			usage: Usage::default(),
 		})
@@ -206,13 +206,10 @@ where
 					println!("================================================================");
 					println!();
 
-					if self.0.show_usage() {
-						let Usage {
-							input_tokens,
-							output_tokens,
-							..
-						} = self.0.usage().unwrap();
-						println!("Input {input_tokens} tokens\nOutput {output_tokens} tokens");
+					if self.0.show_usage()
+						&& let Some(usage) = self.0.usage()
+					{
+						println!("Tokens: {usage}");
 					}
 				}
 				Err(e) => println!("Error reading request: {e}"),
@@ -26,6 +26,56 @@ pub fn merge_inplace(a: &mut serde_json::Value, b: serde_json::Value) {
 	}
 }
 
+/// How [deep_merge] combines two arrays found at the same key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArrayMergeStrategy {
+	/// The array of the second value replaces the first one
+	#[default]
+	Replace,
+	/// The elements of the second array are appended to the first one
+	Concat,
+}
+
+/// Recursively merge `b` into `a`, unlike [merge] which replaces nested objects as a whole.
+///
+/// Objects are merged key by key, arrays are combined according to `arrays`, and any other
+/// value of `b` replaces the value of `a`. A `null` in `b` is kept as an explicit `null` rather
+/// than removing the key.
+pub fn deep_merge(
+	mut a: serde_json::Value,
+	b: serde_json::Value,
+	arrays: ArrayMergeStrategy,
+) -> serde_json::Value {
+	deep_merge_inplace(&mut a, b, arrays);
+	a
+}
+
+/// In place version of [deep_merge].
+pub fn deep_merge_inplace(
+	a: &mut serde_json::Value,
+	b: serde_json::Value,
+	arrays: ArrayMergeStrategy,
+) {
+	match (a, b) {
+		(serde_json::Value::Object(a_map), serde_json::Value::Object(b_map)) => {
+			for (key, value) in b_map {
+				match a_map.get_mut(&key) {
+					Some(existing) => deep_merge_inplace(existing, value, arrays),
+					None => {
+						a_map.insert(key, value);
+					}
+				}
+			}
+		}
+		(serde_json::Value::Array(a_items), serde_json::Value::Array(b_items))
+			if arrays == ArrayMergeStrategy::Concat =>
+		{
+			a_items.extend(b_items);
+		}
+		(a, b) => *a = b,
+	}
+}
+
 /// Convert a serde_json::Value to a JSON string for tool arguments.
 /// Handles the case where vLLM returns arguments as a JSON string (Value::String)
 /// instead of a JSON object (Value::Object) like OpenAI does.
@@ -181,6 +231,61 @@ mod tests {
 		assert_eq!(a, expected);
 	}
 
+	#[test]
+	fn test_deep_merge_nested_objects() {
+		let a = serde_json::json!({
+			"stream": false,
+			"stream_options": {"include_obfuscation": false},
+			"options": {"sampling": {"top_k": 40, "top_p": 0.9}},
+		});
+		let b = serde_json::json!({
+			"stream": true,
+			"stream_options": {"include_usage": true},
+			"options": {"sampling": {"top_p": 0.5}},
+		});
+
+		let result = deep_merge(a, b, ArrayMergeStrategy::Replace);
+		let expected = serde_json::json!({
+			"stream": true,
+			"stream_options": {"include_obfuscation": false, "include_usage": true},
+			"options": {"sampling": {"top_k": 40, "top_p": 0.5}},
+		});
+		assert_eq!(result, expected);
+	}
+
+	#[test]
+	fn test_deep_merge_arrays() {
+		let a = serde_json::json!({"options": {"stop": ["\n"]}});
+		let b = serde_json::json!({"options": {"stop": ["</s>"]}});
+
+		assert_eq!(
+			deep_merge(a.clone(), b.clone(), ArrayMergeStrategy::Replace),
+			serde_json::json!({"options": {"stop": ["</s>"]}})
+		);
+		assert_eq!(
+			deep_merge(a, b, ArrayMergeStrategy::Concat),
+			serde_json::json!({"options": {"stop": ["\n", "</s>"]}})
+		);
+	}
+
+	#[test]
+	fn test_deep_merge_null_and_type_changes() {
+		let mut a = serde_json::json!({"seed": 42, "options": {"num_ctx": 4096}, "tags": ["a"]});
+		let b = serde_json::json!({"seed": null, "options": null, "tags": {"name": "a"}});
+
+		deep_merge_inplace(&mut a, b, ArrayMergeStrategy::Concat);
+		let expected = serde_json::json!({"seed": null, "options": null, "tags": {"name": "a"}});
+		assert_eq!(a, expected);
+
+		// A null is replaced like any other value
+		let result = deep_merge(
+			serde_json::json!({"options": null}),
+			serde_json::json!({"options": {"num_ctx": 4096}}),
+			ArrayMergeStrategy::Replace,
+		);
+		assert_eq!(result, serde_json::json!({"options": {"num_ctx": 4096}}));
+	}
+
 	#[test]
 	fn test_stringified_json_serialize() {
 		let dummy = Dummy {
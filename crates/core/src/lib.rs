@@ -112,8 +112,11 @@ pub mod one_or_many;
 pub mod pipeline;
 pub mod prelude;
 pub mod providers;
+pub mod quick;
 
 pub mod streaming;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub mod tool;
 pub mod transcription;
 pub mod vector_store;
@@ -126,5 +129,6 @@ pub use clankers_derive::{Embed, clankers_tool as tool_macro};
 pub use completion::message;
 pub use embeddings::Embed;
 pub use one_or_many::{EmptyListError, OneOrMany};
+pub use schemars;
 
 pub mod telemetry;
@@ -2,8 +2,8 @@ use std::future::IntoFuture;
 
 use super::Op;
 use crate::completion::{self, CompletionModel};
-use crate::extractor::{ExtractionError, Extractor};
-use crate::message::Message;
+use crate::extractor::{ExtractionError, Extractor, ExtractorBuilder};
+use crate::message::{AssistantContent, Message};
 use crate::vector_store::request::VectorSearchRequest;
 use crate::vector_store::{self};
 use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
@@ -106,6 +106,55 @@ where
 	Prompt::new(model)
 }
 
+pub struct Complete<M, In> {
+	model: M,
+	_in: std::marker::PhantomData<In>,
+}
+
+impl<M, In> Complete<M, In> {
+	pub(crate) fn new(model: M) -> Self {
+		Self {
+			model,
+			_in: std::marker::PhantomData,
+		}
+	}
+}
+
+impl<M, In> Op for Complete<M, In>
+where
+	M: CompletionModel,
+	In: Into<Message> + WasmCompatSend + WasmCompatSync,
+{
+	type Input = In;
+	type Output = Result<String, completion::PromptError>;
+
+	async fn call(&self, input: Self::Input) -> Self::Output {
+		let response = self.model.completion_request(input).send().await?;
+
+		Ok(response
+			.choice
+			.iter()
+			.filter_map(|content| match content {
+				AssistantContent::Text(text) => Some(text.text.as_str()),
+				_ => None,
+			})
+			.collect::<Vec<_>>()
+			.join("\n"))
+	}
+}
+
+/// Create a new completion operation.
+///
+/// Unlike [prompt], which needs an agent, the op sends the input straight to any completion
+/// `model` and returns the text of the response. Tool calls are not executed.
+pub fn complete<M, In>(model: M) -> Complete<M, In>
+where
+	M: CompletionModel,
+	In: Into<Message> + WasmCompatSend + WasmCompatSync,
+{
+	Complete::new(model)
+}
+
 pub struct Extract<M, Input, Output>
 where
 	M: CompletionModel,
@@ -154,6 +203,28 @@ where
 	Extract::new(extractor)
 }
 
+/// Create a new extract operation from a completion `model`, using an extractor with the default
+/// settings. See [ExtractorBuilder] to customize it.
+///
+/// ```rust
+/// use clankers::pipeline::agent_ops;
+///
+/// let op = agent_ops::extract_with::<Sentiment, _, String>(model);
+/// ```
+pub fn extract_with<Output, M, Input>(model: M) -> Extract<M, Input, Output>
+where
+	M: CompletionModel,
+	Output: schemars::JsonSchema
+		+ for<'a> serde::Deserialize<'a>
+		+ serde::Serialize
+		+ WasmCompatSend
+		+ WasmCompatSync
+		+ 'static,
+	Input: Into<String> + WasmCompatSend + WasmCompatSync,
+{
+	Extract::new(ExtractorBuilder::new(model).build())
+}
+
 #[cfg(test)]
 pub mod tests {
 	use completion::{Prompt, PromptError};
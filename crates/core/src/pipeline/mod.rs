@@ -244,6 +244,30 @@ impl<E> PipelineBuilder<E> {
 		agent_ops::Prompt::new(agent)
 	}
 
+	/// Add a completion operation to the current pipeline/op. Same as [prompt](Self::prompt),
+	/// but for any [CompletionModel](completion::CompletionModel) rather than an agent.
+	///
+	/// # Example
+	/// ```rust
+	/// use clankers::pipeline::{self, Op};
+	///
+	/// let model = openai_client.completion_model("gpt-4o");
+	///
+	/// let pipeline = pipeline::new()
+	///    .map(|name| format!("Find funny nicknames for the following name: {name}!"))
+	///    .complete(model);
+	///
+	/// let result = pipeline.call("Alice".to_string()).await;
+	/// ```
+	pub fn complete<M, Input>(self, model: M) -> agent_ops::Complete<M, Input>
+	where
+		M: completion::CompletionModel,
+		Input: Into<crate::message::Message> + Send + Sync,
+		Self: Sized,
+	{
+		agent_ops::Complete::new(model)
+	}
+
 	/// Add an extract operation to the current pipeline/op. The extract operation expects the
 	/// current pipeline to output a string. The extract operation will use the given `extractor`
 	/// to extract information from the string in the form of the type `T` and return it.
@@ -304,8 +328,27 @@ pub fn with_error<E>() -> PipelineBuilder<E> {
 #[cfg(test)]
 mod tests {
 	use agent_ops::tests::{Foo, MockIndex, MockModel};
+	use serde_json::json;
 
 	use super::*;
+	use crate::OneOrMany;
+	use crate::completion::{AssistantContent, CompletionError, PromptError};
+	use crate::message::{Message, UserContent};
+	use crate::test_utils::MockCompletionModel;
+
+	fn prompt_text(model: &MockCompletionModel) -> Vec<String> {
+		model
+			.requests()
+			.into_iter()
+			.map(|request| match request.chat_history.last() {
+				Message::User { content } => match content.first() {
+					UserContent::Text(text) => text.text,
+					other => panic!("Expected a text prompt, got {other:?}"),
+				},
+				other => panic!("Expected a user prompt, got {other:?}"),
+			})
+			.collect()
+	}
 
 	#[tokio::test]
 	async fn test_prompt_pipeline() {
@@ -380,4 +423,77 @@ mod tests {
 			"Mock response: User query: What is a flurbo?\n\nTop documents:\nbar"
 		);
 	}
+
+	#[tokio::test]
+	async fn test_chained_completions() {
+		let namer =
+			MockCompletionModel::with_responses([OneOrMany::one(AssistantContent::text("Alice"))]);
+		let greeter = MockCompletionModel::with_responses([OneOrMany::one(
+			AssistantContent::text("Hello, Alice!"),
+		)]);
+
+		let chain = super::new()
+			.map(|topic: String| format!("Pick a name for a {topic}"))
+			.complete(namer.clone())
+			.traced("name")
+			.map_ok(|name| format!("Greet {name}"))
+			.try_chain(agent_ops::complete(greeter.clone()).traced("greet"));
+
+		let result = chain
+			.try_call("cat".to_string())
+			.await
+			.expect("Failed to run chain");
+
+		assert_eq!(result, "Hello, Alice!");
+		assert_eq!(prompt_text(&namer), vec!["Pick a name for a cat"]);
+		assert_eq!(prompt_text(&greeter), vec!["Greet Alice"]);
+	}
+
+	#[tokio::test]
+	async fn test_chain_short_circuits_on_error() {
+		let namer = MockCompletionModel::default();
+		let greeter = MockCompletionModel::default();
+
+		let chain = super::new()
+			.complete(namer.clone())
+			.and_then(|_: String| async move {
+				Err::<String, _>(PromptError::from(CompletionError::ResponseError(
+					"No name".to_string(),
+				)))
+			})
+			.try_chain(agent_ops::complete(greeter.clone()));
+
+		let err = chain
+			.try_call("Pick a name")
+			.await
+			.expect_err("The second stage fails");
+
+		assert!(err.to_string().contains("No name"), "{err}");
+		assert_eq!(namer.requests().len(), 1);
+		assert!(greeter.requests().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_extract_with_model() {
+		#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
+		struct Person {
+			name: String,
+		}
+
+		let model = MockCompletionModel::with_responses([OneOrMany::one(
+			AssistantContent::tool_call("call-1", "submit", json!({ "name": "Alice" })),
+		)]);
+
+		let chain = super::new()
+			.map(|text: &str| format!("Extract the person: {text}"))
+			.chain(agent_ops::extract_with::<Person, _, _>(model));
+
+		let person = chain.try_call("Alice went home").await.unwrap();
+		assert_eq!(
+			person,
+			Person {
+				name: "Alice".to_string()
+			}
+		);
+	}
 }
@@ -1,6 +1,7 @@
 use std::future::Future;
 
 use futures::stream;
+use tracing::Instrument;
 
 use crate::wasm_compat::*;
 
@@ -168,6 +169,64 @@ pub trait Op: WasmCompatSend + WasmCompatSync {
 	{
 		Sequential::new(self, Prompt::new(prompt))
 	}
+
+	/// Chain a completion operation to the current chain. Same as [prompt](Op::prompt), but
+	/// works with any completion model instead of an agent, see [agent_ops::complete].
+	///
+	/// # Example
+	/// ```rust
+	/// use clankers::pipeline::{self, Op};
+	///
+	/// let model = openai_client.completion_model("gpt-4o");
+	///
+	/// let chain = pipeline::new()
+	///    .map(|name| format!("Find funny nicknames for the following name: {name}!"))
+	///    .complete(model);
+	///
+	/// let result = chain.call("Alice".to_string()).await;
+	/// ```
+	fn complete<M>(self, model: M) -> Sequential<Self, Complete<M, Self::Output>>
+	where
+		M: completion::CompletionModel,
+		Self::Output: Into<Message>,
+		Self: Sized,
+	{
+		Sequential::new(self, Complete::new(model))
+	}
+
+	/// Chain an extract operation to the current chain, extracting the output of the current
+	/// chain into `T` with the given `extractor`.
+	fn extract<M, T>(
+		self,
+		extractor: Extractor<M, T>,
+	) -> Sequential<Self, Extract<M, Self::Output, T>>
+	where
+		M: completion::CompletionModel,
+		T: schemars::JsonSchema + for<'a> serde::Deserialize<'a> + WasmCompatSend + WasmCompatSync,
+		Self::Output: Into<Message>,
+		Self: Sized,
+	{
+		Sequential::new(self, Extract::new(extractor))
+	}
+
+	/// Run the current op in a `pipeline_stage` span named `name`, on the `clankers::pipeline`
+	/// target. The spans of the completions made by the op are nested under it.
+	///
+	/// # Example
+	/// ```rust
+	/// use clankers::pipeline::{self, Op};
+	///
+	/// let chain = pipeline::new()
+	///     .map(|name: String| format!("Find funny nicknames for {name}!"))
+	///     .complete(model)
+	///     .traced("nicknames");
+	/// ```
+	fn traced(self, name: impl Into<String>) -> Traced<Self>
+	where
+		Self: Sized,
+	{
+		Traced::new(self, name)
+	}
 }
 
 impl<T: Op> Op for &T {
@@ -206,7 +265,38 @@ where
 	}
 }
 
-use super::agent_ops::{Lookup, Prompt};
+/// An op running in its own tracing span, see [Op::traced].
+pub struct Traced<T> {
+	op: T,
+	name: String,
+}
+
+impl<T> Traced<T> {
+	pub(crate) fn new(op: T, name: impl Into<String>) -> Self {
+		Self {
+			op,
+			name: name.into(),
+		}
+	}
+}
+
+impl<T: Op> Op for Traced<T> {
+	type Input = T::Input;
+	type Output = T::Output;
+
+	fn call(&self, input: Self::Input) -> impl Future<Output = Self::Output> + WasmCompatSend {
+		let span = tracing::info_span!(
+			target: "clankers::pipeline",
+			"pipeline_stage",
+			pipeline.stage = self.name.as_str(),
+		);
+		self.op.call(input).instrument(span)
+	}
+}
+
+use super::agent_ops::{Complete, Extract, Lookup, Prompt};
+use crate::extractor::Extractor;
+use crate::message::Message;
 use crate::{completion, vector_store};
 
 pub struct Map<F, Input> {
@@ -182,6 +182,29 @@ pub trait TryOp: WasmCompatSend + WasmCompatSync {
 	{
 		TrySequential::new(self, op)
 	}
+
+	/// Chain a fallible op `op` to the current op. The new op will only be called with the
+	/// success value of the current op, errors of either op are returned as is, short-circuiting
+	/// the rest of the chain.
+	///
+	/// # Example
+	/// ```rust
+	/// use clankers::pipeline::{self, TryOp, map};
+	///
+	/// let op = pipeline::new()
+	///     .map(|x: i32| if x % 2 == 0 { Ok(x) } else { Err("x is odd") })
+	///     .try_chain(map(|x: i32| if x > 0 { Ok(x * 2) } else { Err("x is negative") }));
+	///
+	/// let result = op.try_call(2).await;
+	/// assert_eq!(result, Ok(4));
+	/// ```
+	fn try_chain<T>(self, op: T) -> AndThen<Self, T>
+	where
+		T: TryOp<Input = Self::Output, Error = Self::Error>,
+		Self: Sized,
+	{
+		AndThen::new(self, op)
+	}
 }
 
 impl<Op, T, E> TryOp for Op
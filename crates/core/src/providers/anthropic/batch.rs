@@ -0,0 +1,636 @@
+//! Anthropic Message Batches API, processing many completion requests asynchronously at half
+//! the price.
+//!
+//! Each request of a batch is identified by a `custom_id` chosen by the caller, and built like
+//! the requests of the [CompletionModel] the batch model was created from. Results are streamed
+//! once the batch has ended, each request succeeding or failing on its own.
+//!
+//! # Example
+//! ```
+//! use futures::StreamExt;
+//! use clankers::completion::CompletionRequest;
+//! use clankers::providers::anthropic::{self, batch::BatchEntry};
+//!
+//! let client = anthropic::Client::from_env();
+//! let batch_model = client.batch_model(anthropic::types::CLAUDE_4_SONNET);
+//!
+//! let batch = batch_model
+//!     .create_batch(vec![
+//!         BatchEntry::new("france", CompletionRequest::builder("Capital of France?").build()),
+//!         BatchEntry::new("italy", CompletionRequest::builder("Capital of Italy?").build())
+//!             .with_prompt_caching(true),
+//!     ])
+//!     .await
+//!     .expect("Failed to create the batch");
+//!
+//! // Later, once `batch_model.get_batch(&batch.id)` reports the batch as ended
+//! let mut results = batch_model.results(&batch.id).await.expect("Failed to get the results");
+//! while let Some(result) = results.next().await {
+//!     let result = result.expect("Failed to parse a result");
+//!     match result.result {
+//!         Ok(response) => println!("{}: {:?}", result.custom_id, response.choice),
+//!         Err(error) => println!("{} failed: {error}", result.custom_id),
+//!     }
+//! }
+//! ```
+use std::collections::HashSet;
+
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::completion::CompletionModel;
+use super::error::{parse_api_error, parse_stream_error};
+use super::types::{AnthropicCompletionRequest, AnthropicRequestParams, CompletionResponse};
+use crate::completion::{self, ApiError, CompletionError, CompletionRequest};
+use crate::http_client::{self, HttpClientExt, NoBody};
+use crate::wasm_compat::WasmCompatSend;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BatchError {
+	/// Http error (e.g.: connection error, timeout, etc.)
+	#[error("HttpError: {0}")]
+	HttpError(#[from] http_client::Error),
+
+	/// Json error (e.g.: serialization, deserialization)
+	#[error("JsonError: {0}")]
+	JsonError(#[from] serde_json::Error),
+
+	/// Error converting a request of the batch
+	#[error("CompletionError: {0}")]
+	CompletionError(#[from] CompletionError),
+
+	/// Custom IDs must be unique within a batch, and made of 1 to 64 letters, digits, `-` or `_`
+	#[error("Invalid custom ID `{0}`")]
+	InvalidCustomId(String),
+
+	/// Error response of the batches API
+	#[error("ApiError: {0}")]
+	ApiError(ApiError),
+}
+
+/// Why a request of a batch failed.
+#[derive(Debug, thiserror::Error)]
+pub enum BatchItemError {
+	/// The request was processed but Anthropic returned an error, e.g. an invalid request
+	#[error("ApiError: {0}")]
+	Errored(ApiError),
+
+	/// The batch was canceled before the request was processed
+	#[error("The request was canceled before being processed")]
+	Canceled,
+
+	/// The batch expired before the request was processed
+	#[error("The request expired before being processed")]
+	Expired,
+
+	/// The response couldn't be converted
+	#[error("CompletionError: {0}")]
+	CompletionError(#[from] CompletionError),
+}
+
+/// A request of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchEntry {
+	pub custom_id: String,
+	pub request: CompletionRequest,
+	/// Overrides the prompt caching setting of the model for this request
+	pub prompt_caching: Option<bool>,
+}
+
+impl BatchEntry {
+	pub fn new(custom_id: impl Into<String>, request: CompletionRequest) -> Self {
+		Self {
+			custom_id: custom_id.into(),
+			request,
+			prompt_caching: None,
+		}
+	}
+
+	/// Enables or disables the `cache_control` breakpoints of this request, see
+	/// [CompletionModel::with_prompt_caching].
+	pub fn with_prompt_caching(mut self, prompt_caching: bool) -> Self {
+		self.prompt_caching = Some(prompt_caching);
+		self
+	}
+}
+
+impl<S> From<(S, CompletionRequest)> for BatchEntry
+where
+	S: Into<String>,
+{
+	fn from((custom_id, request): (S, CompletionRequest)) -> Self {
+		Self::new(custom_id, request)
+	}
+}
+
+/// Body of a request to the `/v1/messages/batches` endpoint
+#[derive(Debug, Serialize)]
+pub(crate) struct CreateBatchRequest {
+	pub(crate) requests: Vec<BatchRequest>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchRequest {
+	pub(crate) custom_id: String,
+	pub(crate) params: AnthropicCompletionRequest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+	InProgress,
+	Canceling,
+	Ended,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestCounts {
+	pub processing: u64,
+	pub succeeded: u64,
+	pub errored: u64,
+	pub canceled: u64,
+	pub expired: u64,
+}
+
+/// A message batch, as returned by Anthropic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageBatch {
+	pub id: String,
+	pub processing_status: ProcessingStatus,
+	#[serde(default)]
+	pub request_counts: RequestCounts,
+	pub created_at: String,
+	pub expires_at: String,
+	#[serde(default)]
+	pub ended_at: Option<String>,
+	#[serde(default)]
+	pub cancel_initiated_at: Option<String>,
+	/// Where the results can be downloaded from, once the batch has ended
+	#[serde(default)]
+	pub results_url: Option<String>,
+}
+
+/// The result of a request of a batch.
+#[derive(Debug)]
+pub struct BatchResult {
+	pub custom_id: String,
+	pub result: Result<completion::CompletionResponse<CompletionResponse>, BatchItemError>,
+}
+
+/// A line of the results of a batch
+#[derive(Debug, Deserialize)]
+struct BatchResultLine {
+	custom_id: String,
+	result: BatchResultBody,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BatchResultBody {
+	Succeeded {
+		message: CompletionResponse,
+	},
+	/// The error has the body of an error response, `{ "type": "error", "error": { .. } }`
+	Errored {
+		error: serde_json::Value,
+	},
+	Canceled,
+	Expired,
+}
+
+impl From<BatchResultLine> for BatchResult {
+	fn from(line: BatchResultLine) -> Self {
+		let result = match line.result {
+			BatchResultBody::Succeeded { message } => {
+				completion::CompletionResponse::try_from(message).map_err(BatchItemError::from)
+			}
+			BatchResultBody::Errored { error } => Err(BatchItemError::Errored(parse_stream_error(
+				error.to_string(),
+			))),
+			BatchResultBody::Canceled => Err(BatchItemError::Canceled),
+			BatchResultBody::Expired => Err(BatchItemError::Expired),
+		};
+
+		Self {
+			custom_id: line.custom_id,
+			result,
+		}
+	}
+}
+
+fn is_valid_custom_id(custom_id: &str) -> bool {
+	(1..=64).contains(&custom_id.len())
+		&& custom_id
+			.bytes()
+			.all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_'))
+}
+
+/// Sends batches of completion requests, built like the requests of a [CompletionModel].
+#[derive(Clone)]
+pub struct BatchModel<T = reqwest::Client> {
+	pub model: CompletionModel<T>,
+}
+
+impl<T> BatchModel<T> {
+	pub fn new(model: CompletionModel<T>) -> Self {
+		Self { model }
+	}
+}
+
+impl<T> From<CompletionModel<T>> for BatchModel<T> {
+	fn from(model: CompletionModel<T>) -> Self {
+		Self::new(model)
+	}
+}
+
+impl<T> BatchModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	pub(crate) fn batch_request(
+		&self,
+		entries: impl IntoIterator<Item = impl Into<BatchEntry>>,
+	) -> Result<CreateBatchRequest, BatchError> {
+		let mut custom_ids = HashSet::new();
+		let mut requests = Vec::new();
+
+		for entry in entries {
+			let BatchEntry {
+				custom_id,
+				mut request,
+				prompt_caching,
+			} = entry.into();
+
+			if !is_valid_custom_id(&custom_id) || !custom_ids.insert(custom_id.clone()) {
+				return Err(BatchError::InvalidCustomId(custom_id));
+			}
+
+			if request.max_tokens.is_none() {
+				request.max_tokens = self.model.default_max_tokens;
+			}
+
+			let params = AnthropicCompletionRequest::try_from(AnthropicRequestParams {
+				model: &self.model.model,
+				request,
+				prompt_caching: prompt_caching.unwrap_or(self.model.prompt_caching),
+				server_tools: &self.model.server_tools,
+			})?;
+
+			requests.push(BatchRequest { custom_id, params });
+		}
+
+		Ok(CreateBatchRequest { requests })
+	}
+
+	/// Creates a batch processing `entries` within 24 hours.
+	pub async fn create_batch(
+		&self,
+		entries: impl IntoIterator<Item = impl Into<BatchEntry>>,
+	) -> Result<MessageBatch, BatchError> {
+		let request = self.batch_request(entries)?;
+
+		let req = self
+			.model
+			.client
+			.post("/v1/messages/batches")?
+			.body(serde_json::to_vec(&request)?)
+			.map_err(http_client::Error::Protocol)?;
+
+		let batch: MessageBatch = self.send(req).await?;
+
+		tracing::debug!(target: "clankers",
+			"Created Anthropic message batch {} of {} requests",
+			batch.id,
+			request.requests.len()
+		);
+
+		Ok(batch)
+	}
+
+	/// Gets the processing status of a batch.
+	pub async fn get_batch(&self, batch_id: &str) -> Result<MessageBatch, BatchError> {
+		let req = self
+			.model
+			.client
+			.get(format!("/v1/messages/batches/{batch_id}"))?
+			.body(NoBody)
+			.map_err(http_client::Error::Protocol)?;
+
+		self.send(req).await
+	}
+
+	/// Cancels a batch. Requests that are already being processed still complete.
+	pub async fn cancel_batch(&self, batch_id: &str) -> Result<MessageBatch, BatchError> {
+		let req = self
+			.model
+			.client
+			.post(format!("/v1/messages/batches/{batch_id}/cancel"))?
+			.body(NoBody)
+			.map_err(http_client::Error::Protocol)?;
+
+		self.send(req).await
+	}
+
+	/// Streams the results of an ended batch, one per request, in no particular order.
+	pub async fn results(
+		&self,
+		batch_id: &str,
+	) -> Result<impl Stream<Item = Result<BatchResult, BatchError>> + WasmCompatSend, BatchError> {
+		let req = self
+			.model
+			.client
+			.get(format!("/v1/messages/batches/{batch_id}/results"))?
+			.body(NoBody)
+			.map_err(http_client::Error::Protocol)?;
+
+		let response = self.model.client.send_streaming(req).await?;
+		let status = response.status();
+
+		if !status.is_success() {
+			let mut body = Vec::new();
+			let mut byte_stream = response.into_body();
+			while let Some(chunk) = byte_stream.next().await {
+				body.extend_from_slice(&chunk?);
+			}
+			return Err(BatchError::ApiError(parse_api_error(
+				status,
+				String::from_utf8_lossy(&body).into(),
+			)));
+		}
+
+		Ok(batch_results(response.into_body()))
+	}
+
+	async fn send<B, R>(&self, req: http::Request<B>) -> Result<R, BatchError>
+	where
+		B: Into<Bytes> + WasmCompatSend,
+		R: DeserializeOwned,
+	{
+		let response = self.model.client.send::<_, Bytes>(req).await?;
+		let status = response.status();
+		let body = response.into_body().await?;
+
+		if !status.is_success() {
+			return Err(BatchError::ApiError(parse_api_error(
+				status,
+				String::from_utf8_lossy(&body).into(),
+			)));
+		}
+
+		Ok(serde_json::from_slice(&body)?)
+	}
+}
+
+/// Parse the JSONL results of a batch.
+/// Lines may be split across chunks, so incomplete lines are buffered until their newline arrives.
+fn batch_results<S>(
+	mut byte_stream: S,
+) -> impl Stream<Item = Result<BatchResult, BatchError>> + WasmCompatSend
+where
+	S: Stream<Item = http_client::Result<Bytes>> + Unpin + WasmCompatSend,
+{
+	try_stream! {
+		let mut buffer = Vec::new();
+
+		while let Some(chunk) = byte_stream.next().await {
+			buffer.extend_from_slice(&chunk?);
+
+			while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+				let line = buffer.drain(..=end).collect::<Vec<_>>();
+				if let Some(result) = parse_result_line(&line)? {
+					yield result;
+				}
+			}
+		}
+
+		if let Some(result) = parse_result_line(&buffer)? {
+			yield result;
+		}
+	}
+}
+
+fn parse_result_line(line: &[u8]) -> Result<Option<BatchResult>, BatchError> {
+	if line.trim_ascii().is_empty() {
+		return Ok(None);
+	}
+
+	Ok(Some(
+		serde_json::from_slice::<BatchResultLine>(line)?.into(),
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::OneOrMany;
+	use crate::completion::{AssistantContent, ProviderErrorKind};
+	use crate::providers::anthropic::Client;
+	use crate::providers::anthropic::types::CLAUDE_4_SONNET;
+	use crate::test_utils::MockSseClient;
+
+	fn batch_model(http_client: MockSseClient) -> BatchModel<MockSseClient> {
+		Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap()
+			.batch_model(CLAUDE_4_SONNET)
+	}
+
+	fn batch(status: &str) -> String {
+		json!({
+			"id": "msgbatch_01",
+			"type": "message_batch",
+			"processing_status": status,
+			"request_counts": { "processing": 0, "succeeded": 1, "errored": 1, "canceled": 0, "expired": 0 },
+			"created_at": "2025-01-10T10:00:00Z",
+			"expires_at": "2025-01-11T10:00:00Z",
+			"ended_at": null,
+			"cancel_initiated_at": null,
+			"results_url": null
+		})
+		.to_string()
+	}
+
+	#[test]
+	fn test_batch_request() {
+		let model = batch_model(MockSseClient::default());
+
+		let request = model
+			.batch_request([
+				BatchEntry::new(
+					"first",
+					CompletionRequest::builder("Capital of France?")
+						.preamble("Answer briefly.".to_string())
+						.build(),
+				),
+				BatchEntry::new(
+					"second",
+					CompletionRequest::builder("Capital of Italy?")
+						.preamble("Answer briefly.".to_string())
+						.max_tokens(64)
+						.build(),
+				)
+				.with_prompt_caching(true),
+			])
+			.unwrap();
+
+		let body = serde_json::to_value(&request).unwrap();
+		let requests = body["requests"].as_array().unwrap();
+
+		assert_eq!(requests.len(), 2);
+		assert_eq!(requests[0]["custom_id"], "first");
+		assert_eq!(requests[0]["params"]["model"], CLAUDE_4_SONNET);
+		assert_eq!(requests[0]["params"]["max_tokens"], 64000);
+		assert_eq!(
+			requests[0]["params"]["system"],
+			json!([{ "type": "text", "text": "Answer briefly." }])
+		);
+		assert_eq!(
+			requests[0]["params"]["messages"],
+			json!([{
+				"role": "user",
+				"content": [{ "type": "text", "text": "Capital of France?" }]
+			}])
+		);
+
+		// Prompt caching only applies to the second request
+		assert_eq!(requests[1]["custom_id"], "second");
+		assert_eq!(requests[1]["params"]["max_tokens"], 64);
+		assert_eq!(
+			requests[1]["params"]["system"][0]["cache_control"],
+			json!({ "type": "ephemeral" })
+		);
+		assert_eq!(
+			requests[1]["params"]["messages"][0]["content"][0]["cache_control"],
+			json!({ "type": "ephemeral" })
+		);
+	}
+
+	#[test]
+	fn test_batch_request_rejects_invalid_custom_ids() {
+		let model = batch_model(MockSseClient::default());
+		let request = || CompletionRequest::builder("Hello").build();
+
+		assert!(matches!(
+			model.batch_request([("same", request()), ("same", request())]),
+			Err(BatchError::InvalidCustomId(id)) if id == "same"
+		));
+		assert!(matches!(
+			model.batch_request([("with space", request())]),
+			Err(BatchError::InvalidCustomId(_))
+		));
+		assert!(matches!(
+			model.batch_request([("", request())]),
+			Err(BatchError::InvalidCustomId(_))
+		));
+	}
+
+	#[tokio::test]
+	async fn test_parse_results() {
+		let succeeded = json!({
+			"custom_id": "first",
+			"result": {
+				"type": "succeeded",
+				"message": {
+					"id": "msg_01",
+					"type": "message",
+					"role": "assistant",
+					"model": "claude-sonnet-4-20250514",
+					"content": [{ "type": "text", "text": "Paris" }],
+					"stop_reason": "end_turn",
+					"stop_sequence": null,
+					"usage": { "input_tokens": 12, "output_tokens": 3 }
+				}
+			}
+		})
+		.to_string();
+		let errored = json!({
+			"custom_id": "second",
+			"result": {
+				"type": "errored",
+				"error": {
+					"type": "error",
+					"error": { "type": "invalid_request_error", "message": "max_tokens: Field required" }
+				}
+			}
+		})
+		.to_string();
+		let expired = json!({ "custom_id": "third", "result": { "type": "expired" } }).to_string();
+
+		// The first line is split across chunks
+		let (start, end) = succeeded.split_at(40);
+		let chunks = vec![
+			Ok(Bytes::from(start.to_string())),
+			Ok(Bytes::from(format!("{end}\n{errored}\n"))),
+			Ok(Bytes::from(expired)),
+		];
+
+		let results = batch_results(futures::stream::iter(chunks))
+			.collect::<Vec<_>>()
+			.await
+			.into_iter()
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+
+		assert_eq!(results.len(), 3);
+
+		assert_eq!(results[0].custom_id, "first");
+		let response = results[0].result.as_ref().unwrap();
+		assert_eq!(
+			response.choice,
+			OneOrMany::one(AssistantContent::text("Paris"))
+		);
+		assert_eq!(response.usage.input_tokens, 12);
+
+		assert_eq!(results[1].custom_id, "second");
+		match &results[1].result {
+			Err(BatchItemError::Errored(error)) => {
+				assert_eq!(error.status, http::StatusCode::BAD_REQUEST);
+				assert_eq!(error.kind, ProviderErrorKind::Other);
+				assert_eq!(error.message, "max_tokens: Field required");
+			}
+			other => panic!("expected an errored result, got {other:?}"),
+		}
+
+		assert_eq!(results[2].custom_id, "third");
+		assert!(matches!(results[2].result, Err(BatchItemError::Expired)));
+	}
+
+	#[tokio::test]
+	async fn test_create_get_and_cancel_batch() {
+		let http_client = MockSseClient::default().with_json_responses([
+			batch("in_progress"),
+			batch("in_progress"),
+			batch("canceling"),
+		]);
+		let model = batch_model(http_client.clone());
+
+		let batch = model
+			.create_batch([("first", CompletionRequest::builder("Hello").build())])
+			.await
+			.unwrap();
+		assert_eq!(batch.id, "msgbatch_01");
+		assert_eq!(batch.processing_status, ProcessingStatus::InProgress);
+
+		let batch = model.get_batch(&batch.id).await.unwrap();
+		assert_eq!(batch.request_counts.errored, 1);
+
+		let batch = model.cancel_batch(&batch.id).await.unwrap();
+		assert_eq!(batch.processing_status, ProcessingStatus::Canceling);
+
+		let uris = http_client.request_uris();
+		assert!(uris[0].ends_with("/v1/messages/batches"));
+		assert!(uris[1].ends_with("/v1/messages/batches/msgbatch_01"));
+		assert!(uris[2].ends_with("/v1/messages/batches/msgbatch_01/cancel"));
+		assert_eq!(
+			http_client.request_bodies()[0]["requests"][0]["custom_id"],
+			"first"
+		);
+	}
+}
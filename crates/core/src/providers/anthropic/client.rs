@@ -1,10 +1,11 @@
 //! Anthropic client api implementation
 use http::{HeaderName, HeaderValue};
 
+use super::batch::BatchModel;
 use super::completion::CompletionModel;
 use super::types::ANTHROPIC_VERSION_LATEST;
 use crate::client::{
-	self, ApiKey, Capabilities, Capable, DebugExt, Nothing, Provider, ProviderBuilder,
+	self, ApiKey, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider, ProviderBuilder,
 	ProviderClient,
 };
 use crate::http_client;
@@ -86,6 +87,26 @@ impl ProviderBuilder for AnthropicBuilder {
 		&self,
 		mut builder: client::ClientBuilder<Self, AnthropicKey, H>,
 	) -> http_client::Result<client::ClientBuilder<Self, AnthropicKey, H>> {
+		if !is_valid_version(&self.anthropic_version) {
+			return Err(http_client::Error::Instance(
+				format!(
+					"Invalid Anthropic API version `{}`, expected a date such as `{ANTHROPIC_VERSION_LATEST}`",
+					self.anthropic_version
+				)
+				.into(),
+			));
+		}
+
+		if let Some(beta) = self
+			.anthropic_betas
+			.iter()
+			.find(|beta| !is_valid_beta(beta))
+		{
+			return Err(http_client::Error::Instance(
+				format!("Invalid Anthropic beta `{beta}`").into(),
+			));
+		}
+
 		builder.headers_mut().insert(
 			"anthropic-version",
 			HeaderValue::from_str(&self.anthropic_version)?,
@@ -102,8 +123,39 @@ impl ProviderBuilder for AnthropicBuilder {
 	}
 }
 
+/// Versions are dates, e.g. `2023-06-01`.
+fn is_valid_version(version: &str) -> bool {
+	let bytes = version.as_bytes();
+
+	bytes.len() == 10
+		&& bytes.iter().enumerate().all(|(i, byte)| match i {
+			4 | 7 => *byte == b'-',
+			_ => byte.is_ascii_digit(),
+		})
+}
+
+/// Betas are identifiers such as `output-128k-2025-02-19`. Commas are rejected since the betas
+/// are sent as a comma separated list.
+fn is_valid_beta(beta: &str) -> bool {
+	!beta.is_empty()
+		&& beta
+			.bytes()
+			.all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.'))
+}
+
 impl DebugExt for AnthropicExt {}
 
+impl<H> Client<H>
+where
+	H: http_client::HttpClientExt + Clone,
+{
+	/// Create a model sending batches of completion requests, see [super::batch].
+	/// Use [BatchModel::new] to batch the requests of a configured [CompletionModel].
+	pub fn batch_model(&self, model: impl Into<String>) -> BatchModel<H> {
+		BatchModel::new(CompletionModel::new(self.clone(), model))
+	}
+}
+
 impl ProviderClient for Client {
 	type Input = String;
 
@@ -111,9 +163,13 @@ impl ProviderClient for Client {
 	where
 		Self: Sized,
 	{
-		let key = std::env::var("ANTHROPIC_API_KEY").expect("ANTHROPIC_API_KEY not set");
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
 
-		Self::builder().api_key(key).build().unwrap()
+	fn try_from_env() -> Result<Self, ClientError> {
+		let key = client::env_var("ANTHROPIC_API_KEY")?;
+
+		Ok(Self::builder().api_key(key).build()?)
 	}
 
 	fn from_val(input: Self::Input) -> Self
@@ -128,15 +184,20 @@ impl ProviderClient for Client {
 ///
 /// # Example
 /// ```
-/// use clankers::providers::anthropic::{ClientBuilder, self};
+/// use clankers::providers::anthropic::{self, types::ANTHROPIC_VERSION_LATEST};
 ///
 /// // Initialize the Anthropic client
-/// let anthropic_client = ClientBuilder::new("your-claude-api-key")
+/// let anthropic_client = anthropic::Client::builder()
+///    .api_key("your-claude-api-key")
 ///    .anthropic_version(ANTHROPIC_VERSION_LATEST)
-///    .anthropic_beta("prompt-caching-2024-07-31")
+///    .anthropic_beta("output-128k-2025-02-19")
+///    .anthropic_beta("files-api-2025-04-14")
 ///    .build()
+///    .unwrap();
 /// ```
 impl<H> ClientBuilder<H> {
+	/// Sets the `anthropic-version` header sent with every request.
+	/// Defaults to [ANTHROPIC_VERSION_LATEST].
 	pub fn anthropic_version(self, anthropic_version: &str) -> Self {
 		self.over_ext(|ext| AnthropicBuilder {
 			anthropic_version: anthropic_version.into(),
@@ -144,6 +205,7 @@ impl<H> ClientBuilder<H> {
 		})
 	}
 
+	/// Adds betas to the `anthropic-beta` header sent with every request.
 	pub fn anthropic_betas(self, anthropic_betas: &[&str]) -> Self {
 		self.over_ext(|mut ext| {
 			ext.anthropic_betas
@@ -153,6 +215,8 @@ impl<H> ClientBuilder<H> {
 		})
 	}
 
+	/// Adds a beta to the `anthropic-beta` header sent with every request, e.g.
+	/// `output-128k-2025-02-19`. Can be called several times.
 	pub fn anthropic_beta(self, anthropic_beta: &str) -> Self {
 		self.over_ext(|mut ext| {
 			ext.anthropic_betas.push(anthropic_beta.into());
@@ -160,4 +224,123 @@ impl<H> ClientBuilder<H> {
 			ext
 		})
 	}
+
+	/// Alias of [`ClientBuilder::anthropic_version`].
+	pub fn with_version(self, version: &str) -> Self {
+		self.anthropic_version(version)
+	}
+
+	/// Alias of [`ClientBuilder::anthropic_beta`].
+	pub fn with_beta_header(self, beta: &str) -> Self {
+		self.anthropic_beta(beta)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::StreamExt;
+
+	use super::*;
+	use crate::client::CompletionClient;
+	use crate::completion::CompletionModel as _;
+	use crate::test_utils::MockSseClient;
+
+	const SSE: &str = concat!(
+		"event: message_start\n",
+		"data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-0\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":5,\"output_tokens\":1}}}\n\n",
+		"event: message_stop\n",
+		"data: {\"type\":\"message_stop\"}\n\n",
+	);
+
+	async fn sent_headers(builder: ClientBuilder<MockSseClient>) -> Vec<http::HeaderMap> {
+		let http_client = MockSseClient::new(SSE);
+		let model = builder
+			.http_client(http_client.clone())
+			.build()
+			.unwrap()
+			.completion_model("claude-sonnet-4-0");
+
+		// The mock rejects non-streaming requests, only the sent headers matter
+		let _ = model
+			.completion_request("Hello")
+			.max_tokens(16)
+			.send()
+			.await;
+
+		let request = model.completion_request("Hello").max_tokens(16).build();
+		let mut stream = model.stream(request).await.unwrap();
+		while stream.next().await.is_some() {}
+
+		http_client.request_headers()
+	}
+
+	#[tokio::test]
+	async fn test_version_and_betas_sent_with_every_request() {
+		let headers = sent_headers(
+			Client::builder()
+				.api_key("test-key")
+				.anthropic_version("2023-01-01")
+				.anthropic_beta("output-128k-2025-02-19")
+				.anthropic_beta("files-api-2025-04-14"),
+		)
+		.await;
+
+		assert_eq!(headers.len(), 2);
+		for headers in headers {
+			assert_eq!(headers["anthropic-version"], "2023-01-01");
+			assert_eq!(
+				headers["anthropic-beta"],
+				"output-128k-2025-02-19,files-api-2025-04-14"
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_with_version_and_beta_header() {
+		let headers = sent_headers(
+			Client::builder()
+				.api_key("test-key")
+				.with_version("2023-01-01")
+				.with_beta_header("output-128k-2025-02-19")
+				.with_beta_header("files-api-2025-04-14"),
+		)
+		.await;
+
+		assert_eq!(headers.len(), 2);
+		for headers in headers {
+			assert_eq!(headers["anthropic-version"], "2023-01-01");
+			assert_eq!(
+				headers["anthropic-beta"],
+				"output-128k-2025-02-19,files-api-2025-04-14"
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_default_headers() {
+		let headers = sent_headers(Client::builder().api_key("test-key")).await;
+
+		assert_eq!(headers.len(), 2);
+		for headers in headers {
+			assert_eq!(headers["anthropic-version"], ANTHROPIC_VERSION_LATEST);
+			assert!(headers.get("anthropic-beta").is_none());
+		}
+	}
+
+	#[test]
+	fn test_rejects_malformed_values() {
+		let builder = || -> ClientBuilder { Client::builder().api_key("test-key") };
+
+		assert!(builder().anthropic_version("latest").build().is_err());
+		assert!(builder().anthropic_version("2023-6-01").build().is_err());
+		assert!(builder().anthropic_beta("").build().is_err());
+		assert!(builder().anthropic_beta("a,b").build().is_err());
+		assert!(builder().anthropic_beta("files api").build().is_err());
+		assert!(
+			builder()
+				.anthropic_beta("files-api-2025-04-14")
+				.build()
+				.is_ok()
+		);
+	}
 }
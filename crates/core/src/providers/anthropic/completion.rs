@@ -4,6 +4,7 @@ use bytes::Bytes;
 use tracing::{Instrument, Level, enabled, info_span};
 
 use super::client::Client;
+use super::error::parse_api_error;
 use super::types::{ApiErrorResponse, ApiResponse, *};
 use crate::completion::{self, CompletionError, CompletionRequest};
 use crate::http_client::HttpClientExt;
@@ -18,6 +19,8 @@ pub struct CompletionModel<T = reqwest::Client> {
 	pub default_max_tokens: Option<u64>,
 	/// Enable automatic prompt caching (adds cache_control breakpoints to system prompt and messages)
 	pub prompt_caching: bool,
+	/// Tools executed by Anthropic, sent with every request
+	pub server_tools: Vec<ServerTool>,
 }
 
 impl<T> CompletionModel<T>
@@ -33,6 +36,7 @@ where
 			model,
 			default_max_tokens,
 			prompt_caching: false, // Default to off
+			server_tools: vec![],
 		}
 	}
 
@@ -42,6 +46,7 @@ where
 			model: model.to_string(),
 			default_max_tokens: Some(calculate_max_tokens_custom(model)),
 			prompt_caching: false, // Default to off
+			server_tools: vec![],
 		}
 	}
 
@@ -56,6 +61,67 @@ where
 		self.prompt_caching = true;
 		self
 	}
+
+	/// Declare a tool executed by Anthropic, such as [ServerTool::WebSearch].
+	///
+	/// Server tool calls and their results are kept in the raw response but are not
+	/// returned as tool calls, since there is nothing for the client to execute.
+	pub fn with_server_tool(mut self, tool: ServerTool) -> Self {
+		self.server_tools.push(tool);
+		self
+	}
+}
+
+impl<T> CompletionModel<T>
+where
+	T: HttpClientExt + Clone + Default + WasmCompatSend + WasmCompatSync + 'static,
+{
+	/// Counts the input tokens of a request exactly, using Anthropic's token counting endpoint.
+	/// The preamble, documents and tool definitions are included in the count, and the
+	/// `cache_control` breakpoints are placed as they would be when sending the request.
+	///
+	/// Unlike completions, `max_tokens` does not need to be set.
+	pub async fn count_tokens(&self, request: &CompletionRequest) -> Result<u64, CompletionError> {
+		let request = CountTokensRequest::try_from(AnthropicRequestParams {
+			model: &self.model,
+			request: request.clone(),
+			prompt_caching: self.prompt_caching,
+			server_tools: &self.server_tools,
+		})?;
+
+		let req = self
+			.client
+			.post("/v1/messages/count_tokens")?
+			.body(serde_json::to_vec(&request)?)
+			.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+		let response = self
+			.client
+			.send::<_, Bytes>(req)
+			.await
+			.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+
+		let status = response.status();
+		let headers = response.headers().clone();
+		let body = response
+			.into_body()
+			.await
+			.map_err(CompletionError::HttpError)?;
+
+		if status.is_success() {
+			Ok(serde_json::from_slice::<CountTokensResponse>(&body)?.input_tokens)
+		} else {
+			Err(CompletionError::ApiError(
+				parse_api_error(status, String::from_utf8_lossy(&body).into())
+					.with_retry_after(&headers),
+			))
+		}
+	}
+
+	/// Same as [CompletionModel::count_tokens], taking the request by value.
+	pub async fn count_remote(&self, request: CompletionRequest) -> Result<u64, CompletionError> {
+		self.count_tokens(&request).await
+	}
 }
 
 /// Anthropic requires a `max_tokens` parameter to be set, which is dependent on the model. If not
@@ -107,10 +173,29 @@ where
 		Self::new(client.clone(), model.into())
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	fn validate_request(
+		&self,
+		request: &CompletionRequest,
+		report: &mut completion::ValidationReport,
+	) {
+		if let Some(temperature) = request.temperature
+			&& temperature > 1.0
+		{
+			report.error(format!(
+				"Anthropic's `temperature` must be between 0 and 1, got {temperature}"
+			));
+		}
+	}
+
 	async fn completion(
 		&self,
 		mut completion_request: completion::CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -123,6 +208,10 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -143,7 +232,9 @@ where
 			model: &self.model,
 			request: completion_request,
 			prompt_caching: self.prompt_caching,
+			server_tools: &self.server_tools,
 		})?;
+		span.record_input_messages(&request.messages);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(
@@ -166,7 +257,8 @@ where
 				.client
 				.send::<_, Bytes>(req)
 				.await
-				.map_err(CompletionError::HttpError)?;
+				.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			if response.status().is_success() {
 				match serde_json::from_slice::<ApiResponse<CompletionResponse>>(
@@ -181,6 +273,13 @@ where
 						let span = tracing::Span::current();
 						span.record_response_metadata(&completion);
 						span.record_token_usage(&completion.usage);
+						span.record_cost(
+							self.client.pricing(),
+							"anthropic",
+							&self.model,
+							&completion.usage,
+						);
+						span.record_output_messages(&completion.content);
 						if enabled!(Level::TRACE) {
 							tracing::trace!(
 								target: "clankers::completions",
@@ -188,13 +287,16 @@ where
 								serde_json::to_string_pretty(&completion)?
 							);
 						}
-						completion.try_into()
+						completion::CompletionResponse::try_from(completion)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Error(ApiErrorResponse { message }) => {
 						Err(CompletionError::ResponseError(message))
 					}
 				}
 			} else {
+				let status = response.status();
+				let headers = response.headers().clone();
 				let text: String = String::from_utf8_lossy(
 					&response
 						.into_body()
@@ -202,7 +304,9 @@ where
 						.map_err(CompletionError::HttpError)?,
 				)
 				.into();
-				Err(CompletionError::ProviderError(text))
+				Err(CompletionError::ApiError(
+					parse_api_error(status, text).with_retry_after(&headers),
+				))
 			}
 		}
 		.instrument(span)
@@ -216,12 +320,15 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let request = self.client.apply_default_params(request);
 		CompletionModel::stream(self, request).await
 	}
 }
 
 #[cfg(test)]
 mod tests {
+	use base64::Engine;
+	use base64::prelude::BASE64_STANDARD;
 	use serde_json::json;
 	use serde_path_to_error::deserialize;
 
@@ -350,7 +457,7 @@ mod tests {
 						source,
 						ImageSource {
 							data: ImageSourceData::Base64("/9j/4AAQSkZJRg...".to_owned()),
-							media_type: ImageFormat::JPEG,
+							media_type: Some(ImageFormat::JPEG),
 							r#type: SourceType::BASE64,
 						}
 					);
@@ -644,4 +751,509 @@ mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn test_deserialize_web_search_response() {
+		let response_json = r#"
+        {
+            "id": "msg_01Yf4mJqXa1Yp4yV9Hc2Zb3K",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [
+                {
+                    "type": "text",
+                    "text": "I'll search for the latest Rust release."
+                },
+                {
+                    "type": "server_tool_use",
+                    "id": "srvtoolu_01WYG3ziw53XMcoyKL4XcZmE",
+                    "name": "web_search",
+                    "input": { "query": "latest Rust release" }
+                },
+                {
+                    "type": "web_search_tool_result",
+                    "tool_use_id": "srvtoolu_01WYG3ziw53XMcoyKL4XcZmE",
+                    "content": [
+                        {
+                            "type": "web_search_result",
+                            "url": "https://blog.rust-lang.org/releases/latest/",
+                            "title": "Announcing Rust",
+                            "encrypted_content": "EqgfCioIARgBIiQ3YTAwMjY1Mi1mZjM5LTQ1NGUtODgxNC1kNjNjNTk1ZWI3Y...",
+                            "page_age": "2 weeks ago"
+                        }
+                    ]
+                },
+                {
+                    "type": "text",
+                    "text": "The latest Rust release is announced on the Rust blog.",
+                    "citations": [
+                        {
+                            "type": "web_search_result_location",
+                            "url": "https://blog.rust-lang.org/releases/latest/",
+                            "title": "Announcing Rust",
+                            "encrypted_index": "Eo8BCioIAhgBIiQyYjQ0OWJmZi1lNm..",
+                            "cited_text": "The Rust team is happy to announce a new version of Rust"
+                        }
+                    ]
+                }
+            ],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {
+                "input_tokens": 6039,
+                "output_tokens": 931,
+                "server_tool_use": { "web_search_requests": 1 }
+            }
+        }
+        "#;
+
+		let response: CompletionResponse = {
+			let jd = &mut serde_json::Deserializer::from_str(response_json);
+			deserialize(jd).unwrap_or_else(|err| {
+				panic!("Deserialization error at {}: {}", err.path(), err);
+			})
+		};
+
+		assert!(matches!(
+			&response.content[1],
+			Content::ServerToolUse { name, input, .. }
+				if name == "web_search" && input["query"] == "latest Rust release"
+		));
+		assert!(matches!(
+			&response.content[2],
+			Content::WebSearchToolResult { tool_use_id, content }
+				if tool_use_id == "srvtoolu_01WYG3ziw53XMcoyKL4XcZmE" && content.is_array()
+		));
+
+		let completion: completion::CompletionResponse<CompletionResponse> =
+			response.try_into().unwrap();
+		let texts = completion
+			.choice
+			.iter()
+			.map(|content| match content {
+				completion::AssistantContent::Text(text) => text.text.as_str(),
+				other => panic!("Unexpected content: {other:?}"),
+			})
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			texts,
+			vec![
+				"I'll search for the latest Rust release.",
+				"The latest Rust release is announced on the Rust blog."
+			]
+		);
+		assert_eq!(completion.raw_response.content.len(), 4);
+	}
+
+	#[test]
+	fn test_response_usage_includes_cache_tokens() {
+		let response: CompletionResponse = serde_json::from_value(json!({
+			"id": "msg_01",
+			"type": "message",
+			"role": "assistant",
+			"model": "claude-sonnet-4-20250514",
+			"content": [{ "type": "text", "text": "Hello!" }],
+			"stop_reason": "end_turn",
+			"stop_sequence": null,
+			"usage": {
+				"input_tokens": 12,
+				"cache_creation_input_tokens": 1500,
+				"cache_read_input_tokens": 2048,
+				"output_tokens": 42
+			}
+		}))
+		.unwrap();
+
+		let completion: completion::CompletionResponse<CompletionResponse> =
+			response.try_into().unwrap();
+		assert_eq!(completion.usage.input_tokens, 12 + 1500 + 2048);
+		assert_eq!(completion.usage.cached_input_tokens, 2048);
+		assert_eq!(completion.usage.cache_creation_input_tokens, 1500);
+		assert_eq!(completion.usage.total_tokens, 12 + 1500 + 2048 + 42);
+	}
+
+	#[test]
+	fn test_server_tools_are_sent_with_function_tools() {
+		let request = CompletionRequest::builder("What's new in Rust?")
+			.tool(completion::ToolDefinition {
+				name: "get_weather".to_string(),
+				description: "Get the weather".to_string(),
+				parameters: json!({ "type": "object" }),
+			})
+			.max_tokens(1024)
+			.build();
+		let server_tools = [
+			ServerTool::WebSearch {
+				max_uses: Some(3),
+				allowed_domains: vec!["rust-lang.org".to_string()],
+				blocked_domains: vec![],
+			},
+			ServerTool::Custom(json!({ "type": "web_fetch_20250910", "name": "web_fetch" })),
+		];
+
+		let request = AnthropicCompletionRequest::try_from(AnthropicRequestParams {
+			model: CLAUDE_4_SONNET,
+			request,
+			prompt_caching: false,
+			server_tools: &server_tools,
+		})
+		.unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["tools"],
+			json!([
+				{
+					"name": "get_weather",
+					"description": "Get the weather",
+					"input_schema": { "type": "object" }
+				},
+				{
+					"type": "web_search_20250305",
+					"name": "web_search",
+					"max_uses": 3,
+					"allowed_domains": ["rust-lang.org"]
+				},
+				{ "type": "web_fetch_20250910", "name": "web_fetch" }
+			])
+		);
+	}
+
+	#[test]
+	fn test_tool_without_description_or_parameters() {
+		let tool = ToolDefinition {
+			name: "now".to_string(),
+			description: None,
+			input_schema: json!({ "type": "object" }),
+		};
+		assert_eq!(
+			serde_json::to_value(&tool).unwrap(),
+			json!({ "name": "now", "input_schema": { "type": "object" } })
+		);
+		let tool: ToolDefinition =
+			serde_json::from_value(json!({ "name": "now", "input_schema": {} })).unwrap();
+		assert_eq!(tool.description, None);
+
+		let request = CompletionRequest::builder("What time is it?")
+			.tools(vec![
+				completion::ToolDefinition {
+					name: "now".to_string(),
+					description: String::new(),
+					parameters: json!({ "type": "object", "properties": {}, "required": [] }),
+				},
+				completion::ToolDefinition {
+					name: "uptime".to_string(),
+					description: "Get the uptime".to_string(),
+					parameters: serde_json::Value::Null,
+				},
+				completion::ToolDefinition {
+					name: "get_weather".to_string(),
+					description: "Get the weather".to_string(),
+					parameters: json!({
+						"type": "object",
+						"properties": { "city": { "type": "string" } },
+						"required": ["city"]
+					}),
+				},
+			])
+			.max_tokens(1024)
+			.build();
+
+		let request = AnthropicCompletionRequest::try_from(AnthropicRequestParams {
+			model: CLAUDE_4_SONNET,
+			request,
+			prompt_caching: false,
+			server_tools: &[],
+		})
+		.unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["tools"],
+			json!([
+				{ "name": "now", "input_schema": { "type": "object" } },
+				{
+					"name": "uptime",
+					"description": "Get the uptime",
+					"input_schema": { "type": "object" }
+				},
+				{
+					"name": "get_weather",
+					"description": "Get the weather",
+					"input_schema": {
+						"type": "object",
+						"properties": { "city": { "type": "string" } },
+						"required": ["city"]
+					}
+				}
+			])
+		);
+	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = CompletionRequest::builder("Count to ten")
+			.max_tokens(1024)
+			.stop_sequences(vec!["\n\n".to_string(), "END".to_string()])
+			.build();
+
+		let request = AnthropicCompletionRequest::try_from(AnthropicRequestParams {
+			model: CLAUDE_4_SONNET,
+			request,
+			prompt_caching: false,
+			server_tools: &[],
+		})
+		.unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["stop_sequences"],
+			json!(["\n\n", "END"])
+		);
+	}
+
+	#[test]
+	fn test_assistant_documents_are_skipped() {
+		let document = crate::message::AssistantContent::Document(crate::message::Document {
+			data: crate::message::DocumentSourceKind::String("notes".to_string()),
+			media_type: Some(crate::message::DocumentMediaType::TXT),
+			additional_params: None,
+		});
+		let request = CompletionRequest::builder("Summarize")
+			.max_tokens(1024)
+			.messages(vec![
+				crate::message::Message::Assistant {
+					id: None,
+					content: OneOrMany::many(vec![
+						crate::message::AssistantContent::text("Here are my notes"),
+						document.clone(),
+					])
+					.unwrap(),
+				},
+				crate::message::Message::Assistant {
+					id: None,
+					content: OneOrMany::one(document),
+				},
+			])
+			.build();
+
+		let request = AnthropicCompletionRequest::try_from(AnthropicRequestParams {
+			model: CLAUDE_4_SONNET,
+			request,
+			prompt_caching: false,
+			server_tools: &[],
+		})
+		.unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["messages"],
+			json!([
+				{
+					"role": "assistant",
+					"content": [{ "type": "text", "text": "Here are my notes" }]
+				},
+				{
+					"role": "user",
+					"content": [{ "type": "text", "text": "Summarize" }]
+				}
+			])
+		);
+	}
+
+	#[test]
+	fn test_raw_image_media_type_is_sniffed() {
+		let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+		let message = crate::message::Message::User {
+			content: OneOrMany::one(crate::message::UserContent::image_raw(
+				png.clone(),
+				None,
+				None,
+			)),
+		};
+
+		let converted: Message = message.try_into().unwrap();
+		assert_eq!(
+			serde_json::to_value(&converted).unwrap()["content"][0]["source"],
+			json!({
+				"type": "base64",
+				"media_type": "image/png",
+				"data": BASE64_STANDARD.encode(&png),
+			})
+		);
+	}
+
+	#[test]
+	fn test_tool_result_image_sources() {
+		let message = crate::message::Message::User {
+			content: OneOrMany::one(crate::message::UserContent::tool_result(
+				"toolu_01",
+				OneOrMany::many(vec![
+					crate::message::ToolResultContent::image_base64(
+						"iVBORw0KGgo=",
+						Some(crate::message::ImageMediaType::PNG),
+						None,
+					),
+					crate::message::ToolResultContent::image_url(
+						"https://example.com/screenshot.png",
+						Some(crate::message::ImageMediaType::PNG),
+						None,
+					),
+				])
+				.unwrap(),
+			)),
+		};
+
+		let converted: Message = message.try_into().unwrap();
+		let json = serde_json::to_value(&converted).unwrap();
+		assert_eq!(
+			json["content"][0]["content"],
+			json!([
+				{
+					"type": "image",
+					"source": {
+						"type": "base64",
+						"media_type": "image/png",
+						"data": "iVBORw0KGgo=",
+					},
+				},
+				{
+					"type": "image",
+					"source": {
+						"type": "url",
+						"url": "https://example.com/screenshot.png",
+					},
+				},
+			])
+		);
+
+		let message: crate::message::Message = serde_json::from_value::<Message>(json)
+			.unwrap()
+			.try_into()
+			.unwrap();
+		let crate::message::Message::User { content } = message else {
+			panic!("Expected user message");
+		};
+		let crate::message::UserContent::ToolResult(tool_result) = content.first() else {
+			panic!("Expected tool result");
+		};
+		let images = tool_result
+			.content
+			.into_iter()
+			.map(|content| match content {
+				crate::message::ToolResultContent::Image(image) => (image.data, image.media_type),
+				_ => panic!("Expected image content"),
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			images,
+			vec![
+				(
+					crate::message::DocumentSourceKind::Base64("iVBORw0KGgo=".to_owned()),
+					Some(crate::message::ImageMediaType::PNG),
+				),
+				(
+					crate::message::DocumentSourceKind::Url(
+						"https://example.com/screenshot.png".to_owned()
+					),
+					None,
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn test_count_tokens_request_serialization() {
+		let request = CompletionRequest::builder("Weather in Paris?")
+			.preamble("You are a weather bot.".to_string())
+			.tool(completion::ToolDefinition {
+				name: "get_weather".to_string(),
+				description: "Get the weather".to_string(),
+				parameters: json!({ "type": "object" }),
+			})
+			.temperature(0.5)
+			.stop_sequences(vec!["END".to_string()])
+			.build();
+
+		let request = CountTokensRequest::try_from(AnthropicRequestParams {
+			model: CLAUDE_4_SONNET,
+			request,
+			prompt_caching: false,
+			server_tools: &[],
+		})
+		.unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap(),
+			json!({
+				"model": CLAUDE_4_SONNET,
+				"messages": [{
+					"role": "user",
+					"content": [{ "type": "text", "text": "Weather in Paris?" }]
+				}],
+				"system": [{ "type": "text", "text": "You are a weather bot." }],
+				"tools": [{
+					"name": "get_weather",
+					"description": "Get the weather",
+					"input_schema": { "type": "object" }
+				}]
+			})
+		);
+	}
+
+	#[test]
+	fn test_count_tokens_request_with_prompt_caching() {
+		let request = CompletionRequest::builder("Weather in Paris?")
+			.preamble("You are a weather bot.".to_string())
+			.build();
+
+		let request = CountTokensRequest::try_from(AnthropicRequestParams {
+			model: CLAUDE_4_SONNET,
+			request,
+			prompt_caching: true,
+			server_tools: &[],
+		})
+		.unwrap();
+
+		let body = serde_json::to_value(&request).unwrap();
+		assert_eq!(
+			body["system"],
+			json!([{
+				"type": "text",
+				"text": "You are a weather bot.",
+				"cache_control": { "type": "ephemeral" }
+			}])
+		);
+		assert_eq!(
+			body["messages"][0]["content"][0]["cache_control"],
+			json!({ "type": "ephemeral" })
+		);
+	}
+
+	#[test]
+	fn test_count_tokens_response_deserialization() {
+		let response: CountTokensResponse =
+			serde_json::from_str(r#"{ "input_tokens": 2095 }"#).unwrap();
+		assert_eq!(response.input_tokens, 2095);
+	}
+
+	#[tokio::test]
+	async fn test_count_tokens() {
+		use crate::client::CompletionClient;
+		use crate::completion::CompletionModel as _;
+		use crate::providers::anthropic::Client;
+		use crate::test_utils::MockSseClient;
+
+		let http_client = MockSseClient::default().with_json_response(r#"{ "input_tokens": 14 }"#);
+		let model = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap()
+			.completion_model(CLAUDE_4_SONNET)
+			.with_prompt_caching();
+
+		// `max_tokens` is not required to count tokens
+		let request = model.completion_request("Hello").build();
+		assert_eq!(model.count_tokens(&request).await.unwrap(), 14);
+		assert!(http_client.request_uris()[0].ends_with("/v1/messages/count_tokens"));
+	}
 }
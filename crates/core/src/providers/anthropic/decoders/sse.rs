@@ -4,15 +4,6 @@ use bytes::Bytes;
 use futures::{Stream, StreamExt};
 use thiserror::Error;
 
-use super::line::{self, LineDecoder};
-use crate::{if_not_wasm, if_wasm};
-if_not_wasm! {
-	use futures::stream::BoxStream;
-}
-if_wasm! {
-	use std::pin::Pin;
-}
-
 #[derive(Debug, Error)]
 pub enum SSEDecoderError {
 	#[error("Failed to parse SSE: {0}")]
@@ -38,6 +29,8 @@ pub struct SSEDecoder {
 	data: Vec<String>,
 	event: Option<String>,
 	chunks: Vec<String>,
+	/// Bytes of the line being received, lines and UTF-8 characters may be split across chunks
+	buffer: Vec<u8>,
 }
 
 impl Default for SSEDecoder {
@@ -53,7 +46,56 @@ impl SSEDecoder {
 			data: Vec::new(),
 			event: None,
 			chunks: Vec::new(),
+			buffer: Vec::new(),
+		}
+	}
+
+	/// Decode a chunk of bytes, returning the events it completes.
+	///
+	/// Lines may end with `\n`, `\r\n` or `\r`, and events end at a blank line. Incomplete lines
+	/// are buffered until the rest of the line is received.
+	pub fn decode_bytes(&mut self, chunk: &[u8]) -> Vec<ServerSentEvent> {
+		self.buffer.extend_from_slice(chunk);
+
+		let mut events = Vec::new();
+		while let Some(line) = self.next_line(false) {
+			events.extend(self.decode(&line));
+		}
+		events
+	}
+
+	/// Decode the rest of the stream once it ended, returning the last event if it wasn't
+	/// terminated by a blank line.
+	pub fn flush(&mut self) -> Option<ServerSentEvent> {
+		let mut event = None;
+		while let Some(line) = self.next_line(true) {
+			event = event.or(self.decode(&line));
+		}
+		if !self.buffer.is_empty() {
+			let line = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned();
+			event = event.or(self.decode(&line));
 		}
+
+		event.or_else(|| self.decode(""))
+	}
+
+	/// Take the next complete line out of the buffer.
+	fn next_line(&mut self, end_of_stream: bool) -> Option<String> {
+		let end = self
+			.buffer
+			.iter()
+			.position(|&byte| byte == b'\n' || byte == b'\r')?;
+
+		let terminator_length = match (self.buffer[end], self.buffer.get(end + 1)) {
+			(b'\r', Some(b'\n')) => 2,
+			// A `\r` ending the buffer may be the first half of a `\r\n` split across chunks
+			(b'\r', None) if !end_of_stream => return None,
+			_ => 1,
+		};
+
+		let line = String::from_utf8_lossy(&self.buffer[..end]).into_owned();
+		self.buffer.drain(..end + terminator_length);
+		Some(line)
 	}
 
 	/// Decode a line of SSE text, returning an event if complete
@@ -129,8 +171,6 @@ where
 	S: Stream<Item = Result<Vec<u8>, std::io::Error>> + Unpin,
 {
 	let mut sse_decoder = SSEDecoder::new();
-	let mut line_decoder = LineDecoder::new();
-	let mut buffer = Vec::new();
 
 	async_stream::stream! {
 		while let Some(chunk_result) = stream.next().await {
@@ -142,79 +182,173 @@ where
 				}
 			};
 
-			// Process bytes through SSE chunking
-			buffer.extend_from_slice(&chunk);
-
-			// Extract chunks at double newlines
-			while let Some((chunk_data, remaining)) = extract_sse_chunk(&buffer) {
-				buffer = remaining;
-
-				// Process the chunk lines
-				for line in line_decoder.decode(&chunk_data) {
-					if let Some(sse) = sse_decoder.decode(&line) {
-						yield Ok(sse);
-					}
-				}
-			}
-		}
-
-		// Process any remaining data
-		for line in line_decoder.flush() {
-			if let Some(sse) = sse_decoder.decode(&line) {
+			for sse in sse_decoder.decode_bytes(&chunk) {
 				yield Ok(sse);
 			}
 		}
 
 		// Force final event if we have pending data
-		// TODO: Collapse if statement (when `||` operator is supported in if-let chains)
-		#[allow(clippy::collapsible_if)]
-		if !sse_decoder.data.is_empty() || sse_decoder.event.is_some() {
-			if let Some(sse) = sse_decoder.decode("") {
-				yield Ok(sse);
-			}
+		if let Some(sse) = sse_decoder.flush() {
+			yield Ok(sse);
 		}
 	}
 }
 
-/// Extract an SSE chunk up to a double newline
-fn extract_sse_chunk(buffer: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
-	let pattern_index = line::find_double_newline_index(buffer);
+/// Decode SSE messages from a response body.
+///
+/// Neither the stream nor its errors need to be `Send`, so this works with `fetch` bodies on wasm.
+pub fn from_response<S, E>(
+	stream: S,
+) -> impl Stream<Item = Result<ServerSentEvent, SSEDecoderError>>
+where
+	S: Stream<Item = Result<Bytes, E>> + Unpin,
+	E: std::fmt::Display,
+{
+	iter_sse_messages(stream.map(|result| match result {
+		Ok(bytes) => Ok(bytes.to_vec()),
+		Err(e) => Err(std::io::Error::other(e.to_string())),
+	}))
+}
 
-	if pattern_index <= 0 {
-		return None;
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Captured from the Anthropic API, with a ping comment and a multi-line data field added
+	const STREAM: &str = concat!(
+		"event: message_start\n",
+		"data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_01\",\"role\":\"assistant\"}}\n",
+		"\n",
+		": ping\n",
+		"\n",
+		"event: content_block_delta\n",
+		"data: {\"type\":\"content_block_delta\",\"index\":0,\n",
+		"data: \"delta\":{\"type\":\"text_delta\",\"text\":\"Привет, ça va ? 🦀\"}}\n",
+		"\n",
+		"event: message_stop\n",
+		"data: {\"type\":\"message_stop\"}\n",
+		"\n",
+	);
+
+	fn expected() -> Vec<(Option<String>, String)> {
+		vec![
+			(
+				Some("message_start".to_string()),
+				r#"{"type":"message_start","message":{"id":"msg_01","role":"assistant"}}"#
+					.to_string(),
+			),
+			(
+				Some("content_block_delta".to_string()),
+				"{\"type\":\"content_block_delta\",\"index\":0,\n\"delta\":{\"type\":\"text_delta\",\"text\":\"Привет, ça va ? 🦀\"}}".to_string(),
+			),
+			(
+				Some("message_stop".to_string()),
+				r#"{"type":"message_stop"}"#.to_string(),
+			),
+		]
 	}
 
-	let pattern_index = pattern_index as usize;
-	let chunk = buffer[0..pattern_index].to_vec();
-	let remaining = buffer[pattern_index..].to_vec();
+	fn decode_chunks(chunks: &[&[u8]]) -> Vec<(Option<String>, String)> {
+		let mut decoder = SSEDecoder::new();
+		let mut events = chunks
+			.iter()
+			.flat_map(|chunk| decoder.decode_bytes(chunk))
+			.collect::<Vec<_>>();
+		events.extend(decoder.flush());
+
+		events
+			.into_iter()
+			.map(|sse| (sse.event, sse.data))
+			.collect()
+	}
 
-	Some((chunk, remaining))
-}
+	#[test]
+	fn test_split_at_every_byte() {
+		for stream in [STREAM.to_string(), STREAM.replace('\n', "\r\n")] {
+			let bytes = stream.as_bytes();
+
+			for split in 0..=bytes.len() {
+				assert_eq!(
+					decode_chunks(&[&bytes[..split], &bytes[split..]]),
+					expected(),
+					"split at byte {split} of {stream:?}"
+				);
+			}
 
-if_wasm! {
-	pub fn from_response<'a, E>(
-		stream: Pin<Box<dyn Stream<Item = Result<Bytes, E>> + 'a>>,
-	) -> impl Stream<Item = Result<ServerSentEvent, SSEDecoderError>>
-	where
-		E: std::fmt::Display + 'static
-	{
-		iter_sse_messages(stream.map(|result| match result {
-			Ok(bytes) => Ok(bytes.to_vec()),
-			Err(e) => Err(std::io::Error::other(e.to_string())),
-		}))
+			let byte_by_byte = bytes.chunks(1).collect::<Vec<_>>();
+			assert_eq!(decode_chunks(&byte_by_byte), expected());
+		}
+	}
+
+	#[test]
+	fn test_crlf() {
+		let stream = STREAM.replace('\n', "\r\n");
+		assert_eq!(decode_chunks(&[stream.as_bytes()]), expected());
+
+		// A lone `\r` also ends a line
+		let stream = STREAM.replace('\n', "\r");
+		assert_eq!(decode_chunks(&[stream.as_bytes()]), expected());
+	}
+
+	#[test]
+	fn test_comments_are_ignored() {
+		let events = decode_chunks(&[b": ping\n\n:\n\nevent: ping\ndata: {}\n\n"]);
+
+		assert_eq!(events, vec![(Some("ping".to_string()), "{}".to_string())]);
+	}
+
+	#[test]
+	fn test_flush_unterminated_event() {
+		assert_eq!(
+			decode_chunks(&[b"event: message_stop\ndata: {}"]),
+			vec![(Some("message_stop".to_string()), "{}".to_string())]
+		);
+		assert_eq!(
+			decode_chunks(&[b"data: {}\r"]),
+			vec![(None, "{}".to_string())]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_iter_sse_messages() {
+		let bytes = STREAM.replace('\n', "\r\n").into_bytes();
+		let chunks = bytes
+			.chunks(7)
+			.map(|chunk| Ok(chunk.to_vec()))
+			.collect::<Vec<_>>();
+
+		let events = iter_sse_messages(futures::stream::iter(chunks))
+			.map(|sse| {
+				let sse = sse.unwrap();
+				(sse.event, sse.data)
+			})
+			.collect::<Vec<_>>()
+			.await;
+
+		assert_eq!(events, expected());
 	}
-}
 
-if_not_wasm! {
-	pub fn from_response<'a, E>(
-		stream: BoxStream<'a, Result<Bytes, E>>,
-	) -> impl Stream<Item = Result<ServerSentEvent, SSEDecoderError>>
-	where
-		E: Into<Box<dyn std::error::Error + Send + Sync>>
-	{
-		iter_sse_messages(stream.map(|result| match result {
-			Ok(bytes) => Ok(bytes.to_vec()),
-			Err(e) => Err(std::io::Error::other(e)),
-		}))
+	#[tokio::test]
+	async fn test_from_response_accepts_local_streams() {
+		// `Rc` is neither `Send` nor `Sync`, like the bodies and errors of wasm `fetch` responses
+		let chunks: Vec<Result<Bytes, std::rc::Rc<str>>> = vec![
+			Ok(Bytes::from_static(b"event: ping\ndata: {}\n\n")),
+			Err(std::rc::Rc::from("connection reset")),
+		];
+
+		let events = from_response(futures::stream::iter(chunks))
+			.collect::<Vec<_>>()
+			.await;
+
+		assert_eq!(events.len(), 2);
+		let sse = events[0].as_ref().unwrap();
+		assert_eq!(
+			(sse.event.as_deref(), sse.data.as_str()),
+			(Some("ping"), "{}")
+		);
+		assert!(matches!(
+			&events[1],
+			Err(SSEDecoderError::IoError(e)) if e.to_string() == "connection reset"
+		));
 	}
 }
@@ -0,0 +1,127 @@
+//! Classification of Anthropic error responses.
+use http::StatusCode;
+use serde::Deserialize;
+
+use crate::completion::{ApiError, ProviderErrorKind};
+
+/// `{ "type": "error", "error": { "type": "...", "message": "..." } }`
+#[derive(Deserialize)]
+struct ErrorBody {
+	error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+	r#type: String,
+	message: String,
+}
+
+/// Classifies an `error` event sent in the middle of a streaming response, e.g. when the API
+/// becomes overloaded after the response started. These have the same body as error responses,
+/// but no status code, so it is derived from the error type.
+pub fn parse_stream_error(body: String) -> ApiError {
+	let status = match serde_json::from_str::<ErrorBody>(&body) {
+		Ok(ErrorBody { error }) => match error.r#type.as_str() {
+			"invalid_request_error" => StatusCode::BAD_REQUEST,
+			"authentication_error" => StatusCode::UNAUTHORIZED,
+			"permission_error" => StatusCode::FORBIDDEN,
+			"not_found_error" => StatusCode::NOT_FOUND,
+			"request_too_large" => StatusCode::PAYLOAD_TOO_LARGE,
+			"rate_limit_error" => StatusCode::TOO_MANY_REQUESTS,
+			"overloaded_error" => StatusCode::from_u16(529).expect("529 is a valid status code"),
+			_ => StatusCode::INTERNAL_SERVER_ERROR,
+		},
+		Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+	};
+
+	parse_api_error(status, body)
+}
+
+/// Classifies an Anthropic error response from its status and body.
+///
+/// Anthropic only reports retry delays in the `Retry-After` header, see
+/// [ApiError::with_retry_after].
+pub fn parse_api_error(status: StatusCode, body: String) -> ApiError {
+	let mut error = ApiError::new(status, body);
+	let Ok(ErrorBody { error: detail }) = serde_json::from_str(&error.body) else {
+		return error;
+	};
+
+	error.kind = match detail.r#type.as_str() {
+		"authentication_error" | "permission_error" => ProviderErrorKind::AuthenticationFailed,
+		"not_found_error" => ProviderErrorKind::NotFound,
+		"rate_limit_error" => ProviderErrorKind::RateLimited { retry_after: None },
+		"overloaded_error" => ProviderErrorKind::Overloaded,
+		"invalid_request_error" if detail.message.starts_with("prompt is too long") => {
+			ProviderErrorKind::ContextLengthExceeded
+		}
+		_ => error.kind,
+	};
+	error.message = detail.message;
+	error
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rate_limit() {
+		let body = r#"{"type":"error","error":{"type":"rate_limit_error","message":"This request would exceed the rate limit for your organization of 50,000 input tokens per minute."},"request_id":"req_011CSHoEeqs5C35K2UUqR7Fy"}"#;
+
+		let error = parse_api_error(StatusCode::TOO_MANY_REQUESTS, body.to_string());
+		assert_eq!(
+			error.kind,
+			ProviderErrorKind::RateLimited { retry_after: None }
+		);
+		assert!(error.message.starts_with("This request would exceed"));
+	}
+
+	#[test]
+	fn test_overloaded() {
+		let body = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+
+		let error = parse_api_error(StatusCode::from_u16(529).unwrap(), body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::Overloaded);
+	}
+
+	#[test]
+	fn test_stream_error() {
+		let body = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+
+		let error = parse_stream_error(body.to_string());
+		assert_eq!(error.status.as_u16(), 529);
+		assert_eq!(error.kind, ProviderErrorKind::Overloaded);
+		assert_eq!(error.message, "Overloaded");
+
+		let error = parse_stream_error("not json".to_string());
+		assert_eq!(error.status, StatusCode::INTERNAL_SERVER_ERROR);
+		assert_eq!(error.message, "not json");
+	}
+
+	#[test]
+	fn test_authentication() {
+		let body = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+
+		let error = parse_api_error(StatusCode::UNAUTHORIZED, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::AuthenticationFailed);
+	}
+
+	#[test]
+	fn test_prompt_too_long() {
+		let body = r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 215321 tokens > 200000 maximum"}}"#;
+
+		let error = parse_api_error(StatusCode::BAD_REQUEST, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::ContextLengthExceeded);
+	}
+
+	#[test]
+	fn test_not_found() {
+		let body =
+			r#"{"type":"error","error":{"type":"not_found_error","message":"model: claude-9"}}"#;
+
+		let error = parse_api_error(StatusCode::NOT_FOUND, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::NotFound);
+		assert_eq!(error.message, "model: claude-9");
+	}
+}
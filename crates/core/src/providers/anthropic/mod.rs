@@ -9,9 +9,11 @@
 //! let sonnet = client.completion_model(anthropic::CLAUDE_3_5_SONNET);
 //! ```
 
+pub mod batch;
 pub mod client;
 pub mod completion;
 pub mod decoders;
+pub mod error;
 pub mod streaming;
 pub mod types;
 
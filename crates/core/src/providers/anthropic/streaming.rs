@@ -6,8 +6,10 @@ use tracing::{Level, enabled, info_span};
 use tracing_futures::Instrument;
 
 use super::completion::CompletionModel;
+use super::error::{parse_api_error, parse_stream_error};
 use super::types::{
-	Content, Message, SystemContent, ToolChoice, ToolDefinition, Usage, apply_cache_control,
+	Content, StopReason, SystemContent, ToolChoice, Usage, apply_cache_control,
+	history_to_messages, request_tools,
 };
 use crate::completion::{CompletionError, CompletionRequest, GetTokenUsage};
 use crate::http_client::sse::{Event, GenericEventSource};
@@ -41,10 +43,21 @@ pub enum StreamingEvent {
 	},
 	MessageStop,
 	Ping,
+	/// An error sent in the middle of the response, after which the stream ends
+	Error {
+		error: StreamError,
+	},
 	#[serde(other)]
 	Unknown,
 }
 
+/// The error of an `error` event, e.g. `overloaded_error`
+#[derive(Debug, Deserialize)]
+pub struct StreamError {
+	pub r#type: String,
+	pub message: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MessageStart {
 	pub id: String,
@@ -67,7 +80,7 @@ pub enum ContentDelta {
 
 #[derive(Debug, Deserialize)]
 pub struct MessageDelta {
-	pub stop_reason: Option<String>,
+	pub stop_reason: Option<StopReason>,
 	pub stop_sequence: Option<String>,
 }
 
@@ -76,15 +89,54 @@ pub struct PartialUsage {
 	pub output_tokens: usize,
 	#[serde(default)]
 	pub input_tokens: Option<usize>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cache_read_input_tokens: Option<usize>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cache_creation_input_tokens: Option<usize>,
+}
+
+impl PartialUsage {
+	/// Combines the usage of a `message_start` event with the cumulative usage of a
+	/// `message_delta` event, which only sometimes repeats the input counts.
+	fn merge_delta(&self, delta: &PartialUsage) -> PartialUsage {
+		PartialUsage {
+			output_tokens: delta.output_tokens,
+			input_tokens: delta.input_tokens.or(self.input_tokens),
+			cache_read_input_tokens: delta
+				.cache_read_input_tokens
+				.or(self.cache_read_input_tokens),
+			cache_creation_input_tokens: delta
+				.cache_creation_input_tokens
+				.or(self.cache_creation_input_tokens),
+		}
+	}
+}
+
+impl From<&Usage> for PartialUsage {
+	fn from(usage: &Usage) -> Self {
+		PartialUsage {
+			output_tokens: usage.output_tokens as usize,
+			input_tokens: Some(usage.input_tokens as usize),
+			cache_read_input_tokens: usage.cache_read_input_tokens.map(|tokens| tokens as usize),
+			cache_creation_input_tokens: usage
+				.cache_creation_input_tokens
+				.map(|tokens| tokens as usize),
+		}
+	}
 }
 
 impl GetTokenUsage for PartialUsage {
 	fn token_usage(&self) -> Option<crate::completion::Usage> {
 		let mut usage = crate::completion::Usage::new();
 
-		usage.input_tokens = self.input_tokens.unwrap_or_default() as u64;
+		let cache_read = self.cache_read_input_tokens.unwrap_or_default() as u64;
+		let cache_creation = self.cache_creation_input_tokens.unwrap_or_default() as u64;
+		usage.input_tokens =
+			self.input_tokens.unwrap_or_default() as u64 + cache_creation + cache_read;
 		usage.output_tokens = self.output_tokens as u64;
 		usage.total_tokens = usage.input_tokens + usage.output_tokens;
+		usage.cached_input_tokens = cache_read;
+		usage.cache_creation_input_tokens = cache_creation;
 		Some(usage)
 	}
 }
@@ -106,17 +158,14 @@ struct ThinkingState {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StreamingCompletionResponse {
 	pub usage: PartialUsage,
+	/// `None` if the stream ended before the model stopped, e.g. because of an error
+	#[serde(default)]
+	pub stop_reason: Option<StopReason>,
 }
 
 impl GetTokenUsage for StreamingCompletionResponse {
 	fn token_usage(&self) -> Option<crate::completion::Usage> {
-		let mut usage = crate::completion::Usage::new();
-		usage.input_tokens = self.usage.input_tokens.unwrap_or(0) as u64;
-		usage.output_tokens = self.usage.output_tokens as u64;
-		usage.total_tokens =
-			self.usage.input_tokens.unwrap_or(0) as u64 + self.usage.output_tokens as u64;
-
-		Some(usage)
+		self.usage.token_usage()
 	}
 }
 
@@ -141,6 +190,8 @@ where
 				gen_ai.response.model = self.model,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
 				gen_ai.input.messages = tracing::field::Empty,
 				gen_ai.output.messages = tracing::field::Empty,
 			)
@@ -163,10 +214,7 @@ where
 		}
 		full_history.extend(completion_request.chat_history);
 
-		let mut messages = full_history
-			.into_iter()
-			.map(Message::try_from)
-			.collect::<Result<Vec<Message>, _>>()?;
+		let mut messages = history_to_messages(full_history)?;
 
 		// Convert system prompt to array format for cache_control support
 		let mut system: Vec<SystemContent> =
@@ -187,6 +235,7 @@ where
 		if self.prompt_caching {
 			apply_cache_control(&mut system, &mut messages);
 		}
+		span.record_input_messages(&messages);
 
 		let mut body = json!({
 			"model": self.model,
@@ -204,19 +253,19 @@ where
 			merge_inplace(&mut body, json!({ "temperature": temperature }));
 		}
 
-		if !completion_request.tools.is_empty() {
+		if !completion_request.stop_sequences.is_empty() {
+			merge_inplace(
+				&mut body,
+				json!({ "stop_sequences": completion_request.stop_sequences }),
+			);
+		}
+
+		let tools = request_tools(completion_request.tools, &self.server_tools);
+		if !tools.is_empty() {
 			merge_inplace(
 				&mut body,
 				json!({
-					"tools": completion_request
-						.tools
-						.into_iter()
-						.map(|tool| ToolDefinition {
-							name: tool.name,
-							description: Some(tool.description),
-							input_schema: tool.parameters,
-						})
-						.collect::<Vec<_>>(),
+					"tools": tools,
 					"tool_choice": ToolChoice::Auto,
 				}),
 			);
@@ -243,78 +292,120 @@ where
 			.map_err(http_client::Error::Protocol)?;
 
 		let stream = GenericEventSource::new(self.client.clone(), req);
+		let cost = self.client.cost_recorder("anthropic", &self.model);
 
 		// Use our SSE decoder to directly handle Server-Sent Events format
-		let stream: StreamingResult<StreamingCompletionResponse> = Box::pin(stream! {
-            let mut current_tool_call: Option<ToolCallState> = None;
-            let mut current_thinking: Option<ThinkingState> = None;
-            let mut sse_stream = Box::pin(stream);
-            let mut input_tokens = 0;
-            let mut final_usage = None;
-
-            let mut text_content = String::new();
-
-            while let Some(sse_result) = sse_stream.next().await {
-                match sse_result {
-                    Ok(Event::Open) => {}
-                    Ok(Event::Message(sse)) => {
-                        // Parse the SSE data as a StreamingEvent
-                        match serde_json::from_str::<StreamingEvent>(&sse.data) {
-                            Ok(event) => {
-                                match &event {
-                                    StreamingEvent::MessageStart { message } => {
-                                        input_tokens = message.usage.input_tokens;
-
-                                        let span = tracing::Span::current();
-                                        span.record("gen_ai.response.id", &message.id);
-                                        span.record("gen_ai.response.model_name", &message.model);
-                                    },
-                                    StreamingEvent::MessageDelta { delta, usage } => {
-                                        if delta.stop_reason.is_some() {
-                                            let usage = PartialUsage {
-                                                 output_tokens: usage.output_tokens,
-                                                 input_tokens: Some(input_tokens.try_into().expect("Failed to convert input_tokens to usize")),
-                                            };
-
-                                            let span = tracing::Span::current();
-                                            span.record_token_usage(&usage);
-                                            final_usage = Some(usage);
-                                            break;
-                                        }
-                                    }
-                                    _ => {}
-                                }
-
-                                if let Some(result) = handle_event(&event, &mut current_tool_call, &mut current_thinking) {
-                                    if let Ok(RawStreamingChoice::Message(ref text)) = result {
-                                        text_content += text;
-                                    }
-                                    yield result;
-                                }
-                            },
-                            Err(e) => {
-                                if !sse.data.trim().is_empty() {
-                                    yield Err(CompletionError::ResponseError(
-                                        format!("Failed to parse JSON: {} (Data: {})", e, sse.data)
-                                    ));
-                                }
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        yield Err(CompletionError::ProviderError(format!("SSE Error: {e}")));
-                        break;
-                    }
-                }
-            }
+		let stream: StreamingResult<StreamingCompletionResponse> = Box::pin(
+			stream! {
+				let mut current_tool_call: Option<ToolCallState> = None;
+				let mut current_thinking: Option<ThinkingState> = None;
+				let mut sse_stream = Box::pin(stream);
+				let mut input_usage = PartialUsage::default();
+				let mut final_usage = None;
+				let mut stop_reason = None;
+
+				let mut text_content = String::new();
+				let mut tool_uses = Vec::new();
+
+				while let Some(sse_result) = sse_stream.next().await {
+					match sse_result {
+						Ok(Event::Open) => {
+							if let Some(metadata) = sse_stream.response_metadata() {
+								yield Ok(RawStreamingChoice::ResponseMetadata(metadata.clone()));
+							}
+						}
+						Ok(Event::Message(sse)) => {
+							// Parse the SSE data as a StreamingEvent
+							match serde_json::from_str::<StreamingEvent>(&sse.data) {
+								Ok(event) => {
+									match &event {
+										StreamingEvent::MessageStart { message } => {
+											input_usage = PartialUsage::from(&message.usage);
+
+											let span = tracing::Span::current();
+											span.record("gen_ai.response.id", &message.id);
+											span.record("gen_ai.response.model_name", &message.model);
+										},
+										StreamingEvent::MessageDelta { delta, usage } => {
+											if delta.stop_reason.is_some() {
+												let usage = input_usage.merge_delta(usage);
+
+												let span = tracing::Span::current();
+												span.record_token_usage(&usage);
+												cost.record(&span, &usage);
+												final_usage = Some(usage);
+												stop_reason = delta.stop_reason;
+												break;
+											}
+										}
+										StreamingEvent::Error { .. } => {
+											// No `message_delta` follows, so only the usage reported by
+											// `message_start` is known
+											let span = tracing::Span::current();
+											span.record_token_usage(&input_usage);
+											cost.record(&span, &input_usage);
+											final_usage = Some(input_usage.clone());
+
+											yield Err(CompletionError::ApiError(parse_stream_error(sse.data.clone())));
+											break;
+										}
+										_ => {}
+									}
+
+									if let Some(result) = handle_event(&event, &mut current_tool_call, &mut current_thinking) {
+										match &result {
+											Ok(RawStreamingChoice::Message(text)) => text_content += text,
+											Ok(RawStreamingChoice::ToolCall(tool_call)) => tool_uses.push(Content::ToolUse {
+												id: tool_call.id.clone(),
+												name: tool_call.name.clone(),
+												input: tool_call.arguments.clone(),
+											}),
+											_ => {}
+										}
+										yield result;
+									}
+								},
+								Err(e) => {
+									if !sse.data.trim().is_empty() {
+										yield Err(CompletionError::ResponseError(
+											format!("Failed to parse JSON: {} (Data: {})", e, sse.data)
+										));
+									}
+								}
+							}
+						},
+						Err(http_client::Error::InvalidStatusCodeWithMessage(status, body)) => {
+							yield Err(CompletionError::ApiError(parse_api_error(status, body)));
+							break;
+						}
+						Err(http_client::Error::StreamStalled(timeout)) => {
+							yield Err(CompletionError::StreamStalled(timeout));
+							break;
+						}
+						Err(e) => {
+							yield Err(CompletionError::ProviderError(format!("SSE Error: {e}")));
+							break;
+						}
+					}
+				}
+
+				// Ensure event source is closed when stream ends
+				sse_stream.close();
 
-            // Ensure event source is closed when stream ends
-            sse_stream.close();
+				let mut output = Vec::new();
+				if !text_content.is_empty() {
+					output.push(Content::Text { text: text_content, cache_control: None });
+				}
+				output.extend(tool_uses);
+				tracing::Span::current().record_output_messages(&output);
 
-            yield Ok(RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
-                usage: final_usage.unwrap_or_default()
-            }))
-        }.instrument(span));
+				yield Ok(RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
+					usage: final_usage.unwrap_or_default(),
+					stop_reason,
+				}))
+			}
+			.instrument(span),
+		);
 
 		Ok(streaming::StreamingCompletionResponse::stream(stream))
 	}
@@ -435,6 +526,7 @@ fn handle_event(
 		| StreamingEvent::MessageDelta { .. }
 		| StreamingEvent::MessageStop
 		| StreamingEvent::Ping
+		| StreamingEvent::Error { .. }
 		| StreamingEvent::Unknown => None,
 	}
 }
@@ -443,6 +535,70 @@ fn handle_event(
 mod tests {
 	use super::*;
 
+	#[test]
+	fn test_streaming_usage_with_prompt_caching() {
+		let start: StreamingEvent = serde_json::from_str(
+			r#"{
+                "type": "message_start",
+                "message": {
+                    "id": "msg_01",
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [],
+                    "model": "claude-sonnet-4-5",
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": {
+                        "input_tokens": 12,
+                        "cache_creation_input_tokens": 100,
+                        "cache_read_input_tokens": 2048,
+                        "output_tokens": 1
+                    }
+                }
+            }"#,
+		)
+		.unwrap();
+		let delta: StreamingEvent = serde_json::from_str(
+			r#"{
+                "type": "message_delta",
+                "delta": { "stop_reason": "end_turn", "stop_sequence": null },
+                "usage": { "output_tokens": 42 }
+            }"#,
+		)
+		.unwrap();
+
+		let (
+			StreamingEvent::MessageStart { message },
+			StreamingEvent::MessageDelta { usage: delta, .. },
+		) = (start, delta)
+		else {
+			panic!("unexpected events");
+		};
+
+		let response = StreamingCompletionResponse {
+			usage: PartialUsage::from(&message.usage).merge_delta(&delta),
+			stop_reason: None,
+		};
+		let usage = response.token_usage().unwrap();
+		assert_eq!(usage.input_tokens, 12 + 100 + 2048);
+		assert_eq!(usage.cached_input_tokens, 2048);
+		assert_eq!(usage.cache_creation_input_tokens, 100);
+		assert_eq!(usage.output_tokens, 42);
+		assert_eq!(usage.total_tokens, 12 + 100 + 2048 + 42);
+
+		// Cumulative counts in message_delta take precedence
+		let delta: PartialUsage = serde_json::from_str(
+			r#"{ "input_tokens": 12, "cache_read_input_tokens": 4096, "output_tokens": 50 }"#,
+		)
+		.unwrap();
+		let usage = PartialUsage::from(&message.usage)
+			.merge_delta(&delta)
+			.token_usage()
+			.unwrap();
+		assert_eq!(usage.cached_input_tokens, 4096);
+		assert_eq!(usage.input_tokens, 12 + 100 + 4096);
+	}
+
 	#[test]
 	fn test_thinking_delta_deserialization() {
 		let json = r#"{"type": "thinking_delta", "thinking": "Let me think about this..."}"#;
@@ -749,4 +905,238 @@ mod tests {
 		// Tool call state should be taken
 		assert!(tool_call_state.is_none());
 	}
+
+	#[tokio::test]
+	async fn test_response_metadata() {
+		use crate::client::CompletionClient;
+		use crate::completion::CompletionModel as _;
+		use crate::providers::anthropic::Client;
+		use crate::test_utils::MockSseClient;
+
+		let sse = concat!(
+			"event: message_start\n",
+			"data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-0\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":5,\"output_tokens\":1}}}\n\n",
+			"event: message_stop\n",
+			"data: {\"type\":\"message_stop\"}\n\n",
+		);
+		let http_client = MockSseClient::new(sse)
+			.with_response_header("request-id", "req_011CSHoEeqs5C35K2UUqR7Fy")
+			.with_response_header("anthropic-ratelimit-tokens-remaining", "79000");
+
+		let model = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.capture_response_headers(true)
+			.build()
+			.unwrap()
+			.completion_model("claude-sonnet-4-0");
+		let request = model.completion_request("Hello").max_tokens(16).build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let final_response = stream.final_response().await.unwrap();
+		let metadata = final_response.response_metadata.unwrap();
+		assert_eq!(
+			metadata.request_id.as_deref(),
+			Some("req_011CSHoEeqs5C35K2UUqR7Fy")
+		);
+		assert_eq!(
+			metadata.header("anthropic-ratelimit-tokens-remaining"),
+			Some("79000")
+		);
+	}
+
+	#[tokio::test]
+	async fn test_stalled_stream() {
+		use std::time::Duration;
+
+		use crate::client::CompletionClient;
+		use crate::completion::CompletionModel as _;
+		use crate::providers::anthropic::Client;
+		use crate::streaming::StreamedAssistantContent;
+		use crate::test_utils::MockSseClient;
+
+		let ms = Duration::from_millis;
+		let http_client = MockSseClient::paused([
+			(
+				ms(0),
+				concat!(
+					"event: message_start\n",
+					"data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-0\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+				),
+			),
+			// Pings keep the stream alive without being surfaced
+			(ms(120), "event: ping\ndata: {\"type\": \"ping\"}\n\n"),
+			(
+				ms(120),
+				concat!(
+					"event: content_block_start\n",
+					"data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+					"event: content_block_delta\n",
+					"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+				),
+			),
+			(
+				ms(2000),
+				"event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+			),
+		]);
+
+		let model = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.stream_stall_timeout(ms(200))
+			.build()
+			.unwrap()
+			.completion_model("claude-sonnet-4-0");
+		let request = model.completion_request("Hello").max_tokens(16).build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let mut items = Vec::new();
+		while let Some(item) = stream.next().await {
+			items.push(item);
+		}
+
+		assert!(
+			matches!(&items[0], Ok(StreamedAssistantContent::Text(text)) if text.text == "Hello"),
+			"{items:?}"
+		);
+		assert!(
+			matches!(items[1], Err(CompletionError::StreamStalled(timeout)) if timeout == ms(200)),
+			"{items:?}"
+		);
+		// Only the final response follows, message_stop is never read
+		assert!(
+			matches!(items[2..], [Ok(StreamedAssistantContent::Final(_))]),
+			"{items:?}"
+		);
+	}
+
+	fn streaming_model(sse: &'static str) -> CompletionModel<crate::test_utils::MockSseClient> {
+		use crate::client::CompletionClient;
+		use crate::providers::anthropic::Client;
+		use crate::test_utils::MockSseClient;
+
+		Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(MockSseClient::new(sse))
+			.build()
+			.unwrap()
+			.completion_model("claude-sonnet-4-0")
+	}
+
+	#[tokio::test]
+	async fn test_character_split_across_chunks() {
+		use crate::client::CompletionClient;
+		use crate::completion::CompletionModel as _;
+		use crate::providers::anthropic::Client;
+		use crate::streaming::StreamedAssistantContent;
+		use crate::test_utils::MockSseClient;
+
+		let sse = concat!(
+			"event: message_start\n",
+			"data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-0\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+			"event: content_block_start\n",
+			"data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+			"event: content_block_delta\n",
+			"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Crabs 🦀 rock\"}}\n\n",
+			"event: content_block_stop\n",
+			"data: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+			"event: message_delta\n",
+			"data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":4}}\n\n",
+			"event: message_stop\n",
+			"data: {\"type\":\"message_stop\"}\n\n",
+		);
+		let model = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(MockSseClient::split_in_char(sse, '🦀'))
+			.build()
+			.unwrap()
+			.completion_model("claude-sonnet-4-0");
+		let request = model.completion_request("Hello").max_tokens(16).build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let mut text = String::new();
+		while let Some(item) = stream.next().await {
+			if let StreamedAssistantContent::Text(delta) = item.unwrap() {
+				text += &delta.text;
+			}
+		}
+		assert_eq!(text, "Crabs 🦀 rock");
+	}
+
+	#[tokio::test]
+	async fn test_error_event_ends_stream() {
+		use crate::completion::{CompletionModel as _, ProviderErrorKind};
+		use crate::streaming::StreamedAssistantContent;
+
+		let sse = concat!(
+			"event: message_start\n",
+			"data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-0\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+			"event: content_block_start\n",
+			"data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+			"event: content_block_delta\n",
+			"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+			"event: error\n",
+			"data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+		);
+		let model = streaming_model(sse);
+		let request = model.completion_request("Hello").max_tokens(16).build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let mut items = vec![];
+		while let Some(item) = stream.next().await {
+			items.push(item);
+		}
+
+		assert!(matches!(
+			&items[0],
+			Ok(StreamedAssistantContent::Text(text)) if text.text == "Hello"
+		));
+		match &items[1] {
+			Err(CompletionError::ApiError(error)) => {
+				assert_eq!(error.kind, ProviderErrorKind::Overloaded);
+				assert_eq!(error.message, "Overloaded");
+			}
+			other => panic!("Expected an ApiError, got {other:?}"),
+		}
+		assert!(items[2..].iter().all(|item| item.is_ok()));
+
+		let response = stream.response.unwrap();
+		assert_eq!(response.stop_reason, None);
+		assert_eq!(response.token_usage().unwrap().input_tokens, 25);
+	}
+
+	#[tokio::test]
+	async fn test_max_tokens_stop_reason() {
+		use crate::completion::CompletionModel as _;
+
+		let sse = concat!(
+			"event: message_start\n",
+			"data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"claude-sonnet-4-0\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+			"event: content_block_start\n",
+			"data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+			"event: content_block_delta\n",
+			"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Once upon a\"}}\n\n",
+			"event: content_block_stop\n",
+			"data: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+			"event: message_delta\n",
+			"data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"max_tokens\",\"stop_sequence\":null},\"usage\":{\"output_tokens\":16}}\n\n",
+			"event: message_stop\n",
+			"data: {\"type\":\"message_stop\"}\n\n",
+		);
+		let model = streaming_model(sse);
+		let request = model
+			.completion_request("Tell me a story")
+			.max_tokens(16)
+			.build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let final_response = stream.final_response().await.unwrap();
+		assert_eq!(final_response.text, "Once upon a");
+
+		let response = final_response.response.unwrap();
+		assert_eq!(response.stop_reason, Some(StopReason::MaxTokens));
+		assert_eq!(response.stop_reason.unwrap().normalized(), "length");
+		assert_eq!(response.token_usage().unwrap().output_tokens, 16);
+	}
 }
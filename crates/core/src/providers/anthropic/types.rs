@@ -1,6 +1,8 @@
 use std::convert::Infallible;
 use std::str::FromStr;
 
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use serde::{Deserialize, Serialize};
 
 use crate::OneOrMany;
@@ -35,6 +37,38 @@ pub struct CompletionResponse {
 	pub usage: Usage,
 }
 
+/// Why the model stopped generating.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+	EndTurn,
+	MaxTokens,
+	StopSequence,
+	ToolUse,
+	/// A long-running turn was paused, and can be continued by sending the response back as is
+	PauseTurn,
+	/// The output was blocked by the model's safety classifiers
+	Refusal,
+	/// The model's context window was exceeded
+	ModelContextWindowExceeded,
+	#[serde(other)]
+	Unknown,
+}
+
+impl StopReason {
+	/// The stop reason as one of the finish reasons shared by most providers:
+	/// `stop`, `tool_calls`, `length`, `content_filter` or `unknown`.
+	pub fn normalized(&self) -> &'static str {
+		match self {
+			Self::EndTurn | Self::StopSequence | Self::PauseTurn => "stop",
+			Self::ToolUse => "tool_calls",
+			Self::MaxTokens | Self::ModelContextWindowExceeded => "length",
+			Self::Refusal => "content_filter",
+			Self::Unknown => "unknown",
+		}
+	}
+}
+
 impl ProviderResponseExt for CompletionResponse {
 	type OutputMessage = Content;
 	type Usage = Usage;
@@ -109,6 +143,8 @@ impl GetTokenUsage for Usage {
 			+ self.cache_read_input_tokens.unwrap_or_default();
 		usage.output_tokens = self.output_tokens;
 		usage.total_tokens = usage.input_tokens + usage.output_tokens;
+		usage.cached_input_tokens = self.cache_read_input_tokens.unwrap_or_default();
+		usage.cache_creation_input_tokens = self.cache_creation_input_tokens.unwrap_or_default();
 
 		Some(usage)
 	}
@@ -117,10 +153,126 @@ impl GetTokenUsage for Usage {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ToolDefinition {
 	pub name: String,
+	/// Skipped when `None`, Anthropic rejects a null description
+	#[serde(default, skip_serializing_if = "Option::is_none")]
 	pub description: Option<String>,
 	pub input_schema: serde_json::Value,
 }
 
+/// The input schema of a tool, a tool without parameters being sent as `{"type": "object"}`
+/// instead of a null schema or an object with empty `properties` and `required`.
+fn input_schema(parameters: serde_json::Value) -> serde_json::Value {
+	let serde_json::Value::Object(mut schema) = parameters else {
+		return serde_json::json!({ "type": "object" });
+	};
+
+	for key in ["properties", "required"] {
+		let is_empty = match schema.get(key) {
+			Some(serde_json::Value::Object(map)) => map.is_empty(),
+			Some(serde_json::Value::Array(array)) => array.is_empty(),
+			_ => false,
+		};
+		if is_empty {
+			schema.remove(key);
+		}
+	}
+	schema
+		.entry("type")
+		.or_insert_with(|| serde_json::Value::from("object"));
+
+	serde_json::Value::Object(schema)
+}
+
+/// A tool executed by Anthropic on the server side.
+///
+/// Server tools are declared on the model with
+/// [`CompletionModel::with_server_tool`](super::completion::CompletionModel::with_server_tool)
+/// and are sent alongside regular tools. Their calls and results are not surfaced as
+/// tool calls in the core response, only in the raw provider response.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerTool {
+	/// The `web_search` tool
+	WebSearch {
+		/// Maximum number of searches per request
+		max_uses: Option<u32>,
+		/// Only include results from these domains
+		allowed_domains: Vec<String>,
+		/// Never include results from these domains
+		blocked_domains: Vec<String>,
+	},
+	/// Any other server tool, sent verbatim (e.g. `{"type": "web_fetch_20250910", "name": "web_fetch"}`)
+	Custom(serde_json::Value),
+}
+
+impl ServerTool {
+	/// The `web_search` tool without any restrictions
+	pub fn web_search() -> Self {
+		ServerTool::WebSearch {
+			max_uses: None,
+			allowed_domains: vec![],
+			blocked_domains: vec![],
+		}
+	}
+}
+
+impl Serialize for ServerTool {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		use serde::ser::SerializeMap;
+
+		match self {
+			ServerTool::WebSearch {
+				max_uses,
+				allowed_domains,
+				blocked_domains,
+			} => {
+				let mut map = serializer.serialize_map(None)?;
+				map.serialize_entry("type", "web_search_20250305")?;
+				map.serialize_entry("name", "web_search")?;
+				if let Some(max_uses) = max_uses {
+					map.serialize_entry("max_uses", max_uses)?;
+				}
+				if !allowed_domains.is_empty() {
+					map.serialize_entry("allowed_domains", allowed_domains)?;
+				}
+				if !blocked_domains.is_empty() {
+					map.serialize_entry("blocked_domains", blocked_domains)?;
+				}
+				map.end()
+			}
+			ServerTool::Custom(value) => value.serialize(serializer),
+		}
+	}
+}
+
+/// Entry of the `tools` array of a request
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RequestTool {
+	Function(ToolDefinition),
+	Server(ServerTool),
+}
+
+/// Merges the request's function tools with the model's server tools
+pub(crate) fn request_tools(
+	tools: Vec<completion::ToolDefinition>,
+	server_tools: &[ServerTool],
+) -> Vec<RequestTool> {
+	tools
+		.into_iter()
+		.map(|tool| {
+			RequestTool::Function(ToolDefinition {
+				name: tool.name,
+				description: Some(tool.description).filter(|description| !description.is_empty()),
+				input_schema: input_schema(tool.parameters),
+			})
+		})
+		.chain(server_tools.iter().cloned().map(RequestTool::Server))
+		.collect()
+}
+
 /// Cache control directive for Anthropic prompt caching
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -143,9 +295,12 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 	type Error = CompletionError;
 
 	fn try_from(response: CompletionResponse) -> Result<Self, Self::Error> {
+		// Server tool calls and their results are handled by Anthropic, the model's answer
+		// to them follows as regular text blocks. They remain available in `raw_response`.
 		let content = response
 			.content
 			.iter()
+			.filter(|content| !content.is_server_tool())
 			.map(|content| content.clone().try_into())
 			.collect::<Result<Vec<_>, _>>()?;
 
@@ -155,17 +310,13 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			)
 		})?;
 
-		let usage = completion::Usage {
-			input_tokens: response.usage.input_tokens,
-			output_tokens: response.usage.output_tokens,
-			total_tokens: response.usage.input_tokens + response.usage.output_tokens,
-			cached_input_tokens: response.usage.cache_read_input_tokens.unwrap_or(0),
-		};
+		let usage = response.usage.token_usage().unwrap_or_default();
 
 		Ok(completion::CompletionResponse {
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -221,6 +372,28 @@ pub enum Content {
 		#[serde(skip_serializing_if = "Option::is_none")]
 		signature: Option<String>,
 	},
+	/// A call to a tool executed by Anthropic, see [ServerTool]
+	ServerToolUse {
+		id: String,
+		name: String,
+		input: serde_json::Value,
+	},
+	/// Results of a `web_search` server tool call. `content` is either a list of
+	/// `web_search_result` blocks or a `web_search_tool_result_error` object.
+	WebSearchToolResult {
+		tool_use_id: String,
+		content: serde_json::Value,
+	},
+}
+
+impl Content {
+	/// Whether this block belongs to a tool executed by Anthropic rather than by the client
+	pub fn is_server_tool(&self) -> bool {
+		matches!(
+			self,
+			Content::ServerToolUse { .. } | Content::WebSearchToolResult { .. }
+		)
+	}
 }
 
 impl FromStr for Content {
@@ -238,7 +411,7 @@ impl FromStr for Content {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ToolResultContent {
 	Text { text: String },
-	Image(ImageSource),
+	Image { source: ImageSource },
 }
 
 impl FromStr for ToolResultContent {
@@ -249,10 +422,12 @@ impl FromStr for ToolResultContent {
 	}
 }
 
+/// The body of an image source, serialized as the `data` or `url` field of the source.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
-#[serde(untagged)]
 pub enum ImageSourceData {
+	#[serde(rename = "data")]
 	Base64(String),
+	#[serde(rename = "url")]
 	Url(String),
 }
 
@@ -287,11 +462,67 @@ impl From<ImageSourceData> for String {
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ImageSource {
+	#[serde(flatten)]
 	pub data: ImageSourceData,
-	pub media_type: ImageFormat,
+	/// Required for base64 sources, URL sources don't take one
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub media_type: Option<ImageFormat>,
 	pub r#type: SourceType,
 }
 
+impl TryFrom<message::Image> for ImageSource {
+	type Error = MessageError;
+
+	fn try_from(mut image: message::Image) -> Result<Self, Self::Error> {
+		image.sniff_media_type();
+		let message::Image {
+			data, media_type, ..
+		} = image;
+
+		let data = match data {
+			DocumentSourceKind::Url(url) => {
+				return Ok(ImageSource {
+					data: ImageSourceData::Url(url),
+					media_type: None,
+					r#type: SourceType::URL,
+				});
+			}
+			DocumentSourceKind::Base64(data) => data,
+			DocumentSourceKind::Raw(data) => BASE64_STANDARD.encode(data),
+			DocumentSourceKind::Unknown => {
+				return Err(MessageError::ConversionError(
+					"Image content has no body".into(),
+				));
+			}
+			doc => {
+				return Err(MessageError::ConversionError(format!(
+					"Unsupported document type: {doc:?}"
+				)));
+			}
+		};
+		let media_type = media_type.ok_or(MessageError::ConversionError(
+			"Image media type is required for Claude API".to_string(),
+		))?;
+
+		Ok(ImageSource {
+			data: ImageSourceData::Base64(data),
+			media_type: Some(ImageFormat::try_from(media_type)?),
+			r#type: SourceType::BASE64,
+		})
+	}
+}
+
+impl From<ImageSource> for message::Image {
+	fn from(source: ImageSource) -> Self {
+		message::Image {
+			data: source.data.into(),
+			media_type: source.media_type.map(Into::into),
+			detail: None,
+			additional_params: None,
+		}
+	}
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct DocumentSource {
 	pub data: String,
@@ -420,6 +651,9 @@ impl TryFrom<message::AssistantContent> for Content {
 			message::AssistantContent::Image(_) => Err(MessageError::ConversionError(
 				"Anthropic currently doesn't support images.".to_string(),
 			)),
+			message::AssistantContent::Document(_) => Err(MessageError::ConversionError(
+				"Anthropic doesn't support assistant documents".to_string(),
+			)),
 			message::AssistantContent::ToolCall(message::ToolCall { id, function, .. }) => {
 				Ok(Content::ToolUse {
 					id,
@@ -460,64 +694,23 @@ impl TryFrom<message::Message> for Message {
 								Ok(ToolResultContent::Text { text })
 							}
 							message::ToolResultContent::Image(image) => {
-								let DocumentSourceKind::Base64(data) = image.data else {
-									return Err(MessageError::ConversionError(
-										"Only base64 strings can be used with the Anthropic API"
-											.to_string(),
-									));
-								};
-								let media_type =
-									image.media_type.ok_or(MessageError::ConversionError(
-										"Image media type is required".to_owned(),
-									))?;
-								Ok(ToolResultContent::Image(ImageSource {
-									data: ImageSourceData::Base64(data),
-									media_type: media_type.try_into()?,
-									r#type: SourceType::BASE64,
-								}))
+								Ok(ToolResultContent::Image {
+									source: image.try_into()?,
+								})
 							}
 						})?,
 						is_error: None,
 						cache_control: None,
 					}),
-					message::UserContent::Image(message::Image {
-						data, media_type, ..
-					}) => {
-						let media_type = media_type.ok_or(MessageError::ConversionError(
-							"Image media type is required for Claude API".to_string(),
-						))?;
-
-						let source = match data {
-							DocumentSourceKind::Base64(data) => ImageSource {
-								data: ImageSourceData::Base64(data),
-								r#type: SourceType::BASE64,
-								media_type: ImageFormat::try_from(media_type)?,
-							},
-							DocumentSourceKind::Url(url) => ImageSource {
-								data: ImageSourceData::Url(url),
-								r#type: SourceType::URL,
-								media_type: ImageFormat::try_from(media_type)?,
-							},
-							DocumentSourceKind::Unknown => {
-								return Err(MessageError::ConversionError(
-									"Image content has no body".into(),
-								));
-							}
-							doc => {
-								return Err(MessageError::ConversionError(format!(
-									"Unsupported document type: {doc:?}"
-								)));
-							}
-						};
-
-						Ok(Content::Image {
-							source,
-							cache_control: None,
-						})
-					}
-					message::UserContent::Document(message::Document {
-						data, media_type, ..
-					}) => {
+					message::UserContent::Image(image) => Ok(Content::Image {
+						source: image.try_into()?,
+						cache_control: None,
+					}),
+					message::UserContent::Document(mut document) => {
+						document.sniff_media_type();
+						let message::Document {
+							data, media_type, ..
+						} = document;
 						let media_type = media_type.ok_or(MessageError::ConversionError(
 							"Document media type is required".to_string(),
 						))?;
@@ -526,6 +719,7 @@ impl TryFrom<message::Message> for Message {
 							DocumentSourceKind::Base64(data) | DocumentSourceKind::String(data) => {
 								data
 							}
+							DocumentSourceKind::Raw(data) => BASE64_STANDARD.encode(data),
 							_ => {
 								return Err(MessageError::ConversionError(
 									"Only base64 encoded documents currently supported".into(),
@@ -552,10 +746,30 @@ impl TryFrom<message::Message> for Message {
 				})?,
 			},
 
-			message::Message::Assistant { content, .. } => Message {
-				content: content.try_map(|content| content.try_into())?,
-				role: Role::Assistant,
-			},
+			message::Message::Assistant { content, .. } => {
+				let content = content
+					.into_iter()
+					.filter(|content| {
+						let is_document = matches!(content, message::AssistantContent::Document(_));
+						if is_document {
+							tracing::warn!(
+								"Anthropic doesn't support assistant documents, skipping"
+							);
+						}
+						!is_document
+					})
+					.map(Content::try_from)
+					.collect::<Result<Vec<_>, _>>()?;
+
+				Message {
+					content: OneOrMany::many(content).map_err(|_| {
+						MessageError::ConversionError(
+							"Assistant message only contains documents".to_string(),
+						)
+					})?,
+					role: Role::Assistant,
+				}
+			}
 		})
 	}
 }
@@ -588,11 +802,7 @@ impl From<ToolResultContent> for message::ToolResultContent {
 	fn from(content: ToolResultContent) -> Self {
 		match content {
 			ToolResultContent::Text { text } => message::ToolResultContent::text(text),
-			ToolResultContent::Image(ImageSource {
-				data,
-				media_type: format,
-				..
-			}) => message::ToolResultContent::image_base64(data, Some(format.into()), None),
+			ToolResultContent::Image { source } => message::ToolResultContent::Image(source.into()),
 		}
 	}
 }
@@ -614,14 +824,7 @@ impl TryFrom<Message> for message::Message {
 							tool_use_id,
 							content.map(|content| content.into()),
 						),
-						Content::Image { source, .. } => {
-							message::UserContent::Image(message::Image {
-								data: source.data.into(),
-								media_type: Some(source.media_type.into()),
-								detail: None,
-								additional_params: None,
-							})
-						}
+						Content::Image { source, .. } => message::UserContent::Image(source.into()),
 						Content::Document { source, .. } => message::UserContent::document(
 							source.data,
 							Some(message::DocumentMediaType::PDF),
@@ -693,7 +896,7 @@ impl TryFrom<message::ToolChoice> for ToolChoice {
 	}
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Serialize)]
 pub(crate) struct AnthropicCompletionRequest {
 	pub(crate) model: String,
 	pub(crate) messages: Vec<Message>,
@@ -703,10 +906,12 @@ pub(crate) struct AnthropicCompletionRequest {
 	pub(crate) system: Vec<SystemContent>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub(crate) temperature: Option<f64>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub(crate) stop_sequences: Vec<String>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub(crate) tool_choice: Option<ToolChoice>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
-	pub(crate) tools: Vec<ToolDefinition>,
+	pub(crate) tools: Vec<RequestTool>,
 	#[serde(flatten, skip_serializing_if = "Option::is_none")]
 	pub(crate) additional_params: Option<serde_json::Value>,
 }
@@ -722,6 +927,31 @@ fn set_content_cache_control(content: &mut Content, value: Option<CacheControl>)
 	}
 }
 
+/// Convert chat history into Anthropic messages, dropping assistant messages that
+/// are left empty once their unsupported documents are skipped.
+pub(crate) fn history_to_messages(
+	history: Vec<message::Message>,
+) -> Result<Vec<Message>, MessageError> {
+	history
+		.into_iter()
+		.filter(|message| match message {
+			message::Message::Assistant { content, .. } => {
+				let only_documents = content
+					.iter()
+					.all(|content| matches!(content, message::AssistantContent::Document(_)));
+				if only_documents {
+					tracing::warn!(
+						"Anthropic doesn't support assistant documents, dropping a message that only contains documents"
+					);
+				}
+				!only_documents
+			}
+			_ => true,
+		})
+		.map(Message::try_from)
+		.collect()
+}
+
 /// Apply cache control breakpoints to system prompt and messages.
 /// Strategy: cache the system prompt, and mark the last content block of the last message
 /// for caching. This allows the conversation history to be cached while new messages
@@ -750,6 +980,7 @@ pub struct AnthropicRequestParams<'a> {
 	pub model: &'a str,
 	pub request: CompletionRequest,
 	pub prompt_caching: bool,
+	pub server_tools: &'a [ServerTool],
 }
 
 impl TryFrom<AnthropicRequestParams<'_>> for AnthropicCompletionRequest {
@@ -760,6 +991,7 @@ impl TryFrom<AnthropicRequestParams<'_>> for AnthropicCompletionRequest {
 			model,
 			request: req,
 			prompt_caching,
+			server_tools,
 		} = params;
 
 		// Check if max_tokens is set, required for Anthropic
@@ -775,20 +1007,9 @@ impl TryFrom<AnthropicRequestParams<'_>> for AnthropicCompletionRequest {
 		}
 		full_history.extend(req.chat_history);
 
-		let mut messages = full_history
-			.into_iter()
-			.map(Message::try_from)
-			.collect::<Result<Vec<Message>, _>>()?;
+		let mut messages = history_to_messages(full_history)?;
 
-		let tools = req
-			.tools
-			.into_iter()
-			.map(|tool| ToolDefinition {
-				name: tool.name,
-				description: Some(tool.description),
-				input_schema: tool.parameters,
-			})
-			.collect::<Vec<_>>();
+		let tools = request_tools(req.tools, server_tools);
 
 		// Convert system prompt to array format for cache_control support
 		let mut system = if let Some(preamble) = req.preamble {
@@ -815,6 +1036,7 @@ impl TryFrom<AnthropicRequestParams<'_>> for AnthropicCompletionRequest {
 			max_tokens,
 			system,
 			temperature: req.temperature,
+			stop_sequences: req.stop_sequences,
 			tool_choice: req.tool_choice.and_then(|x| ToolChoice::try_from(x).ok()),
 			tools,
 			additional_params: req.additional_params,
@@ -822,6 +1044,50 @@ impl TryFrom<AnthropicRequestParams<'_>> for AnthropicCompletionRequest {
 	}
 }
 
+/// Body of a request to the `/v1/messages/count_tokens` endpoint
+#[derive(Debug, Serialize)]
+pub(crate) struct CountTokensRequest {
+	pub(crate) model: String,
+	pub(crate) messages: Vec<Message>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub(crate) system: Vec<SystemContent>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) tool_choice: Option<ToolChoice>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub(crate) tools: Vec<RequestTool>,
+}
+
+impl TryFrom<AnthropicRequestParams<'_>> for CountTokensRequest {
+	type Error = CompletionError;
+
+	fn try_from(mut params: AnthropicRequestParams<'_>) -> Result<Self, Self::Error> {
+		// Required to build a messages request, but irrelevant to the count
+		params.request.max_tokens.get_or_insert(1);
+
+		let AnthropicCompletionRequest {
+			model,
+			messages,
+			system,
+			tool_choice,
+			tools,
+			..
+		} = params.try_into()?;
+
+		Ok(Self {
+			model,
+			messages,
+			system,
+			tool_choice,
+			tools,
+		})
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountTokensResponse {
+	pub input_tokens: u64,
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct ApiErrorResponse {
 	pub(crate) message: String,
@@ -6,11 +6,29 @@ use crate::audio_generation::{
 	self, AudioGenerationError, AudioGenerationRequest, AudioGenerationResponse,
 };
 use crate::http_client::HttpClientExt;
+use crate::json_utils::merge_inplace;
+pub use crate::providers::openai::audio_generation::AudioFormat;
 
+/// Voices of the Azure OpenAI text to speech models
+pub const VOICES: [&str; 11] = [
+	"alloy", "ash", "ballad", "coral", "echo", "fable", "nova", "onyx", "sage", "shimmer", "verse",
+];
+
+/// An Azure OpenAI text to speech deployment.
+///
+/// Options set on the model apply to every request, and can be overridden per request with
+/// [crate::audio_generation::AudioGenerationRequestBuilder::additional_params].
 #[derive(Clone)]
 pub struct AudioGenerationModel<T = reqwest::Client> {
 	client: Client<T>,
 	model: String,
+	/// Overrides the client's API version for this deployment
+	pub api_version: Option<String>,
+	pub response_format: Option<AudioFormat>,
+	/// Voice used by requests without one
+	pub voice: Option<String>,
+	/// Whether voices other than [VOICES] are sent, e.g. custom neural voices
+	pub allow_custom_voices: bool,
 }
 
 impl<T> AudioGenerationModel<T> {
@@ -18,37 +36,91 @@ impl<T> AudioGenerationModel<T> {
 		Self {
 			client,
 			model: deployment_name.into(),
+			api_version: None,
+			response_format: None,
+			voice: None,
+			allow_custom_voices: false,
 		}
 	}
-}
 
-impl<T> audio_generation::AudioGenerationModel for AudioGenerationModel<T>
-where
-	T: HttpClientExt + Clone + Default + std::fmt::Debug + Send + 'static,
-{
-	type Response = Bytes;
-	type Client = Client<T>;
+	/// Use `api_version` for this deployment instead of the client's default.
+	pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+		self.api_version = Some(api_version.into());
+		self
+	}
 
-	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
-		Self::new(client.clone(), model)
+	pub fn with_response_format(mut self, response_format: AudioFormat) -> Self {
+		self.response_format = Some(response_format);
+		self
 	}
 
-	async fn audio_generation(
+	/// Use `voice` for requests without one.
+	pub fn with_voice(mut self, voice: impl Into<String>) -> Self {
+		self.voice = Some(voice.into());
+		self
+	}
+
+	/// Send voices other than [VOICES] instead of rejecting them before the request.
+	pub fn allow_custom_voices(mut self) -> Self {
+		self.allow_custom_voices = true;
+		self
+	}
+
+	fn request_body(
 		&self,
+		deployment: &str,
 		request: AudioGenerationRequest,
-	) -> Result<AudioGenerationResponse<Self::Response>, AudioGenerationError> {
-		let request = json!({
-			"model": self.model,
+	) -> Result<serde_json::Value, AudioGenerationError> {
+		let voice = match (request.voice.is_empty(), &self.voice) {
+			(true, Some(voice)) => voice.clone(),
+			_ => request.voice,
+		};
+
+		if !self.allow_custom_voices && !VOICES.contains(&voice.as_str()) {
+			return Err(AudioGenerationError::RequestError(
+				format!(
+					"unknown voice {voice:?}, expected one of {}",
+					VOICES.join(", ")
+				)
+				.into(),
+			));
+		}
+
+		let mut body = json!({
+			"model": deployment,
 			"input": request.text,
-			"voice": request.voice,
+			"voice": voice,
 			"speed": request.speed,
 		});
 
-		let body = serde_json::to_vec(&request)?;
+		if let Some(response_format) = self.response_format {
+			merge_inplace(&mut body, json!({ "response_format": response_format }));
+		}
+
+		if let Some(params) = request.additional_params {
+			merge_inplace(&mut body, params);
+		}
+
+		Ok(body)
+	}
+}
+
+impl<T> AudioGenerationModel<T>
+where
+	T: HttpClientExt + Clone + Default + std::fmt::Debug + Send + 'static,
+{
+	/// Generates audio with `deployment` rather than the model's deployment, e.g. to route a
+	/// request to a deployment in another region.
+	pub async fn audio_generation_with_deployment(
+		&self,
+		deployment: &str,
+		request: AudioGenerationRequest,
+	) -> Result<AudioGenerationResponse<Bytes>, AudioGenerationError> {
+		let body = serde_json::to_vec(&self.request_body(deployment, request)?)?;
 
 		let req = self
 			.client
-			.post_audio_generation("/audio/speech")?
+			.post_audio_generation(deployment, self.api_version.as_deref())?
 			.header("Content-Type", "application/json")
 			.body(body)
 			.map_err(|e| AudioGenerationError::HttpError(e.into()))?;
@@ -70,3 +142,131 @@ where
 		})
 	}
 }
+
+impl<T> audio_generation::AudioGenerationModel for AudioGenerationModel<T>
+where
+	T: HttpClientExt + Clone + Default + std::fmt::Debug + Send + 'static,
+{
+	type Response = Bytes;
+	type Client = Client<T>;
+
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		Self::new(client.clone(), model)
+	}
+
+	async fn audio_generation(
+		&self,
+		request: AudioGenerationRequest,
+	) -> Result<AudioGenerationResponse<Self::Response>, AudioGenerationError> {
+		self.audio_generation_with_deployment(&self.model, request)
+			.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::audio_generation::AudioGenerationModel as _;
+	use crate::test_utils::MockSseClient;
+
+	fn model(http_client: MockSseClient) -> AudioGenerationModel<MockSseClient> {
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.azure_endpoint("https://example.openai.azure.com".to_string())
+			.api_version("2025-03-01-preview")
+			.http_client(http_client)
+			.build()
+			.unwrap();
+
+		AudioGenerationModel::new(client, "tts-eastus")
+	}
+
+	#[tokio::test]
+	async fn test_audio_generation_request() {
+		let http_client = MockSseClient::default().with_json_response("audio");
+
+		let response = model(http_client.clone())
+			.with_response_format(AudioFormat::Opus)
+			.audio_generation_request()
+			.text("Hello")
+			.voice("coral")
+			.speed(1.25)
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(response.audio, b"audio");
+		assert_eq!(
+			http_client.request_uris()[0],
+			"https://example.openai.azure.com/openai/deployments/tts-eastus/audio/speech?api-version=2025-03-01-preview"
+		);
+		assert_eq!(
+			http_client.request_bodies()[0],
+			json!({
+				"model": "tts-eastus",
+				"input": "Hello",
+				"voice": "coral",
+				"speed": 1.25,
+				"response_format": "opus"
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_audio_generation_deployment_override() {
+		let http_client = MockSseClient::default().with_json_response("audio");
+		let model = model(http_client.clone())
+			.with_voice("nova")
+			.with_api_version("2025-04-01-preview");
+		let request = model.audio_generation_request().text("Hello").build();
+
+		model
+			.audio_generation_with_deployment("tts-westeurope", request)
+			.await
+			.unwrap();
+
+		assert_eq!(
+			http_client.request_uris()[0],
+			"https://example.openai.azure.com/openai/deployments/tts-westeurope/audio/speech?api-version=2025-04-01-preview"
+		);
+		let body = &http_client.request_bodies()[0];
+		assert_eq!(body["model"], "tts-westeurope");
+		assert_eq!(body["voice"], "nova");
+	}
+
+	#[tokio::test]
+	async fn test_unknown_voice() {
+		let http_client = MockSseClient::default().with_json_response("audio");
+
+		let error = model(http_client.clone())
+			.audio_generation_request()
+			.text("Hello")
+			.voice("corall")
+			.send()
+			.await
+			.err()
+			.expect("unknown voices should be rejected");
+
+		assert!(
+			error.to_string().contains("unknown voice \"corall\""),
+			"{error}"
+		);
+		assert!(http_client.request_uris().is_empty());
+
+		model(http_client.clone())
+			.allow_custom_voices()
+			.audio_generation_request()
+			.text("Hello")
+			.voice("en-US-AvaMultilingualNeural")
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(
+			http_client.request_bodies()[0]["voice"],
+			"en-US-AvaMultilingualNeural"
+		);
+	}
+}
@@ -4,11 +4,14 @@ use std::fmt::Debug;
 use super::audio_generation::AudioGenerationModel;
 use super::completion::CompletionModel;
 use super::embedding::EmbeddingModel;
+#[cfg(feature = "image")]
+use super::image_generation::ImageGenerationModel;
 use super::transcription::TranscriptionModel;
 #[cfg(feature = "image")]
 use crate::client::Nothing;
 use crate::client::{
-	self, ApiKey, Capabilities, Capable, DebugExt, Provider, ProviderBuilder, ProviderClient,
+	self, ApiKey, Capabilities, Capable, ClientError, DebugExt, DynamicAuth, Provider,
+	ProviderBuilder, ProviderClient,
 };
 use crate::http_client::{self, HttpClientExt, bearer_auth_header};
 
@@ -116,6 +119,8 @@ impl ProviderBuilder for AzureExtBuilder {
 
 				builder.headers_mut().insert(k, v);
 			}
+			// Set before each request by the client
+			Dynamic(_) => {}
 		}
 
 		Ok(builder)
@@ -141,25 +146,42 @@ impl<H> client::ClientBuilder<AzureExtBuilder, AzureOpenAIAuth, H> {
 	}
 }
 
-/// The authentication type for Azure OpenAI. Can either be an API key or a token.
+/// The authentication type for Azure OpenAI. Can either be an API key, a token, or a
+/// [DynamicAuth] refreshing short-lived Azure AD tokens.
 /// String types will automatically be coerced to a bearer auth token by default.
 #[derive(Clone)]
 pub enum AzureOpenAIAuth {
 	ApiKey(String),
 	Token(String),
+	/// Sent as a bearer token, or in the `api-key` header if set with [DynamicAuth::header]
+	Dynamic(DynamicAuth),
 }
 
-impl ApiKey for AzureOpenAIAuth {}
+impl ApiKey for AzureOpenAIAuth {
+	fn dynamic_auth(&self) -> Option<DynamicAuth> {
+		match self {
+			Self::Dynamic(auth) => Some(auth.clone()),
+			_ => None,
+		}
+	}
+}
 
 impl std::fmt::Debug for AzureOpenAIAuth {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
 			Self::ApiKey(_) => write!(f, "API key <REDACTED>"),
 			Self::Token(_) => write!(f, "Token <REDACTED>"),
+			Self::Dynamic(auth) => auth.fmt(f),
 		}
 	}
 }
 
+impl From<DynamicAuth> for AzureOpenAIAuth {
+	fn from(auth: DynamicAuth) -> Self {
+		AzureOpenAIAuth::Dynamic(auth)
+	}
+}
+
 impl<S> From<S> for AzureOpenAIAuth
 where
 	S: Into<String>,
@@ -181,77 +203,127 @@ where
 		&self.ext().api_version
 	}
 
-	pub(super) fn post_embedding(
+	/// Builds the URL of a deployment endpoint, using `api_version` if set and the client's
+	/// default version otherwise.
+	pub(super) fn deployment_url(
 		&self,
 		deployment_id: &str,
-	) -> http_client::Result<http_client::Builder> {
-		let url = format!(
-			"{}/openai/deployments/{}/embeddings?api-version={}",
+		path: &str,
+		api_version: Option<&str>,
+	) -> http_client::Result<String> {
+		deployment_url(
 			self.endpoint(),
-			deployment_id.trim_start_matches('/'),
-			self.api_version()
-		);
+			deployment_id,
+			path,
+			api_version.unwrap_or(self.api_version()),
+		)
+		.map_err(|e| http_client::Error::Instance(e.into()))
+	}
 
-		self.post(&url)
+	pub(super) fn post_embedding(
+		&self,
+		deployment_id: &str,
+		api_version: Option<&str>,
+	) -> http_client::Result<http_client::Builder> {
+		self.post(self.deployment_url(deployment_id, "embeddings", api_version)?)
 	}
 
 	#[cfg(feature = "audio")]
 	pub(super) fn post_audio_generation(
 		&self,
 		deployment_id: &str,
+		api_version: Option<&str>,
 	) -> http_client::Result<http_client::Builder> {
-		let url = format!(
-			"{}/openai/deployments/{}/audio/speech?api-version={}",
-			self.endpoint(),
-			deployment_id.trim_start_matches('/'),
-			self.api_version()
-		);
-
-		self.post(url)
+		self.post(self.deployment_url(deployment_id, "audio/speech", api_version)?)
 	}
 
 	pub(super) fn post_chat_completion(
 		&self,
 		deployment_id: &str,
+		api_version: Option<&str>,
 	) -> http_client::Result<http_client::Builder> {
-		let url = format!(
-			"{}/openai/deployments/{}/chat/completions?api-version={}",
-			self.endpoint(),
-			deployment_id.trim_start_matches('/'),
-			self.api_version()
-		);
-
-		self.post(&url)
+		self.post(self.deployment_url(deployment_id, "chat/completions", api_version)?)
 	}
 
 	pub(super) fn post_transcription(
 		&self,
 		deployment_id: &str,
+		api_version: Option<&str>,
 	) -> http_client::Result<http_client::Builder> {
-		let url = format!(
-			"{}/openai/deployments/{}/audio/translations?api-version={}",
-			self.endpoint(),
-			deployment_id.trim_start_matches('/'),
-			self.api_version()
-		);
-
-		self.post(&url)
+		self.post(self.deployment_url(deployment_id, "audio/translations", api_version)?)
 	}
 
 	#[cfg(feature = "image")]
 	pub(super) fn post_image_generation(
 		&self,
 		deployment_id: &str,
+		api_version: Option<&str>,
 	) -> http_client::Result<http_client::Builder> {
-		let url = format!(
-			"{}/openai/deployments/{}/images/generations?api-version={}",
-			self.endpoint(),
-			deployment_id.trim_start_matches('/'),
-			self.api_version()
-		);
+		self.post(self.deployment_url(deployment_id, "images/generations", api_version)?)
+	}
+}
+
+impl<T> Client<T>
+where
+	T: Clone,
+{
+	/// Create a completion model for `deployment_name` that overrides the client's API version.
+	pub fn completion_model_with_api_version(
+		&self,
+		deployment_name: impl Into<String>,
+		api_version: impl Into<String>,
+	) -> CompletionModel<T> {
+		CompletionModel::new(self.clone(), deployment_name).with_api_version(api_version)
+	}
 
-		self.post(&url)
+	/// Create an embedding model for `deployment_name` that overrides the client's API version.
+	pub fn embedding_model_with_api_version(
+		&self,
+		deployment_name: impl Into<String>,
+		api_version: impl Into<String>,
+	) -> EmbeddingModel<T> {
+		EmbeddingModel::new(self.clone(), deployment_name, None).with_api_version(api_version)
 	}
+
+	/// Create a transcription model for `deployment_name` that overrides the client's API version.
+	pub fn transcription_model_with_api_version(
+		&self,
+		deployment_name: impl Into<String>,
+		api_version: impl Into<String>,
+	) -> TranscriptionModel<T> {
+		TranscriptionModel::new(self.clone(), deployment_name).with_api_version(api_version)
+	}
+
+	/// Create an image generation model for `deployment_name` that overrides the client's API version.
+	#[cfg(feature = "image")]
+	pub fn image_generation_model_with_api_version(
+		&self,
+		deployment_name: impl Into<String>,
+		api_version: impl Into<String>,
+	) -> ImageGenerationModel<T> {
+		ImageGenerationModel::new(self.clone(), deployment_name).with_api_version(api_version)
+	}
+}
+
+/// Builds `{endpoint}/openai/deployments/{deployment_id}/{path}?api-version={api_version}`,
+/// percent-encoding the deployment name and the API version.
+fn deployment_url(
+	endpoint: &str,
+	deployment_id: &str,
+	path: &str,
+	api_version: &str,
+) -> Result<String, url::ParseError> {
+	let mut url = url::Url::parse(endpoint)?;
+
+	url.path_segments_mut()
+		.map_err(|_| url::ParseError::RelativeUrlWithCannotBeABaseBase)?
+		.pop_if_empty()
+		.extend(["openai", "deployments", deployment_id.trim_matches('/')])
+		.extend(path.split('/'));
+	url.query_pairs_mut()
+		.append_pair("api-version", api_version);
+
+	Ok(url.into())
 }
 
 pub struct AzureOpenAIClientParams {
@@ -265,23 +337,41 @@ impl ProviderClient for Client {
 
 	/// Create a new Azure OpenAI client from the `AZURE_API_KEY` or `AZURE_TOKEN`, `AZURE_API_VERSION`, and `AZURE_ENDPOINT` environment variables.
 	fn from_env() -> Self {
-		let auth = if let Ok(api_key) = std::env::var("AZURE_API_KEY") {
-			AzureOpenAIAuth::ApiKey(api_key)
-		} else if let Ok(token) = std::env::var("AZURE_TOKEN") {
-			AzureOpenAIAuth::Token(token)
-		} else {
-			panic!("Neither AZURE_API_KEY nor AZURE_TOKEN is set");
-		};
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	/// Every missing variable is listed in the error, `AZURE_API_KEY` and `AZURE_TOKEN` when
+	/// neither is set.
+	fn try_from_env() -> Result<Self, ClientError> {
+		let auth = std::env::var("AZURE_API_KEY")
+			.map(AzureOpenAIAuth::ApiKey)
+			.or_else(|_| std::env::var("AZURE_TOKEN").map(AzureOpenAIAuth::Token))
+			.ok();
+		let api_version = std::env::var("AZURE_API_VERSION").ok();
+		let azure_endpoint = std::env::var("AZURE_ENDPOINT").ok();
+
+		let mut missing = Vec::new();
+		if auth.is_none() {
+			missing.extend(["AZURE_API_KEY", "AZURE_TOKEN"]);
+		}
+		if api_version.is_none() {
+			missing.push("AZURE_API_VERSION");
+		}
+		if azure_endpoint.is_none() {
+			missing.push("AZURE_ENDPOINT");
+		}
 
-		let api_version = std::env::var("AZURE_API_VERSION").expect("AZURE_API_VERSION not set");
-		let azure_endpoint = std::env::var("AZURE_ENDPOINT").expect("AZURE_ENDPOINT not set");
+		let (Some(auth), Some(api_version), Some(azure_endpoint)) =
+			(auth, api_version, azure_endpoint)
+		else {
+			return Err(ClientError::MissingEnvVar(missing));
+		};
 
-		Self::builder()
+		Ok(Self::builder()
 			.api_key(auth)
 			.azure_endpoint(azure_endpoint)
 			.api_version(&api_version)
-			.build()
-			.unwrap()
+			.build()?)
 	}
 
 	fn from_val(
@@ -301,3 +391,125 @@ impl ProviderClient for Client {
 			.unwrap()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::with_env;
+
+	#[test]
+	fn test_deployment_url() {
+		assert_eq!(
+			deployment_url(
+				"https://example.openai.azure.com",
+				"gpt-4o",
+				"chat/completions",
+				"2024-10-21"
+			)
+			.unwrap(),
+			"https://example.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-10-21"
+		);
+
+		// Trailing and leading slashes do not produce empty segments
+		assert_eq!(
+			deployment_url(
+				"https://example.openai.azure.com/",
+				"/text-embedding-3-small",
+				"embeddings",
+				"2024-10-21"
+			)
+			.unwrap(),
+			"https://example.openai.azure.com/openai/deployments/text-embedding-3-small/embeddings?api-version=2024-10-21"
+		);
+	}
+
+	#[test]
+	fn test_deployment_url_encoding() {
+		assert_eq!(
+			deployment_url(
+				"https://example.openai.azure.com",
+				"my deployment/v2",
+				"audio/translations",
+				"2025-01-01-preview&x=1"
+			)
+			.unwrap(),
+			"https://example.openai.azure.com/openai/deployments/my%20deployment%2Fv2/audio/translations?api-version=2025-01-01-preview%26x%3D1"
+		);
+
+		assert!(deployment_url("not a url", "gpt-4o", "embeddings", "2024-10-21").is_err());
+	}
+
+	#[test]
+	fn test_api_version_override() {
+		let client = Client::<reqwest::Client>::builder()
+			.api_key(AzureOpenAIAuth::ApiKey("test".into()))
+			.azure_endpoint("https://example.openai.azure.com".into())
+			.api_version("2024-10-21")
+			.build()
+			.unwrap();
+
+		assert_eq!(
+			client
+				.deployment_url("text-embedding-3-small", "embeddings", None)
+				.unwrap(),
+			"https://example.openai.azure.com/openai/deployments/text-embedding-3-small/embeddings?api-version=2024-10-21"
+		);
+
+		let model = client.embedding_model_with_api_version("text-embedding-3-small", "2023-05-15");
+		assert_eq!(model.api_version.as_deref(), Some("2023-05-15"));
+		assert_eq!(
+			client
+				.deployment_url(
+					"text-embedding-3-small",
+					"embeddings",
+					model.api_version.as_deref()
+				)
+				.unwrap(),
+			"https://example.openai.azure.com/openai/deployments/text-embedding-3-small/embeddings?api-version=2023-05-15"
+		);
+	}
+
+	#[test]
+	fn test_try_from_env_lists_missing_vars() {
+		let vars = [
+			("AZURE_API_KEY", None),
+			("AZURE_TOKEN", None),
+			("AZURE_API_VERSION", None),
+			("AZURE_ENDPOINT", None),
+		];
+		let error = with_env(&vars, Client::try_from_env).err().unwrap();
+		assert!(
+			matches!(
+				&error,
+				ClientError::MissingEnvVar(names)
+					if names == &["AZURE_API_KEY", "AZURE_TOKEN", "AZURE_API_VERSION", "AZURE_ENDPOINT"]
+			),
+			"{error:?}"
+		);
+		assert_eq!(
+			error.to_string(),
+			"Environment variables not set: `AZURE_API_KEY`, `AZURE_TOKEN`, `AZURE_API_VERSION`, `AZURE_ENDPOINT`"
+		);
+
+		let vars = [
+			("AZURE_API_KEY", None),
+			("AZURE_TOKEN", Some("token")),
+			("AZURE_API_VERSION", Some("2024-10-21")),
+			("AZURE_ENDPOINT", None),
+		];
+		let error = with_env(&vars, Client::try_from_env).err().unwrap();
+		assert_eq!(
+			error.to_string(),
+			"Environment variables not set: `AZURE_ENDPOINT`"
+		);
+
+		let vars = [
+			("AZURE_API_KEY", Some("key")),
+			("AZURE_TOKEN", None),
+			("AZURE_API_VERSION", Some("2024-10-21")),
+			("AZURE_ENDPOINT", Some("https://example.openai.azure.com")),
+		];
+		let client = with_env(&vars, Client::try_from_env).unwrap();
+		assert_eq!(client.api_version(), "2024-10-21");
+	}
+}
@@ -19,6 +19,8 @@ pub(crate) struct AzureOpenAICompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<openai::completion::types::ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<crate::providers::openrouter::ToolChoice>,
@@ -30,6 +32,11 @@ impl TryFrom<(&str, CompletionRequest)> for AzureOpenAICompletionRequest {
 	type Error = CompletionError;
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
+		req.check_stop_sequences(
+			"Azure OpenAI",
+			openai::completion::types::MAX_STOP_SEQUENCES,
+		)?;
+
 		//FIXME: Must fix!
 		if req.tool_choice.is_some() {
 			tracing::warn!(
@@ -69,6 +76,7 @@ impl TryFrom<(&str, CompletionRequest)> for AzureOpenAICompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools: req
 				.tools
 				.clone()
@@ -86,6 +94,8 @@ pub struct CompletionModel<T = reqwest::Client> {
 	client: Client<T>,
 	/// Name of the model (e.g.: gpt-4o-mini)
 	pub model: String,
+	/// Overrides the client's API version for this deployment
+	pub api_version: Option<String>,
 }
 
 impl<T> CompletionModel<T> {
@@ -93,8 +103,15 @@ impl<T> CompletionModel<T> {
 		Self {
 			client,
 			model: model.into(),
+			api_version: None,
 		}
 	}
+
+	/// Use `api_version` for this deployment instead of the client's default.
+	pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+		self.api_version = Some(api_version.into());
+		self
+	}
 }
 
 impl<T> completion::CompletionModel for CompletionModel<T>
@@ -109,6 +126,10 @@ where
 		Self::new(client.clone(), model.into())
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
@@ -116,6 +137,7 @@ where
 		completion::CompletionResponse<openai::completion::types::CompletionResponse>,
 		CompletionError,
 	> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -128,6 +150,8 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -135,6 +159,7 @@ where
 
 		let request =
 			AzureOpenAICompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -147,12 +172,13 @@ where
 
 		let req = self
 			.client
-			.post_chat_completion(&self.model)?
+			.post_chat_completion(&self.model, self.api_version.as_deref())?
 			.body(body)
 			.map_err(http_client::Error::from)?;
 
 		async move {
 			let response = self.client.send::<_, Bytes>(req).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			let status = response.status();
 			let response_body = response.into_body().into_future().await?.to_vec();
@@ -166,13 +192,15 @@ where
 						let span = tracing::Span::current();
 						span.record_response_metadata(&response);
 						span.record_token_usage(&response.usage);
+						span.record_output_messages(&response.choices);
 						if enabled!(Level::TRACE) {
 							tracing::trace!(target: "clankers::completions",
 								"Azure OpenAI completion response: {}",
 								serde_json::to_string_pretty(&response)?
 							);
 						}
-						response.try_into()
+						completion::CompletionResponse::try_from(response)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
 				}
@@ -190,6 +218,7 @@ where
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let preamble = completion_request.preamble.clone();
 		let mut request =
 			AzureOpenAICompletionRequest::try_from((self.model.as_ref(), completion_request))?;
@@ -212,7 +241,7 @@ where
 
 		let req = self
 			.client
-			.post_chat_completion(&self.model)?
+			.post_chat_completion(&self.model, self.api_version.as_deref())?
 			.body(body)
 			.map_err(http_client::Error::from)?;
 
@@ -228,10 +257,13 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		tracing_futures::Instrument::instrument(
 			send_compatible_streaming_request(self.client.clone(), req),
@@ -72,6 +72,8 @@ pub struct EmbeddingModel<T = reqwest::Client> {
 	client: Client<T>,
 	pub model: String,
 	ndims: usize,
+	/// Overrides the client's API version for this deployment
+	pub api_version: Option<String>,
 }
 
 impl<T> embeddings::EmbeddingModel for EmbeddingModel<T>
@@ -86,6 +88,10 @@ where
 		Self::new(client.clone(), model, dims)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	fn ndims(&self) -> usize {
 		self.ndims
 	}
@@ -108,7 +114,7 @@ where
 
 		let req = self
 			.client
-			.post_embedding(self.model.as_str())?
+			.post_embedding(&self.model, self.api_version.as_deref())?
 			.body(body)
 			.map_err(|e| EmbeddingError::HttpError(e.into()))?;
 
@@ -161,6 +167,7 @@ impl<T> EmbeddingModel<T> {
 			client,
 			model,
 			ndims,
+			api_version: None,
 		}
 	}
 
@@ -171,6 +178,13 @@ impl<T> EmbeddingModel<T> {
 			client,
 			model: model.into(),
 			ndims,
+			api_version: None,
 		}
 	}
+
+	/// Use `api_version` for this deployment instead of the client's default.
+	pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+		self.api_version = Some(api_version.into());
+		self
+	}
 }
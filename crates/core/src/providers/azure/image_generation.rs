@@ -5,6 +5,7 @@ use super::client::Client;
 use crate::http_client::HttpClientExt;
 use crate::image_generation;
 use crate::image_generation::{ImageGenerationError, ImageGenerationRequest};
+use crate::json_utils::merge_inplace;
 use crate::providers::openai::ImageGenerationResponse;
 use crate::providers::openai_compat::ApiResponse;
 
@@ -12,6 +13,24 @@ use crate::providers::openai_compat::ApiResponse;
 pub struct ImageGenerationModel<T = reqwest::Client> {
 	client: Client<T>,
 	pub model: String,
+	/// Overrides the client's API version for this deployment
+	pub api_version: Option<String>,
+}
+
+impl<T> ImageGenerationModel<T> {
+	pub fn new(client: Client<T>, model: impl Into<String>) -> Self {
+		Self {
+			client,
+			model: model.into(),
+			api_version: None,
+		}
+	}
+
+	/// Use `api_version` for this deployment instead of the client's default.
+	pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+		self.api_version = Some(api_version.into());
+		self
+	}
 }
 
 impl<T> image_generation::ImageGenerationModel for ImageGenerationModel<T>
@@ -23,28 +42,29 @@ where
 	type Client = Client<T>;
 
 	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
-		Self {
-			client: client.clone(),
-			model: model.into(),
-		}
+		Self::new(client.clone(), model)
 	}
 
 	async fn image_generation(
 		&self,
 		generation_request: ImageGenerationRequest,
 	) -> Result<image_generation::ImageGenerationResponse<Self::Response>, ImageGenerationError> {
-		let request = json!({
+		let mut request = json!({
 			"model": self.model,
 			"prompt": generation_request.prompt,
 			"size": format!("{}x{}", generation_request.width, generation_request.height),
 			"response_format": "b64_json"
 		});
 
+		if let Some(n) = generation_request.n {
+			merge_inplace(&mut request, json!({ "n": n }));
+		}
+
 		let body = serde_json::to_vec(&request)?;
 
 		let req = self
 			.client
-			.post_image_generation(&self.model)?
+			.post_image_generation(&self.model, self.api_version.as_deref())?
 			.body(body)
 			.map_err(|e| ImageGenerationError::HttpError(e.into()))?;
 
@@ -76,7 +76,6 @@ pub const GPT_35_TURBO_16K: &str = "gpt-3.5-turbo-16k";
 #[cfg(test)]
 mod azure_tests {
 	use super::*;
-	use crate::OneOrMany;
 	use crate::client::ProviderClient;
 	use crate::client::completion::CompletionClient;
 	use crate::client::embeddings::EmbeddingsClient;
@@ -121,16 +120,13 @@ mod azure_tests {
 		let client = Client::<reqwest::Client>::from_env();
 		let model = client.completion_model(GPT_4O_MINI);
 		let completion = model
-			.completion(CompletionRequest {
-				preamble: Some("You are a helpful assistant.".to_string()),
-				chat_history: OneOrMany::one("Hello!".into()),
-				documents: vec![],
-				max_tokens: Some(100),
-				temperature: Some(0.0),
-				tools: vec![],
-				tool_choice: None,
-				additional_params: None,
-			})
+			.completion(
+				CompletionRequest::builder("Hello!")
+					.preamble("You are a helpful assistant.".to_string())
+					.temperature(0.0)
+					.max_tokens(100)
+					.build(),
+			)
 			.await
 			.unwrap();
 
@@ -12,6 +12,8 @@ pub struct TranscriptionModel<T = reqwest::Client> {
 	client: Client<T>,
 	/// Name of the model (e.g.: gpt-3.5-turbo-1106)
 	pub model: String,
+	/// Overrides the client's API version for this deployment
+	pub api_version: Option<String>,
 }
 
 impl<T> TranscriptionModel<T> {
@@ -19,8 +21,15 @@ impl<T> TranscriptionModel<T> {
 		Self {
 			client,
 			model: model.into(),
+			api_version: None,
 		}
 	}
+
+	/// Use `api_version` for this deployment instead of the client's default.
+	pub fn with_api_version(mut self, api_version: impl Into<String>) -> Self {
+		self.api_version = Some(api_version.into());
+		self
+	}
 }
 
 impl<T> transcription::TranscriptionModel for TranscriptionModel<T>
@@ -34,6 +43,10 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn transcription(
 		&self,
 		request: transcription::TranscriptionRequest,
@@ -65,7 +78,7 @@ where
 
 		let req = self
 			.client
-			.post_transcription(&self.model)?
+			.post_transcription(&self.model, self.api_version.as_deref())?
 			.body(body)
 			.map_err(|e| TranscriptionError::HttpError(e.into()))?;
 
@@ -0,0 +1,272 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use super::completion::CompletionModel;
+use super::signer::RequestSigner;
+use crate::client::{
+	self, ApiKey, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider, ProviderBuilder,
+	ProviderClient, Transport, join_url,
+};
+use crate::http_client::{self, HttpClientExt, Request, bearer_auth_header};
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+#[derive(Debug, Clone)]
+pub struct BedrockExt {
+	region: String,
+	signer: Option<Arc<dyn RequestSigner>>,
+}
+
+impl DebugExt for BedrockExt {
+	fn fields(&self) -> impl Iterator<Item = (&'static str, &dyn std::fmt::Debug)> {
+		[("region", (&self.region as &dyn Debug))].into_iter()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct BedrockBuilder {
+	region: String,
+}
+
+impl Default for BedrockBuilder {
+	fn default() -> Self {
+		Self {
+			region: DEFAULT_REGION.into(),
+		}
+	}
+}
+
+pub type Client<H = reqwest::Client> = client::Client<BedrockExt, H>;
+pub type ClientBuilder<H = reqwest::Client> = client::ClientBuilder<BedrockBuilder, BedrockAuth, H>;
+
+impl Provider for BedrockExt {
+	type Builder = BedrockBuilder;
+
+	/// Verifying Bedrock auth requires the control plane API, which isn't supported
+	const VERIFY_PATH: &'static str = "";
+
+	fn build<H>(
+		builder: &client::ClientBuilder<
+			Self::Builder,
+			<Self::Builder as ProviderBuilder>::ApiKey,
+			H,
+		>,
+	) -> http_client::Result<Self> {
+		let signer = match builder.get_api_key() {
+			BedrockAuth::Signer(signer) => Some(signer.clone()),
+			BedrockAuth::ApiKey(_) => None,
+		};
+
+		Ok(Self {
+			region: builder.ext().region.clone(),
+			signer,
+		})
+	}
+
+	/// Requests go to the runtime endpoint of the client's region unless a base URL was set, e.g.
+	/// for a VPC endpoint.
+	fn build_uri(&self, base_url: &str, path: &str, _transport: Transport) -> String {
+		if base_url.is_empty() {
+			join_url(
+				&format!("https://bedrock-runtime.{}.amazonaws.com", self.region),
+				path,
+			)
+		} else {
+			join_url(base_url, path)
+		}
+	}
+}
+
+impl<H> Capabilities<H> for BedrockExt {
+	type Completion = Capable<CompletionModel<H>>;
+	type Embeddings = Nothing;
+	type Transcription = Nothing;
+	#[cfg(feature = "image")]
+	type ImageGeneration = Nothing;
+	#[cfg(feature = "audio")]
+	type AudioGeneration = Nothing;
+}
+
+impl ProviderBuilder for BedrockBuilder {
+	type Output = BedrockExt;
+	type ApiKey = BedrockAuth;
+
+	const BASE_URL: &'static str = "";
+
+	fn finish<H>(
+		&self,
+		mut builder: client::ClientBuilder<Self, Self::ApiKey, H>,
+	) -> http_client::Result<client::ClientBuilder<Self, Self::ApiKey, H>> {
+		if let BedrockAuth::ApiKey(key) = builder.get_api_key().clone() {
+			bearer_auth_header(builder.headers_mut(), key.as_str())?;
+		}
+
+		Ok(builder)
+	}
+}
+
+impl<K, H> client::ClientBuilder<BedrockBuilder, K, H> {
+	/// AWS region to send requests to, e.g. "eu-west-3". Defaults to "us-east-1".
+	pub fn region(mut self, region: impl Into<String>) -> Self {
+		self.ext_mut().region = region.into();
+
+		self
+	}
+}
+
+/// The authentication of Bedrock requests: either a Bedrock API key, or a [RequestSigner] signing
+/// every request with IAM credentials.
+/// String types will automatically be coerced to an API key.
+#[derive(Clone)]
+pub enum BedrockAuth {
+	ApiKey(String),
+	Signer(Arc<dyn RequestSigner>),
+}
+
+impl BedrockAuth {
+	pub fn signer(signer: impl RequestSigner + 'static) -> Self {
+		Self::Signer(Arc::new(signer))
+	}
+}
+
+impl ApiKey for BedrockAuth {}
+
+impl std::fmt::Debug for BedrockAuth {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::ApiKey(_) => write!(f, "API key <REDACTED>"),
+			Self::Signer(signer) => f.debug_tuple("Signer").field(signer).finish(),
+		}
+	}
+}
+
+impl<S> From<S> for BedrockAuth
+where
+	S: Into<String>,
+{
+	fn from(key: S) -> Self {
+		BedrockAuth::ApiKey(key.into())
+	}
+}
+
+impl<H> Client<H>
+where
+	H: HttpClientExt,
+{
+	pub fn region(&self) -> &str {
+		&self.ext().region
+	}
+
+	/// Signs `request` if the client authenticates with a [RequestSigner].
+	pub(super) async fn sign(&self, request: &mut Request<Vec<u8>>) -> http_client::Result<()> {
+		match &self.ext().signer {
+			Some(signer) => signer.sign(request, &self.ext().region).await,
+			None => Ok(()),
+		}
+	}
+}
+
+impl ProviderClient for Client {
+	type Input = String;
+
+	/// Create a new Bedrock client from the environment.
+	/// Uses the `AWS_BEARER_TOKEN_BEDROCK` API key if set, and the `AWS_ACCESS_KEY_ID`,
+	/// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN` credentials otherwise (requires the
+	/// `bedrock-sigv4` feature). The region is read from `AWS_REGION` or `AWS_DEFAULT_REGION`.
+	/// Panics if no credentials are set.
+	fn from_env() -> Self
+	where
+		Self: Sized,
+	{
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let auth = std::env::var("AWS_BEARER_TOKEN_BEDROCK")
+			.ok()
+			.map(BedrockAuth::ApiKey)
+			.or_else(env_signer)
+			.ok_or_else(|| {
+				ClientError::MissingEnvVar(
+					CREDENTIAL_ENV_VARS
+						.into_iter()
+						.filter(|name| std::env::var(name).is_err())
+						.collect(),
+				)
+			})?;
+
+		Ok(Self::builder().api_key(auth).region(env_region()).build()?)
+	}
+
+	fn from_val(input: Self::Input) -> Self {
+		Self::builder()
+			.api_key(input)
+			.region(env_region())
+			.build()
+			.unwrap()
+	}
+}
+
+fn env_region() -> String {
+	std::env::var("AWS_REGION")
+		.or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+		.unwrap_or_else(|_| DEFAULT_REGION.into())
+}
+
+/// The environment variables checked for credentials, the unset ones being listed in the
+/// error of [Client::try_from_env]
+#[cfg(feature = "bedrock-sigv4")]
+const CREDENTIAL_ENV_VARS: [&str; 3] = [
+	"AWS_BEARER_TOKEN_BEDROCK",
+	"AWS_ACCESS_KEY_ID",
+	"AWS_SECRET_ACCESS_KEY",
+];
+
+#[cfg(not(feature = "bedrock-sigv4"))]
+const CREDENTIAL_ENV_VARS: [&str; 1] = ["AWS_BEARER_TOKEN_BEDROCK"];
+
+#[cfg(feature = "bedrock-sigv4")]
+fn env_signer() -> Option<BedrockAuth> {
+	super::signer::SigV4Signer::from_env().map(BedrockAuth::signer)
+}
+
+#[cfg(not(feature = "bedrock-sigv4"))]
+fn env_signer() -> Option<BedrockAuth> {
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_build_uri() {
+		let client: Client = Client::builder()
+			.api_key("key")
+			.region("eu-west-3")
+			.build()
+			.unwrap();
+
+		assert_eq!(client.region(), "eu-west-3");
+		assert_eq!(
+			client
+				.ext()
+				.build_uri(client.base_url(), "/model/m/converse", Transport::Http),
+			"https://bedrock-runtime.eu-west-3.amazonaws.com/model/m/converse"
+		);
+		assert_eq!(client.headers()[http::header::AUTHORIZATION], "Bearer key");
+
+		let client: Client = Client::builder()
+			.api_key("key")
+			.base_url("https://vpce-1234.bedrock-runtime.us-east-1.vpce.amazonaws.com/")
+			.build()
+			.unwrap();
+
+		assert_eq!(
+			client
+				.ext()
+				.build_uri(client.base_url(), "model/m/converse", Transport::Http),
+			"https://vpce-1234.bedrock-runtime.us-east-1.vpce.amazonaws.com/model/m/converse"
+		);
+	}
+}
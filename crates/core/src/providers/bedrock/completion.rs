@@ -0,0 +1,260 @@
+use bytes::Bytes;
+use tracing::{Instrument, Level, enabled, info_span};
+
+use super::client::Client;
+use super::error::parse_api_error;
+use super::types::{ConverseRequest, ConverseResponse};
+use crate::completion::{self, CompletionError, CompletionRequest};
+use crate::http_client::{HttpClientExt, Request};
+use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
+use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
+
+/// `anthropic.claude-opus-4-1-20250805-v1:0` completion model
+pub const CLAUDE_OPUS_4_1: &str = "anthropic.claude-opus-4-1-20250805-v1:0";
+/// `anthropic.claude-sonnet-4-20250514-v1:0` completion model
+pub const CLAUDE_SONNET_4: &str = "anthropic.claude-sonnet-4-20250514-v1:0";
+/// `anthropic.claude-3-7-sonnet-20250219-v1:0` completion model
+pub const CLAUDE_3_7_SONNET: &str = "anthropic.claude-3-7-sonnet-20250219-v1:0";
+/// `anthropic.claude-3-5-haiku-20241022-v1:0` completion model
+pub const CLAUDE_3_5_HAIKU: &str = "anthropic.claude-3-5-haiku-20241022-v1:0";
+/// `meta.llama4-maverick-17b-instruct-v1:0` completion model
+pub const LLAMA_4_MAVERICK_17B: &str = "meta.llama4-maverick-17b-instruct-v1:0";
+/// `meta.llama3-3-70b-instruct-v1:0` completion model
+pub const LLAMA_3_3_70B: &str = "meta.llama3-3-70b-instruct-v1:0";
+/// `meta.llama3-1-8b-instruct-v1:0` completion model
+pub const LLAMA_3_1_8B: &str = "meta.llama3-1-8b-instruct-v1:0";
+/// `amazon.nova-pro-v1:0` completion model
+pub const NOVA_PRO: &str = "amazon.nova-pro-v1:0";
+/// `amazon.nova-lite-v1:0` completion model
+pub const NOVA_LITE: &str = "amazon.nova-lite-v1:0";
+
+/// The name of the provider in telemetry and pricing tables
+pub(super) const PROVIDER_NAME: &str = "aws.bedrock";
+
+/// A Bedrock model, identified by a model ID, an inference profile ID (e.g.
+/// `us.anthropic.claude-sonnet-4-20250514-v1:0` for cross-region inference) or an ARN.
+#[derive(Clone, Debug)]
+pub struct CompletionModel<T = reqwest::Client> {
+	pub(crate) client: Client<T>,
+	pub model: String,
+}
+
+impl<T> CompletionModel<T>
+where
+	T: HttpClientExt,
+{
+	pub fn new(client: Client<T>, model: impl Into<String>) -> Self {
+		Self {
+			client,
+			model: model.into(),
+		}
+	}
+
+	/// Builds a signed request to `operation` (`converse` or `converse-stream`) of the model.
+	pub(super) async fn request(
+		&self,
+		operation: &str,
+		request: &ConverseRequest,
+	) -> Result<Request<Vec<u8>>, CompletionError> {
+		// Model IDs contain colons and ARNs slashes, which must be encoded in the path
+		let model: String = url::form_urlencoded::byte_serialize(self.model.as_bytes()).collect();
+
+		let mut request = self
+			.client
+			.post(format!("model/{model}/{operation}"))?
+			.header(http::header::CONTENT_TYPE, "application/json")
+			.body(serde_json::to_vec(request)?)
+			.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+		self.client.sign(&mut request).await?;
+
+		Ok(request)
+	}
+}
+
+impl<T> completion::CompletionModel for CompletionModel<T>
+where
+	T: HttpClientExt
+		+ Clone
+		+ Default
+		+ std::fmt::Debug
+		+ WasmCompatSend
+		+ WasmCompatSync
+		+ 'static,
+{
+	type Response = ConverseResponse;
+	type StreamingResponse = super::streaming::StreamingCompletionResponse;
+	type Client = Client<T>;
+
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		Self::new(client.clone(), model)
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	async fn completion(
+		&self,
+		completion_request: CompletionRequest,
+	) -> Result<completion::CompletionResponse<ConverseResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
+		let span = if tracing::Span::current().is_disabled() {
+			info_span!(
+				target: "clankers::completions",
+				"chat",
+				gen_ai.operation.name = "chat",
+				gen_ai.provider.name = PROVIDER_NAME,
+				gen_ai.request.model = &self.model,
+				gen_ai.system_instructions = &completion_request.preamble,
+				gen_ai.response.id = tracing::field::Empty,
+				gen_ai.response.model = tracing::field::Empty,
+				gen_ai.usage.output_tokens = tracing::field::Empty,
+				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
+			)
+		} else {
+			tracing::Span::current()
+		};
+
+		let request = ConverseRequest::try_from(completion_request)?;
+		span.record_input_messages(&request.messages);
+
+		if enabled!(Level::TRACE) {
+			tracing::trace!(
+				target: "clankers::completions",
+				"Bedrock completion request: {}",
+				serde_json::to_string_pretty(&request)?
+			);
+		}
+
+		async move {
+			let req = self.request("converse", &request).await?;
+
+			let response = self
+				.client
+				.send::<_, Bytes>(req)
+				.await
+				.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
+
+			let status = response.status();
+			let headers = response.headers().clone();
+			let body = response
+				.into_body()
+				.await
+				.map_err(CompletionError::HttpError)?;
+
+			if !status.is_success() {
+				let text = String::from_utf8_lossy(&body).into_owned();
+				return Err(CompletionError::ApiError(
+					parse_api_error(status, text).with_retry_after(&headers),
+				));
+			}
+
+			let response: ConverseResponse = serde_json::from_slice(&body)?;
+
+			let span = tracing::Span::current();
+			span.record_token_usage(&response.usage);
+			span.record_cost(
+				self.client.pricing(),
+				PROVIDER_NAME,
+				&self.model,
+				&response.usage,
+			);
+			span.record_output_messages(std::slice::from_ref(response.message()));
+			if enabled!(Level::TRACE) {
+				tracing::trace!(
+					target: "clankers::completions",
+					"Bedrock completion response: {}",
+					serde_json::to_string_pretty(&response)?
+				);
+			}
+
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
+		}
+		.instrument(span)
+		.await
+	}
+
+	async fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let request = self.client.apply_default_params(request);
+		CompletionModel::stream(self, request).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use super::*;
+	use crate::client::CompletionClient;
+	use crate::completion::CompletionModel as _;
+	use crate::providers::bedrock::{BedrockAuth, RequestSigner};
+	use crate::test_utils::MockSseClient;
+	use crate::wasm_compat::WasmBoxedFuture;
+
+	#[derive(Debug, Default)]
+	struct RecordingSigner {
+		regions: Mutex<Vec<String>>,
+	}
+
+	impl RequestSigner for Arc<RecordingSigner> {
+		fn sign<'a>(
+			&'a self,
+			request: &'a mut Request<Vec<u8>>,
+			region: &'a str,
+		) -> WasmBoxedFuture<'a, crate::http_client::Result<()>> {
+			self.regions.lock().unwrap().push(region.to_string());
+			// The signer gets the complete request
+			assert_eq!(
+				request.headers()[http::header::CONTENT_TYPE],
+				"application/json"
+			);
+			assert!(!request.body().is_empty());
+			request.headers_mut().insert(
+				http::header::AUTHORIZATION,
+				"AWS4-HMAC-SHA256 test".parse().unwrap(),
+			);
+			Box::pin(async { Ok(()) })
+		}
+	}
+
+	#[tokio::test]
+	async fn test_requests_are_signed() {
+		let signer = Arc::new(RecordingSigner::default());
+		let mock = MockSseClient::default();
+		let client = Client::<MockSseClient>::builder()
+			.api_key(BedrockAuth::signer(signer.clone()))
+			.region("eu-central-1")
+			.http_client(mock.clone())
+			.build()
+			.unwrap();
+
+		let model = client.completion_model("arn:aws:bedrock:eu-central-1:123456789012:inference-profile/eu.anthropic.claude-sonnet-4-20250514-v1:0");
+		// The mock answers non-streaming requests with an error, only the request matters here
+		let _ = model
+			.completion(model.completion_request("Hello").build())
+			.await;
+
+		assert_eq!(*signer.regions.lock().unwrap(), vec!["eu-central-1"]);
+		assert_eq!(
+			mock.request_uris(),
+			vec![
+				"https://bedrock-runtime.eu-central-1.amazonaws.com/model/arn%3Aaws%3Abedrock%3Aeu-central-1%3A123456789012%3Ainference-profile%2Feu.anthropic.claude-sonnet-4-20250514-v1%3A0/converse"
+			]
+		);
+		assert_eq!(
+			mock.request_headers()[0][http::header::AUTHORIZATION],
+			"AWS4-HMAC-SHA256 test"
+		);
+	}
+}
@@ -0,0 +1,93 @@
+//! Classification of Bedrock error responses.
+use http::StatusCode;
+use serde::Deserialize;
+
+use crate::completion::{ApiError, ProviderErrorKind};
+
+/// `{ "message": "..." }`, the type of the error is sent in the `x-amzn-ErrorType` header
+#[derive(Deserialize)]
+struct ErrorBody {
+	#[serde(alias = "Message")]
+	message: String,
+}
+
+/// Classifies a Bedrock error response from its status and body.
+pub fn parse_api_error(status: StatusCode, body: String) -> ApiError {
+	let mut error = ApiError::new(status, body);
+	let Ok(ErrorBody { message }) = serde_json::from_str(&error.body) else {
+		return error;
+	};
+
+	if status == StatusCode::BAD_REQUEST && is_context_length_message(&message) {
+		error.kind = ProviderErrorKind::ContextLengthExceeded;
+	}
+	error.message = message;
+	error
+}
+
+/// Classifies an exception sent in the middle of a ConverseStream response, e.g.
+/// `throttlingException`, from its type and payload.
+pub fn parse_stream_exception(exception_type: &str, payload: String) -> ApiError {
+	let status = match exception_type {
+		"throttlingException" => StatusCode::TOO_MANY_REQUESTS,
+		"serviceUnavailableException" => StatusCode::SERVICE_UNAVAILABLE,
+		"validationException" => StatusCode::BAD_REQUEST,
+		"modelStreamErrorException" => StatusCode::FAILED_DEPENDENCY,
+		_ => StatusCode::INTERNAL_SERVER_ERROR,
+	};
+
+	parse_api_error(status, payload)
+}
+
+fn is_context_length_message(message: &str) -> bool {
+	let message = message.to_lowercase();
+	message.contains("input is too long") || message.contains("too many input tokens")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_throttling() {
+		let body = r#"{"message":"Too many requests, please wait before trying again."}"#;
+
+		let error = parse_api_error(StatusCode::TOO_MANY_REQUESTS, body.to_string());
+		assert_eq!(
+			error.kind,
+			ProviderErrorKind::RateLimited { retry_after: None }
+		);
+		assert_eq!(
+			error.message,
+			"Too many requests, please wait before trying again."
+		);
+	}
+
+	#[test]
+	fn test_access_denied() {
+		let body =
+			r#"{"Message":"You don't have access to the model with the specified model ID."}"#;
+
+		let error = parse_api_error(StatusCode::FORBIDDEN, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::AuthenticationFailed);
+		assert!(error.message.starts_with("You don't have access"));
+	}
+
+	#[test]
+	fn test_input_too_long() {
+		let body = r#"{"message":"Input is too long for requested model."}"#;
+
+		let error = parse_api_error(StatusCode::BAD_REQUEST, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::ContextLengthExceeded);
+	}
+
+	#[test]
+	fn test_stream_exception() {
+		let error = parse_stream_exception(
+			"serviceUnavailableException",
+			r#"{"message":"Bedrock is unable to process your request."}"#.to_string(),
+		);
+		assert_eq!(error.kind, ProviderErrorKind::Overloaded);
+		assert_eq!(error.message, "Bedrock is unable to process your request.");
+	}
+}
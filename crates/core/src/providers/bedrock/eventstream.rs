@@ -0,0 +1,265 @@
+//! Decoder for the `application/vnd.amazon.eventstream` framing of ConverseStream responses.
+//!
+//! Each message is laid out as:
+//! - total length (u32), headers length (u32) and the CRC32 of these 8 bytes (u32)
+//! - the headers, each a name (u8 length + bytes), a type (u8) and a value
+//! - the payload
+//! - the CRC32 of everything before it (u32)
+//!
+//! All integers are big endian.
+
+use bytes::{Buf, Bytes, BytesMut};
+
+const PRELUDE_LENGTH: usize = 12;
+const CRC_LENGTH: usize = 4;
+/// Messages are capped at 16 MB by the format
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum EventStreamError {
+	#[error("Invalid event stream message length: {0}")]
+	InvalidLength(usize),
+	#[error("Event stream checksum mismatch")]
+	ChecksumMismatch,
+	#[error("Invalid event stream header: {0}")]
+	InvalidHeader(String),
+	#[error("Event stream ended in the middle of a message")]
+	Truncated,
+}
+
+/// A decoded message. Only string headers are kept, which is all Bedrock sends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+	pub headers: Vec<(String, String)>,
+	pub payload: Bytes,
+}
+
+impl Message {
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers
+			.iter()
+			.find(|(header, _)| header == name)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+/// Incrementally decodes messages from the chunks of a response body.
+#[derive(Debug, Default)]
+pub struct Decoder {
+	buffer: BytesMut,
+}
+
+impl Decoder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(&mut self, chunk: &[u8]) {
+		self.buffer.extend_from_slice(chunk);
+	}
+
+	/// Decodes the next complete message, or returns `None` until enough bytes were pushed.
+	pub fn next_message(&mut self) -> Result<Option<Message>, EventStreamError> {
+		if self.buffer.len() < PRELUDE_LENGTH {
+			return Ok(None);
+		}
+
+		let total_length = read_u32(&self.buffer[0..4]) as usize;
+		let headers_length = read_u32(&self.buffer[4..8]) as usize;
+
+		if total_length > MAX_MESSAGE_LENGTH
+			|| total_length < PRELUDE_LENGTH + headers_length + CRC_LENGTH
+		{
+			return Err(EventStreamError::InvalidLength(total_length));
+		}
+		if crc32(&self.buffer[0..8]) != read_u32(&self.buffer[8..12]) {
+			return Err(EventStreamError::ChecksumMismatch);
+		}
+		if self.buffer.len() < total_length {
+			return Ok(None);
+		}
+
+		let mut message = self.buffer.split_to(total_length).freeze();
+		let checksum = read_u32(&message[total_length - CRC_LENGTH..]);
+		if crc32(&message[..total_length - CRC_LENGTH]) != checksum {
+			return Err(EventStreamError::ChecksumMismatch);
+		}
+
+		message.advance(PRELUDE_LENGTH);
+		let headers = parse_headers(message.split_to(headers_length))?;
+		message.truncate(message.len() - CRC_LENGTH);
+
+		Ok(Some(Message {
+			headers,
+			payload: message,
+		}))
+	}
+
+	/// Fails if the stream ended with an incomplete message.
+	pub fn finish(&self) -> Result<(), EventStreamError> {
+		if self.buffer.is_empty() {
+			Ok(())
+		} else {
+			Err(EventStreamError::Truncated)
+		}
+	}
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+	u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn parse_headers(mut bytes: Bytes) -> Result<Vec<(String, String)>, EventStreamError> {
+	let mut headers = Vec::new();
+
+	while bytes.has_remaining() {
+		let name_length = bytes.get_u8() as usize;
+		let name = take_string(&mut bytes, name_length)?;
+
+		if !bytes.has_remaining() {
+			return Err(EventStreamError::InvalidHeader(name));
+		}
+
+		// Sizes of the fixed length value types, strings and byte arrays are length prefixed
+		let value_length = match bytes.get_u8() {
+			0 | 1 => 0,
+			2 => 1,
+			3 => 2,
+			4 => 4,
+			5 | 8 => 8,
+			9 => 16,
+			6 | 7 => {
+				if bytes.remaining() < 2 {
+					return Err(EventStreamError::InvalidHeader(name));
+				}
+				let length = bytes.get_u16() as usize;
+				if bytes.remaining() < length {
+					return Err(EventStreamError::InvalidHeader(name));
+				}
+				let value = bytes.split_to(length);
+				headers.push((name, String::from_utf8_lossy(&value).into_owned()));
+				continue;
+			}
+			_ => return Err(EventStreamError::InvalidHeader(name)),
+		};
+
+		if bytes.remaining() < value_length {
+			return Err(EventStreamError::InvalidHeader(name));
+		}
+		bytes.advance(value_length);
+	}
+
+	Ok(headers)
+}
+
+fn take_string(bytes: &mut Bytes, length: usize) -> Result<String, EventStreamError> {
+	if bytes.remaining() < length {
+		return Err(EventStreamError::InvalidHeader(
+			String::from_utf8_lossy(bytes).into_owned(),
+		));
+	}
+
+	Ok(String::from_utf8_lossy(&bytes.split_to(length)).into_owned())
+}
+
+/// CRC32 (IEEE 802.3)
+fn crc32(bytes: &[u8]) -> u32 {
+	!bytes.iter().fold(u32::MAX, |crc, &byte| {
+		(0..8).fold(crc ^ byte as u32, |crc, _| {
+			if crc & 1 == 1 {
+				(crc >> 1) ^ 0xEDB8_8320
+			} else {
+				crc >> 1
+			}
+		})
+	})
+}
+
+/// Encodes a message with string headers, used to build test payloads.
+#[cfg(test)]
+pub(crate) fn encode(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+	let mut encoded_headers = Vec::new();
+	for (name, value) in headers {
+		encoded_headers.push(name.len() as u8);
+		encoded_headers.extend_from_slice(name.as_bytes());
+		encoded_headers.push(7);
+		encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+		encoded_headers.extend_from_slice(value.as_bytes());
+	}
+
+	let total_length = PRELUDE_LENGTH + encoded_headers.len() + payload.len() + CRC_LENGTH;
+	let mut message = Vec::with_capacity(total_length);
+	message.extend_from_slice(&(total_length as u32).to_be_bytes());
+	message.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+	message.extend_from_slice(&crc32(&message).to_be_bytes());
+	message.extend_from_slice(&encoded_headers);
+	message.extend_from_slice(payload);
+	message.extend_from_slice(&crc32(&message).to_be_bytes());
+	message
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_crc32() {
+		assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+	}
+
+	#[test]
+	fn test_decode_split_messages() {
+		let mut bytes = encode(
+			&[
+				(":event-type", "messageStart"),
+				(":content-type", "application/json"),
+				(":message-type", "event"),
+			],
+			br#"{"p":"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVW","role":"assistant"}"#,
+		);
+		bytes.extend(encode(
+			&[(":event-type", "messageStop"), (":message-type", "event")],
+			br#"{"stopReason":"end_turn"}"#,
+		));
+
+		let mut decoder = Decoder::new();
+		let mut messages = Vec::new();
+		// Feed the bytes in small chunks, splitting the prelude and headers
+		for chunk in bytes.chunks(7) {
+			decoder.push(chunk);
+			while let Some(message) = decoder.next_message().unwrap() {
+				messages.push(message);
+			}
+		}
+		decoder.finish().unwrap();
+
+		assert_eq!(messages.len(), 2);
+		assert_eq!(messages[0].header(":event-type"), Some("messageStart"));
+		assert_eq!(
+			messages[0].header(":content-type"),
+			Some("application/json")
+		);
+		assert!(messages[0].payload.ends_with(br#""role":"assistant"}"#));
+		assert_eq!(messages[1].header(":event-type"), Some("messageStop"));
+		assert_eq!(&messages[1].payload[..], br#"{"stopReason":"end_turn"}"#);
+	}
+
+	#[test]
+	fn test_decode_rejects_corrupted_messages() {
+		let mut bytes = encode(&[(":event-type", "messageStop")], b"{}");
+		let last = bytes.len() - 5;
+		bytes[last] ^= 0xFF;
+
+		let mut decoder = Decoder::new();
+		decoder.push(&bytes);
+		assert_eq!(
+			decoder.next_message(),
+			Err(EventStreamError::ChecksumMismatch)
+		);
+
+		let mut decoder = Decoder::new();
+		decoder.push(&encode(&[], b"{}")[..10]);
+		assert_eq!(decoder.next_message(), Ok(None));
+		assert_eq!(decoder.finish(), Err(EventStreamError::Truncated));
+	}
+}
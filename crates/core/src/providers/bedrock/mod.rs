@@ -0,0 +1,40 @@
+//! Amazon Bedrock client and Clankers integration, using the Converse API.
+//!
+//! Requests are authenticated either with a Bedrock API key or by signing them with IAM
+//! credentials through a [RequestSigner], see the [signer] module.
+//!
+//! # Example
+//! ```
+//! use clankers::providers::bedrock;
+//!
+//! // With a Bedrock API key
+//! let client = bedrock::Client::builder()
+//!     .api_key("YOUR_API_KEY")
+//!     .region("us-west-2")
+//!     .build()?;
+//!
+//! // Or with IAM credentials, using the `bedrock-sigv4` feature
+//! let client = bedrock::Client::builder()
+//!     .api_key(bedrock::BedrockAuth::signer(bedrock::SigV4Signer::from_env().unwrap()))
+//!     .region("us-west-2")
+//!     .build()?;
+//!
+//! let sonnet = client.completion_model(bedrock::CLAUDE_SONNET_4);
+//! ```
+
+pub mod client;
+pub mod completion;
+pub mod error;
+mod eventstream;
+pub mod signer;
+pub mod streaming;
+pub mod types;
+
+pub use client::{BedrockAuth, Client, ClientBuilder};
+pub use completion::{
+	CLAUDE_3_5_HAIKU, CLAUDE_3_7_SONNET, CLAUDE_OPUS_4_1, CLAUDE_SONNET_4, CompletionModel,
+	LLAMA_3_1_8B, LLAMA_3_3_70B, LLAMA_4_MAVERICK_17B, NOVA_LITE, NOVA_PRO,
+};
+pub use signer::RequestSigner;
+#[cfg(feature = "bedrock-sigv4")]
+pub use signer::{Credentials, SigV4Signer};
@@ -0,0 +1,462 @@
+//! Request signing for Bedrock.
+//!
+//! Requests authenticated with IAM credentials must be signed with AWS Signature Version 4.
+//! Signing is pluggable through the [RequestSigner] trait so that applications already using the
+//! AWS SDK can sign with its credential providers (profiles, SSO, instance roles...), e.g. by
+//! wrapping `aws-sigv4`:
+//!
+//! ```rust,ignore
+//! // Not compiled: it needs the `aws-sigv4` and `aws-credential-types` crates
+//! use std::time::SystemTime;
+//!
+//! use aws_credential_types::provider::{ProvideCredentials, SharedCredentialsProvider};
+//! use aws_sigv4::http_request::{SignableBody, SignableRequest, SigningSettings, sign};
+//! use aws_sigv4::sign::v4;
+//! use clankers::http_client;
+//! use clankers::providers::bedrock::{RequestSigner, SIGNING_SERVICE};
+//! use clankers::wasm_compat::WasmBoxedFuture;
+//!
+//! fn signing_error(error: impl std::error::Error + Send + Sync + 'static) -> http_client::Error {
+//!     http_client::Error::Instance(Box::new(error))
+//! }
+//!
+//! #[derive(Debug)]
+//! struct SdkSigner(SharedCredentialsProvider);
+//!
+//! impl RequestSigner for SdkSigner {
+//!     fn sign<'a>(
+//!         &'a self,
+//!         request: &'a mut http::Request<Vec<u8>>,
+//!         region: &'a str,
+//!     ) -> WasmBoxedFuture<'a, http_client::Result<()>> {
+//!         Box::pin(async move {
+//!             let identity = self.0.provide_credentials().await.map_err(signing_error)?.into();
+//!             let params = v4::SigningParams::builder()
+//!                 .identity(&identity)
+//!                 .region(region)
+//!                 .name(SIGNING_SERVICE)
+//!                 .time(SystemTime::now())
+//!                 .settings(SigningSettings::default())
+//!                 .build()
+//!                 .map_err(signing_error)?
+//!                 .into();
+//!
+//!             let headers = request
+//!                 .headers()
+//!                 .iter()
+//!                 .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)));
+//!             let signable = SignableRequest::new(
+//!                 request.method().as_str(),
+//!                 request.uri().to_string(),
+//!                 headers,
+//!                 SignableBody::Bytes(request.body()),
+//!             )
+//!             .map_err(signing_error)?;
+//!
+//!             let (instructions, _) = sign(signable, &params).map_err(signing_error)?.into_parts();
+//!             instructions.apply_to_request_http1x(request);
+//!             Ok(())
+//!         })
+//!     }
+//! }
+//! ```
+//!
+//! The `bedrock-sigv4` feature provides [SigV4Signer], a minimal signer using static credentials.
+
+use std::fmt::Debug;
+
+use crate::http_client::{self, Request};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
+
+/// The name of the Bedrock service in AWS signatures.
+pub const SIGNING_SERVICE: &str = "bedrock";
+
+/// Signs Bedrock requests, usually with AWS Signature Version 4.
+pub trait RequestSigner: Debug + WasmCompatSend + WasmCompatSync {
+	/// Signs `request` for the [SIGNING_SERVICE] service in `region`, adding the authentication
+	/// headers to it. The request is complete (URI, headers and body) and is sent as is once signed.
+	fn sign<'a>(
+		&'a self,
+		request: &'a mut Request<Vec<u8>>,
+		region: &'a str,
+	) -> WasmBoxedFuture<'a, http_client::Result<()>>;
+}
+
+#[cfg(feature = "bedrock-sigv4")]
+pub use sigv4::{Credentials, SigV4Signer};
+
+#[cfg(feature = "bedrock-sigv4")]
+mod sigv4 {
+	use std::time::SystemTime;
+
+	use hmac::{Hmac, Mac};
+	use http::{HeaderName, HeaderValue};
+	use sha2::{Digest, Sha256};
+
+	use super::{RequestSigner, SIGNING_SERVICE};
+	use crate::http_client::{self, Request};
+	use crate::wasm_compat::{self, WasmBoxedFuture};
+
+	const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+	/// Static AWS credentials.
+	#[derive(Clone)]
+	pub struct Credentials {
+		pub access_key_id: String,
+		pub secret_access_key: String,
+		/// Session token of temporary credentials
+		pub session_token: Option<String>,
+	}
+
+	impl Credentials {
+		pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+			Self {
+				access_key_id: access_key_id.into(),
+				secret_access_key: secret_access_key.into(),
+				session_token: None,
+			}
+		}
+
+		pub fn with_session_token(mut self, session_token: impl Into<String>) -> Self {
+			self.session_token = Some(session_token.into());
+			self
+		}
+
+		/// Reads `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and the optional `AWS_SESSION_TOKEN`.
+		/// Returns `None` if the key id or secret is not set.
+		pub fn from_env() -> Option<Self> {
+			let credentials = Self::new(
+				std::env::var("AWS_ACCESS_KEY_ID").ok()?,
+				std::env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+			);
+
+			Some(match std::env::var("AWS_SESSION_TOKEN") {
+				Ok(token) if !token.is_empty() => credentials.with_session_token(token),
+				_ => credentials,
+			})
+		}
+	}
+
+	impl std::fmt::Debug for Credentials {
+		fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+			f.debug_struct("Credentials")
+				.field("access_key_id", &self.access_key_id)
+				.field("secret_access_key", &"<REDACTED>")
+				.field(
+					"session_token",
+					&self.session_token.as_ref().map(|_| "<REDACTED>"),
+				)
+				.finish()
+		}
+	}
+
+	/// A minimal AWS Signature Version 4 signer using static [Credentials].
+	/// Credentials are not refreshed, use a [RequestSigner] backed by the AWS SDK for that.
+	#[derive(Clone, Debug)]
+	pub struct SigV4Signer {
+		credentials: Credentials,
+	}
+
+	impl SigV4Signer {
+		pub fn new(credentials: Credentials) -> Self {
+			Self { credentials }
+		}
+
+		/// Creates a signer from the credentials in the environment, see [Credentials::from_env].
+		pub fn from_env() -> Option<Self> {
+			Credentials::from_env().map(Self::new)
+		}
+	}
+
+	impl RequestSigner for SigV4Signer {
+		fn sign<'a>(
+			&'a self,
+			request: &'a mut Request<Vec<u8>>,
+			region: &'a str,
+		) -> WasmBoxedFuture<'a, http_client::Result<()>> {
+			let result = sign_request(
+				request,
+				&self.credentials,
+				region,
+				SIGNING_SERVICE,
+				wasm_compat::system_time_now(),
+			);
+
+			Box::pin(async move { result })
+		}
+	}
+
+	/// Signs `request`, adding the `x-amz-date`, `x-amz-security-token` and `authorization`
+	/// headers. The host and every header already on the request are signed.
+	pub(super) fn sign_request(
+		request: &mut Request<Vec<u8>>,
+		credentials: &Credentials,
+		region: &str,
+		service: &str,
+		time: SystemTime,
+	) -> http_client::Result<()> {
+		let (date, date_time) = format_time(time);
+		let headers = request.headers_mut();
+
+		headers.remove(http::header::AUTHORIZATION);
+		headers.insert("x-amz-date", HeaderValue::from_str(&date_time)?);
+		if let Some(token) = &credentials.session_token {
+			headers.insert("x-amz-security-token", HeaderValue::from_str(token)?);
+		}
+
+		let mut canonical_headers = request
+			.headers()
+			.iter()
+			.map(|(name, value)| {
+				let value = String::from_utf8_lossy(value.as_bytes());
+				(
+					name.as_str().to_string(),
+					value.split_whitespace().collect::<Vec<_>>().join(" "),
+				)
+			})
+			.collect::<Vec<_>>();
+		if !request.headers().contains_key(http::header::HOST) {
+			canonical_headers.push(("host".to_string(), host(request.uri())?));
+		}
+		canonical_headers.sort();
+
+		let signed_headers = canonical_headers
+			.iter()
+			.map(|(name, _)| name.as_str())
+			.collect::<Vec<_>>()
+			.join(";");
+
+		let canonical_request = [
+			request.method().as_str().to_string(),
+			canonical_uri(request.uri().path()),
+			canonical_query(request.uri().query()),
+			canonical_headers
+				.iter()
+				.map(|(name, value)| format!("{name}:{value}\n"))
+				.collect(),
+			signed_headers.clone(),
+			hex(&Sha256::digest(request.body())),
+		]
+		.join("\n");
+
+		let scope = format!("{date}/{region}/{service}/aws4_request");
+		let string_to_sign = format!(
+			"{ALGORITHM}\n{date_time}\n{scope}\n{}",
+			hex(&Sha256::digest(canonical_request.as_bytes()))
+		);
+
+		let key = [region, service, "aws4_request"].into_iter().fold(
+			hmac(
+				format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+				date.as_bytes(),
+			),
+			|key, part| hmac(&key, part.as_bytes()),
+		);
+		let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+		request.headers_mut().insert(
+			HeaderName::from_static("authorization"),
+			HeaderValue::from_str(&format!(
+				"{ALGORITHM} Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+				credentials.access_key_id
+			))?,
+		);
+
+		Ok(())
+	}
+
+	fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+		let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+		mac.update(data);
+		mac.finalize().into_bytes().to_vec()
+	}
+
+	fn hex(bytes: &[u8]) -> String {
+		bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+	}
+
+	/// The `host` header sent by the HTTP client, without the default port of the scheme.
+	fn host(uri: &http::Uri) -> http_client::Result<String> {
+		let authority = uri.authority().ok_or_else(|| {
+			http_client::Error::Instance(format!("Request URI `{uri}` has no host").into())
+		})?;
+
+		Ok(match (uri.scheme_str(), authority.port_u16()) {
+			(Some("https"), Some(443)) | (Some("http"), Some(80)) => authority.host().to_string(),
+			_ => authority.as_str().to_string(),
+		})
+	}
+
+	/// Percent-encodes everything but the unreserved characters of RFC 3986.
+	fn uri_encode(input: &str) -> String {
+		input
+			.bytes()
+			.map(|byte| match byte {
+				b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+					(byte as char).to_string()
+				}
+				_ => format!("%{byte:02X}"),
+			})
+			.collect()
+	}
+
+	/// Every service but S3 encodes the (already encoded) path segments a second time.
+	fn canonical_uri(path: &str) -> String {
+		if path.is_empty() {
+			return "/".to_string();
+		}
+
+		path.split('/')
+			.map(uri_encode)
+			.collect::<Vec<_>>()
+			.join("/")
+	}
+
+	fn canonical_query(query: Option<&str>) -> String {
+		let mut params = url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+			.map(|(name, value)| (uri_encode(&name), uri_encode(&value)))
+			.collect::<Vec<_>>();
+		params.sort();
+
+		params
+			.into_iter()
+			.map(|(name, value)| format!("{name}={value}"))
+			.collect::<Vec<_>>()
+			.join("&")
+	}
+
+	/// Formats `time` as the `YYYYMMDD` date and `YYYYMMDD'T'HHMMSS'Z'` timestamp of signatures.
+	fn format_time(time: SystemTime) -> (String, String) {
+		let seconds = time
+			.duration_since(SystemTime::UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+		let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+		// Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+		let z = days as i64 + 719_468;
+		let era = z.div_euclid(146_097);
+		let day_of_era = z.rem_euclid(146_097);
+		let year_of_era =
+			(day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+		let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+		let mp = (5 * day_of_year + 2) / 153;
+		let day = day_of_year - (153 * mp + 2) / 5 + 1;
+		let month = if mp < 10 { mp + 3 } else { mp - 9 };
+		let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+		let date = format!("{year:04}{month:02}{day:02}");
+		let date_time = format!(
+			"{date}T{:02}{:02}{:02}Z",
+			seconds / 3600,
+			seconds % 3600 / 60,
+			seconds % 60
+		);
+
+		(date, date_time)
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use std::time::Duration;
+
+		use super::*;
+
+		fn credentials() -> Credentials {
+			Credentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY")
+		}
+
+		/// 2015-08-30T12:36:00Z, the date of the AWS Signature Version 4 test suite
+		fn test_suite_time() -> SystemTime {
+			SystemTime::UNIX_EPOCH + Duration::from_secs(1_440_938_160)
+		}
+
+		#[test]
+		fn test_format_time() {
+			assert_eq!(
+				format_time(test_suite_time()),
+				("20150830".to_string(), "20150830T123600Z".to_string())
+			);
+			assert_eq!(
+				format_time(SystemTime::UNIX_EPOCH + Duration::from_secs(951_825_600)).1,
+				"20000229T120000Z"
+			);
+		}
+
+		#[test]
+		fn test_get_vanilla() {
+			let mut request = Request::get("https://example.amazonaws.com/")
+				.body(Vec::new())
+				.unwrap();
+
+			sign_request(
+				&mut request,
+				&credentials(),
+				"us-east-1",
+				"service",
+				test_suite_time(),
+			)
+			.unwrap();
+
+			assert_eq!(request.headers()["x-amz-date"], "20150830T123600Z");
+			assert_eq!(
+				request.headers()["authorization"],
+				"AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+			);
+		}
+
+		#[test]
+		fn test_post_vanilla() {
+			let mut request = Request::post("https://example.amazonaws.com/")
+				.body(Vec::new())
+				.unwrap();
+
+			sign_request(
+				&mut request,
+				&credentials(),
+				"us-east-1",
+				"service",
+				test_suite_time(),
+			)
+			.unwrap();
+
+			assert_eq!(
+				request.headers()["authorization"],
+				"AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+			);
+		}
+
+		#[test]
+		fn test_canonical_uri_encodes_model_ids_twice() {
+			assert_eq!(
+				canonical_uri("/model/anthropic.claude-3-5-haiku-20241022-v1%3A0/converse"),
+				"/model/anthropic.claude-3-5-haiku-20241022-v1%253A0/converse"
+			);
+		}
+
+		#[test]
+		fn test_session_token_is_signed() {
+			let mut request = Request::post(
+				"https://bedrock-runtime.us-east-1.amazonaws.com/model/meta.llama3-3-70b-instruct-v1%3A0/converse",
+			)
+			.header("content-type", "application/json")
+			.body(b"{}".to_vec())
+			.unwrap();
+
+			sign_request(
+				&mut request,
+				&credentials().with_session_token("session"),
+				"us-east-1",
+				SIGNING_SERVICE,
+				test_suite_time(),
+			)
+			.unwrap();
+
+			assert_eq!(request.headers()["x-amz-security-token"], "session");
+			let authorization = request.headers()["authorization"].to_str().unwrap();
+			assert!(authorization.starts_with(
+				"AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/bedrock/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-security-token, Signature="
+			));
+		}
+	}
+}
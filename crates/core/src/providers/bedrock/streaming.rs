@@ -0,0 +1,562 @@
+//! ConverseStream support.
+//!
+//! Responses are sent as `application/vnd.amazon.eventstream` messages, each carrying the type of
+//! the event in its `:event-type` header and the event itself as a JSON payload.
+
+use async_stream::stream;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::{Level, enabled, info_span};
+use tracing_futures::Instrument;
+
+use super::completion::{CompletionModel, PROVIDER_NAME};
+use super::error::{parse_api_error, parse_stream_exception};
+use super::eventstream::{self, Decoder};
+use super::types::{
+	ContentBlock, ConverseRequest, Message, Role, StopReason, TokenUsage, ToolUseBlock,
+};
+use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage};
+use crate::http_client::HttpClientExt;
+use crate::streaming::{
+	self, RawStreamingChoice, RawStreamingToolCall, StreamingResult, ToolCallDeltaContent,
+};
+use crate::telemetry::SpanCombinator;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockStartEvent {
+	pub content_block_index: usize,
+	pub start: ContentBlockStart,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentBlockStart {
+	ToolUse(ToolUseStart),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUseStart {
+	pub tool_use_id: String,
+	pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentBlockDeltaEvent {
+	pub content_block_index: usize,
+	pub delta: ContentBlockDelta,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentBlockDelta {
+	Text(String),
+	/// A chunk of the JSON input of a tool call
+	ToolUse {
+		input: String,
+	},
+	ReasoningContent(ReasoningDelta),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReasoningDelta {
+	Text(String),
+	Signature(String),
+	RedactedContent(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStopEvent {
+	pub stop_reason: StopReason,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataEvent {
+	pub usage: TokenUsage,
+}
+
+/// An event of a ConverseStream response.
+#[derive(Debug)]
+pub enum StreamingEvent {
+	MessageStart,
+	ContentBlockStart(ContentBlockStartEvent),
+	ContentBlockDelta(ContentBlockDeltaEvent),
+	ContentBlockStop,
+	MessageStop(MessageStopEvent),
+	Metadata(MetadataEvent),
+	Unknown,
+}
+
+impl StreamingEvent {
+	/// Parses an event from its type and payload.
+	pub fn parse(event_type: &str, payload: &[u8]) -> Result<Self, serde_json::Error> {
+		Ok(match event_type {
+			"messageStart" => Self::MessageStart,
+			"contentBlockStart" => Self::ContentBlockStart(serde_json::from_slice(payload)?),
+			"contentBlockDelta" => Self::ContentBlockDelta(serde_json::from_slice(payload)?),
+			"contentBlockStop" => Self::ContentBlockStop,
+			"messageStop" => Self::MessageStop(serde_json::from_slice(payload)?),
+			"metadata" => Self::Metadata(serde_json::from_slice(payload)?),
+			_ => Self::Unknown,
+		})
+	}
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct StreamingCompletionResponse {
+	pub usage: TokenUsage,
+	pub stop_reason: Option<StopReason>,
+}
+
+impl GetTokenUsage for StreamingCompletionResponse {
+	fn token_usage(&self) -> Option<completion::Usage> {
+		self.usage.token_usage()
+	}
+}
+
+#[derive(Default)]
+struct ToolCallState {
+	id: String,
+	name: String,
+	internal_call_id: String,
+	input_json: String,
+}
+
+#[derive(Default)]
+struct ReasoningState {
+	text: String,
+	signature: String,
+}
+
+/// The state of the content block being streamed, blocks are streamed one at a time.
+#[derive(Default)]
+struct StreamState {
+	tool_call: Option<ToolCallState>,
+	reasoning: Option<ReasoningState>,
+	text: String,
+	tool_uses: Vec<ContentBlock>,
+}
+
+impl<T> CompletionModel<T>
+where
+	T: HttpClientExt + Clone + Default + 'static,
+{
+	pub(crate) async fn stream(
+		&self,
+		completion_request: CompletionRequest,
+	) -> Result<streaming::StreamingCompletionResponse<StreamingCompletionResponse>, CompletionError>
+	{
+		let span = if tracing::Span::current().is_disabled() {
+			info_span!(
+				target: "clankers::completions",
+				"chat_streaming",
+				gen_ai.operation.name = "chat_streaming",
+				gen_ai.provider.name = PROVIDER_NAME,
+				gen_ai.request.model = self.model,
+				gen_ai.system_instructions = &completion_request.preamble,
+				gen_ai.response.id = tracing::field::Empty,
+				gen_ai.response.model = self.model,
+				gen_ai.usage.output_tokens = tracing::field::Empty,
+				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
+			)
+		} else {
+			tracing::Span::current()
+		};
+
+		let request = ConverseRequest::try_from(completion_request)?;
+		span.record_input_messages(&request.messages);
+
+		if enabled!(Level::TRACE) {
+			tracing::trace!(
+				target: "clankers::completions",
+				"Bedrock streaming completion request: {}",
+				serde_json::to_string_pretty(&request)?
+			);
+		}
+
+		let req = self.request("converse-stream", &request).await?;
+		let response = self
+			.client
+			.send_streaming(req)
+			.await
+			.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+		let response_metadata = completion::ResponseMetadata::from_response(&response);
+		let cost = self.client.cost_recorder(PROVIDER_NAME, &self.model);
+
+		let stream: StreamingResult<StreamingCompletionResponse> = Box::pin(
+			stream! {
+				if let Some(metadata) = response_metadata {
+					yield Ok(RawStreamingChoice::ResponseMetadata(metadata));
+				}
+
+				let mut body = response.into_body();
+				let mut decoder = Decoder::new();
+				let mut state = StreamState::default();
+				let mut final_response = StreamingCompletionResponse::default();
+
+				while let Some(chunk) = body.next().await {
+					match chunk {
+						Ok(chunk) => decoder.push(&chunk),
+						Err(error) => {
+							yield Err(CompletionError::from_stream_error(error, parse_api_error));
+							return;
+						}
+					}
+
+					loop {
+						let message = match decoder.next_message() {
+							Ok(Some(message)) => message,
+							Ok(None) => break,
+							Err(error) => {
+								yield Err(CompletionError::ResponseError(error.to_string()));
+								return;
+							}
+						};
+
+						match handle_message(&message, &mut state, &mut final_response) {
+							Ok(Some(choice)) => yield Ok(choice),
+							Ok(None) => {}
+							Err(error) => {
+								yield Err(error);
+								return;
+							}
+						}
+					}
+				}
+
+				if let Err(error) = decoder.finish() {
+					yield Err(CompletionError::ResponseError(error.to_string()));
+					return;
+				}
+
+				let span = tracing::Span::current();
+				span.record_token_usage(&final_response.usage);
+				cost.record(&span, &final_response.usage);
+
+				let mut content = Vec::new();
+				if !state.text.is_empty() {
+					content.push(ContentBlock::Text(state.text));
+				}
+				content.extend(state.tool_uses);
+				span.record_output_messages(&[Message { role: Role::Assistant, content }]);
+
+				yield Ok(RawStreamingChoice::FinalResponse(final_response));
+			}
+			.instrument(span),
+		);
+
+		Ok(streaming::StreamingCompletionResponse::stream(stream))
+	}
+}
+
+fn handle_message(
+	message: &eventstream::Message,
+	state: &mut StreamState,
+	final_response: &mut StreamingCompletionResponse,
+) -> Result<Option<RawStreamingChoice<StreamingCompletionResponse>>, CompletionError> {
+	let payload = &message.payload;
+
+	match message.header(":message-type") {
+		Some("event") => {}
+		Some("exception") => {
+			let exception_type = message.header(":exception-type").unwrap_or_default();
+			return Err(CompletionError::ApiError(parse_stream_exception(
+				exception_type,
+				String::from_utf8_lossy(payload).into_owned(),
+			)));
+		}
+		_ => {
+			return Err(CompletionError::ProviderError(format!(
+				"Unexpected ConverseStream message: {}",
+				String::from_utf8_lossy(payload)
+			)));
+		}
+	}
+
+	let event_type = message.header(":event-type").unwrap_or_default();
+	let event = StreamingEvent::parse(event_type, payload).map_err(|e| {
+		CompletionError::ResponseError(format!(
+			"Failed to parse {event_type} event: {e} (Data: {})",
+			String::from_utf8_lossy(payload)
+		))
+	})?;
+
+	Ok(match event {
+		StreamingEvent::ContentBlockStart(ContentBlockStartEvent {
+			start: ContentBlockStart::ToolUse(ToolUseStart { tool_use_id, name }),
+			..
+		}) => {
+			let internal_call_id = nanoid::nanoid!();
+			state.tool_call = Some(ToolCallState {
+				id: tool_use_id.clone(),
+				name: name.clone(),
+				internal_call_id: internal_call_id.clone(),
+				input_json: String::new(),
+			});
+
+			Some(RawStreamingChoice::ToolCallDelta {
+				id: tool_use_id,
+				internal_call_id,
+				content: ToolCallDeltaContent::Name(name),
+			})
+		}
+		StreamingEvent::ContentBlockDelta(ContentBlockDeltaEvent { delta, .. }) => match delta {
+			ContentBlockDelta::Text(text) => {
+				state.text.push_str(&text);
+				Some(RawStreamingChoice::Message(text))
+			}
+			ContentBlockDelta::ToolUse { input } => state.tool_call.as_mut().map(|tool_call| {
+				tool_call.input_json.push_str(&input);
+				RawStreamingChoice::ToolCallDelta {
+					id: tool_call.id.clone(),
+					internal_call_id: tool_call.internal_call_id.clone(),
+					content: ToolCallDeltaContent::Delta(input),
+				}
+			}),
+			ContentBlockDelta::ReasoningContent(ReasoningDelta::Text(text)) => {
+				state
+					.reasoning
+					.get_or_insert_with(ReasoningState::default)
+					.text
+					.push_str(&text);
+				Some(RawStreamingChoice::ReasoningDelta {
+					id: None,
+					reasoning: text,
+				})
+			}
+			// Don't yield signature chunks, they will be included in the final Reasoning
+			ContentBlockDelta::ReasoningContent(ReasoningDelta::Signature(signature)) => {
+				state
+					.reasoning
+					.get_or_insert_with(ReasoningState::default)
+					.signature
+					.push_str(&signature);
+				None
+			}
+			ContentBlockDelta::ReasoningContent(ReasoningDelta::RedactedContent(_)) => None,
+		},
+		StreamingEvent::ContentBlockStop => {
+			if let Some(reasoning) = state.reasoning.take()
+				&& !reasoning.text.is_empty()
+			{
+				let signature = (!reasoning.signature.is_empty()).then_some(reasoning.signature);
+				return Ok(Some(RawStreamingChoice::Reasoning {
+					id: None,
+					reasoning: reasoning.text,
+					signature,
+				}));
+			}
+
+			match state.tool_call.take() {
+				Some(tool_call) => {
+					let input_json = if tool_call.input_json.is_empty() {
+						"{}"
+					} else {
+						&tool_call.input_json
+					};
+					let input: serde_json::Value = serde_json::from_str(input_json)?;
+
+					state.tool_uses.push(ContentBlock::ToolUse(ToolUseBlock {
+						tool_use_id: tool_call.id.clone(),
+						name: tool_call.name.clone(),
+						input: input.clone(),
+					}));
+
+					Some(RawStreamingChoice::ToolCall(
+						RawStreamingToolCall::new(tool_call.id, tool_call.name, input)
+							.with_internal_call_id(tool_call.internal_call_id),
+					))
+				}
+				None => None,
+			}
+		}
+		StreamingEvent::MessageStop(MessageStopEvent { stop_reason }) => {
+			final_response.stop_reason = Some(stop_reason);
+			None
+		}
+		StreamingEvent::Metadata(MetadataEvent { usage }) => {
+			final_response.usage = usage;
+			None
+		}
+		StreamingEvent::MessageStart | StreamingEvent::Unknown => None,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::client::CompletionClient;
+	use crate::completion::CompletionModel as _;
+	use crate::providers::bedrock::Client;
+	use crate::providers::bedrock::eventstream::encode;
+	use crate::streaming::StreamedAssistantContent;
+	use crate::test_utils::MockSseClient;
+
+	fn event(event_type: &str, payload: &str) -> Vec<u8> {
+		encode(
+			&[
+				(":event-type", event_type),
+				(":content-type", "application/json"),
+				(":message-type", "event"),
+			],
+			payload.as_bytes(),
+		)
+	}
+
+	fn client(body: Vec<u8>) -> (Client<MockSseClient>, MockSseClient) {
+		let mock = MockSseClient::new(body);
+		let client = Client::<MockSseClient>::builder()
+			.api_key("bedrock-api-key")
+			.region("us-west-2")
+			.http_client(mock.clone())
+			.build()
+			.unwrap();
+		(client, mock)
+	}
+
+	#[tokio::test]
+	async fn test_stream_text_and_tool_call() {
+		// Captured from a ConverseStream response of Claude 3.5 Haiku
+		let body = [
+			event("messageStart", r#"{"p":"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0","role":"assistant"}"#),
+			event("contentBlockDelta", r#"{"contentBlockIndex":0,"delta":{"text":"Let me check "},"p":"abcdefghijk"}"#),
+			event("contentBlockDelta", r#"{"contentBlockIndex":0,"delta":{"text":"the weather."},"p":"abcd"}"#),
+			event("contentBlockStop", r#"{"contentBlockIndex":0,"p":"abcdefghijklmnopqrstuvwx"}"#),
+			event("contentBlockStart", r#"{"contentBlockIndex":1,"p":"abcdefghijklmnopqrstuvwxyzABCDEF","start":{"toolUse":{"name":"get_weather","toolUseId":"tooluse_kZJMlvQmRJ6eAyJE5GIl7Q"}}}"#),
+			event("contentBlockDelta", r#"{"contentBlockIndex":1,"delta":{"toolUse":{"input":"{\"city\": \"Pa"}},"p":"abcdefghijklmnopq"}"#),
+			event("contentBlockDelta", r#"{"contentBlockIndex":1,"delta":{"toolUse":{"input":"ris\"}"}},"p":"abcdefghijklmnopqrstu"}"#),
+			event("contentBlockStop", r#"{"contentBlockIndex":1,"p":"abcdefghijklmnopqrstuvwxyzABCD"}"#),
+			event("messageStop", r#"{"p":"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKL","stopReason":"tool_use"}"#),
+			event("metadata", r#"{"metrics":{"latencyMs":1254},"p":"abcdefghijklmnopqrstuvwxyzABCDEFG","usage":{"inputTokens":402,"outputTokens":58,"totalTokens":460}}"#),
+		]
+		.concat();
+		let (client, mock) = client(body);
+
+		let model = client.completion_model(super::super::CLAUDE_3_5_HAIKU);
+		let request = model
+			.completion_request("What's the weather in Paris?")
+			.build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let mut text = String::new();
+		let mut tool_calls = Vec::new();
+		while let Some(chunk) = stream.next().await {
+			match chunk.unwrap() {
+				StreamedAssistantContent::Text(t) => text.push_str(&t.text),
+				StreamedAssistantContent::ToolCall { tool_call, .. } => tool_calls.push(tool_call),
+				_ => {}
+			}
+		}
+
+		assert_eq!(text, "Let me check the weather.");
+		assert_eq!(tool_calls.len(), 1);
+		assert_eq!(tool_calls[0].id, "tooluse_kZJMlvQmRJ6eAyJE5GIl7Q");
+		assert_eq!(tool_calls[0].function.name, "get_weather");
+		assert_eq!(
+			tool_calls[0].function.arguments,
+			serde_json::json!({ "city": "Paris" })
+		);
+
+		let response = stream.response.unwrap();
+		assert_eq!(response.stop_reason, Some(StopReason::ToolUse));
+		assert_eq!(response.usage.total_tokens, 460);
+
+		assert_eq!(
+			mock.request_uris(),
+			vec![
+				"https://bedrock-runtime.us-west-2.amazonaws.com/model/anthropic.claude-3-5-haiku-20241022-v1%3A0/converse-stream"
+			]
+		);
+		assert_eq!(
+			mock.request_headers()[0][http::header::AUTHORIZATION],
+			"Bearer bedrock-api-key"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_stream_reasoning() {
+		let body = [
+			event("messageStart", r#"{"role":"assistant"}"#),
+			event(
+				"contentBlockDelta",
+				r#"{"contentBlockIndex":0,"delta":{"reasoningContent":{"text":"The user greets me."}}}"#,
+			),
+			event(
+				"contentBlockDelta",
+				r#"{"contentBlockIndex":0,"delta":{"reasoningContent":{"signature":"ErUBCkYIBRgCIkDz"}}}"#,
+			),
+			event("contentBlockStop", r#"{"contentBlockIndex":0}"#),
+			event(
+				"contentBlockDelta",
+				r#"{"contentBlockIndex":1,"delta":{"text":"Hello!"}}"#,
+			),
+			event("contentBlockStop", r#"{"contentBlockIndex":1}"#),
+			event("messageStop", r#"{"stopReason":"end_turn"}"#),
+		]
+		.concat();
+		let (client, _) = client(body);
+
+		let model = client.completion_model(super::super::CLAUDE_3_7_SONNET);
+		let mut stream = model
+			.stream(model.completion_request("Hi").build())
+			.await
+			.unwrap();
+
+		let mut reasoning = Vec::new();
+		while let Some(chunk) = stream.next().await {
+			if let StreamedAssistantContent::Reasoning(r) = chunk.unwrap() {
+				reasoning.push(r);
+			}
+		}
+
+		assert_eq!(reasoning.len(), 1);
+		assert_eq!(reasoning[0].reasoning, vec!["The user greets me."]);
+		assert_eq!(reasoning[0].signature.as_deref(), Some("ErUBCkYIBRgCIkDz"));
+		assert_eq!(
+			stream.response.unwrap().stop_reason,
+			Some(StopReason::EndTurn)
+		);
+	}
+
+	#[tokio::test]
+	async fn test_stream_exception() {
+		let mut body = event("messageStart", r#"{"role":"assistant"}"#);
+		body.extend(encode(
+			&[
+				(":exception-type", "throttlingException"),
+				(":content-type", "application/json"),
+				(":message-type", "exception"),
+			],
+			br#"{"message":"Too many tokens, please wait before trying again."}"#,
+		));
+		let (client, _) = client(body);
+
+		let model = client.completion_model(super::super::CLAUDE_3_5_HAIKU);
+		let mut stream = model
+			.stream(model.completion_request("Hi").build())
+			.await
+			.unwrap();
+
+		let error = loop {
+			match stream.next().await {
+				Some(Err(error)) => break error,
+				Some(Ok(_)) => {}
+				None => panic!("Expected an error"),
+			}
+		};
+		assert!(matches!(
+			error,
+			CompletionError::ApiError(ref error)
+				if error.kind == completion::ProviderErrorKind::RateLimited { retry_after: None }
+		));
+	}
+}
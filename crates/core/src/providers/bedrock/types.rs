@@ -0,0 +1,953 @@
+//! Types of the Bedrock Converse API and their conversions from and to the core message types.
+//!
+//! Converse content blocks are unions serialized as objects with a single key, e.g.
+//! `{ "text": "Hello" }` or `{ "toolUse": { ... } }`.
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::{Deserialize, Serialize};
+
+use crate::OneOrMany;
+use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage};
+use crate::message::{self, DocumentMediaType, DocumentSourceKind, ImageMediaType, MessageError};
+
+/// Bedrock accepts at most 4 stop sequences for most models.
+pub const MAX_STOP_SEQUENCES: usize = 4;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+	User,
+	Assistant,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Message {
+	pub role: Role,
+	pub content: Vec<ContentBlock>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentBlock {
+	Text(String),
+	Image(ImageBlock),
+	Document(DocumentBlock),
+	ToolUse(ToolUseBlock),
+	ToolResult(ToolResultBlock),
+	ReasoningContent(ReasoningContentBlock),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ImageBlock {
+	pub format: ImageFormat,
+	pub source: Source,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+	Png,
+	Jpeg,
+	Gif,
+	Webp,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct DocumentBlock {
+	pub format: DocumentFormat,
+	/// A name for the document, which may contain alphanumeric characters, whitespace, hyphens,
+	/// parentheses and square brackets
+	pub name: String,
+	pub source: Source,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+	Pdf,
+	Csv,
+	Doc,
+	Docx,
+	Xls,
+	Xlsx,
+	Html,
+	Txt,
+	Md,
+}
+
+/// The data of an image or document, either inline or stored in S3.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Source {
+	/// Base64 encoded bytes
+	Bytes(String),
+	S3Location(S3Location),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct S3Location {
+	pub uri: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub bucket_owner: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUseBlock {
+	pub tool_use_id: String,
+	pub name: String,
+	pub input: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolResultBlock {
+	pub tool_use_id: String,
+	pub content: Vec<ToolResultContentBlock>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub status: Option<ToolResultStatus>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolResultContentBlock {
+	Text(String),
+	Json(serde_json::Value),
+	Image(ImageBlock),
+	Document(DocumentBlock),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolResultStatus {
+	Success,
+	Error,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ReasoningContentBlock {
+	ReasoningText(ReasoningText),
+	/// Reasoning encrypted by the model provider, sent back as is
+	RedactedContent(String),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ReasoningText {
+	pub text: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub signature: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SystemContentBlock {
+	pub text: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InferenceConfig {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max_tokens: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub temperature: Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub top_p: Option<f64>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub stop_sequences: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolConfig {
+	pub tools: Vec<Tool>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tool_choice: Option<ToolChoice>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Tool {
+	ToolSpec(ToolSpec),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolSpec {
+	pub name: String,
+	pub description: String,
+	pub input_schema: ToolInputSchema,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ToolInputSchema {
+	pub json: serde_json::Value,
+}
+
+impl From<completion::ToolDefinition> for Tool {
+	fn from(tool: completion::ToolDefinition) -> Self {
+		Tool::ToolSpec(ToolSpec {
+			name: tool.name,
+			description: tool.description,
+			input_schema: ToolInputSchema {
+				json: tool.parameters,
+			},
+		})
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolChoice {
+	/// The model decides whether to call a tool
+	Auto(EmptyObject),
+	/// The model must call at least one tool
+	Any(EmptyObject),
+	/// The model must call this tool
+	Tool { name: String },
+}
+
+/// Serializes to `{}`, the value of the unit members of Converse unions.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct EmptyObject {}
+
+impl TryFrom<message::ToolChoice> for ToolChoice {
+	type Error = CompletionError;
+
+	fn try_from(value: message::ToolChoice) -> Result<Self, Self::Error> {
+		match value {
+			message::ToolChoice::Auto => Ok(Self::Auto(EmptyObject {})),
+			message::ToolChoice::Required => Ok(Self::Any(EmptyObject {})),
+			message::ToolChoice::Specific { function_names } if function_names.len() == 1 => {
+				Ok(Self::Tool {
+					name: function_names.into_iter().next().unwrap_or_default(),
+				})
+			}
+			message::ToolChoice::Specific { .. } => Err(CompletionError::ProviderError(
+				"Bedrock can only require a single specific tool to be called".to_string(),
+			)),
+			message::ToolChoice::None => Err(CompletionError::ProviderError(
+				"Bedrock doesn't support disabling tool calls, remove the tools instead"
+					.to_string(),
+			)),
+		}
+	}
+}
+
+/// A request to the `Converse` and `ConverseStream` operations.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverseRequest {
+	pub messages: Vec<Message>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub system: Vec<SystemContentBlock>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub inference_config: Option<InferenceConfig>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tool_config: Option<ToolConfig>,
+	/// Additional fields of the request, e.g. `additionalModelRequestFields` for model specific
+	/// parameters or `guardrailConfig`
+	#[serde(flatten, skip_serializing_if = "Option::is_none")]
+	pub additional_params: Option<serde_json::Value>,
+}
+
+impl TryFrom<CompletionRequest> for ConverseRequest {
+	type Error = CompletionError;
+
+	fn try_from(request: CompletionRequest) -> Result<Self, Self::Error> {
+		request.check_stop_sequences("Bedrock", MAX_STOP_SEQUENCES)?;
+
+		let mut history = Vec::new();
+		if let Some(documents) = request.normalized_documents() {
+			history.push(documents);
+		}
+		history.extend(request.chat_history);
+
+		let messages = history
+			.into_iter()
+			.map(Message::try_from)
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let system = request
+			.preamble
+			.filter(|preamble| !preamble.is_empty())
+			.map(|text| vec![SystemContentBlock { text }])
+			.unwrap_or_default();
+
+		let inference_config = InferenceConfig {
+			max_tokens: request.max_tokens,
+			temperature: request.temperature,
+			top_p: None,
+			stop_sequences: request.stop_sequences,
+		};
+
+		let tool_config = if request.tools.is_empty() {
+			None
+		} else {
+			Some(ToolConfig {
+				tools: request.tools.into_iter().map(Tool::from).collect(),
+				tool_choice: request.tool_choice.map(ToolChoice::try_from).transpose()?,
+			})
+		};
+
+		Ok(Self {
+			messages,
+			system,
+			inference_config: (inference_config != InferenceConfig::default())
+				.then_some(inference_config),
+			tool_config,
+			additional_params: request.additional_params,
+		})
+	}
+}
+
+/// The response of the `Converse` operation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverseResponse {
+	pub output: ConverseOutput,
+	pub stop_reason: StopReason,
+	pub usage: TokenUsage,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub metrics: Option<Metrics>,
+	/// Model specific fields, e.g. the reasoning of some models
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub additional_model_response_fields: Option<serde_json::Value>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConverseOutput {
+	Message(Message),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metrics {
+	pub latency_ms: u64,
+}
+
+/// Why the model stopped generating.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+	EndTurn,
+	ToolUse,
+	MaxTokens,
+	StopSequence,
+	/// The output was blocked by a guardrail
+	GuardrailIntervened,
+	/// The output was blocked by the content filters of the model provider
+	ContentFiltered,
+	/// The model's context window was exceeded
+	ModelContextWindowExceeded,
+	#[serde(other)]
+	Unknown,
+}
+
+impl StopReason {
+	/// The stop reason as one of the finish reasons shared by most providers:
+	/// `stop`, `tool_calls`, `length`, `content_filter` or `unknown`.
+	pub fn normalized(&self) -> &'static str {
+		match self {
+			Self::EndTurn | Self::StopSequence => "stop",
+			Self::ToolUse => "tool_calls",
+			Self::MaxTokens | Self::ModelContextWindowExceeded => "length",
+			Self::GuardrailIntervened | Self::ContentFiltered => "content_filter",
+			Self::Unknown => "unknown",
+		}
+	}
+
+	/// Whether the output was blocked, in which case it may be empty.
+	pub fn is_filtered(&self) -> bool {
+		matches!(self, Self::GuardrailIntervened | Self::ContentFiltered)
+	}
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+	pub input_tokens: u64,
+	pub output_tokens: u64,
+	pub total_tokens: u64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cache_read_input_tokens: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cache_write_input_tokens: Option<u64>,
+}
+
+impl GetTokenUsage for TokenUsage {
+	/// Bedrock reports cached tokens separately from `inputTokens`, they are added back here.
+	fn token_usage(&self) -> Option<completion::Usage> {
+		let cached_input_tokens = self.cache_read_input_tokens.unwrap_or_default();
+		let cache_creation_input_tokens = self.cache_write_input_tokens.unwrap_or_default();
+		let input_tokens = self.input_tokens + cached_input_tokens + cache_creation_input_tokens;
+
+		Some(completion::Usage {
+			input_tokens,
+			output_tokens: self.output_tokens,
+			total_tokens: input_tokens + self.output_tokens,
+			cached_input_tokens,
+			cache_creation_input_tokens,
+		})
+	}
+}
+
+impl GetTokenUsage for ConverseResponse {
+	fn token_usage(&self) -> Option<completion::Usage> {
+		self.usage.token_usage()
+	}
+}
+
+impl ConverseResponse {
+	pub fn message(&self) -> &Message {
+		let ConverseOutput::Message(message) = &self.output;
+		message
+	}
+}
+
+impl crate::telemetry::ProviderResponseExt for ConverseResponse {
+	type OutputMessage = Message;
+	type Usage = TokenUsage;
+
+	/// Converse responses have no id, the request id is only sent in the headers
+	fn get_response_id(&self) -> Option<String> {
+		None
+	}
+
+	fn get_response_model_name(&self) -> Option<String> {
+		None
+	}
+
+	fn get_output_messages(&self) -> Vec<Self::OutputMessage> {
+		vec![self.message().clone()]
+	}
+
+	fn get_text_response(&self) -> Option<String> {
+		let text = self
+			.message()
+			.content
+			.iter()
+			.filter_map(|block| match block {
+				ContentBlock::Text(text) => Some(text.as_str()),
+				_ => None,
+			})
+			.collect::<Vec<_>>()
+			.join("\n");
+
+		if text.is_empty() { None } else { Some(text) }
+	}
+
+	fn get_usage(&self) -> Option<Self::Usage> {
+		Some(self.usage.clone())
+	}
+}
+
+impl TryFrom<ConverseResponse> for completion::CompletionResponse<ConverseResponse> {
+	type Error = CompletionError;
+
+	fn try_from(response: ConverseResponse) -> Result<Self, Self::Error> {
+		let content = response
+			.message()
+			.content
+			.iter()
+			.cloned()
+			.filter_map(|block| message::AssistantContent::try_from(block).ok())
+			.collect::<Vec<_>>();
+
+		let choice = OneOrMany::many(content).map_err(|_| {
+			CompletionError::ResponseError(if response.stop_reason.is_filtered() {
+				format!(
+					"Response was blocked by Bedrock ({:?})",
+					response.stop_reason
+				)
+			} else {
+				"Response contained no message or tool call (empty)".to_owned()
+			})
+		})?;
+
+		Ok(completion::CompletionResponse {
+			choice,
+			usage: response.usage.token_usage().unwrap_or_default(),
+			raw_response: response,
+			response_metadata: None,
+		})
+	}
+}
+
+/// Converts a block of a response. Blocks without an assistant counterpart are rejected.
+impl TryFrom<ContentBlock> for message::AssistantContent {
+	type Error = MessageError;
+
+	fn try_from(block: ContentBlock) -> Result<Self, Self::Error> {
+		match block {
+			ContentBlock::Text(text) => Ok(message::AssistantContent::text(text)),
+			ContentBlock::ToolUse(ToolUseBlock {
+				tool_use_id,
+				name,
+				input,
+			}) => Ok(message::AssistantContent::tool_call(
+				tool_use_id,
+				name,
+				input,
+			)),
+			ContentBlock::ReasoningContent(ReasoningContentBlock::ReasoningText(reasoning)) => {
+				Ok(message::AssistantContent::Reasoning(
+					message::Reasoning::new(&reasoning.text).with_signature(reasoning.signature),
+				))
+			}
+			ContentBlock::ReasoningContent(ReasoningContentBlock::RedactedContent(_)) => {
+				Err(MessageError::ConversionError(
+					"Redacted reasoning can't be represented as assistant content".into(),
+				))
+			}
+			ContentBlock::Image(_) | ContentBlock::Document(_) | ContentBlock::ToolResult(_) => {
+				Err(MessageError::ConversionError(
+					"Unexpected content block in a Bedrock response".into(),
+				))
+			}
+		}
+	}
+}
+
+impl TryFrom<message::Message> for Message {
+	type Error = MessageError;
+
+	fn try_from(message: message::Message) -> Result<Self, Self::Error> {
+		match message {
+			message::Message::User { content } => Ok(Message {
+				role: Role::User,
+				content: content
+					.into_iter()
+					.map(ContentBlock::try_from)
+					.collect::<Result<_, _>>()?,
+			}),
+			message::Message::Assistant { content, .. } => Ok(Message {
+				role: Role::Assistant,
+				content: content
+					.into_iter()
+					.map(ContentBlock::try_from)
+					.collect::<Result<_, _>>()?,
+			}),
+		}
+	}
+}
+
+impl TryFrom<message::UserContent> for ContentBlock {
+	type Error = MessageError;
+
+	fn try_from(content: message::UserContent) -> Result<Self, Self::Error> {
+		match content {
+			message::UserContent::Text(message::Text { text }) => Ok(ContentBlock::Text(text)),
+			message::UserContent::Image(image) => Ok(ContentBlock::Image(image.try_into()?)),
+			message::UserContent::Document(message::Document {
+				data: DocumentSourceKind::String(text),
+				..
+			}) => Ok(ContentBlock::Text(text)),
+			message::UserContent::Document(document) => {
+				Ok(ContentBlock::Document(document.try_into()?))
+			}
+			message::UserContent::ToolResult(message::ToolResult { id, content, .. }) => {
+				Ok(ContentBlock::ToolResult(ToolResultBlock {
+					tool_use_id: id,
+					content: content
+						.into_iter()
+						.map(|content| match content {
+							message::ToolResultContent::Text(message::Text { text }) => {
+								Ok(ToolResultContentBlock::Text(text))
+							}
+							message::ToolResultContent::Image(image) => {
+								Ok(ToolResultContentBlock::Image(image.try_into()?))
+							}
+						})
+						.collect::<Result<_, MessageError>>()?,
+					status: None,
+				}))
+			}
+			message::UserContent::Audio(_) | message::UserContent::Video(_) => {
+				Err(MessageError::ConversionError(
+					"Bedrock Converse doesn't support audio or video content".into(),
+				))
+			}
+		}
+	}
+}
+
+impl TryFrom<message::AssistantContent> for ContentBlock {
+	type Error = MessageError;
+
+	fn try_from(content: message::AssistantContent) -> Result<Self, Self::Error> {
+		match content {
+			message::AssistantContent::Text(message::Text { text }) => Ok(ContentBlock::Text(text)),
+			message::AssistantContent::ToolCall(tool_call) => {
+				Ok(ContentBlock::ToolUse(ToolUseBlock {
+					tool_use_id: tool_call.id,
+					name: tool_call.function.name,
+					input: tool_call.function.arguments,
+				}))
+			}
+			message::AssistantContent::Reasoning(message::Reasoning {
+				reasoning,
+				signature,
+				..
+			}) => Ok(ContentBlock::ReasoningContent(
+				ReasoningContentBlock::ReasoningText(ReasoningText {
+					text: reasoning.join("\n"),
+					signature,
+				}),
+			)),
+			message::AssistantContent::Image(image) => Ok(ContentBlock::Image(image.try_into()?)),
+			message::AssistantContent::Document(document) => {
+				Ok(ContentBlock::Document(document.try_into()?))
+			}
+		}
+	}
+}
+
+impl TryFrom<message::Image> for ImageBlock {
+	type Error = MessageError;
+
+	fn try_from(mut image: message::Image) -> Result<Self, Self::Error> {
+		image.sniff_media_type();
+		let format = match image.media_type {
+			Some(ImageMediaType::PNG) => ImageFormat::Png,
+			Some(ImageMediaType::JPEG) => ImageFormat::Jpeg,
+			Some(ImageMediaType::GIF) => ImageFormat::Gif,
+			Some(ImageMediaType::WEBP) => ImageFormat::Webp,
+			Some(media_type) => {
+				return Err(MessageError::ConversionError(format!(
+					"Bedrock doesn't support {media_type:?} images"
+				)));
+			}
+			None => {
+				return Err(MessageError::ConversionError(
+					"Image media type is required for Bedrock".into(),
+				));
+			}
+		};
+
+		Ok(ImageBlock {
+			format,
+			source: image.data.try_into()?,
+		})
+	}
+}
+
+impl TryFrom<message::Document> for DocumentBlock {
+	type Error = MessageError;
+
+	fn try_from(mut document: message::Document) -> Result<Self, Self::Error> {
+		document.sniff_media_type();
+		let format = match document.media_type {
+			Some(DocumentMediaType::PDF) => DocumentFormat::Pdf,
+			Some(DocumentMediaType::CSV) => DocumentFormat::Csv,
+			Some(DocumentMediaType::HTML) => DocumentFormat::Html,
+			Some(DocumentMediaType::MARKDOWN) => DocumentFormat::Md,
+			Some(DocumentMediaType::TXT) | None => DocumentFormat::Txt,
+			Some(media_type) => {
+				return Err(MessageError::ConversionError(format!(
+					"Bedrock doesn't support {media_type:?} documents"
+				)));
+			}
+		};
+
+		// Converse requires a name, which can be set with `additional_params`
+		let name = document
+			.additional_params
+			.as_ref()
+			.and_then(|params| params.get("name"))
+			.and_then(|name| name.as_str())
+			.unwrap_or("document")
+			.to_string();
+
+		Ok(DocumentBlock {
+			format,
+			name,
+			source: document.data.try_into()?,
+		})
+	}
+}
+
+impl TryFrom<DocumentSourceKind> for Source {
+	type Error = MessageError;
+
+	fn try_from(data: DocumentSourceKind) -> Result<Self, Self::Error> {
+		match data {
+			DocumentSourceKind::Base64(data) => Ok(Source::Bytes(data)),
+			DocumentSourceKind::Raw(bytes) => Ok(Source::Bytes(BASE64_STANDARD.encode(bytes))),
+			DocumentSourceKind::String(text) => Ok(Source::Bytes(BASE64_STANDARD.encode(text))),
+			DocumentSourceKind::Url(uri) if uri.starts_with("s3://") => {
+				Ok(Source::S3Location(S3Location {
+					uri,
+					bucket_owner: None,
+				}))
+			}
+			DocumentSourceKind::Url(_) => Err(MessageError::ConversionError(
+				"Bedrock only supports inline data and S3 locations (s3://...)".into(),
+			)),
+			DocumentSourceKind::Unknown => {
+				Err(MessageError::ConversionError("Content has no data".into()))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::completion::ToolDefinition;
+	use crate::message::{AssistantContent, UserContent};
+
+	#[test]
+	fn test_serialize_request() {
+		let request = CompletionRequest::builder(message::Message::tool_result(
+			"tooluse_kZJMlvQmRJ6eAyJE5GIl7Q",
+			"18°C, sunny",
+		))
+		.messages(vec![
+			message::Message::User {
+				content: OneOrMany::many(vec![
+					UserContent::text("What's the weather where this was taken?"),
+					UserContent::image_base64("iVBORw0KGgo=", Some(ImageMediaType::PNG), None),
+				])
+				.unwrap(),
+			},
+			message::Message::Assistant {
+				id: None,
+				content: OneOrMany::one(AssistantContent::tool_call(
+					"tooluse_kZJMlvQmRJ6eAyJE5GIl7Q",
+					"get_weather",
+					json!({ "city": "Paris" }),
+				)),
+			},
+		])
+		.preamble("You are a weather assistant.".to_string())
+		.tool(ToolDefinition {
+			name: "get_weather".to_string(),
+			description: "Get the current weather of a city".to_string(),
+			parameters: json!({
+				"type": "object",
+				"properties": { "city": { "type": "string" } },
+				"required": ["city"]
+			}),
+		})
+		.temperature(0.5)
+		.max_tokens(1024)
+		.tool_choice(message::ToolChoice::Required)
+		.additional_params(json!({
+			"additionalModelRequestFields": { "top_k": 200 }
+		}))
+		.build();
+
+		let request = ConverseRequest::try_from(request).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap(),
+			json!({
+				"messages": [
+					{
+						"role": "user",
+						"content": [
+							{ "text": "What's the weather where this was taken?" },
+							{ "image": { "format": "png", "source": { "bytes": "iVBORw0KGgo=" } } }
+						]
+					},
+					{
+						"role": "assistant",
+						"content": [{
+							"toolUse": {
+								"toolUseId": "tooluse_kZJMlvQmRJ6eAyJE5GIl7Q",
+								"name": "get_weather",
+								"input": { "city": "Paris" }
+							}
+						}]
+					},
+					{
+						"role": "user",
+						"content": [{
+							"toolResult": {
+								"toolUseId": "tooluse_kZJMlvQmRJ6eAyJE5GIl7Q",
+								"content": [{ "text": "18°C, sunny" }]
+							}
+						}]
+					}
+				],
+				"system": [{ "text": "You are a weather assistant." }],
+				"inferenceConfig": { "maxTokens": 1024, "temperature": 0.5 },
+				"toolConfig": {
+					"tools": [{
+						"toolSpec": {
+							"name": "get_weather",
+							"description": "Get the current weather of a city",
+							"inputSchema": {
+								"json": {
+									"type": "object",
+									"properties": { "city": { "type": "string" } },
+									"required": ["city"]
+								}
+							}
+						}
+					}],
+					"toolChoice": { "any": {} }
+				},
+				"additionalModelRequestFields": { "top_k": 200 }
+			})
+		);
+	}
+
+	#[test]
+	fn test_tool_choice() {
+		assert_eq!(
+			serde_json::to_value(ToolChoice::try_from(message::ToolChoice::Auto).unwrap()).unwrap(),
+			json!({ "auto": {} })
+		);
+		assert_eq!(
+			serde_json::to_value(
+				ToolChoice::try_from(message::ToolChoice::Specific {
+					function_names: vec!["get_weather".to_string()]
+				})
+				.unwrap()
+			)
+			.unwrap(),
+			json!({ "tool": { "name": "get_weather" } })
+		);
+		assert!(ToolChoice::try_from(message::ToolChoice::None).is_err());
+	}
+
+	#[test]
+	fn test_deserialize_tool_use_response() {
+		// Captured from a Converse response of Claude 3.5 Haiku
+		let response = json!({
+			"metrics": { "latencyMs": 1131 },
+			"output": {
+				"message": {
+					"content": [
+						{ "text": "I'll check the current weather in Paris for you." },
+						{
+							"toolUse": {
+								"input": { "city": "Paris" },
+								"name": "get_weather",
+								"toolUseId": "tooluse_kZJMlvQmRJ6eAyJE5GIl7Q"
+							}
+						}
+					],
+					"role": "assistant"
+				}
+			},
+			"stopReason": "tool_use",
+			"usage": {
+				"cacheReadInputTokenCount": 0,
+				"cacheReadInputTokens": 0,
+				"cacheWriteInputTokenCount": 0,
+				"cacheWriteInputTokens": 0,
+				"inputTokens": 402,
+				"outputTokens": 67,
+				"totalTokens": 469
+			}
+		});
+
+		let response: ConverseResponse = serde_json::from_value(response).unwrap();
+		assert_eq!(response.stop_reason, StopReason::ToolUse);
+		assert_eq!(response.stop_reason.normalized(), "tool_calls");
+		assert_eq!(response.metrics.as_ref().unwrap().latency_ms, 1131);
+
+		let response = completion::CompletionResponse::try_from(response).unwrap();
+		assert_eq!(response.usage.input_tokens, 402);
+		assert_eq!(response.usage.output_tokens, 67);
+		assert_eq!(response.usage.total_tokens, 469);
+
+		let content = response.choice.into_iter().collect::<Vec<_>>();
+		assert_eq!(
+			content,
+			vec![
+				AssistantContent::text("I'll check the current weather in Paris for you."),
+				AssistantContent::tool_call(
+					"tooluse_kZJMlvQmRJ6eAyJE5GIl7Q",
+					"get_weather",
+					json!({ "city": "Paris" })
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn test_deserialize_reasoning_response() {
+		let response = json!({
+			"output": {
+				"message": {
+					"role": "assistant",
+					"content": [
+						{
+							"reasoningContent": {
+								"reasoningText": {
+									"text": "The user is asking for 2 + 2.",
+									"signature": "ErUBCkYIBRgCIkDz"
+								}
+							}
+						},
+						{ "reasoningContent": { "redactedContent": "EmwKAhgBEgy3va3pzix" } },
+						{ "text": "2 + 2 = 4" }
+					]
+				}
+			},
+			"stopReason": "end_turn",
+			"usage": {
+				"inputTokens": 46,
+				"outputTokens": 70,
+				"totalTokens": 1116,
+				"cacheReadInputTokens": 1000
+			}
+		});
+
+		let response: ConverseResponse = serde_json::from_value(response).unwrap();
+		let response = completion::CompletionResponse::try_from(response).unwrap();
+
+		// Redacted reasoning is skipped, cached tokens count as input tokens
+		let content = response.choice.into_iter().collect::<Vec<_>>();
+		assert_eq!(content.len(), 2);
+		let AssistantContent::Reasoning(reasoning) = &content[0] else {
+			panic!("Expected reasoning, got {:?}", content[0]);
+		};
+		assert_eq!(reasoning.reasoning, vec!["The user is asking for 2 + 2."]);
+		assert_eq!(reasoning.signature.as_deref(), Some("ErUBCkYIBRgCIkDz"));
+		assert_eq!(response.usage.input_tokens, 1046);
+		assert_eq!(response.usage.cached_input_tokens, 1000);
+		assert_eq!(response.usage.total_tokens, 1116);
+	}
+
+	#[test]
+	fn test_blocked_response() {
+		let response = json!({
+			"output": { "message": { "role": "assistant", "content": [] } },
+			"stopReason": "guardrail_intervened",
+			"usage": { "inputTokens": 12, "outputTokens": 0, "totalTokens": 12 }
+		});
+
+		let response: ConverseResponse = serde_json::from_value(response).unwrap();
+		assert_eq!(response.stop_reason.normalized(), "content_filter");
+
+		let error = completion::CompletionResponse::try_from(response).unwrap_err();
+		assert!(error.to_string().contains("blocked"));
+	}
+
+	#[test]
+	fn test_stop_reasons() {
+		let reasons = [
+			("end_turn", "stop"),
+			("stop_sequence", "stop"),
+			("max_tokens", "length"),
+			("model_context_window_exceeded", "length"),
+			("content_filtered", "content_filter"),
+			("some_new_reason", "unknown"),
+		];
+
+		for (reason, normalized) in reasons {
+			let reason: StopReason = serde_json::from_value(json!(reason)).unwrap();
+			assert_eq!(reason.normalized(), normalized);
+		}
+	}
+}
@@ -1,8 +1,8 @@
 use super::{CompletionModel, EmbeddingModel};
 use crate::Embed;
 use crate::client::{
-	self, BearerAuth, Capabilities, Capable, DebugExt, Nothing, Provider, ProviderBuilder,
-	ProviderClient,
+	self, BearerAuth, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider,
+	ProviderBuilder, ProviderClient,
 };
 use crate::embeddings::EmbeddingsBuilder;
 use crate::http_client::{self, HttpClientExt};
@@ -65,8 +65,12 @@ impl ProviderClient for Client {
 	where
 		Self: Sized,
 	{
-		let key = std::env::var("COHERE_API_KEY").expect("COHERE_API_KEY not set");
-		Self::new(key).unwrap()
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let key = client::env_var("COHERE_API_KEY")?;
+		Ok(Self::new(key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self
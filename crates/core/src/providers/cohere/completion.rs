@@ -180,6 +180,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 					output_tokens: output_tokens as u64,
 					total_tokens: (input_tokens + output_tokens) as u64,
 					cached_input_tokens: 0,
+					cache_creation_input_tokens: 0,
 				}
 			})
 			.unwrap_or_default();
@@ -188,6 +189,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			choice: OneOrMany::many(model_response).expect("There is atleast one content"),
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -421,6 +423,9 @@ impl TryFrom<message::Message> for Vec<Message> {
 								"Cohere currently doesn't support images.".to_owned(),
 							));
 						}
+						message::AssistantContent::Document(_) => {
+							tracing::warn!("Cohere doesn't support assistant documents, skipping");
+						}
 					}
 				}
 
@@ -524,6 +529,9 @@ pub struct CompletionModel<T = reqwest::Client> {
 	pub model: String,
 }
 
+/// Maximum number of stop sequences accepted by Cohere
+pub const MAX_STOP_SEQUENCES: usize = 5;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(super) struct CohereCompletionRequest {
 	model: String,
@@ -532,6 +540,8 @@ pub(super) struct CohereCompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop_sequences: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<Tool>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<ToolChoice>,
@@ -543,6 +553,8 @@ impl TryFrom<(&str, CompletionRequest)> for CohereCompletionRequest {
 	type Error = CompletionError;
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
+		req.check_stop_sequences("Cohere", MAX_STOP_SEQUENCES)?;
+
 		let mut partial_history = vec![];
 		if let Some(docs) = req.normalized_documents() {
 			partial_history.push(docs);
@@ -580,6 +592,7 @@ impl TryFrom<(&str, CompletionRequest)> for CohereCompletionRequest {
 			messages: full_history,
 			documents: req.documents,
 			temperature: req.temperature,
+			stop_sequences: req.stop_sequences,
 			tools: req.tools.into_iter().map(Tool::from).collect::<Vec<_>>(),
 			tool_choice,
 			additional_params: req.additional_params,
@@ -611,10 +624,15 @@ where
 		Self::new(client.clone(), model.into())
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: completion::CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let request = CohereCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
 
 		let llm_span = if tracing::Span::current().is_disabled() {
@@ -628,10 +646,13 @@ where
 			gen_ai.response.model = self.model,
 			gen_ai.usage.output_tokens = tracing::field::Empty,
 			gen_ai.usage.input_tokens = tracing::field::Empty,
+			gen_ai.input.messages = tracing::field::Empty,
+			gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		llm_span.record_input_messages(&request.messages);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(
@@ -650,6 +671,7 @@ where
 				.send::<_, bytes::Bytes>(req)
 				.await
 				.map_err(|e| http_client::Error::Instance(e.into()))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			let status = response.status();
 			let body = response.into_body().into_future().await?.to_owned();
@@ -659,6 +681,7 @@ where
 				let span = tracing::Span::current();
 				span.record_token_usage(&json_response.usage);
 				span.record_response_metadata(&json_response);
+				span.record_output_messages(std::slice::from_ref(&json_response.message));
 
 				if enabled!(Level::TRACE) {
 					tracing::trace!(
@@ -670,7 +693,7 @@ where
 
 				let completion: completion::CompletionResponse<CompletionResponse> =
 					json_response.try_into()?;
-				Ok(completion)
+				Ok(completion.with_response_metadata(response_metadata))
 			} else {
 				Err(CompletionError::ProviderError(
 					String::from_utf8_lossy(&body).to_string(),
@@ -688,6 +711,7 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let request = self.client.apply_default_params(request);
 		CompletionModel::stream(self, request).await
 	}
 }
@@ -801,4 +825,28 @@ mod tests {
 		let completion_message: completion::Message = message.clone().try_into().unwrap();
 		let _converted_back: Vec<Message> = completion_message.try_into().unwrap();
 	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = CompletionRequest::builder("Count to ten")
+			.stop_sequences(vec!["\n\n".to_string(), "END".to_string()])
+			.build();
+
+		let request = CohereCompletionRequest::try_from(("command-r", request)).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["stop_sequences"],
+			serde_json::json!(["\n\n", "END"])
+		);
+	}
+
+	#[test]
+	fn test_stop_sequences_limit() {
+		let request = CompletionRequest::builder("Count to ten")
+			.stop_sequences((0..6).map(|i| i.to_string()).collect())
+			.build();
+
+		let error = CohereCompletionRequest::try_from(("command-r", request)).unwrap_err();
+		assert!(matches!(error, CompletionError::RequestError(_)), "{error}");
+	}
 }
@@ -112,10 +112,13 @@ where
 				gen_ai.response.model = self.model,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		let params = json_utils::merge(
 			request.additional_params.unwrap_or(serde_json::json!({})),
@@ -148,6 +151,9 @@ where
                 match event_result {
                     Ok(Event::Open) => {
                         tracing::trace!("SSE connection opened");
+                        if let Some(metadata) = event_source.response_metadata() {
+                            yield Ok(RawStreamingChoice::ResponseMetadata(metadata.clone()));
+                        }
                         continue;
                     }
 
@@ -186,7 +192,7 @@ where
 
                                 let span = tracing::Span::current();
                                 span.record_token_usage(&delta.usage);
-                                span.record_model_output(&vec![message]);
+                                span.record_output_messages(&[message]);
 
                                 final_usage = Some(delta.usage.clone());
                                 break;
@@ -253,6 +259,10 @@ where
                     Err(crate::http_client::Error::StreamEnded) => {
                         break;
                     }
+                    Err(crate::http_client::Error::StreamStalled(timeout)) => {
+                        yield Err(CompletionError::StreamStalled(timeout));
+                        break;
+                    }
                     Err(err) => {
                         tracing::error!(?err, "SSE error");
                         yield Err(CompletionError::ProviderError(err.to_string()));
@@ -1,5 +1,5 @@
 use super::completion::CompletionModel;
-use crate::client::{self, BearerAuth, Capable, Nothing, ProviderClient};
+use crate::client::{self, BearerAuth, Capable, ClientError, Nothing, ProviderClient};
 use crate::providers::openai_compat::{self, OpenAiCompat, PBuilder};
 
 const DEEPSEEK_API_BASE_URL: &str = "https://api.deepseek.com";
@@ -35,6 +35,10 @@ impl ProviderClient for Client {
 		openai_compat::default_from_env::<DeepSeek>()
 	}
 
+	fn try_from_env() -> Result<Self, ClientError> {
+		openai_compat::default_try_from_env::<DeepSeek>()
+	}
+
 	fn from_val(input: Self::Input) -> Self {
 		Self::new(&input).unwrap()
 	}
@@ -5,7 +5,9 @@ use super::client::{Client, DeepSeek};
 use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage};
 use crate::http_client::{self, HttpClientExt};
 use crate::message::{Document, DocumentSourceKind};
+use crate::providers::openai::completion::streaming::CompatStreamingResponse;
 use crate::providers::openai_compat::{self, OpenAiCompat};
+use crate::telemetry::SpanCombinator;
 use crate::{OneOrMany, json_utils, message};
 
 /// The response shape from the DeepSeek API
@@ -76,6 +78,9 @@ pub enum Message {
 		/// only exists on `deepseek-reasoner` model at time of addition
 		#[serde(skip_serializing_if = "Option::is_none")]
 		reasoning_content: Option<String>,
+		/// Marks the prefilled last message of a prefix completion (beta)
+		#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+		prefix: bool,
 	},
 	#[serde(rename = "tool")]
 	ToolResult {
@@ -91,6 +96,17 @@ impl Message {
 			name: None,
 		}
 	}
+
+	/// The prefilled assistant message of a prefix completion
+	pub fn assistant_prefix(content: impl Into<String>) -> Self {
+		Message::Assistant {
+			content: content.into(),
+			name: None,
+			tool_calls: vec![],
+			reasoning_content: None,
+			prefix: true,
+		}
+	}
 }
 
 impl From<message::ToolResult> for Message {
@@ -187,6 +203,7 @@ impl TryFrom<message::Message> for Vec<Message> {
 					content: text_content,
 					name: None,
 					tool_calls: vec![],
+					prefix: false,
 					reasoning_content: if reasoning_content.is_empty() {
 						None
 					} else {
@@ -213,6 +230,7 @@ impl TryFrom<message::Message> for Vec<Message> {
 						name: None,
 						tool_calls,
 						reasoning_content: None,
+						prefix: false,
 					});
 				}
 
@@ -321,23 +339,58 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 				.and_then(|d| d.cached_tokens)
 				.map(|c| c as u64)
 				.unwrap_or(0),
+			cache_creation_input_tokens: 0,
 		};
 
 		Ok(completion::CompletionResponse {
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
 
+/// Maximum number of stop sequences accepted by DeepSeek
+pub const MAX_STOP_SEQUENCES: usize = 16;
+
+/// Path of the chat completions endpoint of the beta API, required for prefix completions
+const BETA_COMPLETION_PATH: &str = "/beta/chat/completions";
+
+/// Additional parameters for DeepSeek completion requests, passed as the request's
+/// `additional_params` with [AdditionalParameters::to_json].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AdditionalParameters {
+	/// Prefill of the assistant response, see [AdditionalParameters::with_assistant_prefix]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub assistant_prefix: Option<String>,
+}
+
+impl AdditionalParameters {
+	/// Use prefix completion (beta): the model continues its response from `prefix`, which is
+	/// sent as the last message of the conversation. The response doesn't repeat the prefix.
+	/// The request is sent to the beta API.
+	pub fn with_assistant_prefix(mut self, prefix: impl Into<String>) -> Self {
+		self.assistant_prefix = Some(prefix.into());
+		self
+	}
+
+	pub fn to_json(self) -> serde_json::Value {
+		serde_json::to_value(self).expect(
+			"this should never fail since a struct that impls Deserialize will always be valid JSON",
+		)
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub(super) struct DeepseekCompletionRequest {
+pub(crate) struct DeepseekCompletionRequest {
 	model: String,
 	pub messages: Vec<Message>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<crate::providers::openrouter::ToolChoice>,
@@ -349,12 +402,14 @@ impl TryFrom<(&str, CompletionRequest)> for DeepseekCompletionRequest {
 	type Error = CompletionError;
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
+		req.check_stop_sequences("DeepSeek", MAX_STOP_SEQUENCES)?;
+
 		let mut full_history: Vec<Message> = match &req.preamble {
 			Some(preamble) => vec![Message::system(preamble)],
 			None => vec![],
 		};
 
-		if let Some(docs) = req.normalized_documents() {
+		if let Some(docs) = req.rendered_documents() {
 			let docs: Vec<Message> = docs.try_into()?;
 			full_history.extend(docs);
 		}
@@ -399,16 +454,27 @@ impl TryFrom<(&str, CompletionRequest)> for DeepseekCompletionRequest {
 			*reasoning_content = Some(reasoning);
 		}
 
+		let mut additional_params = req.additional_params;
+		let assistant_prefix = additional_params
+			.as_mut()
+			.and_then(|params| params.as_object_mut()?.remove("assistant_prefix"))
+			.map(serde_json::from_value::<String>)
+			.transpose()?;
+		if let Some(prefix) = assistant_prefix {
+			full_history.push(Message::assistant_prefix(prefix));
+		}
+
 		let tool_choice = req
 			.tool_choice
 			.clone()
 			.map(crate::providers::openrouter::ToolChoice::try_from)
 			.transpose()?;
 
-		Ok(Self {
+		let request = Self {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools: req
 				.tools
 				.clone()
@@ -416,8 +482,47 @@ impl TryFrom<(&str, CompletionRequest)> for DeepseekCompletionRequest {
 				.map(ToolDefinition::from)
 				.collect::<Vec<_>>(),
 			tool_choice,
-			additional_params: req.additional_params,
-		})
+			additional_params,
+		};
+		request.validate_prefix()?;
+
+		Ok(request)
+	}
+}
+
+impl DeepseekCompletionRequest {
+	/// Whether the request is a prefix completion
+	fn is_prefix_completion(&self) -> bool {
+		matches!(
+			self.messages.last(),
+			Some(Message::Assistant { prefix: true, .. })
+		)
+	}
+
+	/// Checks that the prefix message, if any, is the last message of the conversation
+	fn validate_prefix(&self) -> Result<(), CompletionError> {
+		let prefixes = self
+			.messages
+			.iter()
+			.filter(|message| matches!(message, Message::Assistant { prefix: true, .. }))
+			.count();
+
+		if prefixes > 1 || (prefixes == 1 && !self.is_prefix_completion()) {
+			return Err(CompletionError::RequestError(
+				"DeepSeek prefix completion requires the prefix to be the final message".into(),
+			));
+		}
+
+		Ok(())
+	}
+
+	/// The path of the chat completions endpoint to send the request to
+	pub(super) fn path(&self) -> &'static str {
+		if self.is_prefix_completion() {
+			BETA_COMPLETION_PATH
+		} else {
+			DeepSeek::COMPLETION_PATH
+		}
 	}
 }
 
@@ -437,10 +542,15 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::completion_span(
 			DeepSeek::PROVIDER_NAME,
 			&self.model,
@@ -449,6 +559,7 @@ where
 
 		let request =
 			DeepseekCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -460,12 +571,12 @@ where
 		let body = serde_json::to_vec(&request)?;
 		let req = self
 			.client
-			.post("/chat/completions")?
+			.post(request.path())?
 			.body(body)
 			.map_err(http_client::Error::from)?;
 
 		let async_block = async move {
-			let response = openai_compat::send_and_parse::<
+			let (response, response_metadata) = openai_compat::send_and_parse::<
 				_,
 				CompletionResponse,
 				openai_compat::FlatApiError,
@@ -480,8 +591,10 @@ where
 				"gen_ai.usage.output_tokens",
 				response.usage.completion_tokens,
 			);
+			current_span.record_output_messages(&response.choices);
 
-			response.try_into()
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
 		};
 
 		tracing::Instrument::instrument(async_block, span).await
@@ -494,6 +607,7 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::streaming_span(
 			DeepSeek::PROVIDER_NAME,
 			&self.model,
@@ -502,10 +616,12 @@ where
 
 		let mut request =
 			DeepseekCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
-		let params = json_utils::merge(
+		let params = json_utils::deep_merge(
 			request.additional_params.unwrap_or(serde_json::json!({})),
 			serde_json::json!({"stream": true, "stream_options": {"include_usage": true} }),
+			json_utils::ArrayMergeStrategy::Replace,
 		);
 
 		request.additional_params = Some(params);
@@ -521,7 +637,7 @@ where
 
 		let req = self
 			.client
-			.post("/chat/completions")?
+			.post(request.path())?
 			.body(body)
 			.map_err(http_client::Error::from)?;
 
@@ -547,21 +663,13 @@ impl GetTokenUsage for StreamingCompletionResponse {
 		usage.input_tokens = self.usage.prompt_tokens as u64;
 		usage.output_tokens = self.usage.completion_tokens as u64;
 		usage.total_tokens = self.usage.total_tokens as u64;
-		usage.cached_input_tokens = self
-			.usage
-			.prompt_tokens_details
-			.as_ref()
-			.and_then(|d| d.cached_tokens)
-			.map(|c| c as u64)
-			.unwrap_or(0);
+		usage.cached_input_tokens = Self::cached_input_tokens(&self.usage);
 
 		Some(usage)
 	}
 }
 
-impl crate::providers::openai::completion::streaming::CompatStreamingResponse
-	for StreamingCompletionResponse
-{
+impl CompatStreamingResponse for StreamingCompletionResponse {
 	type Usage = Usage;
 	fn from_usage(usage: Usage) -> Self {
 		Self { usage }
@@ -572,6 +680,13 @@ impl crate::providers::openai::completion::streaming::CompatStreamingResponse
 	fn output_tokens(usage: &Usage) -> u64 {
 		usage.completion_tokens as u64
 	}
+	fn cached_input_tokens(usage: &Usage) -> u64 {
+		usage
+			.prompt_tokens_details
+			.as_ref()
+			.and_then(|details| details.cached_tokens)
+			.unwrap_or(usage.prompt_cache_hit_tokens) as u64
+	}
 }
 
 pub const DEEPSEEK_CHAT: &str = "deepseek-chat";
@@ -581,6 +696,30 @@ pub const DEEPSEEK_REASONER: &str = "deepseek-reasoner";
 mod tests {
 
 	use super::*;
+	use crate::completion::CompletionRequestBuilder;
+
+	#[test]
+	fn test_streaming_usage_with_cache_hits() {
+		let usage: Usage = serde_json::from_str(
+			r#"{
+                "completion_tokens": 30,
+                "prompt_tokens": 1200,
+                "prompt_cache_hit_tokens": 1024,
+                "prompt_cache_miss_tokens": 176,
+                "total_tokens": 1230
+            }"#,
+		)
+		.unwrap();
+
+		let usage = StreamingCompletionResponse::from_usage(usage)
+			.token_usage()
+			.unwrap();
+		assert_eq!(usage.input_tokens, 1200);
+		assert_eq!(usage.output_tokens, 30);
+		assert_eq!(usage.total_tokens, 1230);
+		assert_eq!(usage.cached_input_tokens, 1024);
+		assert_eq!(usage.cache_creation_input_tokens, 0);
+	}
 
 	#[test]
 	fn test_deserialize_vec_choice() {
@@ -723,9 +862,128 @@ mod tests {
 					r#type: ToolType::Function,
 				}],
 				reasoning_content: None,
+				prefix: false,
 			},
 		};
 
 		assert_eq!(choice, expected_choice);
 	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = CompletionRequest::builder("Count to ten")
+			.stop_sequences(vec!["\n\n".to_string(), "END".to_string()])
+			.build();
+
+		let request = DeepseekCompletionRequest::try_from(("deepseek-chat", request)).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["stop"],
+			serde_json::json!(["\n\n", "END"])
+		);
+	}
+
+	fn prefix_request() -> CompletionRequest {
+		CompletionRequest::builder("Describe a cat in JSON")
+			.additional_params(
+				AdditionalParameters::default()
+					.with_assistant_prefix("```json\n")
+					.to_json(),
+			)
+			.build()
+	}
+
+	#[test]
+	fn test_assistant_prefix_serialization() {
+		let request =
+			DeepseekCompletionRequest::try_from(("deepseek-chat", prefix_request())).unwrap();
+
+		assert_eq!(request.path(), "/beta/chat/completions");
+		assert_eq!(
+			serde_json::to_value(&request).unwrap(),
+			serde_json::json!({
+				"model": "deepseek-chat",
+				"messages": [
+					{ "role": "user", "content": "Describe a cat in JSON" },
+					{ "role": "assistant", "content": "```json\n", "prefix": true }
+				]
+			})
+		);
+
+		let request = DeepseekCompletionRequest::try_from((
+			"deepseek-chat",
+			CompletionRequestBuilder::from(prefix_request())
+				.additional_params_opt(None)
+				.build(),
+		))
+		.unwrap();
+
+		assert_eq!(request.path(), "/chat/completions");
+		assert!(
+			!serde_json::to_string(&request)
+				.unwrap()
+				.contains("\"prefix\"")
+		);
+	}
+
+	#[test]
+	fn test_assistant_prefix_must_be_last() {
+		let mut request =
+			DeepseekCompletionRequest::try_from(("deepseek-chat", prefix_request())).unwrap();
+		request.messages.push(Message::ToolResult {
+			tool_call_id: "call_1".to_string(),
+			content: "{}".to_string(),
+		});
+
+		assert!(request.validate_prefix().is_err());
+	}
+
+	#[tokio::test]
+	async fn test_assistant_prefix_uses_beta_endpoint() {
+		use crate::client::CompletionClient;
+		use crate::completion::CompletionModel as _;
+		use crate::test_utils::MockSseClient;
+
+		let http_client = MockSseClient::default().with_json_response(
+			r#"{
+				"choices": [{
+					"index": 0,
+					"logprobs": null,
+					"finish_reason": "stop",
+					"message": { "role": "assistant", "content": "{\"name\": \"Tom\"}" }
+				}],
+				"usage": {
+					"completion_tokens": 6,
+					"prompt_tokens": 10,
+					"prompt_cache_hit_tokens": 0,
+					"prompt_cache_miss_tokens": 10,
+					"total_tokens": 16
+				}
+			}"#,
+		);
+		let model = super::super::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap()
+			.completion_model(DEEPSEEK_CHAT);
+
+		model.completion(prefix_request()).await.unwrap();
+		model
+			.completion(
+				CompletionRequestBuilder::from(prefix_request())
+					.additional_params_opt(None)
+					.build(),
+			)
+			.await
+			.unwrap();
+
+		assert_eq!(
+			http_client.request_uris(),
+			[
+				"https://api.deepseek.com/beta/chat/completions",
+				"https://api.deepseek.com/chat/completions"
+			]
+		);
+	}
 }
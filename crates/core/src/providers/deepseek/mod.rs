@@ -14,6 +14,6 @@ pub mod completion;
 
 pub use client::{Client, ClientBuilder, DeepSeek};
 pub use completion::{
-	CompletionModel, CompletionResponse, DEEPSEEK_CHAT, DEEPSEEK_REASONER,
+	AdditionalParameters, CompletionModel, CompletionResponse, DEEPSEEK_CHAT, DEEPSEEK_REASONER,
 	StreamingCompletionResponse,
 };
@@ -1,4 +1,4 @@
-use crate::client::{self, BearerAuth, Capable, Nothing, ProviderClient};
+use crate::client::{self, BearerAuth, Capable, ClientError, Nothing, ProviderClient};
 use crate::http_client;
 use crate::providers::openai_compat::{OpenAiCompat, PBuilder};
 
@@ -65,7 +65,11 @@ impl ProviderClient for Client {
 	/// and optionally from the `GALADRIEL_FINE_TUNE_API_KEY` environment variable.
 	/// Panics if the `GALADRIEL_API_KEY` environment variable is not set.
 	fn from_env() -> Self {
-		let api_key = std::env::var("GALADRIEL_API_KEY").expect("GALADRIEL_API_KEY not set");
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("GALADRIEL_API_KEY")?;
 		let fine_tune_api_key = std::env::var("GALADRIEL_FINE_TUNE_API_KEY").ok();
 
 		let mut builder = Self::builder().api_key(api_key);
@@ -74,7 +78,7 @@ impl ProviderClient for Client {
 			builder = builder.fine_tune_api_key(fine_tune_api_key);
 		}
 
-		builder.build().unwrap()
+		Ok(builder.build()?)
 	}
 
 	fn from_val((api_key, fine_tune_api_key): Self::Input) -> Self {
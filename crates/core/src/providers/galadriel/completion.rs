@@ -1,3 +1,5 @@
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use serde::{Deserialize, Serialize};
 use tracing::{Instrument, enabled, info_span};
 
@@ -8,6 +10,7 @@ use crate::message::MessageError;
 use crate::providers::openai;
 use crate::providers::openai_compat::{self, FlatApiError};
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 use crate::{json_utils, message};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,31 +19,110 @@ pub struct Message {
 	pub content: Option<String>,
 	#[serde(default, deserialize_with = "json_utils::null_or_vec")]
 	pub tool_calls: Vec<openai::completion::types::ToolCall>,
+	/// The ID of the tool call answered by a `tool` message
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tool_call_id: Option<String>,
 }
 
 impl Message {
-	fn system(preamble: &str) -> Self {
+	fn new(role: &str, content: String) -> Self {
 		Self {
-			role: "system".to_string(),
-			content: Some(preamble.to_string()),
+			role: role.to_string(),
+			content: Some(content),
 			tool_calls: Vec::new(),
+			tool_call_id: None,
 		}
 	}
+
+	fn system(preamble: &str) -> Self {
+		Self::new("system", preamble.to_string())
+	}
+}
+
+impl TryFrom<message::ToolResult> for Message {
+	type Error = message::MessageError;
+
+	fn try_from(tool_result: message::ToolResult) -> Result<Self, Self::Error> {
+		let content = tool_result
+			.content
+			.into_iter()
+			.map(|content| match content {
+				message::ToolResultContent::Text(text) => Ok(text.text),
+				message::ToolResultContent::Image(_) => Err(MessageError::ConversionError(
+					"Galadriel currently doesn't support images in tool results.".into(),
+				)),
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(Self {
+			tool_call_id: Some(tool_result.id),
+			..Self::new("tool", content.join("\n"))
+		})
+	}
+}
+
+/// The text of a document, decoding encoded documents whose media type is text.
+fn document_text(document: message::Document) -> Result<String, MessageError> {
+	let is_text = document
+		.media_type
+		.as_ref()
+		.is_some_and(message::DocumentMediaType::is_text);
+
+	let bytes = match document.data {
+		message::DocumentSourceKind::String(text) => return Ok(text),
+		message::DocumentSourceKind::Base64(data) if is_text => BASE64_STANDARD
+			.decode(data)
+			.map_err(|e| MessageError::ConversionError(format!("Invalid base64 document: {e}")))?,
+		message::DocumentSourceKind::Raw(data) if is_text => data,
+		_ => {
+			return Err(MessageError::ConversionError(
+				"Galadriel only supports text documents.".into(),
+			));
+		}
+	};
+
+	String::from_utf8(bytes)
+		.map_err(|e| MessageError::ConversionError(format!("Document is not valid UTF-8: {e}")))
 }
 
-impl TryFrom<message::Message> for Message {
+impl TryFrom<message::Message> for Vec<Message> {
 	type Error = message::MessageError;
 
 	fn try_from(message: message::Message) -> Result<Self, Self::Error> {
 		match message {
-			message::Message::User { content } => Ok(Self {
-				role: "user".to_string(),
-				content: content.iter().find_map(|c| match c {
-					message::UserContent::Text(text) => Some(text.text.clone()),
-					_ => None,
-				}),
-				tool_calls: vec![],
-			}),
+			message::Message::User { content } => {
+				let mut messages = vec![];
+				let mut texts = vec![];
+
+				// Tool results go first, as they must directly follow the assistant's tool calls
+				for content in content {
+					match content {
+						message::UserContent::ToolResult(tool_result) => {
+							messages.push(Message::try_from(tool_result)?);
+						}
+						message::UserContent::Text(text) => texts.push(text.text),
+						message::UserContent::Document(document) => {
+							texts.push(document_text(document)?);
+						}
+						message::UserContent::Image(_) => {
+							return Err(MessageError::ConversionError(
+								"Galadriel currently doesn't support images.".into(),
+							));
+						}
+						message::UserContent::Audio(_) | message::UserContent::Video(_) => {
+							return Err(MessageError::ConversionError(
+								"Galadriel currently doesn't support audio or video.".into(),
+							));
+						}
+					}
+				}
+
+				if !texts.is_empty() {
+					messages.push(Message::new("user", texts.join("\n")));
+				}
+
+				Ok(messages)
+			}
 			message::Message::Assistant { content, .. } => {
 				let mut text_content: Option<String> = None;
 				let mut tool_calls = vec![];
@@ -71,14 +153,20 @@ impl TryFrom<message::Message> for Message {
 								"Galadriel currently doesn't support images.".into(),
 							));
 						}
+						message::AssistantContent::Document(_) => {
+							tracing::warn!(
+								"Galadriel doesn't support assistant documents, skipping"
+							);
+						}
 					}
 				}
 
-				Ok(Self {
+				Ok(vec![Message {
 					role: "assistant".to_string(),
 					content: text_content,
 					tool_calls,
-				})
+					tool_call_id: None,
+				}])
 			}
 		}
 	}
@@ -106,6 +194,8 @@ pub(crate) struct GaladrielCompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<crate::providers::openai::completion::types::ToolChoice>,
@@ -118,7 +208,7 @@ impl TryFrom<(&str, CompletionRequest)> for GaladrielCompletionRequest {
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
 		let mut partial_history = vec![];
-		if let Some(docs) = req.normalized_documents() {
+		if let Some(docs) = req.rendered_documents() {
 			partial_history.push(docs);
 		}
 		partial_history.extend(req.chat_history);
@@ -128,12 +218,9 @@ impl TryFrom<(&str, CompletionRequest)> for GaladrielCompletionRequest {
 			None => vec![],
 		};
 
-		full_history.extend(
-			partial_history
-				.into_iter()
-				.map(message::Message::try_into)
-				.collect::<Result<Vec<Message>, _>>()?,
-		);
+		for message in partial_history {
+			full_history.extend(Vec::<Message>::try_from(message)?);
+		}
 
 		let tool_choice = req
 			.tool_choice
@@ -145,6 +232,7 @@ impl TryFrom<(&str, CompletionRequest)> for GaladrielCompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools: req
 				.tools
 				.clone()
@@ -183,6 +271,8 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -192,6 +282,7 @@ where
 
 		let request =
 			GaladrielCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -209,7 +300,7 @@ where
 			.map_err(http_client::Error::from)?;
 
 		async move {
-			let response = openai_compat::send_and_parse::<
+			let (response, response_metadata) = openai_compat::send_and_parse::<
 				_,
 				openai::completion::types::CompletionResponse,
 				FlatApiError,
@@ -220,6 +311,7 @@ where
 			let span = tracing::Span::current();
 			span.record("gen_ai.response.id", response.id.clone());
 			span.record("gen_ai.response.model_name", response.model.clone());
+			span.record_output_messages(&response.choices);
 			if let Some(ref usage) = response.usage {
 				span.record("gen_ai.usage.input_tokens", usage.prompt_tokens);
 				span.record(
@@ -227,7 +319,8 @@ where
 					usage.total_tokens - usage.prompt_tokens,
 				);
 			}
-			response.try_into()
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
 		}
 		.instrument(span)
 		.await
@@ -244,9 +337,10 @@ where
 		let mut request =
 			GaladrielCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
 
-		let params = json_utils::merge(
+		let params = json_utils::deep_merge(
 			request.additional_params.unwrap_or(serde_json::json!({})),
 			serde_json::json!({"stream": true, "stream_options": {"include_usage": true} }),
+			json_utils::ArrayMergeStrategy::Replace,
 		);
 
 		request.additional_params = Some(params);
@@ -271,12 +365,13 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
-				gen_ai.input.messages = serde_json::to_string(&request.messages)?,
+				gen_ai.input.messages = tracing::field::Empty,
 				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		openai::completion::streaming::send_compatible_streaming_request(self.client.clone(), req)
 			.instrument(span)
@@ -299,6 +394,10 @@ where
 		}
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
@@ -306,6 +405,7 @@ where
 		completion::CompletionResponse<openai::completion::types::CompletionResponse>,
 		CompletionError,
 	> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		self.completion_impl(completion_request).await
 	}
 
@@ -313,6 +413,127 @@ where
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		self.stream_impl(completion_request).await
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::OneOrMany;
+	use crate::message::{AssistantContent, DocumentMediaType, ImageMediaType, UserContent};
+
+	fn request(mut chat_history: Vec<message::Message>) -> CompletionRequest {
+		let prompt = chat_history.pop().unwrap();
+
+		CompletionRequest::builder(prompt)
+			.messages(chat_history)
+			.preamble("You are a weather bot".to_string())
+			.build()
+	}
+
+	#[test]
+	fn test_tool_round_trip() {
+		let request = request(vec![
+			message::Message::user("What's the weather in Paris?"),
+			message::Message::Assistant {
+				id: None,
+				content: OneOrMany::one(AssistantContent::tool_call(
+					"call_1",
+					"get_weather",
+					json!({ "location": "Paris" }),
+				)),
+			},
+			message::Message::User {
+				content: OneOrMany::many([
+					UserContent::tool_result(
+						"call_1",
+						OneOrMany::one(message::ToolResultContent::text("Sunny")),
+					),
+					UserContent::text("And in"),
+					UserContent::text("Lyon?"),
+				])
+				.unwrap(),
+			},
+		]);
+
+		let request = GaladrielCompletionRequest::try_from(("gpt-4o", request)).unwrap();
+		let messages = serde_json::to_value(&request.messages).unwrap();
+		assert_eq!(
+			messages,
+			json!([
+				{ "role": "system", "content": "You are a weather bot", "tool_calls": [] },
+				{ "role": "user", "content": "What's the weather in Paris?", "tool_calls": [] },
+				{
+					"role": "assistant",
+					"content": null,
+					"tool_calls": [{
+						"id": "call_1",
+						"type": "function",
+						"function": { "name": "get_weather", "arguments": "{\"location\":\"Paris\"}" }
+					}]
+				},
+				{ "role": "tool", "content": "Sunny", "tool_calls": [], "tool_call_id": "call_1" },
+				{ "role": "user", "content": "And in\nLyon?", "tool_calls": [] }
+			])
+		);
+
+		let messages = serde_json::from_value::<Vec<Message>>(messages).unwrap();
+		assert_eq!(messages[2].tool_calls[0].id, "call_1");
+		assert_eq!(messages[3].tool_call_id.as_deref(), Some("call_1"));
+	}
+
+	#[test]
+	fn test_text_documents_are_decoded() {
+		let request = request(vec![message::Message::User {
+			content: OneOrMany::many([
+				UserContent::document("plain", None),
+				UserContent::Document(message::Document {
+					data: message::DocumentSourceKind::base64("aGVsbG8="),
+					media_type: Some(DocumentMediaType::TXT),
+					additional_params: None,
+				}),
+			])
+			.unwrap(),
+		}]);
+
+		let request = GaladrielCompletionRequest::try_from(("gpt-4o", request)).unwrap();
+		assert_eq!(request.messages[1].content.as_deref(), Some("plain\nhello"));
+	}
+
+	#[test]
+	fn test_binary_documents_are_an_error() {
+		for media_type in [Some(DocumentMediaType::PDF), None] {
+			let request = request(vec![message::Message::User {
+				content: OneOrMany::one(UserContent::Document(message::Document {
+					data: message::DocumentSourceKind::base64("aGVsbG8="),
+					media_type,
+					additional_params: None,
+				})),
+			}]);
+
+			let err = GaladrielCompletionRequest::try_from(("gpt-4o", request)).unwrap_err();
+			assert!(
+				err.to_string().contains("only supports text documents"),
+				"{err}"
+			);
+		}
+	}
+
+	#[test]
+	fn test_images_are_an_error() {
+		let request = request(vec![message::Message::User {
+			content: OneOrMany::many([
+				UserContent::text("What's in this picture?"),
+				UserContent::image_base64("aGVsbG8=", Some(ImageMediaType::PNG), None),
+			])
+			.unwrap(),
+		}]);
+
+		let err = GaladrielCompletionRequest::try_from(("gpt-4o", request)).unwrap_err();
+		assert!(err.to_string().contains("doesn't support images"), "{err}");
+	}
+}
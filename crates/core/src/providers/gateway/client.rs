@@ -0,0 +1,180 @@
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+use super::completion::CompletionModel;
+use crate::client::{
+	self, ApiKey, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider, ProviderBuilder,
+	ProviderClient,
+};
+use crate::http_client;
+
+/// Base URL of a LiteLLM proxy running locally with its default port
+pub const LITELLM_BASE_URL: &str = "http://localhost:4000/v1";
+
+/// Prefix of the headers LiteLLM adds to its responses, e.g. `x-litellm-response-cost`
+pub const LITELLM_HEADER_PREFIX: &str = "x-litellm-";
+
+#[derive(Debug, Clone)]
+pub struct GatewayExt {
+	header_prefixes: Vec<String>,
+}
+
+impl GatewayExt {
+	/// The response headers starting with one of the configured prefixes, with lowercase names.
+	pub(crate) fn gateway_headers(
+		&self,
+		headers: &HeaderMap,
+	) -> std::collections::HashMap<String, String> {
+		headers
+			.iter()
+			.filter(|(name, _)| {
+				self.header_prefixes
+					.iter()
+					.any(|prefix| name.as_str().starts_with(prefix.as_str()))
+			})
+			.filter_map(|(name, value)| {
+				Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+			})
+			.collect()
+	}
+}
+
+impl Provider for GatewayExt {
+	type Builder = GatewayBuilder;
+
+	const VERIFY_PATH: &'static str = "/models";
+
+	fn build<H>(
+		builder: &client::ClientBuilder<Self::Builder, GatewayKey, H>,
+	) -> http_client::Result<Self> {
+		Ok(Self {
+			header_prefixes: builder.ext().header_prefixes.clone(),
+		})
+	}
+}
+
+impl<H> Capabilities<H> for GatewayExt {
+	type Completion = Capable<CompletionModel<H>>;
+
+	type Embeddings = Nothing;
+	type Transcription = Nothing;
+	#[cfg(feature = "image")]
+	type ImageGeneration = Nothing;
+	#[cfg(feature = "audio")]
+	type AudioGeneration = Nothing;
+}
+
+impl DebugExt for GatewayExt {}
+
+#[derive(Debug, Clone)]
+pub struct GatewayBuilder {
+	auth_header: Option<String>,
+	header_prefixes: Vec<String>,
+}
+
+impl Default for GatewayBuilder {
+	fn default() -> Self {
+		Self {
+			auth_header: None,
+			header_prefixes: vec![LITELLM_HEADER_PREFIX.to_string()],
+		}
+	}
+}
+
+/// The key of the gateway, sent as a bearer token unless another header is set with
+/// [ClientBuilder::auth_header]. An empty key isn't sent at all.
+#[derive(Clone)]
+pub struct GatewayKey(String);
+
+impl<S> From<S> for GatewayKey
+where
+	S: Into<String>,
+{
+	fn from(value: S) -> Self {
+		Self(value.into())
+	}
+}
+
+/// The header is added by [GatewayBuilder::finish], which knows its name.
+impl ApiKey for GatewayKey {}
+
+pub type Client<H = reqwest::Client> = client::Client<GatewayExt, H>;
+pub type ClientBuilder<H = reqwest::Client> = client::ClientBuilder<GatewayBuilder, GatewayKey, H>;
+
+impl ProviderBuilder for GatewayBuilder {
+	type Output = GatewayExt;
+	type ApiKey = GatewayKey;
+
+	const BASE_URL: &'static str = LITELLM_BASE_URL;
+
+	fn finish<H>(
+		&self,
+		mut builder: client::ClientBuilder<Self, GatewayKey, H>,
+	) -> http_client::Result<client::ClientBuilder<Self, GatewayKey, H>> {
+		let GatewayKey(key) = builder.get_api_key();
+		if key.is_empty() {
+			return Ok(builder);
+		}
+
+		let (name, mut value) = match &self.auth_header {
+			Some(name) => (
+				HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+					http_client::Error::Instance(format!("Invalid auth header `{name}`").into())
+				})?,
+				HeaderValue::from_str(key)?,
+			),
+			None => (
+				http::header::AUTHORIZATION,
+				HeaderValue::from_str(&format!("Bearer {key}"))?,
+			),
+		};
+		value.set_sensitive(true);
+		builder.headers_mut().insert(name, value);
+
+		Ok(builder)
+	}
+}
+
+impl<H> ClientBuilder<H> {
+	/// Sends the key in `header` instead of as a bearer token in `Authorization`, e.g.
+	/// `x-litellm-api-key`.
+	pub fn auth_header(self, header: &str) -> Self {
+		self.over_ext(|ext| GatewayBuilder {
+			auth_header: Some(header.to_string()),
+			..ext
+		})
+	}
+
+	/// Also keeps the response headers starting with `prefix` on the raw responses, next to the
+	/// ones starting with [LITELLM_HEADER_PREFIX].
+	pub fn response_header_prefix(self, prefix: &str) -> Self {
+		self.over_ext(|mut ext| {
+			ext.header_prefixes.push(prefix.to_ascii_lowercase());
+			ext
+		})
+	}
+}
+
+impl ProviderClient for Client {
+	type Input = String;
+
+	/// Create a new gateway client from the `GATEWAY_API_KEY` environment variable, and
+	/// `GATEWAY_BASE_URL` if set. Panics if `GATEWAY_API_KEY` is not set.
+	fn from_env() -> Self {
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("GATEWAY_API_KEY")?;
+		let builder = Self::builder().api_key(api_key);
+
+		Ok(match std::env::var("GATEWAY_BASE_URL") {
+			Ok(base_url) => builder.base_url(base_url),
+			Err(_) => builder,
+		}
+		.build()?)
+	}
+
+	fn from_val(input: Self::Input) -> Self {
+		Self::builder().api_key(input).build().unwrap()
+	}
+}
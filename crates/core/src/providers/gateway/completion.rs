@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tracing::{Instrument, Level, enabled, info_span};
+
+use super::client::Client;
+use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage};
+use crate::http_client::{self, HttpClientExt};
+use crate::providers::openai;
+use crate::providers::openai::completion::streaming::send_streaming_request;
+use crate::providers::openai::completion::types::OpenAIRequestParams;
+use crate::providers::openai::error::parse_api_error;
+use crate::providers::openai_compat::{self, ApiResponse, NestedApiError};
+use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
+
+const PROVIDER_NAME: &str = "gateway";
+
+/// Header LiteLLM reports the cost of a request in
+const LITELLM_COST_HEADER: &str = "x-litellm-response-cost";
+
+/// A chat completion proxied by the gateway. Upstreams don't all fill in the same fields, so
+/// everything but the choices is optional.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompletionResponse {
+	#[serde(default)]
+	pub id: String,
+	#[serde(default)]
+	pub object: String,
+	#[serde(default)]
+	pub created: u64,
+	#[serde(default)]
+	pub model: String,
+	pub choices: Vec<Choice>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub system_fingerprint: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub usage: Option<Usage>,
+	/// The gateway headers of the HTTP response, see
+	/// [ClientBuilder::response_header_prefix](super::ClientBuilder::response_header_prefix)
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub headers: HashMap<String, String>,
+	/// Fields outside of the OpenAI schema, added by the gateway or the upstream
+	#[serde(flatten)]
+	pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CompletionResponse {
+	/// Cost of the request as reported by the gateway, in the usage of the body or in the
+	/// `x-litellm-response-cost` header.
+	pub fn cost(&self) -> Option<f64> {
+		self.usage
+			.as_ref()
+			.and_then(|usage| usage.cost)
+			.or_else(|| self.header(LITELLM_COST_HEADER)?.parse().ok())
+	}
+
+	/// The value of a gateway header, `name` is case-insensitive.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers
+			.get(&name.to_ascii_lowercase())
+			.map(String::as_str)
+	}
+}
+
+impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
+	type Error = CompletionError;
+
+	fn try_from(response: CompletionResponse) -> Result<Self, Self::Error> {
+		let openai_response = openai::completion::types::CompletionResponse {
+			id: response.id.clone(),
+			object: response.object.clone(),
+			created: response.created,
+			model: response.model.clone(),
+			system_fingerprint: None,
+			choices: response
+				.choices
+				.iter()
+				.map(|choice| openai::completion::types::Choice {
+					index: choice.index,
+					message: choice.message.clone(),
+					logprobs: None,
+					finish_reason: choice.finish_reason.clone().unwrap_or_default(),
+				})
+				.collect(),
+			usage: None,
+		};
+		let choice = completion::CompletionResponse::try_from(openai_response)?.choice;
+
+		Ok(completion::CompletionResponse {
+			choice,
+			usage: response.usage.token_usage().unwrap_or_default(),
+			raw_response: response,
+			response_metadata: None,
+		})
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Choice {
+	#[serde(default)]
+	pub index: usize,
+	pub message: openai::completion::types::Message,
+	/// `null` for some upstreams, e.g. when the response was cut short
+	#[serde(default)]
+	pub finish_reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Usage {
+	#[serde(default)]
+	pub prompt_tokens: usize,
+	#[serde(default)]
+	pub completion_tokens: usize,
+	#[serde(default)]
+	pub total_tokens: usize,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub prompt_tokens_details: Option<openai::completion::types::PromptTokensDetails>,
+	/// Cost of the request, reported by LiteLLM when cost tracking is enabled
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cost: Option<f64>,
+}
+
+impl GetTokenUsage for Usage {
+	fn token_usage(&self) -> Option<crate::completion::Usage> {
+		let mut usage = crate::completion::Usage::new();
+		usage.input_tokens = self.prompt_tokens as u64;
+		usage.output_tokens = self.completion_tokens as u64;
+		usage.total_tokens = if self.total_tokens == 0 {
+			usage.input_tokens + usage.output_tokens
+		} else {
+			self.total_tokens as u64
+		};
+		usage.cached_input_tokens = self
+			.prompt_tokens_details
+			.as_ref()
+			.map_or(0, |details| details.cached_tokens as u64);
+
+		Some(usage)
+	}
+}
+
+/// A model behind the gateway, named the way the gateway routes it, e.g.
+/// `anthropic/claude-3-5-sonnet`. The name is sent as is.
+#[derive(Clone)]
+pub struct CompletionModel<T = reqwest::Client> {
+	pub(crate) client: Client<T>,
+	pub model: String,
+}
+
+impl<T> CompletionModel<T> {
+	pub fn new(client: Client<T>, model: impl Into<String>) -> Self {
+		Self {
+			client,
+			model: model.into(),
+		}
+	}
+
+	fn request(
+		&self,
+		request: CompletionRequest,
+	) -> Result<openai::completion::types::CompletionRequest, CompletionError> {
+		openai::completion::types::CompletionRequest::try_from(OpenAIRequestParams {
+			model: self.model.clone(),
+			request,
+			strict_tools: false,
+			tool_result_array_content: false,
+			prediction: None,
+		})
+	}
+}
+
+impl<T> completion::CompletionModel for CompletionModel<T>
+where
+	T: HttpClientExt + Clone + Default + std::fmt::Debug + Send + 'static,
+{
+	type Response = CompletionResponse;
+	type StreamingResponse = openai::completion::streaming::StreamingCompletionResponse;
+
+	type Client = Client<T>;
+
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		Self::new(client.clone(), model)
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	async fn completion(
+		&self,
+		completion_request: CompletionRequest,
+	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
+		let span = if tracing::Span::current().is_disabled() {
+			info_span!(
+				target: "clankers::completions",
+				"chat",
+				gen_ai.operation.name = "chat",
+				gen_ai.provider.name = PROVIDER_NAME,
+				gen_ai.request.model = self.model,
+				gen_ai.system_instructions = &completion_request.preamble,
+				gen_ai.response.id = tracing::field::Empty,
+				gen_ai.response.model = tracing::field::Empty,
+				gen_ai.usage.output_tokens = tracing::field::Empty,
+				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
+			)
+		} else {
+			tracing::Span::current()
+		};
+
+		let request = self.request(completion_request)?;
+		span.record_input_messages(&request.messages);
+
+		if enabled!(Level::TRACE) {
+			tracing::trace!(
+				target: "clankers::completions",
+				"Gateway completion request: {}",
+				serde_json::to_string_pretty(&request)?
+			);
+		}
+
+		let body = serde_json::to_vec(&request)?;
+		let req = self
+			.client
+			.post("/chat/completions")?
+			.body(body)
+			.map_err(http_client::Error::from)?;
+
+		async move {
+			let response = self
+				.client
+				.send(req)
+				.await
+				.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
+			let status = response.status();
+			let headers = response.headers().clone();
+			let text = http_client::text(response).await?;
+
+			if !status.is_success() {
+				return Err(CompletionError::ApiError(
+					parse_api_error(status, text).with_retry_after(&headers),
+				));
+			}
+
+			let mut response = match serde_json::from_str::<
+				ApiResponse<CompletionResponse, NestedApiError>,
+			>(&text)?
+			{
+				ApiResponse::Ok(response) => response,
+				ApiResponse::Err(err) => return Err(err.into()),
+			};
+			response.headers = self.client.ext().gateway_headers(&headers);
+
+			let span = tracing::Span::current();
+			span.record("gen_ai.response.id", &response.id);
+			span.record("gen_ai.response.model", &response.model);
+			span.record_token_usage(&response.usage);
+			match response.cost() {
+				Some(cost) => {
+					span.record("gen_ai.usage.cost", cost);
+				}
+				None => span.record_cost(
+					self.client.pricing(),
+					PROVIDER_NAME,
+					&self.model,
+					&response.usage,
+				),
+			}
+			span.record_output_messages(&response.choices);
+
+			if enabled!(Level::TRACE) {
+				tracing::trace!(
+					target: "clankers::completions",
+					"Gateway completion response: {}",
+					serde_json::to_string_pretty(&response)?
+				);
+			}
+
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
+		}
+		.instrument(span)
+		.await
+	}
+
+	async fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let mut request = self.client.apply_default_params(request);
+		let span = openai_compat::streaming_span(PROVIDER_NAME, &self.model, &request.preamble);
+
+		openai_compat::merge_stream_params(&mut request.additional_params);
+		let request = self.request(request)?;
+		span.record_input_messages(&request.messages);
+
+		if enabled!(Level::TRACE) {
+			tracing::trace!(
+				target: "clankers::completions",
+				"Gateway streaming completion request: {}",
+				serde_json::to_string_pretty(&request)?
+			);
+		}
+
+		let body = serde_json::to_vec(&request)?;
+		let req = self
+			.client
+			.post("/chat/completions")?
+			.body(body)
+			.map_err(http_client::Error::from)?;
+
+		let cost = self.client.cost_recorder(PROVIDER_NAME, &self.model);
+		send_streaming_request(self.client.clone(), req, Some(cost))
+			.instrument(span)
+			.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use http::header::AUTHORIZATION;
+	use serde_json::json;
+
+	use crate::client::CompletionClient;
+	use crate::completion::CompletionModel as _;
+	use crate::message::AssistantContent;
+	use crate::providers::gateway::{self, LITELLM_BASE_URL};
+	use crate::test_utils::MockSseClient;
+
+	/// An Anthropic response translated by LiteLLM, with a tool call and no text, no finish
+	/// reason and Anthropic specific fields
+	const ANTHROPIC_RESPONSE: &str = r#"{
+		"id": "chatcmpl-0c5b6f4e",
+		"created": 1741290958,
+		"model": "claude-3-5-sonnet-20241022",
+		"object": "chat.completion",
+		"system_fingerprint": null,
+		"choices": [{
+			"finish_reason": null,
+			"index": 0,
+			"message": {
+				"content": null,
+				"role": "assistant",
+				"tool_calls": [{
+					"index": 0,
+					"function": { "arguments": "{\"city\": \"Paris\"}", "name": "get_weather" },
+					"id": "toolu_01",
+					"type": "function"
+				}],
+				"function_call": null,
+				"provider_specific_fields": { "citations": null, "thinking_blocks": null }
+			}
+		}],
+		"usage": {
+			"completion_tokens": 20,
+			"prompt_tokens": 50,
+			"total_tokens": 70,
+			"prompt_tokens_details": { "cached_tokens": 10 },
+			"cache_creation_input_tokens": 0,
+			"cache_read_input_tokens": 10,
+			"cost": 0.00045
+		},
+		"service_tier": null
+	}"#;
+
+	/// An OpenAI response passed through by the gateway, without usage
+	const OPENAI_RESPONSE: &str = r#"{
+		"id": "chatcmpl-1",
+		"object": "chat.completion",
+		"created": 1741290958,
+		"model": "gpt-4o-2024-08-06",
+		"choices": [{
+			"index": 0,
+			"message": { "role": "assistant", "content": "Hello!" },
+			"logprobs": null,
+			"finish_reason": "stop"
+		}]
+	}"#;
+
+	#[tokio::test]
+	async fn test_anthropic_response_through_gateway() {
+		let http_client = MockSseClient::default()
+			.with_json_response(ANTHROPIC_RESPONSE)
+			.with_response_header("x-litellm-model-id", "claude-prod")
+			.with_response_header("x-litellm-response-cost", "0.0005")
+			.with_response_header("x-request-id", "req_1");
+		let client = gateway::Client::<MockSseClient>::builder()
+			.api_key("sk-1234")
+			.auth_header("x-litellm-api-key")
+			.base_url("https://litellm.internal/v1")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+
+		let response = client
+			.completion_model("anthropic/claude-3-5-sonnet")
+			.completion_request("What's the weather in Paris?")
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(
+			http_client.request_uris(),
+			["https://litellm.internal/v1/chat/completions"]
+		);
+		let headers = &http_client.request_headers()[0];
+		assert_eq!(headers["x-litellm-api-key"], "sk-1234");
+		assert!(!headers.contains_key(AUTHORIZATION));
+		assert_eq!(
+			http_client.request_bodies()[0]["model"],
+			"anthropic/claude-3-5-sonnet"
+		);
+
+		let AssistantContent::ToolCall(tool_call) = response.choice.first() else {
+			panic!("Expected a tool call");
+		};
+		assert_eq!(tool_call.id, "toolu_01");
+		assert_eq!(tool_call.function.name, "get_weather");
+		assert_eq!(tool_call.function.arguments, json!({ "city": "Paris" }));
+		assert_eq!(response.usage.input_tokens, 50);
+		assert_eq!(response.usage.output_tokens, 20);
+		assert_eq!(response.usage.cached_input_tokens, 10);
+
+		let raw = &response.raw_response;
+		assert_eq!(raw.choices[0].finish_reason, None);
+		// The cost in the body wins over the header
+		assert_eq!(raw.cost(), Some(0.00045));
+		assert_eq!(raw.header("X-LiteLLM-Model-Id"), Some("claude-prod"));
+		assert_eq!(raw.header("x-request-id"), None);
+		assert_eq!(raw.extra["service_tier"], serde_json::Value::Null);
+	}
+
+	#[tokio::test]
+	async fn test_openai_response_through_gateway() {
+		let http_client = MockSseClient::default()
+			.with_json_response(OPENAI_RESPONSE)
+			.with_response_header("x-litellm-response-cost", "0.0001")
+			.with_response_header("x-portkey-trace-id", "trace-1");
+		let client = gateway::Client::<MockSseClient>::builder()
+			.api_key("sk-1234")
+			.response_header_prefix("X-Portkey-")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+
+		let response = client
+			.completion_model("openai/gpt-4o")
+			.completion_request("Hello")
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(
+			http_client.request_uris(),
+			[format!("{LITELLM_BASE_URL}/chat/completions")]
+		);
+		assert_eq!(
+			http_client.request_headers()[0][AUTHORIZATION],
+			"Bearer sk-1234"
+		);
+		assert_eq!(http_client.request_bodies()[0]["model"], "openai/gpt-4o");
+
+		assert_eq!(response.choice.first(), AssistantContent::text("Hello!"));
+		assert_eq!(response.usage, crate::completion::Usage::new());
+
+		let raw = &response.raw_response;
+		assert!(raw.usage.is_none());
+		assert_eq!(raw.choices[0].finish_reason.as_deref(), Some("stop"));
+		assert_eq!(raw.cost(), Some(0.0001));
+		assert_eq!(raw.header("x-portkey-trace-id"), Some("trace-1"));
+	}
+}
@@ -0,0 +1,26 @@
+//! Client for OpenAI-compatible gateways such as [LiteLLM](https://docs.litellm.ai), which pick
+//! the upstream provider of a request from the model name, e.g. `anthropic/claude-3-5-sonnet`.
+//!
+//! Model names are sent as is. The headers the gateway adds to its responses (`x-litellm-*` by
+//! default) and the cost it reports are kept on the raw response, see
+//! [CompletionResponse::cost].
+//!
+//! # Example
+//! ```
+//! use clankers::providers::gateway;
+//!
+//! let client = gateway::Client::builder()
+//!     .api_key("sk-1234")
+//!     .base_url("https://litellm.internal/v1")
+//!     .auth_header("x-litellm-api-key")
+//!     .build()
+//!     .unwrap();
+//!
+//! let sonnet = client.completion_model("anthropic/claude-3-5-sonnet");
+//! ```
+
+pub mod client;
+pub mod completion;
+
+pub use client::{Client, ClientBuilder, GatewayKey, LITELLM_BASE_URL, LITELLM_HEADER_PREFIX};
+pub use completion::{Choice, CompletionModel, CompletionResponse, Usage};
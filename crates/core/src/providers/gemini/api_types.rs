@@ -287,7 +287,7 @@ impl TryFrom<(ImageMediaType, DocumentSourceKind)> for PartKind {
 		(mime_type, doc_src): (ImageMediaType, DocumentSourceKind),
 	) -> Result<Self, Self::Error> {
 		let mime_type = mime_type.to_mime_type().to_string();
-		let part = match doc_src {
+		let part = match doc_src.into_base64() {
 			DocumentSourceKind::Url(url) => PartKind::FileData(FileData {
 				mime_type: Some(mime_type),
 				file_uri: url,
@@ -295,12 +295,7 @@ impl TryFrom<(ImageMediaType, DocumentSourceKind)> for PartKind {
 			DocumentSourceKind::Base64(data) | DocumentSourceKind::String(data) => {
 				PartKind::InlineData(Blob { mime_type, data })
 			}
-			DocumentSourceKind::Raw(_) => {
-				return Err(message::MessageError::ConversionError(
-					"Raw files not supported, encode as base64 first".into(),
-				));
-			}
-			DocumentSourceKind::Unknown => {
+			DocumentSourceKind::Raw(_) | DocumentSourceKind::Unknown => {
 				return Err(message::MessageError::ConversionError(
 					"Can't convert an unknown document source".to_string(),
 				));
@@ -314,7 +309,8 @@ impl TryFrom<(ImageMediaType, DocumentSourceKind)> for PartKind {
 impl TryFrom<message::UserContent> for Part {
 	type Error = message::MessageError;
 
-	fn try_from(content: message::UserContent) -> Result<Self, Self::Error> {
+	fn try_from(mut content: message::UserContent) -> Result<Self, Self::Error> {
+		content.sniff_media_type();
 		match content {
 			message::UserContent::Text(message::Text { text }) => Ok(Part {
 				thought: Some(false),
@@ -326,7 +322,7 @@ impl TryFrom<message::UserContent> for Part {
 				let mut response_json: Option<serde_json::Value> = None;
 				let mut parts: Vec<FunctionResponsePart> = Vec::new();
 
-				for item in content.iter() {
+				for item in content {
 					match item {
 						message::ToolResultContent::Text(text) => {
 							let result: serde_json::Value = serde_json::from_str(&text.text)
@@ -349,7 +345,7 @@ impl TryFrom<message::UserContent> for Part {
 							});
 						}
 						message::ToolResultContent::Image(image) => {
-							let part = match &image.data {
+							let part = match image.data.into_base64() {
 								DocumentSourceKind::Base64(b64) => {
 									let mime_type = image
 										.media_type
@@ -363,7 +359,7 @@ impl TryFrom<message::UserContent> for Part {
 									FunctionResponsePart {
 										inline_data: Some(FunctionResponseInlineData {
 											mime_type: mime_type.to_string(),
-											data: b64.clone(),
+											data: b64,
 											display_name: None,
 										}),
 										file_data: None,
@@ -379,7 +375,7 @@ impl TryFrom<message::UserContent> for Part {
 										inline_data: None,
 										file_data: Some(FileData {
 											mime_type,
-											file_uri: url.clone(),
+											file_uri: url,
 										}),
 									}
 								}
@@ -443,7 +439,7 @@ impl TryFrom<message::UserContent> for Part {
 				if !media_type.is_code() {
 					let mime_type = media_type.to_mime_type().to_string();
 
-					let part = match data {
+					let part = match data.into_base64() {
 						DocumentSourceKind::Url(file_uri) => PartKind::FileData(FileData {
 							mime_type: Some(mime_type),
 							file_uri,
@@ -451,11 +447,6 @@ impl TryFrom<message::UserContent> for Part {
 						DocumentSourceKind::Base64(data) | DocumentSourceKind::String(data) => {
 							PartKind::InlineData(Blob { mime_type, data })
 						}
-						DocumentSourceKind::Raw(_) => {
-							return Err(message::MessageError::ConversionError(
-								"Raw files not supported, encode as base64 first".into(),
-							));
-						}
 						_ => {
 							return Err(message::MessageError::ConversionError(
 								"Document has no body".to_string(),
@@ -486,7 +477,7 @@ impl TryFrom<message::UserContent> for Part {
 
 				let mime_type = media_type.to_mime_type().to_string();
 
-				let part = match data {
+				let part = match data.into_base64() {
 					DocumentSourceKind::Base64(data) => {
 						PartKind::InlineData(Blob { data, mime_type })
 					}
@@ -500,12 +491,7 @@ impl TryFrom<message::UserContent> for Part {
 							"Strings cannot be used as audio files!".into(),
 						));
 					}
-					DocumentSourceKind::Raw(_) => {
-						return Err(message::MessageError::ConversionError(
-							"Raw files not supported, encode as base64 first".into(),
-						));
-					}
-					DocumentSourceKind::Unknown => {
+					DocumentSourceKind::Raw(_) | DocumentSourceKind::Unknown => {
 						return Err(message::MessageError::ConversionError(
 							"Content has no body".to_string(),
 						));
@@ -614,6 +600,49 @@ impl TryFrom<message::AssistantContent> for Part {
 					"Media type for image is required for Gemini".to_string(),
 				)),
 			},
+			message::AssistantContent::Document(message::Document {
+				data,
+				media_type,
+				additional_params,
+			}) => {
+				// Files with a mime type unknown to clankers keep it in the additional params
+				let mime_type = media_type
+					.map(|media_type| media_type.to_mime_type().to_string())
+					.or_else(|| {
+						additional_params
+							.as_ref()?
+							.get("mime_type")?
+							.as_str()
+							.map(str::to_string)
+					})
+					.ok_or_else(|| {
+						MessageError::ConversionError(
+							"A mime type is required for documents sent to Gemini".to_string(),
+						)
+					})?;
+
+				let part = match data {
+					DocumentSourceKind::Url(file_uri) => PartKind::FileData(FileData {
+						mime_type: Some(mime_type),
+						file_uri,
+					}),
+					DocumentSourceKind::Base64(data) => {
+						PartKind::InlineData(Blob { mime_type, data })
+					}
+					_ => {
+						return Err(MessageError::ConversionError(
+							"Only base64 encoded or URL documents can be sent to Gemini"
+								.to_string(),
+						));
+					}
+				};
+
+				Ok(Part {
+					thought: Some(false),
+					part,
+					..Default::default()
+				})
+			}
 			message::AssistantContent::ToolCall(tool_call) => Ok(tool_call.into()),
 			message::AssistantContent::Reasoning(message::Reasoning { reasoning, .. }) => {
 				Ok(Part {
@@ -795,9 +824,10 @@ impl GetTokenUsage for UsageMetadata {
 	fn token_usage(&self) -> Option<crate::completion::Usage> {
 		let mut usage = crate::completion::Usage::new();
 
+		// The prompt token count includes the cached content
 		usage.input_tokens = self.prompt_token_count as u64;
-		usage.output_tokens = (self.cached_content_token_count.unwrap_or_default()
-			+ self.candidates_token_count.unwrap_or_default()
+		usage.cached_input_tokens = self.cached_content_token_count.unwrap_or_default() as u64;
+		usage.output_tokens = (self.candidates_token_count.unwrap_or_default()
 			+ self.thoughts_token_count.unwrap_or_default()) as u64;
 		usage.total_tokens = usage.input_tokens + usage.output_tokens;
 
@@ -1013,10 +1043,16 @@ impl Default for GenerationConfig {
 	}
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Configuration of the thinking of Gemini 2.5 models.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ThinkingConfig {
-	pub thinking_budget: u32,
+	/// The number of thinking tokens the model may use, `0` disabling thinking and `-1` letting
+	/// the model decide.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub thinking_budget: Option<i32>,
+	/// Whether the thoughts are included in the response, as parts with `thought: true`.
+	#[serde(skip_serializing_if = "Option::is_none")]
 	pub include_thoughts: Option<bool>,
 }
 
@@ -1354,7 +1390,10 @@ pub struct GenerateContentRequest {
 	/// Optional. Developer set system instruction(s). Currently, text only.
 	/// From [Gemini API Reference](https://ai.google.dev/gemini-api/docs/system-instructions?lang=rest)
 	pub system_instruction: Option<Content>,
-	// cachedContent: Optional<String>
+	/// Optional. The name of the cached content to use as context, e.g. `cachedContents/abc123`.
+	/// See [caching](super::caching).
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub cached_content: Option<String>,
 	/// Additional parameters.
 	#[serde(flatten, skip_serializing_if = "Option::is_none")]
 	pub additional_params: Option<serde_json::Value>,
@@ -1388,8 +1427,12 @@ pub enum FunctionCallingMode {
 	#[default]
 	Auto,
 	None,
+	/// Forces a function call, restricted to `allowed_function_names` when set
 	Any {
-		#[serde(skip_serializing_if = "Option::is_none")]
+		#[serde(
+			rename = "allowedFunctionNames",
+			skip_serializing_if = "Option::is_none"
+		)]
 		allowed_function_names: Option<Vec<String>>,
 	},
 }
@@ -1403,6 +1446,11 @@ impl TryFrom<message::ToolChoice> for FunctionCallingMode {
 			message::ToolChoice::Required => Self::Any {
 				allowed_function_names: None,
 			},
+			message::ToolChoice::Specific { function_names } if function_names.is_empty() => {
+				return Err(CompletionError::RequestError(
+					"`ToolChoice::Specific` requires at least one function name".into(),
+				));
+			}
 			message::ToolChoice::Specific { function_names } => Self::Any {
 				allowed_function_names: Some(function_names),
 			},
@@ -0,0 +1,388 @@
+//! Gemini explicit context caching, which stores a prefix of a conversation (e.g. a large
+//! document and the system instruction) to be referenced by later requests at a lower cost.
+//! From [Gemini API Reference](https://ai.google.dev/api/caching)
+//!
+//! # Example
+//! ```
+//! use std::time::Duration;
+//!
+//! use clankers::{agent::AgentBuilder, completion::Prompt, message::Message, providers::gemini};
+//!
+//! let client = gemini::Client::from_env();
+//! let model = client.completion_model(gemini::completion::GEMINI_2_5_FLASH);
+//! let contract = std::fs::read_to_string("contract.txt")?;
+//!
+//! let cache = model
+//!     .create_cached_content(
+//!         vec![Message::user(contract)],
+//!         Some("You answer questions about the contract.".to_string()),
+//!         Duration::from_secs(600),
+//!     )
+//!     .await?;
+//!
+//! let agent = AgentBuilder::new(model.with_cached_content(cache.name)).build();
+//! let answer = agent.prompt("When does the contract end?").await?;
+//! ```
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use super::api_types::{Content, Role};
+use super::completion::CompletionModel;
+use super::error::parse_api_error;
+use crate::completion::CompletionError;
+use crate::http_client::{self, HttpClientExt};
+use crate::message::Message;
+use crate::wasm_compat::WasmCompatSend;
+
+/// Prefix of the resource names of cached contents
+const CACHED_CONTENTS: &str = "cachedContents/";
+
+/// Returns the resource name of a cached content from its name or its id.
+pub(crate) fn cached_content_name(name: impl Into<String>) -> String {
+	let name = name.into();
+	if name.starts_with(CACHED_CONTENTS) {
+		name
+	} else {
+		format!("{CACHED_CONTENTS}{name}")
+	}
+}
+
+/// Content that has been preprocessed and can be used in subsequent requests to the model.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContent {
+	/// The resource name of the cached content, e.g. `cachedContents/abc123`
+	pub name: String,
+	/// The resource name of the model the content was cached for, e.g. `models/gemini-2.5-flash`
+	pub model: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub display_name: Option<String>,
+	/// Creation time, in RFC 3339 format
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub create_time: Option<String>,
+	/// Last update time, in RFC 3339 format
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub update_time: Option<String>,
+	/// Expiration time, in RFC 3339 format
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub expire_time: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub usage_metadata: Option<CachedContentUsageMetadata>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContentUsageMetadata {
+	/// Total number of tokens that the cached content consumes
+	#[serde(default)]
+	pub total_token_count: i32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateCachedContentRequest {
+	model: String,
+	contents: Vec<Content>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	system_instruction: Option<Content>,
+	ttl: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListCachedContentsResponse {
+	#[serde(default)]
+	cached_contents: Vec<CachedContent>,
+	next_page_token: Option<String>,
+}
+
+/// Formats a duration as a protobuf `Duration`, e.g. `300s` or `1.5s`.
+fn format_ttl(ttl: Duration) -> String {
+	format!("{}s", ttl.as_secs_f64())
+}
+
+impl<T> CompletionModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	/// Caches `contents` and the system instruction for this model, for `ttl`.
+	/// Reference the returned [CachedContent::name] with [CompletionModel::with_cached_content].
+	pub async fn create_cached_content(
+		&self,
+		contents: Vec<Message>,
+		system_instruction: Option<String>,
+		ttl: Duration,
+	) -> Result<CachedContent, CompletionError> {
+		let request = CreateCachedContentRequest {
+			model: format!("models/{}", self.model),
+			contents: contents
+				.into_iter()
+				.map(|msg| {
+					msg.try_into()
+						.map_err(|e| CompletionError::RequestError(Box::new(e)))
+				})
+				.collect::<Result<Vec<_>, _>>()?,
+			system_instruction: system_instruction.map(|instruction| Content {
+				parts: vec![instruction.into()],
+				role: Some(Role::Model),
+			}),
+			ttl: format_ttl(ttl),
+		};
+
+		let req = self
+			.client
+			.post("/v1beta/cachedContents")?
+			.body(serde_json::to_vec(&request)?)
+			.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+		self.send_caching_request(req).await
+	}
+
+	/// Gets a cached content from its name, e.g. `cachedContents/abc123`.
+	pub async fn get_cached_content(
+		&self,
+		name: impl Into<String>,
+	) -> Result<CachedContent, CompletionError> {
+		let req = self
+			.client
+			.get(format!("/v1beta/{}", cached_content_name(name)))?
+			.body(http_client::NoBody)
+			.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+		self.send_caching_request(req).await
+	}
+
+	/// Lists all the cached contents of the API key, following every page.
+	pub async fn list_cached_contents(&self) -> Result<Vec<CachedContent>, CompletionError> {
+		let mut cached_contents = Vec::new();
+		let mut page_token: Option<String> = None;
+
+		loop {
+			// Page tokens are base64-like, the encoding also keeps their `/` out of the URL join
+			let path = match &page_token {
+				Some(token) => format!(
+					"/v1beta/cachedContents?pageToken={}",
+					url::form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>()
+				),
+				None => "/v1beta/cachedContents".to_string(),
+			};
+			let req = self
+				.client
+				.get(path)?
+				.body(http_client::NoBody)
+				.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+			let page: ListCachedContentsResponse = self.send_caching_request(req).await?;
+			cached_contents.extend(page.cached_contents);
+
+			match page.next_page_token {
+				Some(token) if !token.is_empty() => page_token = Some(token),
+				_ => return Ok(cached_contents),
+			}
+		}
+	}
+
+	/// Deletes a cached content from its name, e.g. `cachedContents/abc123`.
+	pub async fn delete_cached_content(
+		&self,
+		name: impl Into<String>,
+	) -> Result<(), CompletionError> {
+		let req = self
+			.client
+			.delete(format!("/v1beta/{}", cached_content_name(name)))?
+			.body(http_client::NoBody)
+			.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+		self.send_caching_request::<_, serde_json::Value>(req)
+			.await
+			.map(|_| ())
+	}
+
+	async fn send_caching_request<B, R>(&self, req: http::Request<B>) -> Result<R, CompletionError>
+	where
+		B: Into<Bytes> + WasmCompatSend,
+		R: serde::de::DeserializeOwned,
+	{
+		let response = self
+			.client
+			.send::<_, Vec<u8>>(req)
+			.await
+			.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+
+		let status = response.status();
+		let body = response
+			.into_body()
+			.await
+			.map_err(CompletionError::HttpError)?;
+
+		if !status.is_success() {
+			return Err(CompletionError::ApiError(parse_api_error(
+				status,
+				String::from_utf8_lossy(&body).into(),
+			)));
+		}
+
+		Ok(serde_json::from_slice(&body)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::completion::CompletionModel as _;
+	use crate::providers::gemini::Client;
+	use crate::test_utils::MockSseClient;
+
+	const CACHED_CONTENT: &str = r#"{
+		"name": "cachedContents/abc123",
+		"model": "models/gemini-2.5-flash",
+		"createTime": "2025-06-01T10:00:00.000000Z",
+		"updateTime": "2025-06-01T10:00:00.000000Z",
+		"expireTime": "2025-06-01T10:10:00.000000Z",
+		"usageMetadata": { "totalTokenCount": 40213 }
+	}"#;
+
+	fn model(http_client: MockSseClient) -> CompletionModel<MockSseClient> {
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap();
+
+		CompletionModel::new(client, "gemini-2.5-flash")
+	}
+
+	#[tokio::test]
+	async fn test_create_cached_content() {
+		let http_client = MockSseClient::default().with_json_response(CACHED_CONTENT);
+
+		let cache = model(http_client.clone())
+			.create_cached_content(
+				vec![Message::user("The contract")],
+				Some("Answer questions about the contract.".to_string()),
+				Duration::from_secs(600),
+			)
+			.await
+			.unwrap();
+
+		assert_eq!(cache.name, "cachedContents/abc123");
+		assert_eq!(cache.usage_metadata.unwrap().total_token_count, 40213);
+		assert!(
+			http_client.request_uris()[0].contains("/v1beta/cachedContents?key="),
+			"{}",
+			http_client.request_uris()[0]
+		);
+		assert_eq!(
+			http_client.request_bodies()[0],
+			json!({
+				"model": "models/gemini-2.5-flash",
+				"contents": [{
+					"role": "user",
+					"parts": [{ "text": "The contract", "thought": false }]
+				}],
+				"systemInstruction": {
+					"role": "model",
+					"parts": [{ "text": "Answer questions about the contract.", "thought": false }]
+				},
+				"ttl": "600s"
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_completion_with_cached_content() {
+		let response = json!({
+			"candidates": [{
+				"content": { "role": "model", "parts": [{ "text": "It ends in 2030." }] },
+				"finishReason": "STOP"
+			}],
+			"responseId": "response-1",
+			"modelVersion": "gemini-2.5-flash",
+			"usageMetadata": {
+				"promptTokenCount": 40230,
+				"cachedContentTokenCount": 40213,
+				"candidatesTokenCount": 6,
+				"totalTokenCount": 40236
+			}
+		});
+		let http_client = MockSseClient::default().with_json_response(response.to_string());
+		let model = model(http_client.clone()).with_cached_content("abc123");
+
+		let response = model
+			.completion_request("When does the contract end?")
+			.preamble("Answer questions about the contract.".to_string())
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(response.usage.input_tokens, 40230);
+		assert_eq!(response.usage.cached_input_tokens, 40213);
+		assert_eq!(response.usage.output_tokens, 6);
+
+		let body = &http_client.request_bodies()[0];
+		assert_eq!(body["cachedContent"], "cachedContents/abc123");
+		assert_eq!(
+			body["contents"],
+			json!([{
+				"role": "user",
+				"parts": [{ "text": "When does the contract end?", "thought": false }]
+			}])
+		);
+		// The system instruction is part of the cache, so it's not sent again
+		assert!(body["systemInstruction"].is_null(), "{body}");
+	}
+
+	#[tokio::test]
+	async fn test_list_cached_contents_follows_pages() {
+		let first_page = format!(
+			r#"{{ "cachedContents": [{CACHED_CONTENT}], "nextPageToken": "a+b//c/=&d=" }}"#
+		);
+		let second_page = format!(r#"{{ "cachedContents": [{CACHED_CONTENT}] }}"#);
+		let http_client = MockSseClient::default().with_json_responses([first_page, second_page]);
+
+		let cached_contents = model(http_client.clone())
+			.list_cached_contents()
+			.await
+			.unwrap();
+
+		assert_eq!(cached_contents.len(), 2);
+		let uris = http_client.request_uris();
+		assert_eq!(uris.len(), 2);
+		assert!(
+			uris[0].contains("/v1beta/cachedContents?key="),
+			"{}",
+			uris[0]
+		);
+		assert!(
+			uris[1].contains("/v1beta/cachedContents?pageToken=a%2Bb%2F%2Fc%2F%3D%26d%3D&key="),
+			"{}",
+			uris[1]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_delete_cached_content() {
+		let http_client = MockSseClient::default().with_json_response("{}");
+
+		model(http_client.clone())
+			.delete_cached_content("cachedContents/abc123")
+			.await
+			.unwrap();
+
+		assert!(
+			http_client.request_uris()[0].contains("/v1beta/cachedContents/abc123?key="),
+			"{}",
+			http_client.request_uris()[0]
+		);
+	}
+
+	#[test]
+	fn test_format_ttl() {
+		assert_eq!(format_ttl(Duration::from_secs(300)), "300s");
+		assert_eq!(format_ttl(Duration::from_millis(1500)), "1.5s");
+	}
+}
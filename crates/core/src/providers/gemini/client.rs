@@ -3,8 +3,8 @@ use std::fmt::Debug;
 #[cfg(feature = "image")]
 use crate::client::Nothing;
 use crate::client::{
-	self, ApiKey, Capabilities, Capable, DebugExt, Provider, ProviderBuilder, ProviderClient,
-	Transport,
+	self, ApiKey, Capabilities, Capable, ClientError, DebugExt, Provider, ProviderBuilder,
+	ProviderClient, Transport,
 };
 use crate::http_client;
 
@@ -54,23 +54,13 @@ impl Provider for GeminiExt {
 	}
 
 	fn build_uri(&self, base_url: &str, path: &str, transport: Transport) -> String {
+		let uri = client::join_url(base_url, path);
+		// The path may already have a query, e.g. a page token
+		let separator = if uri.contains('?') { '&' } else { '?' };
+
 		match transport {
-			Transport::Sse => {
-				format!(
-					"{}/{}?alt=sse&key={}",
-					base_url,
-					path.trim_start_matches('/'),
-					self.api_key
-				)
-			}
-			_ => {
-				format!(
-					"{}/{}?key={}",
-					base_url,
-					path.trim_start_matches('/'),
-					self.api_key
-				)
-			}
+			Transport::Sse => format!("{uri}{separator}alt=sse&key={}", self.api_key),
+			_ => format!("{uri}{separator}key={}", self.api_key),
 		}
 	}
 }
@@ -99,8 +89,12 @@ impl ProviderClient for Client {
 	/// Create a new Google Gemini client from the `GEMINI_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
-		let api_key = std::env::var("GEMINI_API_KEY").expect("GEMINI_API_KEY not set");
-		Self::new(api_key).unwrap()
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("GEMINI_API_KEY")?;
+		Ok(Self::new(api_key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
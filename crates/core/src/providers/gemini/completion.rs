@@ -26,9 +26,11 @@ use tracing_futures::Instrument;
 
 use super::Client;
 use super::api_types::{
-	Content, FunctionDeclaration, GenerateContentRequest, GenerateContentResponse, Part, PartKind,
-	Role, Schema, Tool,
+	Content, FunctionDeclaration, GenerateContentRequest, GenerateContentResponse,
+	GenerationConfig, Part, PartKind, Role, Schema, ThinkingConfig, Tool,
 };
+use super::caching::cached_content_name;
+use super::error::parse_api_error;
 use crate::OneOrMany;
 use crate::completion::{self, CompletionError, CompletionRequest};
 use crate::http_client::HttpClientExt;
@@ -41,6 +43,8 @@ use crate::telemetry::SpanCombinator;
 pub struct CompletionModel<T = reqwest::Client> {
 	pub(crate) client: Client<T>,
 	pub model: String,
+	pub(crate) cached_content: Option<String>,
+	pub(crate) thinking_config: ThinkingConfig,
 }
 
 impl<T> CompletionModel<T> {
@@ -48,14 +52,75 @@ impl<T> CompletionModel<T> {
 		Self {
 			client,
 			model: model.into(),
+			cached_content: None,
+			thinking_config: ThinkingConfig::default(),
 		}
 	}
 
 	pub fn with_model(client: Client<T>, model: &str) -> Self {
-		Self {
-			client,
-			model: model.into(),
+		Self::new(client, model)
+	}
+
+	/// Uses a cached content created with [CompletionModel::create_cached_content] as the
+	/// context of every request, e.g. `cachedContents/abc123`.
+	///
+	/// The system instruction and tools must be part of the cache: the preamble, tools and tool
+	/// choice of the requests aren't sent.
+	pub fn with_cached_content(mut self, name: impl Into<String>) -> Self {
+		self.cached_content = Some(cached_content_name(name));
+		self
+	}
+
+	/// Sets the number of tokens the model may think with, `0` disabling thinking and `-1`
+	/// letting the model decide. Sent as `generationConfig.thinkingConfig.thinkingBudget`, unless
+	/// the request sets one in its additional parameters.
+	pub fn with_thinking_budget(mut self, budget: i32) -> Self {
+		self.thinking_config.thinking_budget = Some(budget);
+		self
+	}
+
+	/// Sets whether the thoughts of the model are included in the responses, as
+	/// [Reasoning] content. Sent as `generationConfig.thinkingConfig.includeThoughts`, unless the
+	/// request sets it in its additional parameters.
+	pub fn with_include_thoughts(mut self, include_thoughts: bool) -> Self {
+		self.thinking_config.include_thoughts = Some(include_thoughts);
+		self
+	}
+
+	/// Creates the request body, referencing the cached content if any.
+	pub(crate) fn create_request(
+		&self,
+		completion_request: CompletionRequest,
+	) -> Result<GenerateContentRequest, CompletionError> {
+		let mut request = create_request_body(completion_request)?;
+
+		if self.thinking_config != ThinkingConfig::default() {
+			let thinking_config = request
+				.generation_config
+				.get_or_insert_with(|| GenerationConfig {
+					temperature: None,
+					max_output_tokens: None,
+					..Default::default()
+				})
+				.thinking_config
+				.get_or_insert_with(ThinkingConfig::default);
+			thinking_config.thinking_budget = thinking_config
+				.thinking_budget
+				.or(self.thinking_config.thinking_budget);
+			thinking_config.include_thoughts = thinking_config
+				.include_thoughts
+				.or(self.thinking_config.include_thoughts);
 		}
+
+		if let Some(cached_content) = &self.cached_content {
+			// Gemini rejects them alongside a cached content, which already holds them
+			request.system_instruction = None;
+			request.tools = None;
+			request.tool_config = None;
+			request.cached_content = Some(cached_content.clone());
+		}
+
+		Ok(request)
 	}
 }
 
@@ -71,10 +136,34 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	fn validate_request(
+		&self,
+		request: &CompletionRequest,
+		report: &mut completion::ValidationReport,
+	) {
+		if !request.documents.is_empty() {
+			report.warn("Gemini doesn't support `documents`, they will be ignored");
+		}
+
+		if self.cached_content.is_some() {
+			if request.preamble.is_some() {
+				report.warn("The preamble is ignored when using a cached content");
+			}
+			if !request.tools.is_empty() || request.tool_choice.is_some() {
+				report.warn("Tools are ignored when using a cached content");
+			}
+		}
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<GenerateContentResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -87,12 +176,17 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
 
-		let request = create_request_body(completion_request)?;
+		let request = self.create_request(completion_request)?;
+		span.record_input_messages(&request.contents);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(
@@ -113,7 +207,12 @@ where
 			.map_err(|e| CompletionError::HttpError(e.into()))?;
 
 		async move {
-			let response = self.client.send::<_, Vec<u8>>(request).await?;
+			let response = self
+				.client
+				.send::<_, Vec<u8>>(request)
+				.await
+				.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			if response.status().is_success() {
 				let response_body = response
@@ -136,6 +235,13 @@ where
 				let span = tracing::Span::current();
 				span.record_response_metadata(&response);
 				span.record_token_usage(&response.usage_metadata);
+				span.record_cost(
+					self.client.pricing(),
+					"gcp.gemini",
+					&self.model,
+					&response.usage_metadata,
+				);
+				span.record_output_messages(&response.candidates);
 
 				if enabled!(Level::TRACE) {
 					tracing::trace!(
@@ -145,8 +251,11 @@ where
 					);
 				}
 
-				response.try_into()
+				completion::CompletionResponse::try_from(response)
+					.map(|response| response.with_response_metadata(response_metadata))
 			} else {
+				let status = response.status();
+				let headers = response.headers().clone();
 				let text = String::from_utf8_lossy(
 					&response
 						.into_body()
@@ -155,7 +264,9 @@ where
 				)
 				.into();
 
-				Err(CompletionError::ProviderError(text))
+				Err(CompletionError::ApiError(
+					parse_api_error(status, text).with_retry_after(&headers),
+				))
 			}
 		}
 		.instrument(span)
@@ -169,13 +280,19 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let request = self.client.apply_default_params(request);
 		CompletionModel::stream(self, request).await
 	}
 }
 
+/// Maximum number of stop sequences accepted by Gemini
+pub const MAX_STOP_SEQUENCES: usize = 5;
+
 pub(crate) fn create_request_body(
 	completion_request: CompletionRequest,
 ) -> Result<GenerateContentRequest, CompletionError> {
+	completion_request.check_stop_sequences("Gemini", MAX_STOP_SEQUENCES)?;
+
 	let mut full_history = Vec::new();
 	full_history.extend(completion_request.chat_history);
 
@@ -188,23 +305,49 @@ pub(crate) fn create_request_body(
 		additional_params,
 	} = serde_json::from_value::<AdditionalParameters>(additional_params)?;
 
-	generation_config = generation_config.map(|mut cfg| {
+	if !completion_request.stop_sequences.is_empty()
+		|| completion_request.temperature.is_some()
+		|| completion_request.max_tokens.is_some()
+	{
+		// Don't pick up the default temperature and max tokens when the caller didn't provide a
+		// config, only the settings of the request apply
+		let cfg = generation_config.get_or_insert_with(|| GenerationConfig {
+			temperature: None,
+			max_output_tokens: None,
+			..Default::default()
+		});
+
+		if !completion_request.stop_sequences.is_empty() {
+			cfg.stop_sequences = Some(completion_request.stop_sequences);
+		}
+
 		if let Some(temp) = completion_request.temperature {
 			cfg.temperature = Some(temp);
-		};
+		}
 
 		if let Some(max_tokens) = completion_request.max_tokens {
 			cfg.max_output_tokens = Some(max_tokens);
-		};
-
-		cfg
-	});
+		}
+	}
 
 	let system_instruction = completion_request.preamble.clone().map(|preamble| Content {
 		parts: vec![preamble.into()],
 		role: Some(Role::Model),
 	});
 
+	if let Some(message::ToolChoice::Specific { function_names }) = &completion_request.tool_choice
+		&& let Some(name) = function_names.iter().find(|name| {
+			!completion_request
+				.tools
+				.iter()
+				.any(|tool| &tool.name == *name)
+		}) {
+		return Err(CompletionError::RequestError(
+			format!("`tool_choice` allows the function `{name}` which isn't a declared tool")
+				.into(),
+		));
+	}
+
 	let tools = if completion_request.tools.is_empty() {
 		None
 	} else {
@@ -232,6 +375,7 @@ pub(crate) fn create_request_body(
 		tools,
 		tool_config,
 		system_instruction,
+		cached_content: None,
 		additional_params,
 	};
 
@@ -353,10 +497,25 @@ impl TryFrom<GenerateContentResponse> for completion::CompletionResponse<Generat
 										Some(message::ImageDetail::default()),
 									)
 								}
-								_ => {
-									return Err(CompletionError::ResponseError(format!(
-										"Unsupported media type {mime_type:?}"
-									)));
+								// Any other file, e.g. a PDF or the output of code execution
+								media_type => {
+									let media_type = match media_type {
+										Some(message::MediaType::Document(media_type)) => {
+											Some(media_type)
+										}
+										_ => None,
+									};
+									let additional_params = media_type.is_none().then(
+										|| serde_json::json!({ "mime_type": inline_data.mime_type }),
+									);
+
+									message::AssistantContent::Document(message::Document {
+										data: message::DocumentSourceKind::Base64(
+											inline_data.data.clone(),
+										),
+										media_type,
+										additional_params,
+									})
 								}
 							}
 						}
@@ -395,7 +554,8 @@ impl TryFrom<GenerateContentResponse> for completion::CompletionResponse<Generat
 				input_tokens: usage.prompt_token_count as u64,
 				output_tokens: usage.candidates_token_count.unwrap_or(0) as u64,
 				total_tokens: usage.total_token_count as u64,
-				cached_input_tokens: 0,
+				cached_input_tokens: usage.cached_content_token_count.unwrap_or(0) as u64,
+				cache_creation_input_tokens: 0,
 			})
 			.unwrap_or_default();
 
@@ -403,6 +563,7 @@ impl TryFrom<GenerateContentResponse> for completion::CompletionResponse<Generat
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -622,6 +783,124 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn test_response_inline_data_becomes_document() {
+		let response: GenerateContentResponse = serde_json::from_value(json!({
+			"responseId": "resp_1",
+			"candidates": [{
+				"content": {
+					"parts": [
+						{"text": "Here is the report"},
+						{"inlineData": {"mimeType": "application/pdf", "data": "JVBERi0="}},
+						{"inlineData": {"mimeType": "application/zip", "data": "UEsDBA=="}}
+					],
+					"role": "model"
+				}
+			}]
+		}))
+		.unwrap();
+
+		let response: completion::CompletionResponse<_> = response.try_into().unwrap();
+		let content: Vec<_> = response.choice.into_iter().collect();
+
+		assert_eq!(content.len(), 3);
+		assert_eq!(
+			content[1],
+			message::AssistantContent::Document(message::Document {
+				data: message::DocumentSourceKind::Base64("JVBERi0=".to_string()),
+				media_type: Some(message::DocumentMediaType::PDF),
+				additional_params: None,
+			})
+		);
+		assert_eq!(
+			content[2],
+			message::AssistantContent::Document(message::Document {
+				data: message::DocumentSourceKind::Base64("UEsDBA==".to_string()),
+				media_type: None,
+				additional_params: Some(json!({ "mime_type": "application/zip" })),
+			})
+		);
+	}
+
+	#[test]
+	fn test_response_interleaved_thoughts_become_reasoning() {
+		let response: GenerateContentResponse = serde_json::from_value(json!({
+			"responseId": "resp_1",
+			"candidates": [{
+				"content": {
+					"parts": [
+						{"text": "The user wants a greeting.", "thought": true},
+						{"text": "Hello!"},
+						{"text": "Maybe also ask how they are.", "thought": true},
+						{"text": "How are you?"}
+					],
+					"role": "model"
+				}
+			}]
+		}))
+		.unwrap();
+
+		let response: completion::CompletionResponse<_> = response.try_into().unwrap();
+		let content: Vec<_> = response.choice.into_iter().collect();
+
+		assert_eq!(
+			content,
+			[
+				message::AssistantContent::Reasoning(Reasoning::new("The user wants a greeting.")),
+				message::AssistantContent::text("Hello!"),
+				message::AssistantContent::Reasoning(Reasoning::new(
+					"Maybe also ask how they are."
+				)),
+				message::AssistantContent::text("How are you?"),
+			]
+		);
+	}
+
+	#[test]
+	fn test_message_conversion_assistant_document() {
+		let msg = message::Message::Assistant {
+			id: None,
+			content: OneOrMany::many([
+				message::AssistantContent::Document(message::Document {
+					data: message::DocumentSourceKind::Base64("JVBERi0=".to_string()),
+					media_type: Some(message::DocumentMediaType::PDF),
+					additional_params: None,
+				}),
+				message::AssistantContent::Document(message::Document {
+					data: message::DocumentSourceKind::Base64("UEsDBA==".to_string()),
+					media_type: None,
+					additional_params: Some(json!({ "mime_type": "application/zip" })),
+				}),
+			])
+			.unwrap(),
+		};
+
+		let content: Content = msg.try_into().unwrap();
+		assert_eq!(content.role, Some(Role::Model));
+
+		let blobs: Vec<_> = content
+			.parts
+			.iter()
+			.map(|part| match &part.part {
+				PartKind::InlineData(blob) => (blob.mime_type.as_str(), blob.data.as_str()),
+				part => panic!("Expected inline data, got {part:?}"),
+			})
+			.collect();
+		assert_eq!(
+			blobs,
+			[
+				("application/pdf", "JVBERi0="),
+				("application/zip", "UEsDBA==")
+			]
+		);
+
+		let document = message::AssistantContent::Document(message::Document {
+			data: message::DocumentSourceKind::Base64("UEsDBA==".to_string()),
+			..Default::default()
+		});
+		assert!(Part::try_from(document).is_err());
+	}
+
 	#[test]
 	fn test_vec_schema_conversion() {
 		let schema_with_ref = json!({
@@ -804,6 +1083,38 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn test_raw_media_is_sent_inline_with_sniffed_media_type() {
+		use crate::message::{DocumentSourceKind, Image};
+		use crate::providers::gemini::api_types::Blob;
+
+		let png = b"\x89PNG\r\n\x1a\n".to_vec();
+		let image = message::UserContent::Image(Image {
+			data: DocumentSourceKind::Raw(png),
+			..Default::default()
+		});
+		let pdf = message::UserContent::document_raw(b"%PDF-1.7".to_vec(), None);
+
+		let parts = [image, pdf]
+			.into_iter()
+			.map(|content| Part::try_from(content).unwrap().part)
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			parts,
+			vec![
+				PartKind::InlineData(Blob {
+					mime_type: "image/png".to_string(),
+					data: "iVBORw0KGgo=".to_string(),
+				}),
+				PartKind::InlineData(Blob {
+					mime_type: "application/pdf".to_string(),
+					data: "JVBERi0xLjc=".to_string(),
+				}),
+			]
+		);
+	}
+
 	#[test]
 	fn test_tool_result_with_url_image() {
 		// Test that a ToolResult with a URL-based image converts to file_data
@@ -928,6 +1239,7 @@ mod tests {
 		use serde::{Deserialize, Serialize};
 
 		use crate::completion::{Prompt, ToolDefinition};
+		use crate::message::{ImageMediaType, ToolResultContent};
 		use crate::prelude::*;
 		use crate::providers::gemini;
 		use crate::tool::Tool;
@@ -944,7 +1256,7 @@ mod tests {
 			const NAME: &'static str = "generate_test_image";
 			type Error = ImageToolError;
 			type Args = serde_json::Value;
-			// Return the image in the format that from_tool_output expects
+			// The base64 PNG, turned into an image by `into_tool_result`
 			type Output = String;
 
 			async fn definition(&self, _prompt: String) -> ToolDefinition {
@@ -960,13 +1272,18 @@ mod tests {
 			}
 
 			async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
-				// Return a JSON object that from_tool_output will parse as an image
 				// This is a 1x1 red PNG pixel
-				Ok(json!({
-                    "type": "image",
-                    "data": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8DwHwAFBQIAX8jx0gAAAABJRU5ErkJggg==",
-                    "mimeType": "image/png"
-                }).to_string())
+				Ok("iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8DwHwAFBQIAX8jx0gAAAABJRU5ErkJggg==".to_string())
+			}
+
+			fn into_tool_result(
+				output: Self::Output,
+			) -> Result<OneOrMany<ToolResultContent>, serde_json::Error> {
+				Ok(OneOrMany::one(ToolResultContent::image_base64(
+					output,
+					Some(ImageMediaType::PNG),
+					None,
+				)))
 			}
 		}
 
@@ -996,4 +1313,184 @@ mod tests {
 		// Gemini should have been able to see the image and potentially describe its color
 		assert!(!response_text.is_empty(), "Response should not be empty");
 	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = completion::CompletionRequest::builder("Count to ten")
+			.stop_sequences(vec!["\n\n".to_string(), "END".to_string()])
+			.build();
+
+		let request = create_request_body(request).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["generationConfig"],
+			json!({ "stopSequences": ["\n\n", "END"] })
+		);
+	}
+
+	#[test]
+	fn test_temperature_without_additional_params() {
+		let request = completion::CompletionRequest::builder("Hello")
+			.temperature(0.2)
+			.build();
+
+		let request = create_request_body(request).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["generationConfig"],
+			json!({ "temperature": 0.2 })
+		);
+	}
+
+	#[test]
+	fn test_max_tokens_without_additional_params() {
+		let request = completion::CompletionRequest::builder("Hello")
+			.max_tokens(256)
+			.build();
+
+		let request = create_request_body(request).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["generationConfig"],
+			json!({ "maxOutputTokens": 256 })
+		);
+	}
+
+	#[test]
+	fn test_request_settings_merge_with_generation_config() {
+		let request = completion::CompletionRequest::builder("Hello")
+			.temperature(0.2)
+			.additional_params(json!({
+				"generationConfig": { "temperature": 0.9, "topK": 40 }
+			}))
+			.build();
+
+		let request = create_request_body(request).unwrap();
+		let config = &serde_json::to_value(&request).unwrap()["generationConfig"];
+
+		assert_eq!(config["temperature"], json!(0.2));
+		assert_eq!(config["topK"], json!(40));
+	}
+
+	#[test]
+	fn test_thinking_config_serialization() {
+		let model = CompletionModel::new(
+			Client::<reqwest::Client>::builder()
+				.api_key("test-key")
+				.build()
+				.unwrap(),
+			GEMINI_2_5_FLASH,
+		)
+		.with_thinking_budget(1024)
+		.with_include_thoughts(true);
+
+		let request = model
+			.create_request(completion::CompletionRequest::builder("Hello").build())
+			.unwrap();
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["generationConfig"],
+			json!({ "thinkingConfig": { "thinkingBudget": 1024, "includeThoughts": true } })
+		);
+
+		// The settings of the request take precedence
+		let request = model
+			.create_request(
+				completion::CompletionRequest::builder("Hello")
+					.temperature(0.5)
+					.additional_params(json!({
+						"generationConfig": { "thinkingConfig": { "thinkingBudget": 0 } }
+					}))
+					.build(),
+			)
+			.unwrap();
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["generationConfig"],
+			json!({
+				"temperature": 0.5,
+				"thinkingConfig": { "thinkingBudget": 0, "includeThoughts": true }
+			})
+		);
+	}
+
+	#[test]
+	fn test_no_generation_config_without_settings() {
+		let request = completion::CompletionRequest::builder("Hello").build();
+
+		let request = create_request_body(request).unwrap();
+
+		assert!(request.generation_config.is_none());
+	}
+
+	fn tool(name: &str) -> completion::ToolDefinition {
+		completion::ToolDefinition {
+			name: name.to_string(),
+			description: format!("The {name} tool"),
+			parameters: json!({"type": "object", "properties": {}}),
+		}
+	}
+
+	fn tool_config(function_names: &[&str]) -> Result<Value, CompletionError> {
+		let request = completion::CompletionRequest::builder("What's the weather?")
+			.tools(vec![
+				tool("get_weather"),
+				tool("get_time"),
+				tool("get_news"),
+			])
+			.tool_choice(message::ToolChoice::Specific {
+				function_names: function_names.iter().map(ToString::to_string).collect(),
+			})
+			.build();
+
+		let request = create_request_body(request)?;
+		Ok(serde_json::to_value(&request).unwrap()["toolConfig"].clone())
+	}
+
+	#[test]
+	fn test_tool_config_single_allowed_function_name() {
+		assert_eq!(
+			tool_config(&["get_weather"]).unwrap(),
+			json!({
+				"functionCallingConfig": {
+					"mode": "ANY",
+					"allowedFunctionNames": ["get_weather"]
+				}
+			})
+		);
+	}
+
+	#[test]
+	fn test_tool_config_multiple_allowed_function_names() {
+		assert_eq!(
+			tool_config(&["get_weather", "get_time"]).unwrap(),
+			json!({
+				"functionCallingConfig": {
+					"mode": "ANY",
+					"allowedFunctionNames": ["get_weather", "get_time"]
+				}
+			})
+		);
+	}
+
+	#[test]
+	fn test_tool_config_required_has_no_allowed_function_names() {
+		let request = completion::CompletionRequest::builder("What's the weather?")
+			.tools(vec![tool("get_weather")])
+			.tool_choice(message::ToolChoice::Required)
+			.build();
+
+		let request = create_request_body(request).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["toolConfig"],
+			json!({ "functionCallingConfig": { "mode": "ANY" } })
+		);
+	}
+
+	#[test]
+	fn test_tool_config_rejects_undeclared_function_name() {
+		let error = tool_config(&["get_weather", "get_stocks"]).unwrap_err();
+		assert!(error.to_string().contains("get_stocks"), "{error}");
+
+		assert!(tool_config(&[]).is_err());
+	}
 }
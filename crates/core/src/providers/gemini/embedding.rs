@@ -1,11 +1,14 @@
 //! Google Gemini Embeddings Integration
 //! From [Gemini API Reference](https://ai.google.dev/api/embeddings)
 
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use serde_json::json;
 
 use super::Client;
 use super::client::ApiResponse;
-use crate::embeddings::{self, EmbeddingError};
+use crate::completion::message::MimeType;
+use crate::embeddings::{self, EmbeddingError, ImageInput};
 use crate::http_client::HttpClientExt;
 use crate::wasm_compat::WasmCompatSend;
 
@@ -39,39 +42,46 @@ impl<T> EmbeddingModel<T> {
 	}
 }
 
-impl<T> embeddings::EmbeddingModel for EmbeddingModel<T>
+impl<T> EmbeddingModel<T>
 where
 	T: Clone + HttpClientExt + 'static,
 {
-	type Client = Client<T>;
-
-	const MAX_DOCUMENTS: usize = 1024;
-
-	fn make(client: &Self::Client, model: impl Into<String>, dims: Option<usize>) -> Self {
-		Self::new(client.clone(), model, dims)
-	}
+	/// Embeds one content per part, each embedding being named after the matching document.
+	/// Sends a request per [MAX_DOCUMENTS](embeddings::EmbeddingModel::MAX_DOCUMENTS) parts.
+	async fn batch_embed_contents(
+		&self,
+		documents: Vec<String>,
+		parts: Vec<serde_json::Value>,
+	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+		let max_documents = <Self as embeddings::EmbeddingModel>::MAX_DOCUMENTS;
+		let mut embeddings = Vec::with_capacity(documents.len());
+		for (documents, parts) in documents
+			.chunks(max_documents)
+			.zip(parts.chunks(max_documents))
+		{
+			embeddings.extend(
+				self.batch_embed_chunk(documents.to_vec(), parts.to_vec())
+					.await?,
+			);
+		}
 
-	fn ndims(&self) -> usize {
-		768
+		Ok(embeddings)
 	}
 
 	/// <https://ai.google.dev/api/embeddings#batch_embed_contents-SHELL>
-	async fn embed_texts(
+	async fn batch_embed_chunk(
 		&self,
-		documents: impl IntoIterator<Item = String> + WasmCompatSend,
+		documents: Vec<String>,
+		parts: Vec<serde_json::Value>,
 	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
-		let documents: Vec<String> = documents.into_iter().collect();
-
 		// Google batch embed requests. See docstrings for API ref link.
-		let requests: Vec<_> = documents
-			.iter()
-			.map(|doc| {
+		let requests: Vec<_> = parts
+			.into_iter()
+			.map(|part| {
 				json!({
 					"model": format!("models/{}", self.model),
 					"content": json!({
-						"parts": [json!({
-							"text": doc.to_string()
-						})]
+						"parts": [part]
 					}),
 					"output_dimensionality": self.ndims,
 				})
@@ -116,6 +126,179 @@ where
 	}
 }
 
+impl<T> embeddings::EmbeddingModel for EmbeddingModel<T>
+where
+	T: Clone + HttpClientExt + 'static,
+{
+	type Client = Client<T>;
+
+	const MAX_DOCUMENTS: usize = 1024;
+
+	fn make(client: &Self::Client, model: impl Into<String>, dims: Option<usize>) -> Self {
+		Self::new(client.clone(), model, dims)
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	fn ndims(&self) -> usize {
+		768
+	}
+
+	/// <https://ai.google.dev/api/embeddings#batch_embed_contents-SHELL>
+	async fn embed_texts(
+		&self,
+		documents: impl IntoIterator<Item = String> + WasmCompatSend,
+	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+		let documents: Vec<String> = documents.into_iter().collect();
+		let parts = documents.iter().map(|doc| json!({ "text": doc })).collect();
+
+		self.batch_embed_contents(documents, parts).await
+	}
+}
+
+impl<T> embeddings::ImageEmbeddingModel for EmbeddingModel<T>
+where
+	T: Clone + HttpClientExt + 'static,
+{
+	/// Images are sent as `inlineData` parts, or `fileData` parts for URLs, which must be files
+	/// the API can read such as uploaded files.
+	async fn embed_images(
+		&self,
+		images: Vec<ImageInput>,
+	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+		let documents = images.iter().map(ImageInput::to_string).collect();
+		let parts = images
+			.iter()
+			.map(|image| {
+				let mime_type = image.media_type().to_mime_type();
+				match image {
+					ImageInput::Bytes { data, .. } => json!({
+						"inlineData": {
+							"mimeType": mime_type,
+							"data": BASE64_STANDARD.encode(data),
+						}
+					}),
+					ImageInput::Url { url, .. } => json!({
+						"fileData": {
+							"mimeType": mime_type,
+							"fileUri": url,
+						}
+					}),
+				}
+			})
+			.collect();
+
+		self.batch_embed_contents(documents, parts).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::completion::message::ImageMediaType;
+	use crate::embeddings::{EmbeddingModel as _, ImageEmbeddingModel as _};
+	use crate::test_utils::MockSseClient;
+
+	#[tokio::test]
+	async fn test_embed_images_request() {
+		let http_client = MockSseClient::default().with_json_response(
+			r#"{ "embeddings": [{ "values": [0.1, 0.2] }, { "values": [0.3, 0.4] }] }"#,
+		);
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+		let model = EmbeddingModel::new(client, "gemini-embedding-001", Some(2));
+
+		let embeddings = model
+			.embed_images(vec![
+				ImageInput::bytes(b"png".to_vec(), ImageMediaType::PNG),
+				ImageInput::url("https://example.com/cat.jpg", ImageMediaType::JPEG),
+			])
+			.await
+			.unwrap();
+
+		assert_eq!(embeddings[0].document, "image/png (3 bytes)");
+		assert_eq!(embeddings[0].vec, vec![0.1, 0.2]);
+		assert_eq!(
+			embeddings[1].document,
+			"image/jpeg (https://example.com/cat.jpg)"
+		);
+		assert!(
+			http_client.request_uris()[0]
+				.contains("/v1beta/models/gemini-embedding-001:batchEmbedContents")
+		);
+		assert_eq!(
+			http_client.request_bodies()[0],
+			json!({
+				"requests": [
+					{
+						"model": "models/gemini-embedding-001",
+						"content": {
+							"parts": [{
+								"inlineData": { "mimeType": "image/png", "data": "cG5n" }
+							}]
+						},
+						"output_dimensionality": 2,
+					},
+					{
+						"model": "models/gemini-embedding-001",
+						"content": {
+							"parts": [{
+								"fileData": {
+									"mimeType": "image/jpeg",
+									"fileUri": "https://example.com/cat.jpg"
+								}
+							}]
+						},
+						"output_dimensionality": 2,
+					}
+				]
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_embed_texts_in_batches_of_max_documents() {
+		let response = |count: usize| {
+			let embeddings = vec![r#"{ "values": [0.1] }"#; count].join(",");
+			format!(r#"{{ "embeddings": [{embeddings}] }}"#)
+		};
+		let http_client = MockSseClient::default().with_json_responses([
+			response(EmbeddingModel::<MockSseClient>::MAX_DOCUMENTS),
+			response(1),
+		]);
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+		let model = EmbeddingModel::new(client, EMBEDDING_004, None);
+
+		let documents: Vec<_> = (0..=EmbeddingModel::<MockSseClient>::MAX_DOCUMENTS)
+			.map(|i| i.to_string())
+			.collect();
+		let embeddings = model.embed_texts(documents.clone()).await.unwrap();
+
+		let batch_sizes: Vec<_> = http_client
+			.request_bodies()
+			.iter()
+			.map(|body| body["requests"].as_array().unwrap().len())
+			.collect();
+		assert_eq!(
+			batch_sizes,
+			[EmbeddingModel::<MockSseClient>::MAX_DOCUMENTS, 1]
+		);
+		let embedded: Vec<_> = embeddings.into_iter().map(|e| e.document).collect();
+		assert_eq!(embedded, documents);
+	}
+}
+
 /// Rust Implementation of the Gemini Types from [Gemini API Reference](https://ai.google.dev/api/embeddings)
 #[allow(dead_code)]
 mod gemini_api_types {
@@ -0,0 +1,166 @@
+//! Classification of Gemini error responses.
+use http::StatusCode;
+use serde::Deserialize;
+
+use crate::completion::provider_error::parse_delay;
+use crate::completion::{ApiError, ProviderErrorKind};
+
+/// `{ "error": { "code": 429, "message": "...", "status": "RESOURCE_EXHAUSTED", "details": [...] } }`
+///
+/// Streaming requests wrap the same object in an array.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorBody {
+	Single { error: ErrorDetail },
+	Streamed([Box<ErrorBody>; 1]),
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+	message: String,
+	#[serde(default)]
+	status: String,
+	#[serde(default)]
+	details: Vec<ErrorDetails>,
+}
+
+/// The `google.rpc` detail messages used for classification.
+#[derive(Deserialize)]
+struct ErrorDetails {
+	/// Set by `google.rpc.ErrorInfo`, e.g. `API_KEY_INVALID`
+	#[serde(default)]
+	reason: Option<String>,
+	/// Set by `google.rpc.RetryInfo`, e.g. `23s`
+	#[serde(default, rename = "retryDelay")]
+	retry_delay: Option<String>,
+}
+
+impl ErrorBody {
+	fn into_detail(self) -> ErrorDetail {
+		match self {
+			Self::Single { error } => error,
+			Self::Streamed([body]) => body.into_detail(),
+		}
+	}
+}
+
+/// Classifies a Gemini error response from its status and body.
+pub fn parse_api_error(status: StatusCode, body: String) -> ApiError {
+	let mut error = ApiError::new(status, body);
+	let Ok(detail) = serde_json::from_str::<ErrorBody>(&error.body).map(ErrorBody::into_detail)
+	else {
+		return error;
+	};
+
+	let reason = |expected: &str| {
+		detail
+			.details
+			.iter()
+			.any(|details| details.reason.as_deref() == Some(expected))
+	};
+
+	error.kind = match detail.status.as_str() {
+		"UNAUTHENTICATED" | "PERMISSION_DENIED" => ProviderErrorKind::AuthenticationFailed,
+		"INVALID_ARGUMENT" if reason("API_KEY_INVALID") => ProviderErrorKind::AuthenticationFailed,
+		"INVALID_ARGUMENT"
+			if detail
+				.message
+				.contains("exceeds the maximum number of tokens") =>
+		{
+			ProviderErrorKind::ContextLengthExceeded
+		}
+		"RESOURCE_EXHAUSTED" => ProviderErrorKind::RateLimited {
+			retry_after: detail
+				.details
+				.iter()
+				.find_map(|details| details.retry_delay.as_deref().and_then(parse_delay)),
+		},
+		"UNAVAILABLE" => ProviderErrorKind::Overloaded,
+		"NOT_FOUND" => ProviderErrorKind::NotFound,
+		_ => error.kind,
+	};
+	error.message = detail.message;
+	error
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::*;
+
+	#[test]
+	fn test_resource_exhausted() {
+		let body = r#"{
+  "error": {
+    "code": 429,
+    "message": "You exceeded your current quota, please check your plan and billing details.",
+    "status": "RESOURCE_EXHAUSTED",
+    "details": [
+      {
+        "@type": "type.googleapis.com/google.rpc.QuotaFailure",
+        "violations": [
+          {
+            "quotaMetric": "generativelanguage.googleapis.com/generate_content_free_tier_requests",
+            "quotaId": "GenerateRequestsPerMinutePerProjectPerModel-FreeTier",
+            "quotaValue": "10"
+          }
+        ]
+      },
+      {
+        "@type": "type.googleapis.com/google.rpc.RetryInfo",
+        "retryDelay": "23s"
+      }
+    ]
+  }
+}"#;
+
+		let error = parse_api_error(StatusCode::TOO_MANY_REQUESTS, body.to_string());
+		assert_eq!(
+			error.kind,
+			ProviderErrorKind::RateLimited {
+				retry_after: Some(Duration::from_secs(23))
+			}
+		);
+		assert_eq!(
+			error.message,
+			"You exceeded your current quota, please check your plan and billing details."
+		);
+	}
+
+	#[test]
+	fn test_invalid_api_key() {
+		let body = r#"{"error":{"code":400,"message":"API key not valid. Please pass a valid API key.","status":"INVALID_ARGUMENT","details":[{"@type":"type.googleapis.com/google.rpc.ErrorInfo","reason":"API_KEY_INVALID","domain":"googleapis.com","metadata":{"service":"generativelanguage.googleapis.com"}}]}}"#;
+
+		let error = parse_api_error(StatusCode::BAD_REQUEST, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::AuthenticationFailed);
+	}
+
+	#[test]
+	fn test_token_limit() {
+		let body = r#"{"error":{"code":400,"message":"The input token count (1200000) exceeds the maximum number of tokens allowed (1048576).","status":"INVALID_ARGUMENT"}}"#;
+
+		let error = parse_api_error(StatusCode::BAD_REQUEST, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::ContextLengthExceeded);
+	}
+
+	#[test]
+	fn test_streamed_overloaded() {
+		let body = r#"[{"error":{"code":503,"message":"The model is overloaded. Please try again later.","status":"UNAVAILABLE"}}]"#;
+
+		let error = parse_api_error(StatusCode::SERVICE_UNAVAILABLE, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::Overloaded);
+		assert_eq!(
+			error.message,
+			"The model is overloaded. Please try again later."
+		);
+	}
+
+	#[test]
+	fn test_model_not_found() {
+		let body = r#"{"error":{"code":404,"message":"models/gemini-9 is not found for API version v1beta, or is not supported for generateContent.","status":"NOT_FOUND"}}"#;
+
+		let error = parse_api_error(StatusCode::NOT_FOUND, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::NotFound);
+	}
+}
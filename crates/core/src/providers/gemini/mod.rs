@@ -9,9 +9,11 @@
 //! let gemini_embedding_model = client.embedding_model(gemini::EMBEDDING_001);
 //! ```
 
+pub mod caching;
 pub mod client;
 pub mod completion;
 pub mod embedding;
+pub mod error;
 pub mod streaming;
 pub mod transcription;
 
@@ -4,13 +4,15 @@ use serde::{Deserialize, Serialize};
 use tracing::{Level, enabled, info_span};
 use tracing_futures::Instrument;
 
-use super::api_types::{ContentCandidate, Part, PartKind};
-use super::completion::{CompletionModel, create_request_body};
+use super::api_types::{Content, ContentCandidate, Part, PartKind, Role};
+use super::completion::CompletionModel;
+use super::error::parse_api_error;
 use crate::completion::{CompletionError, CompletionRequest, GetTokenUsage};
 use crate::http_client::HttpClientExt;
 use crate::http_client::sse::{Event, GenericEventSource};
 use crate::streaming;
 use crate::telemetry::SpanCombinator;
+use crate::telemetry::pricing::CostRecorder;
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -29,9 +31,10 @@ impl GetTokenUsage for PartialUsage {
 	fn token_usage(&self) -> Option<crate::completion::Usage> {
 		let mut usage = crate::completion::Usage::new();
 
+		// The prompt token count includes the cached content
 		usage.input_tokens = self.prompt_token_count as u64;
-		usage.output_tokens = (self.cached_content_token_count.unwrap_or_default()
-			+ self.candidates_token_count.unwrap_or_default()
+		usage.cached_input_tokens = self.cached_content_token_count.unwrap_or_default() as u64;
+		usage.output_tokens = (self.candidates_token_count.unwrap_or_default()
 			+ self.thoughts_token_count.unwrap_or_default()) as u64;
 		usage.total_tokens = usage.input_tokens + usage.output_tokens;
 
@@ -63,6 +66,11 @@ impl GetTokenUsage for StreamingCompletionResponse {
 			.map(|x| x as u64)
 			.unwrap_or(0);
 		usage.input_tokens = self.usage_metadata.prompt_token_count as u64;
+		usage.cached_input_tokens = self
+			.usage_metadata
+			.cached_content_token_count
+			.map(|x| x as u64)
+			.unwrap_or(0);
 		Some(usage)
 	}
 }
@@ -88,11 +96,16 @@ where
 				gen_ai.response.model = self.model,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
-		let request = create_request_body(completion_request)?;
+		let request = self.create_request(completion_request)?;
+		span.record_input_messages(&request.contents);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(
@@ -115,13 +128,21 @@ where
 			.map_err(|e| CompletionError::HttpError(e.into()))?;
 
 		let mut event_source = GenericEventSource::new(self.client.clone(), req);
+		let mut usage = UsageGuard {
+			span: span.clone(),
+			cost: self.client.cost_recorder("gcp.gemini", &self.model),
+			usage: None,
+		};
 
 		let stream = stream! {
-            let mut final_usage = None;
+            let mut output_parts = Vec::new();
             while let Some(event_result) = event_source.next().await {
                 match event_result {
                     Ok(Event::Open) => {
                         tracing::debug!("SSE connection opened");
+                        if let Some(metadata) = event_source.response_metadata() {
+                            yield Ok(streaming::RawStreamingChoice::ResponseMetadata(metadata.clone()));
+                        }
                         continue;
                     }
                     Ok(Event::Message(message)) => {
@@ -138,6 +159,14 @@ where
                             }
                         };
 
+                        // Gemini reports the cumulative usage on every chunk
+                        if let Some(usage_metadata) = data.usage_metadata {
+                            if let Some(current) = usage_metadata.token_usage() {
+                                yield Ok(streaming::RawStreamingChoice::Usage(current));
+                            }
+                            usage.usage = Some(usage_metadata);
+                        }
+
                         // Process the response data
                         let Some(choice) = data.candidates.into_iter().next() else {
                             tracing::debug!("There is no content candidate");
@@ -154,48 +183,14 @@ where
                         }
 
                         for part in content.parts {
-                            match part {
-                                Part {
-                                    part: PartKind::Text(text),
-                                    thought: Some(true),
-                                    ..
-                                } => {
-                                    if !text.is_empty() {
-                                        yield Ok(streaming::RawStreamingChoice::ReasoningDelta {
-                                            id: None,
-                                            reasoning: text,
-                                        });
-                                    }
-                                },
-                                Part {
-                                    part: PartKind::Text(text),
-                                    ..
-                                } => {
-                                    if !text.is_empty() {
-                                        yield Ok(streaming::RawStreamingChoice::Message(text));
-                                    }
-                                },
-                                Part {
-                                    part: PartKind::FunctionCall(function_call),
-                                    thought_signature,
-                                    ..
-                                } => {
-                                    yield Ok(streaming::RawStreamingChoice::ToolCall(
-                                        streaming::RawStreamingToolCall::new(function_call.name.clone(), function_call.name.clone(), function_call.args.clone())
-                                            .with_signature(thought_signature)
-                                    ));
-                                },
-                                part => {
-                                    tracing::warn!(?part, "Unsupported response type with streaming");
-                                }
+                            output_parts.push(part.clone());
+                            if let Some(choice) = handle_part(part) {
+                                yield Ok(choice);
                             }
                         }
 
                         // Check if this is the final response
                         if choice.finish_reason.is_some() {
-                            let span = tracing::Span::current();
-                            span.record_token_usage(&data.usage_metadata);
-                            final_usage = data.usage_metadata;
                             break;
                         }
                     }
@@ -204,7 +199,7 @@ where
                     }
                     Err(error) => {
                         tracing::error!(?error, "SSE error");
-                        yield Err(CompletionError::ProviderError(error.to_string()));
+                        yield Err(CompletionError::from_stream_error(error, parse_api_error));
                         break;
                     }
                 }
@@ -213,8 +208,13 @@ where
             // Ensure event source is closed when stream ends
             event_source.close();
 
+            tracing::Span::current().record_output_messages(&[Content {
+                parts: output_parts,
+                role: Some(Role::Model),
+            }]);
+
             yield Ok(streaming::RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
-                usage_metadata: final_usage.unwrap_or_default()
+                usage_metadata: usage.usage.clone().unwrap_or_default()
             }));
         }.instrument(span);
 
@@ -224,6 +224,61 @@ where
 	}
 }
 
+/// Records the latest usage reported by the stream into its span once the stream ends, including
+/// when it is dropped before the final response.
+struct UsageGuard {
+	span: tracing::Span,
+	cost: CostRecorder,
+	usage: Option<PartialUsage>,
+}
+
+impl Drop for UsageGuard {
+	fn drop(&mut self) {
+		if let Some(usage) = self.usage.take() {
+			self.span.record_token_usage(&usage);
+			self.cost.record(&self.span, &usage);
+		}
+	}
+}
+
+/// Converts a part of a streamed candidate into a streaming choice, skipping empty text parts.
+fn handle_part(part: Part) -> Option<streaming::RawStreamingChoice<StreamingCompletionResponse>> {
+	match part {
+		Part {
+			part: PartKind::Text(text),
+			..
+		} if text.is_empty() => None,
+		Part {
+			part: PartKind::Text(text),
+			thought: Some(true),
+			..
+		} => Some(streaming::RawStreamingChoice::ReasoningDelta {
+			id: None,
+			reasoning: text,
+		}),
+		Part {
+			part: PartKind::Text(text),
+			..
+		} => Some(streaming::RawStreamingChoice::Message(text)),
+		Part {
+			part: PartKind::FunctionCall(function_call),
+			thought_signature,
+			..
+		} => Some(streaming::RawStreamingChoice::ToolCall(
+			streaming::RawStreamingToolCall::new(
+				function_call.name.clone(),
+				function_call.name,
+				function_call.args,
+			)
+			.with_signature(thought_signature),
+		)),
+		part => {
+			tracing::warn!(?part, "Unsupported response type with streaming");
+			None
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use serde_json::json;
@@ -499,12 +554,13 @@ mod tests {
 			cached_content_token_count: Some(20),
 			candidates_token_count: Some(30),
 			thoughts_token_count: Some(10),
-			prompt_token_count: 40,
+			prompt_token_count: 60,
 		};
 
 		let token_usage = usage.token_usage().unwrap();
-		assert_eq!(token_usage.input_tokens, 40);
-		assert_eq!(token_usage.output_tokens, 60); // 20 + 30 + 10
+		assert_eq!(token_usage.input_tokens, 60);
+		assert_eq!(token_usage.cached_input_tokens, 20);
+		assert_eq!(token_usage.output_tokens, 40); // 30 + 10
 		assert_eq!(token_usage.total_tokens, 100);
 	}
 
@@ -541,4 +597,117 @@ mod tests {
 		assert_eq!(token_usage.output_tokens, 75);
 		assert_eq!(token_usage.total_tokens, 150);
 	}
+
+	#[test]
+	fn test_handle_part() {
+		let parts: Vec<Part> = serde_json::from_value(json!([
+			{"text": "Let me think...", "thought": true},
+			{"text": ""},
+			{"text": "Hello"},
+			{
+				"functionCall": {"name": "search", "args": {"query": "rust async"}},
+				"thoughtSignature": "c2lnbmF0dXJl"
+			}
+		]))
+		.unwrap();
+
+		let choices: Vec<_> = parts.into_iter().filter_map(handle_part).collect();
+		assert_eq!(choices.len(), 3);
+
+		assert!(matches!(
+			&choices[0],
+			streaming::RawStreamingChoice::ReasoningDelta { reasoning, .. } if reasoning == "Let me think..."
+		));
+		assert!(matches!(
+			&choices[1],
+			streaming::RawStreamingChoice::Message(text) if text == "Hello"
+		));
+		let streaming::RawStreamingChoice::ToolCall(tool_call) = &choices[2] else {
+			panic!("Expected a tool call, got {:?}", choices[2]);
+		};
+		assert_eq!(tool_call.name, "search");
+		assert_eq!(tool_call.arguments, json!({"query": "rust async"}));
+		assert_eq!(tool_call.signature.as_deref(), Some("c2lnbmF0dXJl"));
+	}
+
+	#[tokio::test]
+	async fn test_usage_is_recorded_when_stream_is_dropped() {
+		use tracing_subscriber::layer::SubscriberExt;
+
+		use crate::completion::CompletionModel as _;
+		use crate::providers::gemini::Client;
+		use crate::test_utils::{MockSseClient, RecordedFields};
+
+		fn chunk(text: &str, candidates_token_count: i32) -> String {
+			let data = json!({
+				"candidates": [{ "content": { "parts": [{ "text": text }], "role": "model" } }],
+				"usageMetadata": {
+					"promptTokenCount": 10,
+					"candidatesTokenCount": candidates_token_count,
+					"totalTokenCount": 10 + candidates_token_count
+				}
+			});
+			format!("data: {data}\n\n")
+		}
+
+		let fields = RecordedFields::default();
+		let _subscriber =
+			tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+		let sse = [chunk("Hello", 2), chunk(" there", 5), chunk(", how", 9)].concat();
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(MockSseClient::new(sse))
+			.build()
+			.unwrap();
+		let model = CompletionModel::new(client, "gemini-2.5-flash");
+		let request = model.completion_request("Hello").build();
+		let mut stream = model.stream(request).await.unwrap();
+		assert!(stream.current_usage().is_none());
+
+		stream.next().await.unwrap().unwrap();
+		let usage = stream.current_usage().unwrap();
+		assert_eq!((usage.input_tokens, usage.output_tokens), (10, 2));
+
+		stream.next().await.unwrap().unwrap();
+		let usage = stream.current_usage().unwrap();
+		assert_eq!((usage.input_tokens, usage.output_tokens), (10, 5));
+		assert!(!fields.get().contains_key("gen_ai.usage.output_tokens"));
+
+		drop(stream);
+		let fields = fields.get();
+		assert_eq!(fields["gen_ai.usage.input_tokens"], "10");
+		assert_eq!(fields["gen_ai.usage.output_tokens"], "5");
+	}
+
+	#[tokio::test]
+	async fn test_character_split_across_chunks() {
+		use crate::completion::CompletionModel as _;
+		use crate::providers::gemini::Client;
+		use crate::streaming::StreamedAssistantContent;
+		use crate::test_utils::MockSseClient;
+
+		let sse = [
+			json!({ "candidates": [{ "content": { "parts": [{ "text": "Crabs 🦀" }], "role": "model" } }] }),
+			json!({ "candidates": [{ "content": { "parts": [{ "text": " rock" }], "role": "model" }, "finishReason": "STOP" }] }),
+		]
+		.map(|data| format!("data: {data}\n\n"))
+		.concat();
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(MockSseClient::split_in_char(&sse, '🦀'))
+			.build()
+			.unwrap();
+		let model = CompletionModel::new(client, "gemini-2.5-flash");
+		let request = model.completion_request("Hello").build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let mut text = String::new();
+		while let Some(item) = stream.next().await {
+			if let StreamedAssistantContent::Text(delta) = item.unwrap() {
+				text += &delta.text;
+			}
+		}
+		assert_eq!(text, "Crabs 🦀 rock");
+	}
 }
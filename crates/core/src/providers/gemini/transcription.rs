@@ -3,7 +3,8 @@ use std::path::Path;
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
 use mime_guess;
-use serde_json::{Map, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
 
 use super::Client;
 use super::api_types::GenerateContentResponse;
@@ -17,11 +18,184 @@ use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
 const TRANSCRIPTION_PREAMBLE: &str =
 	"Translate the provided audio exactly. Do not add additional information.";
 
+/// The key of the [TranscriptionOptions] in the `additional_params` of a request
+const OPTIONS_KEY: &str = "transcription_options";
+
+/// How finely a structured transcript is split, see [TranscriptionOptions::timestamps]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampGranularity {
+	/// One segment per sentence or utterance
+	Segment,
+	/// One segment per word
+	Word,
+}
+
+/// Gemini specific transcription options.
+///
+/// Set on the model with [TranscriptionModel::with_options], or per request with
+/// [TranscriptionOptions::into_additional_params], e.g.
+/// `.additional_params(TranscriptionOptions::default().language("fr").into_additional_params())`.
+/// Options set on the request override the model's.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionOptions {
+	/// The language spoken in the audio, e.g. `en` or `French`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub language: Option<String>,
+	/// Requests a structured transcript with timestamps, parsed into
+	/// [TranscriptionResponse::segments]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub timestamps: Option<TimestampGranularity>,
+}
+
+impl TranscriptionOptions {
+	pub fn language(mut self, language: impl Into<String>) -> Self {
+		self.language = Some(language.into());
+		self
+	}
+
+	pub fn timestamps(mut self, granularity: TimestampGranularity) -> Self {
+		self.timestamps = Some(granularity);
+		self
+	}
+
+	/// The options as the `additional_params` of a [transcription::TranscriptionRequest].
+	pub fn into_additional_params(self) -> Value {
+		json!({ OPTIONS_KEY: self })
+	}
+
+	/// Overrides the options set in `other`.
+	fn merge(self, other: Self) -> Self {
+		Self {
+			language: other.language.or(self.language),
+			timestamps: other.timestamps.or(self.timestamps),
+		}
+	}
+
+	fn instructions(&self) -> String {
+		let mut instructions = TRANSCRIPTION_PREAMBLE.to_string();
+
+		if let Some(language) = &self.language {
+			instructions.push_str(&format!(" The audio is in {language}."));
+		}
+
+		match self.timestamps {
+			Some(TimestampGranularity::Segment) => instructions.push_str(
+				" Split the transcript into segments of one sentence or utterance, with their \
+				start and end times in seconds from the beginning of the audio.",
+			),
+			Some(TimestampGranularity::Word) => instructions.push_str(
+				" Split the transcript into segments of one word each, with their start and \
+				end times in seconds from the beginning of the audio.",
+			),
+			None => {}
+		}
+
+		instructions
+	}
+}
+
+/// The schema of a structured transcript
+fn segments_schema() -> Value {
+	json!({
+		"type": "object",
+		"properties": {
+			"segments": {
+				"type": "array",
+				"items": {
+					"type": "object",
+					"properties": {
+						"start": { "type": "number" },
+						"end": { "type": "number" },
+						"text": { "type": "string" },
+					},
+					"required": ["start", "end", "text"],
+				},
+			},
+		},
+		"required": ["segments"],
+	})
+}
+
+/// A timed part of a structured transcript.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionSegment {
+	/// Start time in seconds
+	pub start: f64,
+	/// End time in seconds
+	pub end: f64,
+	pub text: String,
+}
+
+#[derive(Deserialize)]
+struct StructuredTranscript {
+	segments: Vec<TranscriptionSegment>,
+}
+
+/// The raw response of a Gemini transcription.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TranscriptionResponse {
+	/// The timed segments of the transcript, empty unless
+	/// [TranscriptionOptions::timestamps] was set
+	pub segments: Vec<TranscriptionSegment>,
+	pub response: GenerateContentResponse,
+}
+
+impl TranscriptionResponse {
+	/// Parses the model's answer, as JSON segments if `structured`.
+	fn parse(
+		response: GenerateContentResponse,
+		structured: bool,
+	) -> Result<transcription::TranscriptionResponse<Self>, TranscriptionError> {
+		let transcription::TranscriptionResponse { text, response } =
+			transcription::TranscriptionResponse::try_from(response)?;
+
+		if !structured {
+			return Ok(transcription::TranscriptionResponse {
+				text,
+				response: Self {
+					segments: Vec::new(),
+					response,
+				},
+			});
+		}
+
+		let StructuredTranscript { segments } = serde_json::from_str(&text).map_err(|error| {
+			TranscriptionError::ResponseError(format!(
+				"Structured transcript is not valid: {error}"
+			))
+		})?;
+		let text = segments
+			.iter()
+			.map(|segment| segment.text.trim())
+			.filter(|text| !text.is_empty())
+			.collect::<Vec<_>>()
+			.join(" ");
+
+		Ok(transcription::TranscriptionResponse {
+			text,
+			response: Self { segments, response },
+		})
+	}
+}
+
+impl transcription::OffsetTimestamps for TranscriptionResponse {
+	fn offset_timestamps(&mut self, offset: std::time::Duration) {
+		let offset = offset.as_secs_f64();
+		for segment in &mut self.segments {
+			segment.start += offset;
+			segment.end += offset;
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct TranscriptionModel<T = reqwest::Client> {
 	client: Client<T>,
 	/// Name of the model (e.g.: gemini-1.5-flash)
 	pub model: String,
+	/// Options applied to every request
+	pub options: TranscriptionOptions,
 }
 
 impl<T> TranscriptionModel<T> {
@@ -29,41 +203,56 @@ impl<T> TranscriptionModel<T> {
 		Self {
 			client,
 			model: model.into(),
+			options: TranscriptionOptions::default(),
 		}
 	}
-}
 
-impl<T> transcription::TranscriptionModel for TranscriptionModel<T>
-where
-	T: HttpClientExt + WasmCompatSend + WasmCompatSync + Clone + 'static,
-{
-	type Response = GenerateContentResponse;
-	type Client = Client<T>;
-
-	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
-		TranscriptionModel::new(client.clone(), model)
+	pub fn with_options(mut self, options: TranscriptionOptions) -> Self {
+		self.options = options;
+		self
 	}
 
-	async fn transcription(
+	/// Builds the request body, returning whether a structured transcript was requested.
+	fn generate_content_request(
 		&self,
 		request: transcription::TranscriptionRequest,
-	) -> Result<
-		transcription::TranscriptionResponse<Self::Response>,
-		transcription::TranscriptionError,
-	> {
+	) -> Result<(GenerateContentRequest, bool), TranscriptionError> {
 		// Handle Gemini specific parameters
-		let additional_params = request
+		let mut additional_params = request
 			.additional_params
 			.unwrap_or_else(|| Value::Object(Map::new()));
+		let request_options = match additional_params.as_object_mut() {
+			Some(params) => params.remove(OPTIONS_KEY),
+			None => None,
+		}
+		.map(serde_json::from_value::<TranscriptionOptions>)
+		.transpose()?
+		.unwrap_or_default();
 		let mut generation_config = serde_json::from_value::<GenerationConfig>(additional_params)?;
 
+		// The language of the request is overridden by the typed options
+		let options = self
+			.options
+			.clone()
+			.merge(TranscriptionOptions {
+				language: request.language,
+				timestamps: None,
+			})
+			.merge(request_options);
+		let structured = options.timestamps.is_some();
+
 		// Set temperature from completion_request or additional_params
 		if let Some(temp) = request.temperature {
 			generation_config.temperature = Some(temp);
 		}
 
+		if structured {
+			generation_config.response_mime_type = Some("application/json".to_string());
+			generation_config.response_json_schema = Some(segments_schema());
+		}
+
 		let system_instruction = Some(Content {
-			parts: vec![TRANSCRIPTION_PREAMBLE.into()],
+			parts: vec![options.instructions().into()],
 			role: Some(Role::Model),
 		});
 
@@ -92,9 +281,38 @@ where
 			tools: None,
 			tool_config: None,
 			system_instruction,
+			cached_content: None,
 			additional_params: None,
 		};
 
+		Ok((request, structured))
+	}
+}
+
+impl<T> transcription::TranscriptionModel for TranscriptionModel<T>
+where
+	T: HttpClientExt + WasmCompatSend + WasmCompatSync + Clone + 'static,
+{
+	type Response = TranscriptionResponse;
+	type Client = Client<T>;
+
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		TranscriptionModel::new(client.clone(), model)
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	async fn transcription(
+		&self,
+		request: transcription::TranscriptionRequest,
+	) -> Result<
+		transcription::TranscriptionResponse<Self::Response>,
+		transcription::TranscriptionError,
+	> {
+		let (request, structured) = self.generate_content_request(request)?;
+
 		tracing::trace!(
 			target: "clankers::transcription",
 			"Sending completion request to Gemini API {}",
@@ -126,7 +344,7 @@ where
 
 			tracing::debug!("Received response");
 
-			Ok(transcription::TranscriptionResponse::try_from(body)?)
+			TranscriptionResponse::parse(body, structured)
 		} else {
 			let text = String::from_utf8_lossy(&response.into_body().await?).into();
 			Err(TranscriptionError::ProviderError(text))
@@ -172,3 +390,165 @@ impl TryFrom<GenerateContentResponse>
 		})
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::transcription::TranscriptionModel as _;
+
+	fn model() -> TranscriptionModel {
+		TranscriptionModel::new(Client::new("test-key").unwrap(), "gemini-2.5-flash")
+	}
+
+	fn request(
+		model: &TranscriptionModel,
+	) -> transcription::TranscriptionRequestBuilder<TranscriptionModel> {
+		model
+			.transcription_request()
+			.filename(Some("interview.wav".to_string()))
+			.data(vec![0, 1, 2, 3])
+	}
+
+	/// A response of `gemini-2.5-flash`, answering with `text`
+	fn response(text: &str) -> GenerateContentResponse {
+		serde_json::from_value(json!({
+			"candidates": [{
+				"content": {
+					"parts": [{ "text": text }],
+					"role": "model"
+				},
+				"finishReason": "STOP",
+				"index": 0
+			}],
+			"usageMetadata": {
+				"promptTokenCount": 154,
+				"candidatesTokenCount": 61,
+				"totalTokenCount": 215
+			},
+			"modelVersion": "gemini-2.5-flash",
+			"responseId": "qBK2aKWXEZ6Z1dkPiL3z4Q0"
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn test_plain_request() {
+		let model = model();
+		let (request, structured) = model
+			.generate_content_request(request(&model).temperature(0.2).build())
+			.unwrap();
+		let body = serde_json::to_value(&request).unwrap();
+
+		assert!(!structured);
+		assert_eq!(body["generationConfig"]["temperature"], 0.2);
+		assert!(body["generationConfig"].get("responseMimeType").is_none());
+		assert_eq!(
+			body["systemInstruction"]["parts"][0]["text"],
+			TRANSCRIPTION_PREAMBLE
+		);
+		assert_eq!(
+			body["contents"][0]["parts"][0]["inlineData"]["mimeType"],
+			"audio/wav"
+		);
+	}
+
+	#[test]
+	fn test_structured_request_options() {
+		let model = model().with_options(
+			TranscriptionOptions::default()
+				.language("German")
+				.timestamps(TimestampGranularity::Segment),
+		);
+		let request = request(&model)
+			.language("French".to_string())
+			.additional_params(json!({ "maxOutputTokens": 8192 }))
+			.additional_params(
+				TranscriptionOptions::default()
+					.timestamps(TimestampGranularity::Word)
+					.into_additional_params(),
+			)
+			.build();
+
+		let (request, structured) = model.generate_content_request(request).unwrap();
+		let body = serde_json::to_value(&request).unwrap();
+
+		assert!(structured);
+		let config = &body["generationConfig"];
+		assert_eq!(config["responseMimeType"], "application/json");
+		assert_eq!(config["responseJsonSchema"], segments_schema());
+		assert_eq!(config["maxOutputTokens"], 8192);
+		assert!(config.get(OPTIONS_KEY).is_none());
+
+		// The request's language and granularity override the model's
+		let instructions = body["systemInstruction"]["parts"][0]["text"]
+			.as_str()
+			.unwrap();
+		assert!(instructions.contains("The audio is in French."));
+		assert!(instructions.contains("segments of one word each"));
+	}
+
+	#[test]
+	fn test_parse_plain_response() {
+		let response = TranscriptionResponse::parse(
+			response("Thanks for having me. It's great to be here."),
+			false,
+		)
+		.unwrap();
+
+		assert_eq!(
+			response.text,
+			"Thanks for having me. It's great to be here."
+		);
+		assert!(response.response.segments.is_empty());
+	}
+
+	#[test]
+	fn test_parse_segments() {
+		let transcript = json!({
+			"segments": [
+				{ "start": 0.0, "end": 1.84, "text": "Thanks for having me." },
+				{ "start": 2.1, "end": 4.35, "text": " It's great to be here. " }
+			]
+		});
+
+		let response =
+			TranscriptionResponse::parse(response(&transcript.to_string()), true).unwrap();
+
+		assert_eq!(
+			response.text,
+			"Thanks for having me. It's great to be here."
+		);
+		assert_eq!(
+			response.response.segments[0],
+			TranscriptionSegment {
+				start: 0.0,
+				end: 1.84,
+				text: "Thanks for having me.".to_string(),
+			}
+		);
+		assert_eq!(response.response.segments[1].end, 4.35);
+	}
+
+	#[test]
+	fn test_parse_word_segments() {
+		let transcript = r#"{"segments":[{"start":0.0,"end":0.42,"text":"Bonjour"},{"start":0.42,"end":0.61,"text":"à"},{"start":0.61,"end":1.2,"text":"tous"}]}"#;
+
+		let response = TranscriptionResponse::parse(response(transcript), true).unwrap();
+
+		assert_eq!(response.text, "Bonjour à tous");
+		assert_eq!(response.response.segments.len(), 3);
+		assert_eq!(
+			response.response.response.response_id,
+			"qBK2aKWXEZ6Z1dkPiL3z4Q0"
+		);
+	}
+
+	#[test]
+	fn test_parse_invalid_segments() {
+		let error = TranscriptionResponse::parse(response("Thanks for having me."), true)
+			.err()
+			.unwrap();
+
+		assert!(matches!(error, TranscriptionError::ResponseError(_)));
+	}
+}
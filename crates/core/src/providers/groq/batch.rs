@@ -0,0 +1,660 @@
+//! Groq batch API, processing chat completion requests asynchronously at a lower cost.
+//!
+//! The requests are uploaded as a JSONL file, each line identified by a `custom_id` of the form
+//! `request-{index}`. Once the batch has ended, the results are downloaded and matched back to
+//! the requests, failed requests included.
+//!
+//! # Example
+//! ```
+//! use std::time::Duration;
+//!
+//! use clankers::completion::CompletionRequest;
+//! use clankers::providers::groq;
+//!
+//! let client = groq::Client::from_env();
+//! let batch = client.batch_model(groq::completion::LLAMA_3_1_8B_INSTANT);
+//!
+//! let handle = batch
+//!     .create_batch(vec![
+//!         CompletionRequest::builder("Capital of France?").build(),
+//!         CompletionRequest::builder("Capital of Italy?").build(),
+//!     ])
+//!     .await
+//!     .expect("Failed to create the batch");
+//!
+//! batch.wait(&handle, Duration::from_secs(60)).await.expect("Failed to poll the batch");
+//!
+//! for (custom_id, result) in batch.results(&handle).await.expect("Failed to get the results") {
+//!     match result {
+//!         Ok(response) => println!("{custom_id}: {:?}", response.choice),
+//!         Err(error) => println!("{custom_id} failed: {error}"),
+//!     }
+//! }
+//! ```
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::client::Client;
+use super::completion::GroqCompletionRequest;
+use crate::completion::{self, ApiError, CompletionError, CompletionRequest};
+use crate::http_client::multipart::Part;
+use crate::http_client::{self, HttpClientExt, MultipartForm, NoBody};
+use crate::providers::openai::completion::types::CompletionResponse;
+use crate::wasm_compat::WasmCompatSend;
+
+/// Endpoint the requests of a batch are sent to
+const ENDPOINT: &str = "/v1/chat/completions";
+
+#[derive(Debug, thiserror::Error)]
+pub enum BatchError {
+	/// Http error (e.g.: connection error, timeout, etc.)
+	#[error("HttpError: {0}")]
+	HttpError(#[from] http_client::Error),
+
+	/// Json error (e.g.: serialization, deserialization)
+	#[error("JsonError: {0}")]
+	JsonError(#[from] serde_json::Error),
+
+	/// Error converting a request of the batch
+	#[error("CompletionError: {0}")]
+	CompletionError(#[from] CompletionError),
+
+	/// The results were requested before the batch ended
+	#[error("Batch {id} is {status:?}, its results are available once it has ended")]
+	NotFinished { id: String, status: BatchStatus },
+
+	/// Error returned by Groq
+	#[error("ProviderError: {0}")]
+	ProviderError(String),
+}
+
+/// A line of the JSONL file of a batch, one chat completion request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRequestLine {
+	pub custom_id: String,
+	pub method: String,
+	pub url: String,
+	pub body: serde_json::Value,
+}
+
+/// Converts `requests` to the lines of the JSONL file of a batch, identified by `request-{index}`.
+pub fn to_jsonl(model: &str, requests: Vec<CompletionRequest>) -> Result<String, BatchError> {
+	let mut jsonl = String::new();
+
+	for (index, request) in requests.into_iter().enumerate() {
+		let line = BatchRequestLine {
+			custom_id: format!("request-{index}"),
+			method: "POST".to_string(),
+			url: ENDPOINT.to_string(),
+			body: serde_json::to_value(GroqCompletionRequest::try_from((model, request))?)?,
+		};
+		jsonl.push_str(&serde_json::to_string(&line)?);
+		jsonl.push('\n');
+	}
+
+	Ok(jsonl)
+}
+
+/// A line of the output or error file of a batch.
+#[derive(Debug, Deserialize)]
+struct BatchResultLine {
+	custom_id: String,
+	#[serde(default)]
+	response: Option<BatchResultResponse>,
+	#[serde(default)]
+	error: Option<BatchLineError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResultResponse {
+	status_code: u16,
+	body: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchLineError {
+	#[serde(default)]
+	code: Option<String>,
+	message: String,
+}
+
+/// The result of a request of a batch, by custom ID.
+pub type BatchResult = (
+	String,
+	Result<completion::CompletionResponse<CompletionResponse>, CompletionError>,
+);
+
+impl BatchResultLine {
+	fn into_result(self) -> BatchResult {
+		let result = match (self.response, self.error) {
+			(Some(response), None) if (200..300).contains(&response.status_code) => {
+				serde_json::from_value::<CompletionResponse>(response.body)
+					.map_err(CompletionError::from)
+					.and_then(completion::CompletionResponse::try_from)
+			}
+			(Some(response), _) => {
+				let status = http::StatusCode::from_u16(response.status_code)
+					.unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR);
+				let mut error = ApiError::new(status, response.body.to_string());
+				if let Some(message) = response.body["error"]["message"].as_str() {
+					error.message = message.to_string();
+				}
+				Err(CompletionError::ApiError(error))
+			}
+			(None, Some(error)) => Err(CompletionError::ProviderError(match error.code {
+				Some(code) => format!("{code}: {}", error.message),
+				None => error.message,
+			})),
+			(None, None) => Err(CompletionError::ResponseError(
+				"Batch result contained neither a response nor an error".to_string(),
+			)),
+		};
+
+		(self.custom_id, result)
+	}
+}
+
+/// Parses the output or error file of a batch, one result per line.
+pub fn parse_results(jsonl: &str) -> Result<Vec<BatchResult>, BatchError> {
+	jsonl
+		.lines()
+		.filter(|line| !line.trim().is_empty())
+		.map(|line| Ok(serde_json::from_str::<BatchResultLine>(line)?.into_result()))
+		.collect()
+}
+
+/// The index of the request of a `request-{index}` custom ID.
+fn request_index(custom_id: &str) -> Option<usize> {
+	custom_id.strip_prefix("request-")?.parse().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+	Validating,
+	Failed,
+	InProgress,
+	Finalizing,
+	Completed,
+	Expired,
+	Cancelling,
+	Cancelled,
+}
+
+impl BatchStatus {
+	/// Whether the batch has ended, successfully or not.
+	pub fn is_terminal(&self) -> bool {
+		matches!(
+			self,
+			Self::Failed | Self::Completed | Self::Expired | Self::Cancelled
+		)
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestCounts {
+	pub total: u64,
+	pub completed: u64,
+	pub failed: u64,
+}
+
+/// A batch, as returned by Groq.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Batch {
+	pub id: String,
+	pub status: BatchStatus,
+	pub input_file_id: String,
+	/// File of the successful requests, once the batch has ended
+	#[serde(default)]
+	pub output_file_id: Option<String>,
+	/// File of the failed requests, once the batch has ended
+	#[serde(default)]
+	pub error_file_id: Option<String>,
+	#[serde(default)]
+	pub request_counts: Option<RequestCounts>,
+	#[serde(default)]
+	pub created_at: u64,
+}
+
+impl Batch {
+	pub fn handle(&self) -> BatchHandle {
+		BatchHandle::new(&self.id)
+	}
+}
+
+/// Identifies a batch, e.g. to get its results from another process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchHandle {
+	pub id: String,
+}
+
+impl BatchHandle {
+	pub fn new(id: impl Into<String>) -> Self {
+		Self { id: id.into() }
+	}
+}
+
+/// A file uploaded to Groq.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileObject {
+	pub id: String,
+	#[serde(default)]
+	pub bytes: u64,
+	#[serde(default)]
+	pub filename: String,
+	#[serde(default)]
+	pub purpose: String,
+	#[serde(default)]
+	pub created_at: u64,
+}
+
+/// Sends batches of chat completion requests to a model.
+#[derive(Clone)]
+pub struct BatchModel<T = reqwest::Client> {
+	client: Client<T>,
+	/// Name of the model (e.g.: llama-3.1-8b-instant)
+	pub model: String,
+}
+
+impl<T> BatchModel<T> {
+	pub fn new(client: Client<T>, model: impl Into<String>) -> Self {
+		Self {
+			client,
+			model: model.into(),
+		}
+	}
+}
+
+impl<T> BatchModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	/// Uploads the JSONL `content` of a batch.
+	pub async fn upload_file(
+		&self,
+		filename: &str,
+		content: Vec<u8>,
+	) -> Result<FileObject, BatchError> {
+		let body = MultipartForm::new()
+			.text("purpose", "batch")
+			.part(Part::bytes("file", content).filename(filename));
+
+		let req = self
+			.client
+			.post("/files")?
+			.body(body)
+			.map_err(http_client::Error::Protocol)?;
+
+		let response = self.client.send_multipart::<Vec<u8>>(req).await?;
+
+		if !response.status().is_success() {
+			let text = http_client::text(response).await?;
+			return Err(BatchError::ProviderError(text));
+		}
+
+		let body: Vec<u8> = response.into_body().await?;
+		Ok(serde_json::from_slice(&body)?)
+	}
+
+	/// Downloads the content of a file, e.g. the output file of a batch.
+	pub async fn download_file(&self, file_id: &str) -> Result<Vec<u8>, BatchError> {
+		let req = self
+			.client
+			.get(format!("/files/{file_id}/content"))?
+			.body(NoBody)
+			.map_err(http_client::Error::Protocol)?;
+
+		let response = self.client.send::<_, Vec<u8>>(req).await?;
+
+		if !response.status().is_success() {
+			let text = http_client::text(response).await?;
+			return Err(BatchError::ProviderError(text));
+		}
+
+		Ok(response.into_body().await?)
+	}
+
+	/// Uploads `requests` and creates a batch processing them within 24 hours.
+	pub async fn create_batch(
+		&self,
+		requests: Vec<CompletionRequest>,
+	) -> Result<BatchHandle, BatchError> {
+		let jsonl = to_jsonl(&self.model, requests)?;
+		let file = self.upload_file("batch.jsonl", jsonl.into_bytes()).await?;
+
+		let body = serde_json::to_vec(&json!({
+			"input_file_id": file.id,
+			"endpoint": ENDPOINT,
+			"completion_window": "24h",
+		}))?;
+
+		let req = self
+			.client
+			.post("/batches")?
+			.header("Content-Type", "application/json")
+			.body(body)
+			.map_err(http_client::Error::Protocol)?;
+
+		let batch: Batch = self.send(req).await?;
+
+		tracing::debug!(target: "clankers", "Created Groq batch {}", batch.id);
+
+		Ok(batch.handle())
+	}
+
+	/// Gets the status of the batch.
+	pub async fn status(&self, handle: &BatchHandle) -> Result<Batch, BatchError> {
+		let req = self
+			.client
+			.get(format!("/batches/{}", handle.id))?
+			.body(NoBody)
+			.map_err(http_client::Error::Protocol)?;
+
+		self.send(req).await
+	}
+
+	/// Polls the status of the batch every `poll_interval` until it has ended.
+	pub async fn wait(
+		&self,
+		handle: &BatchHandle,
+		poll_interval: Duration,
+	) -> Result<Batch, BatchError> {
+		loop {
+			let batch = self.status(handle).await?;
+			if batch.status.is_terminal() {
+				return Ok(batch);
+			}
+			futures_timer::Delay::new(poll_interval).await;
+		}
+	}
+
+	/// Downloads the results of an ended batch, ordered as the requests it was created with.
+	/// Each request fails or succeeds on its own.
+	pub async fn results(&self, handle: &BatchHandle) -> Result<Vec<BatchResult>, BatchError> {
+		let batch = self.status(handle).await?;
+		if !batch.status.is_terminal() {
+			return Err(BatchError::NotFinished {
+				id: batch.id,
+				status: batch.status,
+			});
+		}
+
+		let mut results = Vec::new();
+		for file_id in [&batch.output_file_id, &batch.error_file_id]
+			.into_iter()
+			.flatten()
+		{
+			let content = self.download_file(file_id).await?;
+			results.extend(parse_results(&String::from_utf8_lossy(&content))?);
+		}
+
+		results.sort_by_key(|(custom_id, _)| request_index(custom_id).unwrap_or(usize::MAX));
+
+		Ok(results)
+	}
+
+	async fn send<B, R>(&self, req: http::Request<B>) -> Result<R, BatchError>
+	where
+		B: Into<Bytes> + WasmCompatSend,
+		R: DeserializeOwned,
+	{
+		let response = self.client.send::<_, Vec<u8>>(req).await?;
+
+		if !response.status().is_success() {
+			let text = http_client::text(response).await?;
+			return Err(BatchError::ProviderError(text));
+		}
+
+		let body: Vec<u8> = response.into_body().await?;
+		Ok(serde_json::from_slice(&body)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::OneOrMany;
+	use crate::completion::AssistantContent;
+	use crate::test_utils::MockSseClient;
+
+	fn model(http_client: MockSseClient) -> BatchModel<MockSseClient> {
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap();
+
+		BatchModel::new(client, "llama-3.1-8b-instant")
+	}
+
+	fn batch(status: &str, output_file_id: Option<&str>, error_file_id: Option<&str>) -> String {
+		json!({
+			"id": "batch_01",
+			"object": "batch",
+			"endpoint": "/v1/chat/completions",
+			"input_file_id": "file_in",
+			"completion_window": "24h",
+			"status": status,
+			"output_file_id": output_file_id,
+			"error_file_id": error_file_id,
+			"created_at": 1736472600,
+			"request_counts": { "total": 3, "completed": 2, "failed": 1 }
+		})
+		.to_string()
+	}
+
+	fn completion(content: &str) -> serde_json::Value {
+		json!({
+			"id": "chatcmpl-1",
+			"object": "chat.completion",
+			"created": 1736472600,
+			"model": "llama-3.1-8b-instant",
+			"choices": [{
+				"index": 0,
+				"message": { "role": "assistant", "content": content },
+				"finish_reason": "stop"
+			}],
+			"usage": { "prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12 }
+		})
+	}
+
+	#[test]
+	fn test_jsonl_round_trip() {
+		let jsonl = to_jsonl(
+			"llama-3.1-8b-instant",
+			vec![
+				CompletionRequest::builder("Capital of France?")
+					.preamble("Answer briefly.".to_string())
+					.temperature(0.0)
+					.build(),
+				CompletionRequest::builder("Capital of Italy?").build(),
+			],
+		)
+		.unwrap();
+
+		let lines = jsonl
+			.lines()
+			.map(|line| serde_json::from_str::<BatchRequestLine>(line).unwrap())
+			.collect::<Vec<_>>();
+
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[0].custom_id, "request-0");
+		assert_eq!(lines[1].custom_id, "request-1");
+		assert_eq!(lines[0].method, "POST");
+		assert_eq!(lines[0].url, "/v1/chat/completions");
+		assert_eq!(lines[0].body["model"], "llama-3.1-8b-instant");
+		assert_eq!(lines[0].body["temperature"], 0.0);
+		assert_eq!(lines[0].body["messages"][0]["role"], "system");
+		assert_eq!(lines[1].body["messages"][0]["role"], "user");
+
+		// Re-serializing the parsed lines gives the same file
+		let reserialized = lines
+			.iter()
+			.map(|line| serde_json::to_string(line).unwrap() + "\n")
+			.collect::<String>();
+		assert_eq!(reserialized, jsonl);
+	}
+
+	#[test]
+	fn test_parse_results_with_partial_failures() {
+		let jsonl = [
+			json!({
+				"id": "batch_req_1",
+				"custom_id": "request-1",
+				"response": { "status_code": 200, "request_id": "req_1", "body": completion("Rome") },
+				"error": null
+			}),
+			json!({
+				"id": "batch_req_2",
+				"custom_id": "request-2",
+				"response": {
+					"status_code": 400,
+					"request_id": "req_2",
+					"body": { "error": { "message": "max_tokens is too large", "type": "invalid_request_error" } }
+				},
+				"error": null
+			}),
+			json!({
+				"id": "batch_req_0",
+				"custom_id": "request-0",
+				"response": null,
+				"error": { "code": "batch_expired", "message": "This request could not be executed before the completion window expired." }
+			}),
+		]
+		.iter()
+		.map(|line| line.to_string() + "\n")
+		.collect::<String>();
+
+		let results = parse_results(&jsonl).unwrap();
+
+		assert_eq!(results.len(), 3);
+		let (custom_id, response) = &results[0];
+		assert_eq!(custom_id, "request-1");
+		let response = response.as_ref().unwrap();
+		assert_eq!(
+			response.choice,
+			OneOrMany::one(AssistantContent::text("Rome"))
+		);
+		assert_eq!(response.usage.total_tokens, 12);
+
+		let (custom_id, error) = &results[1];
+		assert_eq!(custom_id, "request-2");
+		assert!(
+			matches!(error, Err(CompletionError::ApiError(error))
+				if error.status == 400 && error.message == "max_tokens is too large"),
+			"{error:?}"
+		);
+
+		let (custom_id, error) = &results[2];
+		assert_eq!(custom_id, "request-0");
+		assert!(
+			matches!(error, Err(CompletionError::ProviderError(message)) if message.starts_with("batch_expired: ")),
+			"{error:?}"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_status_polling_and_results() {
+		let output = json!({
+			"id": "batch_req_1",
+			"custom_id": "request-1",
+			"response": { "status_code": 200, "body": completion("Rome") }
+		})
+		.to_string();
+		let errors = json!({
+			"id": "batch_req_0",
+			"custom_id": "request-0",
+			"error": { "code": "invalid_request", "message": "Invalid request" }
+		})
+		.to_string();
+
+		let http_client = MockSseClient::default().with_json_responses([
+			batch("validating", None, None),
+			batch("in_progress", None, None),
+			batch("completed", Some("file_out"), Some("file_err")),
+			batch("completed", Some("file_out"), Some("file_err")),
+			output,
+			errors,
+		]);
+		let model = model(http_client.clone());
+		let handle = BatchHandle::new("batch_01");
+
+		let batch = model.wait(&handle, Duration::from_millis(1)).await.unwrap();
+		assert_eq!(batch.status, BatchStatus::Completed);
+		assert_eq!(batch.request_counts.unwrap().failed, 1);
+
+		let results = model.results(&handle).await.unwrap();
+		let custom_ids = results
+			.iter()
+			.map(|(id, _)| id.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(custom_ids, ["request-0", "request-1"]);
+		assert!(results[0].1.is_err());
+		assert!(results[1].1.is_ok());
+
+		let uris = http_client.request_uris();
+		assert_eq!(uris.len(), 6);
+		assert!(
+			uris[..4]
+				.iter()
+				.all(|uri| uri.ends_with("/batches/batch_01"))
+		);
+		assert!(uris[4].ends_with("/files/file_out/content"));
+		assert!(uris[5].ends_with("/files/file_err/content"));
+	}
+
+	#[tokio::test]
+	async fn test_results_of_unfinished_batch() {
+		let http_client =
+			MockSseClient::default().with_json_responses([batch("in_progress", None, None)]);
+
+		let error = model(http_client)
+			.results(&BatchHandle::new("batch_01"))
+			.await
+			.expect_err("results of an unfinished batch should be an error");
+
+		assert!(
+			matches!(
+				error,
+				BatchError::NotFinished {
+					status: BatchStatus::InProgress,
+					..
+				}
+			),
+			"{error}"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_create_batch() {
+		let http_client = MockSseClient::default().with_json_responses([
+			json!({ "id": "file_in", "object": "file", "bytes": 512, "filename": "batch.jsonl", "purpose": "batch", "created_at": 1736472600 }).to_string(),
+			batch("validating", None, None),
+		]);
+
+		let handle = model(http_client.clone())
+			.create_batch(vec![
+				CompletionRequest::builder("Capital of France?").build(),
+			])
+			.await
+			.unwrap();
+
+		assert_eq!(handle, BatchHandle::new("batch_01"));
+		let uris = http_client.request_uris();
+		assert!(uris[0].ends_with("/files"));
+		assert!(uris[1].ends_with("/batches"));
+		// The upload is multipart, the batch creation the only JSON body
+		assert_eq!(
+			http_client.request_bodies()[0],
+			json!({
+				"input_file_id": "file_in",
+				"endpoint": "/v1/chat/completions",
+				"completion_window": "24h"
+			})
+		);
+	}
+}
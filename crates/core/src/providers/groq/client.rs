@@ -1,7 +1,9 @@
+use super::batch::BatchModel;
 use super::completion::CompletionModel;
 use super::transcription::TranscriptionModel;
-use crate::client::{self, BearerAuth, Capable, Nothing, ProviderClient};
-use crate::providers::openai_compat::{OpenAiCompat, PBuilder};
+use crate::client::{self, BearerAuth, Capable, ClientError, Nothing, ProviderClient};
+use crate::http_client::HttpClientExt;
+use crate::providers::openai_compat::{self, ModelInfo, ModelsError, OpenAiCompat, PBuilder};
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Groq;
@@ -25,14 +27,43 @@ impl OpenAiCompat for Groq {
 pub type Client<H = reqwest::Client> = client::Client<Groq, H>;
 pub type ClientBuilder<H = reqwest::Client> = client::ClientBuilder<PBuilder<Groq>, BearerAuth, H>;
 
+impl<H> Client<H>
+where
+	H: Clone,
+{
+	/// Create a model processing batches of completion requests, see [super::batch].
+	pub fn batch_model(&self, model: impl Into<String>) -> BatchModel<H> {
+		BatchModel::new(self.clone(), model)
+	}
+}
+
+impl<H> Client<H>
+where
+	H: HttpClientExt + Clone + 'static,
+{
+	/// List the models available to the account, with their context window (`/models`).
+	pub async fn list_models(&self) -> Result<Vec<ModelInfo>, ModelsError> {
+		openai_compat::list_models(self).await
+	}
+
+	/// Get the metadata of a model (`/models/{id}`).
+	pub async fn get_model(&self, id: &str) -> Result<ModelInfo, ModelsError> {
+		openai_compat::get_model(self, id).await
+	}
+}
+
 impl ProviderClient for Client {
 	type Input = String;
 
 	/// Create a new Groq client from the `GROQ_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
-		let api_key = std::env::var("GROQ_API_KEY").expect("GROQ_API_KEY not set");
-		Self::new(&api_key).unwrap()
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("GROQ_API_KEY")?;
+		Ok(Self::new(&api_key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
@@ -5,10 +5,12 @@ use super::client::{Client, Groq};
 use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage};
 use crate::http_client::{self, HttpClientExt};
 use crate::message::{self};
+use crate::providers::openai::completion::streaming::CompatStreamingResponse;
 use crate::providers::openai::completion::types::{
 	CompletionResponse, Message as OpenAIMessage, ToolDefinition, Usage,
 };
 use crate::providers::openai_compat::{self, OpenAiCompat};
+use crate::telemetry::SpanCombinator;
 
 /// The `deepseek-r1-distill-llama-70b` model. Used for chat completion.
 pub const DEEPSEEK_R1_DISTILL_LLAMA_70B: &str = "deepseek-r1-distill-llama-70b";
@@ -45,6 +47,9 @@ pub enum ReasoningFormat {
 	Hidden,
 }
 
+/// Maximum number of stop sequences accepted by Groq
+pub const MAX_STOP_SEQUENCES: usize = 4;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(super) struct GroqCompletionRequest {
 	model: String,
@@ -52,6 +57,8 @@ pub(super) struct GroqCompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<crate::providers::openai::completion::types::ToolChoice>,
@@ -71,6 +78,8 @@ impl TryFrom<(&str, CompletionRequest)> for GroqCompletionRequest {
 	type Error = CompletionError;
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
+		req.check_stop_sequences("Groq", MAX_STOP_SEQUENCES)?;
+
 		let mut partial_history = vec![];
 		if let Some(docs) = req.normalized_documents() {
 			partial_history.push(docs);
@@ -109,6 +118,7 @@ impl TryFrom<(&str, CompletionRequest)> for GroqCompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools: req
 				.tools
 				.clone()
@@ -168,10 +178,15 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::completion_span(
 			Groq::PROVIDER_NAME,
 			&self.model,
@@ -179,6 +194,7 @@ where
 		);
 
 		let request = GroqCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if tracing::enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -195,7 +211,7 @@ where
 			.map_err(|e| http_client::Error::Instance(e.into()))?;
 
 		let async_block = async move {
-			let response = openai_compat::send_and_parse::<
+			let (response, response_metadata) = openai_compat::send_and_parse::<
 				_,
 				CompletionResponse,
 				openai_compat::FlatApiError,
@@ -214,7 +230,8 @@ where
 				);
 			}
 
-			response.try_into()
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
 		};
 
 		tracing::Instrument::instrument(async_block, span).await
@@ -227,10 +244,12 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let request = self.client.apply_default_params(request);
 		let span =
 			openai_compat::streaming_span(Groq::PROVIDER_NAME, &self.model, &request.preamble);
 
 		let mut request = GroqCompletionRequest::try_from((self.model.as_ref(), request))?;
+		span.record_input_messages(&request.messages);
 
 		request.stream = true;
 		request.stream_options = Some(StreamOptions {
@@ -274,20 +293,13 @@ impl GetTokenUsage for StreamingCompletionResponse {
 		usage.input_tokens = self.usage.prompt_tokens as u64;
 		usage.total_tokens = self.usage.total_tokens as u64;
 		usage.output_tokens = self.usage.total_tokens as u64 - self.usage.prompt_tokens as u64;
-		usage.cached_input_tokens = self
-			.usage
-			.prompt_tokens_details
-			.as_ref()
-			.map(|d| d.cached_tokens as u64)
-			.unwrap_or(0);
+		usage.cached_input_tokens = Self::cached_input_tokens(&self.usage);
 
 		Some(usage)
 	}
 }
 
-impl crate::providers::openai::completion::streaming::CompatStreamingResponse
-	for StreamingCompletionResponse
-{
+impl CompatStreamingResponse for StreamingCompletionResponse {
 	type Usage = Usage;
 	fn from_usage(usage: Usage) -> Self {
 		Self { usage }
@@ -298,12 +310,21 @@ impl crate::providers::openai::completion::streaming::CompatStreamingResponse
 	fn output_tokens(usage: &Usage) -> u64 {
 		(usage.total_tokens - usage.prompt_tokens) as u64
 	}
+	fn cached_input_tokens(usage: &Usage) -> u64 {
+		usage
+			.prompt_tokens_details
+			.as_ref()
+			.map_or(0, |details| details.cached_tokens as u64)
+	}
 }
 
 #[cfg(test)]
 mod tests {
+	use super::StreamingCompletionResponse;
 	use crate::OneOrMany;
+	use crate::completion::GetTokenUsage;
 	use crate::providers::groq::completion::{GroqAdditionalParameters, GroqCompletionRequest};
+	use crate::providers::openai::completion::streaming::CompatStreamingResponse;
 	use crate::providers::openai::completion::types::{Message, UserContent};
 
 	#[test]
@@ -317,6 +338,7 @@ mod tests {
 		let groq = GroqCompletionRequest {
 			model: "openai/gpt-120b-oss".to_string(),
 			temperature: None,
+			stop: Vec::new(),
 			tool_choice: None,
 			stream_options: None,
 			tools: Vec::new(),
@@ -351,4 +373,44 @@ mod tests {
 			})
 		)
 	}
+
+	#[test]
+	fn test_stop_sequences_limit() {
+		let request = crate::completion::CompletionRequest::builder("Count to ten")
+			.stop_sequences((0..5).map(|i| i.to_string()).collect())
+			.build();
+
+		let err =
+			GroqCompletionRequest::try_from(("llama-3.3-70b-versatile", request)).unwrap_err();
+
+		assert_eq!(
+			err.to_string(),
+			"RequestError: Groq supports at most 4 stop sequences, got 5"
+		);
+	}
+
+	#[test]
+	fn test_streaming_usage_with_cached_tokens() {
+		let usage = serde_json::from_str(
+			r#"{
+                "queue_time": 0.02,
+                "prompt_tokens": 1500,
+                "prompt_time": 0.01,
+                "completion_tokens": 25,
+                "completion_time": 0.05,
+                "total_tokens": 1525,
+                "total_time": 0.06,
+                "prompt_tokens_details": { "cached_tokens": 1280 }
+            }"#,
+		)
+		.unwrap();
+
+		let usage = StreamingCompletionResponse::from_usage(usage)
+			.token_usage()
+			.unwrap();
+		assert_eq!(usage.input_tokens, 1500);
+		assert_eq!(usage.output_tokens, 25);
+		assert_eq!(usage.total_tokens, 1525);
+		assert_eq!(usage.cached_input_tokens, 1280);
+	}
 }
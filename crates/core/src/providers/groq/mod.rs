@@ -9,6 +9,7 @@
 //! let gpt4o = client.completion_model(groq::completion::GPT_4O);
 //! ```
 
+pub mod batch;
 pub mod client;
 pub mod completion;
 pub mod transcription;
@@ -1,9 +1,9 @@
 use bytes::Bytes;
+use serde::{Deserialize, Serialize};
 
 use super::client::Client;
 use crate::http_client::multipart::Part;
 use crate::http_client::{HttpClientExt, MultipartForm};
-use crate::providers::openai::TranscriptionResponse;
 use crate::providers::openai_compat::ApiResponse;
 use crate::transcription::{self, TranscriptionError};
 
@@ -11,11 +11,130 @@ pub const WHISPER_LARGE_V3: &str = "whisper-large-v3";
 pub const WHISPER_LARGE_V3_TURBO: &str = "whisper-large-v3-turbo";
 pub const DISTIL_WHISPER_LARGE_V3_EN: &str = "distil-whisper-large-v3-en";
 
+/// Format of the transcription returned by Groq
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptionResponseFormat {
+	Json,
+	/// Includes the detected language, duration and timestamped segments and words
+	VerboseJson,
+	Text,
+}
+
+impl TranscriptionResponseFormat {
+	fn as_str(&self) -> &'static str {
+		match self {
+			TranscriptionResponseFormat::Json => "json",
+			TranscriptionResponseFormat::VerboseJson => "verbose_json",
+			TranscriptionResponseFormat::Text => "text",
+		}
+	}
+}
+
+/// Level of timestamp detail included in a `verbose_json` transcription
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampGranularity {
+	Segment,
+	Word,
+}
+
+impl TimestampGranularity {
+	fn as_str(&self) -> &'static str {
+		match self {
+			TimestampGranularity::Segment => "segment",
+			TimestampGranularity::Word => "word",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TranscriptionResponse {
+	pub text: String,
+	/// Only returned with [`TranscriptionResponseFormat::VerboseJson`]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub task: Option<String>,
+	/// Only returned with [`TranscriptionResponseFormat::VerboseJson`]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub language: Option<String>,
+	/// Duration of the audio in seconds. Only returned with [`TranscriptionResponseFormat::VerboseJson`]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub duration: Option<f64>,
+	/// Only returned with the [`TimestampGranularity::Segment`] granularity
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub segments: Vec<TranscriptionSegment>,
+	/// Only returned with the [`TimestampGranularity::Word`] granularity
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub words: Vec<TranscriptionWord>,
+}
+
+/// A timestamped segment of a `verbose_json` transcription
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TranscriptionSegment {
+	pub id: u64,
+	#[serde(default)]
+	pub seek: u64,
+	/// Start time of the segment in seconds
+	pub start: f64,
+	/// End time of the segment in seconds
+	pub end: f64,
+	pub text: String,
+	#[serde(default)]
+	pub tokens: Vec<u64>,
+	#[serde(default)]
+	pub temperature: f64,
+	#[serde(default)]
+	pub avg_logprob: f64,
+	#[serde(default)]
+	pub compression_ratio: f64,
+	#[serde(default)]
+	pub no_speech_prob: f64,
+}
+
+/// A timestamped word of a `verbose_json` transcription
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TranscriptionWord {
+	pub word: String,
+	/// Start time of the word in seconds
+	pub start: f64,
+	/// End time of the word in seconds
+	pub end: f64,
+}
+
+impl TryFrom<TranscriptionResponse>
+	for transcription::TranscriptionResponse<TranscriptionResponse>
+{
+	type Error = TranscriptionError;
+
+	fn try_from(value: TranscriptionResponse) -> Result<Self, Self::Error> {
+		Ok(transcription::TranscriptionResponse {
+			text: value.text.clone(),
+			response: value,
+		})
+	}
+}
+
+impl transcription::OffsetTimestamps for TranscriptionResponse {
+	fn offset_timestamps(&mut self, offset: std::time::Duration) {
+		let offset = offset.as_secs_f64();
+		for segment in &mut self.segments {
+			segment.start += offset;
+			segment.end += offset;
+		}
+		for word in &mut self.words {
+			word.start += offset;
+			word.end += offset;
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct TranscriptionModel<T> {
 	client: Client<T>,
 	/// Name of the model (e.g.: gpt-3.5-turbo-1106)
 	pub model: String,
+	pub response_format: Option<TranscriptionResponseFormat>,
+	pub timestamp_granularities: Vec<TimestampGranularity>,
 }
 
 impl<T> TranscriptionModel<T> {
@@ -23,77 +142,277 @@ impl<T> TranscriptionModel<T> {
 		Self {
 			client,
 			model: model.into(),
+			response_format: None,
+			timestamp_granularities: Vec::new(),
 		}
 	}
-}
-impl<T> transcription::TranscriptionModel for TranscriptionModel<T>
-where
-	T: HttpClientExt + Clone + Send + std::fmt::Debug + Default + 'static,
-{
-	type Response = TranscriptionResponse;
 
-	type Client = Client<T>;
+	pub fn with_response_format(mut self, response_format: TranscriptionResponseFormat) -> Self {
+		self.response_format = Some(response_format);
+		self
+	}
 
-	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
-		Self::new(client.clone(), model)
+	/// Timestamps are only returned with the `verbose_json` response format, which is
+	/// selected automatically unless another format was set explicitly.
+	pub fn with_timestamp_granularities(
+		mut self,
+		timestamp_granularities: impl IntoIterator<Item = TimestampGranularity>,
+	) -> Self {
+		self.timestamp_granularities = timestamp_granularities.into_iter().collect();
+		self
 	}
 
-	async fn transcription(
+	fn response_format(&self) -> Option<TranscriptionResponseFormat> {
+		self.response_format.or_else(|| {
+			(!self.timestamp_granularities.is_empty())
+				.then_some(TranscriptionResponseFormat::VerboseJson)
+		})
+	}
+
+	fn request_body(
 		&self,
 		request: transcription::TranscriptionRequest,
-	) -> Result<
-		transcription::TranscriptionResponse<Self::Response>,
-		transcription::TranscriptionError,
-	> {
-		let data = request.data;
-
+	) -> Result<MultipartForm, TranscriptionError> {
 		let mut body = MultipartForm::new()
 			.text("model", self.model.clone())
-			.part(Part::bytes("file", data).filename(request.filename.clone()));
+			.part(Part::bytes("file", request.data).filename(request.filename));
 
 		if let Some(language) = request.language {
 			body = body.text("language", language);
 		}
 
 		if let Some(prompt) = request.prompt {
-			body = body.text("prompt", prompt.clone());
+			body = body.text("prompt", prompt);
 		}
 
-		if let Some(ref temperature) = request.temperature {
+		if let Some(temperature) = request.temperature {
 			body = body.text("temperature", temperature.to_string());
 		}
 
-		if let Some(ref additional_params) = request.additional_params {
-			for (key, value) in additional_params
-				.as_object()
-				.expect("Additional Parameters to OpenAI Transcription should be a map")
-			{
-				body = body.text(key.to_owned(), value.to_string());
+		if let Some(response_format) = self.response_format() {
+			body = body.text("response_format", response_format.as_str());
+		}
+
+		for granularity in &self.timestamp_granularities {
+			body = body.text("timestamp_granularities[]", granularity.as_str());
+		}
+
+		if let Some(additional_params) = request.additional_params {
+			let serde_json::Value::Object(params) = additional_params else {
+				return Err(TranscriptionError::RequestError(
+					"Additional parameters to Groq transcription should be a map".into(),
+				));
+			};
+
+			for (key, value) in params {
+				let value = match value {
+					serde_json::Value::String(value) => value,
+					value => value.to_string(),
+				};
+				body = body.text(key, value);
 			}
 		}
 
+		Ok(body)
+	}
+
+	fn parse_response(
+		&self,
+		response_body: &[u8],
+	) -> Result<transcription::TranscriptionResponse<TranscriptionResponse>, TranscriptionError> {
+		if self.response_format() == Some(TranscriptionResponseFormat::Text) {
+			return TranscriptionResponse {
+				text: String::from_utf8_lossy(response_body)
+					.trim_end()
+					.to_string(),
+				..Default::default()
+			}
+			.try_into();
+		}
+
+		match serde_json::from_slice::<ApiResponse<TranscriptionResponse>>(response_body)? {
+			ApiResponse::Ok(response) => response.try_into(),
+			ApiResponse::Err(api_error_response) => Err(TranscriptionError::ProviderError(
+				api_error_response.message,
+			)),
+		}
+	}
+}
+
+impl<T> transcription::TranscriptionModel for TranscriptionModel<T>
+where
+	T: HttpClientExt + Clone + Send + std::fmt::Debug + Default + 'static,
+{
+	type Response = TranscriptionResponse;
+
+	type Client = Client<T>;
+
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		Self::new(client.clone(), model)
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	async fn transcription(
+		&self,
+		request: transcription::TranscriptionRequest,
+	) -> Result<
+		transcription::TranscriptionResponse<Self::Response>,
+		transcription::TranscriptionError,
+	> {
+		let body = self.request_body(request)?;
+
 		let req = self
 			.client
 			.post("/audio/transcriptions")?
 			.body(body)
-			.unwrap();
+			.map_err(|e| TranscriptionError::HttpError(e.into()))?;
 
-		let response = self.client.send_multipart::<Bytes>(req).await.unwrap();
+		let response = self.client.send_multipart::<Bytes>(req).await?;
 
 		let status = response.status();
 		let response_body = response.into_body().into_future().await?.to_vec();
 
 		if status.is_success() {
-			match serde_json::from_slice::<ApiResponse<TranscriptionResponse>>(&response_body)? {
-				ApiResponse::Ok(response) => response.try_into(),
-				ApiResponse::Err(api_error_response) => Err(TranscriptionError::ProviderError(
-					api_error_response.message,
-				)),
-			}
+			self.parse_response(&response_body)
 		} else {
-			Err(TranscriptionError::ProviderError(
-				String::from_utf8_lossy(&response_body).to_string(),
-			))
+			Err(TranscriptionError::ProviderError(format!(
+				"{status}: {}",
+				String::from_utf8_lossy(&response_body)
+			)))
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::transcription::TranscriptionModel as _;
+
+	fn model() -> TranscriptionModel<reqwest::Client> {
+		TranscriptionModel::new(Client::new("test-key").unwrap(), WHISPER_LARGE_V3_TURBO)
+	}
+
+	#[test]
+	fn test_multipart_body_contents() {
+		let model = model().with_timestamp_granularities([
+			TimestampGranularity::Segment,
+			TimestampGranularity::Word,
+		]);
+		let request = model
+			.transcription_request()
+			.data(b"RIFF audio".to_vec())
+			.filename(Some("clip.wav".to_string()))
+			.language("en".to_string())
+			.temperature(0.2)
+			.additional_params(json!({ "user": "abc" }))
+			.build();
+
+		let form = model.request_body(request).unwrap();
+		let names = form
+			.parts()
+			.iter()
+			.map(|part| part.name())
+			.collect::<Vec<_>>();
+		assert_eq!(
+			names,
+			[
+				"model",
+				"file",
+				"language",
+				"temperature",
+				"response_format",
+				"timestamp_granularities[]",
+				"timestamp_granularities[]",
+				"user",
+			]
+		);
+		assert_eq!(form.parts()[1].get_filename(), Some("clip.wav"));
+
+		let (_, body) = form.encode();
+		let body = String::from_utf8_lossy(&body);
+		assert!(body.contains("whisper-large-v3-turbo"));
+		assert!(body.contains("RIFF audio"));
+		assert!(body.contains("verbose_json"));
+		assert!(body.contains("segment"));
+		// String parameters are sent without JSON quoting
+		assert!(body.contains("\r\n\r\nabc\r\n"), "{body}");
+	}
+
+	#[test]
+	fn test_additional_params_must_be_a_map() {
+		let model = model();
+		let request = model
+			.transcription_request()
+			.data(vec![0])
+			.additional_params(json!(["not", "a", "map"]))
+			.build();
+
+		assert!(matches!(
+			model.request_body(request),
+			Err(TranscriptionError::RequestError(_))
+		));
+	}
+
+	#[test]
+	fn test_deserialize_verbose_json() {
+		let body = json!({
+			"task": "transcribe",
+			"language": "English",
+			"duration": 2.5,
+			"text": "Hello there.",
+			"segments": [{
+				"id": 0,
+				"seek": 0,
+				"start": 0.0,
+				"end": 2.5,
+				"text": " Hello there.",
+				"tokens": [50364, 2425, 456, 13, 50489],
+				"temperature": 0.0,
+				"avg_logprob": -0.25,
+				"compression_ratio": 0.6,
+				"no_speech_prob": 0.01
+			}],
+			"words": [
+				{ "word": "Hello", "start": 0.0, "end": 0.8 },
+				{ "word": "there", "start": 0.9, "end": 1.4 }
+			],
+			"x_groq": { "id": "req_123" }
+		});
+
+		let response = model()
+			.with_response_format(TranscriptionResponseFormat::VerboseJson)
+			.parse_response(&serde_json::to_vec(&body).unwrap())
+			.unwrap();
+
+		assert_eq!(response.text, "Hello there.");
+		let response = response.response;
+		assert_eq!(response.language.as_deref(), Some("English"));
+		assert_eq!(response.duration, Some(2.5));
+		assert_eq!(response.segments.len(), 1);
+		assert_eq!(response.segments[0].tokens.len(), 5);
+		assert_eq!(response.segments[0].no_speech_prob, 0.01);
+		assert_eq!(
+			response.words[1],
+			TranscriptionWord {
+				word: "there".to_string(),
+				start: 0.9,
+				end: 1.4,
+			}
+		);
+	}
+
+	#[test]
+	fn test_text_response_format() {
+		let response = model()
+			.with_response_format(TranscriptionResponseFormat::Text)
+			.parse_response(b"Hello there.\n")
+			.unwrap();
+
+		assert_eq!(response.text, "Hello there.");
+	}
+}
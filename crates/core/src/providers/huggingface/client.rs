@@ -1,9 +1,10 @@
 use std::fmt::{Debug, Display};
 
 use crate::client::{
-	self, BearerAuth, Capabilities, Capable, DebugExt, Nothing, Provider, ProviderBuilder,
-	ProviderClient,
+	self, BearerAuth, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider,
+	ProviderBuilder, ProviderClient,
 };
+use crate::completion::CompletionError;
 use crate::http_client;
 #[cfg(feature = "image")]
 use crate::image_generation::ImageGenerationError;
@@ -23,11 +24,37 @@ pub enum SubProvider {
 }
 
 impl SubProvider {
-	/// Get the chat completion endpoint for the SubProvider
-	/// Required because Huggingface Inference requires the model
-	/// in the url and in the request body.
+	/// Get the chat completion endpoint for the SubProvider.
+	/// Huggingface Inference uses the router's OpenAI-compatible endpoint, every other
+	/// SubProvider is reached through its own route on the router.
 	pub fn completion_endpoint(&self, _model: &str) -> String {
-		"v1/chat/completions".to_string()
+		match self {
+			SubProvider::HFInference => "v1/chat/completions".to_string(),
+			SubProvider::Fireworks => format!("{self}/inference/v1/chat/completions"),
+			SubProvider::Novita => format!("{self}/v3/openai/chat/completions"),
+			SubProvider::Together
+			| SubProvider::SambaNova
+			| SubProvider::Hyperbolic
+			| SubProvider::Nebius
+			| SubProvider::Custom(_) => format!("{self}/v1/chat/completions"),
+		}
+	}
+
+	/// Get the text generation endpoint for the SubProvider, which takes a plain prompt rather
+	/// than messages. Custom routes are expected to be served by Text Generation Inference.
+	pub fn text_generation_endpoint(
+		&self,
+		model: &str,
+		stream: bool,
+	) -> Result<String, CompletionError> {
+		match self {
+			SubProvider::HFInference => Ok(format!("{self}/{model}")),
+			SubProvider::Custom(route) if stream => Ok(format!("{route}/generate_stream")),
+			SubProvider::Custom(route) => Ok(format!("{route}/generate")),
+			_ => Err(CompletionError::ProviderError(format!(
+				"text generation endpoint is not supported yet for {self}"
+			))),
+		}
 	}
 
 	/// Get the transcription endpoint for the SubProvider
@@ -55,12 +82,43 @@ impl SubProvider {
 		}
 	}
 
+	/// Get the identifier the SubProvider uses for a Huggingface model.
 	pub fn model_identifier(&self, model: &str) -> String {
 		match self {
 			SubProvider::Fireworks => format!("accounts/fireworks/models/{model}"),
+			// SambaNova model names don't include the organization
+			SubProvider::SambaNova => model
+				.rsplit_once('/')
+				.map_or(model, |(_, name)| name)
+				.to_string(),
 			_ => model.to_string(),
 		}
 	}
+
+	/// Get the SubProvider for a route name of the Huggingface router, e.g. `nebius`.
+	/// Returns `None` for unknown routes.
+	pub fn from_route(route: &str) -> Option<Self> {
+		match route {
+			"hf-inference" | "hf-inference/models" => Some(SubProvider::HFInference),
+			"together" => Some(SubProvider::Together),
+			"sambanova" => Some(SubProvider::SambaNova),
+			"fireworks-ai" => Some(SubProvider::Fireworks),
+			"hyperbolic" => Some(SubProvider::Hyperbolic),
+			"nebius" => Some(SubProvider::Nebius),
+			"novita" => Some(SubProvider::Novita),
+			_ => None,
+		}
+	}
+
+	/// Infers the SubProvider from a model of the form `{model}:{route}`,
+	/// e.g. `meta-llama/Llama-3.3-70B-Instruct:nebius`, and returns it with the bare model.
+	/// Models without a known route suffix are served by Huggingface Inference.
+	pub fn from_model(model: &str) -> (Self, &str) {
+		model
+			.rsplit_once(':')
+			.and_then(|(name, route)| Some((Self::from_route(route)?, name)))
+			.unwrap_or((SubProvider::HFInference, model))
+	}
 }
 
 impl From<&str> for SubProvider {
@@ -96,12 +154,13 @@ const HUGGINGFACE_API_BASE_URL: &str = "https://router.huggingface.co";
 
 #[derive(Debug, Default, Clone)]
 pub struct HuggingFaceExt {
-	subprovider: SubProvider,
+	/// SubProvider forced for every model, inferred from the model when `None`
+	subprovider: Option<SubProvider>,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct HuggingFaceBuilder {
-	subprovider: SubProvider,
+	subprovider: Option<SubProvider>,
 }
 
 type HuggingFaceApiKey = BearerAuth;
@@ -154,9 +213,12 @@ impl ProviderClient for Client {
 	/// Create a new Huggingface client from the `HUGGINGFACE_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
-		let api_key = std::env::var("HUGGINGFACE_API_KEY").expect("HUGGINGFACE_API_KEY is not set");
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
 
-		Self::new(&api_key).unwrap()
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("HUGGINGFACE_API_KEY")?;
+		Ok(Self::new(&api_key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
@@ -165,14 +227,161 @@ impl ProviderClient for Client {
 }
 
 impl<H> ClientBuilder<H> {
+	/// Route every model through `subprovider` instead of inferring it from the model.
 	pub fn subprovider(mut self, subprovider: SubProvider) -> Self {
-		*self.ext_mut() = HuggingFaceBuilder { subprovider };
+		*self.ext_mut() = HuggingFaceBuilder {
+			subprovider: Some(subprovider),
+		};
 		self
 	}
 }
 
 impl<H> Client<H> {
-	pub(crate) fn subprovider(&self) -> &SubProvider {
-		&self.ext().subprovider
+	/// Returns a client that routes every model through `subprovider` instead of inferring it
+	/// from the model.
+	pub fn with_subprovider(&self, subprovider: SubProvider) -> Self
+	where
+		H: Clone,
+	{
+		self.clone().with_ext(HuggingFaceExt {
+			subprovider: Some(subprovider),
+		})
+	}
+
+	/// Creates a model for the text generation task, which takes a plain prompt rather than
+	/// messages.
+	pub fn raw_completion_model(&self, model: &str) -> super::RawCompletionModel<H>
+	where
+		H: Clone,
+	{
+		super::RawCompletionModel::new(self.clone(), model)
+	}
+
+	/// Returns the SubProvider serving `model`, along with the model without its route suffix.
+	pub(crate) fn subprovider<'a>(&self, model: &'a str) -> (SubProvider, &'a str) {
+		match &self.ext().subprovider {
+			Some(subprovider) => (subprovider.clone(), model),
+			None => SubProvider::from_model(model),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MODEL: &str = "meta-llama/Llama-3.3-70B-Instruct";
+
+	#[test]
+	fn test_completion_endpoints() {
+		let cases = [
+			(SubProvider::HFInference, "v1/chat/completions"),
+			(SubProvider::Together, "together/v1/chat/completions"),
+			(SubProvider::SambaNova, "sambanova/v1/chat/completions"),
+			(
+				SubProvider::Fireworks,
+				"fireworks-ai/inference/v1/chat/completions",
+			),
+			(SubProvider::Hyperbolic, "hyperbolic/v1/chat/completions"),
+			(SubProvider::Nebius, "nebius/v1/chat/completions"),
+			(SubProvider::Novita, "novita/v3/openai/chat/completions"),
+			(
+				SubProvider::Custom("my-route".into()),
+				"my-route/v1/chat/completions",
+			),
+		];
+
+		for (subprovider, endpoint) in cases {
+			assert_eq!(subprovider.completion_endpoint(MODEL), endpoint);
+		}
+	}
+
+	#[test]
+	fn test_text_generation_endpoints() {
+		assert_eq!(
+			SubProvider::HFInference
+				.text_generation_endpoint(MODEL, false)
+				.unwrap(),
+			"hf-inference/models/meta-llama/Llama-3.3-70B-Instruct"
+		);
+		assert_eq!(
+			SubProvider::Custom("tgi".into())
+				.text_generation_endpoint(MODEL, true)
+				.unwrap(),
+			"tgi/generate_stream"
+		);
+		assert!(
+			SubProvider::Nebius
+				.text_generation_endpoint(MODEL, false)
+				.is_err()
+		);
+	}
+
+	#[test]
+	fn test_model_identifiers() {
+		let cases = [
+			(SubProvider::HFInference, MODEL),
+			(SubProvider::Together, MODEL),
+			(SubProvider::SambaNova, "Llama-3.3-70B-Instruct"),
+			(
+				SubProvider::Fireworks,
+				"accounts/fireworks/models/meta-llama/Llama-3.3-70B-Instruct",
+			),
+			(SubProvider::Hyperbolic, MODEL),
+			(SubProvider::Nebius, MODEL),
+			(SubProvider::Novita, MODEL),
+		];
+
+		for (subprovider, identifier) in cases {
+			assert_eq!(subprovider.model_identifier(MODEL), identifier);
+		}
+
+		assert_eq!(
+			SubProvider::SambaNova.model_identifier("DeepSeek-R1"),
+			"DeepSeek-R1"
+		);
+	}
+
+	#[test]
+	fn test_subprovider_inferred_from_model() {
+		let client = Client::<reqwest::Client>::new("test-key").unwrap();
+
+		assert_eq!(
+			client.subprovider("meta-llama/Llama-3.3-70B-Instruct:nebius"),
+			(SubProvider::Nebius, MODEL)
+		);
+		assert_eq!(
+			client.subprovider("meta-llama/Llama-3.3-70B-Instruct:sambanova"),
+			(SubProvider::SambaNova, MODEL)
+		);
+		assert_eq!(client.subprovider(MODEL), (SubProvider::HFInference, MODEL));
+		// Unknown suffixes are part of the model name
+		assert_eq!(
+			client.subprovider("some-model:latest"),
+			(SubProvider::HFInference, "some-model:latest")
+		);
+	}
+
+	#[test]
+	fn test_forced_subprovider() {
+		let client = Client::<reqwest::Client>::new("test-key")
+			.unwrap()
+			.with_subprovider(SubProvider::SambaNova);
+
+		assert_eq!(client.subprovider(MODEL), (SubProvider::SambaNova, MODEL));
+
+		let client = Client::<reqwest::Client>::builder()
+			.api_key("test-key")
+			.subprovider(SubProvider::Nebius)
+			.build()
+			.unwrap();
+
+		assert_eq!(
+			client.subprovider("meta-llama/Llama-3.3-70B-Instruct:sambanova"),
+			(
+				SubProvider::Nebius,
+				"meta-llama/Llama-3.3-70B-Instruct:sambanova"
+			)
+		);
 	}
 }
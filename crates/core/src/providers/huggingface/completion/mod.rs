@@ -38,10 +38,15 @@ where
 		Self::new(client.clone(), &model.into())
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -54,13 +59,18 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
 
-		let model = self.client.subprovider().model_identifier(&self.model);
+		let (subprovider, model) = self.client.subprovider(&self.model);
+		let path = subprovider.completion_endpoint(model);
+		let model = subprovider.model_identifier(model);
 		let request = HuggingfaceCompletionRequest::try_from((model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(
@@ -72,7 +82,6 @@ where
 
 		let request = serde_json::to_vec(&request)?;
 
-		let path = self.client.subprovider().completion_endpoint(&self.model);
 		let request = self
 			.client
 			.post(&path)?
@@ -82,6 +91,7 @@ where
 
 		async move {
 			let response = self.client.send(request).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			if response.status().is_success() {
 				let bytes: Vec<u8> = response.into_body().await?;
@@ -102,8 +112,10 @@ where
 						let span = tracing::Span::current();
 						span.record_token_usage(&response.usage);
 						span.record_response_metadata(&response);
+						span.record_output_messages(&response.choices);
 
-						response.try_into()
+						completion::CompletionResponse::try_from(response)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.to_string())),
 				}
@@ -129,6 +141,7 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let request = self.client.apply_default_params(request);
 		CompletionModel::stream(self, request).await
 	}
 }
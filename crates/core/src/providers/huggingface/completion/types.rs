@@ -209,14 +209,11 @@ impl TryFrom<message::UserContent> for UserContent {
 					| message::DocumentSourceKind::String(text),
 				..
 			}) => Ok(UserContent::Text { text }),
-			message::UserContent::Image(message::Image { data, .. }) => match data {
-				message::DocumentSourceKind::Url(url) => Ok(UserContent::ImageUrl {
-					image_url: ImageUrl { url },
-				}),
-				_ => Err(message::MessageError::ConversionError(
-					"Huggingface only supports images as urls".into(),
-				)),
-			},
+			message::UserContent::Image(image) => Ok(UserContent::ImageUrl {
+				image_url: ImageUrl {
+					url: image.try_into_url()?,
+				},
+			}),
 			_ => Err(message::MessageError::ConversionError(
 				"Huggingface only supports text and images".into(),
 			)),
@@ -360,6 +357,11 @@ impl TryFrom<message::Message> for Vec<Message> {
 									"Image content is not supported on HuggingFace via Clankers"
 								);
 							}
+							message::AssistantContent::Document(_) => {
+								tracing::warn!(
+									"HuggingFace doesn't support assistant documents, skipping"
+								);
+							}
 						}
 						(texts, tools)
 					},
@@ -594,12 +596,14 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			output_tokens: response.usage.completion_tokens as u64,
 			total_tokens: response.usage.total_tokens as u64,
 			cached_input_tokens: 0,
+			cache_creation_input_tokens: 0,
 		};
 
 		Ok(completion::CompletionResponse {
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -611,6 +615,8 @@ pub(in crate::providers::huggingface) struct HuggingfaceCompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<crate::providers::openai::completion::types::ToolChoice>,
@@ -653,6 +659,7 @@ impl TryFrom<(&str, CompletionRequest)> for HuggingfaceCompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools: req
 				.tools
 				.clone()
@@ -4,6 +4,7 @@ use super::client::Client;
 use crate::http_client::HttpClientExt;
 use crate::image_generation;
 use crate::image_generation::{ImageGenerationError, ImageGenerationRequest};
+use crate::json_utils::merge_inplace;
 
 #[allow(non_upper_case_globals)]
 pub mod image_generation_models {
@@ -24,10 +25,7 @@ impl TryFrom<ImageGenerationResponse>
 	type Error = ImageGenerationError;
 
 	fn try_from(value: ImageGenerationResponse) -> Result<Self, Self::Error> {
-		Ok(image_generation::ImageGenerationResponse {
-			image: value.data.clone(),
-			response: value,
-		})
+		Self::new(vec![value.data.clone()], value)
 	}
 }
 
@@ -62,18 +60,37 @@ where
 		&self,
 		request: ImageGenerationRequest,
 	) -> Result<image_generation::ImageGenerationResponse<Self::Response>, ImageGenerationError> {
+		let mut parameters = json!({
+			"width": request.width,
+			"height": request.height
+		});
+
+		if let Some(negative_prompt) = request.negative_prompt {
+			merge_inplace(
+				&mut parameters,
+				json!({ "negative_prompt": negative_prompt }),
+			);
+		}
+
+		if let Some(seed) = request.seed {
+			merge_inplace(&mut parameters, json!({ "seed": seed }));
+		}
+
+		if let Some(steps) = request.steps {
+			merge_inplace(&mut parameters, json!({ "num_inference_steps": steps }));
+		}
+
+		if let Some(cfg_scale) = request.cfg_scale {
+			merge_inplace(&mut parameters, json!({ "guidance_scale": cfg_scale }));
+		}
+
 		let request = json!({
 			"inputs": request.prompt,
-			"parameters": {
-				"width": request.width,
-				"height": request.height
-			}
+			"parameters": parameters,
 		});
 
-		let route = self
-			.client
-			.subprovider()
-			.image_generation_endpoint(&self.model)?;
+		let (subprovider, model) = self.client.subprovider(&self.model);
+		let route = subprovider.image_generation_endpoint(model)?;
 
 		let body = serde_json::to_vec(&request)?;
 
@@ -17,8 +17,10 @@ pub mod completion;
 #[cfg_attr(docsrs, doc(cfg(feature = "image")))]
 pub mod image_generation;
 pub mod streaming;
+pub mod text_generation;
 pub mod transcription;
 
 pub use client::{Client, ClientBuilder, SubProvider};
 #[cfg(feature = "image")]
 pub use image_generation::image_generation_models::*;
+pub use text_generation::RawCompletionModel;
@@ -9,6 +9,7 @@ use crate::providers::openai::completion::streaming::{
 	StreamingCompletionResponse, send_compatible_streaming_request,
 };
 use crate::streaming;
+use crate::telemetry::SpanCombinator;
 
 impl<T> CompletionModel<T>
 where
@@ -19,7 +20,9 @@ where
 		completion_request: CompletionRequest,
 	) -> Result<streaming::StreamingCompletionResponse<StreamingCompletionResponse>, CompletionError>
 	{
-		let model = self.client.subprovider().model_identifier(&self.model);
+		let (subprovider, model) = self.client.subprovider(&self.model);
+		let path = subprovider.completion_endpoint(model);
+		let model = subprovider.model_identifier(model);
 		let mut request =
 			HuggingfaceCompletionRequest::try_from((model.as_ref(), completion_request))?;
 
@@ -38,9 +41,6 @@ where
 			);
 		}
 
-		// HF Inference API uses the model in the path even though its specified in the request body
-		let path = self.client.subprovider().completion_endpoint(&self.model);
-
 		let body = serde_json::to_vec(&request)?;
 
 		let req = self
@@ -61,10 +61,13 @@ where
 			gen_ai.response.model = self.model,
 			gen_ai.usage.output_tokens = tracing::field::Empty,
 			gen_ai.usage.input_tokens = tracing::field::Empty,
+			gen_ai.input.messages = tracing::field::Empty,
+			gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		send_compatible_streaming_request(self.client.clone(), req)
 			.instrument(span)
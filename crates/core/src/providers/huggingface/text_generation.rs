@@ -0,0 +1,581 @@
+//! Huggingface text generation task, for models and endpoints which take a plain prompt rather
+//! than messages, e.g. Text Generation Inference (TGI) `/generate` endpoints.
+//! From [Huggingface Inference API Reference](https://huggingface.co/docs/inference-providers/tasks/text-generation)
+//!
+//! # Example
+//! ```
+//! use clankers::{completion::Prompt, providers::huggingface};
+//!
+//! let client = huggingface::Client::from_env();
+//! let model = client.raw_completion_model("bigcode/starcoder2-15b");
+//!
+//! let agent = clankers::agent::AgentBuilder::new(model).build();
+//! let code = agent.prompt("def fibonacci(n):").await?;
+//! ```
+use async_stream::stream;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{Level, enabled, info_span};
+use tracing_futures::Instrument;
+
+use super::client::Client;
+use super::completion::types::ApiResponse;
+use crate::OneOrMany;
+use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage};
+use crate::http_client::HttpClientExt;
+use crate::http_client::sse::{Event, GenericEventSource};
+use crate::message::{self, AssistantContent, Message, UserContent};
+use crate::streaming;
+use crate::telemetry::SpanCombinator;
+
+/// Model for the text generation task: the request is rendered as a single prompt and the
+/// model continues it.
+#[derive(Clone)]
+pub struct RawCompletionModel<T = reqwest::Client> {
+	pub(crate) client: Client<T>,
+	/// Name of the model (e.g: bigcode/starcoder2-15b)
+	pub model: String,
+}
+
+impl<T> RawCompletionModel<T> {
+	pub fn new(client: Client<T>, model: &str) -> Self {
+		Self {
+			client,
+			model: model.to_string(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextGenerationRequest {
+	pub inputs: String,
+	pub parameters: TextGenerationParameters,
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	pub stream: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextGenerationParameters {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_new_tokens: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub temperature: Option<f64>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub stop: Vec<String>,
+	/// Whether to return the generation details, which hold the token usage
+	pub details: bool,
+	/// Whether to prepend the prompt to the generated text
+	pub return_full_text: bool,
+	#[serde(flatten, skip_serializing_if = "Option::is_none")]
+	pub additional_params: Option<Value>,
+}
+
+impl TextGenerationRequest {
+	pub fn new(request: CompletionRequest, stream: bool) -> Result<Self, CompletionError> {
+		Ok(Self {
+			inputs: render_prompt(&request)?,
+			parameters: TextGenerationParameters {
+				max_new_tokens: request.max_tokens,
+				temperature: request.temperature,
+				stop: request.stop_sequences,
+				details: true,
+				return_full_text: false,
+				additional_params: request.additional_params,
+			},
+			stream,
+		})
+	}
+}
+
+/// Renders the request as a prompt. A lone user message is sent as is, anything else (preamble,
+/// documents or several turns) is rendered as `Role: text` turns ending with the assistant's
+/// turn.
+fn render_prompt(request: &CompletionRequest) -> Result<String, CompletionError> {
+	let mut turns = Vec::new();
+
+	if let Some(preamble) = &request.preamble {
+		turns.push(("System", preamble.clone()));
+	}
+
+	for message in request
+		.normalized_documents()
+		.iter()
+		.chain(request.chat_history.iter())
+	{
+		match message {
+			Message::User { content } => turns.push((
+				"User",
+				content.iter().map(user_text).collect::<Result<_, _>>()?,
+			)),
+			Message::Assistant { content, .. } => turns.push((
+				"Assistant",
+				content
+					.iter()
+					.map(assistant_text)
+					.collect::<Result<_, _>>()?,
+			)),
+		}
+	}
+
+	if let [("User", prompt)] = turns.as_slice() {
+		return Ok(prompt.clone());
+	}
+
+	let mut prompt = turns
+		.into_iter()
+		.map(|(role, text)| format!("{role}: {text}\n\n"))
+		.collect::<String>();
+	prompt.push_str("Assistant:");
+
+	Ok(prompt)
+}
+
+fn user_text(content: &UserContent) -> Result<String, message::MessageError> {
+	match content {
+		UserContent::Text(text) => Ok(text.text.clone()),
+		UserContent::Document(message::Document {
+			data: message::DocumentSourceKind::Raw(raw),
+			..
+		}) => Ok(String::from_utf8_lossy(raw).into()),
+		UserContent::Document(message::Document {
+			data:
+				message::DocumentSourceKind::Base64(text) | message::DocumentSourceKind::String(text),
+			..
+		}) => Ok(text.clone()),
+		_ => Err(message::MessageError::ConversionError(
+			"Huggingface text generation only supports text messages".into(),
+		)),
+	}
+}
+
+fn assistant_text(content: &AssistantContent) -> Result<String, message::MessageError> {
+	match content {
+		AssistantContent::Text(text) => Ok(text.text.clone()),
+		// Reasoning isn't part of the prompt
+		AssistantContent::Reasoning(_) => Ok(String::new()),
+		_ => Err(message::MessageError::ConversionError(
+			"Huggingface text generation only supports text messages".into(),
+		)),
+	}
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TextGenerationResponse {
+	pub generated_text: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub details: Option<TextGenerationDetails>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TextGenerationDetails {
+	/// e.g. `length`, `eos_token` or `stop_sequence`
+	pub finish_reason: String,
+	pub generated_tokens: u64,
+	#[serde(default)]
+	pub seed: Option<u64>,
+	/// Prompt tokens, only returned by streaming responses
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub input_length: Option<u64>,
+	/// Prompt tokens, only returned by non-streaming responses when `decoder_input_details` is set
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub prefill: Vec<Value>,
+}
+
+impl GetTokenUsage for TextGenerationResponse {
+	fn token_usage(&self) -> Option<completion::Usage> {
+		let details = self.details.as_ref()?;
+
+		let mut usage = completion::Usage::new();
+		usage.input_tokens = details.input_length.unwrap_or(details.prefill.len() as u64);
+		usage.output_tokens = details.generated_tokens;
+		usage.total_tokens = usage.input_tokens + usage.output_tokens;
+
+		Some(usage)
+	}
+}
+
+/// Huggingface Inference returns a list of generations, TGI a single one.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TextGenerationOutput {
+	Single(TextGenerationResponse),
+	Batch(Vec<TextGenerationResponse>),
+}
+
+impl TryFrom<TextGenerationOutput> for completion::CompletionResponse<TextGenerationResponse> {
+	type Error = CompletionError;
+
+	fn try_from(output: TextGenerationOutput) -> Result<Self, Self::Error> {
+		let response = match output {
+			TextGenerationOutput::Single(response) => response,
+			TextGenerationOutput::Batch(responses) => {
+				responses.into_iter().next().ok_or_else(|| {
+					CompletionError::ResponseError("Response contained no generation".to_owned())
+				})?
+			}
+		};
+
+		Ok(completion::CompletionResponse {
+			choice: OneOrMany::one(AssistantContent::text(&response.generated_text)),
+			usage: response.token_usage().unwrap_or_default(),
+			raw_response: response,
+			response_metadata: None,
+		})
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamToken {
+	text: String,
+	/// Special tokens, e.g. `</s>`, aren't part of the generated text
+	#[serde(default)]
+	special: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamEvent {
+	token: StreamToken,
+	/// Only set on the last event
+	generated_text: Option<String>,
+	details: Option<TextGenerationDetails>,
+}
+
+impl<T> completion::CompletionModel for RawCompletionModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	type Response = TextGenerationResponse;
+	type StreamingResponse = TextGenerationResponse;
+
+	type Client = Client<T>;
+
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		Self::new(client.clone(), &model.into())
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	fn validate_request(
+		&self,
+		request: &CompletionRequest,
+		report: &mut completion::ValidationReport,
+	) {
+		if !request.tools.is_empty() {
+			report.warn("Huggingface text generation doesn't support tools, they are ignored");
+		}
+	}
+
+	async fn completion(
+		&self,
+		completion_request: CompletionRequest,
+	) -> Result<completion::CompletionResponse<TextGenerationResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
+		let span = if tracing::Span::current().is_disabled() {
+			info_span!(
+				target: "clankers::completions",
+				"text_completion",
+				gen_ai.operation.name = "text_completion",
+				gen_ai.provider.name = "huggingface",
+				gen_ai.request.model = self.model,
+				gen_ai.usage.output_tokens = tracing::field::Empty,
+				gen_ai.usage.input_tokens = tracing::field::Empty,
+			)
+		} else {
+			tracing::Span::current()
+		};
+
+		let (subprovider, model) = self.client.subprovider(&self.model);
+		let path = subprovider.text_generation_endpoint(model, false)?;
+		let request = TextGenerationRequest::new(completion_request, false)?;
+
+		if enabled!(Level::TRACE) {
+			tracing::trace!(
+				target: "clankers::completions",
+				"Huggingface text generation request: {}",
+				serde_json::to_string_pretty(&request)?
+			);
+		}
+
+		let request = self
+			.client
+			.post(&path)?
+			.header("Content-Type", "application/json")
+			.body(serde_json::to_vec(&request)?)
+			.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+		async move {
+			let response = self.client.send(request).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
+			let status = response.status();
+			let bytes: Vec<u8> = response.into_body().await?;
+
+			if !status.is_success() {
+				return Err(CompletionError::ProviderError(format!(
+					"{}: {}",
+					status,
+					String::from_utf8_lossy(&bytes)
+				)));
+			}
+
+			match serde_json::from_slice::<ApiResponse<TextGenerationOutput>>(&bytes)? {
+				ApiResponse::Ok(output) => {
+					let response = completion::CompletionResponse::try_from(output)?;
+					tracing::Span::current().record_token_usage(&response.raw_response);
+
+					Ok(response.with_response_metadata(response_metadata))
+				}
+				ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.to_string())),
+			}
+		}
+		.instrument(span)
+		.await
+	}
+
+	async fn stream(
+		&self,
+		completion_request: CompletionRequest,
+	) -> Result<streaming::StreamingCompletionResponse<TextGenerationResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
+		let span = if tracing::Span::current().is_disabled() {
+			info_span!(
+				target: "clankers::completions",
+				"text_completion_streaming",
+				gen_ai.operation.name = "text_completion_streaming",
+				gen_ai.provider.name = "huggingface",
+				gen_ai.request.model = self.model,
+				gen_ai.usage.output_tokens = tracing::field::Empty,
+				gen_ai.usage.input_tokens = tracing::field::Empty,
+			)
+		} else {
+			tracing::Span::current()
+		};
+
+		let (subprovider, model) = self.client.subprovider(&self.model);
+		let path = subprovider.text_generation_endpoint(model, true)?;
+		let request = TextGenerationRequest::new(completion_request, true)?;
+
+		if enabled!(Level::TRACE) {
+			tracing::trace!(
+				target: "clankers::streaming",
+				"Huggingface text generation streaming request: {}",
+				serde_json::to_string_pretty(&request)?
+			);
+		}
+
+		let req = self
+			.client
+			.post_sse(&path)?
+			.header("Content-Type", "application/json")
+			.body(serde_json::to_vec(&request)?)
+			.map_err(|e| CompletionError::HttpError(e.into()))?;
+
+		let mut event_source = GenericEventSource::new(self.client.clone(), req);
+
+		let stream = stream! {
+			let mut text = String::new();
+			let mut details = None;
+
+			while let Some(event_result) = event_source.next().await {
+				match event_result {
+					Ok(Event::Open) => {
+						if let Some(metadata) = event_source.response_metadata() {
+							yield Ok(streaming::RawStreamingChoice::ResponseMetadata(metadata.clone()));
+						}
+					}
+					Ok(Event::Message(message)) => {
+						if message.data.trim().is_empty() {
+							continue;
+						}
+
+						let event = match serde_json::from_str::<StreamEvent>(&message.data) {
+							Ok(event) => event,
+							Err(error) => {
+								tracing::error!(?error, message = message.data, "Failed to parse SSE message");
+								continue;
+							}
+						};
+
+						if !event.token.special && !event.token.text.is_empty() {
+							text.push_str(&event.token.text);
+							yield Ok(streaming::RawStreamingChoice::Message(event.token.text));
+						}
+
+						if let Some(generated_text) = event.generated_text {
+							text = generated_text;
+							details = event.details;
+							break;
+						}
+					}
+					Err(crate::http_client::Error::StreamStalled(timeout)) => {
+						yield Err(CompletionError::StreamStalled(timeout));
+						break;
+					}
+					Err(error) => {
+						tracing::error!(?error, "SSE error");
+						yield Err(CompletionError::ProviderError(error.to_string()));
+						break;
+					}
+				}
+			}
+
+			event_source.close();
+
+			let response = TextGenerationResponse {
+				generated_text: text,
+				details,
+			};
+			tracing::Span::current().record_token_usage(&response);
+
+			yield Ok(streaming::RawStreamingChoice::FinalResponse(response));
+		}
+		.instrument(span);
+
+		Ok(streaming::StreamingCompletionResponse::stream(Box::pin(
+			stream,
+		)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::completion::CompletionModel as _;
+	use crate::providers::huggingface::SubProvider;
+	use crate::test_utils::MockSseClient;
+
+	fn model(
+		http_client: MockSseClient,
+		subprovider: SubProvider,
+	) -> RawCompletionModel<MockSseClient> {
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.subprovider(subprovider)
+			.build()
+			.unwrap();
+
+		RawCompletionModel::new(client, "bigcode/starcoder2-15b")
+	}
+
+	#[test]
+	fn test_render_prompt() {
+		let model = model(MockSseClient::default(), SubProvider::HFInference);
+
+		let request = model.completion_request("def fibonacci(n):").build();
+		assert_eq!(render_prompt(&request).unwrap(), "def fibonacci(n):");
+
+		let request = model
+			.completion_request("And Italy?")
+			.preamble("Answer in one word.".to_string())
+			.messages(vec![
+				Message::user("What is the capital of France?"),
+				Message::assistant("Paris"),
+			])
+			.build();
+		assert_eq!(
+			render_prompt(&request).unwrap(),
+			"System: Answer in one word.\n\nUser: What is the capital of France?\n\nAssistant: Paris\n\nUser: And Italy?\n\nAssistant:"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_text_generation() {
+		let response = json!([{
+			"generated_text": " Paris.",
+			"details": {
+				"finish_reason": "eos_token",
+				"generated_tokens": 3,
+				"seed": null,
+				"prefill": [],
+				"tokens": []
+			}
+		}]);
+		let http_client = MockSseClient::default().with_json_response(response.to_string());
+
+		let response = model(http_client.clone(), SubProvider::HFInference)
+			.completion_request("The capital of France is")
+			.max_tokens(16)
+			.temperature(0.2)
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(response.choice.first(), AssistantContent::text(" Paris."));
+		assert_eq!(response.usage.output_tokens, 3);
+		assert!(
+			http_client.request_uris()[0].ends_with("/hf-inference/models/bigcode/starcoder2-15b"),
+			"{}",
+			http_client.request_uris()[0]
+		);
+		assert_eq!(
+			http_client.request_bodies()[0],
+			json!({
+				"inputs": "The capital of France is",
+				"parameters": {
+					"max_new_tokens": 16,
+					"temperature": 0.2,
+					"details": true,
+					"return_full_text": false
+				}
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_text_generation_streaming() {
+		let sse = concat!(
+			"data:{\"index\":1,\"token\":{\"id\":3681,\"text\":\" Paris\",\"logprob\":-0.1,\"special\":false},\"generated_text\":null,\"details\":null}\n\n",
+			"data:{\"index\":2,\"token\":{\"id\":2,\"text\":\"</s>\",\"logprob\":-0.01,\"special\":true},\"generated_text\":\" Paris\",\"details\":{\"finish_reason\":\"eos_token\",\"generated_tokens\":2,\"seed\":null,\"input_length\":5}}\n\n",
+		);
+		let http_client = MockSseClient::new(sse);
+
+		let mut stream = model(http_client.clone(), SubProvider::Custom("tgi".into()))
+			.completion_request("The capital of France is")
+			.stream()
+			.await
+			.unwrap();
+
+		let mut text = String::new();
+		while let Some(chunk) = stream.next().await {
+			if let streaming::StreamedAssistantContent::Text(chunk) = chunk.unwrap() {
+				text.push_str(&chunk.text);
+			}
+		}
+
+		assert_eq!(text, " Paris");
+		let response = stream.response.unwrap();
+		let usage = response.token_usage().unwrap();
+		assert_eq!(usage.input_tokens, 5);
+		assert_eq!(usage.output_tokens, 2);
+		assert!(
+			http_client.request_uris()[0].ends_with("/tgi/generate_stream"),
+			"{}",
+			http_client.request_uris()[0]
+		);
+		assert_eq!(http_client.request_bodies()[0]["stream"], true);
+	}
+
+	#[test]
+	fn test_deserialize_tgi_response() {
+		let response: TextGenerationOutput = serde_json::from_value(json!({
+			"generated_text": "def fibonacci(n):\n    return n",
+			"details": {
+				"finish_reason": "length",
+				"generated_tokens": 20,
+				"seed": 42,
+				"prefill": [{ "id": 1, "text": "<s>", "logprob": null }]
+			}
+		}))
+		.unwrap();
+
+		let response = completion::CompletionResponse::try_from(response).unwrap();
+		assert_eq!(response.usage.input_tokens, 1);
+		assert_eq!(response.usage.output_tokens, 20);
+		assert_eq!(response.raw_response.details.unwrap().seed, Some(42));
+	}
+}
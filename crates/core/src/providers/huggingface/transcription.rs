@@ -33,6 +33,11 @@ impl TryFrom<TranscriptionResponse>
 	}
 }
 
+impl transcription::OffsetTimestamps for TranscriptionResponse {
+	/// The response has no timestamps
+	fn offset_timestamps(&mut self, _: std::time::Duration) {}
+}
+
 #[derive(Clone)]
 pub struct TranscriptionModel<T = reqwest::Client> {
 	client: Client<T>,
@@ -60,6 +65,10 @@ where
 		TranscriptionModel::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn transcription(
 		&self,
 		request: transcription::TranscriptionRequest,
@@ -71,10 +80,8 @@ where
 			"inputs": data
 		});
 
-		let route = self
-			.client
-			.subprovider()
-			.transcription_endpoint(&self.model)?;
+		let (subprovider, model) = self.client.subprovider(&self.model);
+		let route = subprovider.transcription_endpoint(model)?;
 
 		let request = serde_json::to_vec(&request)?;
 
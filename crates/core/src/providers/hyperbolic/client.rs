@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use super::audio_generation::AudioGenerationModel;
 #[cfg(feature = "image")]
 use super::image_generation::ImageGenerationModel;
-use crate::client::{self, BearerAuth, Capable, Nothing, ProviderClient};
+use crate::client::{self, BearerAuth, Capable, ClientError, Nothing, ProviderClient};
 use crate::providers::openai_compat::{self, CompletionModel, OpenAiCompat, PBuilder};
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -40,6 +40,10 @@ impl ProviderClient for Client {
 		openai_compat::default_from_env::<Hyperbolic>()
 	}
 
+	fn try_from_env() -> Result<Self, ClientError> {
+		openai_compat::default_try_from_env::<Hyperbolic>()
+	}
+
 	fn from_val(input: Self::Input) -> Self {
 		Self::new(input).unwrap()
 	}
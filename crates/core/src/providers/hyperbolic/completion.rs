@@ -10,6 +10,7 @@ use crate::providers::openai::completion::streaming::send_compatible_streaming_r
 use crate::providers::openai::completion::types::{AssistantContent, Message};
 use crate::providers::openai_compat::{self, CompletionModel, FlatApiError, OpenAiCompat};
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 
 /// A Hyperbolic completion object.
 ///
@@ -81,6 +82,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 				output_tokens: (usage.total_tokens - usage.prompt_tokens) as u64,
 				total_tokens: usage.total_tokens as u64,
 				cached_input_tokens: 0,
+				cache_creation_input_tokens: 0,
 			})
 			.unwrap_or_default();
 
@@ -88,6 +90,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -105,6 +108,8 @@ pub(super) struct HyperbolicCompletionRequest {
 	pub messages: Vec<Message>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
 	#[serde(flatten, skip_serializing_if = "Option::is_none")]
 	pub additional_params: Option<serde_json::Value>,
 }
@@ -147,6 +152,7 @@ impl TryFrom<(&str, CompletionRequest)> for HyperbolicCompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			additional_params: req.additional_params,
 		})
 	}
@@ -165,10 +171,15 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::completion_span(
 			Hyperbolic::PROVIDER_NAME,
 			&self.model,
@@ -177,6 +188,7 @@ where
 
 		let request =
 			HyperbolicCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if tracing::enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -194,14 +206,18 @@ where
 			.map_err(http_client::Error::from)?;
 
 		let async_block = async move {
-			let response = openai_compat::send_and_parse::<_, CompletionResponse, FlatApiError, _>(
-				&self.client,
-				req,
-				"Hyperbolic",
-			)
+			let (response, response_metadata) = openai_compat::send_and_parse::<
+				_,
+				CompletionResponse,
+				FlatApiError,
+				_,
+			>(&self.client, req, "Hyperbolic")
 			.await?;
 
-			response.try_into()
+			tracing::Span::current().record_output_messages(&response.choices);
+
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
 		};
 
 		tracing::Instrument::instrument(async_block, span).await
@@ -211,6 +227,7 @@ where
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::streaming_span(
 			Hyperbolic::PROVIDER_NAME,
 			&self.model,
@@ -219,6 +236,7 @@ where
 
 		let mut request =
 			HyperbolicCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		openai_compat::merge_stream_params(&mut request.additional_params);
 
@@ -37,6 +37,41 @@ impl<T> ImageGenerationModel<T> {
 			model: model.into(),
 		}
 	}
+
+	fn request_body(&self, generation_request: ImageGenerationRequest) -> serde_json::Value {
+		let mut request = json!({
+			"model_name": self.model,
+			"prompt": generation_request.prompt,
+			"height": generation_request.height,
+			"width": generation_request.width,
+		});
+
+		if let Some(negative_prompt) = generation_request.negative_prompt {
+			merge_inplace(&mut request, json!({ "negative_prompt": negative_prompt }));
+		}
+
+		if let Some(seed) = generation_request.seed {
+			merge_inplace(&mut request, json!({ "seed": seed }));
+		}
+
+		if let Some(steps) = generation_request.steps {
+			merge_inplace(&mut request, json!({ "steps": steps }));
+		}
+
+		if let Some(cfg_scale) = generation_request.cfg_scale {
+			merge_inplace(&mut request, json!({ "cfg_scale": cfg_scale }));
+		}
+
+		if let Some(n) = generation_request.n {
+			merge_inplace(&mut request, json!({ "num_images": n }));
+		}
+
+		if let Some(params) = generation_request.additional_params {
+			merge_inplace(&mut request, params);
+		}
+
+		request
+	}
 }
 
 #[derive(Clone, Deserialize)]
@@ -55,14 +90,19 @@ impl TryFrom<ImageGenerationResponse>
 	type Error = ImageGenerationError;
 
 	fn try_from(value: ImageGenerationResponse) -> Result<Self, Self::Error> {
-		let data = BASE64_STANDARD
-			.decode(&value.images[0].image)
-			.expect("Could not decode image.");
-
-		Ok(Self {
-			image: data,
-			response: value,
-		})
+		let images = value
+			.images
+			.iter()
+			.map(|image| {
+				BASE64_STANDARD.decode(&image.image).map_err(|e| {
+					ImageGenerationError::ResponseError(format!(
+						"Failed to decode base64 image: {e}"
+					))
+				})
+			})
+			.collect::<Result<_, _>>()?;
+
+		Self::new(images, value)
 	}
 }
 
@@ -82,18 +122,7 @@ where
 		&self,
 		generation_request: ImageGenerationRequest,
 	) -> Result<image_generation::ImageGenerationResponse<Self::Response>, ImageGenerationError> {
-		let mut request = json!({
-			"model_name": self.model,
-			"prompt": generation_request.prompt,
-			"height": generation_request.height,
-			"width": generation_request.width,
-		});
-
-		if let Some(params) = generation_request.additional_params {
-			merge_inplace(&mut request, params);
-		}
-
-		let body = serde_json::to_vec(&request)?;
+		let body = serde_json::to_vec(&self.request_body(generation_request))?;
 
 		let request = self
 			.client
@@ -120,3 +149,89 @@ where
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::image_generation::ImageGenerationModel as _;
+	use crate::test_utils::MockSseClient;
+
+	fn model(http_client: MockSseClient) -> ImageGenerationModel<MockSseClient> {
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap();
+
+		ImageGenerationModel::new(client, SDXL1_0_BASE)
+	}
+
+	#[test]
+	fn test_request_serialization() {
+		let model = model(MockSseClient::default());
+		let request = model
+			.image_generation_request()
+			.prompt("A red fox")
+			.width(1024)
+			.height(768)
+			.negative_prompt("blurry")
+			.seed(42)
+			.steps(30)
+			.cfg_scale(7.5)
+			.n(2)
+			.additional_params(json!({ "sampler": "DPM++ 2M Karras" }))
+			.build();
+
+		assert_eq!(
+			model.request_body(request),
+			json!({
+				"model_name": "SDXL1.0-base",
+				"prompt": "A red fox",
+				"height": 768,
+				"width": 1024,
+				"negative_prompt": "blurry",
+				"seed": 42,
+				"steps": 30,
+				"cfg_scale": 7.5,
+				"num_images": 2,
+				"sampler": "DPM++ 2M Karras"
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_response_with_every_image() {
+		let http_client = MockSseClient::default().with_json_response(
+			r#"{ "images": [{ "image": "aGVsbG8=" }, { "image": "d29ybGQ=" }] }"#,
+		);
+
+		let response = model(http_client)
+			.image_generation_request()
+			.prompt("A red fox")
+			.n(2)
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(response.image, b"hello");
+		assert_eq!(response.images, [b"hello".to_vec(), b"world".to_vec()]);
+	}
+
+	#[tokio::test]
+	async fn test_response_without_images_is_an_error() {
+		let http_client = MockSseClient::default().with_json_response(r#"{ "images": [] }"#);
+
+		let error = model(http_client)
+			.image_generation_request()
+			.prompt("A red fox")
+			.send()
+			.await
+			.err()
+			.expect("a response without images should be an error");
+
+		assert!(
+			matches!(error, ImageGenerationError::ResponseError(_)),
+			"{error}"
+		);
+	}
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use super::completion::CompletionModel;
-use crate::client::{self, BearerAuth, Capable, Nothing, ProviderClient};
+use crate::client::{self, BearerAuth, Capable, ClientError, Nothing, ProviderClient};
 use crate::http_client::{self, HttpClientExt};
 use crate::providers::openai_compat::{self, OpenAiCompat};
 
@@ -41,6 +41,10 @@ impl ProviderClient for Client {
 		openai_compat::default_from_env::<Mira>()
 	}
 
+	fn try_from_env() -> Result<Self, ClientError> {
+		openai_compat::default_try_from_env::<Mira>()
+	}
+
 	fn from_val(input: Self::Input) -> Self {
 		Self::new(&input).unwrap()
 	}
@@ -72,7 +76,7 @@ struct ModelInfo {
 
 impl<T> Client<T>
 where
-	T: HttpClientExt + 'static,
+	T: HttpClientExt + Clone + 'static,
 {
 	/// List available models
 	pub async fn list_models(&self) -> Result<Vec<String>, MiraError> {
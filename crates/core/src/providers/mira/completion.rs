@@ -2,7 +2,6 @@ use serde::{Deserialize, Serialize};
 use tracing::{self, Instrument};
 
 use super::client::{Client, Mira};
-use crate::OneOrMany;
 use crate::completion::{self, CompletionError, CompletionRequest};
 use crate::http_client::{self, HttpClientExt};
 use crate::message::{self, AssistantContent, Document, DocumentSourceKind, Message, UserContent};
@@ -10,11 +9,33 @@ use crate::providers::openai;
 use crate::providers::openai::completion::streaming::send_compatible_streaming_request;
 use crate::providers::openai_compat::{self, OpenAiCompat};
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
+use crate::{OneOrMany, json_utils};
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct RawMessage {
 	pub role: String,
-	pub content: String,
+	#[serde(default)]
+	pub content: Option<String>,
+	#[serde(
+		default,
+		deserialize_with = "json_utils::null_or_vec",
+		skip_serializing_if = "Vec::is_empty"
+	)]
+	pub tool_calls: Vec<openai::completion::types::ToolCall>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tool_call_id: Option<String>,
+}
+
+impl RawMessage {
+	fn new(role: &str, content: impl Into<String>) -> Self {
+		Self {
+			role: role.to_string(),
+			content: Some(content.into()),
+			tool_calls: Vec::new(),
+			tool_call_id: None,
+		}
+	}
 }
 
 impl TryFrom<RawMessage> for message::Message {
@@ -23,14 +44,39 @@ impl TryFrom<RawMessage> for message::Message {
 	fn try_from(raw: RawMessage) -> Result<Self, Self::Error> {
 		match raw.role.as_str() {
 			"user" => Ok(message::Message::User {
-				content: OneOrMany::one(UserContent::Text(message::Text { text: raw.content })),
-			}),
-			"assistant" => Ok(message::Message::Assistant {
-				id: None,
-				content: OneOrMany::one(AssistantContent::Text(message::Text {
-					text: raw.content,
+				content: OneOrMany::one(UserContent::Text(message::Text {
+					text: raw.content.unwrap_or_default(),
 				})),
 			}),
+			"assistant" => {
+				let mut content = raw
+					.content
+					.filter(|text| !text.is_empty())
+					.map(|text| vec![AssistantContent::Text(message::Text { text })])
+					.unwrap_or_default();
+				content.extend(
+					raw.tool_calls
+						.into_iter()
+						.map(|tool_call| AssistantContent::ToolCall(tool_call.into())),
+				);
+
+				let content = OneOrMany::many(content).map_err(|_| {
+					CompletionError::ResponseError(
+						"Response contained no message or tool call (empty)".to_owned(),
+					)
+				})?;
+
+				Ok(message::Message::Assistant { id: None, content })
+			}
+			"tool" => {
+				let id = raw.tool_call_id.ok_or_else(|| {
+					CompletionError::ResponseError("Tool message missing tool_call_id".to_owned())
+				})?;
+				Ok(message::Message::tool_result(
+					id,
+					raw.content.unwrap_or_default(),
+				))
+			}
 			_ => Err(CompletionError::ResponseError(format!(
 				"Unsupported message role: {}",
 				raw.role
@@ -71,6 +117,10 @@ pub(super) struct MiraCompletionRequest {
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	max_tokens: Option<u64>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	tools: Vec<openai::completion::types::ToolDefinition>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	tool_choice: Option<openai::completion::types::ToolChoice>,
 	pub stream: bool,
 }
 
@@ -78,13 +128,12 @@ impl TryFrom<(&str, CompletionRequest)> for MiraCompletionRequest {
 	type Error = CompletionError;
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
+		req.warn_unsupported_stop_sequences("Mira");
+
 		let mut messages = Vec::new();
 
 		if let Some(content) = &req.preamble {
-			messages.push(RawMessage {
-				role: "system".to_string(),
-				content: content.to_string(),
-			});
+			messages.push(RawMessage::new("system", content));
 		}
 
 		if let Some(Message::User { content }) = req.normalized_documents() {
@@ -103,48 +152,76 @@ impl TryFrom<(&str, CompletionRequest)> for MiraCompletionRequest {
 				.collect::<Vec<_>>()
 				.join("\n");
 
-			messages.push(RawMessage {
-				role: "user".to_string(),
-				content: text,
-			});
+			messages.push(RawMessage::new("user", text));
 		}
 
 		for msg in req.chat_history {
-			let (role, content) = match msg {
+			match msg {
 				Message::User { content } => {
-					let text = content
-						.iter()
-						.map(|c| match c {
-							UserContent::Text(text) => &text.text,
-							_ => "",
-						})
-						.collect::<Vec<_>>()
-						.join("\n");
-					("user", text)
+					let mut text = Vec::new();
+					for c in content {
+						match c {
+							UserContent::Text(t) => text.push(t.text),
+							UserContent::ToolResult(result) => {
+								let content = result
+									.content
+									.into_iter()
+									.filter_map(|c| match c {
+										message::ToolResultContent::Text(t) => Some(t.text),
+										message::ToolResultContent::Image(_) => None,
+									})
+									.collect::<Vec<_>>()
+									.join("\n");
+								messages.push(RawMessage {
+									tool_call_id: Some(result.id),
+									..RawMessage::new("tool", content)
+								});
+							}
+							_ => {}
+						}
+					}
+					if !text.is_empty() {
+						messages.push(RawMessage::new("user", text.join("\n")));
+					}
 				}
 				Message::Assistant { content, .. } => {
-					let text = content
-						.iter()
-						.map(|c| match c {
-							AssistantContent::Text(text) => &text.text,
-							_ => "",
-						})
-						.collect::<Vec<_>>()
-						.join("\n");
-					("assistant", text)
+					let mut text = Vec::new();
+					let mut tool_calls = Vec::new();
+					for c in content {
+						match c {
+							AssistantContent::Text(t) => text.push(t.text),
+							AssistantContent::ToolCall(tool_call) => {
+								tool_calls.push(tool_call.into())
+							}
+							_ => {}
+						}
+					}
+					messages.push(RawMessage {
+						role: "assistant".to_string(),
+						content: (!text.is_empty()).then(|| text.join("\n")),
+						tool_calls,
+						tool_call_id: None,
+					});
 				}
-			};
-			messages.push(RawMessage {
-				role: role.to_string(),
-				content,
-			});
+			}
 		}
 
+		let tool_choice = req
+			.tool_choice
+			.map(openai::completion::types::ToolChoice::try_from)
+			.transpose()?;
+
 		Ok(Self {
 			model: model.to_string(),
 			messages,
 			temperature: req.temperature,
 			max_tokens: req.max_tokens,
+			tools: req
+				.tools
+				.into_iter()
+				.map(openai::completion::types::ToolDefinition::from)
+				.collect(),
+			tool_choice,
 			stream: false,
 		})
 	}
@@ -179,32 +256,27 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::completion_span(
 			Mira::PROVIDER_NAME,
 			&self.model,
 			&completion_request.preamble,
 		);
 
-		if !completion_request.tools.is_empty() {
-			tracing::warn!(target: "clankers::completions",
-				"Tool calls are not supported by Mira AI. {len} tools will be ignored.",
-				len = completion_request.tools.len()
-			);
-		}
-
-		if completion_request.tool_choice.is_some() {
-			tracing::warn!("WARNING: `tool_choice` not supported on Mira AI");
-		}
-
 		if completion_request.additional_params.is_some() {
 			tracing::warn!("WARNING: Additional parameters not supported on Mira AI");
 		}
 
 		let request = MiraCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if tracing::enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -227,6 +299,7 @@ where
 				.send::<_, bytes::Bytes>(req)
 				.await
 				.map_err(|e| CompletionError::ProviderError(e.to_string()))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			let status = response.status();
 			let response_body = response.into_body().into_future().await?.to_vec();
@@ -249,10 +322,15 @@ where
 			}
 
 			if let CompletionResponse::Structured {
-				id, model, usage, ..
+				id,
+				model,
+				choices,
+				usage,
+				..
 			} = &response
 			{
 				let span = tracing::Span::current();
+				span.record_output_messages(choices);
 				span.record("gen_ai.response.model_name", model);
 				span.record("gen_ai.response.id", id);
 				if let Some(usage) = usage {
@@ -264,7 +342,8 @@ where
 				}
 			}
 
-			response.try_into()
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
 		};
 
 		async_block.instrument(span).await
@@ -274,28 +353,19 @@ where
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::streaming_span(
 			Mira::PROVIDER_NAME,
 			&self.model,
 			&completion_request.preamble,
 		);
 
-		if !completion_request.tools.is_empty() {
-			tracing::warn!(target: "clankers::completions",
-				"Tool calls are not supported by Mira AI. {len} tools will be ignored.",
-				len = completion_request.tools.len()
-			);
-		}
-
-		if completion_request.tool_choice.is_some() {
-			tracing::warn!("WARNING: `tool_choice` not supported on Mira AI");
-		}
-
 		if completion_request.additional_params.is_some() {
 			tracing::warn!("WARNING: Additional parameters not supported on Mira AI");
 		}
 		let mut request =
 			MiraCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 		request.stream = true;
 
 		if tracing::enabled!(tracing::Level::TRACE) {
@@ -336,6 +406,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 						output_tokens: (usage.total_tokens - usage.prompt_tokens) as u64,
 						total_tokens: usage.total_tokens as u64,
 						cached_input_tokens: 0,
+						cache_creation_input_tokens: 0,
 					})
 					.unwrap_or_default();
 
@@ -343,31 +414,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 				let message = message::Message::try_from(choice.message.clone())?;
 
 				let content = match message {
-					Message::Assistant { content, .. } => {
-						if content.is_empty() {
-							return Err(CompletionError::ResponseError(
-								"Response contained empty content".to_owned(),
-							));
-						}
-
-						// Log warning for unsupported content types
-						for c in content.iter() {
-							if !matches!(c, AssistantContent::Text(_)) {
-								tracing::warn!(target: "clankers",
-									"Unsupported content type encountered: {:?}. The Mira provider currently only supports text content", c
-								);
-							}
-						}
-
-						content.iter().map(|c| {
-                            match c {
-                                AssistantContent::Text(text) => Ok(completion::AssistantContent::text(&text.text)),
-                                other => Err(CompletionError::ResponseError(
-                                    format!("Unsupported content type: {other:?}. The Mira provider currently only supports text content")
-                                ))
-                            }
-                        }).collect::<Result<Vec<_>, _>>()?
-					}
+					Message::Assistant { content, .. } => content.into_iter().collect::<Vec<_>>(),
 					Message::User { .. } => {
 						tracing::warn!(target: "clankers", "Received user message in response where assistant message was expected");
 						return Err(CompletionError::ResponseError(
@@ -394,6 +441,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -594,10 +642,7 @@ mod tests {
 			created: 1234567890,
 			model: "deepseek-r1".to_string(),
 			choices: vec![ChatChoice {
-				message: RawMessage {
-					role: "assistant".to_string(),
-					content: "Test response".to_string(),
-				},
+				message: RawMessage::new("assistant", "Test response"),
 				finish_reason: Some("stop".to_string()),
 				index: Some(0),
 			}],
@@ -615,4 +660,147 @@ mod tests {
 			completion::AssistantContent::text("Test response")
 		);
 	}
+
+	#[test]
+	fn test_tool_call_response_conversion() {
+		let response_json = r#"
+        {
+            "id": "chatcmpl-7c1f6b1e",
+            "object": "chat.completion",
+            "created": 1740704000,
+            "model": "gpt-4o",
+            "choices": [
+                {
+                    "finish_reason": "tool_calls",
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [
+                            {
+                                "function": {
+                                    "arguments": "{\"x\": 2, \"y\": 5}",
+                                    "name": "subtract"
+                                },
+                                "id": "call_1BspL6mQqjKgvsQbH1TIYkHf",
+                                "type": "function"
+                            },
+                            {
+                                "function": {
+                                    "arguments": "{\"x\": 3, \"y\": 4}",
+                                    "name": "add"
+                                },
+                                "id": "call_9xk2Lm0PqRs7TuVwXyZ",
+                                "type": "function"
+                            }
+                        ]
+                    }
+                }
+            ],
+            "usage": {
+                "prompt_tokens": 248,
+                "total_tokens": 274
+            }
+        }
+        "#;
+
+		let response: CompletionResponse = serde_json::from_str(response_json).unwrap();
+		let completion_response: completion::CompletionResponse<CompletionResponse> =
+			response.try_into().unwrap();
+
+		let tool_calls = completion_response
+			.choice
+			.iter()
+			.filter_map(|c| match c {
+				AssistantContent::ToolCall(tool_call) => Some(tool_call.clone()),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+
+		assert_eq!(completion_response.choice.len(), 2);
+		assert_eq!(tool_calls[0].id, "call_1BspL6mQqjKgvsQbH1TIYkHf");
+		assert_eq!(tool_calls[0].function.name, "subtract");
+		assert_eq!(tool_calls[0].function.arguments, json!({"x": 2, "y": 5}));
+		assert_eq!(tool_calls[1].function.name, "add");
+		assert_eq!(completion_response.usage.output_tokens, 26);
+	}
+
+	#[test]
+	fn test_text_and_tool_call_response_conversion() {
+		let response_json = r#"
+        {
+            "id": "chatcmpl-a92c60ae",
+            "object": "chat.completion",
+            "created": 1740704592,
+            "model": "claude-3.5-sonnet",
+            "choices": [
+                {
+                    "finish_reason": "tool_calls",
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": "Let me calculate that.",
+                        "tool_calls": [
+                            {
+                                "function": {
+                                    "arguments": "{\"x\": \"2\", \"y\": \"5\"}",
+                                    "name": "subtract"
+                                },
+                                "id": "chatcmpl-tool-f6d2af7c",
+                                "type": "function"
+                            }
+                        ]
+                    }
+                }
+            ]
+        }
+        "#;
+
+		let response: CompletionResponse = serde_json::from_str(response_json).unwrap();
+		let completion_response: completion::CompletionResponse<CompletionResponse> =
+			response.try_into().unwrap();
+
+		assert_eq!(
+			completion_response.choice.first(),
+			AssistantContent::text("Let me calculate that.")
+		);
+		assert!(matches!(
+			completion_response.choice.iter().nth(1),
+			Some(AssistantContent::ToolCall(tool_call)) if tool_call.function.name == "subtract"
+		));
+	}
+
+	#[test]
+	fn test_request_includes_tools_and_tool_history() {
+		let request = CompletionRequest::builder(Message::tool_result("call_1", "-3"))
+			.messages(vec![
+				Message::user("What is 2 - 5?"),
+				Message::Assistant {
+					id: None,
+					content: OneOrMany::one(AssistantContent::tool_call(
+						"call_1",
+						"subtract",
+						json!({"x": 2, "y": 5}),
+					)),
+				},
+			])
+			.tool(completion::ToolDefinition {
+				name: "subtract".to_string(),
+				description: "Subtract y from x".to_string(),
+				parameters: json!({"type": "object"}),
+			})
+			.tool_choice(message::ToolChoice::Auto)
+			.build();
+
+		let request = MiraCompletionRequest::try_from(("gpt-4o", request)).unwrap();
+		let json = serde_json::to_value(&request).unwrap();
+
+		assert_eq!(json["tools"][0]["function"]["name"], "subtract");
+		assert_eq!(json["tool_choice"], "auto");
+		assert_eq!(json["messages"][1]["role"], "assistant");
+		assert_eq!(json["messages"][1]["tool_calls"][0]["id"], "call_1");
+		assert_eq!(json["messages"][2]["role"], "tool");
+		assert_eq!(json["messages"][2]["tool_call_id"], "call_1");
+		assert_eq!(json["messages"][2]["content"], "-3");
+	}
 }
@@ -2,9 +2,10 @@ use std::fmt::Debug;
 
 use serde::{Deserialize, Serialize};
 
+use super::OcrModel;
 use crate::client::{
-	self, BearerAuth, Capabilities, Capable, DebugExt, Nothing, Provider, ProviderBuilder,
-	ProviderClient,
+	self, BearerAuth, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider,
+	ProviderBuilder, ProviderClient,
 };
 use crate::http_client;
 
@@ -46,6 +47,16 @@ impl<H> Capabilities<H> for MistralExt {
 
 impl DebugExt for MistralExt {}
 
+impl<H> Client<H>
+where
+	H: Clone,
+{
+	/// Create an OCR model, e.g. [super::ocr::MISTRAL_OCR_LATEST].
+	pub fn ocr_model(&self, model: impl Into<String>) -> OcrModel<H> {
+		OcrModel::new(self.clone(), model)
+	}
+}
+
 impl ProviderBuilder for MistralBuilder {
 	type Output = MistralExt;
 	type ApiKey = MistralApiKey;
@@ -62,8 +73,12 @@ impl ProviderClient for Client {
 	where
 		Self: Sized,
 	{
-		let api_key = std::env::var("MISTRAL_API_KEY").expect("MISTRAL_API_KEY not set");
-		Self::new(&api_key).unwrap()
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("MISTRAL_API_KEY")?;
+		Ok(Self::new(&api_key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
@@ -73,6 +88,8 @@ impl ProviderClient for Client {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Usage {
+	/// Absent from some responses, e.g. embeddings
+	#[serde(default)]
 	pub completion_tokens: usize,
 	pub prompt_tokens: usize,
 	pub total_tokens: usize,
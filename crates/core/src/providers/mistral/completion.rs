@@ -160,6 +160,11 @@ impl TryFrom<message::Message> for Vec<Message> {
 									"Image content is not currently supported on Mistral via Clankers"
 								);
 							}
+							message::AssistantContent::Document(_) => {
+								tracing::warn!(
+									"Mistral doesn't support assistant documents, skipping"
+								);
+							}
 						}
 						(texts, tools)
 					},
@@ -327,6 +332,8 @@ pub(super) struct MistralCompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<crate::providers::openai::completion::types::ToolChoice>,
@@ -369,6 +376,7 @@ impl TryFrom<(&str, CompletionRequest)> for MistralCompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools: req
 				.tools
 				.clone()
@@ -514,6 +522,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 				output_tokens: (usage.total_tokens - usage.prompt_tokens) as u64,
 				total_tokens: usage.total_tokens as u64,
 				cached_input_tokens: 0,
+				cache_creation_input_tokens: 0,
 			})
 			.unwrap_or_default();
 
@@ -521,6 +530,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -538,10 +548,15 @@ where
 		Self::new(client.clone(), model.into())
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let preamble = completion_request.preamble.clone();
 		let request =
 			MistralCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
@@ -566,10 +581,13 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		let body = serde_json::to_vec(&request)?;
 
@@ -581,6 +599,7 @@ where
 
 		async move {
 			let response = self.client.send(request).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			if response.status().is_success() {
 				let text = http_client::text(response).await?;
@@ -589,7 +608,9 @@ where
 						let span = tracing::Span::current();
 						span.record_token_usage(&response);
 						span.record_response_metadata(&response);
-						response.try_into()
+						span.record_output_messages(&response.choices);
+						completion::CompletionResponse::try_from(response)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
 				}
@@ -629,6 +650,7 @@ where
 					message::AssistantContent::Image(_) => {
 						panic!("Image content is not supported on Mistral via Clankers")
 					}
+					message::AssistantContent::Document(_) => {}
 				}
 			}
 
@@ -708,4 +730,19 @@ mod tests {
 		assert_eq!(created, 1702256327);
 		assert_eq!(choices.len(), 1);
 	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = CompletionRequest::builder("Count to ten")
+			.stop_sequences(vec!["\n\n".to_string(), "END".to_string()])
+			.build();
+
+		let request =
+			MistralCompletionRequest::try_from(("mistral-large-latest", request)).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["stop"],
+			serde_json::json!(["\n\n", "END"])
+		);
+	}
 }
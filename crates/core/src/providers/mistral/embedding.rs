@@ -1,19 +1,42 @@
-use serde::Deserialize;
-use serde_json::json;
+use serde::{Deserialize, Serialize};
 
 use super::client::{ApiResponse, Client, Usage};
+use crate::completion::GetTokenUsage;
 use crate::embeddings::{self, EmbeddingError};
 use crate::http_client::{self, HttpClientExt};
 
 pub const MISTRAL_EMBED: &str = "mistral-embed";
+/// Code embedding model, supporting [EmbeddingModel::output_dimension] and
+/// [EmbeddingModel::output_dtype]
+pub const CODESTRAL_EMBED: &str = "codestral-embed";
 
 pub const MAX_DOCUMENTS: usize = 1024;
 
+/// Data type of the returned embeddings.
+///
+/// Only [OutputDtype::Float] embeddings are actual floats. The other types are quantized: the
+/// embedding values are the raw integers returned by the API, converted to `f64` without any
+/// scaling, and the binary types pack 8 dimensions into each value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputDtype {
+	#[default]
+	Float,
+	Int8,
+	Uint8,
+	Binary,
+	Ubinary,
+}
+
 #[derive(Clone)]
 pub struct EmbeddingModel<T = reqwest::Client> {
 	client: Client<T>,
 	pub model: String,
 	ndims: usize,
+	/// Number of dimensions of the embeddings, the model's default when `None`
+	pub output_dimension: Option<usize>,
+	/// Data type of the embeddings, floats when `None`
+	pub output_dtype: Option<OutputDtype>,
 }
 
 impl<T> EmbeddingModel<T> {
@@ -22,6 +45,8 @@ impl<T> EmbeddingModel<T> {
 			client,
 			model: model.into(),
 			ndims,
+			output_dimension: None,
+			output_dtype: None,
 		}
 	}
 
@@ -30,36 +55,46 @@ impl<T> EmbeddingModel<T> {
 			client,
 			model: model.to_string(),
 			ndims,
+			output_dimension: None,
+			output_dtype: None,
 		}
 	}
-}
-
-impl<T> embeddings::EmbeddingModel for EmbeddingModel<T>
-where
-	T: HttpClientExt + Clone + 'static,
-{
-	type Client = Client<T>;
 
-	const MAX_DOCUMENTS: usize = MAX_DOCUMENTS;
+	/// Set the number of dimensions of the embeddings, which also becomes the model's
+	/// [ndims](embeddings::EmbeddingModel::ndims)
+	pub fn output_dimension(mut self, output_dimension: usize) -> Self {
+		self.output_dimension = Some(output_dimension);
+		self.ndims = output_dimension;
+		self
+	}
 
-	fn make(client: &Self::Client, model: impl Into<String>, dims: Option<usize>) -> Self {
-		Self::new(client.clone(), model, dims.unwrap_or_default())
+	/// Set the data type of the embeddings. See [OutputDtype] for how quantized embeddings
+	/// are returned.
+	pub fn output_dtype(mut self, output_dtype: OutputDtype) -> Self {
+		self.output_dtype = Some(output_dtype);
+		self
 	}
 
-	fn ndims(&self) -> usize {
-		self.ndims
+	fn request(&self, documents: Vec<String>) -> EmbeddingRequest {
+		EmbeddingRequest {
+			model: self.model.clone(),
+			input: documents,
+			output_dimension: self.output_dimension,
+			output_dtype: self.output_dtype,
+		}
 	}
+}
 
-	async fn embed_texts(
+impl<T> EmbeddingModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	/// Embeds the documents, returning the raw response, including the token usage.
+	pub async fn embeddings(
 		&self,
-		documents: impl IntoIterator<Item = String>,
-	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
-		let documents = documents.into_iter().collect::<Vec<_>>();
-
-		let body = serde_json::to_vec(&json!({
-			"model": self.model,
-			"input": documents
-		}))?;
+		documents: Vec<String>,
+	) -> Result<EmbeddingResponse, EmbeddingError> {
+		let body = serde_json::to_vec(&self.request(documents))?;
 
 		let req = self
 			.client
@@ -81,21 +116,7 @@ where
 						response.usage
 					);
 
-					if response.data.len() != documents.len() {
-						return Err(EmbeddingError::ResponseError(
-							"Response data length does not match input length".into(),
-						));
-					}
-
-					Ok(response
-						.data
-						.into_iter()
-						.zip(documents.into_iter())
-						.map(|(embedding, document)| embeddings::Embedding {
-							document,
-							vec: embedding.embedding,
-						})
-						.collect())
+					Ok(response)
 				}
 				ApiResponse::Err(err) => Err(EmbeddingError::ProviderError(err.message)),
 			}
@@ -106,6 +127,61 @@ where
 	}
 }
 
+impl<T> embeddings::EmbeddingModel for EmbeddingModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	type Client = Client<T>;
+
+	const MAX_DOCUMENTS: usize = MAX_DOCUMENTS;
+
+	fn make(client: &Self::Client, model: impl Into<String>, dims: Option<usize>) -> Self {
+		Self::new(client.clone(), model, dims.unwrap_or_default())
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	fn ndims(&self) -> usize {
+		self.ndims
+	}
+
+	async fn embed_texts(
+		&self,
+		documents: impl IntoIterator<Item = String>,
+	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+		let documents = documents.into_iter().collect::<Vec<_>>();
+		let response = self.embeddings(documents.clone()).await?;
+
+		if response.data.len() != documents.len() {
+			return Err(EmbeddingError::ResponseError(
+				"Response data length does not match input length".into(),
+			));
+		}
+
+		Ok(response
+			.data
+			.into_iter()
+			.zip(documents.into_iter())
+			.map(|(embedding, document)| embeddings::Embedding {
+				document,
+				vec: embedding.embedding,
+			})
+			.collect())
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct EmbeddingRequest {
+	model: String,
+	input: Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	output_dimension: Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	output_dtype: Option<OutputDtype>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EmbeddingResponse {
 	pub id: String,
@@ -115,9 +191,93 @@ pub struct EmbeddingResponse {
 	pub data: Vec<EmbeddingData>,
 }
 
+impl GetTokenUsage for EmbeddingResponse {
+	fn token_usage(&self) -> Option<crate::completion::Usage> {
+		let mut usage = crate::completion::Usage::new();
+		usage.input_tokens = self.usage.prompt_tokens as u64;
+		usage.total_tokens = self.usage.total_tokens as u64;
+
+		Some(usage)
+	}
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EmbeddingData {
 	pub object: String,
+	/// The embedding, with integer values for the quantized [OutputDtype]s
 	pub embedding: Vec<f64>,
 	pub index: usize,
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::client::EmbeddingsClient;
+	use crate::embeddings::EmbeddingModel as _;
+	use crate::test_utils::MockSseClient;
+
+	fn model(http_client: MockSseClient) -> EmbeddingModel<MockSseClient> {
+		Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap()
+			.embedding_model(CODESTRAL_EMBED)
+	}
+
+	#[test]
+	fn test_request_serialization() {
+		let model = model(MockSseClient::default());
+		assert_eq!(
+			serde_json::to_value(model.request(vec!["fn main() {}".to_string()])).unwrap(),
+			json!({ "model": "codestral-embed", "input": ["fn main() {}"] })
+		);
+
+		let model = model.output_dimension(256).output_dtype(OutputDtype::Int8);
+		assert_eq!(model.ndims(), 256);
+		assert_eq!(
+			serde_json::to_value(model.request(vec!["fn main() {}".to_string()])).unwrap(),
+			json!({
+				"model": "codestral-embed",
+				"input": ["fn main() {}"],
+				"output_dimension": 256,
+				"output_dtype": "int8"
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_quantized_response_with_usage() {
+		let http_client = MockSseClient::default().with_json_response(
+			r#"{
+				"id": "embd-aad6fc62b17349b192ef09225058bc45",
+				"object": "list",
+				"model": "codestral-embed",
+				"data": [
+					{ "object": "embedding", "embedding": [-12, 0, 127, -128], "index": 0 },
+					{ "object": "embedding", "embedding": [3, 5, -7, 9], "index": 1 }
+				],
+				"usage": { "prompt_tokens": 12, "total_tokens": 12 }
+			}"#,
+		);
+		let model = model(http_client).output_dtype(OutputDtype::Int8);
+
+		let response = model
+			.embeddings(vec!["a".to_string(), "b".to_string()])
+			.await
+			.unwrap();
+		let usage = response.token_usage().unwrap();
+		assert_eq!(usage.input_tokens, 12);
+		assert_eq!(usage.total_tokens, 12);
+		assert_eq!(response.data[1].embedding, [3.0, 5.0, -7.0, 9.0]);
+
+		let embeddings = model
+			.embed_texts(["a".to_string(), "b".to_string()])
+			.await
+			.unwrap();
+		assert_eq!(embeddings[0].vec, [-12.0, 0.0, 127.0, -128.0]);
+		assert_eq!(embeddings[1].document, "b");
+	}
+}
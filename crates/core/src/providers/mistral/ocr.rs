@@ -0,0 +1,427 @@
+//! Mistral OCR API, which extracts the text of documents and images as markdown, page by page.
+//!
+//! # Example
+//! ```
+//! use clankers::message::DocumentSourceKind;
+//! use clankers::providers::mistral::{self, ocr::OcrRequest};
+//!
+//! let client = mistral::Client::from_env();
+//! let ocr = client.ocr_model(mistral::ocr::MISTRAL_OCR_LATEST);
+//!
+//! let response = ocr
+//!     .ocr(OcrRequest::new(DocumentSourceKind::url("https://arxiv.org/pdf/2201.04234")).pages([0, 1]))
+//!     .await
+//!     .expect("Failed to run OCR");
+//!
+//! // One markdown document per page, e.g. to send with a prompt
+//! let documents = response.documents();
+//! ```
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::{Deserialize, Serialize};
+
+use super::client::Client;
+use crate::http_client::{self, HttpClientExt};
+use crate::message::{self, DocumentMediaType, DocumentSourceKind, MediaType, MimeType};
+
+pub const MISTRAL_OCR_LATEST: &str = "mistral-ocr-latest";
+
+#[derive(Debug, thiserror::Error)]
+pub enum OcrError {
+	/// Http error (e.g.: connection error, timeout, etc.)
+	#[error("HttpError: {0}")]
+	HttpError(#[from] http_client::Error),
+
+	/// Json error (e.g.: serialization, deserialization)
+	#[error("JsonError: {0}")]
+	JsonError(#[from] serde_json::Error),
+
+	/// Error building the OCR request, e.g. an unsupported document source
+	#[error("RequestError: {0}")]
+	RequestError(String),
+
+	/// Error returned by the OCR provider
+	#[error("ProviderError: {0}")]
+	ProviderError(String),
+}
+
+/// A document or image to extract the text of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrRequest {
+	/// The document, as a URL, a base64 string or raw bytes
+	pub document: DocumentSourceKind,
+	/// Media type of the document, a PDF when `None`. Images are sent as such, other media
+	/// types are only used for base64 and raw documents.
+	pub media_type: Option<MediaType>,
+	/// Indices of the pages to process, starting at 0, all pages when `None`
+	pub pages: Option<Vec<usize>>,
+	/// Whether the images extracted from the pages are returned as base64
+	pub include_image_base64: bool,
+}
+
+impl OcrRequest {
+	pub fn new(document: DocumentSourceKind) -> Self {
+		Self {
+			document,
+			media_type: None,
+			pages: None,
+			include_image_base64: false,
+		}
+	}
+
+	pub fn media_type(mut self, media_type: MediaType) -> Self {
+		self.media_type = Some(media_type);
+		self
+	}
+
+	/// Only process the pages at `pages`, starting at 0.
+	pub fn pages(mut self, pages: impl IntoIterator<Item = usize>) -> Self {
+		self.pages = Some(pages.into_iter().collect());
+		self
+	}
+
+	/// Return the images extracted from the pages as base64.
+	pub fn include_image_base64(mut self, include_image_base64: bool) -> Self {
+		self.include_image_base64 = include_image_base64;
+		self
+	}
+
+	fn to_document_chunk(&self) -> Result<DocumentChunk, OcrError> {
+		// Raw images would otherwise be sent as PDFs
+		let media_type = match (&self.media_type, &self.document) {
+			(None, DocumentSourceKind::Raw(bytes)) => MediaType::sniff(bytes),
+			(media_type, _) => media_type.clone(),
+		};
+		let mime_type = media_type
+			.as_ref()
+			.map_or("application/pdf", MimeType::to_mime_type);
+
+		let url = match &self.document {
+			DocumentSourceKind::Url(url) => url.clone(),
+			DocumentSourceKind::Base64(data) => format!("data:{mime_type};base64,{data}"),
+			DocumentSourceKind::Raw(bytes) => {
+				format!("data:{mime_type};base64,{}", BASE64_STANDARD.encode(bytes))
+			}
+			DocumentSourceKind::String(_) | DocumentSourceKind::Unknown => {
+				return Err(OcrError::RequestError(
+					"OCR documents must be a URL, base64 or raw bytes".into(),
+				));
+			}
+		};
+
+		Ok(match media_type {
+			Some(MediaType::Image(_)) => DocumentChunk::ImageUrl { image_url: url },
+			_ => DocumentChunk::DocumentUrl { document_url: url },
+		})
+	}
+}
+
+impl From<message::Document> for OcrRequest {
+	fn from(document: message::Document) -> Self {
+		Self {
+			media_type: document.media_type.map(MediaType::Document),
+			..Self::new(document.data)
+		}
+	}
+}
+
+impl From<message::Image> for OcrRequest {
+	fn from(image: message::Image) -> Self {
+		Self {
+			media_type: image.media_type.map(MediaType::Image),
+			..Self::new(image.data)
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum DocumentChunk {
+	DocumentUrl { document_url: String },
+	ImageUrl { image_url: String },
+}
+
+#[derive(Debug, Serialize)]
+struct OcrRequestBody<'a> {
+	model: &'a str,
+	document: DocumentChunk,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pages: Option<&'a [usize]>,
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	include_image_base64: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OcrResponse {
+	pub pages: Vec<OcrPage>,
+	pub model: String,
+	pub usage_info: OcrUsage,
+}
+
+impl OcrResponse {
+	/// The markdown of every page, separated by blank lines.
+	pub fn markdown(&self) -> String {
+		self.pages
+			.iter()
+			.map(|page| page.markdown.as_str())
+			.collect::<Vec<_>>()
+			.join("\n\n")
+	}
+
+	/// A markdown document for each page, to feed the OCR output into a completion.
+	pub fn documents(&self) -> Vec<message::Document> {
+		self.pages.iter().cloned().map(Into::into).collect()
+	}
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OcrPage {
+	/// Index of the page in the document, starting at 0
+	pub index: usize,
+	/// Text of the page, referencing the extracted images by their id
+	pub markdown: String,
+	#[serde(default)]
+	pub images: Vec<OcrImage>,
+	pub dimensions: Option<PageDimensions>,
+}
+
+impl From<OcrPage> for message::Document {
+	fn from(page: OcrPage) -> Self {
+		message::Document {
+			data: DocumentSourceKind::String(page.markdown),
+			media_type: Some(DocumentMediaType::MARKDOWN),
+			additional_params: None,
+		}
+	}
+}
+
+/// An image extracted from a page, with its bounding box in pixels.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OcrImage {
+	pub id: String,
+	pub top_left_x: Option<u32>,
+	pub top_left_y: Option<u32>,
+	pub bottom_right_x: Option<u32>,
+	pub bottom_right_y: Option<u32>,
+	/// Data URL of the image, if requested with [OcrRequest::include_image_base64]
+	pub image_base64: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct PageDimensions {
+	pub dpi: u32,
+	pub height: u32,
+	pub width: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OcrUsage {
+	pub pages_processed: u32,
+	pub doc_size_bytes: Option<u64>,
+}
+
+/// A Mistral OCR model, using the `/v1/ocr` endpoint.
+#[derive(Clone)]
+pub struct OcrModel<T = reqwest::Client> {
+	client: Client<T>,
+	pub model: String,
+}
+
+impl<T> OcrModel<T> {
+	pub fn new(client: Client<T>, model: impl Into<String>) -> Self {
+		Self {
+			client,
+			model: model.into(),
+		}
+	}
+}
+
+impl<T> OcrModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	/// Extracts the text of the document as markdown.
+	pub async fn ocr(&self, request: impl Into<OcrRequest>) -> Result<OcrResponse, OcrError> {
+		let request = request.into();
+		let body = serde_json::to_vec(&OcrRequestBody {
+			model: &self.model,
+			document: request.to_document_chunk()?,
+			pages: request.pages.as_deref(),
+			include_image_base64: request.include_image_base64,
+		})?;
+
+		let req = self
+			.client
+			.post("v1/ocr")?
+			.header("Content-Type", "application/json")
+			.body(body)
+			.map_err(http_client::Error::Protocol)?;
+
+		let response = self.client.send(req).await?;
+
+		if !response.status().is_success() {
+			let text = http_client::text(response).await?;
+			return Err(OcrError::ProviderError(text));
+		}
+
+		let body: Vec<u8> = response.into_body().await?;
+		let response: OcrResponse = serde_json::from_slice(&body)?;
+
+		tracing::debug!(target: "clankers",
+			"Mistral OCR processed {} pages",
+			response.usage_info.pages_processed
+		);
+
+		Ok(response)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::message::ImageMediaType;
+	use crate::test_utils::MockSseClient;
+
+	const RESPONSE: &str = r###"{
+		"pages": [
+			{
+				"index": 0,
+				"markdown": "# LEVERAGING UNLABELED DATA\n\n![img-0.jpeg](img-0.jpeg)\n\nWe show that...",
+				"images": [
+					{
+						"id": "img-0.jpeg",
+						"top_left_x": 294,
+						"top_left_y": 176,
+						"bottom_right_x": 1390,
+						"bottom_right_y": 561,
+						"image_base64": "data:image/jpeg;base64,/9j/4AAQSkZJRg=="
+					}
+				],
+				"dimensions": { "dpi": 200, "height": 2200, "width": 1700 }
+			},
+			{
+				"index": 1,
+				"markdown": "## 1 INTRODUCTION\n\nSemi-supervised learning...",
+				"images": [],
+				"dimensions": { "dpi": 200, "height": 2200, "width": 1700 }
+			}
+		],
+		"model": "mistral-ocr-2505-completion",
+		"document_annotation": null,
+		"usage_info": { "pages_processed": 2, "doc_size_bytes": 3002783 }
+	}"###;
+
+	fn model(http_client: MockSseClient) -> OcrModel<MockSseClient> {
+		Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap()
+			.ocr_model(MISTRAL_OCR_LATEST)
+	}
+
+	#[test]
+	fn test_response_deserialization() {
+		let response: OcrResponse = serde_json::from_str(RESPONSE).unwrap();
+
+		assert_eq!(response.pages.len(), 2);
+		assert_eq!(response.usage_info.pages_processed, 2);
+
+		let image = &response.pages[0].images[0];
+		assert_eq!(image.id, "img-0.jpeg");
+		assert_eq!(
+			(image.top_left_x, image.bottom_right_y),
+			(Some(294), Some(561))
+		);
+		assert_eq!(response.pages[0].dimensions.unwrap().width, 1700);
+
+		assert_eq!(
+			response.markdown(),
+			"# LEVERAGING UNLABELED DATA\n\n![img-0.jpeg](img-0.jpeg)\n\nWe show that...\n\n## 1 INTRODUCTION\n\nSemi-supervised learning..."
+		);
+		assert_eq!(
+			response.documents()[1],
+			message::Document {
+				data: DocumentSourceKind::String(
+					"## 1 INTRODUCTION\n\nSemi-supervised learning...".to_string()
+				),
+				media_type: Some(DocumentMediaType::MARKDOWN),
+				additional_params: None,
+			}
+		);
+	}
+
+	#[test]
+	fn test_document_chunks() {
+		let chunk = |request: OcrRequest| {
+			serde_json::to_value(request.to_document_chunk().unwrap()).unwrap()
+		};
+
+		assert_eq!(
+			chunk(OcrRequest::new(DocumentSourceKind::url(
+				"https://arxiv.org/pdf/2201.04234"
+			))),
+			json!({ "type": "document_url", "document_url": "https://arxiv.org/pdf/2201.04234" })
+		);
+		assert_eq!(
+			chunk(OcrRequest::new(DocumentSourceKind::raw(b"%PDF".to_vec()))),
+			json!({ "type": "document_url", "document_url": "data:application/pdf;base64,JVBERg==" })
+		);
+		assert_eq!(
+			chunk(OcrRequest::new(DocumentSourceKind::raw(
+				b"\x89PNG\r\n\x1a\n".to_vec()
+			))),
+			json!({ "type": "image_url", "image_url": "data:image/png;base64,iVBORw0KGgo=" })
+		);
+		assert_eq!(
+			chunk(
+				message::Image {
+					data: DocumentSourceKind::base64("iVBORw0KGgo="),
+					media_type: Some(ImageMediaType::PNG),
+					..Default::default()
+				}
+				.into()
+			),
+			json!({ "type": "image_url", "image_url": "data:image/png;base64,iVBORw0KGgo=" })
+		);
+
+		let error = OcrRequest::new(DocumentSourceKind::String("text".to_string()))
+			.to_document_chunk()
+			.unwrap_err();
+		assert!(matches!(error, OcrError::RequestError(_)), "{error}");
+	}
+
+	#[tokio::test]
+	async fn test_ocr_request() {
+		let http_client = MockSseClient::default().with_json_response(RESPONSE);
+
+		let response = model(http_client.clone())
+			.ocr(
+				OcrRequest::new(DocumentSourceKind::url("https://arxiv.org/pdf/2201.04234"))
+					.pages([0, 1])
+					.include_image_base64(true),
+			)
+			.await
+			.unwrap();
+
+		assert_eq!(response.pages[1].index, 1);
+		assert_eq!(
+			http_client.request_uris(),
+			["https://api.mistral.ai/v1/ocr"]
+		);
+		assert_eq!(
+			http_client.request_bodies()[0],
+			json!({
+				"model": "mistral-ocr-latest",
+				"document": {
+					"type": "document_url",
+					"document_url": "https://arxiv.org/pdf/2201.04234"
+				},
+				"pages": [0, 1],
+				"include_image_base64": true
+			})
+		);
+	}
+}
@@ -10,7 +10,9 @@
 //! - EternalAI
 //! - DeepSeek
 //! - Azure OpenAI
+//! - Amazon Bedrock
 //! - Mira
+//! - OpenAI-compatible gateways such as LiteLLM
 //!
 //! Each provider has its own module, which contains a `Client` implementation that can
 //! be used to initialize completion and embedding models and execute requests to those models.
@@ -47,10 +49,12 @@
 //! be used with the Cohere provider client.
 pub mod anthropic;
 pub mod azure;
+pub mod bedrock;
 pub mod cohere;
 pub mod deepseek;
 
 pub mod galadriel;
+pub mod gateway;
 pub mod gemini;
 pub mod groq;
 pub mod huggingface;
@@ -1,23 +1,31 @@
-//! Moonshot API client and Clankers integration
+//! Moonshot (Kimi) API client and Clankers integration
+//!
+//! The client targets the mainland China endpoint by default, use
+//! [MOONSHOT_GLOBAL_BASE_URL] for the international one.
 //!
 //! # Example
 //! ```
 //! use clankers::providers::moonshot;
 //!
-//! let client = moonshot::Client::new("YOUR_API_KEY");
+//! let client = moonshot::Client::builder()
+//!     .api_key("YOUR_API_KEY")
+//!     .base_url(moonshot::MOONSHOT_GLOBAL_BASE_URL)
+//!     .build()
+//!     .unwrap();
 //!
-//! let moonshot_model = client.completion_model(moonshot::MOONSHOT_CHAT);
+//! let kimi = client.completion_model(moonshot::KIMI_K2_0711_PREVIEW);
 //! ```
 use serde::{Deserialize, Serialize};
 use tracing::Instrument;
 
-use crate::client::{self, BearerAuth, Capable, Nothing, ProviderClient};
+use crate::client::{self, BearerAuth, Capable, ClientError, Nothing, ProviderClient};
 use crate::completion::{self, CompletionError, CompletionRequest};
 use crate::http_client::HttpClientExt;
 use crate::providers::openai;
 use crate::providers::openai::completion::streaming::send_compatible_streaming_request;
 use crate::providers::openai_compat::{self, FlatApiError, OpenAiCompat, PBuilder};
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 use crate::{http_client, message};
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -54,20 +62,109 @@ impl ProviderClient for Client {
 		openai_compat::default_from_env::<Moonshot>()
 	}
 
+	fn try_from_env() -> Result<Self, ClientError> {
+		openai_compat::default_try_from_env::<Moonshot>()
+	}
+
 	fn from_val(input: Self::Input) -> Self {
 		Self::new(&input).unwrap()
 	}
 }
 
-pub const MOONSHOT_CHAT: &str = "moonshot-v1-128k";
+/// Base URL of the international Moonshot API
+pub const MOONSHOT_GLOBAL_BASE_URL: &str = "https://api.moonshot.ai/v1";
+
+pub const KIMI_K2_0711_PREVIEW: &str = "kimi-k2-0711-preview";
+pub const KIMI_K2_TURBO_PREVIEW: &str = "kimi-k2-turbo-preview";
+pub const KIMI_LATEST: &str = "kimi-latest";
+pub const KIMI_THINKING_PREVIEW: &str = "kimi-thinking-preview";
+pub const MOONSHOT_V1_8K: &str = "moonshot-v1-8k";
+pub const MOONSHOT_V1_32K: &str = "moonshot-v1-32k";
+pub const MOONSHOT_V1_128K: &str = "moonshot-v1-128k";
+pub const MOONSHOT_CHAT: &str = MOONSHOT_V1_128K;
+
+/// Additional parameters for Moonshot completion requests, passed as the request's
+/// `additional_params` with [AdditionalParameters::to_json].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AdditionalParameters {
+	/// Prefill of the assistant response
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub partial: Option<Partial>,
+}
+
+impl AdditionalParameters {
+	pub fn with_partial(mut self, partial: Partial) -> Self {
+		self.partial = Some(partial);
+		self
+	}
+
+	pub fn to_json(self) -> serde_json::Value {
+		serde_json::to_value(self).expect(
+			"this should never fail since a struct that impls Deserialize will always be valid JSON",
+		)
+	}
+}
+
+/// Moonshot's partial mode: the model continues its response from `content`, which is sent
+/// as the last message of the conversation. The response doesn't repeat the prefill.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Partial {
+	pub content: String,
+	/// Name of the character the model plays, to keep it in role
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+}
+
+impl Partial {
+	pub fn new(content: impl Into<String>) -> Self {
+		Self {
+			content: content.into(),
+			name: None,
+		}
+	}
+
+	pub fn with_name(mut self, name: impl Into<String>) -> Self {
+		self.name = Some(name.into());
+		self
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(super) enum MoonshotMessage {
+	Partial(PartialMessage),
+	Message(openai::completion::types::Message),
+}
+
+/// The prefilled assistant message of partial mode
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "role", rename = "assistant")]
+pub(super) struct PartialMessage {
+	content: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	name: Option<String>,
+	partial: bool,
+}
+
+impl From<Partial> for PartialMessage {
+	fn from(Partial { content, name }: Partial) -> Self {
+		Self {
+			content,
+			name,
+			partial: true,
+		}
+	}
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(super) struct MoonshotCompletionRequest {
 	model: String,
-	pub messages: Vec<openai::completion::types::Message>,
+	pub messages: Vec<MoonshotMessage>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<openai::completion::types::ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	max_tokens: Option<u64>,
@@ -102,6 +199,21 @@ impl TryFrom<(&str, CompletionRequest)> for MoonshotCompletionRequest {
 				.collect::<Vec<_>>(),
 		);
 
+		let mut additional_params = req.additional_params;
+		let partial = additional_params
+			.as_mut()
+			.and_then(|params| params.as_object_mut()?.remove("partial"))
+			.map(serde_json::from_value::<Partial>)
+			.transpose()?;
+
+		let mut messages: Vec<MoonshotMessage> = full_history
+			.into_iter()
+			.map(MoonshotMessage::Message)
+			.collect();
+		if let Some(partial) = partial {
+			messages.push(MoonshotMessage::Partial(partial.into()));
+		}
+
 		let tool_choice = req
 			.tool_choice
 			.clone()
@@ -110,8 +222,9 @@ impl TryFrom<(&str, CompletionRequest)> for MoonshotCompletionRequest {
 
 		Ok(Self {
 			model: model.to_string(),
-			messages: full_history,
+			messages,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			max_tokens: req.max_tokens,
 			tools: req
 				.tools
@@ -120,7 +233,7 @@ impl TryFrom<(&str, CompletionRequest)> for MoonshotCompletionRequest {
 				.map(openai::completion::types::ToolDefinition::from)
 				.collect::<Vec<_>>(),
 			tool_choice,
-			additional_params: req.additional_params,
+			additional_params,
 		})
 	}
 }
@@ -138,6 +251,10 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
@@ -145,6 +262,7 @@ where
 		completion::CompletionResponse<openai::completion::types::CompletionResponse>,
 		CompletionError,
 	> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::completion_span(
 			Moonshot::PROVIDER_NAME,
 			&self.model,
@@ -153,6 +271,7 @@ where
 
 		let request =
 			MoonshotCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if tracing::enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -169,7 +288,7 @@ where
 			.map_err(http_client::Error::from)?;
 
 		let async_block = async move {
-			let response = openai_compat::send_and_parse::<
+			let (response, response_metadata) = openai_compat::send_and_parse::<
 				_,
 				openai::completion::types::CompletionResponse,
 				FlatApiError,
@@ -179,7 +298,8 @@ where
 
 			let span = tracing::Span::current();
 			openai_compat::record_openai_response_span(&span, &response);
-			response.try_into()
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
 		};
 
 		async_block.instrument(span).await
@@ -189,10 +309,12 @@ where
 		&self,
 		request: CompletionRequest,
 	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let request = self.client.apply_default_params(request);
 		let span =
 			openai_compat::streaming_span(Moonshot::PROVIDER_NAME, &self.model, &request.preamble);
 
 		let mut request = MoonshotCompletionRequest::try_from((self.model.as_ref(), request))?;
+		span.record_input_messages(&request.messages);
 
 		openai_compat::merge_stream_params(&mut request.additional_params);
 
@@ -240,3 +362,120 @@ impl TryFrom<message::ToolChoice> for ToolChoice {
 		Ok(res)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::client::CompletionClient;
+	use crate::completion::CompletionModel as _;
+
+	fn model() -> CompletionModel {
+		Client::new("test-key")
+			.unwrap()
+			.completion_model(KIMI_K2_0711_PREVIEW)
+	}
+
+	#[test]
+	fn test_partial_request_serialization() {
+		let request = model()
+			.completion_request("Tell me about the moon")
+			.preamble("Answer in JSON".to_string())
+			.additional_params(
+				AdditionalParameters::default()
+					.with_partial(Partial::new("{\"moon\": ").with_name("Kimi"))
+					.to_json(),
+			)
+			.build();
+
+		let request = MoonshotCompletionRequest::try_from((KIMI_K2_0711_PREVIEW, request)).unwrap();
+		let request = serde_json::to_value(&request).unwrap();
+
+		assert_eq!(
+			request,
+			json!({
+				"model": "kimi-k2-0711-preview",
+				"messages": [
+					{
+						"role": "system",
+						"content": [{ "type": "text", "text": "Answer in JSON" }]
+					},
+					{
+						"role": "user",
+						"content": [{ "type": "text", "text": "Tell me about the moon" }]
+					},
+					{
+						"role": "assistant",
+						"content": "{\"moon\": ",
+						"name": "Kimi",
+						"partial": true
+					}
+				]
+			})
+		);
+	}
+
+	#[test]
+	fn test_request_without_partial() {
+		let request = model()
+			.completion_request("Hello")
+			.additional_params(json!({ "top_p": 0.9 }))
+			.build();
+
+		let request = MoonshotCompletionRequest::try_from((KIMI_K2_0711_PREVIEW, request)).unwrap();
+		let request = serde_json::to_value(&request).unwrap();
+
+		assert_eq!(request["messages"].as_array().unwrap().len(), 1);
+		assert_eq!(request["top_p"], 0.9);
+	}
+
+	#[test]
+	fn test_tool_call_response() {
+		let response: openai::completion::types::CompletionResponse =
+			serde_json::from_value(json!({
+				"id": "chatcmpl-6a1f",
+				"object": "chat.completion",
+				"created": 1752537600,
+				"model": "kimi-k2-0711-preview",
+				"choices": [{
+					"index": 0,
+					"message": {
+						"role": "assistant",
+						"content": "",
+						"tool_calls": [{
+							"index": 0,
+							"id": "get_weather:0",
+							"type": "function",
+							"function": {
+								"name": "get_weather",
+								"arguments": "{\"city\": \"Beijing\"}"
+							}
+						}]
+					},
+					"finish_reason": "tool_calls"
+				}],
+				"usage": {
+					"prompt_tokens": 52,
+					"completion_tokens": 18,
+					"total_tokens": 70
+				}
+			}))
+			.unwrap();
+
+		let response: completion::CompletionResponse<_> = response.try_into().unwrap();
+
+		let tool_call = response
+			.choice
+			.iter()
+			.find_map(|content| match content {
+				message::AssistantContent::ToolCall(tool_call) => Some(tool_call),
+				_ => None,
+			})
+			.expect("expected a tool call");
+		assert_eq!(tool_call.id, "get_weather:0");
+		assert_eq!(tool_call.function.name, "get_weather");
+		assert_eq!(tool_call.function.arguments, json!({ "city": "Beijing" }));
+		assert_eq!(response.usage.total_tokens, 70);
+	}
+}
@@ -1,7 +1,12 @@
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::str::FromStr;
+
 use super::completion::CompletionModel;
 use super::embedding::EmbeddingModel;
 use crate::client::{
-	self, Capabilities, Capable, DebugExt, Nothing, Provider, ProviderBuilder, ProviderClient,
+	self, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider, ProviderBuilder,
+	ProviderClient,
 };
 use crate::http_client;
 
@@ -55,17 +60,274 @@ pub type ClientBuilder<H = reqwest::Client> = client::ClientBuilder<OllamaBuilde
 impl ProviderClient for Client {
 	type Input = Nothing;
 
+	/// Create a new Ollama client from the `OLLAMA_HOST` environment variable, see [OllamaHost]
+	/// for its formats, or from `OLLAMA_API_BASE_URL` if set. Defaults to
+	/// `http://localhost:11434`. Panics if `OLLAMA_HOST` is malformed.
 	fn from_env() -> Self {
-		let api_base = std::env::var("OLLAMA_API_BASE_URL").expect("OLLAMA_API_BASE_URL not set");
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
 
-		Self::builder()
+	fn try_from_env() -> Result<Self, ClientError> {
+		let host = match std::env::var("OLLAMA_API_BASE_URL") {
+			Ok(api_base) => OllamaHost::Url(api_base),
+			Err(_) => OllamaHost::from_env()?,
+		};
+
+		Ok(Self::builder()
 			.api_key(Nothing)
-			.base_url(&api_base)
-			.build()
-			.unwrap()
+			.host(&host)
+			.and_then(ClientBuilder::build)?)
 	}
 
 	fn from_val(_: Self::Input) -> Self {
 		Self::builder().api_key(Nothing).build().unwrap()
 	}
 }
+
+/// Address of an Ollama server, in one of the forms accepted by `OLLAMA_HOST`: `host`,
+/// `host:port`, `scheme://host:port` with an optional path, or `unix:///path/to/socket` on unix.
+///
+/// Without a scheme the port defaults to `11434`, with `http` or `https` to the scheme's port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OllamaHost {
+	/// Base URL of the server, e.g. `http://127.0.0.1:11434`
+	Url(String),
+	/// Path of the unix domain socket the server listens on
+	#[cfg(unix)]
+	UnixSocket(PathBuf),
+}
+
+impl Default for OllamaHost {
+	fn default() -> Self {
+		Self::Url(OLLAMA_API_BASE_URL.to_string())
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid OLLAMA_HOST {value:?}: {reason}")]
+pub struct OllamaHostError {
+	value: String,
+	reason: &'static str,
+}
+
+impl From<OllamaHostError> for ClientError {
+	fn from(error: OllamaHostError) -> Self {
+		ClientError::InvalidEnvVar {
+			name: "OLLAMA_HOST",
+			reason: format!("{} ({:?})", error.reason, error.value),
+		}
+	}
+}
+
+impl FromStr for OllamaHost {
+	type Err = OllamaHostError;
+
+	fn from_str(value: &str) -> Result<Self, Self::Err> {
+		let error = |reason| OllamaHostError {
+			value: value.to_string(),
+			reason,
+		};
+		let host = value.trim().trim_matches(['"', '\'']);
+
+		if host.is_empty() {
+			return Ok(Self::default());
+		}
+
+		if let Some(path) = host.strip_prefix("unix://") {
+			if path.is_empty() {
+				return Err(error("missing socket path"));
+			}
+			#[cfg(unix)]
+			return Ok(Self::UnixSocket(PathBuf::from(path)));
+			#[cfg(not(unix))]
+			return Err(error("unix domain sockets are only supported on unix"));
+		}
+
+		let (scheme, default_port, rest) = match host.split_once("://") {
+			None => ("http", 11434, host),
+			Some(("http", rest)) => ("http", 80, rest),
+			Some(("https", rest)) => ("https", 443, rest),
+			Some(_) => return Err(error("unsupported scheme")),
+		};
+
+		let (host_port, path) = match rest.find('/') {
+			Some(i) => rest.split_at(i),
+			None => (rest, ""),
+		};
+
+		let (host, port) = if let Some(ipv6) = host_port.strip_prefix('[') {
+			let (ip, port) = ipv6
+				.split_once(']')
+				.ok_or_else(|| error("unclosed IPv6 address"))?;
+			let port = match port {
+				"" => None,
+				port => Some(
+					port.strip_prefix(':')
+						.ok_or_else(|| error("invalid IPv6 address"))?,
+				),
+			};
+			(format!("[{ip}]"), port)
+		} else if host_port.matches(':').count() > 1 {
+			// IPv6 address without brackets, e.g. `::1`
+			(format!("[{host_port}]"), None)
+		} else {
+			match host_port.split_once(':') {
+				Some((host, port)) => (host.to_string(), Some(port)),
+				None => (host_port.to_string(), None),
+			}
+		};
+
+		let host = if host.is_empty() {
+			"127.0.0.1".to_string()
+		} else {
+			host
+		};
+		let port = match port {
+			Some(port) => port.parse::<u16>().map_err(|_| error("invalid port"))?,
+			None => default_port,
+		};
+
+		let url = format!("{scheme}://{host}:{port}{path}");
+		url::Url::parse(&url).map_err(|_| error("invalid host"))?;
+
+		Ok(Self::Url(url))
+	}
+}
+
+impl OllamaHost {
+	/// Reads the host from `OLLAMA_HOST`, defaulting to `http://localhost:11434` when unset.
+	pub fn from_env() -> Result<Self, OllamaHostError> {
+		std::env::var("OLLAMA_HOST").map_or(Ok(Self::default()), |host| host.parse())
+	}
+}
+
+impl ClientBuilder<reqwest::Client> {
+	/// Connects to `host`, through its unix domain socket for [OllamaHost::UnixSocket].
+	pub fn host(self, host: &OllamaHost) -> http_client::Result<Self> {
+		match host {
+			OllamaHost::Url(url) => Ok(self.base_url(url)),
+			#[cfg(unix)]
+			OllamaHost::UnixSocket(path) => {
+				let path = path.clone();
+				// The host is ignored, requests go through the socket
+				self.base_url("http://localhost")
+					.reqwest_client(|builder| builder.unix_socket(path))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn url(host: &str) -> String {
+		match host.parse::<OllamaHost>().unwrap() {
+			OllamaHost::Url(url) => url,
+			host => panic!("expected a URL, got {host:?}"),
+		}
+	}
+
+	#[test]
+	fn test_parse_ollama_host() {
+		let cases = [
+			("", "http://localhost:11434"),
+			("localhost", "http://localhost:11434"),
+			("0.0.0.0:8080", "http://0.0.0.0:8080"),
+			(":8080", "http://127.0.0.1:8080"),
+			("  \"example.com:1234\"  ", "http://example.com:1234"),
+			("http://example.com", "http://example.com:80"),
+			(
+				"https://example.com/ollama",
+				"https://example.com:443/ollama",
+			),
+			("https://example.com:8443", "https://example.com:8443"),
+			("[::1]:11435", "http://[::1]:11435"),
+			("[::1]", "http://[::1]:11434"),
+			("::1", "http://[::1]:11434"),
+		];
+
+		for (host, expected) in cases {
+			assert_eq!(url(host), expected, "{host:?}");
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_parse_ollama_unix_socket() {
+		assert_eq!(
+			"unix:///var/run/ollama.sock".parse::<OllamaHost>().unwrap(),
+			OllamaHost::UnixSocket(PathBuf::from("/var/run/ollama.sock"))
+		);
+	}
+
+	#[test]
+	fn test_parse_malformed_ollama_host() {
+		for host in [
+			"localhost:port",
+			"localhost:99999",
+			"localhost:",
+			"ftp://example.com",
+			"unix://",
+			"[::1",
+			"exa mple.com",
+		] {
+			let error = host.parse::<OllamaHost>().unwrap_err();
+			assert!(
+				error.to_string().starts_with("Invalid OLLAMA_HOST"),
+				"{error}"
+			);
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_unix_socket_client() {
+		let client = Client::builder()
+			.api_key(Nothing)
+			.host(&OllamaHost::UnixSocket(PathBuf::from(
+				"/var/run/ollama.sock",
+			)))
+			.unwrap()
+			.build()
+			.unwrap();
+
+		assert_eq!(client.base_url(), "http://localhost");
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_request_through_unix_socket() {
+		use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("ollama.sock");
+		let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+		let server = tokio::spawn(async move {
+			let (mut stream, _) = listener.accept().await.unwrap();
+			let mut request = vec![0; 4096];
+			let len = stream.read(&mut request).await.unwrap();
+
+			let body = r#"{"models":[]}"#;
+			let response = format!(
+				"HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+				body.len()
+			);
+			stream.write_all(response.as_bytes()).await.unwrap();
+
+			String::from_utf8_lossy(&request[..len]).into_owned()
+		});
+
+		let client = Client::builder()
+			.api_key(Nothing)
+			.host(&OllamaHost::UnixSocket(path))
+			.unwrap()
+			.build()
+			.unwrap();
+
+		assert_eq!(client.list_models().await.unwrap(), vec![]);
+		let request = server.await.unwrap();
+		assert!(request.starts_with("GET /api/tags HTTP/1.1"), "{request}");
+	}
+}
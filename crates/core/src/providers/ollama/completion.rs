@@ -12,6 +12,7 @@ use super::message::{Message, ToolDefinition};
 use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage, Usage};
 use crate::http_client::{self, HttpClientExt};
 use crate::streaming::RawStreamingChoice;
+use crate::telemetry::SpanCombinator;
 use crate::{OneOrMany, json_utils, message, streaming};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -43,7 +44,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			Message::Assistant {
 				content,
 				thinking,
-				tool_calls,
+				mut tool_calls,
 				..
 			} => {
 				let mut assistant_contents = Vec::new();
@@ -52,10 +53,11 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 					assistant_contents.push(completion::AssistantContent::text(&content));
 				}
 				// Process tool_calls following Ollama's chat response definition.
-				// Each ToolCall has an id, a type, and a function field.
-				for tc in tool_calls.iter() {
+				// Ids are written back into the raw response so both stay in sync.
+				for tc in tool_calls.iter_mut() {
+					let id = tc.ensure_id().to_owned();
 					assistant_contents.push(completion::AssistantContent::tool_call(
-						tc.function.name.clone(),
+						id,
 						tc.function.name.clone(),
 						tc.function.arguments.clone(),
 					));
@@ -93,8 +95,10 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 						output_tokens: completion_tokens,
 						total_tokens: prompt_tokens + completion_tokens,
 						cached_input_tokens: 0,
+						cache_creation_input_tokens: 0,
 					},
 					raw_response,
+					response_metadata: None,
 				})
 			}
 			_ => Err(CompletionError::ResponseError(
@@ -127,7 +131,7 @@ impl TryFrom<(&str, CompletionRequest)> for OllamaCompletionRequest {
 			tracing::warn!("WARNING: `tool_choice` not supported for Ollama");
 		}
 		let mut partial_history = vec![];
-		if let Some(docs) = req.normalized_documents() {
+		if let Some(docs) = req.rendered_documents() {
 			partial_history.push(docs);
 		}
 		partial_history.extend(req.chat_history);
@@ -146,19 +150,25 @@ impl TryFrom<(&str, CompletionRequest)> for OllamaCompletionRequest {
 				.flatten()
 				.collect::<Vec<_>>(),
 		);
+		super::message::resolve_tool_names(&mut full_history);
 
 		let mut think = false;
 
 		// TODO: Fix this up to include the full range of ollama options
+		let mut base_options = json!({ "temperature": req.temperature });
+		if !req.stop_sequences.is_empty() {
+			json_utils::merge_inplace(&mut base_options, json!({ "stop": req.stop_sequences }));
+		}
+
 		let options = if let Some(mut extra) = req.additional_params {
 			if extra.get("think").is_some() {
 				think = extra["think"].take().as_bool().ok_or_else(|| {
 					CompletionError::RequestError("`think` must be a bool".into())
 				})?;
 			}
-			json_utils::merge(json!({ "temperature": req.temperature }), extra)
+			json_utils::deep_merge(base_options, extra, json_utils::ArrayMergeStrategy::Replace)
 		} else {
-			json!({ "temperature": req.temperature })
+			base_options
 		};
 
 		Ok(Self {
@@ -231,10 +241,15 @@ where
 		Self::new(client.clone(), model.into().as_str())
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<Self::Response>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -247,6 +262,8 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -254,6 +271,7 @@ where
 
 		span.record("gen_ai.system_instructions", &completion_request.preamble);
 		let request = OllamaCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if tracing::enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -272,6 +290,7 @@ where
 
 		let async_block = async move {
 			let response = self.client.send::<_, Bytes>(req).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 			let status = response.status();
 			let response_body = response.into_body().into_future().await?.to_vec();
 
@@ -302,8 +321,9 @@ where
 
 			let response: completion::CompletionResponse<CompletionResponse> =
 				response.try_into()?;
+			span.record_output_messages(std::slice::from_ref(&response.raw_response.message));
 
-			Ok(response)
+			Ok(response.with_response_metadata(response_metadata))
 		};
 
 		tracing::Instrument::instrument(async_block, span).await
@@ -313,6 +333,7 @@ where
 		&self,
 		request: CompletionRequest,
 	) -> Result<streaming::StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let request = self.client.apply_default_params(request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -325,6 +346,8 @@ where
 				gen_ai.response.model = self.model,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -334,6 +357,7 @@ where
 
 		let mut request = OllamaCompletionRequest::try_from((self.model.as_ref(), request))?;
 		request.stream = true;
+		span.record_input_messages(&request.messages);
 
 		if tracing::enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -352,7 +376,8 @@ where
 
 		let response = self.client.send_streaming(req).await?;
 		let status = response.status();
-		let mut byte_stream = response.into_body();
+		let response_metadata = completion::ResponseMetadata::from_response(&response);
+		let byte_stream = response.into_body();
 
 		if !status.is_success() {
 			return Err(CompletionError::ProviderError(format!(
@@ -366,10 +391,22 @@ where
             let mut text_response = String::new();
             let mut thinking_response = String::new();
 
-            while let Some(chunk) = byte_stream.next().await {
-                let bytes = chunk.map_err(|e| http_client::Error::Instance(e.into()))?;
+            if let Some(metadata) = response_metadata {
+                yield RawStreamingChoice::ResponseMetadata(metadata);
+            }
 
-                for line in bytes.split(|&b| b == b'\n') {
+            // Lines, and the UTF-8 characters in them, may be split across chunks, so incomplete
+            // lines are buffered until their newline arrives. The trailing newline flushes a
+            // last line sent without one.
+            let mut byte_stream = byte_stream.chain(futures::stream::iter([Ok(Bytes::from_static(b"\n"))]));
+            let mut buffer = Vec::new();
+
+            'stream: while let Some(chunk) = byte_stream.next().await {
+                buffer.extend_from_slice(&chunk?);
+
+                while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                    let line = buffer.drain(..=end).collect::<Vec<_>>();
+                    let line = line.trim_ascii();
                     if line.is_empty() {
                         continue;
                     }
@@ -392,10 +429,11 @@ where
                             yield RawStreamingChoice::Message(content);
                         }
 
-                        for tool_call in tool_calls {
+                        for mut tool_call in tool_calls {
+                            let id = tool_call.ensure_id().to_owned();
                             tool_calls_final.push(tool_call.clone());
                             yield RawStreamingChoice::ToolCall(
-                                crate::streaming::RawStreamingToolCall::new(String::new(), tool_call.function.name, tool_call.function.arguments)
+                                crate::streaming::RawStreamingToolCall::new(id, tool_call.function.name, tool_call.function.arguments)
                             );
                         }
                     }
@@ -410,7 +448,7 @@ where
                             name: None,
                             tool_calls: tool_calls_final.clone()
                         };
-                        span.record("gen_ai.output.messages", serde_json::to_string(&vec![message]).unwrap());
+                        span.record_output_messages(&[message]);
                         yield RawStreamingChoice::FinalResponse(
                             StreamingCompletionResponse {
                                 total_duration: response.total_duration,
@@ -422,7 +460,7 @@ where
                                 done_reason: response.done_reason,
                             }
                         );
-                        break;
+                        break 'stream;
                     }
                 }
             }
@@ -682,4 +720,129 @@ mod tests {
 			panic!("Expected Assistant message with thinking and tool calls");
 		}
 	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = CompletionRequest::builder("Count to ten")
+			.stop_sequences(vec!["\n\n".to_string(), "END".to_string()])
+			.build();
+
+		let request = OllamaCompletionRequest::try_from(("llama3.2", request)).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["options"],
+			json!({ "temperature": null, "stop": ["\n\n", "END"] })
+		);
+	}
+
+	#[test]
+	fn test_repeated_tool_calls_get_distinct_ids() {
+		let call = json!({
+			"function": {
+				"name": "get_weather",
+				"arguments": { "location": "Paris" }
+			}
+		});
+		let response: CompletionResponse = serde_json::from_value(json!({
+			"model": "llama3.2",
+			"created_at": "2023-08-04T19:22:45.499127Z",
+			"message": {
+				"role": "assistant",
+				"content": "",
+				"tool_calls": [call, call]
+			},
+			"done": true
+		}))
+		.unwrap();
+
+		let response = completion::CompletionResponse::try_from(response).unwrap();
+		let ids = response
+			.choice
+			.iter()
+			.filter_map(|content| match content {
+				completion::AssistantContent::ToolCall(tc) => Some(tc.id.clone()),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(ids.len(), 2);
+		assert_ne!(ids[0], ids[1]);
+
+		let results = ids
+			.iter()
+			.map(|id| {
+				message::UserContent::tool_result(
+					id,
+					OneOrMany::one(message::ToolResultContent::text("Sunny")),
+				)
+			})
+			.collect::<Vec<_>>();
+		let request = CompletionRequest::builder(crate::message::Message::User {
+			content: OneOrMany::many(results).unwrap(),
+		})
+		.messages(vec![
+			crate::message::Message::user("What's the weather in Paris?"),
+			crate::message::Message::Assistant {
+				id: None,
+				content: response.choice,
+			},
+		])
+		.build();
+		let request = OllamaCompletionRequest::try_from(("llama3.2", request)).unwrap();
+
+		let Message::Assistant { tool_calls, .. } = &request.messages[1] else {
+			panic!("Expected Assistant message");
+		};
+		let sent_ids = tool_calls
+			.iter()
+			.map(|tc| tc.id.clone().unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(sent_ids, ids);
+
+		for (message, id) in request.messages[2..].iter().zip(&ids) {
+			assert_eq!(
+				message,
+				&Message::ToolResult {
+					name: "get_weather".to_string(),
+					content: "Sunny".to_string(),
+					tool_call_id: Some(id.clone()),
+				}
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_streaming_lines_split_across_chunks() {
+		use futures::StreamExt;
+
+		use crate::client::Nothing;
+		use crate::completion::CompletionModel as _;
+		use crate::streaming::StreamedAssistantContent;
+		use crate::test_utils::MockSseClient;
+
+		// The last line has no trailing newline
+		let ndjson = concat!(
+			"{\"model\":\"llama3.2\",\"created_at\":\"2023-08-04T19:22:45.499127Z\",\"message\":{\"role\":\"assistant\",\"content\":\"Crabs 🦀\"},\"done\":false}\n",
+			"{\"model\":\"llama3.2\",\"created_at\":\"2023-08-04T19:22:45.499127Z\",\"message\":{\"role\":\"assistant\",\"content\":\" rock\"},\"done\":false}\n",
+			"{\"model\":\"llama3.2\",\"created_at\":\"2023-08-04T19:22:45.499127Z\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\",\"prompt_eval_count\":10,\"eval_count\":5}",
+		);
+		let client = Client::<MockSseClient>::builder()
+			.api_key(Nothing)
+			.http_client(MockSseClient::split_in_char(ndjson, '🦀'))
+			.build()
+			.unwrap();
+		let model = CompletionModel::new(client, "llama3.2");
+		let request = model.completion_request("Hello").build();
+		let mut stream = model.stream(request).await.unwrap();
+
+		let mut text = String::new();
+		while let Some(item) = stream.next().await {
+			if let StreamedAssistantContent::Text(delta) = item.unwrap() {
+				text += &delta.text;
+			}
+		}
+		assert_eq!(text, "Crabs 🦀 rock");
+
+		let response = stream.response.unwrap();
+		assert_eq!(response.eval_count, Some(5));
+	}
 }
@@ -1,7 +1,9 @@
+use std::sync::{Arc, OnceLock};
+
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 
 use super::client::Client;
+use crate::completion::GetTokenUsage;
 use crate::embeddings::{self, EmbeddingError};
 use crate::http_client::{self, HttpClientExt};
 use crate::providers::openai_compat::ApiResponse;
@@ -18,6 +20,18 @@ pub struct EmbeddingResponse {
 	pub prompt_eval_count: Option<u64>,
 }
 
+impl GetTokenUsage for EmbeddingResponse {
+	fn token_usage(&self) -> Option<crate::completion::Usage> {
+		let input_tokens = self.prompt_eval_count?;
+
+		let mut usage = crate::completion::Usage::new();
+		usage.input_tokens = input_tokens;
+		usage.total_tokens = input_tokens;
+
+		Some(usage)
+	}
+}
+
 impl From<ApiResponse<EmbeddingResponse>> for Result<EmbeddingResponse, EmbeddingError> {
 	fn from(value: ApiResponse<EmbeddingResponse>) -> Self {
 		match value {
@@ -27,11 +41,22 @@ impl From<ApiResponse<EmbeddingResponse>> for Result<EmbeddingResponse, Embeddin
 	}
 }
 
+/// An Ollama embedding model, using the `/api/embed` endpoint.
+///
+/// The number of dimensions given to the constructor can be `0`, in which case it is detected
+/// from the first response.
 #[derive(Clone)]
 pub struct EmbeddingModel<T = reqwest::Client> {
 	client: Client<T>,
 	pub model: String,
 	ndims: usize,
+	/// Length of the embeddings of the first response
+	detected_ndims: Arc<OnceLock<usize>>,
+	/// Whether inputs longer than the model's context are truncated instead of failing, Ollama's
+	/// default (`true`) when `None`
+	pub truncate: Option<bool>,
+	/// Number of dimensions of the embeddings, the model's default when `None`
+	pub dimensions: Option<usize>,
 }
 
 impl<T> EmbeddingModel<T> {
@@ -40,43 +65,73 @@ impl<T> EmbeddingModel<T> {
 			client,
 			model: model.into(),
 			ndims,
+			detected_ndims: Arc::default(),
+			truncate: None,
+			dimensions: None,
 		}
 	}
 
 	pub fn with_model(client: Client<T>, model: &str, ndims: usize) -> Self {
-		Self {
-			client,
-			model: model.into(),
-			ndims,
-		}
+		Self::new(client, model, ndims)
 	}
-}
 
-impl<T> embeddings::EmbeddingModel for EmbeddingModel<T>
-where
-	T: HttpClientExt + Clone + 'static,
-{
-	type Client = Client<T>;
+	/// Set whether inputs longer than the model's context are truncated. When `false`, Ollama
+	/// returns an error for these inputs instead.
+	pub fn truncate(mut self, truncate: bool) -> Self {
+		self.truncate = Some(truncate);
+		self
+	}
 
-	fn make(client: &Self::Client, model: impl Into<String>, dims: Option<usize>) -> Self {
-		Self::new(client.clone(), model, dims.unwrap())
+	/// Set the number of dimensions of the embeddings, which also becomes the model's
+	/// [ndims](embeddings::EmbeddingModel::ndims)
+	pub fn dimensions(mut self, dimensions: usize) -> Self {
+		self.dimensions = Some(dimensions);
+		self.ndims = dimensions;
+		self.detected_ndims = Arc::default();
+		self
 	}
 
-	const MAX_DOCUMENTS: usize = 1024;
-	fn ndims(&self) -> usize {
-		self.ndims
+	fn request(&self, documents: Vec<String>) -> EmbeddingRequest {
+		EmbeddingRequest {
+			model: self.model.clone(),
+			input: documents,
+			truncate: self.truncate,
+			dimensions: self.dimensions,
+		}
 	}
 
-	async fn embed_texts(
-		&self,
-		documents: impl IntoIterator<Item = String>,
-	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
-		let docs: Vec<String> = documents.into_iter().collect();
+	/// Checks that the embeddings have the expected length, which is detected from the first
+	/// response if the model was created without it.
+	fn check_ndims(&self, embeddings: &[Vec<f64>]) -> Result<(), EmbeddingError> {
+		let Some(first) = embeddings.first() else {
+			return Ok(());
+		};
 
-		let body = serde_json::to_vec(&json!({
-			"model": self.model,
-			"input": docs
-		}))?;
+		let expected = match self.ndims {
+			0 => *self.detected_ndims.get_or_init(|| first.len()),
+			ndims => ndims,
+		};
+
+		match embeddings.iter().find(|vec| vec.len() != expected) {
+			Some(vec) => Err(EmbeddingError::ResponseError(format!(
+				"Expected embeddings of {expected} dimensions, got {}",
+				vec.len()
+			))),
+			None => Ok(()),
+		}
+	}
+}
+
+impl<T> EmbeddingModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	/// Embeds the documents, returning the raw response, including the token usage.
+	pub async fn embeddings(
+		&self,
+		documents: Vec<String>,
+	) -> Result<EmbeddingResponse, EmbeddingError> {
+		let body = serde_json::to_vec(&self.request(documents))?;
 
 		let req = self
 			.client
@@ -92,15 +147,58 @@ where
 		}
 
 		let bytes: Vec<u8> = response.into_body().await?;
+		let response: EmbeddingResponse = serde_json::from_slice(&bytes)?;
+
+		tracing::debug!(target: "clankers",
+			"Ollama embedding token usage: {:?}",
+			response.prompt_eval_count
+		);
+
+		self.check_ndims(&response.embeddings)?;
+
+		Ok(response)
+	}
+}
+
+impl<T> embeddings::EmbeddingModel for EmbeddingModel<T>
+where
+	T: HttpClientExt + Clone + 'static,
+{
+	type Client = Client<T>;
 
-		let api_resp: EmbeddingResponse = serde_json::from_slice(&bytes)?;
+	fn make(client: &Self::Client, model: impl Into<String>, dims: Option<usize>) -> Self {
+		Self::new(client.clone(), model, dims.unwrap_or_default())
+	}
+
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	const MAX_DOCUMENTS: usize = 1024;
+
+	/// The number of dimensions given to the constructor, or detected from the first response.
+	/// `0` if neither is known yet.
+	fn ndims(&self) -> usize {
+		match self.ndims {
+			0 => self.detected_ndims.get().copied().unwrap_or_default(),
+			ndims => ndims,
+		}
+	}
+
+	async fn embed_texts(
+		&self,
+		documents: impl IntoIterator<Item = String>,
+	) -> Result<Vec<embeddings::Embedding>, EmbeddingError> {
+		let docs: Vec<String> = documents.into_iter().collect();
+		let response = self.embeddings(docs.clone()).await?;
 
-		if api_resp.embeddings.len() != docs.len() {
+		if response.embeddings.len() != docs.len() {
 			return Err(EmbeddingError::ResponseError(
 				"Number of returned embeddings does not match input".into(),
 			));
 		}
-		Ok(api_resp
+
+		Ok(response
 			.embeddings
 			.into_iter()
 			.zip(docs.into_iter())
@@ -108,3 +206,106 @@ where
 			.collect())
 	}
 }
+
+#[derive(Debug, Serialize)]
+pub(super) struct EmbeddingRequest {
+	model: String,
+	input: Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	truncate: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	dimensions: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::client::Nothing;
+	use crate::embeddings::EmbeddingModel as _;
+	use crate::test_utils::MockSseClient;
+
+	const RESPONSE: &str = r#"{
+		"model": "all-minilm",
+		"embeddings": [
+			[0.010071029, -0.0017594862, 0.05007221],
+			[-0.0098027075, 0.06042469, 0.025257962]
+		],
+		"total_duration": 14143917,
+		"load_duration": 1019500,
+		"prompt_eval_count": 8
+	}"#;
+
+	fn model(http_client: MockSseClient, ndims: usize) -> EmbeddingModel<MockSseClient> {
+		let client = Client::<MockSseClient>::builder()
+			.api_key(Nothing)
+			.http_client(http_client)
+			.build()
+			.unwrap();
+
+		EmbeddingModel::new(client, "all-minilm", ndims)
+	}
+
+	#[test]
+	fn test_request_serialization() {
+		let model = model(MockSseClient::default(), 0);
+		let documents = vec![
+			"Why is the sky blue?".to_string(),
+			"Why is grass green?".to_string(),
+		];
+
+		assert_eq!(
+			serde_json::to_value(model.request(documents.clone())).unwrap(),
+			json!({
+				"model": "all-minilm",
+				"input": ["Why is the sky blue?", "Why is grass green?"]
+			})
+		);
+
+		let model = model.truncate(false).dimensions(256);
+		assert_eq!(model.ndims(), 256);
+		assert_eq!(
+			serde_json::to_value(model.request(documents)).unwrap(),
+			json!({
+				"model": "all-minilm",
+				"input": ["Why is the sky blue?", "Why is grass green?"],
+				"truncate": false,
+				"dimensions": 256
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_batch_response_with_usage() {
+		let model = model(MockSseClient::default().with_json_response(RESPONSE), 0);
+		assert_eq!(model.ndims(), 0);
+
+		let documents = [
+			"Why is the sky blue?".to_string(),
+			"Why is grass green?".to_string(),
+		];
+		let response = model.embeddings(documents.to_vec()).await.unwrap();
+		let usage = response.token_usage().unwrap();
+		assert_eq!(usage.input_tokens, 8);
+		assert_eq!(usage.total_tokens, 8);
+
+		let embeddings = model.embed_texts(documents).await.unwrap();
+		assert_eq!(embeddings.len(), 2);
+		assert_eq!(embeddings[1].document, "Why is grass green?");
+		assert_eq!(embeddings[1].vec, [-0.0098027075, 0.06042469, 0.025257962]);
+		assert_eq!(model.ndims(), 3);
+	}
+
+	#[tokio::test]
+	async fn test_conflicting_ndims_is_an_error() {
+		let model = model(MockSseClient::default().with_json_response(RESPONSE), 384);
+
+		let err = model
+			.embed_texts(["Why is the sky blue?".to_string()])
+			.await
+			.unwrap_err();
+		assert!(matches!(err, EmbeddingError::ResponseError(_)), "{err:?}");
+		assert!(err.to_string().contains("384"), "{err}");
+	}
+}
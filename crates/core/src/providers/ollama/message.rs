@@ -1,6 +1,9 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::str::FromStr;
 
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -31,10 +34,22 @@ impl From<crate::completion::ToolDefinition> for ToolDefinition {
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct ToolCall {
+	/// Only sent by newer Ollama versions, see [`ToolCall::ensure_id`]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub id: Option<String>,
 	#[serde(default, rename = "type")]
 	pub r#type: ToolType,
 	pub function: Function,
 }
+
+impl ToolCall {
+	/// Returns the call id, generating a unique one if Ollama didn't send any.
+	/// Using the function name alone would collide when the same tool is called twice in one turn.
+	pub fn ensure_id(&mut self) -> &str {
+		self.id
+			.get_or_insert_with(|| format!("{}-{}", self.function.name, nanoid::nanoid!()))
+	}
+}
 #[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum ToolType {
@@ -81,6 +96,8 @@ pub enum Message {
 		#[serde(rename = "tool_name")]
 		name: String,
 		content: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		tool_call_id: Option<String>,
 	},
 }
 
@@ -114,9 +131,11 @@ impl TryFrom<crate::message::Message> for Vec<Message> {
 									.collect::<Vec<_>>()
 									.join("\n");
 
+								// `name` is resolved from the matching tool call, see `resolve_tool_names`
 								Ok::<_, crate::message::MessageError>(Message::ToolResult {
-									name: id,
+									name: id.clone(),
 									content: content_string,
+									tool_call_id: Some(id),
 								})
 							}
 							_ => unreachable!(),
@@ -131,10 +150,9 @@ impl TryFrom<crate::message::Message> for Vec<Message> {
 								crate::message::UserContent::Text(crate::message::Text {
 									text,
 								}) => texts.push(text),
-								crate::message::UserContent::Image(crate::message::Image {
-									data: DocumentSourceKind::Base64(data),
-									..
-								}) => images.push(data),
+								crate::message::UserContent::Image(image) => {
+									images.extend(image_data(image))
+								}
 								crate::message::UserContent::Document(
 									crate::message::Document {
 										data:
@@ -168,6 +186,7 @@ impl TryFrom<crate::message::Message> for Vec<Message> {
 			InternalMessage::Assistant { content, .. } => {
 				let mut thinking: Option<String> = None;
 				let mut text_content = Vec::new();
+				let mut images = Vec::new();
 				let mut tool_calls = Vec::new();
 
 				for content in content.into_iter() {
@@ -183,10 +202,11 @@ impl TryFrom<crate::message::Message> for Vec<Message> {
 						) => {
 							thinking = Some(reasoning.first().cloned().unwrap_or(String::new()));
 						}
-						crate::message::AssistantContent::Image(_) => {
-							return Err(crate::message::MessageError::ConversionError(
-								"Ollama currently doesn't support images.".into(),
-							));
+						crate::message::AssistantContent::Image(image) => {
+							images.extend(image_data(image))
+						}
+						crate::message::AssistantContent::Document(_) => {
+							tracing::warn!("Ollama doesn't support assistant documents, skipping");
 						}
 					}
 				}
@@ -196,7 +216,7 @@ impl TryFrom<crate::message::Message> for Vec<Message> {
 				Ok(vec![Message::Assistant {
 					content: text_content.join(" "),
 					thinking,
-					images: None,
+					images: (!images.is_empty()).then_some(images),
 					name: None,
 					tool_calls: tool_calls
 						.into_iter()
@@ -213,24 +233,44 @@ impl TryFrom<crate::message::Message> for Vec<Message> {
 impl From<Message> for crate::completion::Message {
 	fn from(msg: Message) -> Self {
 		match msg {
-			Message::User { content, .. } => crate::completion::Message::User {
-				content: OneOrMany::one(crate::completion::message::UserContent::Text(Text {
-					text: content,
-				})),
-			},
+			Message::User {
+				content, images, ..
+			} => {
+				let images = images.unwrap_or_default();
+				let text = (!content.is_empty() || images.is_empty())
+					.then(|| message::UserContent::text(content));
+				let images = images
+					.into_iter()
+					.map(|data| message::UserContent::image_base64(data, None, None));
+
+				crate::completion::Message::User {
+					content: OneOrMany::many(text.into_iter().chain(images))
+						.expect("There is either a text or an image"),
+				}
+			}
 			Message::Assistant {
 				content,
+				images,
 				tool_calls,
 				..
 			} => {
-				let mut assistant_contents =
-					vec![crate::completion::message::AssistantContent::Text(Text {
-						text: content,
-					})];
-				for tc in tool_calls {
+				let images = images.unwrap_or_default();
+				let mut assistant_contents = Vec::new();
+				if !content.is_empty() || images.is_empty() {
+					assistant_contents.push(crate::completion::message::AssistantContent::Text(
+						Text { text: content },
+					));
+				}
+				assistant_contents.extend(
+					images
+						.into_iter()
+						.map(|data| message::AssistantContent::image_base64(data, None, None)),
+				);
+				for mut tc in tool_calls {
+					let id = tc.ensure_id().to_owned();
 					assistant_contents.push(
 						crate::completion::message::AssistantContent::tool_call(
-							tc.function.name.clone(),
+							id,
 							tc.function.name,
 							tc.function.arguments,
 						),
@@ -247,9 +287,13 @@ impl From<Message> for crate::completion::Message {
 					text: content,
 				})),
 			},
-			Message::ToolResult { name, content } => crate::completion::Message::User {
+			Message::ToolResult {
+				name,
+				content,
+				tool_call_id,
+			} => crate::completion::Message::User {
 				content: OneOrMany::one(message::UserContent::tool_result(
-					name,
+					tool_call_id.unwrap_or(name),
 					OneOrMany::one(message::ToolResultContent::text(content)),
 				)),
 			},
@@ -257,6 +301,18 @@ impl From<Message> for crate::completion::Message {
 	}
 }
 
+/// Ollama takes images as base64 strings, images given by URL are skipped.
+fn image_data(image: message::Image) -> Option<String> {
+	match image.data {
+		DocumentSourceKind::Base64(data) => Some(data),
+		DocumentSourceKind::Raw(bytes) => Some(BASE64_STANDARD.encode(bytes)),
+		_ => {
+			tracing::warn!("Ollama only supports base64 or raw images, skipping");
+			None
+		}
+	}
+}
+
 impl Message {
 	/// Constructs a system message.
 	pub fn system(content: &str) -> Self {
@@ -268,9 +324,37 @@ impl Message {
 	}
 }
 
+/// Ollama matches tool results to calls by function name, so replace the tool call id
+/// carried over from the chat history with the name of the call it answers.
+pub(crate) fn resolve_tool_names(messages: &mut [Message]) {
+	let mut names = HashMap::new();
+	for message in messages.iter_mut() {
+		match message {
+			Message::Assistant { tool_calls, .. } => {
+				for tc in tool_calls.iter() {
+					if let Some(id) = &tc.id {
+						names.insert(id.clone(), tc.function.name.clone());
+					}
+				}
+			}
+			Message::ToolResult {
+				name,
+				tool_call_id: Some(id),
+				..
+			} => {
+				if let Some(function_name) = names.get(id) {
+					*name = function_name.clone();
+				}
+			}
+			_ => {}
+		}
+	}
+}
+
 impl From<crate::message::ToolCall> for ToolCall {
 	fn from(tool_call: crate::message::ToolCall) -> Self {
 		Self {
+			id: Some(tool_call.id),
 			r#type: ToolType::Function,
 			function: Function {
 				name: tool_call.function.name,
@@ -451,4 +535,88 @@ mod tests {
 			panic!("Expected Assistant message with thinking");
 		}
 	}
+
+	// A llava-style conversation: a raw image from the user, a generated image from the assistant
+	#[test]
+	fn test_image_round_trip() {
+		let png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+		let encoded = BASE64_STANDARD.encode(&png);
+
+		let user = crate::message::Message::User {
+			content: OneOrMany::many(vec![
+				message::UserContent::text("What is in this picture?"),
+				message::UserContent::image_raw(png, None, None),
+			])
+			.unwrap(),
+		};
+		let assistant = crate::message::Message::Assistant {
+			id: None,
+			content: OneOrMany::many(vec![
+				message::AssistantContent::text("A cat, here is a sketch of it:"),
+				message::AssistantContent::image_base64("c2tldGNo", None, None),
+			])
+			.unwrap(),
+		};
+
+		let provider_msgs: Vec<Message> = [user, assistant]
+			.into_iter()
+			.flat_map(|msg| Vec::<Message>::try_from(msg).unwrap())
+			.collect();
+		let provider_json = serde_json::to_value(&provider_msgs).unwrap();
+		assert_eq!(
+			provider_json,
+			json!([
+				{ "role": "user", "content": "What is in this picture?", "images": [encoded] },
+				{ "role": "assistant", "content": "A cat, here is a sketch of it:", "images": ["c2tldGNo"], "tool_calls": [] },
+			])
+		);
+
+		let history: Vec<crate::completion::Message> =
+			provider_msgs.into_iter().map(Into::into).collect();
+		let crate::completion::Message::User { content } = &history[0] else {
+			panic!("Expected a user message");
+		};
+		assert_eq!(
+			content.iter().cloned().collect::<Vec<_>>(),
+			vec![
+				message::UserContent::text("What is in this picture?"),
+				message::UserContent::image_base64(encoded, None, None),
+			]
+		);
+		let crate::completion::Message::Assistant { content, .. } = &history[1] else {
+			panic!("Expected an assistant message");
+		};
+		assert_eq!(
+			content.iter().cloned().collect::<Vec<_>>(),
+			vec![
+				message::AssistantContent::text("A cat, here is a sketch of it:"),
+				message::AssistantContent::image_base64("c2tldGNo", None, None),
+			]
+		);
+
+		// Converting the history again gives the same request
+		let again: Vec<Message> = history
+			.into_iter()
+			.flat_map(|msg| Vec::<Message>::try_from(msg).unwrap())
+			.collect();
+		assert_eq!(serde_json::to_value(&again).unwrap(), provider_json);
+	}
+
+	#[test]
+	fn test_image_only_message() {
+		let msg: Message = serde_json::from_value(json!({
+			"role": "user",
+			"content": "",
+			"images": ["aW1hZ2U="]
+		}))
+		.unwrap();
+
+		let crate::completion::Message::User { content } = msg.into() else {
+			panic!("Expected a user message");
+		};
+		assert_eq!(
+			content,
+			OneOrMany::one(message::UserContent::image_base64("aW1hZ2U=", None, None))
+		);
+	}
 }
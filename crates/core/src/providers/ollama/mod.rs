@@ -10,6 +10,10 @@
 //! // In the case of ollama, no API key is necessary, so we use the `Nothing` struct
 //! let client: ollama::Client = ollama::Client::new(Nothing).unwrap();
 //!
+//! // Or connect to the server set in `OLLAMA_HOST`, e.g. `0.0.0.0:11434` or
+//! // `unix:///var/run/ollama.sock`
+//! let client = ollama::Client::from_env();
+//!
 //! // Create an agent with a preamble
 //! let comedian_agent = client
 //!     .agent("qwen2.5:14b")
@@ -20,8 +24,9 @@
 //! let response = comedian_agent.prompt("Entertain me!").await?;
 //! println!("{response}");
 //!
-//! // Create an embedding model using the "all-minilm" model
-//! let emb_model = client.embedding_model("all-minilm", 384);
+//! // Create an embedding model using the "all-minilm" model, its dimensions are detected from
+//! // the first response
+//! let emb_model = client.embedding_model("all-minilm");
 //! let embeddings = emb_model.embed_texts(vec![
 //!     "Why is the sky blue?".to_owned(),
 //!     "Why is the grass green?".to_owned()
@@ -30,17 +35,22 @@
 //!
 //! // Create an extractor if needed
 //! let extractor = client.extractor::<serde_json::Value>("llama3.2").build();
+//!
+//! // List the models available locally
+//! let models = client.list_models().await?;
 //! ```
 
 pub mod client;
 pub mod completion;
 pub mod embedding;
 pub mod message;
+pub mod models;
 
-pub use client::{Client, ClientBuilder};
+pub use client::{Client, ClientBuilder, OllamaHost, OllamaHostError};
 pub use completion::{CompletionModel, CompletionResponse, StreamingCompletionResponse};
 pub use embedding::{EmbeddingModel, EmbeddingResponse};
 pub use message::*;
+pub use models::{ModelDetails, ModelEntry, ModelInfo, OllamaError, PullProgress};
 
 pub const ALL_MINILM: &str = "all-minilm";
 pub const NOMIC_EMBED_TEXT: &str = "nomic-embed-text";
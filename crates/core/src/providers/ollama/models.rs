@@ -0,0 +1,354 @@
+//! Ollama model management: listing, inspecting and pulling local models.
+//!
+//! # Example
+//! ```rust,ignore
+//! use futures::StreamExt;
+//! use clankers::client::Nothing;
+//! use clankers::providers::ollama;
+//!
+//! let client: ollama::Client = ollama::Client::new(Nothing).unwrap();
+//!
+//! // Make sure the model is present before starting an agent
+//! let models = client.list_models().await?;
+//! if !models.iter().any(|model| model.name == "llama3.2:latest") {
+//!     let mut progress = client.pull_model("llama3.2").await?;
+//!     while let Some(update) = progress.next().await {
+//!         let update = update?;
+//!         println!("{} ({:?}/{:?})", update.status, update.completed, update.total);
+//!     }
+//! }
+//! ```
+
+use async_stream::try_stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use super::client::Client;
+use crate::http_client::{self, HttpClientExt};
+use crate::wasm_compat::WasmCompatSend;
+
+#[derive(Debug, Error)]
+pub enum OllamaError {
+	#[error("Request error: {0}")]
+	RequestError(#[from] http_client::Error),
+	#[error("JSON error: {0}")]
+	JsonError(#[from] serde_json::Error),
+	#[error("Provider error: {0}")]
+	ProviderError(String),
+}
+
+/// Response of `/api/tags`
+#[derive(Debug, Deserialize, Serialize)]
+struct ListModelsResponse {
+	models: Vec<ModelEntry>,
+}
+
+/// A model available locally, as listed by `/api/tags`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ModelEntry {
+	pub name: String,
+	pub model: String,
+	pub modified_at: String,
+	/// Size of the model on disk, in bytes
+	pub size: u64,
+	pub digest: String,
+	pub details: ModelDetails,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ModelDetails {
+	pub parent_model: String,
+	pub format: String,
+	pub family: String,
+	pub families: Option<Vec<String>>,
+	pub parameter_size: String,
+	pub quantization_level: String,
+}
+
+/// Information about a model, as returned by `/api/show`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ModelInfo {
+	pub modelfile: String,
+	/// Model parameters, one `name value` pair per line
+	pub parameters: Option<String>,
+	pub template: Option<String>,
+	pub license: Option<String>,
+	pub details: ModelDetails,
+	/// Architecture specific information, e.g. `llama.context_length`
+	pub model_info: serde_json::Map<String, serde_json::Value>,
+	pub capabilities: Vec<String>,
+	pub modified_at: Option<String>,
+}
+
+/// A progress update of `/api/pull`.
+/// `completed` and `total` are given in bytes while downloading a layer.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PullProgress {
+	pub status: String,
+	#[serde(default)]
+	pub digest: Option<String>,
+	#[serde(default)]
+	pub total: Option<u64>,
+	#[serde(default)]
+	pub completed: Option<u64>,
+}
+
+/// A line of the `/api/pull` stream, either a progress update or an error.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PullEvent {
+	Error { error: String },
+	Progress(PullProgress),
+}
+
+impl<H> Client<H>
+where
+	H: HttpClientExt + Clone + 'static,
+{
+	/// List the models available locally (`/api/tags`).
+	pub async fn list_models(&self) -> Result<Vec<ModelEntry>, OllamaError> {
+		let req = self
+			.get("api/tags")?
+			.body(http_client::NoBody)
+			.map_err(http_client::Error::from)?;
+
+		let response: ListModelsResponse = self.send_and_parse(req).await?;
+
+		Ok(response.models)
+	}
+
+	/// Show the parameters, template and details of a model (`/api/show`).
+	pub async fn show_model(&self, name: &str) -> Result<ModelInfo, OllamaError> {
+		let body = serde_json::to_vec(&json!({ "model": name }))?;
+
+		let req = self
+			.post("api/show")?
+			.body(body)
+			.map_err(http_client::Error::from)?;
+
+		self.send_and_parse(req).await
+	}
+
+	/// Pull a model from the Ollama library (`/api/pull`), returning a stream of progress updates.
+	/// The stream ends once the model is fully downloaded, its last update having a `success` status.
+	pub async fn pull_model(
+		&self,
+		name: &str,
+	) -> Result<impl Stream<Item = Result<PullProgress, OllamaError>> + WasmCompatSend, OllamaError>
+	{
+		let body = serde_json::to_vec(&json!({ "model": name, "stream": true }))?;
+
+		let req = self
+			.post("api/pull")?
+			.body(body)
+			.map_err(http_client::Error::from)?;
+
+		let response = self.send_streaming(req).await?;
+		let status = response.status();
+
+		if !status.is_success() {
+			return Err(OllamaError::ProviderError(format!(
+				"Got error status code trying to pull model {name}: {status}"
+			)));
+		}
+
+		Ok(pull_progress(response.into_body()))
+	}
+
+	async fn send_and_parse<B, T>(&self, req: http_client::Request<B>) -> Result<T, OllamaError>
+	where
+		B: Into<Bytes> + WasmCompatSend,
+		T: for<'de> Deserialize<'de>,
+	{
+		let response = self.send::<_, Bytes>(req).await?;
+		let status = response.status();
+		let body = response.into_body().await?;
+
+		if !status.is_success() {
+			return Err(OllamaError::ProviderError(
+				String::from_utf8_lossy(&body).to_string(),
+			));
+		}
+
+		Ok(serde_json::from_slice(&body)?)
+	}
+}
+
+/// Parse the NDJSON body of `/api/pull` into progress updates.
+/// Lines may be split across chunks, so incomplete lines are buffered until their newline arrives.
+fn pull_progress<S>(
+	mut byte_stream: S,
+) -> impl Stream<Item = Result<PullProgress, OllamaError>> + WasmCompatSend
+where
+	S: Stream<Item = http_client::Result<Bytes>> + Unpin + WasmCompatSend,
+{
+	try_stream! {
+		let mut buffer = Vec::new();
+
+		while let Some(chunk) = byte_stream.next().await {
+			buffer.extend_from_slice(&chunk?);
+
+			while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+				let line = buffer.drain(..=end).collect::<Vec<_>>();
+				if let Some(progress) = parse_pull_line(&line)? {
+					yield progress;
+				}
+			}
+		}
+
+		if let Some(progress) = parse_pull_line(&buffer)? {
+			yield progress;
+		}
+	}
+}
+
+fn parse_pull_line(line: &[u8]) -> Result<Option<PullProgress>, OllamaError> {
+	if line.trim_ascii().is_empty() {
+		return Ok(None);
+	}
+
+	tracing::debug!(target: "clankers", "Received NDJSON line from Ollama: {}", String::from_utf8_lossy(line));
+
+	match serde_json::from_slice(line)? {
+		PullEvent::Progress(progress) => Ok(Some(progress)),
+		PullEvent::Error { error } => Err(OllamaError::ProviderError(error)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::StreamExt;
+	use serde_json::json;
+
+	use super::*;
+	use crate::client::Nothing;
+	use crate::test_utils::MockSseClient;
+
+	#[test]
+	fn test_deserialize_list_models() {
+		let response = json!({
+			"models": [
+				{
+					"name": "deepseek-r1:latest",
+					"model": "deepseek-r1:latest",
+					"modified_at": "2025-05-10T08:06:48.639712648-07:00",
+					"size": 4683075271u64,
+					"digest": "0a8c266910232fd3291e71e5ba1e058cc5af9d411192cf88b6d30e92b6e73163",
+					"details": {
+						"parent_model": "",
+						"format": "gguf",
+						"family": "qwen2",
+						"families": ["qwen2"],
+						"parameter_size": "7.6B",
+						"quantization_level": "Q4_K_M"
+					}
+				}
+			]
+		});
+
+		let response: ListModelsResponse = serde_json::from_value(response).unwrap();
+
+		assert_eq!(response.models.len(), 1);
+		let model = &response.models[0];
+		assert_eq!(model.name, "deepseek-r1:latest");
+		assert_eq!(model.size, 4683075271);
+		assert_eq!(model.details.family, "qwen2");
+		assert_eq!(model.details.families, Some(vec!["qwen2".to_string()]));
+		assert_eq!(model.details.parameter_size, "7.6B");
+		assert_eq!(model.details.quantization_level, "Q4_K_M");
+	}
+
+	#[test]
+	fn test_deserialize_show_model() {
+		let response = json!({
+			"modelfile": "# Modelfile generated by \"ollama show\"\nFROM llama3.2:latest\n",
+			"parameters": "num_keep                       24\nstop                           \"<|start_header_id|>\"",
+			"template": "{{ if .System }}<|start_header_id|>system<|end_header_id|>\n\n{{ .System }}<|eot_id|>{{ end }}",
+			"details": {
+				"parent_model": "",
+				"format": "gguf",
+				"family": "llama",
+				"families": ["llama"],
+				"parameter_size": "3.2B",
+				"quantization_level": "Q4_K_M"
+			},
+			"model_info": {
+				"general.architecture": "llama",
+				"llama.context_length": 131072
+			},
+			"capabilities": ["completion", "tools"],
+			"modified_at": "2025-04-22T18:50:55.512Z"
+		});
+
+		let info: ModelInfo = serde_json::from_value(response).unwrap();
+
+		assert!(info.modelfile.contains("FROM llama3.2:latest"));
+		assert!(info.parameters.unwrap().starts_with("num_keep"));
+		assert!(info.template.unwrap().contains("{{ .System }}"));
+		assert_eq!(info.license, None);
+		assert_eq!(info.details.family, "llama");
+		assert_eq!(info.model_info["llama.context_length"], 131072);
+		assert_eq!(info.capabilities, vec!["completion", "tools"]);
+	}
+
+	#[tokio::test]
+	async fn test_pull_progress_stream() {
+		let chunks = vec![
+			Ok(Bytes::from(
+				"{\"status\":\"pulling manifest\"}\n{\"status\":\"pulling 6a0746a1ec1a\",\"digest\":\"sha256:6a0746a1ec1a\",\"total\":2019377376,",
+			)),
+			Ok(Bytes::from(
+				"\"completed\":241970}\n{\"status\":\"verifying sha256 digest\"}\n",
+			)),
+			Ok(Bytes::from("{\"status\":\"success\"}")),
+		];
+
+		let progress = pull_progress(futures::stream::iter(chunks))
+			.collect::<Vec<_>>()
+			.await
+			.into_iter()
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+
+		assert_eq!(progress.len(), 4);
+		assert_eq!(progress[0].status, "pulling manifest");
+		assert_eq!(progress[0].total, None);
+		assert_eq!(
+			progress[1],
+			PullProgress {
+				status: "pulling 6a0746a1ec1a".to_string(),
+				digest: Some("sha256:6a0746a1ec1a".to_string()),
+				total: Some(2019377376),
+				completed: Some(241970),
+			}
+		);
+		assert_eq!(progress[3].status, "success");
+	}
+
+	#[tokio::test]
+	async fn test_pull_model_surfaces_errors() {
+		let client = Client::<MockSseClient>::builder()
+			.http_client(MockSseClient::new(
+				"{\"status\":\"pulling manifest\"}\n{\"error\":\"pull model manifest: file does not exist\"}\n",
+			))
+			.api_key(Nothing)
+			.build()
+			.unwrap();
+
+		let mut stream = Box::pin(client.pull_model("missing-model").await.unwrap());
+
+		assert_eq!(
+			stream.next().await.unwrap().unwrap().status,
+			"pulling manifest"
+		);
+		let error = stream.next().await.unwrap().unwrap_err();
+		assert!(
+			matches!(error, OllamaError::ProviderError(message) if message.contains("file does not exist"))
+		);
+	}
+}
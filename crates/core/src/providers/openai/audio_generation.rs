@@ -1,19 +1,44 @@
-use bytes::{Buf, Bytes};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::audio_generation::{
 	self, AudioGenerationError, AudioGenerationRequest, AudioGenerationResponse,
 };
 use crate::http_client::{self, HttpClientExt};
+use crate::json_utils::merge_inplace;
 use crate::providers::openai::Client;
 
 pub const TTS_1: &str = "tts-1";
 pub const TTS_1_HD: &str = "tts-1-hd";
+pub const GPT_4O_MINI_TTS: &str = "gpt-4o-mini-tts";
 
+/// Encoding of the generated audio, `mp3` by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+	Mp3,
+	Opus,
+	Aac,
+	Flac,
+	Wav,
+	/// Raw 24kHz 16-bit signed little-endian samples, without a header
+	Pcm,
+}
+
+/// An OpenAI text to speech model.
+///
+/// Options set on the model apply to every request, and can be overridden per request with
+/// [crate::audio_generation::AudioGenerationRequestBuilder::additional_params], e.g.
+/// `json!({ "instructions": "Speak in a cheerful tone." })`.
 #[derive(Clone)]
 pub struct AudioGenerationModel<T = reqwest::Client> {
 	client: Client<T>,
 	pub model: String,
+	pub response_format: Option<AudioFormat>,
+	/// Instructions on how to speak, e.g. the tone or accent
+	pub instructions: Option<String>,
 }
 
 impl<T> AudioGenerationModel<T> {
@@ -21,10 +46,50 @@ impl<T> AudioGenerationModel<T> {
 		Self {
 			client,
 			model: model.into(),
+			response_format: None,
+			instructions: None,
+		}
+	}
+
+	pub fn with_response_format(mut self, response_format: AudioFormat) -> Self {
+		self.response_format = Some(response_format);
+		self
+	}
+
+	/// Only supported by `gpt-4o-mini-tts`.
+	pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+		self.instructions = Some(instructions.into());
+		self
+	}
+
+	fn request_body(&self, request: AudioGenerationRequest) -> serde_json::Value {
+		let mut body = json!({
+			"model": self.model,
+			"input": request.text,
+			"voice": request.voice,
+			"speed": request.speed,
+		});
+
+		if let Some(response_format) = self.response_format {
+			merge_inplace(&mut body, json!({ "response_format": response_format }));
+		}
+
+		if let Some(instructions) = &self.instructions {
+			merge_inplace(&mut body, json!({ "instructions": instructions }));
+		}
+
+		if let Some(params) = request.additional_params {
+			merge_inplace(&mut body, params);
 		}
+
+		body
 	}
 }
 
+fn provider_error(status: http::StatusCode, body: &[u8]) -> AudioGenerationError {
+	AudioGenerationError::ProviderError(format!("{status}: {}", String::from_utf8_lossy(body)))
+}
+
 impl<T> audio_generation::AudioGenerationModel for AudioGenerationModel<T>
 where
 	T: HttpClientExt + Clone + std::fmt::Debug + Default + 'static,
@@ -41,12 +106,7 @@ where
 		&self,
 		request: AudioGenerationRequest,
 	) -> Result<AudioGenerationResponse<Self::Response>, AudioGenerationError> {
-		let body = serde_json::to_vec(&json!({
-			"model": self.model,
-			"input": request.text,
-			"voice": request.voice,
-			"speed": request.speed,
-		}))?;
+		let body = serde_json::to_vec(&self.request_body(request))?;
 
 		let req = self
 			.client
@@ -55,26 +115,168 @@ where
 			.map_err(http_client::Error::from)?;
 
 		let response = self.client.send(req).await?;
+		let status = response.status();
+		let bytes: Bytes = response.into_body().await?;
 
-		if !response.status().is_success() {
-			let status = response.status();
-			let mut bytes: Bytes = response.into_body().await?;
-			let mut as_slice = Vec::new();
-			bytes.copy_to_slice(&mut as_slice);
-
-			let text: String = String::from_utf8_lossy(&as_slice).into();
-
-			return Err(AudioGenerationError::ProviderError(format!(
-				"{}: {}",
-				status, text
-			)));
+		if !status.is_success() {
+			return Err(provider_error(status, &bytes));
 		}
 
-		let bytes: Bytes = response.into_body().await?;
-
 		Ok(AudioGenerationResponse {
 			audio: bytes.to_vec(),
 			response: bytes,
 		})
 	}
+
+	/// The audio is sent with chunked transfer encoding, each chunk is yielded as soon as it is
+	/// received.
+	fn stream_audio_generation(
+		&self,
+		request: AudioGenerationRequest,
+	) -> impl Stream<Item = Result<Bytes, AudioGenerationError>> + Send {
+		let request = serde_json::to_vec(&self.request_body(request))
+			.map_err(AudioGenerationError::from)
+			.and_then(|body| {
+				self.client
+					.post("/audio/speech")?
+					.body(body)
+					.map_err(|e| AudioGenerationError::HttpError(e.into()))
+			});
+		let client = self.client.clone();
+
+		async_stream::stream! {
+			let req = match request {
+				Ok(req) => req,
+				Err(error) => {
+					yield Err(error);
+					return;
+				}
+			};
+
+			let response = match client.send_streaming(req).await {
+				Ok(response) => response,
+				Err(error) => {
+					yield Err(error.into());
+					return;
+				}
+			};
+			let status = response.status();
+			let mut body = response.into_body();
+
+			if !status.is_success() {
+				let mut text = Vec::new();
+				while let Some(Ok(chunk)) = body.next().await {
+					text.extend_from_slice(&chunk);
+				}
+				yield Err(provider_error(status, &text));
+				return;
+			}
+
+			while let Some(chunk) = body.next().await {
+				match chunk {
+					Ok(chunk) if chunk.is_empty() => {}
+					Ok(chunk) => yield Ok(chunk),
+					Err(error) => {
+						yield Err(error.into());
+						return;
+					}
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::audio_generation::AudioGenerationModel as _;
+	use crate::test_utils::MockSseClient;
+
+	fn model(name: &str) -> AudioGenerationModel {
+		AudioGenerationModel::new(Client::new("test-key").unwrap(), name)
+	}
+
+	#[test]
+	fn test_request_serialization() {
+		let model = model(GPT_4O_MINI_TTS)
+			.with_response_format(AudioFormat::Opus)
+			.with_instructions("Speak in a cheerful and positive tone.");
+		let request = model
+			.audio_generation_request()
+			.text("Today is a wonderful day to build something people love!")
+			.voice("coral")
+			.speed(1.25)
+			.build();
+
+		assert_eq!(
+			model.request_body(request),
+			json!({
+				"model": "gpt-4o-mini-tts",
+				"input": "Today is a wonderful day to build something people love!",
+				"voice": "coral",
+				"speed": 1.25,
+				"response_format": "opus",
+				"instructions": "Speak in a cheerful and positive tone.",
+			})
+		);
+	}
+
+	#[test]
+	fn test_request_options_override_model_options() {
+		let model = model(GPT_4O_MINI_TTS).with_instructions("Speak slowly.");
+		let request = model
+			.audio_generation_request()
+			.text("Hello")
+			.voice("alloy")
+			.additional_params(json!({ "instructions": "Whisper.", "response_format": "wav" }))
+			.build();
+
+		let body = model.request_body(request);
+
+		assert_eq!(body["instructions"], "Whisper.");
+		assert_eq!(body["response_format"], "wav");
+		assert_eq!(body["speed"], 1.0);
+	}
+
+	#[test]
+	fn test_tts_1_request_omits_unset_options() {
+		let model = model(TTS_1);
+		let request = model
+			.audio_generation_request()
+			.text("Hello")
+			.voice("alloy")
+			.build();
+
+		let body = model.request_body(request);
+
+		assert!(body.get("response_format").is_none());
+		assert!(body.get("instructions").is_none());
+	}
+
+	#[tokio::test]
+	async fn test_stream_yields_chunks_as_received() {
+		let chunks: [&[u8]; 3] = [b"ID3\x04\x00", b"\xff\xfb\x90\x64", b"\xff\xfb\x90\x44"];
+		let http_client = MockSseClient::chunked(chunks);
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+		let model = AudioGenerationModel::new(client, GPT_4O_MINI_TTS);
+
+		let received = model
+			.audio_generation_request()
+			.text("Hello")
+			.voice("alloy")
+			.stream()
+			.map(|chunk| chunk.unwrap())
+			.collect::<Vec<_>>()
+			.await;
+
+		assert_eq!(received, chunks.map(Bytes::from_static));
+		assert_eq!(
+			http_client.request_uris(),
+			vec!["https://api.openai.com/v1/audio/speech"]
+		);
+	}
 }
@@ -3,8 +3,10 @@ use std::fmt::Debug;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use super::moderation::ModerationModel;
 use crate::client::{
-	self, BearerAuth, Capabilities, Capable, DebugExt, Provider, ProviderBuilder, ProviderClient,
+	self, BearerAuth, Capabilities, Capable, ClientError, DebugExt, Provider, ProviderBuilder,
+	ProviderClient,
 };
 use crate::extractor::ExtractorBuilder;
 use crate::http_client::{self, HttpClientExt};
@@ -126,6 +128,11 @@ where
 	pub fn completions_api(self) -> CompletionsClient<H> {
 		self.with_ext(OpenAICompletionsExt)
 	}
+
+	/// Create a moderation model, e.g. [super::moderation::OMNI_MODERATION_LATEST].
+	pub fn moderation_model(&self, model: impl Into<String>) -> ModerationModel<H> {
+		ModerationModel::new(self.clone(), model)
+	}
 }
 
 impl<H> CompletionsClient<H>
@@ -155,6 +162,11 @@ where
 	pub fn responses_api(self) -> Client<H> {
 		self.with_ext(OpenAIResponsesExt)
 	}
+
+	/// Create a moderation model, e.g. [super::moderation::OMNI_MODERATION_LATEST].
+	pub fn moderation_model(&self, model: impl Into<String>) -> ModerationModel<H> {
+		ModerationModel::new(self.clone().responses_api(), model)
+	}
 }
 
 impl ProviderClient for Client {
@@ -163,8 +175,12 @@ impl ProviderClient for Client {
 	/// Create a new OpenAI Responses API client from the `OPENAI_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
 		let base_url: Option<String> = std::env::var("OPENAI_BASE_URL").ok();
-		let api_key = std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
+		let api_key = client::env_var("OPENAI_API_KEY")?;
 
 		let mut builder = Client::builder().api_key(&api_key);
 
@@ -172,7 +188,7 @@ impl ProviderClient for Client {
 			builder = builder.base_url(&base);
 		}
 
-		builder.build().unwrap()
+		Ok(builder.build()?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
@@ -186,8 +202,12 @@ impl ProviderClient for CompletionsClient {
 	/// Create a new OpenAI Completions API client from the `OPENAI_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
 		let base_url: Option<String> = std::env::var("OPENAI_BASE_URL").ok();
-		let api_key = std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
+		let api_key = client::env_var("OPENAI_API_KEY")?;
 
 		let mut builder = CompletionsClient::builder().api_key(&api_key);
 
@@ -195,7 +215,7 @@ impl ProviderClient for CompletionsClient {
 			builder = builder.base_url(&base);
 		}
 
-		builder.build().unwrap()
+		Ok(builder.build()?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
@@ -210,10 +230,13 @@ pub use crate::providers::openai_compat::FlatApiError as ApiErrorResponse;
 mod tests {
 	use serde_path_to_error::deserialize;
 
+	use crate::client::{ClientError, ProviderClient};
 	use crate::message::ImageDetail;
+	use crate::providers::openai::client::{Client, CompletionsClient};
 	use crate::providers::openai::completion::types::{
 		AssistantContent, Function, ImageUrl, Message, ToolCall, ToolType, UserContent,
 	};
+	use crate::test_utils::with_env;
 	use crate::{OneOrMany, message};
 
 	#[test]
@@ -501,4 +524,25 @@ mod tests {
 		assert_eq!(original_user_message[0], user_message);
 		assert_eq!(original_assistant_message[0], assistant_message);
 	}
+
+	#[test]
+	fn test_try_from_env_names_missing_var() {
+		let vars = [("OPENAI_API_KEY", None), ("OPENAI_BASE_URL", None)];
+		let error = with_env(&vars, Client::try_from_env).err().unwrap();
+		assert!(
+			matches!(&error, ClientError::MissingEnvVar(names) if names == &["OPENAI_API_KEY"]),
+			"{error:?}"
+		);
+		assert_eq!(
+			error.to_string(),
+			"Environment variables not set: `OPENAI_API_KEY`"
+		);
+
+		let vars = [
+			("OPENAI_API_KEY", Some("test-key")),
+			("OPENAI_BASE_URL", Some("https://example.com/v1")),
+		];
+		let client = with_env(&vars, CompletionsClient::try_from_env).unwrap();
+		assert_eq!(client.base_url(), "https://example.com/v1");
+	}
 }
@@ -2,6 +2,7 @@ use tracing::{Instrument, Level, enabled, info_span};
 
 use super::CompletionsClient as Client;
 use super::client::ApiResponse;
+use super::error::parse_api_error;
 use crate::completion;
 use crate::completion::{CompletionError, CompletionRequest as CoreCompletionRequest};
 use crate::http_client::{self, HttpClientExt};
@@ -20,6 +21,7 @@ pub struct CompletionModel<T = reqwest::Client> {
 	pub model: String,
 	pub strict_tools: bool,
 	pub tool_result_array_content: bool,
+	pub prediction: Option<Prediction>,
 }
 
 impl<T> CompletionModel<T>
@@ -32,6 +34,7 @@ where
 			model: model.into(),
 			strict_tools: false,
 			tool_result_array_content: false,
+			prediction: None,
 		}
 	}
 
@@ -41,6 +44,7 @@ where
 			model: model.into(),
 			strict_tools: false,
 			tool_result_array_content: false,
+			prediction: None,
 		}
 	}
 
@@ -48,7 +52,8 @@ where
 	///
 	/// When enabled, tool schemas are automatically sanitized to meet OpenAI's strict mode requirements:
 	/// - `additionalProperties: false` is added to all objects
-	/// - All properties are marked as required
+	/// - All properties are marked as required, optional ones becoming nullable instead
+	/// - `oneOf` is rewritten as `anyOf`
 	/// - `strict: true` is set on each function definition
 	///
 	/// This allows OpenAI to guarantee that the model's tool calls will match the schema exactly.
@@ -57,10 +62,19 @@ where
 		self
 	}
 
+	/// Sends tool results as arrays of content parts instead of strings, which also allows
+	/// tool results to contain images.
 	pub fn with_tool_result_array_content(mut self) -> Self {
 		self.tool_result_array_content = true;
 		self
 	}
+
+	/// Sends a [predicted output](Prediction) with every request, from a text or a list of
+	/// text parts.
+	pub fn with_prediction(mut self, prediction: impl Into<Prediction>) -> Self {
+		self.prediction = Some(prediction.into());
+		self
+	}
 }
 
 impl CompletionModel<reqwest::Client> {
@@ -88,10 +102,27 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	fn token_counter(&self) -> std::sync::Arc<dyn completion::TokenCounter> {
+		super::token_counter(&self.model)
+	}
+
+	fn validate_request(
+		&self,
+		request: &CoreCompletionRequest,
+		report: &mut completion::ValidationReport,
+	) {
+		super::validate_request(request, report);
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CoreCompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -104,6 +135,10 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -114,7 +149,9 @@ where
 			request: completion_request,
 			strict_tools: self.strict_tools,
 			tool_result_array_content: self.tool_result_array_content,
+			prediction: self.prediction.clone(),
 		})?;
+		span.record_input_messages(&request.messages);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(
@@ -133,7 +170,12 @@ where
 			.map_err(|e| CompletionError::HttpError(e.into()))?;
 
 		async move {
-			let response = self.client.send(req).await?;
+			let response = self
+				.client
+				.send(req)
+				.await
+				.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			if response.status().is_success() {
 				let text = http_client::text(response).await?;
@@ -143,6 +185,13 @@ where
 						let span = tracing::Span::current();
 						span.record_response_metadata(&response);
 						span.record_token_usage(&response.usage);
+						span.record_cost(
+							self.client.pricing(),
+							"openai",
+							&self.model,
+							&response.usage,
+						);
+						span.record_output_messages(&response.choices);
 
 						if enabled!(Level::TRACE) {
 							tracing::trace!(
@@ -152,13 +201,18 @@ where
 							);
 						}
 
-						response.try_into()
+						completion::CompletionResponse::try_from(response)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
 				}
 			} else {
+				let status = response.status();
+				let headers = response.headers().clone();
 				let text = http_client::text(response).await?;
-				Err(CompletionError::ProviderError(text))
+				Err(CompletionError::ApiError(
+					parse_api_error(status, text).with_retry_after(&headers),
+				))
 			}
 		}
 		.instrument(span)
@@ -172,6 +226,70 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let request = self.client.apply_default_params(request);
 		Self::stream(self, request).await
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use crate::client::CompletionClient;
+	use crate::completion::CompletionModel as _;
+	use crate::providers::openai;
+	use crate::test_utils::MockSseClient;
+
+	const RESPONSE: &str = r#"{
+		"id": "chatcmpl-1",
+		"object": "chat.completion",
+		"created": 1741569952,
+		"model": "gpt-4o-2024-08-06",
+		"choices": [{
+			"index": 0,
+			"message": { "role": "assistant", "content": "Hello!" },
+			"finish_reason": "stop"
+		}],
+		"usage": { "prompt_tokens": 8, "completion_tokens": 2, "total_tokens": 10 }
+	}"#;
+
+	#[tokio::test]
+	async fn test_response_metadata() {
+		let http_client = MockSseClient::default()
+			.with_json_response(RESPONSE)
+			.with_response_header("x-request-id", "req_9d3a61c3b7f54d0a")
+			.with_response_header("openai-processing-ms", "231");
+
+		let model = openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap()
+			.completion_model("gpt-4o")
+			.completions_api();
+		let response = model
+			.completion(model.completion_request("Hello").build())
+			.await
+			.unwrap();
+
+		let metadata = response.response_metadata.unwrap();
+		assert_eq!(metadata.request_id.as_deref(), Some("req_9d3a61c3b7f54d0a"));
+		assert!(metadata.latency.is_some());
+		// Headers are only copied when enabled
+		assert!(metadata.headers.is_empty());
+
+		let model = openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.capture_response_headers(true)
+			.build()
+			.unwrap()
+			.completion_model("gpt-4o")
+			.completions_api();
+		let response = model
+			.completion(model.completion_request("Hello").build())
+			.await
+			.unwrap();
+
+		let metadata = response.response_metadata.unwrap();
+		assert_eq!(metadata.header("openai-processing-ms"), Some("231"));
+	}
+}
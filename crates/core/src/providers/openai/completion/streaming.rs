@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use async_stream::stream;
 use futures::StreamExt;
@@ -14,7 +14,10 @@ use crate::http_client::sse::{Event, GenericEventSource};
 use crate::json_utils::{self, merge};
 use crate::providers::openai::completion::types::{OpenAIRequestParams, Usage};
 use crate::providers::openai::completion::{self, CompletionModel};
+use crate::providers::openai::error::parse_api_error;
 use crate::streaming::{self, RawStreamingChoice};
+use crate::telemetry::SpanCombinator;
+use crate::telemetry::pricing::CostRecorder;
 
 #[derive(Deserialize, Debug)]
 pub(crate) struct StreamingFunction {
@@ -52,6 +55,8 @@ pub enum FinishReason {
 
 #[derive(Deserialize, Debug)]
 struct StreamingChoice {
+	#[serde(default)]
+	index: usize,
 	delta: StreamingDelta,
 	finish_reason: Option<FinishReason>,
 }
@@ -62,6 +67,53 @@ struct StreamingCompletionChunk<U> {
 	usage: Option<U>,
 }
 
+/// Text and tool calls streamed so far for one choice.
+#[derive(Default)]
+struct ChoiceState {
+	/// Tool calls not yielded yet, by index
+	tool_calls: HashMap<usize, streaming::RawStreamingToolCall>,
+	text_content: String,
+	final_tool_calls: Vec<completion::types::ToolCall>,
+}
+
+impl ChoiceState {
+	/// Takes the accumulated tool calls, to yield them in their entirety.
+	fn take_tool_calls(&mut self) -> Vec<streaming::RawStreamingToolCall> {
+		let tool_calls: Vec<_> = std::mem::take(&mut self.tool_calls).into_values().collect();
+		self.final_tool_calls
+			.extend(
+				tool_calls
+					.iter()
+					.map(|tool_call| completion::types::ToolCall {
+						id: tool_call.id.clone(),
+						r#type: completion::types::ToolType::Function,
+						function: completion::types::Function {
+							name: tool_call.name.clone(),
+							arguments: tool_call.arguments.clone(),
+						},
+					}),
+			);
+		tool_calls
+	}
+
+	fn into_message(self) -> completion::types::Message {
+		let content = if self.text_content.is_empty() {
+			vec![]
+		} else {
+			vec![completion::types::AssistantContent::Text {
+				text: self.text_content,
+			}]
+		};
+		completion::types::Message::Assistant {
+			content,
+			refusal: None,
+			audio: None,
+			name: None,
+			tool_calls: self.final_tool_calls,
+		}
+	}
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct StreamingCompletionResponse {
 	pub usage: Usage,
@@ -73,6 +125,7 @@ impl GetTokenUsage for StreamingCompletionResponse {
 		usage.input_tokens = self.usage.prompt_tokens as u64;
 		usage.output_tokens = self.usage.total_tokens as u64 - self.usage.prompt_tokens as u64;
 		usage.total_tokens = self.usage.total_tokens as u64;
+		usage.cached_input_tokens = Self::cached_input_tokens(&self.usage);
 		Some(usage)
 	}
 }
@@ -84,6 +137,10 @@ pub trait CompatStreamingResponse: Clone + Unpin + GetTokenUsage + Send + 'stati
 	fn from_usage(usage: Self::Usage) -> Self;
 	fn prompt_tokens(usage: &Self::Usage) -> u64;
 	fn output_tokens(usage: &Self::Usage) -> u64;
+	/// Input tokens served from the provider's prompt cache, if reported.
+	fn cached_input_tokens(_usage: &Self::Usage) -> u64 {
+		0
+	}
 }
 
 impl CompatStreamingResponse for StreamingCompletionResponse {
@@ -97,6 +154,12 @@ impl CompatStreamingResponse for StreamingCompletionResponse {
 	fn output_tokens(usage: &Usage) -> u64 {
 		(usage.total_tokens - usage.prompt_tokens) as u64
 	}
+	fn cached_input_tokens(usage: &Usage) -> u64 {
+		usage
+			.prompt_tokens_details
+			.as_ref()
+			.map_or(0, |details| details.cached_tokens as u64)
+	}
 }
 
 impl<T> CompletionModel<T>
@@ -113,9 +176,30 @@ where
 			request: completion_request,
 			strict_tools: self.strict_tools,
 			tool_result_array_content: self.tool_result_array_content,
+			prediction: self.prediction.clone(),
 		})?;
-		let request_messages = serde_json::to_string(&request.messages)
-			.expect("Converting to JSON from a Rust struct shouldn't fail");
+
+		let span = if tracing::Span::current().is_disabled() {
+			info_span!(
+				target: "clankers::completions",
+				"chat",
+				gen_ai.operation.name = "chat",
+				gen_ai.provider.name = "openai",
+				gen_ai.request.model = self.model,
+				gen_ai.response.id = tracing::field::Empty,
+				gen_ai.response.model = self.model,
+				gen_ai.usage.output_tokens = tracing::field::Empty,
+				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
+			)
+		} else {
+			tracing::Span::current()
+		};
+		span.record_input_messages(&request.messages);
+
 		let mut request_as_json = serde_json::to_value(request).expect("this should never fail");
 
 		request_as_json = merge(
@@ -139,27 +223,10 @@ where
 			.body(req_body)
 			.map_err(|e| CompletionError::HttpError(e.into()))?;
 
-		let span = if tracing::Span::current().is_disabled() {
-			info_span!(
-				target: "clankers::completions",
-				"chat",
-				gen_ai.operation.name = "chat",
-				gen_ai.provider.name = "openai",
-				gen_ai.request.model = self.model,
-				gen_ai.response.id = tracing::field::Empty,
-				gen_ai.response.model = self.model,
-				gen_ai.usage.output_tokens = tracing::field::Empty,
-				gen_ai.usage.input_tokens = tracing::field::Empty,
-				gen_ai.input.messages = request_messages,
-				gen_ai.output.messages = tracing::field::Empty,
-			)
-		} else {
-			tracing::Span::current()
-		};
-
 		let client = self.client.clone();
+		let cost = self.client.cost_recorder("openai", &self.model);
 
-		tracing::Instrument::instrument(send_compatible_streaming_request(client, req), span).await
+		tracing::Instrument::instrument(send_streaming_request(client, req, Some(cost)), span).await
 	}
 }
 
@@ -167,6 +234,20 @@ pub async fn send_compatible_streaming_request<T, R>(
 	http_client: T,
 	req: Request<Vec<u8>>,
 ) -> Result<streaming::StreamingCompletionResponse<R>, CompletionError>
+where
+	T: HttpClientExt + Clone + 'static,
+	R: CompatStreamingResponse,
+{
+	send_streaming_request(http_client, req, None).await
+}
+
+/// [send_compatible_streaming_request], also recording the estimated cost of the request once its
+/// usage is known.
+pub(crate) async fn send_streaming_request<T, R>(
+	http_client: T,
+	req: Request<Vec<u8>>,
+	cost: Option<CostRecorder>,
+) -> Result<streaming::StreamingCompletionResponse<R>, CompletionError>
 where
 	T: HttpClientExt + Clone + 'static,
 	R: CompatStreamingResponse,
@@ -178,16 +259,17 @@ where
 	let stream = stream! {
         let span = tracing::Span::current();
 
-        // Accumulate tool calls by index while streaming
-        let mut tool_calls: HashMap<usize, streaming::RawStreamingToolCall> = HashMap::new();
-        let mut text_content = String::new();
-        let mut final_tool_calls: Vec<completion::types::ToolCall> = Vec::new();
+        // Accumulate the text and tool calls of each choice while streaming
+        let mut choices: BTreeMap<usize, ChoiceState> = BTreeMap::new();
         let mut final_usage = None;
 
         while let Some(event_result) = event_source.next().await {
             match event_result {
                 Ok(Event::Open) => {
                     tracing::trace!("SSE connection opened");
+                    if let Some(metadata) = event_source.response_metadata() {
+                        yield Ok(RawStreamingChoice::ResponseMetadata(metadata.clone()));
+                    }
                     continue;
                 }
 
@@ -209,19 +291,21 @@ where
                         final_usage = Some(usage);
                     }
 
-                    // Expect at least one choice
-                     let Some(choice) = data.choices.first() else {
+                    if data.choices.is_empty() {
                         tracing::debug!("There is no choice");
                         continue;
-                    };
-                    let delta = &choice.delta;
+                    }
 
-                    if !delta.tool_calls.is_empty() {
-                        for tool_call in &delta.tool_calls {
-                            let index = tool_call.index;
+                    // Requests generating several choices (e.g. with `n`) interleave their deltas,
+                    // the chunks of each choice are attributed to it by index
+                    for choice in &data.choices {
+                        let index = choice.index;
+                        let state = choices.entry(index).or_default();
+                        let delta = &choice.delta;
 
+                        for tool_call in &delta.tool_calls {
                             // Get or create tool call entry
-                            let existing_tool_call = tool_calls.entry(index).or_insert_with(streaming::RawStreamingToolCall::empty);
+                            let existing_tool_call = state.tool_calls.entry(tool_call.index).or_insert_with(streaming::RawStreamingToolCall::empty);
 
                             // Update fields if present
                             if let Some(id) = &tool_call.id && !id.is_empty() {
@@ -234,7 +318,7 @@ where
                                         id: existing_tool_call.id.clone(),
                                         internal_call_id: existing_tool_call.internal_call_id.clone(),
                                         content: streaming::ToolCallDeltaContent::Name(name.clone()),
-                                    });
+                                    }.with_choice_index(index));
                             }
 
                                 // Convert current arguments to string if needed
@@ -263,39 +347,30 @@ where
                                     id: existing_tool_call.id.clone(),
                                     internal_call_id: existing_tool_call.internal_call_id.clone(),
                                     content: streaming::ToolCallDeltaContent::Delta(chunk.clone()),
-                                });
+                                }.with_choice_index(index));
                             }
                         }
-                    }
 
-                    // Streamed text content
-                    if let Some(content) = &delta.content && !content.is_empty() {
-                        text_content += content;
-                        yield Ok(streaming::RawStreamingChoice::Message(content.clone()));
-                    }
+                        // Streamed text content
+                        if let Some(content) = &delta.content && !content.is_empty() {
+                            state.text_content += content;
+                            yield Ok(streaming::RawStreamingChoice::Message(content.clone()).with_choice_index(index));
+                        }
 
-                    // Reasoning content (e.g. DeepSeek, Groq)
-                    if let Some(reasoning) = &delta.reasoning_content && !reasoning.is_empty() {
-                        yield Ok(streaming::RawStreamingChoice::ReasoningDelta {
-                            id: None,
-                            reasoning: reasoning.to_string(),
-                        });
-                    }
+                        // Reasoning content (e.g. DeepSeek, Groq)
+                        if let Some(reasoning) = &delta.reasoning_content && !reasoning.is_empty() {
+                            yield Ok(streaming::RawStreamingChoice::ReasoningDelta {
+                                id: None,
+                                reasoning: reasoning.to_string(),
+                            }.with_choice_index(index));
+                        }
 
-                    // Finish reason
-                    if let Some(finish_reason) = &choice.finish_reason && *finish_reason == FinishReason::ToolCalls {
-                        for (_idx, tool_call) in tool_calls.into_iter() {
-                            final_tool_calls.push(completion::types::ToolCall {
-                                id: tool_call.id.clone(),
-                                r#type: completion::types::ToolType::Function,
-                                function: completion::types::Function {
-                                    name: tool_call.name.clone(),
-                                    arguments: tool_call.arguments.clone(),
-                                },
-                            });
-                            yield Ok(streaming::RawStreamingChoice::ToolCall(tool_call));
+                        // Finish reason
+                        if let Some(finish_reason) = &choice.finish_reason && *finish_reason == FinishReason::ToolCalls {
+                            for tool_call in state.take_tool_calls() {
+                                yield Ok(streaming::RawStreamingChoice::ToolCall(tool_call).with_choice_index(index));
+                            }
                         }
-                        tool_calls = HashMap::new();
                     }
                 }
                 Err(crate::http_client::Error::StreamEnded) => {
@@ -303,7 +378,7 @@ where
                 }
                 Err(error) => {
                     tracing::error!(?error, "SSE error");
-                    yield Err(CompletionError::ProviderError(error.to_string()));
+                    yield Err(CompletionError::from_stream_error(error, parse_api_error));
                     break;
                 }
             }
@@ -314,17 +389,27 @@ where
         event_source.close();
 
         // Flush any accumulated tool calls (that weren't emitted as ToolCall earlier)
-        for (_idx, tool_call) in tool_calls.into_iter() {
-            yield Ok(streaming::RawStreamingChoice::ToolCall(tool_call));
+        for (index, state) in choices.iter_mut() {
+            for tool_call in state.take_tool_calls() {
+                yield Ok(streaming::RawStreamingChoice::ToolCall(tool_call).with_choice_index(*index));
+            }
         }
 
+        let messages: Vec<_> = choices.into_values().map(ChoiceState::into_message).collect();
+        span.record_output_messages(&messages);
+
         let final_usage = final_usage.unwrap_or_default();
         if !span.is_disabled() {
             span.record("gen_ai.usage.input_tokens", R::prompt_tokens(&final_usage));
             span.record("gen_ai.usage.output_tokens", R::output_tokens(&final_usage));
         }
 
-        yield Ok(RawStreamingChoice::FinalResponse(R::from_usage(final_usage)));
+        let final_response = R::from_usage(final_usage);
+        if let Some(cost) = &cost {
+            cost.record(&span, &final_response);
+        }
+
+        yield Ok(RawStreamingChoice::FinalResponse(final_response));
     }.instrument(span);
 
 	Ok(streaming::StreamingCompletionResponse::stream(Box::pin(
@@ -336,6 +421,39 @@ where
 mod tests {
 	use super::*;
 
+	#[test]
+	fn test_usage_chunk_with_cached_tokens() {
+		let chunk: StreamingCompletionChunk<Usage> = serde_json::from_str(
+			r#"{
+                "id": "chatcmpl-123",
+                "object": "chat.completion.chunk",
+                "choices": [],
+                "usage": {
+                    "prompt_tokens": 2006,
+                    "completion_tokens": 300,
+                    "total_tokens": 2306,
+                    "prompt_tokens_details": { "cached_tokens": 1920, "audio_tokens": 0 },
+                    "completion_tokens_details": { "reasoning_tokens": 0 }
+                }
+            }"#,
+		)
+		.unwrap();
+
+		let usage = StreamingCompletionResponse::from_usage(chunk.usage.unwrap())
+			.token_usage()
+			.unwrap();
+		assert_eq!(usage.input_tokens, 2006);
+		assert_eq!(usage.output_tokens, 300);
+		assert_eq!(usage.total_tokens, 2306);
+		assert_eq!(usage.cached_input_tokens, 1920);
+		assert_eq!(usage.cache_creation_input_tokens, 0);
+
+		let usage = StreamingCompletionResponse::from_usage(Usage::new())
+			.token_usage()
+			.unwrap();
+		assert_eq!(usage.cached_input_tokens, 0);
+	}
+
 	#[test]
 	fn test_streaming_function_deserialization() {
 		let json = r#"{"name": "get_weather", "arguments": "{\"location\":\"Paris\"}"}"#;
@@ -513,78 +631,9 @@ mod tests {
 
 	#[tokio::test]
 	async fn test_streaming_usage_only_chunk_is_not_ignored() {
-		use bytes::Bytes;
 		use futures::StreamExt;
 
-		#[derive(Clone)]
-		struct MockHttpClient {
-			sse_bytes: Bytes,
-		}
-
-		impl crate::http_client::HttpClientExt for MockHttpClient {
-			fn send<T, U>(
-				&self,
-				_req: http::Request<T>,
-			) -> impl std::future::Future<
-				Output = crate::http_client::Result<
-					http::Response<crate::http_client::LazyBody<U>>,
-				>,
-			> + crate::wasm_compat::WasmCompatSend
-			+ 'static
-			where
-				T: Into<Bytes>,
-				T: crate::wasm_compat::WasmCompatSend,
-				U: From<Bytes>,
-				U: crate::wasm_compat::WasmCompatSend + 'static,
-			{
-				std::future::ready(Err(crate::http_client::Error::InvalidStatusCode(
-					http::StatusCode::NOT_IMPLEMENTED,
-				)))
-			}
-
-			fn send_multipart<U>(
-				&self,
-				_req: http::Request<crate::http_client::MultipartForm>,
-			) -> impl std::future::Future<
-				Output = crate::http_client::Result<
-					http::Response<crate::http_client::LazyBody<U>>,
-				>,
-			> + crate::wasm_compat::WasmCompatSend
-			+ 'static
-			where
-				U: From<Bytes>,
-				U: crate::wasm_compat::WasmCompatSend + 'static,
-			{
-				std::future::ready(Err(crate::http_client::Error::InvalidStatusCode(
-					http::StatusCode::NOT_IMPLEMENTED,
-				)))
-			}
-
-			fn send_streaming<T>(
-				&self,
-				_req: http::Request<T>,
-			) -> impl std::future::Future<
-				Output = crate::http_client::Result<crate::http_client::StreamingResponse>,
-			> + crate::wasm_compat::WasmCompatSend
-			where
-				T: Into<Bytes>,
-			{
-				let sse_bytes = self.sse_bytes.clone();
-				async move {
-					let byte_stream = futures::stream::iter(vec![Ok::<
-						Bytes,
-						crate::http_client::Error,
-					>(sse_bytes)]);
-					let boxed_stream: crate::http_client::sse::BoxedStream = Box::pin(byte_stream);
-
-					http::Response::builder()
-						.status(http::StatusCode::OK)
-						.header(reqwest::header::CONTENT_TYPE, "text/event-stream")
-						.body(boxed_stream)
-						.map_err(crate::http_client::Error::Protocol)
-				}
-			}
-		}
+		use crate::test_utils::MockSseClient;
 
 		// Some providers emit a final "usage-only" chunk where `choices` is empty.
 		let sse = concat!(
@@ -593,9 +642,7 @@ mod tests {
 			"data: [DONE]\n\n",
 		);
 
-		let client = MockHttpClient {
-			sse_bytes: Bytes::from(sse),
-		};
+		let client = MockSseClient::new(sse);
 
 		let req = http::Request::builder()
 			.method("POST")
@@ -620,4 +667,106 @@ mod tests {
 		assert_eq!(usage.prompt_tokens, 10);
 		assert_eq!(usage.total_tokens, 15);
 	}
+
+	#[tokio::test]
+	async fn test_streaming_character_split_across_chunks() {
+		use futures::StreamExt;
+
+		use crate::test_utils::MockSseClient;
+
+		let sse = concat!(
+			"data: {\"choices\":[{\"delta\":{\"content\":\"Crabs 🦀\"}}]}\n\n",
+			"data: {\"choices\":[{\"delta\":{\"content\":\" rock\"}}]}\n\n",
+			"data: [DONE]\n\n",
+		);
+
+		let req = http::Request::builder()
+			.method("POST")
+			.uri("http://localhost/v1/chat/completions")
+			.body(Vec::new())
+			.unwrap();
+
+		let stream = send_compatible_streaming_request::<_, StreamingCompletionResponse>(
+			MockSseClient::split_in_char(sse, '🦀'),
+			req,
+		)
+		.await
+		.unwrap();
+
+		let text: String = stream
+			.filter_map(|item| async move {
+				match item.unwrap() {
+					streaming::StreamedAssistantContent::Text(text) => Some(text.text),
+					_ => None,
+				}
+			})
+			.collect()
+			.await;
+		assert_eq!(text, "Crabs 🦀 rock");
+	}
+
+	#[tokio::test]
+	async fn test_streaming_multiple_choices() {
+		use futures::StreamExt;
+
+		use crate::OneOrMany;
+		use crate::completion::AssistantContent;
+		use crate::test_utils::MockSseClient;
+
+		// Deltas of both choices of a request with `n: 2`, interleaved and sometimes in one chunk
+		let sse = concat!(
+			"data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hello\"}}]}\n\n",
+			"data: {\"choices\":[{\"index\":1,\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+			"data: {\"choices\":[{\"index\":1,\"delta\":{\"content\":\" there\"}},{\"index\":0,\"delta\":{\"content\":\" world\"}}]}\n\n",
+			"data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+			"data: {\"choices\":[{\"index\":1,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"wave\",\"arguments\":\"{}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+			"data: {\"choices\":[],\"usage\":{\"prompt_tokens\":10,\"completion_tokens\":5,\"total_tokens\":15}}\n\n",
+			"data: [DONE]\n\n",
+		);
+
+		let req = http::Request::builder()
+			.method("POST")
+			.uri("http://localhost/v1/chat/completions")
+			.body(Vec::new())
+			.unwrap();
+
+		let mut stream = send_compatible_streaming_request::<_, StreamingCompletionResponse>(
+			MockSseClient::new(sse),
+			req,
+		)
+		.await
+		.unwrap();
+
+		// Only the first choice is forwarded
+		let items: Vec<_> = stream.by_ref().map(Result::unwrap).collect().await;
+		let text: Vec<_> = items
+			.iter()
+			.filter_map(|item| match item {
+				streaming::StreamedAssistantContent::Text(text) => Some(text.text.as_str()),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(text, ["Hello", " world"]);
+		assert!(matches!(
+			items.last(),
+			Some(streaming::StreamedAssistantContent::Final(_))
+		));
+
+		assert_eq!(
+			stream.choice,
+			OneOrMany::one(AssistantContent::text("Hello world"))
+		);
+		assert_eq!(stream.choices.len(), 2);
+		assert_eq!(stream.choices[0], stream.choice);
+
+		let second: Vec<_> = stream.choices[1].iter().collect();
+		assert!(
+			matches!(
+				second[..],
+				[AssistantContent::Text(text), AssistantContent::ToolCall(tool_call)]
+					if text.text == "Hi there" && tool_call.id == "call_1" && tool_call.function.name == "wave"
+			),
+			"{second:?}"
+		);
+	}
 }
@@ -202,18 +202,18 @@ pub struct InputAudio {
 	pub format: AudioMediaType,
 }
 
+/// A part of a tool result sent as an array, see
+/// [super::CompletionModel::with_tool_result_array_content]
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
-pub struct ToolResultContent {
-	#[serde(default)]
-	r#type: ToolResultContentType,
-	pub text: String,
-}
-
-#[derive(Default, Debug, Serialize, Deserialize, PartialEq, Clone)]
-#[serde(rename_all = "lowercase")]
-pub enum ToolResultContentType {
-	#[default]
-	Text,
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ToolResultContent {
+	Text {
+		text: String,
+	},
+	#[serde(rename = "image_url")]
+	Image {
+		image_url: ImageUrl,
+	},
 }
 
 impl FromStr for ToolResultContent {
@@ -226,10 +226,7 @@ impl FromStr for ToolResultContent {
 
 impl From<String> for ToolResultContent {
 	fn from(s: String) -> Self {
-		ToolResultContent {
-			r#type: ToolResultContentType::default(),
-			text: s,
-		}
+		ToolResultContent::Text { text: s }
 	}
 }
 
@@ -253,7 +250,10 @@ impl ToolResultContentValue {
 		match self {
 			ToolResultContentValue::Array(arr) => arr
 				.iter()
-				.map(|c| c.text.clone())
+				.filter_map(|c| match c {
+					ToolResultContent::Text { text } => Some(text.clone()),
+					ToolResultContent::Image { .. } => None,
+				})
 				.collect::<Vec<_>>()
 				.join("\n"),
 			ToolResultContentValue::String(s) => s.clone(),
@@ -363,6 +363,49 @@ impl TryFrom<message::ToolResult> for Message {
 	type Error = message::MessageError;
 
 	fn try_from(value: message::ToolResult) -> Result<Self, Self::Error> {
+		Message::tool_result(value, false)
+	}
+}
+
+/// Converts an image of a tool result to an `image_url` part, encoding raw images as a base64
+/// data URI.
+fn tool_result_image(image: message::Image) -> Result<ToolResultContent, message::MessageError> {
+	let image = message::Image {
+		detail: Some(image.detail.unwrap_or_default()),
+		..image
+	};
+
+	match UserContent::try_from(message::UserContent::Image(image))? {
+		UserContent::Image { image_url } => Ok(ToolResultContent::Image { image_url }),
+		_ => unreachable!("images are converted to image parts"),
+	}
+}
+
+impl Message {
+	/// Converts a tool result, as an array of text and image parts if `array_content` is set.
+	/// Otherwise the parts are joined into a string, which can't contain images.
+	pub(crate) fn tool_result(
+		value: message::ToolResult,
+		array_content: bool,
+	) -> Result<Self, message::MessageError> {
+		if array_content {
+			let parts = value
+				.content
+				.into_iter()
+				.map(|content| match content {
+					message::ToolResultContent::Text(message::Text { text }) => {
+						Ok(ToolResultContent::Text { text })
+					}
+					message::ToolResultContent::Image(image) => tool_result_image(image),
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+
+			return Ok(Message::ToolResult {
+				tool_call_id: value.id,
+				content: ToolResultContentValue::Array(parts),
+			});
+		}
+
 		let text = value
 			.content
 			.into_iter()
@@ -388,7 +431,8 @@ impl TryFrom<message::ToolResult> for Message {
 impl TryFrom<message::UserContent> for UserContent {
 	type Error = message::MessageError;
 
-	fn try_from(value: message::UserContent) -> Result<Self, Self::Error> {
+	fn try_from(mut value: message::UserContent) -> Result<Self, Self::Error> {
+		value.sniff_media_type();
 		match value {
 			message::UserContent::Text(message::Text { text }) => Ok(UserContent::Text { text }),
 			message::UserContent::Image(message::Image {
@@ -396,7 +440,7 @@ impl TryFrom<message::UserContent> for UserContent {
 				detail,
 				media_type,
 				..
-			}) => match data {
+			}) => match data.into_base64() {
 				DocumentSourceKind::Url(url) => Ok(UserContent::Image {
 					image_url: ImageUrl {
 						url,
@@ -422,9 +466,6 @@ impl TryFrom<message::UserContent> for UserContent {
 						image_url: ImageUrl { url, detail },
 					})
 				}
-				DocumentSourceKind::Raw(_) => Err(message::MessageError::ConversionError(
-					"Raw files not supported, encode as base64 first".into(),
-				)),
 				DocumentSourceKind::Unknown => Err(message::MessageError::ConversionError(
 					"Document has no body".into(),
 				)),
@@ -443,7 +484,7 @@ impl TryFrom<message::UserContent> for UserContent {
 			}
 			message::UserContent::Audio(message::Audio {
 				data, media_type, ..
-			}) => match data {
+			}) => match data.into_base64() {
 				DocumentSourceKind::Base64(data) => Ok(UserContent::Audio {
 					input_audio: InputAudio {
 						data,
@@ -456,9 +497,6 @@ impl TryFrom<message::UserContent> for UserContent {
 				DocumentSourceKind::Url(_) => Err(message::MessageError::ConversionError(
 					"URLs are not supported for audio".into(),
 				)),
-				DocumentSourceKind::Raw(_) => Err(message::MessageError::ConversionError(
-					"Raw files are not supported for audio".into(),
-				)),
 				DocumentSourceKind::Unknown => Err(message::MessageError::ConversionError(
 					"Audio has no body".into(),
 				)),
@@ -480,34 +518,43 @@ impl TryFrom<OneOrMany<message::UserContent>> for Vec<Message> {
 	type Error = message::MessageError;
 
 	fn try_from(value: OneOrMany<message::UserContent>) -> Result<Self, Self::Error> {
-		let (tool_results, other_content): (Vec<_>, Vec<_>) = value
-			.into_iter()
-			.partition(|content| matches!(content, message::UserContent::ToolResult(_)));
+		user_messages(value, false)
+	}
+}
 
-		// If there are messages with both tool results and user content, openai will only
-		//  handle tool results. It's unlikely that there will be both.
-		if !tool_results.is_empty() {
-			tool_results
-				.into_iter()
-				.map(|content| match content {
-					message::UserContent::ToolResult(tool_result) => tool_result.try_into(),
-					_ => unreachable!(),
-				})
-				.collect::<Result<Vec<_>, _>>()
-		} else {
-			let other_content: Vec<UserContent> = other_content
-				.into_iter()
-				.map(|content| content.try_into())
-				.collect::<Result<Vec<_>, _>>()?;
+fn user_messages(
+	value: OneOrMany<message::UserContent>,
+	tool_result_array_content: bool,
+) -> Result<Vec<Message>, message::MessageError> {
+	let (tool_results, other_content): (Vec<_>, Vec<_>) = value
+		.into_iter()
+		.partition(|content| matches!(content, message::UserContent::ToolResult(_)));
+
+	// If there are messages with both tool results and user content, openai will only
+	//  handle tool results. It's unlikely that there will be both.
+	if !tool_results.is_empty() {
+		tool_results
+			.into_iter()
+			.map(|content| match content {
+				message::UserContent::ToolResult(tool_result) => {
+					Message::tool_result(tool_result, tool_result_array_content)
+				}
+				_ => unreachable!(),
+			})
+			.collect::<Result<Vec<_>, _>>()
+	} else {
+		let other_content: Vec<UserContent> = other_content
+			.into_iter()
+			.map(|content| content.try_into())
+			.collect::<Result<Vec<_>, _>>()?;
 
-			let other_content = OneOrMany::many(other_content)
-				.expect("There must be other content here if there were no tool result content");
+		let other_content = OneOrMany::many(other_content)
+			.expect("There must be other content here if there were no tool result content");
 
-			Ok(vec![Message::User {
-				content: other_content,
-				name: None,
-			}])
-		}
+		Ok(vec![Message::User {
+			content: other_content,
+			name: None,
+		}])
 	}
 }
 
@@ -529,6 +576,11 @@ impl TryFrom<OneOrMany<message::AssistantContent>> for Vec<Message> {
 							"The OpenAI Completions API doesn't support image content in assistant messages!"
 						);
 					}
+					message::AssistantContent::Document(_) => {
+						tracing::warn!(
+							"The OpenAI Completions API doesn't support assistant documents, skipping"
+						);
+					}
 				}
 				(texts, tools)
 			},
@@ -556,8 +608,19 @@ impl TryFrom<message::Message> for Vec<Message> {
 	type Error = message::MessageError;
 
 	fn try_from(message: message::Message) -> Result<Self, Self::Error> {
+		Message::from_core(message, false)
+	}
+}
+
+impl Message {
+	/// Converts a message, with tool results as arrays of parts if `tool_result_array_content`
+	/// is set.
+	pub(crate) fn from_core(
+		message: message::Message,
+		tool_result_array_content: bool,
+	) -> Result<Vec<Self>, message::MessageError> {
 		match message {
-			message::Message::User { content } => content.try_into(),
+			message::Message::User { content } => user_messages(content, tool_result_array_content),
 			message::Message::Assistant { content, .. } => content.try_into(),
 		}
 	}
@@ -796,6 +859,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 					.as_ref()
 					.map(|d| d.cached_tokens as u64)
 					.unwrap_or(0),
+				cache_creation_input_tokens: 0,
 			})
 			.unwrap_or_default();
 
@@ -803,6 +867,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -855,12 +920,27 @@ pub struct PromptTokensDetails {
 	pub cached_tokens: usize,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct CompletionTokensDetails {
+	#[serde(default)]
+	pub reasoning_tokens: usize,
+	/// Tokens of the [Prediction] which appeared in the response
+	#[serde(default)]
+	pub accepted_prediction_tokens: usize,
+	/// Tokens of the [Prediction] which didn't appear in the response, still billed as
+	/// completion tokens
+	#[serde(default)]
+	pub rejected_prediction_tokens: usize,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Usage {
 	pub prompt_tokens: usize,
 	pub total_tokens: usize,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub prompt_tokens_details: Option<PromptTokensDetails>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub completion_tokens_details: Option<CompletionTokensDetails>,
 }
 
 impl Usage {
@@ -869,6 +949,7 @@ impl Usage {
 			prompt_tokens: 0,
 			total_tokens: 0,
 			prompt_tokens_details: None,
+			completion_tokens_details: None,
 		}
 	}
 }
@@ -912,22 +993,80 @@ impl GetTokenUsage for Usage {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CompletionRequest {
 	model: String,
-	pub(super) messages: Vec<Message>,
+	pub(crate) messages: Vec<Message>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<ToolChoice>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	prediction: Option<Prediction>,
 	#[serde(flatten)]
 	additional_params: Option<serde_json::Value>,
 }
 
+/// A [predicted output](https://platform.openai.com/docs/guides/predicted-outputs): content
+/// expected in the response, e.g. the file being edited. The parts of the response matching it
+/// are generated faster.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Prediction {
+	Content { content: PredictionContent },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PredictionContent {
+	Text(String),
+	Parts(Vec<PredictionPart>),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PredictionPart {
+	Text { text: String },
+}
+
+impl From<String> for Prediction {
+	fn from(text: String) -> Self {
+		Prediction::Content {
+			content: PredictionContent::Text(text),
+		}
+	}
+}
+
+impl From<&str> for Prediction {
+	fn from(text: &str) -> Self {
+		text.to_string().into()
+	}
+}
+
+/// Each text becomes a content part.
+impl From<Vec<String>> for Prediction {
+	fn from(parts: Vec<String>) -> Self {
+		Prediction::Content {
+			content: PredictionContent::Parts(
+				parts
+					.into_iter()
+					.map(|text| PredictionPart::Text { text })
+					.collect(),
+			),
+		}
+	}
+}
+
+/// Maximum number of stop sequences accepted by the Chat Completions API
+pub const MAX_STOP_SEQUENCES: usize = 4;
+
 pub struct OpenAIRequestParams {
 	pub model: String,
 	pub request: CoreCompletionRequest,
 	pub strict_tools: bool,
 	pub tool_result_array_content: bool,
+	pub prediction: Option<Prediction>,
 }
 
 impl TryFrom<OpenAIRequestParams> for CompletionRequest {
@@ -939,8 +1078,11 @@ impl TryFrom<OpenAIRequestParams> for CompletionRequest {
 			request: req,
 			strict_tools,
 			tool_result_array_content,
+			prediction,
 		} = params;
 
+		req.check_stop_sequences("OpenAI", MAX_STOP_SEQUENCES)?;
+
 		let mut partial_history = vec![];
 		if let Some(docs) = req.normalized_documents() {
 			partial_history.push(docs);
@@ -950,6 +1092,7 @@ impl TryFrom<OpenAIRequestParams> for CompletionRequest {
 			chat_history,
 			tools,
 			temperature,
+			stop_sequences,
 			additional_params,
 			tool_choice,
 			..
@@ -963,21 +1106,13 @@ impl TryFrom<OpenAIRequestParams> for CompletionRequest {
 		full_history.extend(
 			partial_history
 				.into_iter()
-				.map(message::Message::try_into)
+				.map(|message| Message::from_core(message, tool_result_array_content))
 				.collect::<Result<Vec<Vec<Message>>, _>>()?
 				.into_iter()
 				.flatten()
 				.collect::<Vec<_>>(),
 		);
 
-		if tool_result_array_content {
-			for msg in &mut full_history {
-				if let Message::ToolResult { content, .. } = msg {
-					*content = content.to_array();
-				}
-			}
-		}
-
 		let tool_choice = tool_choice.map(ToolChoice::try_from).transpose()?;
 
 		let tools: Vec<ToolDefinition> = tools
@@ -994,6 +1129,8 @@ impl TryFrom<OpenAIRequestParams> for CompletionRequest {
 			tools,
 			tool_choice,
 			temperature,
+			stop: stop_sequences,
+			prediction,
 			additional_params,
 		};
 
@@ -1010,6 +1147,7 @@ impl TryFrom<(String, CoreCompletionRequest)> for CompletionRequest {
 			request: req,
 			strict_tools: false,
 			tool_result_array_content: false,
+			prediction: None,
 		})
 	}
 }
@@ -1051,3 +1189,240 @@ impl crate::telemetry::ProviderRequestExt for CompletionRequest {
 		self.model.clone()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	fn request_with_stop_sequences(stop_sequences: Vec<String>) -> CoreCompletionRequest {
+		CoreCompletionRequest::builder("Count to ten")
+			.stop_sequences(stop_sequences)
+			.build()
+	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = CompletionRequest::try_from(OpenAIRequestParams {
+			model: "gpt-4o".to_string(),
+			request: request_with_stop_sequences(vec!["\n\n".to_string(), "END".to_string()]),
+			strict_tools: false,
+			tool_result_array_content: false,
+			prediction: None,
+		})
+		.unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["stop"],
+			json!(["\n\n", "END"])
+		);
+	}
+
+	#[test]
+	fn test_prediction_serialization() {
+		let request = |prediction: Prediction| {
+			let request = CompletionRequest::try_from(OpenAIRequestParams {
+				model: "gpt-4o".to_string(),
+				request: request_with_stop_sequences(vec![]),
+				strict_tools: false,
+				tool_result_array_content: false,
+				prediction: Some(prediction),
+			})
+			.unwrap();
+			serde_json::to_value(&request).unwrap()["prediction"].clone()
+		};
+
+		assert_eq!(
+			request("fn main() {}".into()),
+			json!({ "type": "content", "content": "fn main() {}" })
+		);
+		assert_eq!(
+			request(vec!["fn main() {".to_string(), "}".to_string()].into()),
+			json!({
+				"type": "content",
+				"content": [
+					{ "type": "text", "text": "fn main() {" },
+					{ "type": "text", "text": "}" }
+				]
+			})
+		);
+	}
+
+	#[test]
+	fn test_prediction_usage_deserialization() {
+		let response: CompletionResponse = serde_json::from_value(json!({
+			"id": "chatcmpl-9nqO2wZ7b9TEeRtsDcYGnuLiAc4ph",
+			"object": "chat.completion",
+			"created": 1721747398,
+			"model": "gpt-4o-2024-08-06",
+			"choices": [{
+				"index": 0,
+				"message": { "role": "assistant", "content": "class User {}" },
+				"logprobs": null,
+				"finish_reason": "stop"
+			}],
+			"usage": {
+				"prompt_tokens": 302,
+				"completion_tokens": 362,
+				"total_tokens": 664,
+				"prompt_tokens_details": { "cached_tokens": 0, "audio_tokens": 0 },
+				"completion_tokens_details": {
+					"reasoning_tokens": 0,
+					"audio_tokens": 0,
+					"accepted_prediction_tokens": 18,
+					"rejected_prediction_tokens": 10
+				}
+			},
+			"system_fingerprint": "fp_159d8341cc"
+		}))
+		.unwrap();
+
+		let details = response.usage.unwrap().completion_tokens_details.unwrap();
+		assert_eq!(details.accepted_prediction_tokens, 18);
+		assert_eq!(details.rejected_prediction_tokens, 10);
+		assert_eq!(details.reasoning_tokens, 0);
+	}
+
+	#[test]
+	fn test_stop_sequences_limit() {
+		let err = CompletionRequest::try_from(OpenAIRequestParams {
+			model: "gpt-4o".to_string(),
+			request: request_with_stop_sequences((0..5).map(|i| i.to_string()).collect()),
+			strict_tools: false,
+			tool_result_array_content: false,
+			prediction: None,
+		})
+		.unwrap_err();
+
+		assert_eq!(
+			err.to_string(),
+			"RequestError: OpenAI supports at most 4 stop sequences, got 5"
+		);
+	}
+
+	fn request_with_tool_result(
+		tool_result_array_content: bool,
+	) -> Result<CompletionRequest, CompletionError> {
+		let tool_result = message::Message::User {
+			content: OneOrMany::one(message::UserContent::ToolResult(message::ToolResult {
+				id: "call_1".to_string(),
+				call_id: None,
+				content: OneOrMany::many(vec![
+					message::ToolResultContent::text("Rendered the chart"),
+					message::ToolResultContent::image_base64(
+						"iVBORw0KGgo=",
+						Some(message::ImageMediaType::PNG),
+						None,
+					),
+					message::ToolResultContent::image_url(
+						"https://example.com/chart.png",
+						None,
+						Some(ImageDetail::High),
+					),
+				])
+				.unwrap(),
+			})),
+		};
+		let mut request = request_with_stop_sequences(vec![]);
+		request.chat_history = OneOrMany::one(tool_result);
+
+		CompletionRequest::try_from(OpenAIRequestParams {
+			model: "gpt-4o".to_string(),
+			request,
+			strict_tools: false,
+			tool_result_array_content,
+			prediction: None,
+		})
+	}
+
+	#[test]
+	fn test_tool_result_images_as_array_content() {
+		let request = request_with_tool_result(true).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["messages"][0],
+			json!({
+				"role": "tool",
+				"tool_call_id": "call_1",
+				"content": [
+					{ "type": "text", "text": "Rendered the chart" },
+					{
+						"type": "image_url",
+						"image_url": { "url": "data:image/png;base64,iVBORw0KGgo=", "detail": "auto" }
+					},
+					{
+						"type": "image_url",
+						"image_url": { "url": "https://example.com/chart.png", "detail": "high" }
+					}
+				]
+			})
+		);
+	}
+
+	#[test]
+	fn test_tool_result_images_rejected_without_array_content() {
+		let err = request_with_tool_result(false).unwrap_err();
+
+		assert!(
+			err.to_string()
+				.contains("OpenAI does not support images in tool results")
+		);
+	}
+
+	#[test]
+	fn test_tool_result_raw_image_is_encoded() {
+		let png = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+		let content = tool_result_image(message::Image {
+			data: DocumentSourceKind::Raw(png.to_vec()),
+			media_type: Some(message::ImageMediaType::PNG),
+			..Default::default()
+		})
+		.unwrap();
+
+		assert_eq!(
+			content,
+			ToolResultContent::Image {
+				image_url: ImageUrl {
+					url: "data:image/png;base64,iVBORw0KGgo=".to_string(),
+					detail: ImageDetail::Auto,
+				}
+			}
+		);
+	}
+
+	#[test]
+	fn test_raw_user_content_media_type_is_sniffed() {
+		let png = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+		let content = UserContent::try_from(message::UserContent::Image(message::Image {
+			data: DocumentSourceKind::Raw(png.to_vec()),
+			detail: Some(ImageDetail::Auto),
+			..Default::default()
+		}))
+		.unwrap();
+		assert_eq!(
+			content,
+			UserContent::Image {
+				image_url: ImageUrl {
+					url: "data:image/png;base64,iVBORw0KGgo=".to_string(),
+					detail: ImageDetail::Auto,
+				}
+			}
+		);
+
+		let content = UserContent::try_from(message::UserContent::Audio(message::Audio {
+			data: DocumentSourceKind::Raw(b"RIFF\0\0\0\0WAVE".to_vec()),
+			..Default::default()
+		}))
+		.unwrap();
+		assert_eq!(
+			content,
+			UserContent::Audio {
+				input_audio: InputAudio {
+					data: "UklGRgAAAABXQVZF".to_string(),
+					format: AudioMediaType::WAV,
+				}
+			}
+		);
+	}
+}
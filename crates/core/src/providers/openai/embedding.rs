@@ -80,6 +80,10 @@ where
 		Self::new(client.clone(), model, dims)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	fn ndims(&self) -> usize {
 		self.ndims
 	}
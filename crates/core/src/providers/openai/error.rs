@@ -0,0 +1,132 @@
+//! Classification of OpenAI error responses, which are shared by most OpenAI-compatible APIs.
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::completion::provider_error::parse_delay;
+use crate::completion::{ApiError, ProviderErrorKind};
+
+/// `{ "error": { "message": "...", "type": "...", "code": "..." } }`
+#[derive(Deserialize)]
+struct ErrorBody {
+	error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+	message: String,
+	#[serde(default)]
+	r#type: Option<String>,
+	#[serde(default)]
+	code: Option<Value>,
+}
+
+/// Classifies an OpenAI error response from its status and body.
+pub fn parse_api_error(status: StatusCode, body: String) -> ApiError {
+	let mut error = ApiError::new(status, body);
+	let Ok(ErrorBody { error: detail }) = serde_json::from_str(&error.body) else {
+		return error;
+	};
+
+	let code = detail
+		.code
+		.as_ref()
+		.and_then(Value::as_str)
+		.unwrap_or_default();
+	match (code, detail.r#type.as_deref().unwrap_or_default()) {
+		("context_length_exceeded" | "string_above_max_length", _) => {
+			error.kind = ProviderErrorKind::ContextLengthExceeded;
+		}
+		("content_filter" | "content_policy_violation", _) => {
+			error.kind = ProviderErrorKind::ContentFiltered;
+		}
+		("invalid_api_key", _) | (_, "authentication_error") => {
+			error.kind = ProviderErrorKind::AuthenticationFailed;
+		}
+		("model_not_found", _) => error.kind = ProviderErrorKind::NotFound,
+		// Billing issue rather than a rate limit, retrying won't help
+		("insufficient_quota", _) | (_, "insufficient_quota") => {
+			error.kind = ProviderErrorKind::Other;
+		}
+		("rate_limit_exceeded", _) => {
+			error.kind = ProviderErrorKind::RateLimited { retry_after: None };
+		}
+		_ => {}
+	}
+
+	if let ProviderErrorKind::RateLimited { retry_after } = &mut error.kind {
+		// "... Please try again in 1.4s. Visit ..."
+		*retry_after = detail
+			.message
+			.split_once("try again in ")
+			.and_then(|(_, rest)| rest.split_whitespace().next())
+			.and_then(|delay| parse_delay(delay.trim_end_matches('.')));
+	}
+
+	error.message = detail.message;
+	error
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::*;
+
+	#[test]
+	fn test_rate_limit() {
+		let body = r#"{"error":{"message":"Rate limit reached for gpt-4o in organization org-abc on tokens per min (TPM): Limit 30000, Used 29500, Requested 1200. Please try again in 1.4s. Visit https://platform.openai.com/account/rate-limits to learn more.","type":"tokens","param":null,"code":"rate_limit_exceeded"}}"#;
+
+		let error = parse_api_error(StatusCode::TOO_MANY_REQUESTS, body.to_string());
+		assert_eq!(
+			error.kind,
+			ProviderErrorKind::RateLimited {
+				retry_after: Some(Duration::from_millis(1400))
+			}
+		);
+		assert!(error.message.starts_with("Rate limit reached for gpt-4o"));
+	}
+
+	#[test]
+	fn test_context_length() {
+		let body = r#"{"error":{"message":"This model's maximum context length is 128000 tokens. However, your messages resulted in 130412 tokens. Please reduce the length of the messages.","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}}"#;
+
+		let error = parse_api_error(StatusCode::BAD_REQUEST, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::ContextLengthExceeded);
+	}
+
+	#[test]
+	fn test_invalid_api_key() {
+		let body = r#"{"error":{"message":"Incorrect API key provided: sk-abc. You can find your API key at https://platform.openai.com/account/api-keys.","type":"invalid_request_error","param":null,"code":"invalid_api_key"}}"#;
+
+		let error = parse_api_error(StatusCode::UNAUTHORIZED, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::AuthenticationFailed);
+	}
+
+	#[test]
+	fn test_insufficient_quota_is_not_a_rate_limit() {
+		let body = r#"{"error":{"message":"You exceeded your current quota, please check your plan and billing details.","type":"insufficient_quota","param":null,"code":"insufficient_quota"}}"#;
+
+		let error = parse_api_error(StatusCode::TOO_MANY_REQUESTS, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::Other);
+		assert!(!error.kind.is_retryable());
+	}
+
+	#[test]
+	fn test_model_not_found_and_content_policy() {
+		let body = r#"{"error":{"message":"The model `gpt-9` does not exist or you do not have access to it.","type":"invalid_request_error","param":null,"code":"model_not_found"}}"#;
+		let error = parse_api_error(StatusCode::NOT_FOUND, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::NotFound);
+
+		let body = r#"{"error":{"message":"Your request was rejected as a result of our safety system.","type":"invalid_request_error","param":null,"code":"content_policy_violation"}}"#;
+		let error = parse_api_error(StatusCode::BAD_REQUEST, body.to_string());
+		assert_eq!(error.kind, ProviderErrorKind::ContentFiltered);
+	}
+
+	#[test]
+	fn test_unparseable_body_uses_status() {
+		let error = parse_api_error(StatusCode::SERVICE_UNAVAILABLE, "upstream error".into());
+		assert_eq!(error.kind, ProviderErrorKind::Overloaded);
+		assert_eq!(error.message, "upstream error");
+	}
+}
@@ -1,12 +1,14 @@
+use async_stream::stream;
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
-use serde::Deserialize;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use super::Client;
-use super::client::ApiResponse;
 use crate::http_client::HttpClientExt;
-use crate::image_generation::{ImageGenerationError, ImageGenerationRequest};
+use crate::http_client::sse::{Event, GenericEventSource};
+use crate::image_generation::{ImageGenerationError, ImageGenerationRequest, PartialImage};
 use crate::json_utils::merge_inplace;
 use crate::{http_client, image_generation};
 
@@ -14,15 +16,79 @@ pub const DALL_E_2: &str = "dall-e-2";
 pub const DALL_E_3: &str = "dall-e-3";
 pub const GPT_IMAGE_1: &str = "gpt-image-1";
 
-#[derive(Debug, Deserialize)]
+/// Rendering quality of images generated by `gpt-image-1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageQuality {
+	Auto,
+	Low,
+	Medium,
+	High,
+	/// DALL·E 3 only
+	Hd,
+	/// DALL·E 3 only
+	Standard,
+}
+
+/// Background of images generated by `gpt-image-1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageBackground {
+	Auto,
+	Transparent,
+	Opaque,
+}
+
+/// Encoding of images generated by `gpt-image-1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageOutputFormat {
+	Png,
+	Webp,
+	Jpeg,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ImageGenerationData {
-	pub b64_json: String,
+	/// Base64 encoded image. Always returned by `gpt-image-1`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub b64_json: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub revised_prompt: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImageInputTokensDetails {
+	pub text_tokens: u64,
+	pub image_tokens: u64,
+}
+
+/// Token usage reported by `gpt-image-1`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImageGenerationUsage {
+	pub input_tokens: u64,
+	pub output_tokens: u64,
+	pub total_tokens: u64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub input_tokens_details: Option<ImageInputTokensDetails>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ImageGenerationResponse {
-	pub created: i32,
+	pub created: i64,
 	pub data: Vec<ImageGenerationData>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub background: Option<ImageBackground>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub output_format: Option<ImageOutputFormat>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub quality: Option<ImageQuality>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub size: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub usage: Option<ImageGenerationUsage>,
 }
 
 impl TryFrom<ImageGenerationResponse>
@@ -31,16 +97,91 @@ impl TryFrom<ImageGenerationResponse>
 	type Error = ImageGenerationError;
 
 	fn try_from(value: ImageGenerationResponse) -> Result<Self, Self::Error> {
-		let b64_json = value.data[0].b64_json.clone();
+		let images = value
+			.data
+			.iter()
+			.filter_map(|data| data.b64_json.as_deref())
+			.map(decode_image)
+			.collect::<Result<_, _>>()?;
+
+		Self::new(images, value)
+	}
+}
+
+/// Event streamed by `gpt-image-1` when `stream` is set
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum ImageGenerationStreamEvent {
+	#[serde(rename = "image_generation.partial_image")]
+	PartialImage {
+		b64_json: String,
+		partial_image_index: usize,
+	},
+	#[serde(rename = "image_generation.completed")]
+	Completed(Box<ImageGenerationCompleted>),
+	#[serde(rename = "error")]
+	Error { error: ApiError },
+	#[serde(other)]
+	Unknown,
+}
 
-		let bytes = BASE64_STANDARD
-			.decode(&b64_json)
-			.expect("Failed to decode b64");
+#[derive(Debug, Deserialize)]
+struct ImageGenerationCompleted {
+	b64_json: String,
+	created_at: i64,
+	#[serde(default)]
+	background: Option<ImageBackground>,
+	#[serde(default)]
+	output_format: Option<ImageOutputFormat>,
+	#[serde(default)]
+	quality: Option<ImageQuality>,
+	#[serde(default)]
+	size: Option<String>,
+	#[serde(default)]
+	usage: Option<ImageGenerationUsage>,
+}
+
+impl From<ImageGenerationCompleted> for ImageGenerationResponse {
+	fn from(value: ImageGenerationCompleted) -> Self {
+		Self {
+			created: value.created_at,
+			data: vec![ImageGenerationData {
+				b64_json: Some(value.b64_json),
+				url: None,
+				revised_prompt: None,
+			}],
+			background: value.background,
+			output_format: value.output_format,
+			quality: value.quality,
+			size: value.size,
+			usage: value.usage,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+	message: String,
+	#[serde(default)]
+	code: Option<String>,
+}
 
-		Ok(image_generation::ImageGenerationResponse {
-			image: bytes,
-			response: value,
-		})
+#[derive(Debug, Deserialize)]
+struct ApiErrorResponse {
+	error: ApiError,
+}
+
+impl From<ApiError> for ImageGenerationError {
+	fn from(err: ApiError) -> Self {
+		match err.code.as_deref() {
+			Some("content_policy_violation" | "moderation_blocked") => {
+				ImageGenerationError::ProviderError(format!(
+					"Request rejected by content policy: {}",
+					err.message
+				))
+			}
+			_ => ImageGenerationError::ProviderError(err.message),
+		}
 	}
 }
 
@@ -49,6 +190,11 @@ pub struct ImageGenerationModel<T = reqwest::Client> {
 	client: Client<T>,
 	/// Name of the model (e.g.: dall-e-2)
 	pub model: String,
+	pub quality: Option<ImageQuality>,
+	pub background: Option<ImageBackground>,
+	pub output_format: Option<ImageOutputFormat>,
+	/// Number of partial images streamed before the final one
+	pub partial_images: Option<u8>,
 }
 
 impl<T> ImageGenerationModel<T> {
@@ -56,32 +202,45 @@ impl<T> ImageGenerationModel<T> {
 		Self {
 			client,
 			model: model.into(),
+			quality: None,
+			background: None,
+			output_format: None,
+			partial_images: None,
 		}
 	}
-}
 
-impl<T> image_generation::ImageGenerationModel for ImageGenerationModel<T>
-where
-	T: HttpClientExt + Clone + Default + std::fmt::Debug + Send + 'static,
-{
-	type Response = ImageGenerationResponse;
+	pub fn with_quality(mut self, quality: ImageQuality) -> Self {
+		self.quality = Some(quality);
+		self
+	}
 
-	type Client = Client<T>;
+	/// Only supported by `gpt-image-1`. A transparent background requires
+	/// the `png` or `webp` output format.
+	pub fn with_background(mut self, background: ImageBackground) -> Self {
+		self.background = Some(background);
+		self
+	}
 
-	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
-		Self::new(client.clone(), model)
+	/// Only supported by `gpt-image-1`.
+	pub fn with_output_format(mut self, output_format: ImageOutputFormat) -> Self {
+		self.output_format = Some(output_format);
+		self
 	}
 
-	async fn image_generation(
-		&self,
-		generation_request: ImageGenerationRequest,
-	) -> Result<image_generation::ImageGenerationResponse<Self::Response>, ImageGenerationError> {
+	/// Only supported by `gpt-image-1` when streaming. Between 0 and 3, defaults to 0.
+	pub fn with_partial_images(mut self, partial_images: u8) -> Self {
+		self.partial_images = Some(partial_images);
+		self
+	}
+
+	fn request_body(&self, generation_request: ImageGenerationRequest) -> serde_json::Value {
 		let mut request = json!({
 			"model": self.model,
 			"prompt": generation_request.prompt,
 			"size": format!("{}x{}", generation_request.width, generation_request.height),
 		});
 
+		// `gpt-image-1` always returns base64 and rejects `response_format`
 		if self.model.as_str() != GPT_IMAGE_1 {
 			merge_inplace(
 				&mut request,
@@ -91,7 +250,139 @@ where
 			);
 		}
 
-		let body = serde_json::to_vec(&request)?;
+		if let Some(quality) = self.quality {
+			merge_inplace(&mut request, json!({ "quality": quality }));
+		}
+
+		if let Some(background) = self.background {
+			merge_inplace(&mut request, json!({ "background": background }));
+		}
+
+		if let Some(output_format) = self.output_format {
+			merge_inplace(&mut request, json!({ "output_format": output_format }));
+		}
+
+		if let Some(n) = generation_request.n {
+			merge_inplace(&mut request, json!({ "n": n }));
+		}
+
+		if let Some(params) = generation_request.additional_params {
+			merge_inplace(&mut request, params);
+		}
+
+		request
+	}
+
+	fn streaming_request_body(
+		&self,
+		generation_request: ImageGenerationRequest,
+	) -> serde_json::Value {
+		let mut request = self.request_body(generation_request);
+
+		merge_inplace(&mut request, json!({ "stream": true }));
+
+		if let Some(partial_images) = self.partial_images {
+			merge_inplace(&mut request, json!({ "partial_images": partial_images }));
+		}
+
+		request
+	}
+}
+
+fn decode_image(b64_json: &str) -> Result<Vec<u8>, ImageGenerationError> {
+	BASE64_STANDARD.decode(b64_json).map_err(|e| {
+		ImageGenerationError::ResponseError(format!("Failed to decode base64 image: {e}"))
+	})
+}
+
+/// Turns the server-sent events of a streaming generation into [PartialImage]s.
+fn stream_partial_images<C>(
+	client: C,
+	req: http::Request<Vec<u8>>,
+) -> impl Stream<Item = Result<PartialImage<ImageGenerationResponse>, ImageGenerationError>> + Send
+where
+	C: HttpClientExt + Clone + 'static,
+{
+	let mut event_source = GenericEventSource::new(client, req);
+
+	stream! {
+		while let Some(event) = event_source.next().await {
+			let message = match event {
+				Ok(Event::Open) => continue,
+				Ok(Event::Message(message)) => message,
+				Err(http_client::Error::InvalidStatusCodeWithMessage(status, text)) => {
+					match serde_json::from_str::<ApiErrorResponse>(&text) {
+						Ok(err) => yield Err(err.error.into()),
+						Err(_) => yield Err(ImageGenerationError::ProviderError(format!("{status}: {text}"))),
+					}
+					break;
+				}
+				Err(error) => {
+					yield Err(error.into());
+					break;
+				}
+			};
+
+			if message.data.trim().is_empty() || message.data == "[DONE]" {
+				continue;
+			}
+
+			match serde_json::from_str::<ImageGenerationStreamEvent>(&message.data) {
+				Ok(ImageGenerationStreamEvent::PartialImage { b64_json, partial_image_index }) => {
+					yield decode_image(&b64_json).map(|image| PartialImage::Partial {
+						index: partial_image_index,
+						image,
+					});
+				}
+				Ok(ImageGenerationStreamEvent::Completed(completed)) => {
+					let response = ImageGenerationResponse::from(*completed);
+					yield response.try_into().map(PartialImage::Final);
+					break;
+				}
+				Ok(ImageGenerationStreamEvent::Error { error }) => {
+					yield Err(error.into());
+					break;
+				}
+				Ok(ImageGenerationStreamEvent::Unknown) => {}
+				Err(error) => {
+					yield Err(error.into());
+					break;
+				}
+			}
+		}
+
+		event_source.close();
+	}
+}
+
+fn parse_response(
+	text: &str,
+) -> Result<image_generation::ImageGenerationResponse<ImageGenerationResponse>, ImageGenerationError>
+{
+	if let Ok(err) = serde_json::from_str::<ApiErrorResponse>(text) {
+		return Err(err.error.into());
+	}
+
+	serde_json::from_str::<ImageGenerationResponse>(text)?.try_into()
+}
+
+impl<T> image_generation::ImageGenerationModel for ImageGenerationModel<T>
+where
+	T: HttpClientExt + Clone + Default + std::fmt::Debug + Send + 'static,
+{
+	type Response = ImageGenerationResponse;
+
+	type Client = Client<T>;
+
+	fn make(client: &Self::Client, model: impl Into<String>) -> Self {
+		Self::new(client.clone(), model)
+	}
+
+	async fn image_generation(
+		&self,
+		generation_request: ImageGenerationRequest,
+	) -> Result<image_generation::ImageGenerationResponse<Self::Response>, ImageGenerationError> {
+		let body = serde_json::to_vec(&self.request_body(generation_request))?;
 
 		let request = self
 			.client
@@ -105,6 +396,10 @@ where
 			let status = response.status();
 			let text = http_client::text(response).await?;
 
+			if let Ok(err) = serde_json::from_str::<ApiErrorResponse>(&text) {
+				return Err(err.error.into());
+			}
+
 			return Err(ImageGenerationError::ProviderError(format!(
 				"{}: {}",
 				status, text,
@@ -113,9 +408,208 @@ where
 
 		let text = http_client::text(response).await?;
 
-		match serde_json::from_str::<ApiResponse<ImageGenerationResponse>>(&text)? {
-			ApiResponse::Ok(response) => response.try_into(),
-			ApiResponse::Err(err) => Err(ImageGenerationError::ProviderError(err.message)),
+		parse_response(&text)
+	}
+
+	fn stream_image_generation(
+		&self,
+		generation_request: ImageGenerationRequest,
+	) -> impl Stream<Item = Result<PartialImage<Self::Response>, ImageGenerationError>> + Send {
+		let request = serde_json::to_vec(&self.streaming_request_body(generation_request))
+			.map_err(ImageGenerationError::from)
+			.and_then(|body| {
+				self.client
+					.post("/images/generations")?
+					.body(body)
+					.map_err(|e| ImageGenerationError::HttpError(e.into()))
+			});
+
+		match request {
+			Ok(req) => stream_partial_images(self.client.clone(), req).left_stream(),
+			Err(error) => futures::stream::once(async { Err(error) }).right_stream(),
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::image_generation::ImageGenerationModel as _;
+
+	fn model(name: &str) -> ImageGenerationModel {
+		ImageGenerationModel::new(Client::new("test-key").unwrap(), name)
+	}
+
+	#[test]
+	fn test_gpt_image_1_request_serialization() {
+		let model = model(GPT_IMAGE_1)
+			.with_quality(ImageQuality::High)
+			.with_background(ImageBackground::Transparent)
+			.with_output_format(ImageOutputFormat::Webp);
+		let request = model
+			.image_generation_request()
+			.prompt("A red fox")
+			.width(1024)
+			.height(1536)
+			.build();
+
+		let body = model.request_body(request);
+
+		assert_eq!(
+			body,
+			json!({
+				"model": "gpt-image-1",
+				"prompt": "A red fox",
+				"size": "1024x1536",
+				"quality": "high",
+				"background": "transparent",
+				"output_format": "webp",
+			})
+		);
+	}
+
+	#[test]
+	fn test_dall_e_request_requests_base64() {
+		let model = model(DALL_E_3);
+		let request = model
+			.image_generation_request()
+			.prompt("A red fox")
+			.width(1024)
+			.height(1024)
+			.additional_params(json!({ "style": "vivid" }))
+			.build();
+
+		let body = model.request_body(request);
+
+		assert_eq!(body["response_format"], "b64_json");
+		assert_eq!(body["style"], "vivid");
+		assert!(body.get("quality").is_none());
+	}
+
+	#[test]
+	fn test_gpt_image_1_response_with_usage() {
+		let text = r#"
+        {
+            "created": 1713833628,
+            "background": "transparent",
+            "output_format": "png",
+            "quality": "high",
+            "size": "1024x1024",
+            "data": [
+                {
+                    "b64_json": "aGVsbG8="
+                }
+            ],
+            "usage": {
+                "total_tokens": 100,
+                "input_tokens": 50,
+                "output_tokens": 50,
+                "input_tokens_details": {
+                    "text_tokens": 10,
+                    "image_tokens": 40
+                }
+            }
+        }
+        "#;
+
+		let response = parse_response(text).unwrap();
+
+		assert_eq!(response.image, b"hello");
+		assert_eq!(
+			response.response.background,
+			Some(ImageBackground::Transparent)
+		);
+		assert_eq!(
+			response.response.output_format,
+			Some(ImageOutputFormat::Png)
+		);
+		let usage = response.response.usage.unwrap();
+		assert_eq!(usage.total_tokens, 100);
+		assert_eq!(usage.input_tokens_details.unwrap().image_tokens, 40);
+	}
+
+	#[test]
+	fn test_response_without_image_is_an_error() {
+		let text = r#"{ "created": 1713833628, "data": [{ "url": "https://example.com/a.png" }] }"#;
+
+		let err = parse_response(text).unwrap_err();
+		assert!(matches!(err, ImageGenerationError::ResponseError(_)));
+	}
+
+	#[test]
+	fn test_content_policy_rejection() {
+		let text = r#"
+        {
+            "error": {
+                "code": "moderation_blocked",
+                "message": "Your request was rejected by the safety system.",
+                "param": null,
+                "type": "image_generation_user_error"
+            }
+        }
+        "#;
+
+		let err = parse_response(text).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"ProviderError: Request rejected by content policy: Your request was rejected by the safety system."
+		);
+	}
+
+	#[test]
+	fn test_streaming_request_serialization() {
+		let model = model(GPT_IMAGE_1).with_partial_images(2);
+		let request = model
+			.image_generation_request()
+			.prompt("A red fox")
+			.width(1024)
+			.height(1024)
+			.build();
+
+		let body = model.streaming_request_body(request);
+
+		assert_eq!(body["stream"], true);
+		assert_eq!(body["partial_images"], 2);
+	}
+
+	#[tokio::test]
+	async fn test_stream_partial_images() {
+		use crate::test_utils::MockSseClient;
+
+		let sse = concat!(
+			"event: image_generation.partial_image\n",
+			"data: {\"type\":\"image_generation.partial_image\",\"b64_json\":\"Zmlyc3Q=\",\"created_at\":1713833628,\"size\":\"1024x1024\",\"quality\":\"high\",\"background\":\"opaque\",\"output_format\":\"png\",\"partial_image_index\":0}\n\n",
+			"event: image_generation.partial_image\n",
+			"data: {\"type\":\"image_generation.partial_image\",\"b64_json\":\"c2Vjb25k\",\"created_at\":1713833628,\"size\":\"1024x1024\",\"quality\":\"high\",\"background\":\"opaque\",\"output_format\":\"png\",\"partial_image_index\":1}\n\n",
+			"event: image_generation.completed\n",
+			"data: {\"type\":\"image_generation.completed\",\"b64_json\":\"aGVsbG8=\",\"created_at\":1713833629,\"size\":\"1024x1024\",\"quality\":\"high\",\"background\":\"opaque\",\"output_format\":\"png\",\"usage\":{\"total_tokens\":100,\"input_tokens\":50,\"output_tokens\":50,\"input_tokens_details\":{\"text_tokens\":50,\"image_tokens\":0}}}\n\n",
+		);
+
+		let req = http::Request::builder()
+			.method("POST")
+			.uri("http://localhost/v1/images/generations")
+			.body(Vec::new())
+			.unwrap();
+
+		let items: Vec<_> = stream_partial_images(MockSseClient::new(sse), req)
+			.collect()
+			.await;
+		assert_eq!(items.len(), 3);
+
+		let mut items = items.into_iter().map(Result::unwrap);
+		let Some(PartialImage::Partial { index: 0, image }) = items.next() else {
+			panic!("expected the first partial image");
+		};
+		assert_eq!(image, b"first");
+		let Some(PartialImage::Partial { index: 1, image }) = items.next() else {
+			panic!("expected the second partial image");
+		};
+		assert_eq!(image, b"second");
+		let Some(PartialImage::Final(response)) = items.next() else {
+			panic!("expected the final image");
+		};
+		assert_eq!(response.image, b"hello");
+		assert_eq!(response.response.created, 1713833629);
+		assert_eq!(response.response.usage.unwrap().total_tokens, 100);
+	}
+}
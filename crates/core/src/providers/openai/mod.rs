@@ -11,6 +11,8 @@
 pub mod client;
 pub mod completion;
 pub mod embedding;
+pub mod error;
+pub mod moderation;
 pub mod responses_api;
 
 #[cfg(feature = "audio")]
@@ -28,6 +30,33 @@ pub mod transcription;
 pub use client::*;
 pub use embedding::*;
 
+/// Token counter for an OpenAI model: its exact BPE encoding with the `tiktoken` feature,
+/// otherwise (or for unknown models) a heuristic estimate.
+pub(crate) fn token_counter(
+	#[cfg_attr(not(feature = "tiktoken"), allow(unused_variables))] model: &str,
+) -> std::sync::Arc<dyn crate::completion::TokenCounter> {
+	#[cfg(feature = "tiktoken")]
+	if let Some(counter) = crate::completion::tokens::TiktokenCounter::for_model(model) {
+		return std::sync::Arc::new(counter);
+	}
+
+	std::sync::Arc::new(crate::completion::HeuristicTokenCounter::default())
+}
+
+/// Checks that apply to both the Chat Completions and the Responses APIs.
+pub(crate) fn validate_request(
+	request: &crate::completion::CompletionRequest,
+	report: &mut crate::completion::ValidationReport,
+) {
+	if let Some(temperature) = request.temperature
+		&& temperature > 2.0
+	{
+		report.error(format!(
+			"OpenAI's `temperature` must be between 0 and 2, got {temperature}"
+		));
+	}
+}
+
 /// Recursively ensures all object schemas in a JSON schema respect OpenAI structured output restrictions.
 /// Nested arrays, schema $defs, object properties and enums should be handled through this method
 pub(crate) fn sanitize_schema(schema: &mut serde_json::Value) {
@@ -43,27 +72,35 @@ pub(crate) fn sanitize_schema(schema: &mut serde_json::Value) {
 			obj.insert("additionalProperties".to_string(), Value::Bool(false));
 		}
 
-		// This is also required by OpenAI's Responses API
-		// Source: https://platform.openai.com/docs/guides/structured-outputs#all-fields-must-be-required
-		if let Some(Value::Object(properties)) = obj.get("properties") {
-			let prop_keys = properties.keys().cloned().map(Value::String).collect();
-			obj.insert("required".to_string(), Value::Array(prop_keys));
-		}
-
-		if let Some(defs) = obj.get_mut("$defs")
-			&& let Value::Object(defs_obj) = defs
-		{
-			for (_, def_schema) in defs_obj.iter_mut() {
-				sanitize_schema(def_schema);
+		for key in ["$defs", "definitions"] {
+			if let Some(Value::Object(defs)) = obj.get_mut(key) {
+				for (_, def_schema) in defs.iter_mut() {
+					sanitize_schema(def_schema);
+				}
 			}
 		}
 
-		if let Some(properties) = obj.get_mut("properties")
-			&& let Value::Object(props) = properties
-		{
-			for (_, prop_value) in props.iter_mut() {
+		// This is also required by OpenAI's Responses API. Optional properties stay optional
+		// by accepting `null` instead.
+		// Source: https://platform.openai.com/docs/guides/structured-outputs#all-fields-must-be-required
+		let required = match obj.get("required") {
+			Some(Value::Array(required)) => required.clone(),
+			_ => Vec::new(),
+		};
+		if let Some(Value::Object(properties)) = obj.get_mut("properties") {
+			for (name, prop_value) in properties.iter_mut() {
 				sanitize_schema(prop_value);
+
+				if !required
+					.iter()
+					.any(|required| required.as_str() == Some(name))
+				{
+					make_nullable(prop_value);
+				}
 			}
+
+			let prop_keys = properties.keys().cloned().map(Value::String).collect();
+			obj.insert("required".to_string(), Value::Array(prop_keys));
 		}
 
 		if let Some(items) = obj.get_mut("items") {
@@ -85,12 +122,10 @@ pub(crate) fn sanitize_schema(schema: &mut serde_json::Value) {
 			}
 		}
 
-		// should handle Enums (anyOf/oneOf)
-		for key in ["anyOf", "oneOf", "allOf"] {
-			if let Some(variants) = obj.get_mut(key)
-				&& let Value::Array(variants_array) = variants
-			{
-				for variant in variants_array.iter_mut() {
+		// should handle Enums (anyOf/allOf)
+		for key in ["anyOf", "allOf"] {
+			if let Some(Value::Array(variants)) = obj.get_mut(key) {
+				for variant in variants.iter_mut() {
 					sanitize_schema(variant);
 				}
 			}
@@ -98,6 +133,223 @@ pub(crate) fn sanitize_schema(schema: &mut serde_json::Value) {
 	}
 }
 
+/// Makes a schema also accept `null`: `null` is added to its `type` (and `enum`) when the
+/// schema has one, otherwise the schema becomes a variant of an `anyOf` with a `null` schema.
+fn make_nullable(schema: &mut serde_json::Value) {
+	use serde_json::{Map, Value, json};
+
+	fn is_nullable(schema: &Value) -> bool {
+		match schema.get("type") {
+			Some(Value::String(ty)) if ty == "null" => return true,
+			Some(Value::Array(types)) if types.iter().any(|ty| ty == "null") => return true,
+			_ => {}
+		}
+
+		matches!(
+			schema.get("anyOf"),
+			Some(Value::Array(variants)) if variants.iter().any(is_nullable)
+		)
+	}
+
+	let Value::Object(obj) = schema else {
+		return;
+	};
+
+	if is_nullable(&Value::Object(obj.clone())) {
+		return;
+	}
+
+	match obj.get_mut("type") {
+		Some(Value::String(ty)) => {
+			let ty = Value::String(std::mem::take(ty));
+			obj.insert("type".to_string(), json!([ty, "null"]));
+		}
+		Some(Value::Array(types)) => types.push(json!("null")),
+		_ => {
+			let inner = std::mem::replace(obj, Map::new());
+			obj.insert("anyOf".to_string(), json!([inner, { "type": "null" }]));
+			return;
+		}
+	}
+
+	if let Some(Value::Array(values)) = obj.get_mut("enum")
+		&& !values.contains(&Value::Null)
+	{
+		values.push(Value::Null);
+	}
+}
+
 #[cfg(feature = "audio")]
-pub use audio_generation::{TTS_1, TTS_1_HD};
+pub use audio_generation::{GPT_4O_MINI_TTS, TTS_1, TTS_1_HD};
 pub use transcription::*;
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::sanitize_schema;
+
+	#[test]
+	fn test_sanitize_tagged_enum() {
+		let mut schema = json!({
+			"type": "object",
+			"properties": {
+				"shape": {
+					"oneOf": [
+						{
+							"type": "object",
+							"properties": {
+								"kind": { "type": "string", "const": "circle" },
+								"radius": { "type": "number" }
+							},
+							"required": ["kind", "radius"]
+						},
+						{
+							"type": "object",
+							"properties": {
+								"kind": { "type": "string", "const": "square" },
+								"side": { "type": "number" },
+								"label": { "type": "string" }
+							},
+							"required": ["kind", "side"]
+						}
+					]
+				}
+			},
+			"required": ["shape"]
+		});
+
+		sanitize_schema(&mut schema);
+
+		assert_eq!(
+			schema,
+			json!({
+				"type": "object",
+				"properties": {
+					"shape": {
+						"anyOf": [
+							{
+								"type": "object",
+								"properties": {
+									"kind": { "type": "string", "const": "circle" },
+									"radius": { "type": "number" }
+								},
+								"required": ["kind", "radius"],
+								"additionalProperties": false
+							},
+							{
+								"type": "object",
+								"properties": {
+									"kind": { "type": "string", "const": "square" },
+									"side": { "type": "number" },
+									"label": { "type": ["string", "null"] }
+								},
+								"required": ["kind", "label", "side"],
+								"additionalProperties": false
+							}
+						]
+					}
+				},
+				"required": ["shape"],
+				"additionalProperties": false
+			})
+		);
+	}
+
+	#[test]
+	fn test_sanitize_optional_nested_object() {
+		let mut schema = json!({
+			"type": "object",
+			"properties": {
+				"name": { "type": "string" },
+				"address": {
+					"type": "object",
+					"properties": {
+						"city": { "type": "string" },
+						"zip": { "type": ["string", "null"] }
+					},
+					"required": ["city"]
+				},
+				"unit": { "type": "string", "enum": ["metric", "imperial"] },
+				"owner": { "$ref": "#/$defs/Person" }
+			},
+			"required": ["name"],
+			"$defs": {
+				"Person": {
+					"type": "object",
+					"properties": { "email": { "type": "string" } }
+				}
+			}
+		});
+
+		sanitize_schema(&mut schema);
+
+		assert_eq!(
+			schema,
+			json!({
+				"type": "object",
+				"properties": {
+					"name": { "type": "string" },
+					"address": {
+						"type": ["object", "null"],
+						"properties": {
+							"city": { "type": "string" },
+							"zip": { "type": ["string", "null"] }
+						},
+						"required": ["city", "zip"],
+						"additionalProperties": false
+					},
+					"unit": { "type": ["string", "null"], "enum": ["metric", "imperial", null] },
+					"owner": { "anyOf": [{ "$ref": "#/$defs/Person" }, { "type": "null" }] }
+				},
+				"required": ["address", "name", "owner", "unit"],
+				"additionalProperties": false,
+				"$defs": {
+					"Person": {
+						"type": "object",
+						"properties": { "email": { "type": ["string", "null"] } },
+						"required": ["email"],
+						"additionalProperties": false
+					}
+				}
+			})
+		);
+	}
+
+	#[test]
+	fn test_sanitize_array_of_objects() {
+		let mut schema = json!({
+			"type": "object",
+			"properties": {
+				"items": {
+					"type": "array",
+					"items": {
+						"type": "object",
+						"properties": {
+							"id": { "type": "integer" },
+							"note": { "type": "string" }
+						},
+						"required": ["id"]
+					}
+				}
+			},
+			"required": ["items"]
+		});
+
+		sanitize_schema(&mut schema);
+
+		assert_eq!(
+			schema["properties"]["items"]["items"],
+			json!({
+				"type": "object",
+				"properties": {
+					"id": { "type": "integer" },
+					"note": { "type": ["string", "null"] }
+				},
+				"required": ["id", "note"],
+				"additionalProperties": false
+			})
+		);
+		assert_eq!(schema["properties"]["items"]["type"], "array");
+	}
+}
@@ -0,0 +1,454 @@
+//! OpenAI moderation API, which classifies text and images as potentially harmful.
+//!
+//! # Example
+//! ```
+//! use clankers::providers::openai;
+//!
+//! let client = openai::Client::from_env();
+//! let moderation = client.moderation_model(openai::moderation::OMNI_MODERATION_LATEST);
+//!
+//! let response = moderation
+//!     .moderate_text("I want to hurt them.")
+//!     .await
+//!     .expect("Failed to moderate the text");
+//!
+//! println!("Flagged for {:?}", response.flagged_categories());
+//! ```
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::Client;
+use super::error::parse_api_error;
+use crate::agent::Moderator;
+use crate::completion::ApiError;
+use crate::http_client::{self, HttpClientExt};
+use crate::message::{AssistantContent, Message, UserContent};
+use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
+
+/// `omni-moderation-latest` moderation model, which supports text and images
+pub const OMNI_MODERATION_LATEST: &str = "omni-moderation-latest";
+/// `text-moderation-latest` legacy moderation model, which only supports text
+pub const TEXT_MODERATION_LATEST: &str = "text-moderation-latest";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModerationError {
+	/// Http error (e.g.: connection error, timeout, etc.)
+	#[error("HttpError: {0}")]
+	HttpError(#[from] http_client::Error),
+
+	/// Json error (e.g.: serialization, deserialization)
+	#[error("JsonError: {0}")]
+	JsonError(#[from] serde_json::Error),
+
+	/// Error response of the API
+	#[error("ApiError: {0}")]
+	ApiError(#[from] ApiError),
+
+	/// Error parsing the moderation response
+	#[error("ResponseError: {0}")]
+	ResponseError(String),
+}
+
+/// An item to classify, moderated together with the other items of a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModerationInput {
+	Text { text: String },
+	ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageUrl {
+	/// A URL or a base64-encoded data URL
+	pub url: String,
+}
+
+impl ModerationInput {
+	pub fn text(text: impl Into<String>) -> Self {
+		Self::Text { text: text.into() }
+	}
+
+	pub fn image_url(url: impl Into<String>) -> Self {
+		Self::ImageUrl {
+			image_url: ImageUrl { url: url.into() },
+		}
+	}
+
+	/// The text and images of a message. Other content, such as tool results, isn't moderated.
+	pub fn from_message(message: &Message) -> Vec<Self> {
+		match message {
+			Message::User { content } => content
+				.iter()
+				.filter_map(|content| match content {
+					UserContent::Text(text) => Some(Self::text(&text.text)),
+					UserContent::Image(image) => {
+						image.clone().try_into_url().ok().map(Self::image_url)
+					}
+					_ => None,
+				})
+				.collect(),
+			Message::Assistant { content, .. } => content
+				.iter()
+				.filter_map(|content| match content {
+					AssistantContent::Text(text) => Some(Self::text(&text.text)),
+					_ => None,
+				})
+				.collect(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ModerationResponse {
+	pub id: String,
+	pub model: String,
+	/// One result per request
+	pub results: Vec<ModerationResult>,
+}
+
+impl ModerationResponse {
+	/// Whether any result was flagged.
+	pub fn flagged(&self) -> bool {
+		self.results.iter().any(|result| result.flagged)
+	}
+
+	/// The categories flagged in any result, in the order of [Categories::iter].
+	pub fn flagged_categories(&self) -> Vec<&'static str> {
+		let mut flagged: Vec<&'static str> = vec![];
+		for category in self
+			.results
+			.iter()
+			.filter(|result| result.flagged)
+			.flat_map(|result| result.categories.flagged())
+		{
+			if !flagged.contains(&category) {
+				flagged.push(category);
+			}
+		}
+		flagged
+	}
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ModerationResult {
+	/// Whether any of the categories was flagged
+	pub flagged: bool,
+	/// Whether each category was flagged
+	pub categories: Categories<bool>,
+	/// The confidence of the model for each category, between 0 and 1
+	pub category_scores: Categories<f64>,
+}
+
+/// A value for each moderation category. Categories missing from a response, e.g. those not
+/// supported by legacy models, are defaulted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Categories<T> {
+	pub harassment: T,
+	#[serde(rename = "harassment/threatening")]
+	pub harassment_threatening: T,
+	pub hate: T,
+	#[serde(rename = "hate/threatening")]
+	pub hate_threatening: T,
+	pub illicit: T,
+	#[serde(rename = "illicit/violent")]
+	pub illicit_violent: T,
+	#[serde(rename = "self-harm")]
+	pub self_harm: T,
+	#[serde(rename = "self-harm/intent")]
+	pub self_harm_intent: T,
+	#[serde(rename = "self-harm/instructions")]
+	pub self_harm_instructions: T,
+	pub sexual: T,
+	#[serde(rename = "sexual/minors")]
+	pub sexual_minors: T,
+	pub violence: T,
+	#[serde(rename = "violence/graphic")]
+	pub violence_graphic: T,
+}
+
+impl<T> Categories<T> {
+	/// The value of each category, with the category names used by the API.
+	pub fn iter(&self) -> impl Iterator<Item = (&'static str, &T)> {
+		[
+			("harassment", &self.harassment),
+			("harassment/threatening", &self.harassment_threatening),
+			("hate", &self.hate),
+			("hate/threatening", &self.hate_threatening),
+			("illicit", &self.illicit),
+			("illicit/violent", &self.illicit_violent),
+			("self-harm", &self.self_harm),
+			("self-harm/intent", &self.self_harm_intent),
+			("self-harm/instructions", &self.self_harm_instructions),
+			("sexual", &self.sexual),
+			("sexual/minors", &self.sexual_minors),
+			("violence", &self.violence),
+			("violence/graphic", &self.violence_graphic),
+		]
+		.into_iter()
+	}
+}
+
+impl Categories<bool> {
+	/// The names of the flagged categories.
+	pub fn flagged(&self) -> Vec<&'static str> {
+		self.iter()
+			.filter(|(_, flagged)| **flagged)
+			.map(|(name, _)| name)
+			.collect()
+	}
+}
+
+/// An OpenAI moderation model, using the `/moderations` endpoint.
+#[derive(Clone)]
+pub struct ModerationModel<T = reqwest::Client> {
+	client: Client<T>,
+	pub model: String,
+}
+
+impl<T> ModerationModel<T> {
+	pub fn new(client: Client<T>, model: impl Into<String>) -> Self {
+		Self {
+			client,
+			model: model.into(),
+		}
+	}
+}
+
+impl<T> ModerationModel<T>
+where
+	T: HttpClientExt + Clone + std::fmt::Debug + Default + 'static,
+{
+	/// Classifies the inputs, which are moderated together and get a single result.
+	pub async fn moderate(
+		&self,
+		input: Vec<ModerationInput>,
+	) -> Result<ModerationResponse, ModerationError> {
+		let body = serde_json::to_vec(&json!({
+			"model": self.model,
+			"input": input,
+		}))?;
+
+		let req = self
+			.client
+			.post("/moderations")?
+			.body(body)
+			.map_err(http_client::Error::Protocol)?;
+
+		let response = self.client.send(req).await?;
+
+		if !response.status().is_success() {
+			let status = response.status();
+			let text = http_client::text(response).await?;
+			return Err(parse_api_error(status, text).into());
+		}
+
+		let body: Vec<u8> = response.into_body().await?;
+		let response: ModerationResponse = serde_json::from_slice(&body)?;
+
+		if response.results.is_empty() {
+			return Err(ModerationError::ResponseError(
+				"Moderation response contained no results".into(),
+			));
+		}
+
+		Ok(response)
+	}
+
+	/// Classifies a single text.
+	pub async fn moderate_text(
+		&self,
+		text: impl Into<String>,
+	) -> Result<ModerationResponse, ModerationError> {
+		self.moderate(vec![ModerationInput::text(text)]).await
+	}
+}
+
+impl<T> Moderator for ModerationModel<T>
+where
+	T: HttpClientExt
+		+ Clone
+		+ std::fmt::Debug
+		+ Default
+		+ WasmCompatSend
+		+ WasmCompatSync
+		+ 'static,
+{
+	type Error = ModerationError;
+
+	async fn moderate(&self, prompt: &Message) -> Result<Vec<String>, Self::Error> {
+		let input = ModerationInput::from_message(prompt);
+		if input.is_empty() {
+			return Ok(vec![]);
+		}
+
+		let response = ModerationModel::moderate(self, input).await?;
+
+		Ok(response
+			.flagged_categories()
+			.into_iter()
+			.map(String::from)
+			.collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::OneOrMany;
+	use crate::completion::ProviderErrorKind;
+	use crate::message::{ImageMediaType, UserContent};
+	use crate::test_utils::MockSseClient;
+
+	const RESPONSE: &str = r#"{
+		"id": "modr-0d9740456c391e43c445bf0f010940c7",
+		"model": "omni-moderation-latest",
+		"results": [
+			{
+				"flagged": true,
+				"categories": {
+					"harassment": true,
+					"harassment/threatening": true,
+					"sexual": false,
+					"hate": false,
+					"hate/threatening": false,
+					"illicit": false,
+					"illicit/violent": false,
+					"self-harm/intent": false,
+					"self-harm/instructions": false,
+					"self-harm": false,
+					"sexual/minors": false,
+					"violence": true,
+					"violence/graphic": true
+				},
+				"category_scores": {
+					"harassment": 0.8189693396524255,
+					"harassment/threatening": 0.804985420696006,
+					"sexual": 1.573112165348997e-6,
+					"hate": 0.007562942636942845,
+					"hate/threatening": 0.004208854591835476,
+					"illicit": 0.030535955153511665,
+					"illicit/violent": 0.008925306722380033,
+					"self-harm/intent": 0.00023023930975076432,
+					"self-harm/instructions": 0.0002293869201073356,
+					"self-harm": 0.012598046106750154,
+					"sexual/minors": 2.212566909570261e-8,
+					"violence": 0.9999992735124786,
+					"violence/graphic": 0.843064871157054
+				},
+				"category_applied_input_types": {
+					"harassment": ["text"],
+					"violence": ["text", "image"]
+				}
+			}
+		]
+	}"#;
+
+	fn model(http_client: MockSseClient) -> ModerationModel<MockSseClient> {
+		let client = Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap();
+
+		ModerationModel::new(client, OMNI_MODERATION_LATEST)
+	}
+
+	#[test]
+	fn test_response_deserialization() {
+		let response: ModerationResponse = serde_json::from_str(RESPONSE).unwrap();
+
+		assert!(response.flagged());
+		assert_eq!(
+			response.flagged_categories(),
+			[
+				"harassment",
+				"harassment/threatening",
+				"violence",
+				"violence/graphic"
+			]
+		);
+
+		let result = &response.results[0];
+		assert!(result.categories.violence_graphic);
+		assert!(!result.categories.self_harm_intent);
+		assert_eq!(result.category_scores.violence, 0.9999992735124786);
+	}
+
+	#[test]
+	fn test_legacy_response_deserialization() {
+		// Legacy models don't return the `illicit` categories
+		let response: ModerationResponse = serde_json::from_str(
+			r#"{
+				"id": "modr-1",
+				"model": "text-moderation-007",
+				"results": [{
+					"flagged": false,
+					"categories": {"hate": false, "violence": false},
+					"category_scores": {"hate": 0.01, "violence": 0.02}
+				}]
+			}"#,
+		)
+		.unwrap();
+
+		assert!(!response.flagged());
+		assert!(response.flagged_categories().is_empty());
+		assert!(!response.results[0].categories.illicit);
+		assert_eq!(response.results[0].category_scores.illicit, 0.0);
+	}
+
+	#[tokio::test]
+	async fn test_moderate_message() {
+		let http_client = MockSseClient::default().with_json_response(RESPONSE);
+		let model = model(http_client.clone());
+
+		let prompt = Message::User {
+			content: OneOrMany::many(vec![
+				UserContent::text("Look at this"),
+				UserContent::image_url("https://example.com/image.png", None, None),
+				UserContent::image_base64("aGVsbG8=", Some(ImageMediaType::PNG), None),
+			])
+			.unwrap(),
+		};
+		let categories = Moderator::moderate(&model, &prompt).await.unwrap();
+		assert_eq!(
+			categories,
+			[
+				"harassment",
+				"harassment/threatening",
+				"violence",
+				"violence/graphic"
+			]
+		);
+
+		assert!(http_client.request_uris()[0].ends_with("/v1/moderations"));
+		assert_eq!(
+			http_client.request_bodies()[0],
+			json!({
+				"model": "omni-moderation-latest",
+				"input": [
+					{"type": "text", "text": "Look at this"},
+					{"type": "image_url", "image_url": {"url": "https://example.com/image.png"}},
+					{"type": "image_url", "image_url": {"url": "data:image/png;base64,aGVsbG8="}}
+				]
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_api_error() {
+		let http_client = MockSseClient::default().with_status_response(
+			429,
+			r#"{"error": {"message": "Rate limit reached", "type": "requests", "code": "rate_limit_exceeded"}}"#,
+		);
+
+		let err = model(http_client).moderate_text("Hello").await.unwrap_err();
+		match err {
+			ModerationError::ApiError(error) => {
+				assert!(matches!(error.kind, ProviderErrorKind::RateLimited { .. }));
+				assert_eq!(error.message, "Rate limit reached");
+			}
+			other => panic!("Expected an ApiError, got {other:?}"),
+		}
+	}
+}
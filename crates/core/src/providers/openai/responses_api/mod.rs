@@ -7,14 +7,26 @@
 //! let openai_client = clankers::providers::openai::Client::from_env();
 //! let model = openai_client.completion_model("gpt-4o").completions_api();
 //! ```
+//!
+//! The conversation can also be stored by OpenAI instead of being sent again with every request,
+//! see [ResponsesCompletionModel::with_server_side_state].
+//!
+//! Tools run by OpenAI, like the web search, can be added with
+//! [ResponsesCompletionModel::with_hosted_tool]. Their calls and the citations of their results
+//! are kept in the [raw response](types::CompletionResponse).
+use std::sync::{Arc, Mutex};
+
 use tracing::{Instrument, Level, enabled, info_span};
 
 use super::Client;
+use super::error::parse_api_error;
 use super::responses_api::streaming::StreamingCompletionResponse;
 use crate::completion::CompletionError;
 use crate::http_client::HttpClientExt;
+use crate::message::Message;
+use crate::telemetry::SpanCombinator;
 use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
-use crate::{completion, http_client};
+use crate::{OneOrMany, completion, http_client};
 
 pub mod streaming;
 pub mod types;
@@ -27,6 +39,14 @@ pub struct ResponsesCompletionModel<T = reqwest::Client> {
 	pub(crate) client: Client<T>,
 	/// Name of the model (e.g.: gpt-3.5-turbo-1106)
 	pub model: String,
+	/// The stored response the next request continues from, shared between clones
+	previous_response_id: Arc<Mutex<Option<String>>>,
+	/// Whether to continue from the last response of this model
+	server_side_state: bool,
+	/// Whether OpenAI stores the responses, OpenAI's default (`true`) when `None`
+	store: Option<bool>,
+	/// Tools run by OpenAI, sent with every request
+	hosted_tools: Vec<HostedTool>,
 }
 
 impl<T> ResponsesCompletionModel<T>
@@ -38,13 +58,59 @@ where
 		Self {
 			client,
 			model: model.into(),
+			previous_response_id: Arc::default(),
+			server_side_state: false,
+			store: None,
+			hosted_tools: Vec::new(),
 		}
 	}
 
 	pub fn with_model(client: Client<T>, model: &str) -> Self {
-		Self {
-			client,
-			model: model.to_string(),
+		Self::new(client, model)
+	}
+
+	/// Continue the conversation of a stored response: only the messages after the last
+	/// assistant message of the chat history are sent, along with the response ID, OpenAI
+	/// restoring the rest of the conversation (including the preamble) from the stored response.
+	///
+	/// The returned model starts a conversation state of its own, shared with its later clones
+	/// only, so the model it was created from keeps continuing from its own responses.
+	pub fn with_previous_response_id(mut self, previous_response_id: impl Into<String>) -> Self {
+		self.previous_response_id = Arc::new(Mutex::new(Some(previous_response_id.into())));
+		self
+	}
+
+	/// Continue each request from the last response of this model (or its clones), as with
+	/// [ResponsesCompletionModel::with_previous_response_id]. The first request, without any
+	/// previous response, sends the whole conversation.
+	///
+	/// This requires the responses to be stored by OpenAI, which is the default.
+	pub fn with_server_side_state(mut self, server_side_state: bool) -> Self {
+		self.server_side_state = server_side_state;
+		self
+	}
+
+	/// Set whether OpenAI stores the responses, so they can be continued from or retrieved later.
+	pub fn with_store(mut self, store: bool) -> Self {
+		self.store = Some(store);
+		self
+	}
+
+	/// Add a tool run by OpenAI to every request, alongside the function tools of the request.
+	pub fn with_hosted_tool(mut self, tool: HostedTool) -> Self {
+		self.hosted_tools.push(tool);
+		self
+	}
+
+	/// The ID of the stored response the next request will continue from, if any.
+	pub fn previous_response_id(&self) -> Option<String> {
+		self.previous_response_id.lock().unwrap().clone()
+	}
+
+	/// Remembers the ID of the last response, when continuing from it.
+	pub(crate) fn set_response_id(&self, response_id: &str) {
+		if self.server_side_state {
+			*self.previous_response_id.lock().unwrap() = Some(response_id.to_string());
 		}
 	}
 
@@ -56,14 +122,47 @@ where
 	/// Attempt to create a completion request from [`crate::completion::CompletionRequest`].
 	pub(crate) fn create_completion_request(
 		&self,
-		completion_request: crate::completion::CompletionRequest,
+		mut completion_request: crate::completion::CompletionRequest,
 	) -> Result<CompletionRequest, CompletionError> {
-		let req = CompletionRequest::try_from((self.model.clone(), completion_request))?;
+		let previous_response_id = completion_request
+			.additional_params
+			.as_ref()
+			.and_then(|params| params.get("previous_response_id")?.as_str())
+			.map(str::to_string)
+			.or_else(|| self.previous_response_id());
+
+		if previous_response_id.is_some() {
+			skip_stored_messages(&mut completion_request);
+		}
+
+		let mut req = CompletionRequest::try_from((self.model.clone(), completion_request))?;
+		req.additional_parameters.previous_response_id = previous_response_id;
+		if req.additional_parameters.store.is_none() {
+			req.additional_parameters.store = self.store;
+		}
+		req.tools
+			.extend(self.hosted_tools.iter().cloned().map(ResponsesTool::Hosted));
 
 		Ok(req)
 	}
 }
 
+/// Removes the preamble and the messages up to the last assistant message, which are part of the
+/// conversation of the previous response. The messages left are the new prompt, or the results of
+/// the tool calls of the previous response.
+fn skip_stored_messages(request: &mut crate::completion::CompletionRequest) {
+	let messages = request.chat_history.iter().cloned().collect::<Vec<_>>();
+	let new_messages = messages
+		.iter()
+		.rposition(|message| matches!(message, Message::Assistant { .. }))
+		.map_or(0, |index| index + 1);
+
+	if let Ok(chat_history) = OneOrMany::many(messages[new_messages..].to_vec()) {
+		request.chat_history = chat_history;
+	}
+	request.preamble = None;
+}
+
 impl<T> completion::CompletionModel for ResponsesCompletionModel<T>
 where
 	T: HttpClientExt
@@ -83,10 +182,31 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
+	fn token_counter(&self) -> std::sync::Arc<dyn completion::TokenCounter> {
+		super::token_counter(&self.model)
+	}
+
+	fn validate_request(
+		&self,
+		request: &crate::completion::CompletionRequest,
+		report: &mut completion::ValidationReport,
+	) {
+		super::validate_request(request, report);
+
+		if self.server_side_state && self.store == Some(false) {
+			report.error("Server-side state requires the responses to be stored");
+		}
+	}
+
 	async fn completion(
 		&self,
 		completion_request: crate::completion::CompletionRequest,
 	) -> Result<completion::CompletionResponse<Self::Response>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -98,6 +218,8 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
 				gen_ai.input.messages = tracing::field::Empty,
 				gen_ai.output.messages = tracing::field::Empty,
 			)
@@ -108,6 +230,7 @@ where
 		span.record("gen_ai.provider.name", "openai");
 		span.record("gen_ai.request.model", &self.model);
 		let request = self.create_completion_request(completion_request)?;
+		span.record_input_messages(&request.input.iter().collect::<Vec<_>>());
 		let body = serde_json::to_vec(&request)?;
 
 		if enabled!(Level::TRACE) {
@@ -125,17 +248,25 @@ where
 			.map_err(|e| CompletionError::HttpError(e.into()))?;
 
 		async move {
-			let response = self.client.send(req).await?;
+			let response = self
+				.client
+				.send(req)
+				.await
+				.map_err(|error| CompletionError::from_http_error(error, parse_api_error))?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 
 			if response.status().is_success() {
 				let t = http_client::text(response).await?;
 				let response = serde_json::from_str::<Self::Response>(&t)?;
+				self.set_response_id(&response.id);
 				let span = tracing::Span::current();
 				span.record("gen_ai.response.id", &response.id);
 				span.record("gen_ai.response.model", &response.model);
+				span.record_output_messages(&response.output);
 				if let Some(ref usage) = response.usage {
 					span.record("gen_ai.usage.output_tokens", usage.output_tokens);
 					span.record("gen_ai.usage.input_tokens", usage.input_tokens);
+					span.record_cost(self.client.pricing(), "openai", &self.model, usage);
 				}
 				if enabled!(Level::TRACE) {
 					tracing::trace!(
@@ -144,10 +275,15 @@ where
 						response = serde_json::to_string_pretty(&response)?
 					);
 				}
-				response.try_into()
+				completion::CompletionResponse::try_from(response)
+					.map(|response| response.with_response_metadata(response_metadata))
 			} else {
+				let status = response.status();
+				let headers = response.headers().clone();
 				let text = http_client::text(response).await?;
-				Err(CompletionError::ProviderError(text))
+				Err(CompletionError::ApiError(
+					parse_api_error(status, text).with_retry_after(&headers),
+				))
 			}
 		}
 		.instrument(span)
@@ -161,6 +297,388 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let request = self.client.apply_default_params(request);
 		ResponsesCompletionModel::stream(self, request).await
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::client::CompletionClient;
+	use crate::completion::{CompletionModel as _, ToolDefinition};
+	use crate::message::AssistantContent;
+	use crate::providers::openai;
+	use crate::test_utils::MockSseClient;
+
+	const RESPONSE: &str = r#"{
+		"id": "resp_1",
+		"object": "response",
+		"created_at": 1741290958,
+		"status": "completed",
+		"error": null,
+		"incomplete_details": null,
+		"instructions": null,
+		"max_output_tokens": null,
+		"model": "gpt-4.1-2025-04-14",
+		"output": [{
+			"type": "message",
+			"id": "msg_1",
+			"status": "completed",
+			"role": "assistant",
+			"content": [{ "type": "output_text", "text": "It is sunny in Paris.", "annotations": [] }]
+		}],
+		"tools": [],
+		"usage": {
+			"input_tokens": 12,
+			"input_tokens_details": { "cached_tokens": 0 },
+			"output_tokens": 6,
+			"output_tokens_details": { "reasoning_tokens": 0 },
+			"total_tokens": 18
+		}
+	}"#;
+
+	fn model(http_client: MockSseClient) -> ResponsesCompletionModel<MockSseClient> {
+		openai::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap()
+			.completion_model("gpt-4.1")
+	}
+
+	/// A prompt, the model's call to `get_weather` and its result.
+	fn tool_round_trip() -> Vec<Message> {
+		vec![
+			Message::user("What's the weather in Paris?"),
+			Message::Assistant {
+				id: None,
+				content: OneOrMany::one(AssistantContent::ToolCall(
+					crate::message::ToolCall::new(
+						"fc_1".to_string(),
+						crate::message::ToolFunction::new(
+							"get_weather".to_string(),
+							json!({ "location": "Paris" }),
+						),
+					)
+					.with_call_id("call_abc".to_string()),
+				)),
+			},
+			Message::tool_result_with_call_id("fc_1", Some("call_abc".to_string()), "Sunny"),
+		]
+	}
+
+	#[test]
+	fn test_previous_response_id_serialization() {
+		let model = model(MockSseClient::default());
+		let mut history = tool_round_trip();
+		let prompt = history.pop().unwrap();
+
+		let request = model
+			.completion_request(prompt.clone())
+			.preamble("You are a weather bot".to_string())
+			.messages(history.clone())
+			.tool(ToolDefinition {
+				name: "get_weather".to_string(),
+				description: "Get the weather".to_string(),
+				parameters: json!({ "type": "object" }),
+			})
+			.build();
+
+		let body = serde_json::to_value(model.create_completion_request(request.clone()).unwrap())
+			.unwrap();
+		assert!(body.get("previous_response_id").is_none());
+		assert!(body.get("store").is_none());
+		assert_eq!(body["input"].as_array().unwrap().len(), 4);
+
+		let model = model.with_previous_response_id("resp_0").with_store(true);
+		let body = serde_json::to_value(model.create_completion_request(request).unwrap()).unwrap();
+		assert_eq!(body["previous_response_id"], "resp_0");
+		assert_eq!(body["store"], true);
+		// Only the tool result is sent, the preamble and the tool call are part of `resp_0`
+		assert_eq!(
+			body["input"],
+			json!([{ "type": "function_call_output", "call_id": "call_abc", "output": "Sunny", "status": "completed" }])
+		);
+		assert_eq!(body["tools"].as_array().unwrap().len(), 1);
+
+		// Explicit additional parameters take precedence
+		let request = model
+			.completion_request(prompt)
+			.messages(history)
+			.additional_params(json!({ "previous_response_id": "resp_9", "store": false }))
+			.build();
+		let body = serde_json::to_value(model.create_completion_request(request).unwrap()).unwrap();
+		assert_eq!(body["previous_response_id"], "resp_9");
+		assert_eq!(body["store"], false);
+	}
+
+	#[tokio::test]
+	async fn test_server_side_state_chaining() {
+		let http_client = MockSseClient::default().with_json_response(RESPONSE);
+		let model = model(http_client.clone()).with_server_side_state(true);
+		assert_eq!(model.previous_response_id(), None);
+
+		let response = model
+			.completion_request("What's the weather in Paris?")
+			.preamble("You are a weather bot".to_string())
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(response.raw_response.id, "resp_1");
+		assert_eq!(model.previous_response_id().as_deref(), Some("resp_1"));
+
+		model
+			.completion_request("And tomorrow?")
+			.preamble("You are a weather bot".to_string())
+			.messages(vec![
+				Message::user("What's the weather in Paris?"),
+				Message::assistant("It is sunny in Paris."),
+			])
+			.send()
+			.await
+			.unwrap();
+
+		let bodies = http_client.request_bodies();
+		assert!(bodies[0].get("previous_response_id").is_none());
+		assert_eq!(bodies[0]["input"].as_array().unwrap().len(), 2);
+
+		assert_eq!(bodies[1]["previous_response_id"], "resp_1");
+		let input = bodies[1]["input"].as_array().unwrap();
+		assert_eq!(input.len(), 1);
+		assert_eq!(input[0]["role"], "user");
+		assert_eq!(input[0]["content"][0]["text"], "And tomorrow?");
+	}
+
+	#[test]
+	fn test_previous_response_id_does_not_affect_other_models() {
+		let base = model(MockSseClient::default()).with_server_side_state(true);
+		let clone = base.clone();
+		base.set_response_id("resp_1");
+
+		let derived = base.clone().with_previous_response_id("resp_0");
+		assert_eq!(derived.previous_response_id().as_deref(), Some("resp_0"));
+		assert_eq!(base.previous_response_id().as_deref(), Some("resp_1"));
+		assert_eq!(clone.previous_response_id().as_deref(), Some("resp_1"));
+
+		// The derived model continues from its own responses
+		derived.set_response_id("resp_2");
+		assert_eq!(
+			derived.clone().previous_response_id().as_deref(),
+			Some("resp_2")
+		);
+		assert_eq!(base.previous_response_id().as_deref(), Some("resp_1"));
+	}
+
+	#[test]
+	fn test_server_side_state_requires_store() {
+		let model = model(MockSseClient::default())
+			.with_server_side_state(true)
+			.with_store(false);
+
+		let err = model.completion_request("Hello").validate().unwrap_err();
+		assert!(err.to_string().contains("stored"), "{err}");
+	}
+
+	#[tokio::test]
+	async fn test_unknown_output_items_are_skipped() {
+		let mut response: serde_json::Value = serde_json::from_str(RESPONSE).unwrap();
+		response["output"].as_array_mut().unwrap().insert(
+			0,
+			json!({ "type": "hologram_call", "id": "hg_1", "status": "completed", "beams": [1, 2] }),
+		);
+		response["output"][1]["content"]
+			.as_array_mut()
+			.unwrap()
+			.push(json!({ "type": "output_hologram", "frames": 3 }));
+
+		let http_client = MockSseClient::default().with_json_response(response.to_string());
+		let response = model(http_client)
+			.completion_request("What's the weather in Paris?")
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(response.choice.len(), 1);
+		assert_eq!(
+			response.choice.first(),
+			AssistantContent::text("It is sunny in Paris.")
+		);
+		let types::Output::Unknown(item) = &response.raw_response.output[0] else {
+			panic!(
+				"expected an unknown item, got {:?}",
+				response.raw_response.output[0]
+			);
+		};
+		assert_eq!(item.kind, "hologram_call");
+		assert_eq!(item.fields["beams"], json!([1, 2]));
+		// The raw JSON is kept as is
+		assert_eq!(
+			serde_json::to_value(item).unwrap(),
+			json!({ "type": "hologram_call", "id": "hg_1", "status": "completed", "beams": [1, 2] })
+		);
+	}
+
+	#[tokio::test]
+	async fn test_only_unknown_output_items_is_an_error() {
+		let mut response: serde_json::Value = serde_json::from_str(RESPONSE).unwrap();
+		response["output"] = json!([{ "type": "hologram_call", "id": "hg_1" }]);
+
+		let http_client = MockSseClient::default().with_json_response(response.to_string());
+		let err = model(http_client)
+			.completion_request("What's the weather in Paris?")
+			.send()
+			.await
+			.expect_err("no usable content");
+		assert!(err.to_string().contains("empty"), "{err}");
+	}
+
+	#[test]
+	fn test_hosted_tools_serialization() {
+		let model = model(MockSseClient::default())
+			.with_hosted_tool(HostedTool::web_search())
+			.with_hosted_tool(HostedTool::file_search(["vs_1"]));
+
+		let request = model
+			.completion_request("What's the weather in Paris?")
+			.tool(ToolDefinition {
+				name: "get_weather".to_string(),
+				description: "Get the weather".to_string(),
+				parameters: json!({ "type": "object" }),
+			})
+			.build();
+		let body = serde_json::to_value(model.create_completion_request(request).unwrap()).unwrap();
+
+		let tools = body["tools"].as_array().unwrap();
+		assert_eq!(tools.len(), 3);
+		assert_eq!(tools[0]["type"], "function");
+		assert_eq!(tools[0]["name"], "get_weather");
+		assert_eq!(tools[1], json!({ "type": "web_search" }));
+		assert_eq!(
+			tools[2],
+			json!({ "type": "file_search", "vector_store_ids": ["vs_1"] })
+		);
+	}
+
+	#[tokio::test]
+	async fn test_web_search_response_with_citations() {
+		let response = json!({
+			"id": "resp_1",
+			"object": "response",
+			"created_at": 1741290958,
+			"status": "completed",
+			"error": null,
+			"incomplete_details": null,
+			"instructions": null,
+			"max_output_tokens": null,
+			"model": "gpt-4.1-2025-04-14",
+			"output": [
+				{
+					"type": "web_search_call",
+					"id": "ws_1",
+					"status": "completed",
+					"action": { "type": "search", "query": "weather in Paris today" }
+				},
+				{
+					"type": "message",
+					"id": "msg_1",
+					"status": "completed",
+					"role": "assistant",
+					"content": [{
+						"type": "output_text",
+						"text": "It is sunny in Paris (meteo.example).",
+						"annotations": [{
+							"type": "url_citation",
+							"url": "https://meteo.example/paris",
+							"title": "Paris weather",
+							"start_index": 22,
+							"end_index": 35
+						}]
+					}]
+				}
+			],
+			"tools": [{ "type": "web_search", "search_context_size": "medium", "user_location": { "type": "approximate" } }],
+			"usage": {
+				"input_tokens": 300,
+				"input_tokens_details": { "cached_tokens": 0 },
+				"output_tokens": 12,
+				"output_tokens_details": { "reasoning_tokens": 0 },
+				"total_tokens": 312
+			}
+		});
+
+		let http_client = MockSseClient::default().with_json_response(response.to_string());
+		let response = model(http_client)
+			.with_hosted_tool(HostedTool::web_search())
+			.completion_request("What's the weather in Paris?")
+			.send()
+			.await
+			.unwrap();
+
+		assert_eq!(response.choice.len(), 1);
+		assert_eq!(
+			response.choice.first(),
+			AssistantContent::text("It is sunny in Paris (meteo.example).")
+		);
+
+		let raw = &response.raw_response;
+		assert_eq!(
+			raw.output[0],
+			types::Output::WebSearchCall(types::WebSearchCall {
+				id: "ws_1".to_string(),
+				status: "completed".to_string(),
+				action: Some(types::WebSearchAction::Search {
+					query: Some("weather in Paris today".to_string()),
+					sources: vec![],
+				}),
+			})
+		);
+		assert!(matches!(
+			raw.tools[..],
+			[types::ResponsesTool::Hosted(HostedTool::WebSearch {
+				search_context_size: Some(types::SearchContextSize::Medium)
+			})]
+		));
+		assert_eq!(
+			raw.annotations().collect::<Vec<_>>(),
+			[&types::Annotation::UrlCitation {
+				url: "https://meteo.example/paris".to_string(),
+				title: Some("Paris weather".to_string()),
+				start_index: 22,
+				end_index: 35,
+			}]
+		);
+	}
+
+	#[test]
+	fn test_file_search_call_results() {
+		let output: types::Output = serde_json::from_value(json!({
+			"type": "file_search_call",
+			"id": "fs_1",
+			"status": "completed",
+			"queries": ["refund policy"],
+			"results": [{
+				"file_id": "file_1",
+				"filename": "policies.pdf",
+				"score": 0.91,
+				"text": "Refunds are accepted within 30 days.",
+				"attributes": {}
+			}]
+		}))
+		.unwrap();
+
+		let types::Output::FileSearchCall(call) = &output else {
+			panic!("expected a file search call, got {output:?}");
+		};
+		assert_eq!(call.queries, ["refund policy"]);
+		let result = &call.results.as_ref().unwrap()[0];
+		assert_eq!(result.filename.as_deref(), Some("policies.pdf"));
+		assert_eq!(
+			result.text.as_deref(),
+			Some("Refunds are accepted within 30 days.")
+		);
+		assert!(Vec::<crate::completion::AssistantContent>::from(output).is_empty());
+	}
+}
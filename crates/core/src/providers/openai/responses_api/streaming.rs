@@ -1,5 +1,7 @@
 //! The streaming module for the OpenAI Responses API.
 //! Please see the `openai_streaming` or `openai_streaming_with_tools` example for more practical usage.
+use std::collections::{HashMap, HashSet};
+
 use async_stream::stream;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
@@ -10,10 +12,12 @@ use super::types::{CompletionResponse, Output};
 use crate::completion::{CompletionError, GetTokenUsage};
 use crate::http_client::HttpClientExt;
 use crate::http_client::sse::{Event, GenericEventSource};
+use crate::providers::openai::error::parse_api_error;
 use crate::providers::openai::responses_api::ResponsesCompletionModel;
 use crate::providers::openai::responses_api::types::{ReasoningSummary, ResponsesUsage};
 use crate::streaming;
 use crate::streaming::RawStreamingChoice;
+use crate::telemetry::SpanCombinator;
 use crate::wasm_compat::WasmCompatSend;
 
 /// A streaming completion chunk.
@@ -30,6 +34,10 @@ pub enum StreamingCompletionChunk {
 /// The final streaming response from the OpenAI Responses API.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StreamingCompletionResponse {
+	/// The ID of the response, which can be continued from with
+	/// [ResponsesCompletionModel::with_previous_response_id]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub id: Option<String>,
 	/// Token usage
 	pub usage: ResponsesUsage,
 }
@@ -96,7 +104,7 @@ pub struct ItemChunk {
 #[serde(tag = "type")]
 pub enum ItemChunkKind {
 	#[serde(rename = "response.output_item.added")]
-	OutputItemAdded(StreamingItemDoneOutput),
+	OutputItemAdded(StreamingItemAddedOutput),
 	#[serde(rename = "response.output_item.done")]
 	OutputItemDone(StreamingItemDoneOutput),
 	#[serde(rename = "response.content_part.added")]
@@ -112,7 +120,7 @@ pub enum ItemChunkKind {
 	#[serde(rename = "response.refusal.done")]
 	RefusalDone(RefusalTextChunk),
 	#[serde(rename = "response.function_call_arguments.delta")]
-	FunctionCallArgsDelta(DeltaTextChunkWithItemId),
+	FunctionCallArgsDelta(ArgsDeltaChunk),
 	#[serde(rename = "response.function_call_arguments.done")]
 	FunctionCallArgsDone(ArgsTextChunk),
 	#[serde(rename = "response.reasoning_summary_part.added")]
@@ -125,6 +133,27 @@ pub enum ItemChunkKind {
 	ReasoningSummaryTextDone(SummaryTextChunk),
 }
 
+/// An output item as announced by `response.output_item.added`, before any of its content
+/// has been streamed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamingItemAddedOutput {
+	pub sequence_number: u64,
+	pub item: AddedItem,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AddedItem {
+	/// A function call. Its arguments follow as `response.function_call_arguments.delta` events.
+	FunctionCall {
+		id: String,
+		call_id: String,
+		name: String,
+	},
+	#[serde(other)]
+	Other,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StreamingItemDoneOutput {
 	pub sequence_number: u64,
@@ -152,10 +181,10 @@ pub struct DeltaTextChunk {
 	pub delta: String,
 }
 
+/// A piece of the arguments of a function call. The function call's ID is in
+/// [`ItemChunk::item_id`].
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct DeltaTextChunkWithItemId {
-	pub item_id: String,
-	pub content_index: u64,
+pub struct ArgsDeltaChunk {
 	pub sequence_number: u64,
 	pub delta: String,
 }
@@ -176,8 +205,11 @@ pub struct RefusalTextChunk {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ArgsTextChunk {
-	pub content_index: u64,
+	/// Not sent with function call arguments
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_index: Option<u64>,
 	pub sequence_number: u64,
+	/// The complete arguments, usually as a JSON-encoded string
 	pub arguments: serde_json::Value,
 }
 
@@ -201,6 +233,197 @@ pub enum SummaryPartChunkPart {
 	SummaryText { text: String },
 }
 
+/// Result of handling a stream chunk
+type ChunkResult = Result<RawStreamingChoice<StreamingCompletionResponse>, CompletionError>;
+
+/// A function call whose arguments are still being streamed
+#[derive(Debug)]
+struct PendingToolCall {
+	id: String,
+	call_id: String,
+	name: String,
+	internal_call_id: String,
+	arguments: String,
+}
+
+/// Turns Responses API stream chunks into [`RawStreamingChoice`]s.
+/// Shared with xAI, whose streaming format is the same.
+#[derive(Debug, Default)]
+pub(crate) struct ResponsesStreamState {
+	id: Option<String>,
+	usage: Option<ResponsesUsage>,
+	/// Function calls in progress, keyed by output index
+	pending_tool_calls: HashMap<u64, PendingToolCall>,
+	/// IDs of the function calls that have already been yielded
+	emitted_tool_calls: HashSet<String>,
+}
+
+impl ResponsesStreamState {
+	pub(crate) fn handle(&mut self, chunk: StreamingCompletionChunk) -> Vec<ChunkResult> {
+		match chunk {
+			StreamingCompletionChunk::Delta(chunk) => self.handle_item(chunk),
+			StreamingCompletionChunk::Response(chunk) => self.handle_response(*chunk),
+		}
+	}
+
+	/// The final response, carrying the ID and usage reported by the last response chunk.
+	pub(crate) fn final_response(self) -> StreamingCompletionResponse {
+		StreamingCompletionResponse {
+			id: self.id,
+			usage: self.usage.unwrap_or_else(ResponsesUsage::new),
+		}
+	}
+
+	fn handle_item(&mut self, chunk: ItemChunk) -> Vec<ChunkResult> {
+		let output_index = chunk.output_index;
+
+		match chunk.data {
+			ItemChunkKind::OutputItemAdded(StreamingItemAddedOutput {
+				item: AddedItem::FunctionCall { id, call_id, name },
+				..
+			}) => {
+				let internal_call_id = nanoid::nanoid!();
+				let delta = RawStreamingChoice::ToolCallDelta {
+					id: id.clone(),
+					internal_call_id: internal_call_id.clone(),
+					content: streaming::ToolCallDeltaContent::Name(name.clone()),
+				};
+				self.pending_tool_calls.insert(
+					output_index,
+					PendingToolCall {
+						id,
+						call_id,
+						name,
+						internal_call_id,
+						arguments: String::new(),
+					},
+				);
+				vec![Ok(delta)]
+			}
+			ItemChunkKind::FunctionCallArgsDelta(delta) => {
+				let Some(pending) = self.pending_tool_calls.get_mut(&output_index) else {
+					return vec![];
+				};
+				pending.arguments.push_str(&delta.delta);
+				vec![Ok(RawStreamingChoice::ToolCallDelta {
+					id: pending.id.clone(),
+					internal_call_id: pending.internal_call_id.clone(),
+					content: streaming::ToolCallDeltaContent::Delta(delta.delta),
+				})]
+			}
+			ItemChunkKind::FunctionCallArgsDone(done) => {
+				let Some(pending) = self.pending_tool_calls.remove(&output_index) else {
+					return vec![];
+				};
+				let arguments = match done.arguments {
+					serde_json::Value::String(arguments) if !arguments.is_empty() => arguments,
+					serde_json::Value::String(_) | serde_json::Value::Null => pending.arguments,
+					arguments => arguments.to_string(),
+				};
+				self.emitted_tool_calls.insert(pending.id.clone());
+				vec![Ok(RawStreamingChoice::ToolCall(
+					streaming::RawStreamingToolCall::new(
+						pending.id,
+						pending.name,
+						parse_arguments(&arguments),
+					)
+					.with_internal_call_id(pending.internal_call_id)
+					.with_call_id(pending.call_id),
+				))]
+			}
+			// Only reached if the arguments were never streamed
+			ItemChunkKind::OutputItemDone(StreamingItemDoneOutput {
+				item: Output::FunctionCall(func),
+				..
+			}) => {
+				if self.emitted_tool_calls.contains(&func.id) {
+					return vec![];
+				}
+				let internal_call_id = self
+					.pending_tool_calls
+					.remove(&output_index)
+					.map(|pending| pending.internal_call_id)
+					.unwrap_or_else(|| nanoid::nanoid!());
+				self.emitted_tool_calls.insert(func.id.clone());
+				vec![Ok(RawStreamingChoice::ToolCall(
+					streaming::RawStreamingToolCall::new(func.id, func.name, func.arguments)
+						.with_internal_call_id(internal_call_id)
+						.with_call_id(func.call_id),
+				))]
+			}
+			ItemChunkKind::OutputItemDone(StreamingItemDoneOutput {
+				item: Output::Reasoning { summary, id },
+				..
+			}) => {
+				let reasoning = summary
+					.iter()
+					.map(|x| {
+						let ReasoningSummary::SummaryText { text } = x;
+						text.to_owned()
+					})
+					.collect::<Vec<String>>()
+					.join("\n");
+				vec![Ok(RawStreamingChoice::Reasoning {
+					id: Some(id),
+					reasoning,
+					signature: None,
+				})]
+			}
+			// Refusals are surfaced as regular text
+			ItemChunkKind::OutputTextDelta(delta) | ItemChunkKind::RefusalDelta(delta) => {
+				vec![Ok(RawStreamingChoice::Message(delta.delta))]
+			}
+			ItemChunkKind::ReasoningSummaryTextDelta(delta) => {
+				vec![Ok(RawStreamingChoice::ReasoningDelta {
+					id: None,
+					reasoning: delta.delta,
+				})]
+			}
+			_ => vec![],
+		}
+	}
+
+	fn handle_response(&mut self, chunk: ResponseChunk) -> Vec<ChunkResult> {
+		let ResponseChunk { kind, response, .. } = chunk;
+
+		match kind {
+			ResponseChunkKind::ResponseCompleted
+			| ResponseChunkKind::ResponseIncomplete
+			| ResponseChunkKind::ResponseFailed => {
+				let span = tracing::Span::current();
+				span.record_output_messages(&response.output);
+				span.record("gen_ai.response.id", &response.id);
+				span.record("gen_ai.response.model", &response.model);
+				if let Some(usage) = response.usage {
+					self.usage = Some(usage);
+				}
+			}
+			_ => return vec![],
+		}
+
+		if let ResponseChunkKind::ResponseFailed = kind {
+			let message = response
+				.error
+				.map(|error| format!("{}: {}", error.code, error.message))
+				.unwrap_or_else(|| "Response failed".to_string());
+			return vec![Err(CompletionError::ProviderError(message))];
+		}
+
+		self.id = Some(response.id);
+		vec![]
+	}
+}
+
+/// Parses streamed function call arguments. Calls without arguments get an empty object.
+fn parse_arguments(arguments: &str) -> serde_json::Value {
+	if arguments.trim().is_empty() {
+		return serde_json::json!({});
+	}
+
+	serde_json::from_str(arguments)
+		.unwrap_or_else(|_| serde_json::Value::String(arguments.to_string()))
+}
+
 impl<T> ResponsesCompletionModel<T>
 where
 	T: HttpClientExt + Clone + Default + std::fmt::Debug + WasmCompatSend + 'static,
@@ -242,161 +465,82 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
 		span.record("gen_ai.provider.name", "openai");
 		span.record("gen_ai.request.model", &self.model);
+		span.record_input_messages(&request.input.iter().collect::<Vec<_>>());
 		// Build the request with proper headers for SSE
 		let client = self.client.clone();
+		let model = self.clone();
+		let cost = self.client.cost_recorder("openai", &self.model);
 
 		let mut event_source = GenericEventSource::new(client, req);
 
 		let stream = stream! {
-            let mut final_usage = ResponsesUsage::new();
-
-            let mut tool_calls: Vec<RawStreamingChoice<StreamingCompletionResponse>> = Vec::new();
-            let mut tool_call_internal_ids: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-            let mut combined_text = String::new();
-            let span = tracing::Span::current();
-
-            while let Some(event_result) = event_source.next().await {
-                match event_result {
-                    Ok(Event::Open) => {
-                        tracing::trace!("SSE connection opened");
-                        tracing::info!("OpenAI stream started");
-                        continue;
-                    }
-                    Ok(Event::Message(evt)) => {
-                        // Skip heartbeat messages or empty data
-                        if evt.data.trim().is_empty() {
-                            continue;
-                        }
-
-                        let data = serde_json::from_str::<StreamingCompletionChunk>(&evt.data);
-
-                        let Ok(data) = data else {
-                            let err = data.unwrap_err();
-                            debug!("Couldn't serialize data as StreamingCompletionResponse: {:?}", err);
-                            continue;
-                        };
-
-                        if let StreamingCompletionChunk::Delta(chunk) = &data {
-                            match &chunk.data {
-                                ItemChunkKind::OutputItemAdded(message) => {
-                                    if let StreamingItemDoneOutput { item: Output::FunctionCall(func), .. } = message {
-                                        let internal_call_id = tool_call_internal_ids
-                                            .entry(func.id.clone())
-                                            .or_insert_with(|| nanoid::nanoid!())
-                                            .clone();
-                                        yield Ok(streaming::RawStreamingChoice::ToolCallDelta {
-                                            id: func.id.clone(),
-                                            internal_call_id,
-                                            content: streaming::ToolCallDeltaContent::Name(func.name.clone()),
-                                        });
-                                    }
-                                }
-                                ItemChunkKind::OutputItemDone(message) => {
-                                    match message {
-                                        StreamingItemDoneOutput {  item: Output::FunctionCall(func), .. } => {
-                                            let internal_id = tool_call_internal_ids
-                                                .entry(func.id.clone())
-                                                .or_insert_with(|| nanoid::nanoid!())
-                                                .clone();
-                                            let raw_tool_call = streaming::RawStreamingToolCall::new(
-                                                func.id.clone(),
-                                                func.name.clone(),
-                                                func.arguments.clone(),
-                                            )
-                                                .with_internal_call_id(internal_id)
-                                                .with_call_id(func.call_id.clone());
-                                            tool_calls.push(streaming::RawStreamingChoice::ToolCall(raw_tool_call));
-                                        }
-
-                                        StreamingItemDoneOutput {  item: Output::Reasoning {  summary, id }, .. } => {
-                                            let reasoning = summary
-                                                .iter()
-                                                .map(|x| {
-                                                    let ReasoningSummary::SummaryText { text } = x;
-                                                    text.to_owned()
-                                                })
-                                                .collect::<Vec<String>>()
-                                                .join("\n");
-                                            yield Ok(streaming::RawStreamingChoice::Reasoning {
-                                                id: Some(id.to_string()),
-                                                reasoning,
-                                                signature: None,
-                                            })
-                                        }
-                                        _ => continue
-                                    }
-                                }
-                                ItemChunkKind::OutputTextDelta(delta) => {
-                                    combined_text.push_str(&delta.delta);
-                                    yield Ok(streaming::RawStreamingChoice::Message(delta.delta.clone()))
-                                }
-                                ItemChunkKind::ReasoningSummaryTextDelta(delta) => {
-                                    yield Ok(streaming::RawStreamingChoice::ReasoningDelta { id: None, reasoning: delta.delta.clone() })
-                                }
-                                ItemChunkKind::RefusalDelta(delta) => {
-                                    combined_text.push_str(&delta.delta);
-                                    yield Ok(streaming::RawStreamingChoice::Message(delta.delta.clone()))
-                                }
-                                ItemChunkKind::FunctionCallArgsDelta(delta) => {
-                                    let internal_call_id = tool_call_internal_ids
-                                        .entry(delta.item_id.clone())
-                                        .or_insert_with(|| nanoid::nanoid!())
-                                        .clone();
-                                    yield Ok(streaming::RawStreamingChoice::ToolCallDelta {
-                                        id: delta.item_id.clone(),
-                                        internal_call_id,
-                                        content: streaming::ToolCallDeltaContent::Delta(delta.delta.clone())
-                                    })
-                                }
-
-                                _ => { continue }
-                            }
-                        }
-
-                        if let StreamingCompletionChunk::Response(chunk) = data {
-                            if let ResponseChunk { kind: ResponseChunkKind::ResponseCompleted, response, .. } = *chunk {
-                                span.record("gen_ai.response.id", response.id);
-                                span.record("gen_ai.response.model", response.model);
-                                if let Some(usage) = response.usage {
-                                    final_usage = usage;
-                                }
-                            } else {
-                                continue;
-                            }
-                        }
-                    }
-                    Err(crate::http_client::Error::StreamEnded) => {
-                        event_source.close();
-                    }
-                    Err(error) => {
-                        tracing::error!(?error, "SSE error");
-                        yield Err(CompletionError::ProviderError(error.to_string()));
-                        break;
-                    }
-                }
-            }
-
-            // Ensure event source is closed when stream ends
-            event_source.close();
-
-            for tool_call in &tool_calls {
-                yield Ok(tool_call.to_owned())
-            }
-
-            span.record("gen_ai.usage.input_tokens", final_usage.input_tokens);
-            span.record("gen_ai.usage.output_tokens", final_usage.output_tokens);
-            tracing::info!("OpenAI stream finished");
-
-            yield Ok(RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
-                usage: final_usage
-            }));
-        }.instrument(span);
+			let mut state = ResponsesStreamState::default();
+
+			while let Some(event_result) = event_source.next().await {
+				match event_result {
+					Ok(Event::Open) => {
+						tracing::trace!("SSE connection opened");
+						tracing::info!("OpenAI stream started");
+						if let Some(metadata) = event_source.response_metadata() {
+							yield Ok(RawStreamingChoice::ResponseMetadata(metadata.clone()));
+						}
+						continue;
+					}
+					Ok(Event::Message(evt)) => {
+						// Skip heartbeat messages or empty data
+						if evt.data.trim().is_empty() {
+							continue;
+						}
+
+						let data = serde_json::from_str::<StreamingCompletionChunk>(&evt.data);
+
+						let Ok(data) = data else {
+							let err = data.unwrap_err();
+							debug!("Couldn't serialize data as StreamingCompletionResponse: {:?}", err);
+							continue;
+						};
+
+						for choice in state.handle(data) {
+							yield choice;
+						}
+					}
+					Err(crate::http_client::Error::StreamEnded) => {
+						event_source.close();
+					}
+					Err(error) => {
+						tracing::error!(?error, "SSE error");
+						yield Err(CompletionError::from_stream_error(error, parse_api_error));
+						break;
+					}
+				}
+			}
+
+			// Ensure event source is closed when stream ends
+			event_source.close();
+
+			let final_response = state.final_response();
+			if let Some(id) = &final_response.id {
+				model.set_response_id(id);
+			}
+			let span = tracing::Span::current();
+			span.record("gen_ai.usage.input_tokens", final_response.usage.input_tokens);
+			span.record("gen_ai.usage.output_tokens", final_response.usage.output_tokens);
+			cost.record(&span, &final_response);
+			tracing::info!("OpenAI stream finished");
+
+			yield Ok(RawStreamingChoice::FinalResponse(final_response));
+		}
+		.instrument(span);
 
 		Ok(streaming::StreamingCompletionResponse::stream(Box::pin(
 			stream,
@@ -412,9 +556,123 @@ mod tests {
 	use futures::StreamExt;
 	use serde_json;
 
+	use super::*;
 	use crate::completion::ToolDefinition;
 	use crate::tool::{Tool, ToolError};
 
+	/// A recorded stream in which the model refuses part of the request, then calls a tool.
+	const TOOL_CALL_TRANSCRIPT: &str = r#"event: response.created
+data: {"type":"response.created","sequence_number":0,"response":{"id":"resp_123","object":"response","created_at":1741290958,"status":"in_progress","error":null,"incomplete_details":null,"instructions":null,"max_output_tokens":null,"model":"gpt-4.1-2025-04-14","output":[],"tools":[],"usage":null}}
+
+event: response.output_item.added
+data: {"type":"response.output_item.added","sequence_number":1,"output_index":0,"item":{"id":"msg_1","type":"message","status":"in_progress","role":"assistant","content":[]}}
+
+event: response.refusal.delta
+data: {"type":"response.refusal.delta","sequence_number":2,"item_id":"msg_1","output_index":0,"content_index":0,"delta":"I can't share that. "}
+
+event: response.refusal.done
+data: {"type":"response.refusal.done","sequence_number":3,"item_id":"msg_1","output_index":0,"content_index":0,"refusal":"I can't share that. "}
+
+event: response.output_item.added
+data: {"type":"response.output_item.added","sequence_number":4,"output_index":1,"item":{"type":"function_call","id":"fc_1","call_id":"call_abc","name":"get_weather","arguments":"","status":"in_progress"}}
+
+event: response.function_call_arguments.delta
+data: {"type":"response.function_call_arguments.delta","sequence_number":5,"item_id":"fc_1","output_index":1,"delta":"{\"location\":"}
+
+event: response.function_call_arguments.delta
+data: {"type":"response.function_call_arguments.delta","sequence_number":6,"item_id":"fc_1","output_index":1,"delta":"\"Paris\"}"}
+
+event: response.function_call_arguments.done
+data: {"type":"response.function_call_arguments.done","sequence_number":7,"item_id":"fc_1","output_index":1,"arguments":"{\"location\":\"Paris\"}"}
+
+event: response.output_item.done
+data: {"type":"response.output_item.done","sequence_number":8,"output_index":1,"item":{"type":"function_call","id":"fc_1","call_id":"call_abc","name":"get_weather","arguments":"{\"location\":\"Paris\"}","status":"completed"}}
+
+event: response.completed
+data: {"type":"response.completed","sequence_number":9,"response":{"id":"resp_123","object":"response","created_at":1741290958,"status":"completed","error":null,"incomplete_details":null,"instructions":null,"max_output_tokens":null,"model":"gpt-4.1-2025-04-14","output":[{"type":"function_call","id":"fc_1","call_id":"call_abc","name":"get_weather","arguments":"{\"location\":\"Paris\"}","status":"completed"}],"tools":[{"type":"function","name":"get_weather","description":"Get the weather","parameters":{"type":"object"},"strict":true}],"usage":{"input_tokens":42,"input_tokens_details":{"cached_tokens":8},"output_tokens":17,"output_tokens_details":{"reasoning_tokens":0},"total_tokens":59}}}
+"#;
+
+	#[test]
+	fn test_tool_call_transcript() {
+		let mut state = ResponsesStreamState::default();
+		let choices = TOOL_CALL_TRANSCRIPT
+			.lines()
+			.filter_map(|line| line.strip_prefix("data: "))
+			.flat_map(|data| {
+				let chunk = serde_json::from_str::<StreamingCompletionChunk>(data)
+					.unwrap_or_else(|err| panic!("{err}: {data}"));
+				state.handle(chunk)
+			})
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+
+		assert_eq!(choices.len(), 5, "{choices:?}");
+		assert!(
+			matches!(&choices[0], RawStreamingChoice::Message(text) if text == "I can't share that. ")
+		);
+
+		let RawStreamingChoice::ToolCallDelta {
+			id,
+			internal_call_id,
+			content: streaming::ToolCallDeltaContent::Name(name),
+		} = &choices[1]
+		else {
+			panic!("expected the tool name, got {:?}", choices[1]);
+		};
+		assert_eq!((id.as_str(), name.as_str()), ("fc_1", "get_weather"));
+
+		let deltas = choices[2..4]
+			.iter()
+			.map(|choice| match choice {
+				RawStreamingChoice::ToolCallDelta {
+					content: streaming::ToolCallDeltaContent::Delta(delta),
+					internal_call_id: delta_call_id,
+					..
+				} => {
+					assert_eq!(delta_call_id, internal_call_id);
+					delta.as_str()
+				}
+				other => panic!("expected an argument delta, got {other:?}"),
+			})
+			.collect::<String>();
+		assert_eq!(deltas, r#"{"location":"Paris"}"#);
+
+		// Yielded once, on `response.function_call_arguments.done`
+		let RawStreamingChoice::ToolCall(tool_call) = &choices[4] else {
+			panic!("expected a tool call, got {:?}", choices[4]);
+		};
+		assert_eq!(tool_call.id, "fc_1");
+		assert_eq!(tool_call.call_id.as_deref(), Some("call_abc"));
+		assert_eq!(&tool_call.internal_call_id, internal_call_id);
+		assert_eq!(tool_call.name, "get_weather");
+		assert_eq!(
+			tool_call.arguments,
+			serde_json::json!({ "location": "Paris" })
+		);
+
+		let final_response = state.final_response();
+		assert_eq!(final_response.id.as_deref(), Some("resp_123"));
+		let usage = final_response.token_usage().unwrap();
+		assert_eq!(usage.input_tokens, 42);
+		assert_eq!(usage.output_tokens, 17);
+		assert_eq!(usage.cached_input_tokens, 8);
+	}
+
+	#[test]
+	fn test_failed_response_is_an_error() {
+		let data = r#"{"type":"response.failed","sequence_number":3,"response":{"id":"resp_123","object":"response","created_at":1741290958,"status":"failed","error":{"code":"server_error","message":"The model crashed"},"incomplete_details":null,"instructions":null,"max_output_tokens":null,"model":"gpt-4.1","output":[],"tools":[],"usage":null}}"#;
+
+		let mut state = ResponsesStreamState::default();
+		let choices = state.handle(serde_json::from_str(data).unwrap());
+
+		let [Err(CompletionError::ProviderError(message))] = choices.as_slice() else {
+			panic!("expected a provider error, got {choices:?}");
+		};
+		assert_eq!(message, "server_error: The model crashed");
+		// A failed response can't be continued from
+		assert_eq!(state.final_response().id, None);
+	}
+
 	struct ExampleTool;
 
 	impl Tool for ExampleTool {
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
 use super::super::completion::types::{InputAudio, ToolChoice};
-use crate::completion::CompletionError;
+use crate::completion::{CompletionError, GetTokenUsage};
 use crate::message::{
 	AudioMediaType, Document, DocumentMediaType, DocumentSourceKind, ImageDetail, MessageError,
 	MimeType, Text,
@@ -38,9 +38,9 @@ pub struct CompletionRequest {
 	/// If none provided, the default option is "auto".
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<ToolChoice>,
-	/// The tools you want to use. Currently this is limited to functions, but will be expanded on in future.
+	/// The tools you want to use: functions, and tools hosted by OpenAI (see [`HostedTool`]).
 	#[serde(skip_serializing_if = "Vec::is_empty")]
-	pub tools: Vec<ResponsesToolDefinition>,
+	pub tools: Vec<ResponsesTool>,
 	/// Additional parameters
 	#[serde(flatten)]
 	pub additional_parameters: AdditionalParameters,
@@ -215,7 +215,8 @@ impl From<Message> for InputItem {
 impl TryFrom<crate::completion::Message> for Vec<InputItem> {
 	type Error = CompletionError;
 
-	fn try_from(value: crate::completion::Message) -> Result<Self, Self::Error> {
+	fn try_from(mut value: crate::completion::Message) -> Result<Self, Self::Error> {
+		value.sniff_media_types();
 		match value {
 			crate::completion::Message::User { content } => {
 				let mut items = Vec::new();
@@ -265,17 +266,11 @@ impl TryFrom<crate::completion::Message> for Vec<InputItem> {
 							media_type: Some(DocumentMediaType::PDF),
 							..
 						}) => {
-							let (file_data, file_url) = match data {
+							let (file_data, file_url) = match data.into_base64() {
 								DocumentSourceKind::Base64(data) => {
 									(Some(format!("data:application/pdf;base64,{data}")), None)
 								}
 								DocumentSourceKind::Url(url) => (None, Some(url)),
-								DocumentSourceKind::Raw(_) => {
-									return Err(CompletionError::RequestError(
-										"Raw file data not supported, encode as base64 first"
-											.into(),
-									));
-								}
 								doc => {
 									return Err(CompletionError::RequestError(
 										format!("Unsupported document type: {doc}").into(),
@@ -322,7 +317,7 @@ impl TryFrom<crate::completion::Message> for Vec<InputItem> {
 							detail,
 							..
 						}) => {
-							let url = match data {
+							let url = match data.into_base64() {
 								DocumentSourceKind::Base64(data) => {
 									let media_type = if let Some(media_type) = media_type {
 										media_type.to_mime_type().to_string()
@@ -332,12 +327,6 @@ impl TryFrom<crate::completion::Message> for Vec<InputItem> {
 									format!("data:{media_type};base64,{data}")
 								}
 								DocumentSourceKind::Url(url) => url,
-								DocumentSourceKind::Raw(_) => {
-									return Err(CompletionError::RequestError(
-										"Raw file data not supported, encode as base64 first"
-											.into(),
-									));
-								}
 								doc => {
 									return Err(CompletionError::RequestError(
 										format!("Unsupported document type: {doc}").into(),
@@ -376,7 +365,7 @@ impl TryFrom<crate::completion::Message> for Vec<InputItem> {
 								role: Some(Role::Assistant),
 								input: InputContent::Message(Message::Assistant {
 									content: OneOrMany::one(AssistantContentType::Text(
-										AssistantContent::OutputText(Text { text }),
+										AssistantContent::OutputText(OutputText::new(text)),
 									)),
 									id,
 									name: None,
@@ -425,6 +414,11 @@ impl TryFrom<crate::completion::Message> for Vec<InputItem> {
 									.to_string(),
 							));
 						}
+						crate::message::AssistantContent::Document(_) => {
+							tracing::warn!(
+								"OpenAI Responses API doesn't support assistant documents, skipping"
+							);
+						}
 					}
 				}
 
@@ -476,6 +470,83 @@ impl From<completion::ToolDefinition> for ResponsesToolDefinition {
 	}
 }
 
+/// A tool of a [`CompletionRequest`], either a function called by the agent or a tool hosted by OpenAI.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum ResponsesTool {
+	Function(ResponsesToolDefinition),
+	Hosted(HostedTool),
+	/// A tool of a type that isn't supported, e.g. in the tools of a response
+	Unknown(UnknownItem),
+}
+
+impl From<completion::ToolDefinition> for ResponsesTool {
+	fn from(value: completion::ToolDefinition) -> Self {
+		Self::Function(value.into())
+	}
+}
+
+impl From<HostedTool> for ResponsesTool {
+	fn from(value: HostedTool) -> Self {
+		Self::Hosted(value)
+	}
+}
+
+/// A tool run by OpenAI instead of the agent. Its calls are returned as output items (e.g.
+/// [`Output::WebSearchCall`]) before the message using their results.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HostedTool {
+	/// Searches the web, the pages being cited with [`Annotation::UrlCitation`]s
+	#[serde(alias = "web_search_preview")]
+	WebSearch {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		search_context_size: Option<SearchContextSize>,
+	},
+	/// Searches the files of vector stores, the files being cited with [`Annotation::FileCitation`]s
+	FileSearch {
+		vector_store_ids: Vec<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		max_num_results: Option<u32>,
+	},
+	/// Runs Python code in a container
+	CodeInterpreter {
+		/// The ID of a container, or `{ "type": "auto" }` to create one
+		container: serde_json::Value,
+	},
+}
+
+impl HostedTool {
+	pub fn web_search() -> Self {
+		Self::WebSearch {
+			search_context_size: None,
+		}
+	}
+
+	pub fn file_search(vector_store_ids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		Self::FileSearch {
+			vector_store_ids: vector_store_ids.into_iter().map(Into::into).collect(),
+			max_num_results: None,
+		}
+	}
+
+	/// The code interpreter in a container created automatically
+	pub fn code_interpreter() -> Self {
+		Self::CodeInterpreter {
+			container: serde_json::json!({ "type": "auto" }),
+		}
+	}
+}
+
+/// How much context is retrieved from the web by the [`HostedTool::WebSearch`] tool.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchContextSize {
+	Low,
+	Medium,
+	High,
+}
+
 /// Token usage.
 /// Token usage from the OpenAI Responses API generally shows the input tokens and output tokens (both with more in-depth details) as well as a total tokens field.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -493,6 +564,22 @@ pub struct ResponsesUsage {
 	pub total_tokens: u64,
 }
 
+impl GetTokenUsage for ResponsesUsage {
+	fn token_usage(&self) -> Option<completion::Usage> {
+		Some(completion::Usage {
+			input_tokens: self.input_tokens,
+			output_tokens: self.output_tokens,
+			total_tokens: self.total_tokens,
+			cached_input_tokens: self
+				.input_tokens_details
+				.as_ref()
+				.map(|d| d.cached_tokens)
+				.unwrap_or(0),
+			cache_creation_input_tokens: 0,
+		})
+	}
+}
+
 impl ResponsesUsage {
 	/// Create a new ResponsesUsage instance
 	pub(crate) fn new() -> Self {
@@ -663,6 +750,12 @@ impl TryFrom<(String, crate::completion::CompletionRequest)> for CompletionReque
 			AdditionalParameters::default()
 		};
 
+		if !req.stop_sequences.is_empty() {
+			return Err(CompletionError::RequestError(
+				"Stop sequences are not supported by the OpenAI Responses API, use the Chat Completions API instead".into(),
+			));
+		}
+
 		let tool_choice = req.tool_choice.map(ToolChoice::try_from).transpose()?;
 
 		Ok(Self {
@@ -672,11 +765,7 @@ impl TryFrom<(String, crate::completion::CompletionRequest)> for CompletionReque
 			max_output_tokens: req.max_tokens,
 			stream,
 			tool_choice,
-			tools: req
-				.tools
-				.into_iter()
-				.map(ResponsesToolDefinition::from)
-				.collect(),
+			tools: req.tools.into_iter().map(ResponsesTool::from).collect(),
 			temperature: req.temperature,
 			additional_parameters,
 		})
@@ -710,7 +799,7 @@ pub struct CompletionResponse {
 	pub output: Vec<Output>,
 	/// Tools
 	#[serde(default)]
-	pub tools: Vec<ResponsesToolDefinition>,
+	pub tools: Vec<ResponsesTool>,
 	/// Additional parameters
 	#[serde(flatten)]
 	pub additional_parameters: AdditionalParameters,
@@ -902,9 +991,12 @@ pub enum Include {
 	ReasoningEncryptedContent,
 	#[serde(rename = "code_interpreter_call.outputs")]
 	CodeInterpreterCallOutputs,
+	#[serde(rename = "web_search_call.action.sources")]
+	WebSearchCallActionSources,
 }
 
 /// A currently non-exhaustive list of output types.
+/// Item types that aren't known yet (e.g. calls of other hosted tools) deserialize into [`Output::Unknown`].
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
@@ -916,6 +1008,20 @@ pub enum Output {
 		id: String,
 		summary: Vec<ReasoningSummary>,
 	},
+	WebSearchCall(WebSearchCall),
+	FileSearchCall(FileSearchCall),
+	CodeInterpreterCall(CodeInterpreterCall),
+	#[serde(untagged)]
+	Unknown(UnknownItem),
+}
+
+/// An output item or content part of a type that isn't supported, kept as raw JSON.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UnknownItem {
+	#[serde(rename = "type")]
+	pub kind: String,
+	#[serde(flatten)]
+	pub fields: serde_json::Map<String, serde_json::Value>,
 }
 
 impl From<Output> for Vec<completion::AssistantContent> {
@@ -923,7 +1029,18 @@ impl From<Output> for Vec<completion::AssistantContent> {
 		let res: Vec<completion::AssistantContent> = match value {
 			Output::Message(OutputMessage { content, .. }) => content
 				.into_iter()
-				.map(completion::AssistantContent::from)
+				.flat_map(|content| {
+					let documents = content.cited_files();
+					let content = completion::AssistantContent::try_from(content)
+						.inspect_err(|item| {
+							tracing::warn!(
+								kind = %item.kind,
+								"Skipping unsupported OpenAI Responses API content part"
+							);
+						})
+						.ok();
+					content.into_iter().chain(documents)
+				})
 				.collect(),
 			Output::FunctionCall(OutputFunctionCall {
 				id,
@@ -941,6 +1058,19 @@ impl From<Output> for Vec<completion::AssistantContent> {
 					message::Reasoning::multi(summary).with_id(id),
 				)]
 			}
+			// Run by OpenAI, their results are used by the message that follows
+			Output::WebSearchCall(_)
+			| Output::FileSearchCall(_)
+			| Output::CodeInterpreterCall(_) => {
+				vec![]
+			}
+			Output::Unknown(item) => {
+				tracing::warn!(
+					kind = %item.kind,
+					"Skipping unsupported OpenAI Responses API output item"
+				);
+				vec![]
+			}
 		};
 
 		res
@@ -965,6 +1095,86 @@ pub struct OutputFunctionCall {
 	pub status: ToolStatus,
 }
 
+/// A call of the [`HostedTool::WebSearch`] tool.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct WebSearchCall {
+	pub id: String,
+	pub status: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub action: Option<WebSearchAction>,
+}
+
+/// What a [`WebSearchCall`] did.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebSearchAction {
+	Search {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		query: Option<String>,
+		/// Only returned when [`Include::WebSearchCallActionSources`] is requested
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		sources: Vec<WebSearchSource>,
+	},
+	OpenPage {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		url: Option<String>,
+	},
+	Find {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		url: Option<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		pattern: Option<String>,
+	},
+	#[serde(other)]
+	Other,
+}
+
+/// A source consulted by a web search.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct WebSearchSource {
+	pub url: String,
+}
+
+/// A call of the [`HostedTool::FileSearch`] tool.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FileSearchCall {
+	pub id: String,
+	pub status: String,
+	#[serde(default)]
+	pub queries: Vec<String>,
+	/// Only returned when [`Include::FileSearchCallResults`] is requested
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub results: Option<Vec<FileSearchResult>>,
+}
+
+/// A chunk of a file found by a [`FileSearchCall`].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FileSearchResult {
+	pub file_id: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub filename: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub score: Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub text: Option<String>,
+	#[serde(default, skip_serializing_if = "Map::is_empty")]
+	pub attributes: Map<String, Value>,
+}
+
+/// A call of the [`HostedTool::CodeInterpreter`] tool.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CodeInterpreterCall {
+	pub id: String,
+	pub status: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub code: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub container_id: Option<String>,
+	/// Only returned when [`Include::CodeInterpreterCallOutputs`] is requested
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub outputs: Option<Vec<Value>>,
+}
+
 /// The status of a given tool.
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -994,6 +1204,25 @@ pub enum OutputRole {
 	Assistant,
 }
 
+impl CompletionResponse {
+	/// The citations of the text of the output messages, e.g. the pages found by the web search
+	/// tool, in order
+	pub fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+		self.output
+			.iter()
+			.filter_map(|output| match output {
+				Output::Message(message) => Some(&message.content),
+				_ => None,
+			})
+			.flatten()
+			.filter_map(|content| match content {
+				AssistantContent::OutputText(text) => Some(&text.annotations),
+				_ => None,
+			})
+			.flatten()
+	}
+}
+
 impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
 	type Error = CompletionError;
 
@@ -1020,22 +1249,14 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 		let usage = response
 			.usage
 			.as_ref()
-			.map(|usage| completion::Usage {
-				input_tokens: usage.input_tokens,
-				output_tokens: usage.output_tokens,
-				total_tokens: usage.total_tokens,
-				cached_input_tokens: usage
-					.input_tokens_details
-					.as_ref()
-					.map(|d| d.cached_tokens)
-					.unwrap_or(0),
-			})
+			.and_then(GetTokenUsage::token_usage)
 			.unwrap_or_default();
 
 		Ok(completion::CompletionResponse {
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -1094,23 +1315,128 @@ impl Message {
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum AssistantContent {
-	OutputText(Text),
-	Refusal { refusal: String },
+	OutputText(OutputText),
+	Refusal {
+		refusal: String,
+	},
+	#[serde(untagged)]
+	Unknown(UnknownItem),
 }
 
-impl From<AssistantContent> for completion::AssistantContent {
-	fn from(value: AssistantContent) -> Self {
+impl AssistantContent {
+	/// The files cited by the text, e.g. the files created by the code interpreter tool.
+	/// Only their IDs are known, so the documents have no data.
+	fn cited_files(&self) -> Vec<completion::AssistantContent> {
+		let AssistantContent::OutputText(OutputText { annotations, .. }) = self else {
+			return Vec::new();
+		};
+
+		let mut file_ids = Vec::new();
+		annotations
+			.iter()
+			.filter_map(|annotation| {
+				let (file_id, filename, container_id) = match annotation {
+					Annotation::FileCitation {
+						file_id, filename, ..
+					} => (file_id, filename, None),
+					Annotation::ContainerFileCitation {
+						container_id,
+						file_id,
+						filename,
+						..
+					} => (file_id, filename, Some(container_id)),
+					Annotation::UrlCitation { .. } | Annotation::Other => return None,
+				};
+
+				if file_ids.contains(&file_id) {
+					return None;
+				}
+				file_ids.push(file_id);
+
+				let mut params = serde_json::json!({ "file_id": file_id });
+				if let Some(filename) = filename {
+					params["filename"] = filename.clone().into();
+				}
+				if let Some(container_id) = container_id {
+					params["container_id"] = container_id.clone().into();
+				}
+
+				Some(completion::AssistantContent::Document(Document {
+					data: DocumentSourceKind::Unknown,
+					media_type: None,
+					additional_params: Some(params),
+				}))
+			})
+			.collect()
+	}
+}
+
+impl TryFrom<AssistantContent> for completion::AssistantContent {
+	type Error = UnknownItem;
+
+	fn try_from(value: AssistantContent) -> Result<Self, Self::Error> {
 		match value {
 			AssistantContent::Refusal { refusal } => {
-				completion::AssistantContent::Text(Text { text: refusal })
+				Ok(completion::AssistantContent::Text(Text { text: refusal }))
 			}
-			AssistantContent::OutputText(Text { text }) => {
-				completion::AssistantContent::Text(Text { text })
+			AssistantContent::OutputText(OutputText { text, .. }) => {
+				Ok(completion::AssistantContent::Text(Text { text }))
 			}
+			AssistantContent::Unknown(item) => Err(item),
 		}
 	}
 }
 
+/// Text output by the model.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct OutputText {
+	pub text: String,
+	/// Citations of URLs and files within the text
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub annotations: Vec<Annotation>,
+}
+
+impl OutputText {
+	pub fn new(text: impl Into<String>) -> Self {
+		Self {
+			text: text.into(),
+			annotations: Vec::new(),
+		}
+	}
+}
+
+/// A citation within output text.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Annotation {
+	/// A file found by the file search tool
+	FileCitation {
+		file_id: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		filename: Option<String>,
+		index: usize,
+	},
+	/// A web page, e.g. found by the web search tool, cited by the text between the indices
+	UrlCitation {
+		url: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		title: Option<String>,
+		start_index: usize,
+		end_index: usize,
+	},
+	/// A file created in a container, e.g. by the code interpreter tool
+	ContainerFileCitation {
+		container_id: String,
+		file_id: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		filename: Option<String>,
+		start_index: usize,
+		end_index: usize,
+	},
+	#[serde(other)]
+	Other,
+}
+
 /// The type of assistant content.
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(untagged)]
@@ -1177,7 +1503,8 @@ pub enum UserContent {
 impl TryFrom<message::Message> for Vec<Message> {
 	type Error = message::MessageError;
 
-	fn try_from(message: message::Message) -> Result<Self, Self::Error> {
+	fn try_from(mut message: message::Message) -> Result<Self, Self::Error> {
+		message.sniff_media_types();
 		match message {
 			message::Message::User { content } => {
 				let (tool_results, other_content): (Vec<_>, Vec<_>) = content
@@ -1222,7 +1549,7 @@ impl TryFrom<message::Message> for Vec<Message> {
 								media_type,
 								..
 							}) => {
-								let url = match data {
+								let url = match data.into_base64() {
 									DocumentSourceKind::Base64(data) => {
 										let media_type = if let Some(media_type) = media_type {
 											media_type.to_mime_type().to_string()
@@ -1232,12 +1559,6 @@ impl TryFrom<message::Message> for Vec<Message> {
 										format!("data:{media_type};base64,{data}")
 									}
 									DocumentSourceKind::Url(url) => url,
-									DocumentSourceKind::Raw(_) => {
-										return Err(MessageError::ConversionError(
-											"Raw files not supported, encode as base64 first"
-												.into(),
-										));
-									}
 									doc => {
 										return Err(MessageError::ConversionError(format!(
 											"Unsupported document type: {doc}"
@@ -1255,17 +1576,11 @@ impl TryFrom<message::Message> for Vec<Message> {
 								data,
 								..
 							}) => {
-								let (file_data, file_url) = match data {
+								let (file_data, file_url) = match data.into_base64() {
 									DocumentSourceKind::Base64(data) => {
 										(Some(format!("data:application/pdf;base64,{data}")), None)
 									}
 									DocumentSourceKind::Url(url) => (None, Some(url)),
-									DocumentSourceKind::Raw(_) => {
-										return Err(MessageError::ConversionError(
-											"Raw files not supported, encode as base64 first"
-												.into(),
-										));
-									}
 									doc => {
 										return Err(MessageError::ConversionError(format!(
 											"Unsupported document type: {doc}"
@@ -1323,7 +1638,7 @@ impl TryFrom<message::Message> for Vec<Message> {
 								.expect("The assistant message ID should exist"),
 							status: ToolStatus::Completed,
 							content: OneOrMany::one(AssistantContentType::Text(
-								AssistantContent::OutputText(Text { text }),
+								AssistantContent::OutputText(OutputText::new(text)),
 							)),
 							name: None,
 						}])
@@ -1373,6 +1688,12 @@ impl TryFrom<message::Message> for Vec<Message> {
 								.into(),
 						))
 					}
+					crate::message::AssistantContent::Document(_) => {
+						tracing::warn!(
+							"OpenAI Responses API doesn't support assistant documents, skipping"
+						);
+						Ok(vec![])
+					}
 				}
 			}
 		}
@@ -28,6 +28,11 @@ impl TryFrom<TranscriptionResponse>
 	}
 }
 
+impl transcription::OffsetTimestamps for TranscriptionResponse {
+	/// The response has no timestamps
+	fn offset_timestamps(&mut self, _: std::time::Duration) {}
+}
+
 #[derive(Clone)]
 pub struct TranscriptionModel<T = reqwest::Client> {
 	client: Client<T>,
@@ -55,6 +60,10 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn transcription(
 		&self,
 		request: transcription::TranscriptionRequest,
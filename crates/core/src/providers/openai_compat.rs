@@ -30,7 +30,7 @@ use tracing::{Instrument, info_span};
 #[cfg(feature = "audio")]
 use crate::audio_generation::AudioGenerationError;
 use crate::client::{self, BearerAuth, Capabilities, DebugExt, Provider, ProviderBuilder};
-use crate::completion::CompletionError;
+use crate::completion::{CompletionError, ResponseMetadata};
 use crate::embeddings::EmbeddingError;
 use crate::http_client::{self, HttpClientExt};
 #[cfg(feature = "image")]
@@ -39,6 +39,7 @@ use crate::json_utils;
 use crate::providers::openai;
 use crate::providers::openai::completion::streaming::send_compatible_streaming_request;
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 use crate::transcription::TranscriptionError;
 
 /// Core trait for OpenAI-compatible providers. Implementing this gives you blanket
@@ -231,6 +232,8 @@ pub fn completion_span(provider: &str, model: &str, preamble: &Option<String>) -
 			gen_ai.response.model = tracing::field::Empty,
 			gen_ai.usage.output_tokens = tracing::field::Empty,
 			gen_ai.usage.input_tokens = tracing::field::Empty,
+			gen_ai.input.messages = tracing::field::Empty,
+			gen_ai.output.messages = tracing::field::Empty,
 		)
 	} else {
 		tracing::Span::current()
@@ -252,6 +255,8 @@ pub fn streaming_span(provider: &str, model: &str, preamble: &Option<String>) ->
 			gen_ai.response.model = tracing::field::Empty,
 			gen_ai.usage.output_tokens = tracing::field::Empty,
 			gen_ai.usage.input_tokens = tracing::field::Empty,
+			gen_ai.input.messages = tracing::field::Empty,
+			gen_ai.output.messages = tracing::field::Empty,
 		)
 	} else {
 		tracing::Span::current()
@@ -267,6 +272,7 @@ pub fn record_openai_response_span(
 ) {
 	span.record("gen_ai.response.id", response.id.clone());
 	span.record("gen_ai.response.model_name", response.model.clone());
+	span.record_output_messages(&response.choices);
 	if let Some(ref usage) = response.usage {
 		span.record("gen_ai.usage.input_tokens", usage.prompt_tokens);
 		span.record(
@@ -277,12 +283,12 @@ pub fn record_openai_response_span(
 }
 
 /// Send an HTTP request, parse the response as `ApiResponse<Resp, Err>`, and return the
-/// successful variant or a `CompletionError`.
+/// successful variant alongside the metadata of the HTTP response, or a `CompletionError`.
 pub async fn send_and_parse<P, Resp, Err, T>(
 	client: &client::Client<P, T>,
 	req: http::Request<Vec<u8>>,
 	provider_name: &str,
-) -> Result<Resp, CompletionError>
+) -> Result<(Resp, Option<ResponseMetadata>), CompletionError>
 where
 	P: Provider + Send + Sync + 'static,
 	T: HttpClientExt + Clone + Send + 'static,
@@ -290,6 +296,7 @@ where
 	Err: serde::de::DeserializeOwned + Debug + Into<CompletionError>,
 {
 	let response = client.send::<_, bytes::Bytes>(req).await?;
+	let response_metadata = ResponseMetadata::from_response(&response);
 
 	let status = response.status();
 	let response_body = response.into_body().into_future().await?.to_vec();
@@ -305,7 +312,7 @@ where
 						serde_json::to_string_pretty(&resp)?
 					);
 				}
-				Ok(resp)
+				Ok((resp, response_metadata))
 			}
 			ApiResponse::Err(err) => Err(err.into()),
 		}
@@ -317,11 +324,13 @@ where
 }
 
 /// Merge `stream: true` and `stream_options: { include_usage: true }` into
-/// `additional_params`, creating the object if it's `None`.
+/// `additional_params`, creating the object if it's `None`. Other `stream_options` set by the
+/// user are kept.
 pub fn merge_stream_params(additional_params: &mut Option<Value>) {
-	let params = json_utils::merge(
+	let params = json_utils::deep_merge(
 		additional_params.take().unwrap_or(serde_json::json!({})),
 		serde_json::json!({"stream": true, "stream_options": {"include_usage": true} }),
+		json_utils::ArrayMergeStrategy::Replace,
 	);
 	*additional_params = Some(params);
 }
@@ -350,12 +359,333 @@ where
 		.await
 }
 
-/// Default `from_env()` implementation: reads `P::API_KEY_ENV` and builds a client.
+/// A model listed by the `/models` endpoint. Only `id` is always present, the other fields
+/// depend on the provider.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ModelInfo {
+	pub id: String,
+	#[serde(default)]
+	pub owned_by: Option<String>,
+	/// Unix timestamp of the model's release
+	#[serde(default)]
+	pub created: Option<u64>,
+	/// Maximum number of tokens of a request and its completion
+	#[serde(default)]
+	pub context_window: Option<u64>,
+	#[serde(default)]
+	pub max_completion_tokens: Option<u64>,
+	/// Whether the model can currently be used
+	#[serde(default)]
+	pub active: Option<bool>,
+}
+
+/// Response of the `/models` endpoint
+#[derive(Debug, Deserialize)]
+pub struct ModelList {
+	pub data: Vec<ModelInfo>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModelsError {
+	/// Http error (e.g.: connection error, timeout, etc.)
+	#[error("HttpError: {0}")]
+	HttpError(#[from] http_client::Error),
+
+	/// Json error (e.g.: serialization, deserialization)
+	#[error("JsonError: {0}")]
+	JsonError(#[from] serde_json::Error),
+
+	/// Error returned by the provider
+	#[error("ProviderError: {0}")]
+	ProviderError(String),
+}
+
+/// Lists the models available to the account (`GET /models`).
+pub async fn list_models<P, T>(client: &client::Client<P, T>) -> Result<Vec<ModelInfo>, ModelsError>
+where
+	P: OpenAiCompat,
+	T: HttpClientExt + Clone + 'static,
+{
+	let list: ModelList = get_json(client, "/models").await?;
+	Ok(list.data)
+}
+
+/// Gets the metadata of the model `id` (`GET /models/{id}`).
+pub async fn get_model<P, T>(
+	client: &client::Client<P, T>,
+	id: &str,
+) -> Result<ModelInfo, ModelsError>
+where
+	P: OpenAiCompat,
+	T: HttpClientExt + Clone + 'static,
+{
+	get_json(client, &format!("/models/{id}")).await
+}
+
+async fn get_json<P, T, R>(client: &client::Client<P, T>, path: &str) -> Result<R, ModelsError>
+where
+	P: OpenAiCompat,
+	T: HttpClientExt + Clone + 'static,
+	R: serde::de::DeserializeOwned,
+{
+	let req = client
+		.get(path)?
+		.body(http_client::NoBody)
+		.map_err(http_client::Error::Protocol)?;
+
+	let response = client.send::<_, Vec<u8>>(req).await?;
+
+	if !response.status().is_success() {
+		let text = http_client::text(response).await?;
+		return Err(ModelsError::ProviderError(text));
+	}
+
+	let body: Vec<u8> = response.into_body().await?;
+	Ok(serde_json::from_slice(&body)?)
+}
+
+/// Default `try_from_env()` implementation: reads `P::API_KEY_ENV` and builds a client.
+pub fn default_try_from_env<P>() -> Result<client::Client<P>, client::ClientError>
+where
+	P: OpenAiCompat,
+{
+	let api_key = client::env_var(P::API_KEY_ENV)?;
+	Ok(client::Client::new(&api_key)?)
+}
+
+/// Default `from_env()` implementation, panicking if `P::API_KEY_ENV` is not set.
 pub fn default_from_env<P>() -> client::Client<P>
 where
 	P: OpenAiCompat,
 {
-	let api_key =
-		std::env::var(P::API_KEY_ENV).unwrap_or_else(|_| panic!("{} not set", P::API_KEY_ENV));
-	client::Client::new(&api_key).unwrap()
+	default_try_from_env().unwrap_or_else(|error| panic!("{error}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ModelInfo, ModelsError};
+	use crate::client::{CompletionClient, EmbeddingsClient, VerifyClient};
+	use crate::completion::CompletionModel;
+	use crate::embeddings::EmbeddingModel;
+	use crate::providers::{groq, moonshot, openai};
+	use crate::test_utils::MockSseClient;
+
+	/// Base URL overrides and the URL expected for a `/models` request.
+	const BASE_URLS: [(&str, &str); 6] = [
+		("https://host/v1", "https://host/v1/models"),
+		("https://host/v1/", "https://host/v1/models"),
+		("https://host/llm/v1", "https://host/llm/v1/models"),
+		("https://host/llm/v1/", "https://host/llm/v1/models"),
+		(
+			"http://localhost:4000/v1",
+			"http://localhost:4000/v1/models",
+		),
+		("https://host//llm//v1//", "https://host/llm/v1/models"),
+	];
+
+	fn expected(models_url: &str, path: &str) -> String {
+		models_url.replace("/models", path)
+	}
+
+	#[tokio::test]
+	async fn test_base_url_overrides_completion_and_verify() {
+		for (base_url, models_url) in BASE_URLS {
+			let http_client = MockSseClient::default();
+			let client = moonshot::Client::<MockSseClient>::builder()
+				.api_key("test-key")
+				.base_url(base_url)
+				.http_client(http_client.clone())
+				.build()
+				.unwrap();
+
+			let model = client.completion_model(moonshot::KIMI_LATEST);
+			let _ = model
+				.completion(model.completion_request("Hello").build())
+				.await;
+			let _ = client.verify().await;
+
+			assert_eq!(
+				http_client.request_uris(),
+				vec![
+					expected(models_url, "/chat/completions"),
+					models_url.to_string()
+				],
+				"base URL {base_url}"
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_base_url_overrides_embeddings() {
+		for (base_url, models_url) in BASE_URLS {
+			let http_client = MockSseClient::default();
+			let client = openai::Client::<MockSseClient>::builder()
+				.api_key("test-key")
+				.base_url(base_url)
+				.http_client(http_client.clone())
+				.build()
+				.unwrap();
+
+			let _ = client
+				.embedding_model(openai::TEXT_EMBEDDING_3_SMALL)
+				.embed_texts(vec!["Hello".to_string()])
+				.await;
+
+			assert_eq!(
+				http_client.request_uris(),
+				vec![expected(models_url, "/embeddings")],
+				"base URL {base_url}"
+			);
+		}
+	}
+
+	#[test]
+	fn test_merge_stream_params_keeps_user_stream_options() {
+		let mut params = Some(serde_json::json!({
+			"stream_options": {"include_obfuscation": false},
+			"seed": 42,
+		}));
+
+		super::merge_stream_params(&mut params);
+
+		assert_eq!(
+			params.unwrap(),
+			serde_json::json!({
+				"stream": true,
+				"stream_options": {"include_obfuscation": false, "include_usage": true},
+				"seed": 42,
+			})
+		);
+	}
+
+	#[tokio::test]
+	async fn test_list_and_get_groq_models() {
+		let http_client = MockSseClient::default().with_json_responses([
+			r#"{
+				"object": "list",
+				"data": [
+					{
+						"id": "llama-3.3-70b-versatile",
+						"object": "model",
+						"created": 1733447754,
+						"owned_by": "Meta",
+						"active": true,
+						"context_window": 131072,
+						"public_apps": null,
+						"max_completion_tokens": 32768
+					},
+					{
+						"id": "whisper-large-v3",
+						"object": "model",
+						"created": 1693721698,
+						"owned_by": "OpenAI",
+						"active": true,
+						"context_window": 448,
+						"public_apps": null
+					}
+				]
+			}"#,
+			r#"{
+				"id": "llama-3.3-70b-versatile",
+				"object": "model",
+				"created": 1733447754,
+				"owned_by": "Meta",
+				"active": true,
+				"context_window": 131072,
+				"public_apps": null,
+				"max_completion_tokens": 32768
+			}"#,
+		]);
+		let client = groq::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client.clone())
+			.build()
+			.unwrap();
+
+		let models = client.list_models().await.unwrap();
+		assert_eq!(models.len(), 2);
+		assert_eq!(models[1].id, "whisper-large-v3");
+		assert_eq!(models[1].max_completion_tokens, None);
+
+		let model = client.get_model("llama-3.3-70b-versatile").await.unwrap();
+		assert_eq!(model, models[0]);
+		assert_eq!(
+			model,
+			ModelInfo {
+				id: "llama-3.3-70b-versatile".to_string(),
+				owned_by: Some("Meta".to_string()),
+				created: Some(1733447754),
+				context_window: Some(131072),
+				max_completion_tokens: Some(32768),
+				active: Some(true),
+			}
+		);
+
+		assert_eq!(
+			http_client.request_uris(),
+			[
+				"https://api.groq.com/openai/v1/models",
+				"https://api.groq.com/openai/v1/models/llama-3.3-70b-versatile"
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn test_list_models_error() {
+		let http_client = MockSseClient::default().with_status_response(
+			401,
+			r#"{"error":{"message":"Invalid API Key","type":"invalid_request_error"}}"#,
+		);
+		let client = groq::Client::<MockSseClient>::builder()
+			.api_key("test-key")
+			.http_client(http_client)
+			.build()
+			.unwrap();
+
+		let error = client.list_models().await.unwrap_err();
+		assert!(
+			matches!(&error, ModelsError::ProviderError(text) if text.contains("Invalid API Key")),
+			"{error}"
+		);
+	}
+
+	#[test]
+	fn test_join_url() {
+		use crate::client::join_url;
+
+		assert_eq!(
+			join_url("https://host", "v1/models"),
+			"https://host/v1/models"
+		);
+		assert_eq!(
+			join_url("https://host/", "/v1/models"),
+			"https://host/v1/models"
+		);
+		assert_eq!(
+			join_url("https://host:8443/prefix/", "//chat/completions"),
+			"https://host:8443/prefix/chat/completions"
+		);
+		// Slashes in the query aren't touched
+		assert_eq!(
+			join_url("https://host/v1/", "/files?url=https://example.com//a"),
+			"https://host/v1/files?url=https://example.com//a"
+		);
+		// The query of the base URL comes after the path
+		assert_eq!(
+			join_url("https://host/v1?api-version=1", "models"),
+			"https://host/v1/models?api-version=1"
+		);
+		assert_eq!(
+			join_url("https://host/v1/?api-version=1", "/models?limit=10"),
+			"https://host/v1/models?limit=10&api-version=1"
+		);
+		// Providers with a blank base URL use the path as the full URL
+		assert_eq!(
+			join_url(
+				"",
+				"https://example.openai.azure.com/openai/deployments/gpt-4o/chat/completions"
+			),
+			"https://example.openai.azure.com/openai/deployments/gpt-4o/chat/completions"
+		);
+	}
 }
@@ -3,8 +3,8 @@ use std::fmt::Debug;
 use serde::{Deserialize, Serialize};
 
 use crate::client::{
-	self, BearerAuth, Capabilities, Capable, DebugExt, Nothing, Provider, ProviderBuilder,
-	ProviderClient,
+	self, BearerAuth, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider,
+	ProviderBuilder, ProviderClient,
 };
 use crate::completion::GetTokenUsage;
 use crate::http_client;
@@ -60,9 +60,12 @@ impl ProviderClient for Client {
 	/// Create a new openrouter client from the `OPENROUTER_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
-		let api_key = std::env::var("OPENROUTER_API_KEY").expect("OPENROUTER_API_KEY not set");
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
 
-		Self::new(&api_key).unwrap()
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("OPENROUTER_API_KEY")?;
+		Ok(Self::new(&api_key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
@@ -77,6 +80,15 @@ pub struct Usage {
 	pub prompt_tokens: usize,
 	pub completion_tokens: usize,
 	pub total_tokens: usize,
+	/// Cost of the request in credits, see [usage accounting](https://openrouter.ai/docs/use-cases/usage-accounting)
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cost: Option<f64>,
+	/// Prompt tokens counted by the tokenizer of the upstream model
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub native_tokens_prompt: Option<usize>,
+	/// Completion tokens counted by the tokenizer of the upstream model
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub native_tokens_completion: Option<usize>,
 }
 
 impl std::fmt::Display for Usage {
@@ -89,13 +101,22 @@ impl std::fmt::Display for Usage {
 	}
 }
 
+/// The native token counts are used when present, falling back to OpenRouter's normalized counts.
 impl GetTokenUsage for Usage {
 	fn token_usage(&self) -> Option<crate::completion::Usage> {
 		let mut usage = crate::completion::Usage::new();
 
-		usage.input_tokens = self.prompt_tokens as u64;
-		usage.output_tokens = self.completion_tokens as u64;
-		usage.total_tokens = self.total_tokens as u64;
+		if self.native_tokens_prompt.is_some() || self.native_tokens_completion.is_some() {
+			usage.input_tokens = self.native_tokens_prompt.unwrap_or(self.prompt_tokens) as u64;
+			usage.output_tokens = self
+				.native_tokens_completion
+				.unwrap_or(self.completion_tokens) as u64;
+			usage.total_tokens = usage.input_tokens + usage.output_tokens;
+		} else {
+			usage.input_tokens = self.prompt_tokens as u64;
+			usage.output_tokens = self.completion_tokens as u64;
+			usage.total_tokens = self.total_tokens as u64;
+		}
 
 		Some(usage)
 	}
@@ -4,7 +4,7 @@ use tracing::{Instrument, Level, enabled, info_span};
 
 use super::client::{ApiResponse, Client, Usage};
 use super::streaming::StreamingCompletionResponse;
-use crate::completion::{self, CompletionError, CompletionRequest};
+use crate::completion::{self, CompletionError, CompletionRequest, GetTokenUsage};
 use crate::http_client::HttpClientExt;
 use crate::one_or_many::string_or_one_or_many;
 use crate::providers::openai;
@@ -34,6 +34,14 @@ pub struct CompletionResponse {
 	pub usage: Option<Usage>,
 }
 
+impl CompletionResponse {
+	/// Cost of the request in credits, when reported by OpenRouter.
+	/// See [CompletionModel::with_include_usage].
+	pub fn cost(&self) -> Option<f64> {
+		self.usage.as_ref()?.cost
+	}
+}
+
 impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionResponse> {
 	type Error = CompletionError;
 
@@ -94,18 +102,14 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 		let usage = response
 			.usage
 			.as_ref()
-			.map(|usage| completion::Usage {
-				input_tokens: usage.prompt_tokens as u64,
-				output_tokens: (usage.total_tokens - usage.prompt_tokens) as u64,
-				total_tokens: usage.total_tokens as u64,
-				cached_input_tokens: 0,
-			})
+			.and_then(GetTokenUsage::token_usage)
 			.unwrap_or_default();
 
 		Ok(completion::CompletionResponse {
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -305,6 +309,9 @@ impl TryFrom<OneOrMany<message::AssistantContent>> for Vec<Message> {
 						"OpenRouter currently doesn't support images.".into(),
 					));
 				}
+				message::AssistantContent::Document(_) => {
+					tracing::warn!("OpenRouter doesn't support assistant documents, skipping");
+				}
 			}
 		}
 
@@ -385,18 +392,32 @@ pub(super) struct OpenrouterCompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<crate::providers::openai::completion::types::ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<crate::providers::openai::completion::types::ToolChoice>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	include_reasoning: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	usage: Option<UsageOptions>,
 	#[serde(flatten, skip_serializing_if = "Option::is_none")]
 	pub additional_params: Option<serde_json::Value>,
 }
 
+/// `usage` option of a request, see [CompletionModel::with_include_usage]
+#[derive(Debug, Serialize, Deserialize)]
+struct UsageOptions {
+	include: bool,
+}
+
 /// Parameters for building an OpenRouter CompletionRequest
 pub struct OpenRouterRequestParams<'a> {
 	pub model: &'a str,
 	pub request: CompletionRequest,
 	pub strict_tools: bool,
+	pub include_reasoning: Option<bool>,
+	pub include_usage: Option<bool>,
 }
 
 impl TryFrom<OpenRouterRequestParams<'_>> for OpenrouterCompletionRequest {
@@ -407,6 +428,8 @@ impl TryFrom<OpenRouterRequestParams<'_>> for OpenrouterCompletionRequest {
 			model,
 			request: req,
 			strict_tools,
+			include_reasoning,
+			include_usage,
 		} = params;
 
 		let mut full_history: Vec<Message> = match &req.preamble {
@@ -450,8 +473,11 @@ impl TryFrom<OpenRouterRequestParams<'_>> for OpenrouterCompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools,
 			tool_choice,
+			include_reasoning,
+			usage: include_usage.map(|include| UsageOptions { include }),
 			additional_params: req.additional_params,
 		})
 	}
@@ -465,6 +491,8 @@ impl TryFrom<(&str, CompletionRequest)> for OpenrouterCompletionRequest {
 			model,
 			request: req,
 			strict_tools: false,
+			include_reasoning: None,
+			include_usage: None,
 		})
 	}
 }
@@ -476,6 +504,11 @@ pub struct CompletionModel<T = reqwest::Client> {
 	/// Enable strict mode for tool schemas.
 	/// When enabled, tool schemas are sanitized to meet OpenAI's strict mode requirements.
 	pub strict_tools: bool,
+	/// Whether OpenRouter should return the model's reasoning tokens.
+	/// Left unset, OpenRouter applies the model's default.
+	pub include_reasoning: Option<bool>,
+	/// Whether OpenRouter should report the cost and native token counts of requests.
+	pub include_usage: Option<bool>,
 }
 
 impl<T> CompletionModel<T> {
@@ -484,6 +517,8 @@ impl<T> CompletionModel<T> {
 			client,
 			model: model.into(),
 			strict_tools: false,
+			include_reasoning: None,
+			include_usage: None,
 		}
 	}
 
@@ -491,7 +526,8 @@ impl<T> CompletionModel<T> {
 	///
 	/// When enabled, tool schemas are automatically sanitized to meet OpenAI's strict mode requirements:
 	/// - `additionalProperties: false` is added to all objects
-	/// - All properties are marked as required
+	/// - All properties are marked as required, optional ones becoming nullable instead
+	/// - `oneOf` is rewritten as `anyOf`
 	/// - `strict: true` is set on each function definition
 	///
 	/// Note: Not all models on OpenRouter support strict mode. This works best with OpenAI models.
@@ -499,6 +535,23 @@ impl<T> CompletionModel<T> {
 		self.strict_tools = true;
 		self
 	}
+
+	/// Ask OpenRouter to return (or omit) the reasoning tokens of reasoning-capable models
+	/// such as DeepSeek-R1. Returned reasoning is surfaced as
+	/// [`AssistantContent::Reasoning`](crate::completion::AssistantContent::Reasoning), or as
+	/// reasoning deltas when streaming.
+	pub fn with_include_reasoning(mut self, include_reasoning: bool) -> Self {
+		self.include_reasoning = Some(include_reasoning);
+		self
+	}
+
+	/// Ask OpenRouter to report the [cost](CompletionResponse::cost) of requests and, when
+	/// available, the token counts of the upstream model's tokenizer, which are then used for
+	/// the [usage](crate::completion::Usage) of responses.
+	pub fn with_include_usage(mut self, include_usage: bool) -> Self {
+		self.include_usage = Some(include_usage);
+		self
+	}
 }
 
 impl<T> completion::CompletionModel for CompletionModel<T>
@@ -514,15 +567,22 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let preamble = completion_request.preamble.clone();
 		let request = OpenrouterCompletionRequest::try_from(OpenRouterRequestParams {
 			model: self.model.as_ref(),
 			request: completion_request,
 			strict_tools: self.strict_tools,
+			include_reasoning: self.include_reasoning,
+			include_usage: self.include_usage,
 		})?;
 
 		if enabled!(Level::TRACE) {
@@ -545,10 +605,13 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		let body = serde_json::to_vec(&request)?;
 
@@ -560,6 +623,7 @@ where
 
 		async move {
 			let response = self.client.send::<_, Bytes>(req).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 			let status = response.status();
 			let response_body = response.into_body().into_future().await?.to_vec();
 
@@ -570,10 +634,12 @@ where
 						span.record_token_usage(&response.usage);
 						span.record("gen_ai.response.id", &response.id);
 						span.record("gen_ai.response.model_name", &response.model);
+						span.record_output_messages(&response.choices);
 
 						tracing::debug!(target: "clankers::completions",
                             "OpenRouter response: {response:?}");
-						response.try_into()
+						completion::CompletionResponse::try_from(response)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Err(err) => Err(CompletionError::ProviderError(err.message)),
 				}
@@ -594,6 +660,7 @@ where
 		crate::streaming::StreamingCompletionResponse<Self::StreamingResponse>,
 		CompletionError,
 	> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		CompletionModel::stream(self, completion_request).await
 	}
 }
@@ -662,4 +729,92 @@ mod tests {
 			_ => panic!("Expected Assistant message"),
 		}
 	}
+
+	fn response_with_usage(usage: serde_json::Value) -> CompletionResponse {
+		serde_json::from_value(json!({
+			"id": "gen-1",
+			"provider": "Anthropic",
+			"model": "anthropic/claude-3.7-sonnet",
+			"object": "chat.completion",
+			"created": 1765971703u64,
+			"choices": [{
+				"finish_reason": "stop",
+				"native_finish_reason": "end_turn",
+				"index": 0,
+				"message": { "role": "assistant", "content": "Hi" }
+			}],
+			"usage": usage
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn test_usage_with_cost_and_native_tokens() {
+		let response = response_with_usage(json!({
+			"prompt_tokens": 10,
+			"completion_tokens": 4,
+			"total_tokens": 14,
+			"cost": 0.00012,
+			"native_tokens_prompt": 12,
+			"native_tokens_completion": 5
+		}));
+		assert_eq!(response.cost(), Some(0.00012));
+
+		let response = completion::CompletionResponse::try_from(response).unwrap();
+		assert_eq!(response.usage.input_tokens, 12);
+		assert_eq!(response.usage.output_tokens, 5);
+		assert_eq!(response.usage.total_tokens, 17);
+		assert_eq!(
+			response.raw_response.usage.unwrap().native_tokens_prompt,
+			Some(12)
+		);
+	}
+
+	#[test]
+	fn test_usage_with_normalized_tokens_only() {
+		let response = response_with_usage(json!({
+			"prompt_tokens": 10,
+			"completion_tokens": 4,
+			"total_tokens": 14
+		}));
+		assert_eq!(response.cost(), None);
+
+		let response = completion::CompletionResponse::try_from(response).unwrap();
+		assert_eq!(response.usage.input_tokens, 10);
+		assert_eq!(response.usage.output_tokens, 4);
+		assert_eq!(response.usage.total_tokens, 14);
+	}
+
+	#[test]
+	fn test_include_usage_serialization() {
+		let params = |include_usage| OpenRouterRequestParams {
+			model: CLAUDE_3_7_SONNET,
+			request: CompletionRequest::builder("Hi").build(),
+			strict_tools: false,
+			include_reasoning: None,
+			include_usage,
+		};
+
+		let request = OpenrouterCompletionRequest::try_from(params(Some(true))).unwrap();
+		let json = serde_json::to_value(&request).unwrap();
+		assert_eq!(json["usage"], json!({ "include": true }));
+
+		let request = OpenrouterCompletionRequest::try_from(params(None)).unwrap();
+		let json = serde_json::to_value(&request).unwrap();
+		assert!(json.get("usage").is_none());
+	}
+
+	#[test]
+	fn test_stop_sequences_serialization() {
+		let request = CompletionRequest::builder("Count to ten")
+			.stop_sequences(vec!["\n\n".to_string(), "END".to_string()])
+			.build();
+
+		let request = OpenrouterCompletionRequest::try_from(("openai/gpt-4o", request)).unwrap();
+
+		assert_eq!(
+			serde_json::to_value(&request).unwrap()["stop"],
+			json!(["\n\n", "END"])
+		);
+	}
 }
@@ -8,10 +8,14 @@ use serde_json::Value;
 use tracing::info_span;
 use tracing_futures::Instrument;
 
-use super::completion::{OpenRouterRequestParams, OpenrouterCompletionRequest, ReasoningDetails};
+use super::completion::{
+	Message, OpenRouterRequestParams, OpenrouterCompletionRequest, ReasoningDetails,
+};
 use crate::completion::{CompletionError, CompletionRequest, GetTokenUsage};
 use crate::http_client::HttpClientExt;
 use crate::http_client::sse::{Event, GenericEventSource};
+use crate::providers::openai;
+use crate::telemetry::SpanCombinator;
 use crate::{json_utils, streaming};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -118,6 +122,8 @@ where
 			model: self.model.as_ref(),
 			request: completion_request,
 			strict_tools: self.strict_tools,
+			include_reasoning: self.include_reasoning,
+			include_usage: self.include_usage,
 		})?;
 
 		let params = json_utils::merge(
@@ -147,10 +153,13 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		tracing::Instrument::instrument(
 			send_compatible_streaming_request(self.client.clone(), req),
@@ -175,11 +184,17 @@ where
         // Accumulate tool calls by index while streaming
         let mut tool_calls: HashMap<usize, streaming::RawStreamingToolCall> = HashMap::new();
         let mut final_usage = None;
+        let mut text_content = String::new();
+        let mut reasoning_content = String::new();
+        let mut final_tool_calls = Vec::new();
 
         while let Some(event_result) = event_source.next().await {
             match event_result {
                 Ok(Event::Open) => {
                     tracing::trace!("SSE connection opened");
+                    if let Some(metadata) = event_source.response_metadata() {
+                        yield Ok(streaming::RawStreamingChoice::ResponseMetadata(metadata.clone()));
+                    }
                     continue;
                 }
 
@@ -268,6 +283,7 @@ where
 
                     // Streamed reasoning content
                     if let Some(reasoning) = &delta.reasoning && !reasoning.is_empty() {
+                        reasoning_content += reasoning;
                         yield Ok(streaming::RawStreamingChoice::ReasoningDelta {
                             reasoning: reasoning.clone(),
                             id: None,
@@ -276,6 +292,7 @@ where
 
                     // Streamed text content
                     if let Some(content) = &delta.content && !content.is_empty() {
+                        text_content += content;
                         yield Ok(streaming::RawStreamingChoice::Message(content.clone()));
                     }
 
@@ -287,6 +304,7 @@ where
                     // Finish reason
                     if let Some(finish_reason) = &choice.finish_reason && *finish_reason == FinishReason::ToolCalls {
                         for (_idx, tool_call) in tool_calls.into_iter() {
+                            final_tool_calls.push(output_tool_call(&tool_call));
                             yield Ok(streaming::RawStreamingChoice::ToolCall(tool_call));
                         }
                         tool_calls = HashMap::new();
@@ -295,6 +313,10 @@ where
                 Err(crate::http_client::Error::StreamEnded) => {
                     break;
                 }
+                Err(crate::http_client::Error::StreamStalled(timeout)) => {
+                    yield Err(CompletionError::StreamStalled(timeout));
+                    break;
+                }
                 Err(error) => {
                     tracing::error!(?error, "SSE error");
                     yield Err(CompletionError::ProviderError(error.to_string()));
@@ -308,9 +330,25 @@ where
 
         // Flush any accumulated tool calls (that weren't emitted as ToolCall earlier)
         for (_idx, tool_call) in tool_calls.into_iter() {
+            final_tool_calls.push(output_tool_call(&tool_call));
             yield Ok(streaming::RawStreamingChoice::ToolCall(tool_call));
         }
 
+        let content = if text_content.is_empty() {
+            vec![]
+        } else {
+            vec![openai::completion::types::AssistantContent::Text { text: text_content }]
+        };
+        tracing::Span::current().record_output_messages(&[Message::Assistant {
+            content,
+            refusal: None,
+            audio: None,
+            name: None,
+            tool_calls: final_tool_calls,
+            reasoning: (!reasoning_content.is_empty()).then_some(reasoning_content),
+            reasoning_details: vec![],
+        }]);
+
         // Final response with usage
         yield Ok(streaming::RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
             usage: final_usage.unwrap_or_default(),
@@ -322,6 +360,19 @@ where
 	)))
 }
 
+fn output_tool_call(
+	tool_call: &streaming::RawStreamingToolCall,
+) -> openai::completion::types::ToolCall {
+	openai::completion::types::ToolCall {
+		id: tool_call.id.clone(),
+		r#type: openai::completion::types::ToolType::Function,
+		function: openai::completion::types::Function {
+			name: tool_call.name.clone(),
+			arguments: tool_call.arguments.clone(),
+		},
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use serde_json::json;
@@ -529,4 +580,70 @@ mod tests {
 		assert_eq!(error.code, 500);
 		assert_eq!(error.message, "Provider disconnected");
 	}
+
+	#[tokio::test]
+	async fn test_deepseek_r1_stream_surfaces_reasoning() {
+		use crate::test_utils::MockSseClient;
+
+		// Captured from deepseek/deepseek-r1 with `include_reasoning: true`, ids shortened
+		let sse = concat!(
+			": OPENROUTER PROCESSING\n\n",
+			"data: {\"id\":\"gen-r1\",\"provider\":\"DeepInfra\",\"model\":\"deepseek/deepseek-r1\",\"object\":\"chat.completion.chunk\",\"created\":1740000000,\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\",\"reasoning\":\"Okay, the user\"},\"finish_reason\":null,\"native_finish_reason\":null,\"logprobs\":null}]}\n\n",
+			"data: {\"id\":\"gen-r1\",\"provider\":\"DeepInfra\",\"model\":\"deepseek/deepseek-r1\",\"object\":\"chat.completion.chunk\",\"created\":1740000000,\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\",\"reasoning\":\" wants 2+2.\"},\"finish_reason\":null,\"native_finish_reason\":null,\"logprobs\":null}]}\n\n",
+			"data: {\"id\":\"gen-r1\",\"provider\":\"DeepInfra\",\"model\":\"deepseek/deepseek-r1\",\"object\":\"chat.completion.chunk\",\"created\":1740000000,\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"2 + 2\",\"reasoning\":null},\"finish_reason\":null,\"native_finish_reason\":null,\"logprobs\":null}]}\n\n",
+			"data: {\"id\":\"gen-r1\",\"provider\":\"DeepInfra\",\"model\":\"deepseek/deepseek-r1\",\"object\":\"chat.completion.chunk\",\"created\":1740000000,\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\" = 4\",\"reasoning\":null},\"finish_reason\":\"stop\",\"native_finish_reason\":\"stop\",\"logprobs\":null}]}\n\n",
+			"data: {\"id\":\"gen-r1\",\"provider\":\"DeepInfra\",\"model\":\"deepseek/deepseek-r1\",\"object\":\"chat.completion.chunk\",\"created\":1740000000,\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\"},\"finish_reason\":null,\"native_finish_reason\":null,\"logprobs\":null}],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":9,\"total_tokens\":21}}\n\n",
+			"data: [DONE]\n\n",
+		);
+
+		let req = http::Request::builder()
+			.method("POST")
+			.uri("http://localhost/api/v1/chat/completions")
+			.body(Vec::new())
+			.unwrap();
+
+		let mut stream = send_compatible_streaming_request(MockSseClient::new(sse), req)
+			.await
+			.unwrap();
+
+		let mut reasoning = vec![];
+		let mut text = vec![];
+		let mut usage = None;
+		while let Some(chunk) = stream.next().await {
+			match chunk.unwrap() {
+				streaming::StreamedAssistantContent::ReasoningDelta {
+					reasoning: delta, ..
+				} => {
+					assert!(text.is_empty(), "reasoning should arrive before the answer");
+					reasoning.push(delta);
+				}
+				streaming::StreamedAssistantContent::Text(delta) => text.push(delta.text),
+				streaming::StreamedAssistantContent::Final(response) => {
+					usage = Some(response.usage)
+				}
+				other => panic!("unexpected chunk: {other:?}"),
+			}
+		}
+
+		assert_eq!(reasoning, ["Okay, the user", " wants 2+2."]);
+		assert_eq!(text, ["2 + 2", " = 4"]);
+		assert_eq!(usage.unwrap().total_tokens, 21);
+	}
+
+	#[test]
+	fn test_include_reasoning_serialization() {
+		let request = crate::completion::CompletionRequest::builder("2+2?").build();
+
+		let request = OpenrouterCompletionRequest::try_from(OpenRouterRequestParams {
+			model: "deepseek/deepseek-r1",
+			request,
+			strict_tools: false,
+			include_reasoning: Some(true),
+			include_usage: None,
+		})
+		.unwrap();
+
+		let json = serde_json::to_value(&request).unwrap();
+		assert_eq!(json["include_reasoning"], json!(true));
+	}
 }
@@ -1,4 +1,4 @@
-use crate::client::{self, BearerAuth, Capable, Nothing, ProviderClient};
+use crate::client::{self, BearerAuth, Capable, ClientError, Nothing, ProviderClient};
 use crate::providers::openai_compat::{self, CompletionModel, OpenAiCompat, PBuilder};
 
 #[derive(Debug, Default, Clone, Copy)]
@@ -31,6 +31,10 @@ impl ProviderClient for Client {
 		openai_compat::default_from_env::<Perplexity>()
 	}
 
+	fn try_from_env() -> Result<Self, ClientError> {
+		openai_compat::default_try_from_env::<Perplexity>()
+	}
+
 	fn from_val(input: Self::Input) -> Self {
 		Self::new(&input).unwrap()
 	}
@@ -9,6 +9,7 @@ use crate::providers::openai;
 use crate::providers::openai::completion::streaming::send_compatible_streaming_request;
 use crate::providers::openai_compat::{self, CompletionModel, FlatApiError, OpenAiCompat};
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 
 pub const SONAR_PRO: &str = "sonar_pro";
 pub const SONAR: &str = "sonar";
@@ -88,8 +89,10 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 					output_tokens: response.usage.completion_tokens as u64,
 					total_tokens: response.usage.total_tokens as u64,
 					cached_input_tokens: 0,
+					cache_creation_input_tokens: 0,
 				},
 				raw_response: response,
+				response_metadata: None,
 			}),
 			_ => Err(CompletionError::ResponseError(
 				"Response contained no assistant message".to_owned(),
@@ -115,6 +118,8 @@ impl TryFrom<(&str, CompletionRequest)> for PerplexityCompletionRequest {
 	type Error = CompletionError;
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
+		req.warn_unsupported_stop_sequences("Perplexity");
+
 		let mut partial_history = vec![];
 		if let Some(docs) = req.normalized_documents() {
 			partial_history.push(docs);
@@ -220,10 +225,15 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: completion::CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::completion_span(
 			Perplexity::PROVIDER_NAME,
 			&self.model,
@@ -240,6 +250,7 @@ where
 
 		let request =
 			PerplexityCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 
 		if tracing::enabled!(tracing::Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -257,11 +268,12 @@ where
 			.map_err(http_client::Error::from)?;
 
 		let async_block = async move {
-			let response = openai_compat::send_and_parse::<_, CompletionResponse, FlatApiError, _>(
-				&self.client,
-				req,
-				Perplexity::PROVIDER_NAME,
-			)
+			let (response, response_metadata) = openai_compat::send_and_parse::<
+				_,
+				CompletionResponse,
+				FlatApiError,
+				_,
+			>(&self.client, req, Perplexity::PROVIDER_NAME)
 			.await?;
 
 			// Record span fields manually for Perplexity
@@ -273,6 +285,7 @@ where
 			);
 			current_span.record("gen_ai.response.id", response.id.to_string());
 			current_span.record("gen_ai.response.model", response.model.to_string());
+			current_span.record_output_messages(&response.choices);
 
 			if tracing::enabled!(tracing::Level::TRACE) {
 				tracing::trace!(target: "clankers::responses",
@@ -281,7 +294,8 @@ where
 				);
 			}
 
-			response.try_into()
+			completion::CompletionResponse::try_from(response)
+				.map(|response| response.with_response_metadata(response_metadata))
 		};
 
 		async_block.instrument(span).await
@@ -291,6 +305,7 @@ where
 		&self,
 		completion_request: completion::CompletionRequest,
 	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = openai_compat::streaming_span(
 			Perplexity::PROVIDER_NAME,
 			&self.model,
@@ -307,6 +322,7 @@ where
 
 		let mut request =
 			PerplexityCompletionRequest::try_from((self.model.as_ref(), completion_request))?;
+		span.record_input_messages(&request.messages);
 		request.stream = true;
 
 		if tracing::enabled!(tracing::Level::TRACE) {
@@ -1,5 +1,6 @@
 use crate::client::{
-	self, BearerAuth, Capabilities, Capable, Nothing, Provider, ProviderBuilder, ProviderClient,
+	self, BearerAuth, Capabilities, Capable, ClientError, Nothing, Provider, ProviderBuilder,
+	ProviderClient,
 };
 use crate::http_client;
 
@@ -52,8 +53,12 @@ impl ProviderClient for Client {
 	/// Create a new Together AI client from the `TOGETHER_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
-		let api_key = std::env::var("TOGETHER_API_KEY").expect("TOGETHER_API_KEY not set");
-		Self::new(&api_key).unwrap()
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("TOGETHER_API_KEY")?;
+		Ok(Self::new(&api_key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
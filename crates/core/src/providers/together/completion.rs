@@ -11,6 +11,7 @@ use crate::completion::{self, CompletionError, CompletionRequest};
 use crate::http_client::HttpClientExt;
 use crate::providers::openai;
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 
 pub const YI_34B_CHAT: &str = "zero-one-ai/Yi-34B-Chat";
 pub const OLMO_7B_INSTRUCT: &str = "allenai/OLMo-7B-Instruct";
@@ -124,6 +125,8 @@ pub(super) struct TogetherAICompletionRequest {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	temperature: Option<f64>,
 	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
 	tools: Vec<crate::providers::openai::completion::types::ToolDefinition>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	tool_choice: Option<ToolChoice>,
@@ -165,6 +168,7 @@ impl TryFrom<(&str, CompletionRequest)> for TogetherAICompletionRequest {
 			model: model.to_string(),
 			messages: full_history,
 			temperature: req.temperature,
+			stop: req.stop_sequences,
 			tools: req
 				.tools
 				.clone()
@@ -205,6 +209,10 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: completion::CompletionRequest,
@@ -212,6 +220,7 @@ where
 		completion::CompletionResponse<openai::completion::types::CompletionResponse>,
 		CompletionError,
 	> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -224,6 +233,8 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -235,6 +246,7 @@ where
 			self.model.to_string().as_ref(),
 			completion_request,
 		))?;
+		span.record_input_messages(&request.messages);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -253,6 +265,7 @@ where
 
 		async move {
 			let response = self.client.send::<_, Bytes>(req).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 			let status = response.status();
 			let response_body = response.into_body().into_future().await?.to_vec();
 
@@ -265,6 +278,7 @@ where
 						let span = tracing::Span::current();
 						span.record("gen_ai.response.id", &response.id);
 						span.record("gen_ai.response.model_name", &response.model);
+						span.record_output_messages(&response.choices);
 						if let Some(ref usage) = response.usage {
 							span.record("gen_ai.usage.input_tokens", usage.prompt_tokens);
 							span.record(
@@ -279,7 +293,8 @@ where
 								serde_json::to_string_pretty(&response)?
 							);
 						}
-						response.try_into()
+						completion::CompletionResponse::try_from(response)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Error(err) => Err(CompletionError::ProviderError(err.error)),
 				}
@@ -297,6 +312,7 @@ where
 		&self,
 		request: CompletionRequest,
 	) -> Result<StreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let request = self.client.apply_default_params(request);
 		CompletionModel::stream(self, request).await
 	}
 }
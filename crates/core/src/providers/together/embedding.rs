@@ -73,6 +73,10 @@ where
 		Self::new(client.clone(), model, dims.unwrap_or_default())
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	fn ndims(&self) -> usize {
 		self.ndims
 	}
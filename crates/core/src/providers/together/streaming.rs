@@ -8,6 +8,7 @@ use crate::providers::openai;
 use crate::providers::openai::completion::streaming::send_compatible_streaming_request;
 use crate::providers::together::completion::TogetherAICompletionRequest;
 use crate::streaming::StreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 
 impl<T> CompletionModel<T>
 where
@@ -60,10 +61,13 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.messages);
 
 		send_compatible_streaming_request(self.client.clone(), req)
 			.instrument(span)
@@ -3,8 +3,8 @@ use serde::Deserialize;
 use serde_json::json;
 
 use crate::client::{
-	self, BearerAuth, Capabilities, Capable, DebugExt, Nothing, Provider, ProviderBuilder,
-	ProviderClient,
+	self, BearerAuth, Capabilities, Capable, ClientError, DebugExt, Nothing, Provider,
+	ProviderBuilder, ProviderClient,
 };
 use crate::embeddings;
 use crate::embeddings::EmbeddingError;
@@ -66,8 +66,12 @@ impl ProviderClient for Client {
 	/// Create a new OpenAI client from the `OPENAI_API_KEY` environment variable.
 	/// Panics if the environment variable is not set.
 	fn from_env() -> Self {
-		let api_key = std::env::var("VOYAGE_API_KEY").expect("VOYAGE_API_KEY not set");
-		Self::new(&api_key).unwrap()
+		Self::try_from_env().unwrap_or_else(|error| panic!("{error}"))
+	}
+
+	fn try_from_env() -> Result<Self, ClientError> {
+		let api_key = client::env_var("VOYAGE_API_KEY")?;
+		Ok(Self::new(&api_key)?)
 	}
 
 	fn from_val(input: Self::Input) -> Self {
@@ -173,6 +177,10 @@ where
 		Self::new(client.clone(), model, dims)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	fn ndims(&self) -> usize {
 		self.ndims
 	}
@@ -114,8 +114,9 @@ impl TryFrom<RigMessage> for Vec<Message> {
 			ToolResultContent, UserContent,
 		};
 
-		fn image_item(img: RigImage) -> Result<ContentItem, CompletionError> {
-			let url = match img.data {
+		fn image_item(mut img: RigImage) -> Result<ContentItem, CompletionError> {
+			img.sniff_media_type();
+			let url = match img.data.into_base64() {
 				DocumentSourceKind::Url(u) => u,
 				DocumentSourceKind::Base64(data) => {
 					let mime = img
@@ -126,7 +127,7 @@ impl TryFrom<RigMessage> for Vec<Message> {
 				}
 				_ => {
 					return Err(CompletionError::RequestError(
-						"xAI does not support raw image data; use base64 or URL".into(),
+						"xAI images must be base64, raw bytes or a URL".into(),
 					));
 				}
 			};
@@ -136,8 +137,9 @@ impl TryFrom<RigMessage> for Vec<Message> {
 			})
 		}
 
-		fn document_item(doc: Document) -> Result<ContentItem, CompletionError> {
-			let (file_data, file_url) = match doc.data {
+		fn document_item(mut doc: Document) -> Result<ContentItem, CompletionError> {
+			doc.sniff_media_type();
+			let (file_data, file_url) = match doc.data.into_base64() {
 				DocumentSourceKind::Url(url) => (None, Some(url)),
 				DocumentSourceKind::Base64(data) => {
 					let mime = doc
@@ -152,7 +154,7 @@ impl TryFrom<RigMessage> for Vec<Message> {
 				}
 				_ => {
 					return Err(CompletionError::RequestError(
-						"xAI does not support raw document data; use base64 or URL".into(),
+						"xAI documents must be base64, raw bytes, text or a URL".into(),
 					));
 				}
 			};
@@ -269,6 +271,9 @@ impl TryFrom<RigMessage> for Vec<Message> {
 								"xAI does not support images in assistant content".into(),
 							));
 						}
+						AssistantContent::Document(_) => {
+							tracing::warn!("xAI doesn't support assistant documents, skipping");
+						}
 					}
 				}
 
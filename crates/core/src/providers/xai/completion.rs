@@ -15,6 +15,7 @@ use crate::providers::openai::completion::types::ToolChoice;
 use crate::providers::openai::responses_api::streaming::StreamingCompletionResponse;
 use crate::providers::openai::responses_api::types::{Output, ResponsesUsage};
 use crate::streaming::StreamingCompletionResponse as BaseStreamingCompletionResponse;
+use crate::telemetry::SpanCombinator;
 
 /// xAI completion models as of 2025-06-04
 pub const GROK_2_1212: &str = "grok-2-1212";
@@ -46,6 +47,8 @@ impl TryFrom<(&str, CompletionRequest)> for XAICompletionRequest {
 	type Error = CompletionError;
 
 	fn try_from((model, req): (&str, CompletionRequest)) -> Result<Self, Self::Error> {
+		req.warn_unsupported_stop_sequences("xAI");
+
 		let mut input: Vec<Message> = req
 			.preamble
 			.as_ref()
@@ -112,6 +115,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 					.clone()
 					.map(|x| x.cached_tokens)
 					.unwrap_or_default(),
+				cache_creation_input_tokens: 0,
 			})
 			.unwrap_or_default();
 
@@ -119,6 +123,7 @@ impl TryFrom<CompletionResponse> for completion::CompletionResponse<CompletionRe
 			choice,
 			usage,
 			raw_response: response,
+			response_metadata: None,
 		})
 	}
 }
@@ -151,10 +156,15 @@ where
 		Self::new(client.clone(), model)
 	}
 
+	fn last_request(&self) -> Option<crate::client::CapturedRequest> {
+		self.client.last_request()
+	}
+
 	async fn completion(
 		&self,
 		completion_request: completion::CompletionRequest,
 	) -> Result<completion::CompletionResponse<CompletionResponse>, CompletionError> {
+		let completion_request = self.client.apply_default_params(completion_request);
 		let span = if tracing::Span::current().is_disabled() {
 			info_span!(
 				target: "clankers::completions",
@@ -167,6 +177,8 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
@@ -176,6 +188,7 @@ where
 
 		let request =
 			XAICompletionRequest::try_from((self.model.to_string().as_ref(), completion_request))?;
+		span.record_input_messages(&request.input);
 
 		if enabled!(Level::TRACE) {
 			tracing::trace!(target: "clankers::completions",
@@ -193,12 +206,15 @@ where
 
 		async move {
 			let response = self.client.send::<_, Bytes>(req).await?;
+			let response_metadata = completion::ResponseMetadata::from_response(&response);
 			let status = response.status();
 			let response_body = response.into_body().into_future().await?.to_vec();
 
 			if status.is_success() {
 				match serde_json::from_slice::<ApiResponse<CompletionResponse>>(&response_body)? {
 					ApiResponse::Ok(response) => {
+						tracing::Span::current().record_output_messages(&response.output);
+
 						if enabled!(Level::TRACE) {
 							tracing::trace!(target: "clankers::completions",
 								"xAI completion response: {}",
@@ -206,7 +222,8 @@ where
 							);
 						}
 
-						response.try_into()
+						completion::CompletionResponse::try_from(response)
+							.map(|response| response.with_response_metadata(response_metadata))
 					}
 					ApiResponse::Error(error) => {
 						Err(CompletionError::ProviderError(error.message()))
@@ -226,6 +243,7 @@ where
 		&self,
 		request: CompletionRequest,
 	) -> Result<BaseStreamingCompletionResponse<Self::StreamingResponse>, CompletionError> {
+		let request = self.client.apply_default_params(request);
 		self.stream(request).await
 	}
 }
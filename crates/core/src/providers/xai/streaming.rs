@@ -13,12 +13,11 @@ use crate::http_client::HttpClientExt;
 use crate::http_client::sse::{Event, GenericEventSource};
 use crate::json_utils;
 use crate::providers::openai::responses_api::streaming::{
-	ItemChunkKind, ResponseChunk, ResponseChunkKind, StreamingCompletionChunk,
-	StreamingCompletionResponse, StreamingItemDoneOutput,
+	ResponsesStreamState, StreamingCompletionChunk, StreamingCompletionResponse,
 };
-use crate::providers::openai::responses_api::types::{Output, ReasoningSummary, ResponsesUsage};
 use crate::providers::xai::completion::{CompletionModel, XAICompletionRequest};
 use crate::streaming::{self, RawStreamingChoice};
+use crate::telemetry::SpanCombinator;
 
 impl<T> CompletionModel<T>
 where
@@ -66,10 +65,13 @@ where
 				gen_ai.response.model = tracing::field::Empty,
 				gen_ai.usage.output_tokens = tracing::field::Empty,
 				gen_ai.usage.input_tokens = tracing::field::Empty,
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
 			)
 		} else {
 			tracing::Span::current()
 		};
+		span.record_input_messages(&request.input);
 
 		send_xai_streaming_request(self.client.clone(), req)
 			.instrument(span)
@@ -89,156 +91,64 @@ where
 	let mut event_source = GenericEventSource::new(http_client, req);
 
 	let stream = stream! {
-        let span = tracing::Span::current();
-        let mut final_usage = ResponsesUsage::new();
-        let mut tool_call_internal_ids: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-
-        while let Some(event_result) = event_source.next().await {
-            match event_result {
-                Ok(Event::Open) => {
-                    tracing::trace!("SSE connection opened");
-                    continue;
-                }
-
-                Ok(Event::Message(evt)) => {
-                    if evt.data.trim().is_empty() || evt.data == "[DONE]" {
-                        continue;
-                    }
-
-                    let data = match serde_json::from_str::<StreamingCompletionChunk>(&evt.data) {
-                        Ok(data) => data,
-                        Err(err) => {
-                            tracing::debug!(?err, data = evt.data, "Failed to parse SSE message");
-                            continue;
-                        }
-                    };
-
-                    if let StreamingCompletionChunk::Delta(chunk) = &data {
-                        match &chunk.data {
-                            ItemChunkKind::OutputItemAdded(StreamingItemDoneOutput {
-                                item: Output::FunctionCall(func),
-                                ..
-                            }) => {
-                                let internal_call_id = tool_call_internal_ids
-                                    .entry(func.id.clone())
-                                    .or_insert_with(|| nanoid::nanoid!())
-                                    .clone();
-                                yield Ok(RawStreamingChoice::ToolCallDelta {
-                                    id: func.id.clone(),
-                                    internal_call_id,
-                                    content: streaming::ToolCallDeltaContent::Name(func.name.clone()),
-                                });
-                            }
-
-                            ItemChunkKind::OutputItemDone(StreamingItemDoneOutput {
-                                item: Output::FunctionCall(func),
-                                ..
-                            }) => {
-                                let internal_id = tool_call_internal_ids
-                                    .entry(func.id.clone())
-                                    .or_insert_with(|| nanoid::nanoid!())
-                                    .clone();
-                                // Yield immediately so users can execute tools while stream continues
-                                yield Ok(RawStreamingChoice::ToolCall(
-                                    streaming::RawStreamingToolCall::new(
-                                        func.id.clone(),
-                                        func.name.clone(),
-                                        func.arguments.clone(),
-                                    )
-                                    .with_internal_call_id(internal_id)
-                                    .with_call_id(func.call_id.clone()),
-                                ));
-                            }
-
-                            ItemChunkKind::OutputItemDone(StreamingItemDoneOutput {
-                                item: Output::Reasoning { summary, id },
-                                ..
-                            }) => {
-                                let reasoning = summary
-                                    .iter()
-                                    .map(|x| {
-                                        let ReasoningSummary::SummaryText { text } = x;
-                                        text.to_owned()
-                                    })
-                                    .collect::<Vec<String>>()
-                                    .join("\n");
-                                yield Ok(RawStreamingChoice::Reasoning {
-                                    id: Some(id.to_string()),
-                                    reasoning,
-                                    signature: None,
-                                });
-                            }
-
-                            ItemChunkKind::OutputTextDelta(delta) => {
-                                yield Ok(RawStreamingChoice::Message(delta.delta.clone()));
-                            }
-
-                            ItemChunkKind::ReasoningSummaryTextDelta(delta) => {
-                                yield Ok(RawStreamingChoice::ReasoningDelta {
-                                    id: None,
-                                    reasoning: delta.delta.clone(),
-                                });
-                            }
-
-                            ItemChunkKind::FunctionCallArgsDelta(delta) => {
-                                let internal_call_id = tool_call_internal_ids
-                                    .entry(delta.item_id.clone())
-                                    .or_insert_with(|| nanoid::nanoid!())
-                                    .clone();
-                                yield Ok(RawStreamingChoice::ToolCallDelta {
-                                    id: delta.item_id.clone(),
-                                    internal_call_id,
-                                    content: streaming::ToolCallDeltaContent::Delta(delta.delta.clone()),
-                                });
-                            }
-
-                            ItemChunkKind::RefusalDelta(delta) => {
-                                yield Ok(RawStreamingChoice::Message(delta.delta.clone()));
-                            }
-
-                            _ => continue,
-                        }
-                    }
-
-                    if let StreamingCompletionChunk::Response(chunk) = data
-                        && let ResponseChunk {
-                            kind: ResponseChunkKind::ResponseCompleted,
-                            response,
-                            ..
-                        } = *chunk
-                    {
-                            span.record("gen_ai.response.id", &response.id);
-                            span.record("gen_ai.response.model", &response.model);
-                            if let Some(usage) = response.usage {
-                                final_usage = usage;
-                            }
-                    }
-                }
-
-                Err(crate::http_client::Error::StreamEnded) => {
-                    break;
-                }
-
-                Err(error) => {
-                    tracing::error!(?error, "SSE error");
-                    yield Err(CompletionError::ProviderError(error.to_string()));
-                    break;
-                }
-            }
-        }
-
-        event_source.close();
-
-        if !span.is_disabled() {
-            span.record("gen_ai.usage.input_tokens", final_usage.input_tokens);
-            span.record("gen_ai.usage.output_tokens", final_usage.output_tokens);
-        }
-
-        yield Ok(RawStreamingChoice::FinalResponse(StreamingCompletionResponse {
-            usage: final_usage,
-        }));
-    }
-    .instrument(span);
+		let mut state = ResponsesStreamState::default();
+
+		while let Some(event_result) = event_source.next().await {
+			match event_result {
+				Ok(Event::Open) => {
+					tracing::trace!("SSE connection opened");
+					if let Some(metadata) = event_source.response_metadata() {
+						yield Ok(RawStreamingChoice::ResponseMetadata(metadata.clone()));
+					}
+					continue;
+				}
+
+				Ok(Event::Message(evt)) => {
+					if evt.data.trim().is_empty() || evt.data == "[DONE]" {
+						continue;
+					}
+
+					let data = match serde_json::from_str::<StreamingCompletionChunk>(&evt.data) {
+						Ok(data) => data,
+						Err(err) => {
+							tracing::debug!(?err, data = evt.data, "Failed to parse SSE message");
+							continue;
+						}
+					};
+
+					for choice in state.handle(data) {
+						yield choice;
+					}
+				}
+
+				Err(crate::http_client::Error::StreamEnded) => {
+					break;
+				}
+
+				Err(crate::http_client::Error::StreamStalled(timeout)) => {
+					yield Err(CompletionError::StreamStalled(timeout));
+					break;
+				}
+				Err(error) => {
+					tracing::error!(?error, "SSE error");
+					yield Err(CompletionError::ProviderError(error.to_string()));
+					break;
+				}
+			}
+		}
+
+		event_source.close();
+
+		let final_response = state.final_response();
+		let span = tracing::Span::current();
+		if !span.is_disabled() {
+			span.record("gen_ai.usage.input_tokens", final_response.usage.input_tokens);
+			span.record("gen_ai.usage.output_tokens", final_response.usage.output_tokens);
+		}
+
+		yield Ok(RawStreamingChoice::FinalResponse(final_response));
+	}
+	.instrument(span);
 
 	Ok(streaming::StreamingCompletionResponse::stream(Box::pin(
 		stream,
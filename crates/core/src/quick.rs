@@ -0,0 +1,354 @@
+//! One-shot completions and embeddings for scripts and examples, without setting up a client,
+//! a model and an agent. Clients are created from the environment, see
+//! [ProviderKind::client_from_env].
+//!
+//! ```no_run
+//! use clankers::quick::{self, ProviderKind, RequestOptions};
+//!
+//! # async fn run() -> Result<(), quick::Error> {
+//! let answer = quick::complete(ProviderKind::OpenAI, "gpt-4o", "Who are you?").await?;
+//!
+//! let answer = quick::complete_with(
+//!     ProviderKind::Anthropic,
+//!     "claude-sonnet-4-5",
+//!     RequestOptions::new("Who are you?")
+//!         .preamble("You are Marvin.")
+//!         .max_tokens(256),
+//! )
+//! .await?;
+//!
+//! let embeddings = quick::embed(ProviderKind::OpenAI, "text-embedding-3-small", ["Hello"]).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::client::builder::{DynProviderClient, Error, ProviderKind};
+use crate::completion::dynamic::CompletionModelDyn;
+use crate::completion::{AssistantContent, CompletionRequest};
+use crate::embeddings::Embedding;
+use crate::embeddings::dynamic::EmbeddingModelDyn;
+
+/// The options of a one-shot completion, see [complete_with].
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+	pub prompt: String,
+	pub preamble: Option<String>,
+	pub temperature: Option<f64>,
+	pub max_tokens: Option<u64>,
+	/// Additional provider-specific parameters
+	pub additional_params: Option<serde_json::Value>,
+}
+
+impl RequestOptions {
+	pub fn new(prompt: impl Into<String>) -> Self {
+		Self {
+			prompt: prompt.into(),
+			..Default::default()
+		}
+	}
+
+	pub fn preamble(mut self, preamble: impl Into<String>) -> Self {
+		self.preamble = Some(preamble.into());
+		self
+	}
+
+	pub fn temperature(mut self, temperature: f64) -> Self {
+		self.temperature = Some(temperature);
+		self
+	}
+
+	pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+		self.max_tokens = Some(max_tokens);
+		self
+	}
+
+	pub fn additional_params(mut self, additional_params: serde_json::Value) -> Self {
+		self.additional_params = Some(additional_params);
+		self
+	}
+}
+
+impl From<RequestOptions> for CompletionRequest {
+	fn from(options: RequestOptions) -> Self {
+		let builder = CompletionRequest::builder(options.prompt)
+			.temperature_opt(options.temperature)
+			.max_tokens_opt(options.max_tokens)
+			.additional_params_opt(options.additional_params);
+
+		match options.preamble {
+			Some(preamble) => builder.preamble(preamble),
+			None => builder,
+		}
+		.build()
+	}
+}
+
+/// Sends `prompt` to `model` and returns the text of the response.
+pub async fn complete(provider: ProviderKind, model: &str, prompt: &str) -> Result<String, Error> {
+	complete_with(provider, model, RequestOptions::new(prompt)).await
+}
+
+/// Sends a request built from `options` to `model` and returns the text of the response, the
+/// text parts being joined by newlines.
+pub async fn complete_with(
+	provider: ProviderKind,
+	model: &str,
+	options: RequestOptions,
+) -> Result<String, Error> {
+	let model = provider.completion_model(model)?;
+	complete_with_model(model.as_ref(), options).await
+}
+
+/// Embeds `texts` with `model`, in a single request.
+pub async fn embed<I>(
+	provider: ProviderKind,
+	model: &str,
+	texts: I,
+) -> Result<Vec<Embedding>, Error>
+where
+	I: IntoIterator,
+	I::Item: Into<String>,
+{
+	let texts = texts.into_iter().map(Into::into).collect();
+	let model = provider.embedding_model(model)?;
+	embed_with_model(model.as_ref(), texts).await
+}
+
+async fn complete_with_model(
+	model: &dyn CompletionModelDyn,
+	options: RequestOptions,
+) -> Result<String, Error> {
+	let response = model.completion(options.into()).await?;
+
+	Ok(response
+		.choice
+		.iter()
+		.filter_map(|content| match content {
+			AssistantContent::Text(text) => Some(text.text.as_str()),
+			_ => None,
+		})
+		.collect::<Vec<_>>()
+		.join("\n"))
+}
+
+async fn embed_with_model(
+	model: &dyn EmbeddingModelDyn,
+	texts: Vec<String>,
+) -> Result<Vec<Embedding>, Error> {
+	Ok(model.embed_texts(texts).await?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::client::ClientError;
+	use crate::providers::{anthropic, openai};
+	use crate::test_utils::{MockSseClient, with_env};
+
+	const COMPLETION: &str = r#"{
+		"id": "resp_1",
+		"object": "response",
+		"created_at": 1741290958,
+		"status": "completed",
+		"error": null,
+		"incomplete_details": null,
+		"instructions": "Be brief.",
+		"max_output_tokens": 16,
+		"model": "gpt-4.1-2025-04-14",
+		"output": [{
+			"type": "message",
+			"id": "msg_1",
+			"status": "completed",
+			"role": "assistant",
+			"content": [{ "type": "output_text", "text": "Hello!", "annotations": [] }]
+		}],
+		"tools": [],
+		"usage": {
+			"input_tokens": 8,
+			"input_tokens_details": { "cached_tokens": 0 },
+			"output_tokens": 2,
+			"output_tokens_details": { "reasoning_tokens": 0 },
+			"total_tokens": 10
+		}
+	}"#;
+
+	const EMBEDDINGS: &str = r#"{
+		"object": "list",
+		"model": "text-embedding-3-small",
+		"data": [
+			{ "object": "embedding", "embedding": [0.1, 0.2], "index": 0 },
+			{ "object": "embedding", "embedding": [0.3, 0.4], "index": 1 }
+		],
+		"usage": { "prompt_tokens": 2, "total_tokens": 2 }
+	}"#;
+
+	fn openai_client(http_client: MockSseClient) -> Box<dyn DynProviderClient> {
+		Box::new(
+			openai::Client::<MockSseClient>::builder()
+				.api_key("test-key")
+				.http_client(http_client)
+				.build()
+				.unwrap(),
+		)
+	}
+
+	#[tokio::test]
+	async fn test_complete_through_registry() {
+		let http_client = MockSseClient::default().with_json_response(COMPLETION);
+		let client = openai_client(http_client.clone());
+		let model = ProviderKind::OpenAI
+			.require_completion(client.as_ref(), "gpt-4.1")
+			.unwrap();
+
+		let text = complete_with_model(
+			model.as_ref(),
+			RequestOptions::new("Hi")
+				.preamble("Be brief.")
+				.temperature(0.5)
+				.max_tokens(16),
+		)
+		.await
+		.unwrap();
+		assert_eq!(text, "Hello!");
+
+		let body = &http_client.request_bodies()[0];
+		assert_eq!(body["model"], "gpt-4.1");
+		assert_eq!(body["temperature"], 0.5);
+		assert_eq!(body["max_output_tokens"], 16);
+		let input = body["input"].to_string();
+		assert!(input.contains("\"Be brief.\""), "{input}");
+		assert!(input.contains("\"Hi\""), "{input}");
+	}
+
+	#[tokio::test]
+	async fn test_embed_through_registry() {
+		let http_client = MockSseClient::default().with_json_response(EMBEDDINGS);
+		let client = openai_client(http_client.clone());
+		let model = ProviderKind::OpenAI
+			.require_embeddings(client.as_ref(), openai::TEXT_EMBEDDING_3_SMALL)
+			.unwrap();
+		assert_eq!(model.ndims(), 1536);
+
+		let embeddings = embed_with_model(model.as_ref(), vec!["a".into(), "b".into()])
+			.await
+			.unwrap();
+		assert_eq!(embeddings.len(), 2);
+		assert_eq!(embeddings[1].document, "b");
+		assert_eq!(embeddings[1].vec, vec![0.3, 0.4]);
+		assert_eq!(
+			http_client.request_bodies()[0]["input"],
+			serde_json::json!(["a", "b"])
+		);
+	}
+
+	fn api_key_var(kind: ProviderKind) -> &'static str {
+		match kind {
+			ProviderKind::Anthropic => "ANTHROPIC_API_KEY",
+			ProviderKind::Cohere => "COHERE_API_KEY",
+			ProviderKind::DeepSeek => "DEEPSEEK_API_KEY",
+			ProviderKind::Gemini => "GEMINI_API_KEY",
+			ProviderKind::Groq => "GROQ_API_KEY",
+			ProviderKind::Mistral => "MISTRAL_API_KEY",
+			ProviderKind::Moonshot => "MOONSHOT_API_KEY",
+			ProviderKind::OpenAI => "OPENAI_API_KEY",
+			ProviderKind::OpenRouter => "OPENROUTER_API_KEY",
+			ProviderKind::Perplexity => "PERPLEXITY_API_KEY",
+			ProviderKind::Together => "TOGETHER_API_KEY",
+			ProviderKind::VoyageAI => "VOYAGE_API_KEY",
+			ProviderKind::XAI => "XAI_API_KEY",
+		}
+	}
+
+	/// Unsets every provider's API key, setting only `vars`
+	fn with_provider_env<T>(vars: &[(&str, Option<&str>)], f: impl FnOnce() -> T) -> T {
+		let mut env: Vec<_> = ProviderKind::ALL
+			.into_iter()
+			.map(|kind| (api_key_var(kind), None))
+			.collect();
+		env.push(("OPENAI_BASE_URL", None));
+		env.extend_from_slice(vars);
+
+		with_env(&env, f)
+	}
+
+	#[test]
+	fn test_missing_env_var_is_named() {
+		for kind in ProviderKind::ALL {
+			let error = with_provider_env(&[], || kind.client_from_env().err().unwrap());
+			assert!(
+				matches!(
+					&error,
+					Error::Client(ClientError::MissingEnvVar(names))
+						if names == &[api_key_var(kind)]
+				),
+				"{kind}: {error}"
+			);
+		}
+	}
+
+	#[test]
+	fn test_every_provider_builds_from_env() {
+		for kind in ProviderKind::ALL {
+			let client = with_provider_env(&[(api_key_var(kind), Some("test-key"))], || {
+				kind.client_from_env().unwrap()
+			});
+			assert!(
+				client.completion_model("model").is_some()
+					|| client.embedding_model("model").is_some(),
+				"{kind}"
+			);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_client_from_env_honors_base_url() {
+		let client = with_provider_env(
+			&[
+				("OPENAI_API_KEY", Some("test-key")),
+				("OPENAI_BASE_URL", Some("http://127.0.0.1:1/custom")),
+			],
+			|| ProviderKind::OpenAI.client_from_env().unwrap(),
+		);
+		let model = ProviderKind::OpenAI
+			.require_completion(client.as_ref(), "gpt-4.1")
+			.unwrap();
+
+		let error = complete_with_model(model.as_ref(), RequestOptions::new("Hi"))
+			.await
+			.err()
+			.unwrap();
+		assert!(
+			format!("{error:?}").contains("127.0.0.1:1/custom"),
+			"{error:?}"
+		);
+	}
+
+	#[test]
+	fn test_not_capable() {
+		let client: Box<dyn DynProviderClient> =
+			Box::new(anthropic::Client::<reqwest::Client>::new("test-key").unwrap());
+		let error = ProviderKind::Anthropic
+			.require_embeddings(client.as_ref(), "model")
+			.err()
+			.unwrap();
+		assert_eq!(
+			error.to_string(),
+			"Provider 'anthropic' cannot be coerced to a 'embedding model'"
+		);
+	}
+
+	#[test]
+	fn test_provider_kind_from_str() {
+		for kind in ProviderKind::ALL {
+			assert_eq!(kind.name().parse::<ProviderKind>().unwrap(), kind);
+		}
+		assert_eq!(
+			"OpenAI".parse::<ProviderKind>().unwrap(),
+			ProviderKind::OpenAI
+		);
+		assert_eq!(
+			"nope".parse::<ProviderKind>().err().unwrap().to_string(),
+			"Provider 'nope' not found"
+		);
+	}
+}
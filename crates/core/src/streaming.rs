@@ -8,13 +8,16 @@
 //! - [StreamingCompletion]: Defines a low-level streaming LLM completion interface
 //!
 
+use std::collections::BTreeMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::atomic::AtomicBool;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use futures::stream::{AbortHandle, Abortable};
-use futures::{Stream, StreamExt};
+use futures::{FutureExt, Stream, StreamExt};
+use futures_timer::Delay;
 use serde::{Deserialize, Serialize};
 use tokio::sync::watch;
 
@@ -23,10 +26,10 @@ use crate::agent::Agent;
 use crate::agent::prompt_request::streaming::StreamingPromptRequest;
 use crate::completion::{
 	CompletionError, CompletionModel, CompletionRequestBuilder, CompletionResponse, GetTokenUsage,
-	Message, Usage,
+	Message, ResponseMetadata, Usage,
 };
 use crate::message::{AssistantContent, Reasoning, Text, ToolCall, ToolFunction, ToolResult};
-use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
+use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
 
 /// Control for pausing and resuming a streaming response
 pub struct PauseControl {
@@ -103,6 +106,84 @@ where
 	/// The final response object, must be yielded if you want the
 	/// `response` field to be populated on the `StreamingCompletionResponse`
 	FinalResponse(R),
+
+	/// Metadata of the HTTP response, populates the `response_metadata` field on the
+	/// `StreamingCompletionResponse` without being forwarded to the outer stream
+	ResponseMetadata(ResponseMetadata),
+
+	/// The cumulative token usage so far, for providers reporting it before the final response.
+	/// Returned by [StreamingCompletionResponse::current_usage] without being forwarded to the
+	/// outer stream
+	Usage(Usage),
+
+	/// A chunk of another choice than the first one, for requests generating several (e.g. with
+	/// OpenAI's `n`). Aggregated in [StreamingCompletionResponse::choices] without being
+	/// forwarded to the outer stream
+	Choice {
+		choice_index: usize,
+		choice: Box<RawStreamingChoice<R>>,
+	},
+}
+
+impl<R> RawStreamingChoice<R>
+where
+	R: Clone,
+{
+	/// Attributes the chunk to the choice `choice_index`, the first choice being left unwrapped.
+	pub fn with_choice_index(self, choice_index: usize) -> Self {
+		match choice_index {
+			0 => self,
+			choice_index => Self::Choice {
+				choice_index,
+				choice: Box::new(self),
+			},
+		}
+	}
+
+	/// Converts the final response with `f`, leaving every other choice unchanged.
+	pub fn map_final_response<S, E>(
+		self,
+		f: impl FnOnce(R) -> Result<S, E>,
+	) -> Result<RawStreamingChoice<S>, E>
+	where
+		S: Clone,
+	{
+		Ok(match self {
+			Self::Message(text) => RawStreamingChoice::Message(text),
+			Self::ToolCall(tool_call) => RawStreamingChoice::ToolCall(tool_call),
+			Self::ToolCallDelta {
+				id,
+				internal_call_id,
+				content,
+			} => RawStreamingChoice::ToolCallDelta {
+				id,
+				internal_call_id,
+				content,
+			},
+			Self::Reasoning {
+				id,
+				reasoning,
+				signature,
+			} => RawStreamingChoice::Reasoning {
+				id,
+				reasoning,
+				signature,
+			},
+			Self::ReasoningDelta { id, reasoning } => {
+				RawStreamingChoice::ReasoningDelta { id, reasoning }
+			}
+			Self::FinalResponse(response) => RawStreamingChoice::FinalResponse(f(response)?),
+			Self::ResponseMetadata(metadata) => RawStreamingChoice::ResponseMetadata(metadata),
+			Self::Usage(usage) => RawStreamingChoice::Usage(usage),
+			Self::Choice {
+				choice_index,
+				choice,
+			} => RawStreamingChoice::Choice {
+				choice_index,
+				choice: Box::new(choice.map_final_response(f)?),
+			},
+		})
+	}
 }
 
 /// Describes a streaming tool call response (in its entirety)
@@ -204,10 +285,19 @@ where
 	/// The final aggregated message from the stream
 	/// contains all text and tool calls generated
 	pub choice: OneOrMany<AssistantContent>,
+	/// The final aggregated message of every choice, ordered by index and starting with
+	/// [Self::choice]. Only requests generating several choices (e.g. with OpenAI's `n`) have
+	/// more than one
+	pub choices: Vec<OneOrMany<AssistantContent>>,
+	/// Text and tool calls of the choices after the first one, by index
+	other_choices: BTreeMap<usize, (String, Vec<ToolCall>)>,
 	/// The final response from the stream, may be `None`
 	/// if the provider didn't yield it during the stream
 	pub response: Option<R>,
+	/// Metadata of the HTTP response, `None` if the provider doesn't report it
+	pub response_metadata: Option<ResponseMetadata>,
 	pub final_response_yielded: AtomicBool,
+	usage: Option<Usage>,
 }
 
 impl<R> StreamingCompletionResponse<R>
@@ -226,15 +316,34 @@ where
 			text: "".to_string(),
 			tool_calls: vec![],
 			choice: OneOrMany::one(AssistantContent::text("")),
+			choices: vec![],
+			other_choices: BTreeMap::new(),
 			response: None,
+			response_metadata: None,
 			final_response_yielded: AtomicBool::new(false),
+			usage: None,
 		}
 	}
 
+	/// The latest token usage reported by the provider, before or with the final response.
+	/// `None` if the provider hasn't reported any usage yet.
+	pub fn current_usage(&self) -> Option<Usage> {
+		self.usage
+	}
+
 	pub fn cancel(&self) {
 		self.abort_handle.abort();
 	}
 
+	/// The stream of raw choices, for wrapping in another response. Must be called before the
+	/// response is polled, as the choices it consumed aren't replayed.
+	pub(crate) fn into_raw_stream(self) -> StreamingResult<R>
+	where
+		R: WasmCompatSend + 'static,
+	{
+		Box::pin(self.inner)
+	}
+
 	pub fn pause(&self) {
 		self.pause_control.pause();
 	}
@@ -246,6 +355,74 @@ where
 	pub fn is_paused(&self) -> bool {
 		self.pause_control.is_paused()
 	}
+
+	/// Drains the stream and returns the concatenated text deltas.
+	/// Reasoning, tool calls and the final response are consumed but ignored.
+	pub async fn collect_text(&mut self) -> Result<String, CompletionError> {
+		let mut text = String::new();
+		while let Some(chunk) = self.next().await {
+			if let StreamedAssistantContent::Text(Text { text: delta }) = chunk? {
+				text.push_str(&delta);
+			}
+		}
+		Ok(text)
+	}
+
+	/// Merges consecutive text deltas, to reduce the number of items yielded by providers
+	/// streaming tiny chunks. See [Coalesced].
+	pub fn coalesce(self, by: impl Into<CoalesceBy>) -> Coalesced<Self> {
+		Coalesced::new(self, by)
+	}
+
+	/// Converts the response into a stream that only yields text deltas.
+	pub fn into_text_stream(self) -> impl Stream<Item = Result<String, CompletionError>> {
+		self.filter_map(|chunk| async move {
+			match chunk {
+				Ok(StreamedAssistantContent::Text(Text { text })) => Some(Ok(text)),
+				Ok(_) => None,
+				Err(err) => Some(Err(err)),
+			}
+		})
+	}
+
+	/// Drains the stream and returns the accumulated text, reasoning and tool calls
+	/// alongside the provider's final response (if one was yielded).
+	pub async fn final_response(&mut self) -> Result<StreamedFinalResponse<R>, CompletionError> {
+		while let Some(chunk) = self.next().await {
+			chunk?;
+		}
+		Ok(StreamedFinalResponse {
+			text: self.text.clone(),
+			reasoning: self.reasoning.clone(),
+			tool_calls: self.tool_calls.clone(),
+			response: self.response.clone(),
+			response_metadata: self.response_metadata.clone(),
+		})
+	}
+}
+
+/// The aggregated result of draining a [StreamingCompletionResponse].
+#[derive(Debug, Clone)]
+pub struct StreamedFinalResponse<R> {
+	/// All text deltas concatenated together
+	pub text: String,
+	/// All reasoning deltas concatenated together
+	pub reasoning: String,
+	/// Every tool call yielded by the stream, in order
+	pub tool_calls: Vec<ToolCall>,
+	/// The provider's final response, `None` if the provider didn't yield one
+	pub response: Option<R>,
+	/// Metadata of the HTTP response, `None` if the provider doesn't report it
+	pub response_metadata: Option<ResponseMetadata>,
+}
+
+impl<R> GetTokenUsage for StreamedFinalResponse<R>
+where
+	R: GetTokenUsage,
+{
+	fn token_usage(&self) -> Option<crate::completion::Usage> {
+		self.response.token_usage()
+	}
 }
 
 impl<R> From<StreamingCompletionResponse<R>> for CompletionResponse<Option<R>>
@@ -255,8 +432,9 @@ where
 	fn from(value: StreamingCompletionResponse<R>) -> CompletionResponse<Option<R>> {
 		CompletionResponse {
 			choice: value.choice,
-			usage: Usage::new(), // Usage is not tracked in streaming responses
+			usage: value.usage.unwrap_or_default(),
 			raw_response: value.response,
+			response_metadata: value.response_metadata,
 		}
 	}
 }
@@ -280,19 +458,15 @@ where
 			Poll::Ready(None) => {
 				// This is run at the end of the inner stream to collect all tokens into
 				// a single unified `Message`.
-				let mut choice = vec![];
-
-				stream.tool_calls.iter().for_each(|tc| {
-					choice.push(AssistantContent::ToolCall(tc.clone()));
-				});
-
-				// This is required to ensure there's always at least one item in the content
-				if choice.is_empty() || !stream.text.is_empty() {
-					choice.insert(0, AssistantContent::text(stream.text.clone()));
-				}
-
-				stream.choice = OneOrMany::many(choice)
-					.expect("There should be at least one assistant message");
+				stream.choice = aggregate_choice(&stream.text, &stream.tool_calls);
+				stream.choices = std::iter::once(stream.choice.clone())
+					.chain(
+						stream
+							.other_choices
+							.values()
+							.map(|(text, tool_calls)| aggregate_choice(text, tool_calls)),
+					)
+					.collect();
 
 				Poll::Ready(None)
 			}
@@ -356,6 +530,9 @@ where
 						stream.poll_next_unpin(cx)
 					} else {
 						// Set the final response field and return the next item in the stream
+						if let Some(usage) = response.token_usage() {
+							stream.usage = Some(usage);
+						}
 						stream.response = Some(response.clone());
 						stream
 							.final_response_yielded
@@ -364,11 +541,191 @@ where
 						Poll::Ready(Some(Ok(final_response)))
 					}
 				}
+				RawStreamingChoice::ResponseMetadata(response_metadata) => {
+					stream.response_metadata = Some(response_metadata);
+					stream.poll_next_unpin(cx)
+				}
+				RawStreamingChoice::Usage(usage) => {
+					stream.usage = Some(usage);
+					stream.poll_next_unpin(cx)
+				}
+				RawStreamingChoice::Choice {
+					choice_index,
+					choice,
+				} => {
+					let (text, tool_calls) = stream.other_choices.entry(choice_index).or_default();
+					match *choice {
+						RawStreamingChoice::Message(delta) => text.push_str(&delta),
+						RawStreamingChoice::ToolCall(tool_call) => {
+							tool_calls.push(tool_call.into())
+						}
+						_ => {}
+					}
+					stream.poll_next_unpin(cx)
+				}
 			},
 		}
 	}
 }
 
+/// The message aggregated from the text and tool calls of a choice.
+fn aggregate_choice(text: &str, tool_calls: &[ToolCall]) -> OneOrMany<AssistantContent> {
+	let mut choice = vec![];
+
+	tool_calls.iter().for_each(|tc| {
+		choice.push(AssistantContent::ToolCall(tc.clone()));
+	});
+
+	// This is required to ensure there's always at least one item in the content
+	if choice.is_empty() || !text.is_empty() {
+		choice.insert(0, AssistantContent::text(text));
+	}
+
+	OneOrMany::many(choice).expect("There should be at least one assistant message")
+}
+
+/// How [Coalesced] decides when to yield the text deltas it merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoalesceBy {
+	/// Yield the merged text once the window, starting at the first merged delta, has elapsed
+	Window(Duration),
+	/// Yield the merged text once it has at least this many characters
+	MinChars(usize),
+}
+
+impl From<Duration> for CoalesceBy {
+	fn from(window: Duration) -> Self {
+		Self::Window(window)
+	}
+}
+
+/// Stream combinator merging consecutive text deltas of a streamed completion.
+///
+/// Tool calls, reasoning, final responses and errors are passed through as soon as they are
+/// received, after yielding the text merged so far, so items are never reordered. The text
+/// still pending when the stream ends is yielded before it. Deltas that are already available
+/// when polled are merged together, so a slow consumer receives fewer, bigger deltas.
+pub struct Coalesced<S>
+where
+	S: Stream,
+{
+	inner: S,
+	by: CoalesceBy,
+	make_delay: fn(Duration) -> WasmBoxedFuture<'static, ()>,
+	delay: Option<WasmBoxedFuture<'static, ()>>,
+	pending: String,
+	stashed: Option<S::Item>,
+	done: bool,
+}
+
+impl<S, R> Coalesced<S>
+where
+	S: Stream<Item = Result<StreamedAssistantContent<R>, CompletionError>> + Unpin,
+{
+	pub fn new(inner: S, by: impl Into<CoalesceBy>) -> Self {
+		Self {
+			inner,
+			by: by.into(),
+			make_delay: |window| Box::pin(Delay::new(window)),
+			delay: None,
+			pending: String::new(),
+			stashed: None,
+			done: false,
+		}
+	}
+
+	/// Replaces the timer ending the coalescing windows
+	#[cfg(test)]
+	pub(crate) fn with_timer(
+		mut self,
+		make_delay: fn(Duration) -> WasmBoxedFuture<'static, ()>,
+	) -> Self {
+		self.make_delay = make_delay;
+		self
+	}
+
+	/// The wrapped stream, e.g. to read the aggregated response once drained
+	pub fn get_ref(&self) -> &S {
+		&self.inner
+	}
+
+	pub fn into_inner(self) -> S {
+		self.inner
+	}
+
+	/// Whether the pending text should be yielded now
+	fn should_flush(&mut self, cx: &mut Context<'_>) -> bool {
+		match self.by {
+			CoalesceBy::Window(_) => self
+				.delay
+				.as_mut()
+				.is_some_and(|delay| delay.poll_unpin(cx).is_ready()),
+			CoalesceBy::MinChars(min_chars) => self.pending.chars().count() >= min_chars,
+		}
+	}
+
+	fn flush(&mut self) -> S::Item {
+		self.delay = None;
+		Ok(StreamedAssistantContent::Text(Text {
+			text: std::mem::take(&mut self.pending),
+		}))
+	}
+}
+
+// The stashed item is never pinned
+impl<S> Unpin for Coalesced<S> where S: Stream + Unpin {}
+
+impl<S, R> Stream for Coalesced<S>
+where
+	S: Stream<Item = Result<StreamedAssistantContent<R>, CompletionError>> + Unpin,
+{
+	type Item = S::Item;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let stream = self.get_mut();
+
+		if let Some(item) = stream.stashed.take() {
+			return Poll::Ready(Some(item));
+		}
+
+		loop {
+			if stream.done {
+				return Poll::Ready((!stream.pending.is_empty()).then(|| stream.flush()));
+			}
+
+			match stream.inner.poll_next_unpin(cx) {
+				Poll::Ready(Some(Ok(StreamedAssistantContent::Text(Text { text })))) => {
+					if stream.pending.is_empty()
+						&& let CoalesceBy::Window(window) = stream.by
+					{
+						stream.delay = Some((stream.make_delay)(window));
+					}
+					stream.pending.push_str(&text);
+
+					if stream.should_flush(cx) {
+						return Poll::Ready(Some(stream.flush()));
+					}
+				}
+				Poll::Ready(Some(item)) => {
+					if stream.pending.is_empty() {
+						return Poll::Ready(Some(item));
+					}
+					// Yield the text received before the item first
+					stream.stashed = Some(item);
+					return Poll::Ready(Some(stream.flush()));
+				}
+				Poll::Ready(None) => stream.done = true,
+				Poll::Pending => {
+					if !stream.pending.is_empty() && stream.should_flush(cx) {
+						return Poll::Ready(Some(stream.flush()));
+					}
+					return Poll::Pending;
+				}
+			}
+		}
+	}
+}
+
 /// Trait for high-level streaming prompt interface
 pub trait StreamingPrompt<M, R>
 where
@@ -520,6 +877,90 @@ mod tests {
 		StreamingCompletionResponse::stream(pinned_stream)
 	}
 
+	fn create_mixed_stream() -> StreamingCompletionResponse<MockResponse> {
+		let stream = stream! {
+			yield Ok(RawStreamingChoice::ReasoningDelta {
+				id: None,
+				reasoning: "thinking ".to_string(),
+			});
+			yield Ok(RawStreamingChoice::ReasoningDelta {
+				id: None,
+				reasoning: "hard".to_string(),
+			});
+			yield Ok(RawStreamingChoice::Message("Hello, ".to_string()));
+			yield Ok(RawStreamingChoice::ToolCallDelta {
+				id: "call_1".to_string(),
+				internal_call_id: "internal_1".to_string(),
+				content: ToolCallDeltaContent::Name("add".to_string()),
+			});
+			yield Ok(RawStreamingChoice::ToolCall(
+				RawStreamingToolCall::new(
+					"call_1".to_string(),
+					"add".to_string(),
+					serde_json::json!({"x": 1, "y": 2}),
+				)
+				.with_internal_call_id("internal_1".to_string()),
+			));
+			yield Ok(RawStreamingChoice::Message("world!".to_string()));
+			yield Ok(RawStreamingChoice::FinalResponse(MockResponse { token_count: 15 }));
+		};
+
+		let pinned_stream: StreamingResult<MockResponse> = Box::pin(stream);
+		StreamingCompletionResponse::stream(pinned_stream)
+	}
+
+	#[tokio::test]
+	async fn test_collect_text_ignores_non_text_chunks() {
+		let mut stream = create_mixed_stream();
+		let text = stream.collect_text().await.unwrap();
+
+		assert_eq!(text, "Hello, world!");
+		assert!(stream.response.is_some());
+	}
+
+	#[tokio::test]
+	async fn test_into_text_stream_yields_only_text() {
+		let deltas = create_mixed_stream()
+			.into_text_stream()
+			.collect::<Vec<_>>()
+			.await
+			.into_iter()
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+
+		assert_eq!(deltas, vec!["Hello, ".to_string(), "world!".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn test_final_response_aggregates_stream() {
+		let mut stream = create_mixed_stream();
+		let final_response = stream.final_response().await.unwrap();
+
+		assert_eq!(final_response.text, "Hello, world!");
+		assert_eq!(final_response.reasoning, "thinking hard");
+		assert_eq!(final_response.tool_calls.len(), 1);
+		assert_eq!(final_response.tool_calls[0].function.name, "add");
+		assert_eq!(
+			final_response.tool_calls[0].function.arguments,
+			serde_json::json!({"x": 1, "y": 2})
+		);
+		assert_eq!(final_response.response.as_ref().unwrap().token_count, 15);
+		assert_eq!(final_response.token_usage().unwrap().total_tokens, 15);
+	}
+
+	#[tokio::test]
+	async fn test_collect_text_propagates_errors() {
+		let stream = stream! {
+			yield Ok(RawStreamingChoice::Message("partial".to_string()));
+			yield Err(CompletionError::ResponseError("boom".to_string()));
+		};
+		let pinned_stream: StreamingResult<MockResponse> = Box::pin(stream);
+		let mut stream = StreamingCompletionResponse::stream(pinned_stream);
+
+		let err = stream.collect_text().await.unwrap_err();
+		assert!(matches!(err, CompletionError::ResponseError(msg) if msg == "boom"));
+	}
+
 	#[tokio::test]
 	async fn test_stream_cancellation() {
 		let mut stream = create_mock_stream();
@@ -583,6 +1024,139 @@ mod tests {
 		);
 	}
 
+	type ScriptedItem = Result<StreamedAssistantContent<MockResponse>, CompletionError>;
+
+	/// A stream yielding the items sent on the returned channel.
+	fn scripted_stream() -> (
+		futures::channel::mpsc::UnboundedSender<ScriptedItem>,
+		futures::channel::mpsc::UnboundedReceiver<ScriptedItem>,
+	) {
+		futures::channel::mpsc::unbounded()
+	}
+
+	fn text(text: &str) -> ScriptedItem {
+		Ok(StreamedAssistantContent::text(text))
+	}
+
+	fn tool_call() -> ScriptedItem {
+		Ok(StreamedAssistantContent::ToolCall {
+			tool_call: RawStreamingToolCall::new(
+				"call_1".to_string(),
+				"add".to_string(),
+				serde_json::json!({"x": 1}),
+			)
+			.into(),
+			internal_call_id: "internal_1".to_string(),
+		})
+	}
+
+	/// Returns the next item if one is ready, without waiting.
+	fn next_now<S: Stream + Unpin>(stream: &mut S) -> Option<Option<S::Item>> {
+		stream.next().now_or_never()
+	}
+
+	fn unwrap_text(item: Option<Option<ScriptedItem>>) -> String {
+		match item {
+			Some(Some(Ok(StreamedAssistantContent::Text(Text { text })))) => text,
+			other => panic!("expected a text delta, got {other:?}"),
+		}
+	}
+
+	/// Ends the coalescing windows when notified by the test.
+	static WINDOW_END: tokio::sync::Notify = tokio::sync::Notify::const_new();
+
+	fn mock_timer(_window: Duration) -> WasmBoxedFuture<'static, ()> {
+		Box::pin(WINDOW_END.notified())
+	}
+
+	#[tokio::test]
+	async fn test_coalesce_window_merges_deltas_until_timer_fires() {
+		let (tx, rx) = scripted_stream();
+		let mut stream = Coalesced::new(rx, Duration::from_millis(50)).with_timer(mock_timer);
+
+		tx.unbounded_send(text("Hel")).unwrap();
+		tx.unbounded_send(text("lo")).unwrap();
+		assert!(next_now(&mut stream).is_none());
+
+		tx.unbounded_send(text(", ")).unwrap();
+		WINDOW_END.notify_one();
+		assert_eq!(unwrap_text(next_now(&mut stream)), "Hello, ");
+
+		// The next window starts with the next delta
+		assert!(next_now(&mut stream).is_none());
+		tx.unbounded_send(text("world")).unwrap();
+		assert!(next_now(&mut stream).is_none());
+
+		// A tool call flushes the pending text first
+		tx.unbounded_send(tool_call()).unwrap();
+		tx.unbounded_send(text("!")).unwrap();
+		assert_eq!(unwrap_text(next_now(&mut stream)), "world");
+		assert!(matches!(
+			next_now(&mut stream),
+			Some(Some(Ok(StreamedAssistantContent::ToolCall { .. })))
+		));
+
+		// The end of the stream flushes the pending text
+		tx.unbounded_send(Ok(StreamedAssistantContent::final_response(MockResponse {
+			token_count: 15,
+		})))
+		.unwrap();
+		drop(tx);
+		assert_eq!(unwrap_text(next_now(&mut stream)), "!");
+		assert!(matches!(
+			next_now(&mut stream),
+			Some(Some(Ok(StreamedAssistantContent::Final(_))))
+		));
+		assert!(matches!(next_now(&mut stream), Some(None)));
+	}
+
+	#[tokio::test]
+	async fn test_coalesce_min_chars() {
+		let (tx, rx) = scripted_stream();
+		let mut stream = Coalesced::new(rx, CoalesceBy::MinChars(5));
+
+		for delta in ["ab", "cd", "ef", "g"] {
+			tx.unbounded_send(text(delta)).unwrap();
+		}
+		tx.unbounded_send(Ok(StreamedAssistantContent::ReasoningDelta {
+			id: None,
+			reasoning: "hmm".to_string(),
+		}))
+		.unwrap();
+		tx.unbounded_send(Err(CompletionError::ResponseError("boom".to_string())))
+			.unwrap();
+		tx.unbounded_send(text("h")).unwrap();
+		drop(tx);
+
+		assert_eq!(unwrap_text(next_now(&mut stream)), "abcdef");
+		assert_eq!(unwrap_text(next_now(&mut stream)), "g");
+		assert!(matches!(
+			next_now(&mut stream),
+			Some(Some(Ok(StreamedAssistantContent::ReasoningDelta { .. })))
+		));
+		assert!(matches!(
+			next_now(&mut stream),
+			Some(Some(Err(CompletionError::ResponseError(_))))
+		));
+		assert_eq!(unwrap_text(next_now(&mut stream)), "h");
+		assert!(matches!(next_now(&mut stream), Some(None)));
+	}
+
+	#[tokio::test]
+	async fn test_coalesce_streaming_response() {
+		let mut stream = create_mixed_stream().coalesce(CoalesceBy::MinChars(100));
+
+		let mut texts = vec![];
+		while let Some(item) = stream.next().await {
+			if let StreamedAssistantContent::Text(Text { text }) = item.unwrap() {
+				texts.push(text);
+			}
+		}
+
+		assert_eq!(texts, ["Hello, ", "world!"]);
+		assert!(stream.get_ref().response.is_some());
+	}
+
 	#[tokio::test]
 	async fn test_stream_pause_resume() {
 		let stream = create_mock_stream();
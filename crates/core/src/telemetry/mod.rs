@@ -3,9 +3,112 @@
 //! agents with the correct tracing style so you can emit the right traces for platforms like Langfuse,
 //! and more.
 
+pub mod pricing;
+
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+pub use pricing::{
+	Cost, Pricing, PricingTable, clear_pricing_table, estimate_cost, register_pricing,
+	set_pricing_table,
+};
 use serde::Serialize;
 
-use crate::completion::GetTokenUsage;
+use crate::completion::{GetTokenUsage, Redactor};
+
+/// Environment variable that enables recording message bodies on completion spans.
+/// Set to `true` or `1` to enable. Overridden by [`set_record_messages`].
+pub const RECORD_MESSAGES_ENV: &str = "CLANKERS_TELEMETRY_RECORD_MESSAGES";
+
+const RECORD_MESSAGES_UNSET: u8 = 0;
+const RECORD_MESSAGES_OFF: u8 = 1;
+const RECORD_MESSAGES_ON: u8 = 2;
+
+static RECORD_MESSAGES: AtomicU8 = AtomicU8::new(RECORD_MESSAGES_UNSET);
+
+static MESSAGE_REDACTOR: RwLock<Option<Redactor>> = RwLock::new(None);
+
+/// Enables or disables recording `gen_ai.input.messages` and `gen_ai.output.messages` on
+/// provider spans. Message bodies can be large and contain sensitive data, so this is off
+/// unless enabled here or through [`RECORD_MESSAGES_ENV`].
+pub fn set_record_messages(enabled: bool) {
+	let value = if enabled {
+		RECORD_MESSAGES_ON
+	} else {
+		RECORD_MESSAGES_OFF
+	};
+	RECORD_MESSAGES.store(value, Ordering::Relaxed);
+}
+
+/// Whether message bodies are recorded on provider spans, see [`set_record_messages`].
+pub fn record_messages_enabled() -> bool {
+	match RECORD_MESSAGES.load(Ordering::Relaxed) {
+		RECORD_MESSAGES_ON => true,
+		RECORD_MESSAGES_OFF => false,
+		_ => {
+			let enabled = std::env::var(RECORD_MESSAGES_ENV)
+				.is_ok_and(|value| matches!(value.to_lowercase().as_str(), "1" | "true"));
+			let value = if enabled {
+				RECORD_MESSAGES_ON
+			} else {
+				RECORD_MESSAGES_OFF
+			};
+			// Don't override a `set_record_messages` racing with the first read
+			match RECORD_MESSAGES.compare_exchange(
+				RECORD_MESSAGES_UNSET,
+				value,
+				Ordering::Relaxed,
+				Ordering::Relaxed,
+			) {
+				Ok(_) => enabled,
+				Err(current) => current == RECORD_MESSAGES_ON,
+			}
+		}
+	}
+}
+
+/// Sets the redactor applied to the messages recorded on provider spans, e.g. to mask
+/// personal data. Defaults to [`Redactor::default`], which replaces base64 payloads and
+/// truncates long texts.
+pub fn set_message_redactor(redactor: Redactor) {
+	*MESSAGE_REDACTOR
+		.write()
+		.unwrap_or_else(|err| err.into_inner()) = Some(redactor);
+}
+
+fn with_redactor<T>(f: impl FnOnce(&Redactor) -> T) -> T {
+	match &*MESSAGE_REDACTOR
+		.read()
+		.unwrap_or_else(|err| err.into_inner())
+	{
+		Some(redactor) => f(redactor),
+		None => f(&Redactor::default()),
+	}
+}
+
+fn messages_to_json<T>(messages: &[T]) -> String
+where
+	T: Serialize,
+{
+	let mut value =
+		serde_json::to_value(messages).expect("Serializing a Rust type to JSON should not break");
+	with_redactor(|redactor| redactor.redact_json(&mut value));
+	value.to_string()
+}
+
+/// Redacts the arguments or output of a tool call with the message redactor, see
+/// [`set_message_redactor`]. JSON payloads are redacted value by value.
+pub(crate) fn redact_tool_payload(payload: &str) -> String {
+	with_redactor(
+		|redactor| match serde_json::from_str::<serde_json::Value>(payload) {
+			Ok(mut value) => {
+				redactor.redact_json(&mut value);
+				value.to_string()
+			}
+			Err(_) => redactor.redact_text(payload),
+		},
+	)
+}
 
 pub trait ProviderRequestExt {
 	type InputMessage: Serialize;
@@ -38,6 +141,18 @@ pub trait SpanCombinator {
 	where
 		U: GetTokenUsage;
 
+	/// Records the estimated cost of a request as `gen_ai.usage.cost` and
+	/// `gen_ai.usage.cached_cost`, see [estimate_cost].
+	/// Does nothing if the model isn't priced.
+	fn record_cost<U>(
+		&self,
+		pricing: Option<&PricingTable>,
+		provider: &str,
+		model: &str,
+		usage: &U,
+	) where
+		U: GetTokenUsage;
+
 	fn record_response_metadata<R>(&self, response: &R)
 	where
 		R: ProviderResponseExt;
@@ -49,6 +164,18 @@ pub trait SpanCombinator {
 	fn record_model_output<T>(&self, messages: &T)
 	where
 		T: Serialize;
+
+	/// Records the provider request messages as `gen_ai.input.messages`.
+	/// Does nothing unless [`record_messages_enabled`].
+	fn record_input_messages<T>(&self, messages: &[T])
+	where
+		T: Serialize;
+
+	/// Records the provider response messages as `gen_ai.output.messages`.
+	/// Does nothing unless [`record_messages_enabled`].
+	fn record_output_messages<T>(&self, messages: &[T])
+	where
+		T: Serialize;
 }
 
 impl SpanCombinator for tracing::Span {
@@ -66,6 +193,23 @@ impl SpanCombinator for tracing::Span {
 		}
 	}
 
+	fn record_cost<U>(&self, pricing: Option<&PricingTable>, provider: &str, model: &str, usage: &U)
+	where
+		U: GetTokenUsage,
+	{
+		if self.is_disabled() {
+			return;
+		}
+
+		if let Some(cost) = usage
+			.token_usage()
+			.and_then(|usage| estimate_cost(pricing, provider, model, &usage))
+		{
+			self.record("gen_ai.usage.cost", cost.total());
+			self.record("gen_ai.usage.cached_cost", cost.cached_input);
+		}
+	}
+
 	fn record_response_metadata<R>(&self, response: &R)
 	where
 		R: ProviderResponseExt,
@@ -110,4 +254,121 @@ impl SpanCombinator for tracing::Span {
 
 		self.record("gen_ai.output.messages", output_as_json_string);
 	}
+
+	fn record_input_messages<T>(&self, messages: &[T])
+	where
+		T: Serialize,
+	{
+		if self.is_disabled() || !record_messages_enabled() {
+			return;
+		}
+
+		self.record("gen_ai.input.messages", messages_to_json(messages));
+	}
+
+	fn record_output_messages<T>(&self, messages: &[T])
+	where
+		T: Serialize,
+	{
+		if self.is_disabled() || !record_messages_enabled() {
+			return;
+		}
+
+		self.record("gen_ai.output.messages", messages_to_json(messages));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use serde_json::json;
+	use tracing_subscriber::layer::SubscriberExt;
+
+	use super::*;
+	use crate::completion::Usage;
+	use crate::test_utils::{RecordedFields, with_record_messages};
+
+	fn record_on_span(f: impl FnOnce(&tracing::Span)) -> HashMap<String, String> {
+		let fields = RecordedFields::default();
+		let subscriber = tracing_subscriber::registry().with(fields.clone());
+		tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!(
+				"chat",
+				gen_ai.input.messages = tracing::field::Empty,
+				gen_ai.output.messages = tracing::field::Empty,
+				gen_ai.usage.cost = tracing::field::Empty,
+				gen_ai.usage.cached_cost = tracing::field::Empty,
+			);
+			f(&span);
+		});
+		fields.get()
+	}
+
+	#[test]
+	fn test_record_messages_is_gated_and_redacts_blobs() {
+		let blob = "QUJD".repeat(100);
+		let input = [json!({
+			"role": "user",
+			"content": [
+				{ "type": "text", "text": "What is in these images?" },
+				{ "type": "image", "data": blob },
+				{ "type": "image_url", "url": format!("data:image/png;base64,{blob}") }
+			]
+		})];
+		let output = [json!({ "role": "assistant", "content": "Two cats." })];
+
+		let record = |span: &tracing::Span| {
+			span.record_input_messages(&input);
+			span.record_output_messages(&output);
+		};
+
+		let fields = with_record_messages(false, || record_on_span(record));
+		assert!(fields.is_empty());
+
+		let fields = with_record_messages(true, || record_on_span(record));
+
+		assert_eq!(
+			serde_json::from_str::<serde_json::Value>(&fields["gen_ai.input.messages"]).unwrap(),
+			json!([{
+				"role": "user",
+				"content": [
+					{ "type": "text", "text": "What is in these images?" },
+					{ "type": "image", "data": "[300 bytes]" },
+					{ "type": "image_url", "url": "[300 bytes image/png]" }
+				]
+			}])
+		);
+		assert_eq!(
+			serde_json::from_str::<serde_json::Value>(&fields["gen_ai.output.messages"]).unwrap(),
+			json!(output)
+		);
+	}
+
+	#[test]
+	fn test_record_cost() {
+		let table = PricingTable::new().with(
+			"openai",
+			"gpt-4o",
+			Pricing::new(2.5, 10.0).with_cached_input(1.25),
+		);
+		let usage = Usage {
+			input_tokens: 1_000_000,
+			output_tokens: 100_000,
+			total_tokens: 1_100_000,
+			cached_input_tokens: 400_000,
+			cache_creation_input_tokens: 0,
+		};
+
+		let fields = record_on_span(|span| {
+			span.record_cost(Some(&table), "openai", "gpt-4o-2024-08-06", &usage);
+		});
+		assert_eq!(fields["gen_ai.usage.cost"], "3.0");
+		assert_eq!(fields["gen_ai.usage.cached_cost"], "0.5");
+
+		let fields = record_on_span(|span| {
+			span.record_cost(Some(&table), "openai", "unpriced-model", &usage);
+		});
+		assert!(fields.is_empty());
+	}
 }
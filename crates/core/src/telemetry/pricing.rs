@@ -0,0 +1,421 @@
+//! Opt-in cost estimation for completion spans.
+//!
+//! When a [PricingTable] is registered, either on a client with
+//! [ClientBuilder::pricing](crate::client::ClientBuilder::pricing) or globally with
+//! [set_pricing_table], the completion and streaming spans of providers with known prices record
+//! `gen_ai.usage.cost` and `gen_ai.usage.cached_cost` once token usage is known.
+//!
+//! # Example
+//! ```rust,ignore
+//! use clankers::telemetry::{Pricing, PricingTable, set_pricing_table};
+//!
+//! // Start from the built-in prices and override the ones negotiated with your provider
+//! set_pricing_table(
+//!     PricingTable::defaults()
+//!         .with("openai", "gpt-4o", Pricing::new(2.0, 8.0).with_cached_input(1.0)),
+//! );
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::SpanCombinator;
+use crate::completion::{GetTokenUsage, Usage};
+
+static PRICING: RwLock<Option<PricingTable>> = RwLock::new(None);
+
+/// Token rates of a model, in currency units (usually USD) per million tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Pricing {
+	pub input: f64,
+	pub output: f64,
+	/// Rate of cached input tokens. Cached tokens are billed at the input rate if not set.
+	pub cached_input: Option<f64>,
+	/// Rate of input tokens written to the prompt cache, billed at the input rate if not set.
+	pub cache_creation_input: Option<f64>,
+}
+
+impl Pricing {
+	pub fn new(input: f64, output: f64) -> Self {
+		Self {
+			input,
+			output,
+			cached_input: None,
+			cache_creation_input: None,
+		}
+	}
+
+	pub fn with_cached_input(mut self, cached_input: f64) -> Self {
+		self.cached_input = Some(cached_input);
+		self
+	}
+
+	pub fn with_cache_creation_input(mut self, cache_creation_input: f64) -> Self {
+		self.cache_creation_input = Some(cache_creation_input);
+		self
+	}
+}
+
+/// The estimated cost of a request, in the currency of the [Pricing] it was computed with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Cost {
+	/// Cost of the uncached input tokens
+	pub input: f64,
+	/// Cost of the cached input tokens
+	pub cached_input: f64,
+	/// Cost of the input tokens written to the prompt cache
+	#[serde(default)]
+	pub cache_creation_input: f64,
+	pub output: f64,
+}
+
+impl Cost {
+	pub fn total(&self) -> f64 {
+		self.input + self.cached_input + self.cache_creation_input + self.output
+	}
+}
+
+/// Prices of models, per provider.
+/// Providers are named as in the `gen_ai.provider.name` span field, e.g. `openai`, `anthropic`
+/// or `gcp.gemini`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PricingTable {
+	providers: HashMap<String, HashMap<String, Pricing>>,
+}
+
+impl PricingTable {
+	/// An empty pricing table.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A pricing table with the list prices (USD per million tokens) of the major OpenAI,
+	/// Anthropic and Gemini models. Prices change over time, override them as needed.
+	pub fn defaults() -> Self {
+		let openai = [
+			("gpt-5", Pricing::new(1.25, 10.0).with_cached_input(0.125)),
+			(
+				"gpt-5-mini",
+				Pricing::new(0.25, 2.0).with_cached_input(0.025),
+			),
+			(
+				"gpt-5-nano",
+				Pricing::new(0.05, 0.4).with_cached_input(0.005),
+			),
+			("gpt-4.1", Pricing::new(2.0, 8.0).with_cached_input(0.5)),
+			(
+				"gpt-4.1-mini",
+				Pricing::new(0.4, 1.6).with_cached_input(0.1),
+			),
+			(
+				"gpt-4.1-nano",
+				Pricing::new(0.1, 0.4).with_cached_input(0.025),
+			),
+			("gpt-4o", Pricing::new(2.5, 10.0).with_cached_input(1.25)),
+			(
+				"gpt-4o-mini",
+				Pricing::new(0.15, 0.6).with_cached_input(0.075),
+			),
+			("o3", Pricing::new(2.0, 8.0).with_cached_input(0.5)),
+			("o3-mini", Pricing::new(1.1, 4.4).with_cached_input(0.55)),
+			("o4-mini", Pricing::new(1.1, 4.4).with_cached_input(0.275)),
+		];
+
+		let anthropic = [
+			(
+				"claude-opus-4",
+				Pricing::new(15.0, 75.0)
+					.with_cached_input(1.5)
+					.with_cache_creation_input(18.75),
+			),
+			(
+				"claude-sonnet-4",
+				Pricing::new(3.0, 15.0)
+					.with_cached_input(0.3)
+					.with_cache_creation_input(3.75),
+			),
+			(
+				"claude-haiku-4-5",
+				Pricing::new(1.0, 5.0)
+					.with_cached_input(0.1)
+					.with_cache_creation_input(1.25),
+			),
+			(
+				"claude-3-7-sonnet",
+				Pricing::new(3.0, 15.0)
+					.with_cached_input(0.3)
+					.with_cache_creation_input(3.75),
+			),
+			(
+				"claude-3-5-haiku",
+				Pricing::new(0.8, 4.0)
+					.with_cached_input(0.08)
+					.with_cache_creation_input(1.0),
+			),
+		];
+
+		let gemini = [
+			(
+				"gemini-2.5-pro",
+				Pricing::new(1.25, 10.0).with_cached_input(0.31),
+			),
+			(
+				"gemini-2.5-flash",
+				Pricing::new(0.3, 2.5).with_cached_input(0.075),
+			),
+			(
+				"gemini-2.5-flash-lite",
+				Pricing::new(0.1, 0.4).with_cached_input(0.025),
+			),
+			(
+				"gemini-2.0-flash",
+				Pricing::new(0.1, 0.4).with_cached_input(0.025),
+			),
+		];
+
+		[
+			("openai", &openai[..]),
+			("anthropic", &anthropic),
+			("gcp.gemini", &gemini),
+		]
+		.into_iter()
+		.flat_map(|(provider, models)| {
+			models
+				.iter()
+				.map(move |(model, pricing)| (provider, *model, *pricing))
+		})
+		.fold(Self::new(), |table, (provider, model, pricing)| {
+			table.with(provider, model, pricing)
+		})
+	}
+
+	/// Sets the pricing of a model, replacing any previous one.
+	pub fn with(
+		mut self,
+		provider: impl Into<String>,
+		model: impl Into<String>,
+		pricing: Pricing,
+	) -> Self {
+		self.insert(provider, model, pricing);
+		self
+	}
+
+	/// Sets the pricing of a model, replacing any previous one.
+	pub fn insert(
+		&mut self,
+		provider: impl Into<String>,
+		model: impl Into<String>,
+		pricing: Pricing,
+	) {
+		self.providers
+			.entry(provider.into())
+			.or_default()
+			.insert(model.into(), pricing);
+	}
+
+	/// Looks up the pricing of a model. Falls back to the longest registered model name the model
+	/// starts with, so `claude-sonnet-4-20250514` uses the pricing of `claude-sonnet-4`.
+	pub fn get(&self, provider: &str, model: &str) -> Option<&Pricing> {
+		let models = self.providers.get(provider)?;
+
+		models.get(model).or_else(|| {
+			models
+				.iter()
+				.filter(|(name, _)| model.starts_with(name.as_str()))
+				.max_by_key(|(name, _)| name.len())
+				.map(|(_, pricing)| pricing)
+		})
+	}
+}
+
+/// Registers the global pricing table, used for clients without their own.
+pub fn set_pricing_table(table: PricingTable) {
+	*PRICING.write().unwrap_or_else(|err| err.into_inner()) = Some(table);
+}
+
+/// Removes the global pricing table, disabling cost estimation for clients without their own.
+pub fn clear_pricing_table() {
+	*PRICING.write().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// Sets the pricing of a model in the global pricing table, registering an empty table first if
+/// there is none.
+pub fn register_pricing(provider: impl Into<String>, model: impl Into<String>, pricing: Pricing) {
+	PRICING
+		.write()
+		.unwrap_or_else(|err| err.into_inner())
+		.get_or_insert_with(PricingTable::new)
+		.insert(provider, model, pricing);
+}
+
+/// Estimates the cost of a request using `table` if it prices the model, the global pricing
+/// table otherwise. Returns `None` if the model isn't priced.
+pub fn estimate_cost(
+	table: Option<&PricingTable>,
+	provider: &str,
+	model: &str,
+	usage: &Usage,
+) -> Option<Cost> {
+	if let Some(pricing) = table.and_then(|table| table.get(provider, model)) {
+		return Some(usage.estimate_cost(pricing));
+	}
+
+	PRICING
+		.read()
+		.unwrap_or_else(|err| err.into_inner())
+		.as_ref()?
+		.get(provider, model)
+		.map(|pricing| usage.estimate_cost(pricing))
+}
+
+/// Everything needed to record the cost of a request once its usage is known, owned so it can be
+/// moved into a response stream.
+#[derive(Clone, Debug)]
+pub(crate) struct CostRecorder {
+	pricing: Option<Arc<PricingTable>>,
+	provider: &'static str,
+	model: String,
+}
+
+impl CostRecorder {
+	pub(crate) fn new(
+		pricing: Option<Arc<PricingTable>>,
+		provider: &'static str,
+		model: impl Into<String>,
+	) -> Self {
+		Self {
+			pricing,
+			provider,
+			model: model.into(),
+		}
+	}
+
+	pub(crate) fn record<U>(&self, span: &tracing::Span, usage: &U)
+	where
+		U: GetTokenUsage,
+	{
+		span.record_cost(self.pricing.as_deref(), self.provider, &self.model, usage);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn usage(input_tokens: u64, cached_input_tokens: u64, output_tokens: u64) -> Usage {
+		Usage {
+			input_tokens,
+			output_tokens,
+			total_tokens: input_tokens + output_tokens,
+			cached_input_tokens,
+			cache_creation_input_tokens: 0,
+		}
+	}
+
+	fn assert_close(actual: f64, expected: f64) {
+		assert!(
+			(actual - expected).abs() < 1e-12,
+			"expected {expected}, got {actual}"
+		);
+	}
+
+	#[test]
+	fn test_estimate_cost() {
+		let cost = usage(1_000_000, 0, 500_000).estimate_cost(&Pricing::new(2.5, 10.0));
+
+		assert_close(cost.input, 2.5);
+		assert_close(cost.cached_input, 0.0);
+		assert_close(cost.output, 5.0);
+		assert_close(cost.total(), 7.5);
+	}
+
+	#[test]
+	fn test_estimate_cost_with_cached_tokens() {
+		let pricing = Pricing::new(3.0, 15.0).with_cached_input(0.3);
+		let cost = usage(10_000, 8_000, 1_000).estimate_cost(&pricing);
+
+		// 2000 uncached tokens at $3/M, 8000 cached at $0.30/M and 1000 output at $15/M
+		assert_close(cost.input, 0.006);
+		assert_close(cost.cached_input, 0.0024);
+		assert_close(cost.output, 0.015);
+		assert_close(cost.total(), 0.0234);
+
+		// Without a cached rate, cached tokens are billed at the input rate
+		let cost = usage(10_000, 8_000, 1_000).estimate_cost(&Pricing::new(3.0, 15.0));
+		assert_close(cost.input + cost.cached_input, 0.03);
+
+		// More cached tokens than input tokens are capped
+		let cost = usage(100, 1_000, 0).estimate_cost(&pricing);
+		assert_close(cost.input, 0.0);
+		assert_close(cost.cached_input, 0.00003);
+	}
+
+	#[test]
+	fn test_estimate_cost_with_cache_creation_tokens() {
+		let pricing = Pricing::new(3.0, 15.0)
+			.with_cached_input(0.3)
+			.with_cache_creation_input(3.75);
+		let written = Usage {
+			cache_creation_input_tokens: 6_000,
+			..usage(10_000, 2_000, 1_000)
+		};
+
+		// 2000 uncached tokens at $3/M, 2000 cached at $0.30/M, 6000 written to the cache at
+		// $3.75/M and 1000 output at $15/M
+		let cost = written.estimate_cost(&pricing);
+		assert_close(cost.input, 0.006);
+		assert_close(cost.cached_input, 0.0006);
+		assert_close(cost.cache_creation_input, 0.0225);
+		assert_close(cost.total(), 0.0441);
+
+		// Without a cache creation rate, written tokens are billed at the input rate
+		let cost = written.estimate_cost(&Pricing::new(3.0, 15.0).with_cached_input(0.3));
+		assert_close(cost.input + cost.cache_creation_input, 0.024);
+
+		// Cache reads and writes together are capped to the input tokens
+		let capped = Usage {
+			cache_creation_input_tokens: 1_000,
+			..usage(500, 400, 0)
+		};
+		let cost = capped.estimate_cost(&pricing);
+		assert_close(cost.input, 0.0);
+		assert_close(cost.cache_creation_input, 100.0 * 3.75 / 1_000_000.0);
+	}
+
+	#[test]
+	fn test_pricing_lookup() {
+		let table = PricingTable::defaults().with("openai", "gpt-4o", Pricing::new(1.0, 2.0));
+
+		assert_eq!(table.get("openai", "gpt-4o"), Some(&Pricing::new(1.0, 2.0)));
+		assert_eq!(
+			table.get("openai", "gpt-4o-mini-2024-07-18"),
+			Some(&Pricing::new(0.15, 0.6).with_cached_input(0.075))
+		);
+		assert_eq!(
+			table
+				.get("anthropic", "claude-sonnet-4-20250514")
+				.unwrap()
+				.input,
+			3.0
+		);
+		assert_eq!(table.get("anthropic", "gpt-4o"), None);
+		assert_eq!(table.get("mistral", "mistral-large"), None);
+	}
+
+	#[test]
+	fn test_client_table_takes_precedence() {
+		let table = PricingTable::new().with("openai", "my-model", Pricing::new(1.0, 1.0));
+		let usage = usage(1_000_000, 0, 0);
+
+		assert_eq!(
+			estimate_cost(Some(&table), "openai", "my-model", &usage).map(|cost| cost.total()),
+			Some(1.0)
+		);
+		assert_eq!(
+			estimate_cost(Some(&table), "openai", "other-model", &usage),
+			None
+		);
+	}
+}
@@ -0,0 +1,520 @@
+//! Test-only helpers shared across modules.
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsString;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::StreamExt;
+use tracing::field::{Field, Visit};
+use tracing::span::{Id, Record};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+
+use crate::OneOrMany;
+use crate::completion::{
+	self, AssistantContent, CompletionError, CompletionRequest, CompletionResponse, Usage,
+};
+use crate::http_client::{self, HttpClientExt, LazyBody, MultipartForm, StreamingResponse};
+use crate::streaming::{
+	RawStreamingChoice, RawStreamingToolCall, StreamingCompletionResponse, StreamingResult,
+};
+use crate::telemetry;
+use crate::transcription::{self, TranscriptionError, TranscriptionRequest, TranscriptionResponse};
+use crate::wasm_compat::WasmCompatSend;
+
+/// A completion model that replays scripted responses and records every request it receives.
+/// Once the script is exhausted it answers with the text `"done"`.
+#[derive(Clone, Default)]
+pub(crate) struct MockCompletionModel {
+	responses: Arc<Mutex<VecDeque<OneOrMany<AssistantContent>>>>,
+	requests: Arc<Mutex<Vec<CompletionRequest>>>,
+	usage: Usage,
+}
+
+impl MockCompletionModel {
+	/// Creates a model that answers with `responses`, in order.
+	pub(crate) fn with_responses(
+		responses: impl IntoIterator<Item = OneOrMany<AssistantContent>>,
+	) -> Self {
+		Self {
+			responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+			..Self::default()
+		}
+	}
+
+	/// Reports `usage` for every completion.
+	pub(crate) fn usage(mut self, usage: Usage) -> Self {
+		self.usage = usage;
+		self
+	}
+
+	/// Returns a copy of every request received so far.
+	pub(crate) fn requests(&self) -> Vec<CompletionRequest> {
+		self.requests.lock().unwrap().clone()
+	}
+
+	fn next_response(&self, request: CompletionRequest) -> OneOrMany<AssistantContent> {
+		self.requests.lock().unwrap().push(request);
+		self.responses
+			.lock()
+			.unwrap()
+			.pop_front()
+			.unwrap_or_else(|| OneOrMany::one(AssistantContent::text("done")))
+	}
+}
+
+impl completion::CompletionModel for MockCompletionModel {
+	type Response = ();
+	type StreamingResponse = ();
+	type Client = ();
+
+	fn make(_: &Self::Client, _: impl Into<String>) -> Self {
+		Self::default()
+	}
+
+	async fn completion(
+		&self,
+		request: CompletionRequest,
+	) -> Result<CompletionResponse<()>, CompletionError> {
+		Ok(CompletionResponse {
+			choice: self.next_response(request),
+			usage: self.usage,
+			raw_response: (),
+			response_metadata: None,
+		})
+	}
+
+	async fn stream(
+		&self,
+		request: CompletionRequest,
+	) -> Result<StreamingCompletionResponse<()>, CompletionError> {
+		let choices = self
+			.next_response(request)
+			.into_iter()
+			.filter_map(|content| match content {
+				AssistantContent::Text(text) => Some(RawStreamingChoice::Message(text.text)),
+				AssistantContent::ToolCall(tool_call) => {
+					Some(RawStreamingChoice::ToolCall(RawStreamingToolCall::new(
+						tool_call.id,
+						tool_call.function.name,
+						tool_call.function.arguments,
+					)))
+				}
+				AssistantContent::Reasoning(reasoning) => Some(RawStreamingChoice::Reasoning {
+					id: reasoning.id,
+					reasoning: reasoning.reasoning.join(""),
+					signature: reasoning.signature,
+				}),
+				AssistantContent::Image(_) | AssistantContent::Document(_) => None,
+			})
+			.chain(std::iter::once(RawStreamingChoice::FinalResponse(())))
+			.map(Ok)
+			.collect::<Vec<_>>();
+
+		let stream: StreamingResult<()> = Box::pin(futures::stream::iter(choices));
+		Ok(StreamingCompletionResponse::stream(stream))
+	}
+}
+
+/// A transcription model that replays scripted transcripts and records every request it receives.
+/// An `Err` entry is returned as a provider error. Once the script is exhausted it answers with
+/// the text `"done"`. Every transcript starts at the beginning of the audio it was sent.
+#[derive(Clone, Default)]
+pub(crate) struct MockTranscriptionModel {
+	responses: Arc<Mutex<VecDeque<Result<String, String>>>>,
+	requests: Arc<Mutex<Vec<TranscriptionRequest>>>,
+}
+
+impl MockTranscriptionModel {
+	/// Creates a model that answers with `responses`, in order.
+	pub(crate) fn with_responses(
+		responses: impl IntoIterator<Item = Result<String, String>>,
+	) -> Self {
+		Self {
+			responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+			..Self::default()
+		}
+	}
+
+	/// Returns a copy of every request received so far.
+	pub(crate) fn requests(&self) -> Vec<TranscriptionRequest> {
+		self.requests.lock().unwrap().clone()
+	}
+}
+
+/// The raw response of [MockTranscriptionModel]
+#[derive(Debug)]
+pub(crate) struct MockTranscript {
+	/// Start of the transcript, the only timestamp
+	pub(crate) start: std::time::Duration,
+}
+
+impl transcription::OffsetTimestamps for MockTranscript {
+	fn offset_timestamps(&mut self, offset: std::time::Duration) {
+		self.start += offset;
+	}
+}
+
+impl transcription::TranscriptionModel for MockTranscriptionModel {
+	type Response = MockTranscript;
+	type Client = ();
+
+	fn make(_: &Self::Client, _: impl Into<String>) -> Self {
+		Self::default()
+	}
+
+	async fn transcription(
+		&self,
+		request: TranscriptionRequest,
+	) -> Result<TranscriptionResponse<MockTranscript>, TranscriptionError> {
+		self.requests.lock().unwrap().push(request);
+		let text = self
+			.responses
+			.lock()
+			.unwrap()
+			.pop_front()
+			.unwrap_or_else(|| Ok("done".to_string()))
+			.map_err(TranscriptionError::ProviderError)?;
+
+		Ok(TranscriptionResponse {
+			text,
+			response: MockTranscript {
+				start: std::time::Duration::ZERO,
+			},
+		})
+	}
+}
+
+/// An HTTP client whose streaming requests answer with a fixed server-sent events body, sent in
+/// one chunk unless created with [MockSseClient::chunked] or [MockSseClient::paused].
+/// Non-streaming and multipart requests fail with `501 Not Implemented`, unless a JSON body is
+/// set with [MockSseClient::with_json_response], [MockSseClient::with_json_responses] or
+/// [MockSseClient::with_status_response].
+/// The URI, headers and body of every request it receives are recorded.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MockSseClient {
+	chunks: Vec<Bytes>,
+	/// Delay before each chunk, none when missing
+	chunk_delays: Vec<std::time::Duration>,
+	json_bytes: Option<Bytes>,
+	/// Responses to the next non-streaming requests, before `json_bytes`
+	queued_json: Arc<Mutex<VecDeque<Bytes>>>,
+	status: http::StatusCode,
+	response_headers: http::HeaderMap,
+	request_uris: Arc<Mutex<Vec<String>>>,
+	request_headers: Arc<Mutex<Vec<http::HeaderMap>>>,
+	request_bodies: Arc<Mutex<Vec<Bytes>>>,
+}
+
+impl MockSseClient {
+	pub(crate) fn new(sse: impl Into<Bytes>) -> Self {
+		Self {
+			chunks: vec![sse.into()],
+			..Self::default()
+		}
+	}
+
+	/// Answers streaming requests with a body sent in `chunks`.
+	pub(crate) fn chunked(chunks: impl IntoIterator<Item = impl Into<Bytes>>) -> Self {
+		Self {
+			chunks: chunks.into_iter().map(Into::into).collect(),
+			..Self::default()
+		}
+	}
+
+	/// Answers streaming requests with `body` split between every byte of the first `ch`, to
+	/// check that partial UTF-8 characters are carried across chunks.
+	pub(crate) fn split_in_char(body: &str, ch: char) -> Self {
+		let start = body.find(ch).expect("`ch` should be in `body`");
+		let mut splits = vec![0];
+		splits.extend(start + 1..start + ch.len_utf8());
+		splits.push(body.len());
+
+		Self::chunked(
+			splits
+				.windows(2)
+				.map(|range| body.as_bytes()[range[0]..range[1]].to_vec()),
+		)
+	}
+
+	/// Answers streaming requests with `chunks`, each sent after its delay, e.g. to simulate a
+	/// provider that stops sending data.
+	pub(crate) fn paused(
+		chunks: impl IntoIterator<Item = (std::time::Duration, impl Into<Bytes>)>,
+	) -> Self {
+		let (chunk_delays, chunks) = chunks
+			.into_iter()
+			.map(|(delay, chunk)| (delay, chunk.into()))
+			.unzip();
+		Self {
+			chunks,
+			chunk_delays,
+			..Self::default()
+		}
+	}
+
+	/// Answers non-streaming requests with `200 OK` and `json`.
+	pub(crate) fn with_json_response(mut self, json: impl Into<Bytes>) -> Self {
+		self.json_bytes = Some(json.into());
+		self
+	}
+
+	/// Answers the next non-streaming requests with `200 OK` and each of `responses` in order,
+	/// then with the response set by [MockSseClient::with_json_response] if any.
+	pub(crate) fn with_json_responses(
+		self,
+		responses: impl IntoIterator<Item = impl Into<Bytes>>,
+	) -> Self {
+		self.queued_json
+			.lock()
+			.unwrap()
+			.extend(responses.into_iter().map(Into::into));
+		self
+	}
+
+	/// The body of the response to the next non-streaming request, if any.
+	fn next_json(&self) -> Option<Bytes> {
+		self.queued_json
+			.lock()
+			.unwrap()
+			.pop_front()
+			.or_else(|| self.json_bytes.clone())
+	}
+
+	/// Answers non-streaming requests with `status` and `json`, e.g. an error response.
+	pub(crate) fn with_status_response(mut self, status: u16, json: impl Into<Bytes>) -> Self {
+		self.status = http::StatusCode::from_u16(status).expect("invalid status code");
+		self.json_bytes = Some(json.into());
+		self
+	}
+
+	/// Adds a header to every response.
+	pub(crate) fn with_response_header(mut self, name: &'static str, value: &'static str) -> Self {
+		self.response_headers
+			.insert(name, http::HeaderValue::from_static(value));
+		self
+	}
+
+	/// The URIs of the requests received so far.
+	pub(crate) fn request_uris(&self) -> Vec<String> {
+		self.request_uris.lock().unwrap().clone()
+	}
+
+	/// The headers of the requests received so far.
+	pub(crate) fn request_headers(&self) -> Vec<http::HeaderMap> {
+		self.request_headers.lock().unwrap().clone()
+	}
+
+	/// The bodies of the requests received so far, as sent, multipart requests excepted.
+	pub(crate) fn raw_request_bodies(&self) -> Vec<Bytes> {
+		self.request_bodies.lock().unwrap().clone()
+	}
+
+	/// The JSON bodies of the requests received so far, multipart requests excepted.
+	pub(crate) fn request_bodies(&self) -> Vec<serde_json::Value> {
+		self.request_bodies
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|body| serde_json::from_slice(body).unwrap_or_default())
+			.collect()
+	}
+
+	fn json_response<U>(&self) -> http_client::Result<http::Response<LazyBody<U>>>
+	where
+		U: From<Bytes>,
+		U: WasmCompatSend + 'static,
+	{
+		let Some(json_bytes) = self.next_json() else {
+			return Err(http_client::Error::InvalidStatusCode(
+				http::StatusCode::NOT_IMPLEMENTED,
+			));
+		};
+
+		let body: LazyBody<U> = Box::pin(async move { Ok(U::from(json_bytes)) });
+		let mut response = http::Response::builder()
+			.status(self.status)
+			.header(http::header::CONTENT_TYPE, "application/json");
+		if let Some(headers) = response.headers_mut() {
+			headers.extend(self.response_headers.clone());
+		}
+		response.body(body).map_err(http_client::Error::Protocol)
+	}
+
+	fn record_body<T: Into<Bytes>>(&self, req: http::Request<T>) {
+		self.record(&req);
+		self.request_bodies
+			.lock()
+			.unwrap()
+			.push(req.into_body().into());
+	}
+
+	fn record<T>(&self, req: &http::Request<T>) {
+		self.request_uris
+			.lock()
+			.unwrap()
+			.push(req.uri().to_string());
+		self.request_headers
+			.lock()
+			.unwrap()
+			.push(req.headers().clone());
+	}
+}
+
+impl HttpClientExt for MockSseClient {
+	fn send<T, U>(
+		&self,
+		req: http::Request<T>,
+	) -> impl Future<Output = http_client::Result<http::Response<LazyBody<U>>>> + WasmCompatSend + 'static
+	where
+		T: Into<Bytes>,
+		T: WasmCompatSend,
+		U: From<Bytes>,
+		U: WasmCompatSend + 'static,
+	{
+		self.record_body(req);
+		std::future::ready(self.json_response())
+	}
+
+	fn send_multipart<U>(
+		&self,
+		req: http::Request<MultipartForm>,
+	) -> impl Future<Output = http_client::Result<http::Response<LazyBody<U>>>> + WasmCompatSend + 'static
+	where
+		U: From<Bytes>,
+		U: WasmCompatSend + 'static,
+	{
+		self.record(&req);
+		std::future::ready(self.json_response())
+	}
+
+	fn send_streaming<T>(
+		&self,
+		req: http::Request<T>,
+	) -> impl Future<Output = http_client::Result<StreamingResponse>> + WasmCompatSend
+	where
+		T: Into<Bytes>,
+	{
+		self.record_body(req);
+		let chunks = self.chunks.clone();
+		let chunk_delays = self.chunk_delays.clone();
+		let response_headers = self.response_headers.clone();
+		async move {
+			let byte_stream =
+				futures::stream::iter(chunks.into_iter().enumerate()).then(move |(i, chunk)| {
+					let delay = chunk_delays.get(i).copied();
+					async move {
+						if let Some(delay) = delay {
+							futures_timer::Delay::new(delay).await;
+						}
+						Ok::<Bytes, http_client::Error>(chunk)
+					}
+				});
+			let boxed_stream: http_client::sse::BoxedStream = Box::pin(byte_stream);
+
+			let mut response = http::Response::builder()
+				.status(http::StatusCode::OK)
+				.header(http::header::CONTENT_TYPE, "text/event-stream");
+			if let Some(headers) = response.headers_mut() {
+				headers.extend(response_headers);
+			}
+			response
+				.body(boxed_stream)
+				.map_err(http_client::Error::Protocol)
+		}
+	}
+}
+
+/// A tracing layer capturing every field recorded on a span after its creation.
+#[derive(Clone, Default)]
+pub(crate) struct RecordedFields(Arc<Mutex<HashMap<String, String>>>);
+
+impl RecordedFields {
+	/// The fields recorded so far, by name.
+	pub(crate) fn get(&self) -> HashMap<String, String> {
+		self.0.lock().unwrap().clone()
+	}
+}
+
+impl Visit for RecordedFields {
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.0
+			.lock()
+			.unwrap()
+			.insert(field.name().to_string(), value.to_string());
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+		self.record_str(field, &format!("{value:?}"));
+	}
+}
+
+impl<S: tracing::Subscriber> Layer<S> for RecordedFields {
+	fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+		values.record(&mut self.clone());
+	}
+}
+
+/// Serializes the tests changing whether messages are recorded on spans.
+static RECORD_MESSAGES_LOCK: Mutex<()> = Mutex::new(());
+
+/// Runs `f` with recording messages on provider spans turned on or off, restoring the previous
+/// setting afterwards. See [`telemetry::set_record_messages`].
+pub(crate) fn with_record_messages<T>(enabled: bool, f: impl FnOnce() -> T) -> T {
+	struct Restore(bool);
+
+	impl Drop for Restore {
+		fn drop(&mut self) {
+			telemetry::set_record_messages(self.0);
+		}
+	}
+
+	let _lock = RECORD_MESSAGES_LOCK
+		.lock()
+		.unwrap_or_else(|e| e.into_inner());
+	let _restore = Restore(telemetry::record_messages_enabled());
+	telemetry::set_record_messages(enabled);
+
+	f()
+}
+
+/// Serializes the tests changing environment variables.
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Runs `f` with the environment variables `vars` set, or removed when `None`, restoring their
+/// previous values afterwards.
+pub(crate) fn with_env<T>(vars: &[(&str, Option<&str>)], f: impl FnOnce() -> T) -> T {
+	struct Restore(Vec<(String, Option<OsString>)>);
+
+	impl Drop for Restore {
+		fn drop(&mut self) {
+			for (name, value) in &self.0 {
+				set_env(name, value.as_ref());
+			}
+		}
+	}
+
+	let _lock = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+	let _restore = Restore(
+		vars.iter()
+			.map(|(name, _)| (name.to_string(), std::env::var_os(name)))
+			.collect(),
+	);
+	for (name, value) in vars {
+		set_env(name, value.map(OsString::from).as_ref());
+	}
+
+	f()
+}
+
+fn set_env(name: &str, value: Option<&OsString>) {
+	// SAFETY: the tests changing the environment hold `ENV_LOCK`, and no other test reads the
+	// variables they change
+	unsafe {
+		match value {
+			Some(value) => std::env::set_var(name, value),
+			None => std::env::remove_var(name),
+		}
+	}
+}
@@ -9,18 +9,22 @@
 //! The [ToolSet] struct is a collection of tools that can be used by an [Agent](crate::agent::Agent)
 //! and optionally RAGged.
 
+pub mod schema;
 pub mod server;
 pub mod think;
 use std::collections::HashMap;
 use std::fmt;
 
 use futures::Future;
+pub use schema::{SchemaTool, definition_for, schema_for};
 use serde::{Deserialize, Serialize};
 pub use think::ThinkTool;
 
+use crate::OneOrMany;
 use crate::completion::{self, ToolDefinition};
 use crate::embeddings::embed::EmbedError;
 use crate::embeddings::tool::ToolSchema;
+use crate::message::ToolResultContent;
 use crate::wasm_compat::{WasmBoxedFuture, WasmCompatSend, WasmCompatSync};
 
 #[derive(Debug, thiserror::Error)]
@@ -60,12 +64,14 @@ impl fmt::Display for ToolError {
 /// ```
 /// use clankers::{
 ///     completion::ToolDefinition,
-///     tool::{ToolSet, Tool},
+///     tool::{SchemaTool, Tool},
 /// };
 ///
-/// #[derive(serde::Deserialize)]
+/// #[derive(serde::Deserialize, clankers::schemars::JsonSchema)]
 /// struct AddArgs {
+///     /// The first number to add
 ///     x: i32,
+///     /// The second number to add
 ///     y: i32,
 /// }
 ///
@@ -78,29 +84,14 @@ impl fmt::Display for ToolError {
 ///
 /// impl Tool for Adder {
 ///     const NAME: &'static str = "add";
+///     const DESCRIPTION: &'static str = "Add x and y together";
 ///
 ///     type Error = MathError;
 ///     type Args = AddArgs;
 ///     type Output = i32;
 ///
 ///     async fn definition(&self, _prompt: String) -> ToolDefinition {
-///         ToolDefinition {
-///             name: "add".to_string(),
-///             description: "Add x and y together".to_string(),
-///             parameters: serde_json::json!({
-///                 "type": "object",
-///                 "properties": {
-///                     "x": {
-///                         "type": "number",
-///                         "description": "The first number to add"
-///                     },
-///                     "y": {
-///                         "type": "number",
-///                         "description": "The second number to add"
-///                     }
-///                 }
-///             })
-///         }
+///         self.schema_definition()
 ///     }
 ///
 ///     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
@@ -112,6 +103,8 @@ impl fmt::Display for ToolError {
 pub trait Tool: Sized + WasmCompatSend + WasmCompatSync {
 	/// The name of the tool. This name should be unique.
 	const NAME: &'static str;
+	/// The description of the tool, used by [SchemaTool::schema_definition].
+	const DESCRIPTION: &'static str = "";
 
 	/// The error type of the tool.
 	type Error: std::error::Error + WasmCompatSend + WasmCompatSync + 'static;
@@ -127,6 +120,9 @@ pub trait Tool: Sized + WasmCompatSend + WasmCompatSync {
 
 	/// A method returning the tool definition. The user prompt can be used to
 	/// tailor the definition to the specific use case.
+	///
+	/// Tools whose [Tool::Args] implement [JsonSchema](schemars::JsonSchema) can return
+	/// [SchemaTool::schema_definition] instead of writing the parameters schema by hand.
 	fn definition(
 		&self,
 		_prompt: String,
@@ -139,6 +135,18 @@ pub trait Tool: Sized + WasmCompatSend + WasmCompatSync {
 		&self,
 		args: Self::Args,
 	) -> impl Future<Output = Result<Self::Output, Self::Error>> + WasmCompatSend;
+
+	/// Converts the output of the tool into the content of the tool result sent to the model.
+	///
+	/// By default, the output is serialized to JSON and sent as text, except for outputs following
+	/// the image and hybrid conventions of [ToolResultContent::from_tool_output], which are kept
+	/// working for backward compatibility. Override this to return images or several parts
+	/// directly, e.g. with [ToolResultContent::image_base64].
+	fn into_tool_result(
+		output: Self::Output,
+	) -> Result<OneOrMany<ToolResultContent>, serde_json::Error> {
+		serde_json::to_string(&output).map(ToolResultContent::from_tool_output)
+	}
 }
 
 /// Trait that represents an LLM tool that can be stored in a vector store and RAGged
@@ -175,6 +183,28 @@ pub trait ToolDyn: WasmCompatSend + WasmCompatSync {
 	fn definition<'a>(&'a self, prompt: String) -> WasmBoxedFuture<'a, ToolDefinition>;
 
 	fn call<'a>(&'a self, args: String) -> WasmBoxedFuture<'a, Result<String, ToolError>>;
+
+	/// Calls the tool, returning the content of the tool result instead of the serialized output.
+	/// Defaults to parsing the output of [ToolDyn::call] with [ToolResultContent::from_tool_output].
+	fn call_content<'a>(
+		&'a self,
+		args: String,
+	) -> WasmBoxedFuture<'a, Result<OneOrMany<ToolResultContent>, ToolError>> {
+		Box::pin(async move {
+			self.call(args)
+				.await
+				.map(ToolResultContent::from_tool_output)
+		})
+	}
+}
+
+/// Deserializes the arguments of a tool call and calls the tool with them.
+async fn call_with_json_args<T: Tool>(tool: &T, args: String) -> Result<T::Output, ToolError> {
+	let args = serde_json::from_str(&args)?;
+
+	tool.call(args)
+		.await
+		.map_err(|e| ToolError::ToolCallError(Box::new(e)))
 }
 
 impl<T: Tool> ToolDyn for T {
@@ -188,15 +218,18 @@ impl<T: Tool> ToolDyn for T {
 
 	fn call<'a>(&'a self, args: String) -> WasmBoxedFuture<'a, Result<String, ToolError>> {
 		Box::pin(async move {
-			match serde_json::from_str(&args) {
-				Ok(args) => <Self as Tool>::call(self, args)
-					.await
-					.map_err(|e| ToolError::ToolCallError(Box::new(e)))
-					.and_then(|output| {
-						serde_json::to_string(&output).map_err(ToolError::JsonError)
-					}),
-				Err(e) => Err(ToolError::JsonError(e)),
-			}
+			let output = call_with_json_args(self, args).await?;
+			Ok(serde_json::to_string(&output)?)
+		})
+	}
+
+	fn call_content<'a>(
+		&'a self,
+		args: String,
+	) -> WasmBoxedFuture<'a, Result<OneOrMany<ToolResultContent>, ToolError>> {
+		Box::pin(async move {
+			let output = call_with_json_args(self, args).await?;
+			Ok(T::into_tool_result(output)?)
 		})
 	}
 }
@@ -247,6 +280,16 @@ impl ToolType {
 			ToolType::Embedding(tool) => tool.call(args).await,
 		}
 	}
+
+	pub async fn call_content(
+		&self,
+		args: String,
+	) -> Result<OneOrMany<ToolResultContent>, ToolError> {
+		match self {
+			ToolType::Simple(tool) => tool.call_content(args).await,
+			ToolType::Embedding(tool) => tool.call_content(args).await,
+		}
+	}
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -347,6 +390,23 @@ impl ToolSet {
 		}
 	}
 
+	/// Call a tool with the given name and arguments, returning the content of the tool result
+	/// as built by [Tool::into_tool_result]
+	pub async fn call_content(
+		&self,
+		toolname: &str,
+		args: String,
+	) -> Result<OneOrMany<ToolResultContent>, ToolSetError> {
+		if let Some(tool) = self.tools.get(toolname) {
+			tracing::debug!(target: "clankers",
+				"Calling tool {toolname} with args:\n{args}",
+			);
+			Ok(tool.call_content(args).await?)
+		} else {
+			Err(ToolSetError::ToolNotFoundError(toolname.to_string()))
+		}
+	}
+
 	/// Get the documents of all the tools in the toolset
 	pub async fn documents(&self) -> Result<Vec<completion::Document>, ToolSetError> {
 		let mut docs = Vec::new();
@@ -524,4 +584,167 @@ mod tests {
 		assert!(!toolset.contains("add"));
 		assert_eq!(toolset.tools.len(), 1);
 	}
+
+	const RED_PIXEL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8DwHwAFBQIAX8jx0gAAAABJRU5ErkJggg==";
+
+	#[derive(Debug, thiserror::Error)]
+	#[error("Lookup error")]
+	struct LookupError;
+
+	#[derive(Serialize)]
+	struct Forecast {
+		city: String,
+		temperature: f32,
+	}
+
+	struct WeatherTool;
+
+	impl Tool for WeatherTool {
+		const NAME: &'static str = "weather";
+		type Error = LookupError;
+		type Args = serde_json::Value;
+		type Output = Forecast;
+
+		async fn definition(&self, _prompt: String) -> ToolDefinition {
+			definition_for::<Self>("Get the weather forecast of a city")
+		}
+
+		async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+			Ok(Forecast {
+				city: args["city"].as_str().ok_or(LookupError)?.to_string(),
+				temperature: 21.5,
+			})
+		}
+	}
+
+	struct ScreenshotTool;
+
+	impl Tool for ScreenshotTool {
+		const NAME: &'static str = "screenshot";
+		type Error = LookupError;
+		type Args = serde_json::Value;
+		type Output = Vec<u8>;
+
+		async fn definition(&self, _prompt: String) -> ToolDefinition {
+			definition_for::<Self>("Take a screenshot")
+		}
+
+		async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+			Ok(vec![0x89, b'P', b'N', b'G'])
+		}
+
+		fn into_tool_result(
+			_output: Self::Output,
+		) -> Result<OneOrMany<ToolResultContent>, serde_json::Error> {
+			Ok(OneOrMany::one(ToolResultContent::image_base64(
+				RED_PIXEL_PNG,
+				Some(crate::message::ImageMediaType::PNG),
+				None,
+			)))
+		}
+	}
+
+	/// A tool following the legacy convention of returning images as JSON objects
+	struct LegacyImageTool;
+
+	impl Tool for LegacyImageTool {
+		const NAME: &'static str = "legacy_image";
+		type Error = LookupError;
+		type Args = serde_json::Value;
+		type Output = serde_json::Value;
+
+		async fn definition(&self, _prompt: String) -> ToolDefinition {
+			definition_for::<Self>("Generate an image")
+		}
+
+		async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+			Ok(json!({ "type": "image", "data": RED_PIXEL_PNG, "mimeType": "image/png" }))
+		}
+	}
+
+	fn tool_result(content: OneOrMany<ToolResultContent>) -> crate::message::ToolResult {
+		crate::message::ToolResult {
+			id: "call_1".to_string(),
+			call_id: None,
+			content,
+		}
+	}
+
+	fn to_gemini(result: crate::message::ToolResult) -> serde_json::Value {
+		let part = crate::providers::gemini::api_types::Part::try_from(
+			crate::message::UserContent::ToolResult(result),
+		)
+		.unwrap();
+		serde_json::to_value(part).unwrap()
+	}
+
+	#[tokio::test]
+	async fn test_struct_output_is_serialized_to_json() {
+		let toolset = ToolSet::from_tools(vec![WeatherTool]);
+
+		let content = toolset
+			.call_content("weather", json!({ "city": "Paris" }).to_string())
+			.await
+			.unwrap();
+		assert_eq!(
+			content,
+			OneOrMany::one(ToolResultContent::text(
+				r#"{"city":"Paris","temperature":21.5}"#
+			))
+		);
+
+		// The string path still returns the same serialized output
+		assert_eq!(
+			toolset
+				.call("weather", json!({ "city": "Paris" }).to_string())
+				.await
+				.unwrap(),
+			r#"{"city":"Paris","temperature":21.5}"#
+		);
+
+		let openai = crate::providers::openai::completion::types::Message::try_from(tool_result(
+			content.clone(),
+		))
+		.unwrap();
+		assert_eq!(
+			serde_json::to_value(openai).unwrap(),
+			json!({
+				"role": "tool",
+				"tool_call_id": "call_1",
+				"content": r#"{"city":"Paris","temperature":21.5}"#,
+			})
+		);
+
+		assert_eq!(
+			to_gemini(tool_result(content))["functionResponse"]["response"],
+			json!({ "result": { "city": "Paris", "temperature": 21.5 } })
+		);
+	}
+
+	#[tokio::test]
+	async fn test_image_output() {
+		let toolset = ToolSet::from_tools_boxed(vec![
+			Box::new(ScreenshotTool) as Box<dyn ToolDyn>,
+			Box::new(LegacyImageTool),
+		]);
+
+		for name in ["screenshot", "legacy_image"] {
+			let content = toolset.call_content(name, "{}".to_string()).await.unwrap();
+
+			let gemini = to_gemini(tool_result(content.clone()));
+			assert_eq!(
+				gemini["functionResponse"]["parts"],
+				json!([{ "inlineData": { "mimeType": "image/png", "data": RED_PIXEL_PNG } }]),
+				"{name}"
+			);
+
+			// OpenAI chat completions only accept text tool results
+			assert!(
+				crate::providers::openai::completion::types::Message::try_from(tool_result(
+					content
+				))
+				.is_err()
+			);
+		}
+	}
 }
@@ -0,0 +1,387 @@
+//! Generation of tool parameter schemas from Rust types.
+//!
+//! Writing [ToolDefinition::parameters] by hand is error-prone. [schema_for] derives the
+//! schema from any type implementing [JsonSchema] and post-processes it into the subset of
+//! JSON schema that providers accept: `$ref`s are inlined and keywords that providers
+//! commonly reject are removed. Tools whose [Tool::Args] implement [JsonSchema] get their
+//! definition from [SchemaTool::schema_definition], or from [definition_for] when they tailor
+//! their description.
+//!
+//! # Example
+//! ```rust
+//! use clankers::{completion::ToolDefinition, tool::{self, Tool}};
+//!
+//! #[derive(serde::Deserialize, clankers::schemars::JsonSchema)]
+//! struct AddArgs {
+//!     /// The first number to add
+//!     x: i32,
+//!     /// The second number to add
+//!     y: i32,
+//! }
+//!
+//! # #[derive(Debug, thiserror::Error)]
+//! # #[error("Math error")]
+//! # struct MathError;
+//! struct Adder;
+//!
+//! impl Tool for Adder {
+//!     const NAME: &'static str = "add";
+//!
+//!     type Error = MathError;
+//!     type Args = AddArgs;
+//!     type Output = i32;
+//!
+//!     async fn definition(&self, prompt: String) -> ToolDefinition {
+//!         tool::definition_for::<Self>(format!("Add x and y together to answer: {prompt}"))
+//!     }
+//!
+//!     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+//!         Ok(args.x + args.y)
+//!     }
+//! }
+//! ```
+
+use schemars::JsonSchema;
+use serde_json::{Map, Value};
+
+use super::Tool;
+use crate::completion::ToolDefinition;
+
+/// Keywords removed from every schema object because providers commonly reject them.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+	"$schema",
+	"$id",
+	"title",
+	"default",
+	"examples",
+	"deprecated",
+	"readOnly",
+	"writeOnly",
+];
+
+/// Generates a provider-friendly JSON schema for `T`.
+pub fn schema_for<T: JsonSchema>() -> Value {
+	let mut schema = serde_json::to_value(schemars::schema_for!(T))
+		.expect("JSON schemas should always serialize");
+
+	let defs = schema.as_object_mut().and_then(|obj| {
+		obj.remove("$defs")
+			.or_else(|| obj.remove("definitions"))
+			.and_then(|defs| match defs {
+				Value::Object(defs) => Some(defs),
+				_ => None,
+			})
+	});
+
+	let mut unresolved = Map::new();
+	if let Some(defs) = &defs {
+		resolve_refs(&mut schema, defs, &mut vec![], &mut unresolved);
+	}
+	strip_unsupported(&mut schema);
+
+	// Recursive types cannot be inlined, so keep the definitions they point to
+	if !unresolved.is_empty()
+		&& let Some(obj) = schema.as_object_mut()
+	{
+		for def in unresolved.values_mut() {
+			strip_unsupported(def);
+		}
+		obj.insert("$defs".to_string(), Value::Object(unresolved));
+	}
+
+	schema
+}
+
+/// Builds a [ToolDefinition] for `T`, generating its parameters schema from `T::Args`.
+pub fn definition_for<T>(description: impl Into<String>) -> ToolDefinition
+where
+	T: Tool,
+	T::Args: JsonSchema,
+{
+	ToolDefinition {
+		name: T::NAME.to_string(),
+		description: description.into(),
+		parameters: schema_for::<T::Args>(),
+	}
+}
+
+/// Builds the definition of tools whose [Tool::Args] implement [JsonSchema] from
+/// [Tool::DESCRIPTION] and the schema of their arguments, see [definition_for].
+pub trait SchemaTool: Tool {
+	fn schema_definition(&self) -> ToolDefinition;
+}
+
+impl<T> SchemaTool for T
+where
+	T: Tool,
+	T::Args: JsonSchema,
+{
+	fn schema_definition(&self) -> ToolDefinition {
+		definition_for::<T>(T::DESCRIPTION)
+	}
+}
+
+fn ref_name(reference: &str) -> Option<&str> {
+	reference
+		.strip_prefix("#/$defs/")
+		.or_else(|| reference.strip_prefix("#/definitions/"))
+}
+
+/// Inlines every `$ref` pointing into `defs`. References that would recurse
+/// into a definition currently being inlined are kept and their definition is
+/// collected into `unresolved`.
+fn resolve_refs(
+	value: &mut Value,
+	defs: &Map<String, Value>,
+	stack: &mut Vec<String>,
+	unresolved: &mut Map<String, Value>,
+) {
+	match value {
+		Value::Object(obj) => {
+			if let Some(name) = obj
+				.get("$ref")
+				.and_then(Value::as_str)
+				.and_then(ref_name)
+				.map(str::to_string)
+				&& let Some(def) = defs.get(&name)
+			{
+				if stack.contains(&name) {
+					obj.insert("$ref".to_string(), Value::String(format!("#/$defs/{name}")));
+					if !unresolved.contains_key(&name) {
+						unresolved.insert(name.clone(), Value::Null);
+						let mut def = def.clone();
+						resolve_refs(&mut def, defs, &mut vec![name.clone()], unresolved);
+						unresolved.insert(name, def);
+					}
+					return;
+				}
+
+				let mut resolved = def.clone();
+				stack.push(name);
+				resolve_refs(&mut resolved, defs, stack, unresolved);
+				stack.pop();
+
+				// Keep sibling keywords such as `description` next to the reference
+				obj.remove("$ref");
+				if let Value::Object(resolved) = resolved {
+					for (key, value) in resolved {
+						obj.entry(key).or_insert(value);
+					}
+				}
+				return;
+			}
+
+			for (_, v) in obj.iter_mut() {
+				resolve_refs(v, defs, stack, unresolved);
+			}
+		}
+		Value::Array(arr) => {
+			for item in arr.iter_mut() {
+				resolve_refs(item, defs, stack, unresolved);
+			}
+		}
+		_ => {}
+	}
+}
+
+/// Removes unsupported keywords from a schema object and recurses into its subschemas.
+fn strip_unsupported(schema: &mut Value) {
+	let Value::Object(obj) = schema else {
+		return;
+	};
+
+	for keyword in UNSUPPORTED_KEYWORDS {
+		obj.remove(*keyword);
+	}
+
+	// Numeric formats such as `int32` or `uint8` are schemars-specific
+	let is_numeric = |t: &Value| matches!(t.as_str(), Some("integer" | "number"));
+	let numeric = match obj.get("type") {
+		Some(Value::Array(types)) => types.iter().any(is_numeric),
+		Some(t) => is_numeric(t),
+		None => false,
+	};
+	if numeric {
+		obj.remove("format");
+	}
+
+	for key in ["properties", "$defs", "definitions"] {
+		if let Some(Value::Object(props)) = obj.get_mut(key) {
+			props.values_mut().for_each(strip_unsupported);
+		}
+	}
+
+	for key in ["items", "additionalProperties", "not"] {
+		if let Some(subschema) = obj.get_mut(key) {
+			strip_unsupported(subschema);
+		}
+	}
+
+	for key in ["anyOf", "oneOf", "allOf", "prefixItems"] {
+		if let Some(Value::Array(variants)) = obj.get_mut(key) {
+			variants.iter_mut().for_each(strip_unsupported);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde::Deserialize;
+	use serde_json::json;
+
+	use super::*;
+
+	#[allow(dead_code)]
+	#[derive(Deserialize, JsonSchema)]
+	#[serde(rename_all = "lowercase")]
+	enum Unit {
+		Celsius,
+		Fahrenheit,
+	}
+
+	#[allow(dead_code)]
+	#[derive(Deserialize, JsonSchema)]
+	#[serde(tag = "kind", rename_all = "snake_case")]
+	enum Location {
+		/// A city name
+		City {
+			name: String,
+		},
+		Coordinates {
+			lat: f64,
+			lon: f64,
+		},
+	}
+
+	#[allow(dead_code)]
+	#[derive(Deserialize, JsonSchema)]
+	struct WeatherArgs {
+		/// Where to get the weather for
+		location: Location,
+		unit: Option<Unit>,
+		days: u8,
+	}
+
+	#[allow(dead_code)]
+	#[derive(Deserialize, JsonSchema)]
+	struct Tree {
+		label: String,
+		children: Vec<Tree>,
+	}
+
+	#[allow(dead_code)]
+	#[derive(Deserialize, JsonSchema)]
+	struct Forest {
+		trees: Vec<Tree>,
+	}
+
+	#[test]
+	fn test_schema_for_nested_enums_and_options() {
+		let schema = schema_for::<WeatherArgs>();
+
+		assert_eq!(
+			schema,
+			json!({
+				"type": "object",
+				"properties": {
+					"location": {
+						"description": "Where to get the weather for",
+						"oneOf": [
+							{
+								"description": "A city name",
+								"type": "object",
+								"properties": {
+									"kind": { "type": "string", "const": "city" },
+									"name": { "type": "string" }
+								},
+								"required": ["kind", "name"]
+							},
+							{
+								"type": "object",
+								"properties": {
+									"kind": { "type": "string", "const": "coordinates" },
+									"lat": { "type": "number" },
+									"lon": { "type": "number" }
+								},
+								"required": ["kind", "lat", "lon"]
+							}
+						]
+					},
+					"unit": {
+						"anyOf": [
+							{ "type": "string", "enum": ["celsius", "fahrenheit"] },
+							{ "type": "null" }
+						]
+					},
+					"days": { "type": "integer", "minimum": 0, "maximum": 255 }
+				},
+				"required": ["location", "days"]
+			})
+		);
+	}
+
+	#[test]
+	fn test_schema_for_recursive_type_keeps_defs() {
+		let schema = schema_for::<Forest>();
+		let tree = &schema["properties"]["trees"]["items"];
+
+		assert_eq!(tree["properties"]["label"], json!({ "type": "string" }));
+		assert_eq!(
+			tree["properties"]["children"]["items"]["$ref"],
+			"#/$defs/Tree"
+		);
+		assert_eq!(
+			schema["$defs"]["Tree"]["properties"]["children"]["items"]["$ref"],
+			"#/$defs/Tree"
+		);
+		assert!(schema.get("title").is_none());
+	}
+
+	#[derive(Debug, thiserror::Error)]
+	#[error("Weather error")]
+	struct WeatherError;
+
+	struct WeatherTool;
+
+	impl Tool for WeatherTool {
+		const NAME: &'static str = "weather";
+		const DESCRIPTION: &'static str = "Get the weather forecast";
+
+		type Error = WeatherError;
+		type Args = WeatherArgs;
+		type Output = String;
+
+		async fn definition(&self, _prompt: String) -> ToolDefinition {
+			self.schema_definition()
+		}
+
+		async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+			Ok("sunny".to_string())
+		}
+	}
+
+	#[tokio::test]
+	async fn test_schema_definition_uses_args_schema() {
+		let definition = WeatherTool.definition(String::new()).await;
+
+		assert_eq!(definition.name, "weather");
+		assert_eq!(definition.description, "Get the weather forecast");
+		assert_eq!(definition.parameters, schema_for::<WeatherArgs>());
+	}
+
+	#[test]
+	fn test_schema_is_accepted_by_openai_sanitizer() {
+		let mut schema = schema_for::<WeatherArgs>();
+		crate::providers::openai::sanitize_schema(&mut schema);
+
+		assert_eq!(schema["additionalProperties"], false);
+		assert_eq!(schema["required"], json!(["days", "location", "unit"]));
+		assert!(schema["properties"]["location"].get("oneOf").is_none());
+		for variant in schema["properties"]["location"]["anyOf"]
+			.as_array()
+			.unwrap()
+		{
+			assert_eq!(variant["additionalProperties"], false);
+		}
+	}
+}
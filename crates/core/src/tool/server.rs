@@ -1,12 +1,15 @@
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 
 use futures::channel::oneshot::Canceled;
-use futures::{StreamExt, TryStreamExt, stream};
+use futures::{FutureExt, StreamExt, TryStreamExt, stream};
 use tokio::sync::RwLock;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::error::SendError;
 
+use crate::OneOrMany;
 use crate::completion::{CompletionError, ToolDefinition};
+use crate::message::ToolResultContent;
 use crate::tool::{Tool, ToolDyn, ToolError, ToolSet, ToolSetError};
 use crate::vector_store::request::Filter;
 use crate::vector_store::{VectorSearchRequest, VectorStoreError, VectorStoreIndexDyn};
@@ -116,6 +119,8 @@ impl ToolServer {
 			data,
 		} = message;
 
+		let content = matches!(data, ToolServerRequestMessageKind::CallToolContent { .. });
+
 		match data {
 			ToolServerRequestMessageKind::AddTool(tool) => {
 				self.static_tool_names.push(tool.name());
@@ -137,37 +142,18 @@ impl ToolServer {
 					.send(ToolServerResponse::ToolDeleted)
 					.unwrap();
 			}
-			ToolServerRequestMessageKind::CallTool { name, args } => {
+			ToolServerRequestMessageKind::CallTool { name, args }
+			| ToolServerRequestMessageKind::CallToolContent { name, args } => {
 				let toolset = Arc::clone(&self.toolset);
 
 				#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
 				tokio::spawn(async move {
-					match toolset.read().await.call(&name, args.clone()).await {
-						Ok(result) => {
-							let _ =
-								callback_channel.send(ToolServerResponse::ToolExecuted { result });
-						}
-						Err(err) => {
-							let _ = callback_channel.send(ToolServerResponse::ToolError {
-								error: err.to_string(),
-							});
-						}
-					}
+					let _ = callback_channel.send(call_tool(toolset, name, args, content).await);
 				});
 
 				#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 				wasm_bindgen_futures::spawn_local(async move {
-					match toolset.read().await.call(&name, args.clone()).await {
-						Ok(result) => {
-							let _ =
-								callback_channel.send(ToolServerResponse::ToolExecuted { result });
-						}
-						Err(err) => {
-							let _ = callback_channel.send(ToolServerResponse::ToolError {
-								error: err.to_string(),
-							});
-						}
-					}
+					let _ = callback_channel.send(call_tool(toolset, name, args, content).await);
 				});
 			}
 			ToolServerRequestMessageKind::GetToolDefs { prompt } => {
@@ -238,6 +224,52 @@ impl ToolServer {
 	}
 }
 
+/// Calls a tool from the toolset. A panicking tool is reported as a tool error so that it does
+/// not take down other tool calls running at the same time.
+/// With `content`, the result is returned as tool result content rather than serialized output.
+async fn call_tool(
+	toolset: Arc<RwLock<ToolSet>>,
+	name: String,
+	args: String,
+	content: bool,
+) -> ToolServerResponse {
+	let call = async {
+		let toolset = toolset.read().await;
+
+		if content {
+			toolset
+				.call_content(&name, args)
+				.await
+				.map(|content| ToolServerResponse::ToolContent { content })
+		} else {
+			toolset
+				.call(&name, args)
+				.await
+				.map(|result| ToolServerResponse::ToolExecuted { result })
+		}
+	};
+
+	match AssertUnwindSafe(call).catch_unwind().await {
+		Ok(Ok(response)) => response,
+		Ok(Err(ToolSetError::ToolNotFoundError(name))) => ToolServerResponse::ToolNotFound { name },
+		Ok(Err(err)) => ToolServerResponse::ToolError {
+			error: err.to_string(),
+		},
+		Err(panic) => {
+			let message = panic
+				.downcast_ref::<&str>()
+				.map(|message| message.to_string())
+				.or_else(|| panic.downcast_ref::<String>().cloned())
+				.unwrap_or_else(|| "unknown panic".to_string());
+			tracing::error!(tool_name = name, "Tool panicked: {message}");
+
+			ToolServerResponse::ToolError {
+				error: format!("Tool `{name}` panicked: {message}"),
+			}
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct ToolServerHandle(Sender<ToolServerRequest>);
 
@@ -320,6 +352,42 @@ impl ToolServerHandle {
 
 		match res {
 			ToolServerResponse::ToolExecuted { result, .. } => Ok(result),
+			ToolServerResponse::ToolNotFound { name } => {
+				Err(ToolSetError::ToolNotFoundError(name).into())
+			}
+			ToolServerResponse::ToolError { error } => Err(ToolServerError::ToolsetError(
+				ToolSetError::ToolCallError(ToolError::ToolCallError(error.into())),
+			)),
+			invalid => Err(ToolServerError::InvalidMessage(invalid)),
+		}
+	}
+
+	/// Calls a tool, returning the content of the tool result as built by
+	/// [Tool::into_tool_result] instead of its serialized output.
+	pub async fn call_tool_content(
+		&self,
+		tool_name: &str,
+		args: &str,
+	) -> Result<OneOrMany<ToolResultContent>, ToolServerError> {
+		let (tx, rx) = futures::channel::oneshot::channel();
+
+		self.0
+			.send(ToolServerRequest {
+				callback_channel: tx,
+				data: ToolServerRequestMessageKind::CallToolContent {
+					name: tool_name.to_string(),
+					args: args.to_string(),
+				},
+			})
+			.await?;
+
+		let res = rx.await?;
+
+		match res {
+			ToolServerResponse::ToolContent { content } => Ok(content),
+			ToolServerResponse::ToolNotFound { name } => {
+				Err(ToolSetError::ToolNotFoundError(name).into())
+			}
 			ToolServerResponse::ToolError { error } => Err(ToolServerError::ToolsetError(
 				ToolSetError::ToolCallError(ToolError::ToolCallError(error.into())),
 			)),
@@ -360,6 +428,7 @@ pub enum ToolServerRequestMessageKind {
 	AppendToolset(ToolSet),
 	RemoveTool { tool_name: String },
 	CallTool { name: String, args: String },
+	CallToolContent { name: String, args: String },
 	GetToolDefs { prompt: Option<String> },
 }
 
@@ -367,8 +436,19 @@ pub enum ToolServerRequestMessageKind {
 pub enum ToolServerResponse {
 	ToolAdded,
 	ToolDeleted,
-	ToolExecuted { result: String },
-	ToolError { error: String },
+	ToolExecuted {
+		result: String,
+	},
+	ToolContent {
+		content: OneOrMany<ToolResultContent>,
+	},
+	ToolError {
+		error: String,
+	},
+	/// The called tool isn't in the toolset
+	ToolNotFound {
+		name: String,
+	},
 	ToolDefinitions(Vec<ToolDefinition>),
 }
 
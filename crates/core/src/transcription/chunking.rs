@@ -0,0 +1,590 @@
+//! Chunked transcription for audio that exceeds a provider's upload limit.
+//!
+//! The audio is split by size into pieces that each fit in a single request. WAV files are
+//! split on frame boundaries and every piece gets its own header, so each chunk is a valid
+//! file with a known time range. Other formats can't be cut without breaking their compressed
+//! frames, so they're only accepted when they fit in a single chunk.
+//!
+//! Chunks are transcribed sequentially. The tail of each transcript is sent as the `prompt`
+//! of the next request so the model keeps its context across the cut, and the timestamps of
+//! each response are offset by the start of its chunk.
+use std::fmt;
+use std::ops::Range;
+use std::time::Duration;
+
+use super::{
+	OffsetTimestamps, TranscriptionError, TranscriptionModel, TranscriptionRequest,
+	TranscriptionResponse,
+};
+
+/// Options controlling how [TranscriptionModel::transcribe_large] splits audio.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkingOptions {
+	/// Maximum size of a single chunk in bytes, including any container header.
+	pub max_chunk_bytes: usize,
+	/// Number of trailing characters of each transcript passed as the prompt of the next chunk.
+	/// `0` disables prompt chaining and sends the original prompt with every chunk.
+	pub prompt_tail_chars: usize,
+	/// When set, 16-bit PCM WAV audio is cut at the quietest point within this window before
+	/// each size boundary rather than exactly at the boundary.
+	#[cfg(feature = "audio")]
+	pub silence_window: Option<Duration>,
+}
+
+impl Default for ChunkingOptions {
+	fn default() -> Self {
+		Self {
+			// Just under the 25MB limit of OpenAI and Groq.
+			max_chunk_bytes: 24 * 1024 * 1024,
+			prompt_tail_chars: 200,
+			#[cfg(feature = "audio")]
+			silence_window: None,
+		}
+	}
+}
+
+impl ChunkingOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the maximum size of a single chunk in bytes
+	pub fn max_chunk_bytes(mut self, max_chunk_bytes: usize) -> Self {
+		self.max_chunk_bytes = max_chunk_bytes;
+		self
+	}
+
+	/// Sets how many trailing characters of each transcript are passed to the next chunk
+	pub fn prompt_tail_chars(mut self, prompt_tail_chars: usize) -> Self {
+		self.prompt_tail_chars = prompt_tail_chars;
+		self
+	}
+
+	/// Cuts WAV audio at the quietest point within `window` before each size boundary
+	#[cfg(feature = "audio")]
+	pub fn split_on_silence(mut self, window: Duration) -> Self {
+		self.silence_window = Some(window);
+		self
+	}
+}
+
+/// The part of the original audio covered by a chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkSpan {
+	/// Byte range of the chunk's audio within the original data
+	pub bytes: Range<usize>,
+	/// Start of the chunk within the original audio, if known
+	pub start: Option<Duration>,
+	/// End of the chunk within the original audio, if the format has a known byte rate
+	pub end: Option<Duration>,
+}
+
+impl fmt::Display for ChunkSpan {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if let (Some(start), Some(end)) = (self.start, self.end) {
+			write!(f, "{:.3}s-{:.3}s, ", start.as_secs_f64(), end.as_secs_f64())?;
+		}
+		write!(f, "bytes {}..{}", self.bytes.start, self.bytes.end)
+	}
+}
+
+/// The transcription of a single chunk.
+///
+/// Timestamps inside `response` are already offset by `span.start`, so they're relative to the
+/// original audio.
+pub struct TranscriptionChunk<T> {
+	pub index: usize,
+	pub span: ChunkSpan,
+	pub text: String,
+	pub response: T,
+}
+
+pub(super) async fn transcribe_chunked<M>(
+	model: &M,
+	request: TranscriptionRequest,
+	options: ChunkingOptions,
+) -> Result<TranscriptionResponse<Vec<TranscriptionChunk<M::Response>>>, TranscriptionError>
+where
+	M: TranscriptionModel,
+	M::Response: OffsetTimestamps,
+{
+	let pieces = split(&request.data, &options)?;
+	let mut prompt = request.prompt.clone();
+	let mut chunks = Vec::with_capacity(pieces.len());
+
+	for (index, piece) in pieces.into_iter().enumerate() {
+		let chunk_request = TranscriptionRequest {
+			data: piece.data,
+			filename: request.filename.clone(),
+			language: request.language.clone(),
+			prompt: prompt.clone(),
+			temperature: request.temperature,
+			additional_params: request.additional_params.clone(),
+		};
+
+		let response = model.transcription(chunk_request).await.map_err(|source| {
+			TranscriptionError::ChunkError {
+				index,
+				span: piece.span.clone(),
+				source: Box::new(source),
+			}
+		})?;
+
+		if let Some(tail) = prompt_tail(&response.text, options.prompt_tail_chars) {
+			prompt = Some(tail);
+		}
+
+		let mut chunk_response = response.response;
+		if let Some(start) = piece.span.start.filter(|start| !start.is_zero()) {
+			chunk_response.offset_timestamps(start);
+		}
+
+		chunks.push(TranscriptionChunk {
+			index,
+			span: piece.span,
+			text: response.text,
+			response: chunk_response,
+		});
+	}
+
+	let text = chunks
+		.iter()
+		.map(|chunk| chunk.text.trim())
+		.filter(|text| !text.is_empty())
+		.collect::<Vec<_>>()
+		.join(" ");
+
+	Ok(TranscriptionResponse {
+		text,
+		response: chunks,
+	})
+}
+
+struct AudioChunk {
+	span: ChunkSpan,
+	data: Vec<u8>,
+}
+
+fn split(data: &[u8], options: &ChunkingOptions) -> Result<Vec<AudioChunk>, TranscriptionError> {
+	if data.is_empty() {
+		return Err(TranscriptionError::RequestError(
+			"Data cannot be empty".into(),
+		));
+	}
+
+	match WavLayout::parse(data) {
+		Some(layout) => layout.split(data, options),
+		None => whole(data, options.max_chunk_bytes),
+	}
+}
+
+/// Sends audio of an unknown format as a single chunk, as cutting it at arbitrary bytes would
+/// break its frames.
+fn whole(data: &[u8], max_chunk_bytes: usize) -> Result<Vec<AudioChunk>, TranscriptionError> {
+	if data.len() > max_chunk_bytes {
+		return Err(TranscriptionError::RequestError(
+			format!(
+				"Only WAV audio can be split, and this {} byte file is larger than the {max_chunk_bytes} byte chunks",
+				data.len()
+			)
+			.into(),
+		));
+	}
+
+	Ok(vec![AudioChunk {
+		span: ChunkSpan {
+			bytes: 0..data.len(),
+			start: Some(Duration::ZERO),
+			end: None,
+		},
+		data: data.to_vec(),
+	}])
+}
+
+/// Location of the sample data in a RIFF/WAVE file.
+struct WavLayout {
+	/// Length of everything before the sample data, including the `data` chunk header
+	header_len: usize,
+	data_len: usize,
+	block_align: usize,
+	byte_rate: usize,
+	#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+	bits_per_sample: u16,
+}
+
+impl WavLayout {
+	fn parse(data: &[u8]) -> Option<Self> {
+		if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+			return None;
+		}
+
+		let read_u16 = |at: usize| Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+		let read_u32 = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+
+		let mut format = None;
+		let mut offset = 12;
+		while offset + 8 <= data.len() {
+			let id = &data[offset..offset + 4];
+			let size = read_u32(offset + 4)? as usize;
+			let body = offset + 8;
+
+			match id {
+				b"fmt " => {
+					// (byte_rate, block_align, bits_per_sample)
+					format = Some((
+						read_u32(body + 8)? as usize,
+						read_u16(body + 12)? as usize,
+						read_u16(body + 14)?,
+					));
+				}
+				b"data" => {
+					let (byte_rate, block_align, bits_per_sample) = format?;
+					if byte_rate == 0 || block_align == 0 {
+						return None;
+					}
+					let available = size.min(data.len() - body);
+					return Some(Self {
+						header_len: body,
+						data_len: available - available % block_align,
+						block_align,
+						byte_rate,
+						bits_per_sample,
+					});
+				}
+				_ => {}
+			}
+
+			// Chunks are padded to an even length.
+			offset = body.checked_add(size)?.checked_add(size % 2)?;
+		}
+
+		None
+	}
+
+	fn split(
+		&self,
+		data: &[u8],
+		options: &ChunkingOptions,
+	) -> Result<Vec<AudioChunk>, TranscriptionError> {
+		let per_chunk = options
+			.max_chunk_bytes
+			.saturating_sub(self.header_len)
+			.div_euclid(self.block_align)
+			* self.block_align;
+		if per_chunk == 0 {
+			return Err(TranscriptionError::RequestError(
+				format!(
+					"max_chunk_bytes must be greater than the {} byte WAV header plus one frame",
+					self.header_len
+				)
+				.into(),
+			));
+		}
+
+		let samples = &data[self.header_len..self.header_len + self.data_len];
+		let mut chunks = Vec::new();
+		let mut start = 0;
+		while start < self.data_len {
+			let end = (start + per_chunk).min(self.data_len);
+			#[cfg(feature = "audio")]
+			let end = match options.silence_window {
+				Some(window) if end < self.data_len => {
+					self.quietest_cut(samples, start, end, window)
+				}
+				_ => end,
+			};
+
+			let piece = &samples[start..end];
+			let mut chunk = Vec::with_capacity(self.header_len + piece.len());
+			chunk.extend_from_slice(&data[..self.header_len]);
+			chunk[4..8]
+				.copy_from_slice(&((self.header_len - 8 + piece.len()) as u32).to_le_bytes());
+			chunk[self.header_len - 4..self.header_len]
+				.copy_from_slice(&(piece.len() as u32).to_le_bytes());
+			chunk.extend_from_slice(piece);
+
+			chunks.push(AudioChunk {
+				span: ChunkSpan {
+					bytes: self.header_len + start..self.header_len + end,
+					start: Some(self.duration(start)),
+					end: Some(self.duration(end)),
+				},
+				data: chunk,
+			});
+			start = end;
+		}
+
+		Ok(chunks)
+	}
+
+	fn duration(&self, bytes: usize) -> Duration {
+		Duration::from_nanos((bytes as u128 * 1_000_000_000 / self.byte_rate as u128) as u64)
+	}
+
+	/// Moves `end` back to the middle of the quietest 10ms frame within `window`.
+	/// Only 16-bit PCM is analysed; other sample formats are cut at `end`.
+	#[cfg(feature = "audio")]
+	fn quietest_cut(&self, samples: &[u8], start: usize, end: usize, window: Duration) -> usize {
+		if self.bits_per_sample != 16 {
+			return end;
+		}
+
+		let align = |bytes: usize| bytes - bytes % self.block_align;
+		let frame = align(self.byte_rate / 100).max(self.block_align);
+		let window = align((window.as_secs_f64() * self.byte_rate as f64) as usize);
+		let mut position = end.saturating_sub(window).max(start + frame);
+
+		let mut best = None;
+		while position + frame <= end {
+			let energy: u64 = samples[position..position + frame]
+				.chunks_exact(2)
+				.map(|sample| i16::from_le_bytes([sample[0], sample[1]]).unsigned_abs() as u64)
+				.sum();
+			if best.is_none_or(|(_, quietest)| energy <= quietest) {
+				best = Some((position, energy));
+			}
+			position += frame;
+		}
+
+		best.map_or(end, |(position, _)| position + align(frame / 2))
+	}
+}
+
+/// Returns the last `max_chars` characters of `text`, without a leading partial word.
+fn prompt_tail(text: &str, max_chars: usize) -> Option<String> {
+	let text = text.trim();
+	if text.is_empty() || max_chars == 0 {
+		return None;
+	}
+
+	let skip = text.chars().count().saturating_sub(max_chars);
+	let Some((at, _)) = text.char_indices().nth(skip).filter(|_| skip > 0) else {
+		return Some(text.to_string());
+	};
+
+	let tail = &text[at..];
+	if text[..at].ends_with(char::is_whitespace) {
+		return Some(tail.to_string());
+	}
+
+	match tail.split_once(char::is_whitespace) {
+		Some((_, rest)) if !rest.trim().is_empty() => Some(rest.trim_start().to_string()),
+		_ => Some(tail.to_string()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::MockTranscriptionModel;
+
+	fn request(data: Vec<u8>, prompt: Option<&str>) -> TranscriptionRequest {
+		TranscriptionRequest {
+			data,
+			filename: "audio.wav".to_string(),
+			language: Some("en".to_string()),
+			prompt: prompt.map(str::to_string),
+			temperature: None,
+			additional_params: None,
+		}
+	}
+
+	/// Builds an 8kHz mono 16-bit PCM WAV file from `samples`.
+	fn wav(samples: &[i16]) -> Vec<u8> {
+		let data_len = (samples.len() * 2) as u32;
+		let mut bytes = Vec::new();
+		bytes.extend_from_slice(b"RIFF");
+		bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+		bytes.extend_from_slice(b"WAVEfmt ");
+		bytes.extend_from_slice(&16u32.to_le_bytes());
+		bytes.extend_from_slice(&1u16.to_le_bytes());
+		bytes.extend_from_slice(&1u16.to_le_bytes());
+		bytes.extend_from_slice(&8000u32.to_le_bytes());
+		bytes.extend_from_slice(&16000u32.to_le_bytes());
+		bytes.extend_from_slice(&2u16.to_le_bytes());
+		bytes.extend_from_slice(&16u16.to_le_bytes());
+		bytes.extend_from_slice(b"data");
+		bytes.extend_from_slice(&data_len.to_le_bytes());
+		for sample in samples {
+			bytes.extend_from_slice(&sample.to_le_bytes());
+		}
+		bytes
+	}
+
+	#[tokio::test]
+	async fn sends_unknown_formats_whole() {
+		let model = MockTranscriptionModel::default();
+		let data: Vec<u8> = (0..10).collect();
+
+		let response = model
+			.transcribe_large(
+				request(data.clone(), None),
+				ChunkingOptions::new().max_chunk_bytes(10),
+			)
+			.await
+			.unwrap();
+
+		let spans: Vec<_> = response.response.iter().map(|c| c.span.clone()).collect();
+		assert_eq!(
+			spans,
+			vec![ChunkSpan {
+				bytes: 0..10,
+				start: Some(Duration::ZERO),
+				end: None,
+			}]
+		);
+		let sent: Vec<_> = model.requests().into_iter().map(|r| r.data).collect();
+		assert_eq!(sent, vec![data]);
+	}
+
+	#[tokio::test]
+	async fn refuses_to_split_unknown_formats() {
+		let model = MockTranscriptionModel::default();
+
+		let err = model
+			.transcribe_large(
+				request((0..10).collect(), None),
+				ChunkingOptions::new().max_chunk_bytes(4),
+			)
+			.await
+			.err()
+			.unwrap();
+
+		assert!(
+			matches!(err, TranscriptionError::RequestError(_)),
+			"{err:?}"
+		);
+		assert!(model.requests().is_empty());
+	}
+
+	#[tokio::test]
+	async fn splits_wav_on_frames_with_a_header_per_chunk() {
+		let model = MockTranscriptionModel::default();
+		// One second of audio, 16000 bytes of samples.
+		let data = wav(&[1; 8000]);
+
+		let response = model
+			.transcribe_large(
+				request(data, None),
+				ChunkingOptions::new().max_chunk_bytes(44 + 8001),
+			)
+			.await
+			.unwrap();
+
+		let spans: Vec<_> = response.response.iter().map(|c| c.span.clone()).collect();
+		assert_eq!(
+			spans,
+			vec![
+				ChunkSpan {
+					bytes: 44..8044,
+					start: Some(Duration::ZERO),
+					end: Some(Duration::from_millis(500)),
+				},
+				ChunkSpan {
+					bytes: 8044..16044,
+					start: Some(Duration::from_millis(500)),
+					end: Some(Duration::from_secs(1)),
+				},
+			]
+		);
+
+		for sent in model.requests() {
+			assert_eq!(sent.data, wav(&[1; 4000]));
+		}
+
+		// Each mock transcript starts at 0s within its chunk.
+		let starts: Vec<_> = response.response.iter().map(|c| c.response.start).collect();
+		assert_eq!(starts, vec![Duration::ZERO, Duration::from_millis(500)]);
+	}
+
+	#[tokio::test]
+	async fn chains_transcript_tails_into_prompts() {
+		let model = MockTranscriptionModel::with_responses([
+			Ok("first part of the talk".to_string()),
+			Ok("   ".to_string()),
+			Ok("and the ending".to_string()),
+		]);
+
+		let response = model
+			.transcribe_large(
+				request(wav(&[0; 9]), Some("Glossary: clankers")),
+				ChunkingOptions::new()
+					.max_chunk_bytes(44 + 6)
+					.prompt_tail_chars(13),
+			)
+			.await
+			.unwrap();
+
+		let prompts: Vec<_> = model.requests().into_iter().map(|r| r.prompt).collect();
+		assert_eq!(
+			prompts,
+			vec![
+				Some("Glossary: clankers".to_string()),
+				Some("of the talk".to_string()),
+				Some("of the talk".to_string()),
+			]
+		);
+		assert_eq!(response.text, "first part of the talk and the ending");
+	}
+
+	#[tokio::test]
+	async fn chunk_errors_identify_the_chunk() {
+		let model = MockTranscriptionModel::with_responses([
+			Ok("fine".to_string()),
+			Err("rate limited".to_string()),
+		]);
+
+		let err = model
+			.transcribe_large(
+				request(wav(&[0; 8000]), None),
+				ChunkingOptions::new().max_chunk_bytes(44 + 4000),
+			)
+			.await
+			.err()
+			.unwrap();
+
+		let TranscriptionError::ChunkError { index, span, .. } = &err else {
+			panic!("expected a chunk error, got {err:?}");
+		};
+		assert_eq!(*index, 1);
+		assert_eq!(span.bytes, 4044..8044);
+		assert_eq!(
+			err.to_string(),
+			"Chunk 1 (0.250s-0.500s, bytes 4044..8044) failed: ProviderError: rate limited"
+		);
+		assert_eq!(model.requests().len(), 2);
+	}
+
+	#[test]
+	fn prompt_tail_drops_partial_words() {
+		assert_eq!(prompt_tail("hello world", 8).as_deref(), Some("world"));
+		assert_eq!(prompt_tail("hello world", 5).as_deref(), Some("world"));
+		assert_eq!(prompt_tail("hello", 3).as_deref(), Some("llo"));
+		assert_eq!(prompt_tail(" hi ", 10).as_deref(), Some("hi"));
+		assert_eq!(prompt_tail("hello", 0), None);
+	}
+
+	#[cfg(feature = "audio")]
+	#[tokio::test]
+	async fn splits_wav_on_silence() {
+		let model = MockTranscriptionModel::default();
+		// Loud audio with 20ms of silence starting at 400ms.
+		let mut samples = vec![1000i16; 8000];
+		samples[3200..3360].fill(0);
+
+		let response = model
+			.transcribe_large(
+				request(wav(&samples), None),
+				ChunkingOptions::new()
+					.max_chunk_bytes(44 + 10000)
+					.split_on_silence(Duration::from_millis(300)),
+			)
+			.await
+			.unwrap();
+
+		assert_eq!(
+			response.response[0].span.end,
+			Some(Duration::from_millis(410))
+		);
+	}
+}
@@ -1,14 +1,20 @@
 //! This module provides functionality for working with audio transcription models.
 //! It provides traits, structs, and enums for generating audio transcription requests,
 //! handling transcription responses, and defining transcription models.
+mod chunking;
+
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 
 use thiserror::Error;
 
+use crate::client::CapturedRequest;
 use crate::wasm_compat::{WasmCompatSend, WasmCompatSync};
 use crate::{http_client, json_utils};
 
+pub use chunking::{ChunkSpan, ChunkingOptions, TranscriptionChunk};
+
 // Errors
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -38,6 +44,14 @@ pub enum TranscriptionError {
 	/// Error returned by the transcription model provider
 	#[error("ProviderError: {0}")]
 	ProviderError(String),
+
+	/// Error transcribing one chunk of a [TranscriptionModel::transcribe_large] request
+	#[error("Chunk {index} ({span}) failed: {source}")]
+	ChunkError {
+		index: usize,
+		span: ChunkSpan,
+		source: Box<TranscriptionError>,
+	},
 }
 
 /// Trait defining a low-level LLM transcription interface
@@ -68,6 +82,13 @@ pub struct TranscriptionResponse<T> {
 	pub response: T,
 }
 
+/// A raw transcription response whose timestamps can be shifted, so that the chunks of
+/// [TranscriptionModel::transcribe_large] are placed in the original audio.
+pub trait OffsetTimestamps {
+	/// Adds `offset` to every timestamp of the response
+	fn offset_timestamps(&mut self, offset: Duration);
+}
+
 /// Trait defining a transcription model that can be used to generate transcription requests.
 /// This trait is meant to be implemented by the user to define a custom transcription model,
 /// either from a third-party provider (e.g: OpenAI) or a local model.
@@ -90,9 +111,38 @@ pub trait TranscriptionModel: Clone + WasmCompatSend + WasmCompatSync {
 	fn transcription_request(&self) -> TranscriptionRequestBuilder<Self> {
 		TranscriptionRequestBuilder::new(self.clone())
 	}
+
+	/// The last request sent by this model or its clones. Only kept when the client was built
+	/// with [capture_last_request](crate::client::ClientBuilder::capture_last_request).
+	fn last_request(&self) -> Option<CapturedRequest> {
+		None
+	}
+
+	/// Transcribes audio larger than the provider accepts in one request.
+	///
+	/// The audio is split according to `options` and each chunk is transcribed in order, with
+	/// the tail of the previous transcript sent as the prompt. The returned text joins every
+	/// chunk's text; the per-chunk responses carry the span each one covers, and their
+	/// timestamps are offset by the start of that span.
+	fn transcribe_large(
+		&self,
+		request: TranscriptionRequest,
+		options: ChunkingOptions,
+	) -> impl std::future::Future<
+		Output = Result<
+			TranscriptionResponse<Vec<TranscriptionChunk<Self::Response>>>,
+			TranscriptionError,
+		>,
+	> + WasmCompatSend
+	where
+		Self::Response: OffsetTimestamps,
+	{
+		chunking::transcribe_chunked(self, request, options)
+	}
 }
 
 /// Struct representing a general transcription request that can be sent to a transcription model provider.
+#[derive(Clone)]
 pub struct TranscriptionRequest {
 	/// The file data to be sent to the transcription model provider
 	pub data: Vec<u8>,
@@ -3,7 +3,7 @@ use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap};
 
 use ordered_float::OrderedFloat;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub use super::builder::InMemoryVectorStoreBuilder;
 use super::lsh::LSHIndex;
@@ -127,9 +127,32 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 		}
 	}
 
+	/// Returns the `n` documents most similar to `query` by cosine similarity, as
+	/// `(score, id, document)` tuples sorted from most to least similar.
+	/// Documents with the same score are ordered by id.
+	///
+	/// Fails with [VectorStoreError::DimensionMismatch] if `query` and the stored embeddings
+	/// don't have the same number of dimensions.
+	pub fn top_n_by_embedding(
+		&self,
+		query: &Embedding,
+		n: usize,
+	) -> Result<Vec<(f64, String, &D)>, VectorStoreError> {
+		Ok(self
+			.vector_search(query, n)?
+			.into_sorted_vec()
+			.into_iter()
+			.map(|Reverse(RankingItem(distance, id, doc, _))| (distance.0, id.clone(), doc))
+			.collect())
+	}
+
 	/// Implement vector search on [InMemoryVectorStore].
 	/// To be used by implementations of [VectorStoreIndex::top_n] and [VectorStoreIndex::top_n_ids] methods.
-	fn vector_search(&self, prompt_embedding: &Embedding, n: usize) -> EmbeddingRanking<'_, D> {
+	fn vector_search(
+		&self,
+		prompt_embedding: &Embedding,
+		n: usize,
+	) -> Result<EmbeddingRanking<'_, D>, VectorStoreError> {
 		match &self.index_strategy {
 			IndexStrategy::BruteForce => self.vector_search_brute_force(prompt_embedding, n),
 			IndexStrategy::LSH {
@@ -144,24 +167,14 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 		&self,
 		prompt_embedding: &Embedding,
 		n: usize,
-	) -> EmbeddingRanking<'_, D> {
+	) -> Result<EmbeddingRanking<'_, D>, VectorStoreError> {
 		// Sort documents by best embedding distance
 		let mut docs = BinaryHeap::new();
 
 		for (id, (doc, embeddings)) in self.embeddings.iter() {
 			// Get the best context for the document given the prompt
-			if let Some((distance, embed_doc)) = embeddings
-				.iter()
-				.map(|embedding| {
-					(
-						OrderedFloat(embedding.cosine_similarity(prompt_embedding, false)),
-						&embedding.document,
-					)
-				})
-				.max_by(|a, b| a.0.cmp(&b.0))
-			{
-				docs.push(Reverse(RankingItem(distance, id, doc, embed_doc)));
-			};
+			let (distance, embed_doc) = best_match(embeddings, prompt_embedding)?;
+			docs.push(Reverse(RankingItem(distance, id, doc, embed_doc)));
 
 			// If the heap size exceeds n, pop the least old element.
 			if docs.len() > n {
@@ -178,7 +191,7 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 				.join(", ")
 		);
 
-		docs
+		Ok(docs)
 	}
 
 	/// LSH-based vector search - uses LSH to find candidates then computes exact distances
@@ -188,7 +201,7 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 		n: usize,
 		_num_tables: usize,
 		_num_hyperplanes: usize,
-	) -> EmbeddingRanking<'_, D> {
+	) -> Result<EmbeddingRanking<'_, D>, VectorStoreError> {
 		// If we don't have an LSH index yet, fall back to brute force
 		if self.lsh_index.is_none() {
 			tracing::warn!("LSH index not initialized, falling back to brute force search");
@@ -207,18 +220,8 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 		for candidate_id in candidates {
 			if let Some((doc, embeddings)) = self.embeddings.get(&candidate_id) {
 				// Get the best context for the document given the prompt
-				if let Some((distance, embed_doc)) = embeddings
-					.iter()
-					.map(|embedding| {
-						(
-							OrderedFloat(embedding.cosine_similarity(prompt_embedding, false)),
-							&embedding.document,
-						)
-					})
-					.max_by(|a, b| a.0.cmp(&b.0))
-				{
-					scored_docs.push((distance, candidate_id, doc, embed_doc));
-				}
+				let (distance, embed_doc) = best_match(embeddings, prompt_embedding)?;
+				scored_docs.push((distance, candidate_id, doc, embed_doc));
 			}
 		}
 
@@ -242,7 +245,7 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 				.join(", ")
 		);
 
-		docs
+		Ok(docs)
 	}
 
 	/// Initialize LSH index from existing embeddings
@@ -283,22 +286,23 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 		&mut self,
 		documents: impl IntoIterator<Item = (D, OneOrMany<Embedding>)>,
 	) {
-		let current_index = self.embeddings.len();
-		documents
-			.into_iter()
-			.enumerate()
-			.for_each(|(index, (doc, embeddings))| {
-				let id = format!("doc{}", index + current_index);
-				self.embeddings
-					.insert(id.clone(), (doc, embeddings.clone()));
-
-				// Update LSH index if it exists
-				if let Some(ref mut lsh_index) = self.lsh_index {
-					for embedding in embeddings.iter() {
-						lsh_index.insert(id.clone(), &embedding.vec);
-					}
+		let mut next_index = self.embeddings.len();
+		documents.into_iter().for_each(|(doc, embeddings)| {
+			// Skip ids that are still taken after documents were removed
+			while self.embeddings.contains_key(&format!("doc{next_index}")) {
+				next_index += 1;
+			}
+			let id = format!("doc{next_index}");
+			self.embeddings
+				.insert(id.clone(), (doc, embeddings.clone()));
+
+			// Update LSH index if it exists
+			if let Some(ref mut lsh_index) = self.lsh_index {
+				for embedding in embeddings.iter() {
+					lsh_index.insert(id.clone(), &embedding.vec);
 				}
-			});
+			}
+		});
 	}
 
 	/// Add documents and their corresponding embeddings to the store with ids.
@@ -341,6 +345,19 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 		}
 	}
 
+	/// Remove a document and its embeddings from the store, returning them if the id existed.
+	pub fn remove_document(&mut self, id: &str) -> Option<(D, OneOrMany<Embedding>)> {
+		let removed = self.embeddings.remove(id);
+
+		if removed.is_some()
+			&& let Some(ref mut lsh_index) = self.lsh_index
+		{
+			lsh_index.remove(id);
+		}
+
+		removed
+	}
+
 	/// Get the document by its id and deserialize it into the given type.
 	pub fn get_document<T: for<'a> Deserialize<'a>>(
 		&self,
@@ -354,13 +371,39 @@ impl<D: Serialize + Eq> InMemoryVectorStore<D> {
 	}
 }
 
+/// Returns the best cosine similarity between `query` and one of a document's embeddings,
+/// along with the text of that embedding.
+fn best_match<'a>(
+	embeddings: &'a OneOrMany<Embedding>,
+	query: &Embedding,
+) -> Result<(OrderedFloat<f64>, &'a String), VectorStoreError> {
+	let mut best: Option<(OrderedFloat<f64>, &String)> = None;
+
+	for embedding in embeddings.iter() {
+		if embedding.vec.len() != query.vec.len() {
+			return Err(VectorStoreError::DimensionMismatch {
+				expected: embedding.vec.len(),
+				actual: query.vec.len(),
+			});
+		}
+
+		let distance = OrderedFloat(embedding.cosine_similarity(query, false));
+		if best.is_none_or(|(best_distance, _)| distance > best_distance) {
+			best = Some((distance, &embedding.document));
+		}
+	}
+
+	Ok(best.expect("OneOrMany always contains at least one embedding"))
+}
+
 /// RankingItem(distance, document_id, serializable document, embeddings document)
 #[derive(Eq, PartialEq)]
 struct RankingItem<'a, D: Serialize>(OrderedFloat<f64>, &'a String, &'a D, &'a String);
 
 impl<D: Serialize + Eq> Ord for RankingItem<'_, D> {
+	/// Orders by distance, then by reverse id so that ties rank the smallest id first.
 	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-		self.0.cmp(&other.0)
+		self.0.cmp(&other.0).then_with(|| other.1.cmp(self.1))
 	}
 }
 
@@ -372,6 +415,52 @@ impl<D: Serialize + Eq> PartialOrd for RankingItem<'_, D> {
 
 type EmbeddingRanking<'a, D> = BinaryHeap<Reverse<RankingItem<'a, D>>>;
 
+impl<D: Serialize + Eq> FromIterator<(D, OneOrMany<Embedding>)> for InMemoryVectorStore<D> {
+	/// Collects the output of [EmbeddingsBuilder::build](crate::embeddings::EmbeddingsBuilder::build)
+	/// into a store, see [InMemoryVectorStore::from_documents].
+	fn from_iter<I: IntoIterator<Item = (D, OneOrMany<Embedding>)>>(documents: I) -> Self {
+		Self::from_documents(documents)
+	}
+}
+
+impl<D: Serialize + Eq> Extend<(D, OneOrMany<Embedding>)> for InMemoryVectorStore<D> {
+	fn extend<I: IntoIterator<Item = (D, OneOrMany<Embedding>)>>(&mut self, documents: I) {
+		self.add_documents(documents);
+	}
+}
+
+/// Serialized form of an [InMemoryVectorStore]. The LSH index is rebuilt when deserializing.
+#[derive(Serialize, Deserialize)]
+struct StoreData<E> {
+	embeddings: E,
+	#[serde(default)]
+	index_strategy: IndexStrategy,
+}
+
+impl<D: Serialize> Serialize for InMemoryVectorStore<D> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		StoreData {
+			embeddings: &self.embeddings,
+			index_strategy: self.index_strategy.clone(),
+		}
+		.serialize(serializer)
+	}
+}
+
+impl<'de, D> Deserialize<'de> for InMemoryVectorStore<D>
+where
+	D: Serialize + Deserialize<'de> + Eq,
+{
+	fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+		let StoreData {
+			embeddings,
+			index_strategy,
+		} = StoreData::<HashMap<String, (D, OneOrMany<Embedding>)>>::deserialize(deserializer)?;
+
+		Ok(Self::from_builder(embeddings, index_strategy))
+	}
+}
+
 impl<D: Serialize> InMemoryVectorStore<D> {
 	pub fn index<M: EmbeddingModel>(self, model: M) -> InMemoryVectorIndex<M, D> {
 		InMemoryVectorIndex::new(model, self)
@@ -426,10 +515,11 @@ impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
 
 		let docs = self
 			.store
-			.vector_search(prompt_embedding, req.samples() as usize);
+			.vector_search(prompt_embedding, req.samples() as usize)?;
 
 		// Return n best
-		docs.into_iter()
+		docs.into_sorted_vec()
+			.into_iter()
 			// The distance should always be between 0 and 1, so distance should be fine to use as an absolute value
 			.map(|Reverse(RankingItem(distance, id, doc, _))| {
 				Ok((
@@ -452,9 +542,10 @@ impl<M: EmbeddingModel + Sync, D: Serialize + Sync + Send + Eq> VectorStoreIndex
 
 		let docs = self
 			.store
-			.vector_search(prompt_embedding, req.samples() as usize);
+			.vector_search(prompt_embedding, req.samples() as usize)?;
 
-		docs.into_iter()
+		docs.into_sorted_vec()
+			.into_iter()
 			.map(|Reverse(RankingItem(distance, id, _, _))| Ok((distance.0, id.clone())))
 			.collect::<Result<Vec<_>, _>>()
 	}
@@ -467,7 +558,146 @@ mod tests {
 	use super::{InMemoryVectorStore, RankingItem};
 	use crate::OneOrMany;
 	use crate::embeddings::embedding::Embedding;
-	use crate::vector_store::IndexStrategy;
+	use crate::vector_store::{IndexStrategy, VectorStoreError};
+
+	fn embedding(document: &str, vec: Vec<f64>) -> OneOrMany<Embedding> {
+		OneOrMany::one(Embedding {
+			document: document.to_string(),
+			vec,
+		})
+	}
+
+	fn query(vec: Vec<f64>) -> Embedding {
+		Embedding {
+			document: "query".to_string(),
+			vec,
+		}
+	}
+
+	fn ranking(store: &InMemoryVectorStore<String>, vec: Vec<f64>, n: usize) -> Vec<String> {
+		store
+			.top_n_by_embedding(&query(vec), n)
+			.unwrap()
+			.into_iter()
+			.map(|(_, id, _)| id)
+			.collect()
+	}
+
+	#[test]
+	fn test_top_n_ranking() {
+		let store: InMemoryVectorStore<String> = vec![
+			("east".to_string(), embedding("east", vec![1.0, 0.0])),
+			("north".to_string(), embedding("north", vec![0.0, 1.0])),
+			(
+				"north-east".to_string(),
+				embedding("north-east", vec![1.0, 1.0]),
+			),
+			("west".to_string(), embedding("west", vec![-1.0, 0.0])),
+		]
+		.into_iter()
+		.collect();
+
+		let results = store.top_n_by_embedding(&query(vec![1.0, 0.2]), 3).unwrap();
+		let documents = results
+			.iter()
+			.map(|(_, _, doc)| doc.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(documents, ["east", "north-east", "north"]);
+		assert!(results.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+
+		assert_eq!(ranking(&store, vec![-1.0, 0.0], 1), ["doc3"]);
+		assert_eq!(ranking(&store, vec![1.0, 0.0], 10).len(), 4);
+	}
+
+	#[test]
+	fn test_top_n_ties_are_ordered_by_id() {
+		let store = InMemoryVectorStore::from_documents_with_ids(vec![
+			("c", "c".to_string(), embedding("c", vec![1.0, 0.0])),
+			("a", "a".to_string(), embedding("a", vec![2.0, 0.0])),
+			("b", "b".to_string(), embedding("b", vec![3.0, 0.0])),
+			("d", "d".to_string(), embedding("d", vec![0.0, 1.0])),
+		]);
+
+		assert_eq!(ranking(&store, vec![1.0, 0.0], 4), ["a", "b", "c", "d"]);
+		assert_eq!(ranking(&store, vec![1.0, 0.0], 2), ["a", "b"]);
+	}
+
+	#[test]
+	fn test_dimension_mismatch() {
+		let store = InMemoryVectorStore::from_documents(vec![(
+			"doc".to_string(),
+			embedding("doc", vec![1.0, 0.0, 0.0]),
+		)]);
+
+		let err = store
+			.top_n_by_embedding(&query(vec![1.0, 0.0]), 1)
+			.unwrap_err();
+		assert!(matches!(
+			err,
+			VectorStoreError::DimensionMismatch {
+				expected: 3,
+				actual: 2
+			}
+		));
+	}
+
+	#[test]
+	fn test_remove_and_reinsert() {
+		let mut store = InMemoryVectorStore::builder()
+			.index_strategy(IndexStrategy::LSH {
+				num_tables: 5,
+				num_hyperplanes: 10,
+			})
+			.documents(vec![
+				("first".to_string(), embedding("first", vec![1.0, 0.0])),
+				("second".to_string(), embedding("second", vec![0.0, 1.0])),
+			])
+			.build();
+
+		let (doc, _) = store.remove_document("doc0").unwrap();
+		assert_eq!(doc, "first");
+		assert!(store.remove_document("doc0").is_none());
+		assert!(!ranking(&store, vec![1.0, 0.0], 2).contains(&"doc0".to_string()));
+		assert_eq!(ranking(&store, vec![0.0, 1.0], 2), ["doc1"]);
+
+		// New documents don't overwrite the ones that are left
+		store.add_documents(vec![(
+			"third".to_string(),
+			embedding("third", vec![1.0, 0.0]),
+		)]);
+		assert_eq!(store.len(), 2);
+		assert_eq!(
+			store.get_document::<String>("doc1").unwrap().as_deref(),
+			Some("second")
+		);
+		assert_eq!(ranking(&store, vec![1.0, 0.0], 1), ["doc2"]);
+	}
+
+	#[test]
+	fn test_serde_roundtrip() {
+		let store = InMemoryVectorStore::builder()
+			.index_strategy(IndexStrategy::LSH {
+				num_tables: 2,
+				num_hyperplanes: 4,
+			})
+			.documents_with_ids(vec![
+				("a", "alpha".to_string(), embedding("alpha", vec![1.0, 0.0])),
+				("b", "beta".to_string(), embedding("beta", vec![0.0, 1.0])),
+			])
+			.build();
+
+		let json = serde_json::to_string(&store).unwrap();
+		let restored: InMemoryVectorStore<String> = serde_json::from_str(&json).unwrap();
+
+		assert_eq!(restored.len(), 2);
+		assert_eq!(restored.index_strategy, store.index_strategy);
+		assert!(restored.lsh_index.is_some());
+		assert_eq!(
+			restored.get_document::<String>("b").unwrap().as_deref(),
+			Some("beta")
+		);
+		assert_eq!(ranking(&restored, vec![0.0, 1.0], 1), ["b"]);
+	}
 
 	#[test]
 	fn test_auto_ids() {
@@ -613,13 +843,15 @@ mod tests {
 			])
 			.build();
 
-		let ranking = vector_store.vector_search(
-			&Embedding {
-				document: "glarby-glarble".to_string(),
-				vec: vec![0.0, 0.1, 0.6],
-			},
-			1,
-		);
+		let ranking = vector_store
+			.vector_search(
+				&Embedding {
+					document: "glarby-glarble".to_string(),
+					vec: vec![0.0, 0.1, 0.6],
+				},
+				1,
+			)
+			.unwrap();
 
 		assert_eq!(
 			ranking
@@ -696,13 +928,15 @@ mod tests {
 			])
 			.build();
 
-		let ranking = vector_store.vector_search(
-			&Embedding {
-				document: "glarby-glarble".to_string(),
-				vec: vec![0.0, 0.1, 0.6],
-			},
-			1,
-		);
+		let ranking = vector_store
+			.vector_search(
+				&Embedding {
+					document: "glarby-glarble".to_string(),
+					vec: vec![0.0, 0.1, 0.6],
+				},
+				1,
+			)
+			.unwrap();
 
 		assert_eq!(
 			ranking
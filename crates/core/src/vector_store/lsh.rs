@@ -104,6 +104,16 @@ impl LSHIndex {
 		}
 	}
 
+	/// Remove a document ID from every table
+	pub fn remove(&mut self, id: &str) {
+		for table in self.tables.iter_mut() {
+			for ids in table.values_mut() {
+				ids.retain(|existing| existing != id);
+			}
+			table.retain(|_, ids| !ids.is_empty());
+		}
+	}
+
 	/// Query for candidate document IDs
 	pub fn query(&self, embedding: &[f64]) -> Vec<String> {
 		use std::collections::HashSet;
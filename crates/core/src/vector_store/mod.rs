@@ -50,6 +50,9 @@ pub enum VectorStoreError {
 	#[error("Missing Id: {0}")]
 	MissingIdError(String),
 
+	#[error("Embedding dimension mismatch: expected {expected}, got {actual}")]
+	DimensionMismatch { expected: usize, actual: usize },
+
 	#[error("HTTP request error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
 
@@ -227,7 +230,7 @@ where
 }
 
 /// Index strategy for the super::InMemoryVectorStore
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum IndexStrategy {
 	/// Checks all documents in the vector store to find the most relevant documents.
 	#[default]
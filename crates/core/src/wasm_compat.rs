@@ -1,4 +1,5 @@
 use std::pin::Pin;
+use std::time::SystemTime;
 
 use bytes::Bytes;
 use futures::Stream;
@@ -57,6 +58,20 @@ pub type WasmBoxedFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 #[cfg(target_family = "wasm")]
 pub type WasmBoxedFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
 
+/// The current system time. [SystemTime::now] panics on `wasm32-unknown-unknown`, where the
+/// time is read from the JavaScript `Date` instead.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn system_time_now() -> SystemTime {
+	SystemTime::now()
+}
+
+/// The current system time. [SystemTime::now] panics on `wasm32-unknown-unknown`, where the
+/// time is read from the JavaScript `Date` instead.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub fn system_time_now() -> SystemTime {
+	SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(js_sys::Date::now() as u64)
+}
+
 #[macro_export]
 macro_rules! if_wasm {
     ($($tokens:tt)*) => {
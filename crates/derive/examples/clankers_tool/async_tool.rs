@@ -30,7 +30,7 @@ async fn main() {
 	tracing_subscriber::fmt().pretty().init();
 
 	let async_agent = providers::openai::Client::from_env()
-		.agent(providers::openai::GPT_4O)
+		.agent(providers::openai::completion::types::GPT_4O)
 		.preamble("You are an agent with tools access, always use the tools")
 		.max_tokens(1024)
 		.tool(AsyncOperation)
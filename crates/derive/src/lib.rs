@@ -311,7 +311,8 @@ pub fn clankers_tool(args: TokenStream, input: TokenStream) -> TokenStream {
 	};
 
 	let expanded = quote! {
-		#[derive(serde::Deserialize)]
+		#[derive(serde::Deserialize, ::clankers::schemars::JsonSchema)]
+		#[schemars(crate = "::clankers::schemars")]
 		pub(crate) struct #params_struct_name {
 			#(#param_names: #param_types,)*
 		}
@@ -143,3 +143,15 @@ async fn test_calculator_tool() {
 
 	assert_eq!(result, serde_json::json!(8));
 }
+
+#[test]
+fn test_parameters_schema() {
+	let schema = clankers::tool::schema_for::<CalculatorParameters>();
+
+	assert_eq!(schema["type"], "object");
+	assert_eq!(
+		schema["required"],
+		serde_json::json!(["x", "y", "operation"])
+	);
+	assert_eq!(schema["properties"]["operation"]["type"], "string");
+}
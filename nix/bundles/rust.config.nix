@@ -1,6 +1,6 @@
 {
   nightlyDate = "2026-01-21";
-  targets = [ ];
+  targets = [ "wasm32-unknown-unknown" ];
   devShell.extraPackages = [ ];
 
   # Base build config - inherited by all packages unless overridden